@@ -0,0 +1,199 @@
+//! An `Interpreter` that tracks the basic type of each value.
+use classfile::{Bytecode, Constant, ConstantPool, ExceptionInfo, FieldType};
+
+use dataflow::{Interpreter, Value};
+use super::{Error, Result};
+
+/// The type of a value, at the granularity of the verification types in a
+/// `StackMapTable`, but without distinguishing between reference types.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BasicValue {
+    /// An unusable value: an unset local, the second slot of a `long` or
+    /// `double`, or a local holding different types on different paths.
+    Top,
+    /// An `int`, or a `boolean`, `byte`, `char` or `short` widened to one.
+    Integer,
+    Float,
+    Long,
+    Double,
+    /// Any reference, including `null`.
+    Reference,
+    /// The address pushed by a `jsr`.
+    ReturnAddress,
+}
+
+impl BasicValue {
+    /// The value of the given type.
+    pub fn of(field_type: &FieldType) -> BasicValue {
+        match *field_type {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short |
+            FieldType::Int => BasicValue::Integer,
+            FieldType::Float => BasicValue::Float,
+            FieldType::Long => BasicValue::Long,
+            FieldType::Double => BasicValue::Double,
+            FieldType::Object(_) | FieldType::Array(_) => BasicValue::Reference,
+        }
+    }
+}
+
+impl Value for BasicValue {
+    fn size(&self) -> usize {
+        match *self {
+            BasicValue::Long | BasicValue::Double => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Computes a `BasicValue` for every value. Merging two different values
+/// gives `Top`.
+///
+/// This interpreter does not check that instructions are applied to values
+/// of the right types.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BasicInterpreter;
+
+impl BasicInterpreter {
+    pub fn new() -> BasicInterpreter {
+        BasicInterpreter
+    }
+}
+
+impl Interpreter<BasicValue> for BasicInterpreter {
+    fn new_value(&mut self, field_type: Option<&FieldType>) -> BasicValue {
+        field_type.map_or(BasicValue::Top, BasicValue::of)
+    }
+
+    fn new_exception_value(&mut self, _: &ExceptionInfo, _: Option<&str>) -> BasicValue {
+        BasicValue::Reference
+    }
+
+    fn new_operation(&mut self, pc: usize, insn: &Bytecode, cp: &ConstantPool) -> Result<BasicValue> {
+        let value = match *insn {
+            Bytecode::aconst_null |
+            Bytecode::new { .. } => BasicValue::Reference,
+            Bytecode::iconst_i(_) |
+            Bytecode::bipush { .. } |
+            Bytecode::sipush { .. } => BasicValue::Integer,
+            Bytecode::lconst_l(_) => BasicValue::Long,
+            Bytecode::fconst_f(_) => BasicValue::Float,
+            Bytecode::dconst_d(_) => BasicValue::Double,
+            Bytecode::ldc { .. } |
+            Bytecode::ldc_w { .. } |
+            Bytecode::ldc2_w { .. } => {
+                match cp.get(insn.constant_index().unwrap()) {
+                    Some(&Constant::Integer(_)) => BasicValue::Integer,
+                    Some(&Constant::Float(_)) => BasicValue::Float,
+                    Some(&Constant::Long(_)) => BasicValue::Long,
+                    Some(&Constant::Double(_)) => BasicValue::Double,
+                    Some(&Constant::String(_)) |
+                    Some(&Constant::Class(_)) |
+                    Some(&Constant::MethodType { .. }) |
+                    Some(&Constant::MethodHandle { .. }) => BasicValue::Reference,
+                    _ => {
+                        return Err(Error::InvalidFrame {
+                            pc: pc,
+                            message: "Illegal ldc constant".to_string(),
+                        })
+                    }
+                }
+            }
+            Bytecode::jsr { .. } |
+            Bytecode::jsr_w { .. } => BasicValue::ReturnAddress,
+            Bytecode::getstatic { .. } => BasicValue::of(&try!(insn.field_type(cp).unwrap())),
+            _ => unreachable!(),
+        };
+        Ok(value)
+    }
+
+    fn copy_operation(&mut self, _: usize, _: &Bytecode, value: &BasicValue) -> Result<BasicValue> {
+        Ok(*value)
+    }
+
+    fn unary_operation(&mut self,
+                       _: usize,
+                       insn: &Bytecode,
+                       cp: &ConstantPool,
+                       _: &BasicValue)
+                       -> Result<Option<BasicValue>> {
+        let value = match *insn {
+            Bytecode::ineg | Bytecode::l2i | Bytecode::f2i | Bytecode::d2i | Bytecode::i2b |
+            Bytecode::i2c | Bytecode::i2s | Bytecode::iinc { .. } | Bytecode::wide_iinc { .. } |
+            Bytecode::arraylength | Bytecode::instanceof { .. } => BasicValue::Integer,
+            Bytecode::fneg | Bytecode::i2f | Bytecode::l2f | Bytecode::d2f => BasicValue::Float,
+            Bytecode::lneg | Bytecode::i2l | Bytecode::f2l | Bytecode::d2l => BasicValue::Long,
+            Bytecode::dneg | Bytecode::i2d | Bytecode::l2d | Bytecode::f2d => BasicValue::Double,
+            Bytecode::newarray { .. } |
+            Bytecode::anewarray { .. } |
+            Bytecode::checkcast { .. } => BasicValue::Reference,
+            Bytecode::getfield { .. } => BasicValue::of(&try!(insn.field_type(cp).unwrap())),
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    fn binary_operation(&mut self,
+                        _: usize,
+                        insn: &Bytecode,
+                        _: &ConstantPool,
+                        _: &BasicValue,
+                        _: &BasicValue)
+                        -> Result<Option<BasicValue>> {
+        let value = match *insn {
+            Bytecode::iaload | Bytecode::baload | Bytecode::caload | Bytecode::saload |
+            Bytecode::iadd | Bytecode::isub | Bytecode::imul | Bytecode::idiv | Bytecode::irem |
+            Bytecode::ishl | Bytecode::ishr | Bytecode::iushr | Bytecode::iand | Bytecode::ior |
+            Bytecode::ixor | Bytecode::lcmp | Bytecode::fcmpl | Bytecode::fcmpg |
+            Bytecode::dcmpl | Bytecode::dcmpg => BasicValue::Integer,
+            Bytecode::faload | Bytecode::fadd | Bytecode::fsub | Bytecode::fmul |
+            Bytecode::fdiv | Bytecode::frem => BasicValue::Float,
+            Bytecode::laload | Bytecode::ladd | Bytecode::lsub | Bytecode::lmul |
+            Bytecode::ldiv | Bytecode::lrem | Bytecode::lshl | Bytecode::lshr |
+            Bytecode::lushr | Bytecode::land | Bytecode::lor | Bytecode::lxor => BasicValue::Long,
+            Bytecode::daload | Bytecode::dadd | Bytecode::dsub | Bytecode::dmul |
+            Bytecode::ddiv | Bytecode::drem => BasicValue::Double,
+            Bytecode::aaload => BasicValue::Reference,
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    fn ternary_operation(&mut self,
+                         _: usize,
+                         _: &Bytecode,
+                         _: &BasicValue,
+                         _: &BasicValue,
+                         _: &BasicValue)
+                         -> Result<()> {
+        Ok(())
+    }
+
+    fn nary_operation(&mut self,
+                      _: usize,
+                      insn: &Bytecode,
+                      cp: &ConstantPool,
+                      _: &[BasicValue])
+                      -> Result<Option<BasicValue>> {
+        match insn.method_descriptor(cp) {
+            Some(descriptor) => Ok(try!(descriptor).ret.as_ref().map(BasicValue::of)),
+            None => Ok(Some(BasicValue::Reference)),
+        }
+    }
+
+    fn return_operation(&mut self,
+                        _: usize,
+                        _: &Bytecode,
+                        _: &BasicValue,
+                        _: &BasicValue)
+                        -> Result<()> {
+        Ok(())
+    }
+
+    fn merge(&mut self, value1: &BasicValue, value2: &BasicValue) -> BasicValue {
+        if value1 == value2 {
+            *value1
+        } else {
+            BasicValue::Top
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Control flow graphs of method bodies.
+//!
+//! A `ControlFlowGraph` splits the code of a `CodeAttribute` into basic
+//! blocks. A new block starts at the first instruction, at every branch and
+//! switch target, after every instruction that ends a block, at every
+//! exception handler, and at both ends of every range protected by a handler.
+//! Splitting at protected ranges means each block is either entirely inside
+//! or entirely outside the range of a given handler.
+use std::collections::BTreeSet;
+use std::io;
+use std::ops::Range;
+
+use classfile::{Bytecode, CodeAttribute, InstructionList};
+
+use super::{Error, Result};
+
+/// The way control passes along an edge.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EdgeKind {
+    /// Falling through to the next block, or an unconditional `goto`. The
+    /// not-taken side of a conditional branch is also `Normal`.
+    Normal,
+    /// The taken side of a conditional branch.
+    Conditional,
+    /// A switch case with the given key, or the default case for `None`.
+    Switch(Option<i32>),
+    /// A jump to an exception handler catching the class at the given
+    /// constant pool index, or any exception if it is zero.
+    Exceptional { catch_type: u16 },
+    /// A `jsr` or `jsr_w` to the start of a subroutine.
+    Jsr,
+    /// A `ret` back to the instruction following a `jsr` to the subroutine
+    /// containing it.
+    Ret,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A maximal sequence of instructions that is only entered at its first
+/// instruction and only left after its last.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BasicBlock {
+    pub id: usize,
+    pub start_pc: usize,
+    /// The pc just past the last instruction of the block.
+    pub end_pc: usize,
+    /// The indices of the block's instructions in the `InstructionList`.
+    pub instructions: Range<usize>,
+}
+
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    instructions: InstructionList,
+    blocks: Vec<BasicBlock>,
+    edges: Vec<Edge>,
+    successors: Vec<Vec<usize>>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl ControlFlowGraph {
+    /// Builds the control flow graph of `code`.
+    ///
+    /// Fails if the code cannot be decoded, a branch targets a pc that does
+    /// not start an instruction, or an exception table entry is malformed.
+    /// Falling off the end of the code is not reported here; the last block
+    /// simply has no fall-through edge.
+    pub fn new(code: &CodeAttribute) -> Result<ControlFlowGraph> {
+        let instructions = try!(code.instruction_list());
+        let code_length = instructions.code_length();
+
+        let mut leaders = BTreeSet::new();
+        if !instructions.is_empty() {
+            leaders.insert(0);
+        }
+        for (index, &(pc, ref bytecode)) in instructions.iter().enumerate() {
+            for target in bytecode.branch_targets(pc) {
+                if !instructions.is_boundary(target) {
+                    return Err(Error::InvalidBranchTarget {
+                        pc: pc,
+                        target: target,
+                    });
+                }
+                leaders.insert(target);
+            }
+            if bytecode.is_terminator() {
+                if let Some(next) = instructions.pc(index + 1) {
+                    leaders.insert(next);
+                }
+            }
+        }
+        for (index, handler) in code.exception_table.iter().enumerate() {
+            let start = handler.start_pc as usize;
+            let end = handler.end_pc as usize;
+            let handler_pc = handler.handler_pc as usize;
+            if start >= end || !instructions.is_boundary(start) ||
+               !(end == code_length || instructions.is_boundary(end)) ||
+               !instructions.is_boundary(handler_pc) {
+                return Err(Error::InvalidExceptionHandler { index: index });
+            }
+            leaders.insert(start);
+            leaders.insert(handler_pc);
+            if end < code_length {
+                leaders.insert(end);
+            }
+        }
+
+        let starts: Vec<usize> = leaders.into_iter().collect();
+        let mut blocks = vec![];
+        for (id, &start_pc) in starts.iter().enumerate() {
+            let end_pc = starts.get(id + 1).cloned().unwrap_or(code_length);
+            let first = instructions.index_of(start_pc).unwrap();
+            let last = instructions.containing(end_pc - 1).unwrap();
+            blocks.push(BasicBlock {
+                id: id,
+                start_pc: start_pc,
+                end_pc: end_pc,
+                instructions: first..last + 1,
+            });
+        }
+
+        let mut cfg = ControlFlowGraph {
+            instructions: instructions,
+            edges: vec![],
+            successors: vec![vec![]; blocks.len()],
+            predecessors: vec![vec![]; blocks.len()],
+            blocks: blocks,
+        };
+        let edges = cfg.find_edges(code);
+        for edge in edges {
+            cfg.add_edge(edge);
+        }
+        let ret_edges = cfg.find_ret_edges();
+        for edge in ret_edges {
+            cfg.add_edge(edge);
+        }
+        Ok(cfg)
+    }
+
+    /// Finds every edge other than those for `ret`.
+    fn find_edges(&self, code: &CodeAttribute) -> Vec<Edge> {
+        let mut edges = vec![];
+        for block in self.blocks.iter() {
+            let (pc, bytecode) = self.last_instruction(block.id);
+            let targets = bytecode.branch_targets(pc);
+            let mut edge = |to: usize, kind: EdgeKind| {
+                edges.push(Edge {
+                    from: block.id,
+                    to: to,
+                    kind: kind,
+                })
+            };
+            match *bytecode {
+                Bytecode::tableswitch { low, .. } => {
+                    edge(self.block_at(targets[0]).unwrap(), EdgeKind::Switch(None));
+                    for (i, &target) in targets[1..].iter().enumerate() {
+                        let key = low.wrapping_add(i as i32);
+                        edge(self.block_at(target).unwrap(), EdgeKind::Switch(Some(key)));
+                    }
+                }
+                Bytecode::lookupswitch { ref pairs, .. } => {
+                    edge(self.block_at(targets[0]).unwrap(), EdgeKind::Switch(None));
+                    for (&(key, _), &target) in pairs.iter().zip(targets[1..].iter()) {
+                        edge(self.block_at(target).unwrap(), EdgeKind::Switch(Some(key)));
+                    }
+                }
+                Bytecode::jsr { .. } |
+                Bytecode::jsr_w { .. } => edge(self.block_at(targets[0]).unwrap(), EdgeKind::Jsr),
+                Bytecode::goto { .. } |
+                Bytecode::goto_w { .. } => edge(self.block_at(targets[0]).unwrap(), EdgeKind::Normal),
+                _ => {
+                    if let Some(&target) = targets.first() {
+                        edge(self.block_at(target).unwrap(), EdgeKind::Conditional);
+                    }
+                    if bytecode.falls_through() && block.id + 1 < self.blocks.len() {
+                        edge(block.id + 1, EdgeKind::Normal);
+                    }
+                }
+            }
+            for handler in code.exception_table.iter() {
+                let range = handler.start_pc as usize..handler.end_pc as usize;
+                if range.start <= block.start_pc && block.start_pc < range.end {
+                    let kind = EdgeKind::Exceptional { catch_type: handler.catch_type };
+                    edge(self.block_at(handler.handler_pc as usize).unwrap(), kind);
+                }
+            }
+        }
+        edges
+    }
+
+    /// Connects each `ret` to the return sites of the subroutines it belongs
+    /// to.
+    ///
+    /// A subroutine is made up of the blocks reachable from its entry without
+    /// following exceptional edges and without entering nested subroutines;
+    /// a nested `jsr` continues at its own return site instead.
+    fn find_ret_edges(&self) -> Vec<Edge> {
+        let mut edges = vec![];
+        for jsr in self.edges.iter().filter(|edge| edge.kind == EdgeKind::Jsr) {
+            let return_site = jsr.from + 1;
+            if return_site >= self.blocks.len() {
+                continue;
+            }
+            let mut visited = vec![false; self.blocks.len()];
+            let mut pending = vec![jsr.to];
+            while let Some(id) = pending.pop() {
+                if visited[id] {
+                    continue;
+                }
+                visited[id] = true;
+                match *self.last_instruction(id).1 {
+                    Bytecode::ret { .. } |
+                    Bytecode::wide_ret { .. } => {
+                        let edge = Edge {
+                            from: id,
+                            to: return_site,
+                            kind: EdgeKind::Ret,
+                        };
+                        if !edges.contains(&edge) {
+                            edges.push(edge);
+                        }
+                    }
+                    Bytecode::jsr { .. } |
+                    Bytecode::jsr_w { .. } => {
+                        if id + 1 < self.blocks.len() {
+                            pending.push(id + 1);
+                        }
+                    }
+                    _ => {
+                        for edge in self.successors(id) {
+                            if let EdgeKind::Exceptional { .. } = edge.kind {
+                                continue;
+                            }
+                            pending.push(edge.to);
+                        }
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    fn add_edge(&mut self, edge: Edge) {
+        let index = self.edges.len();
+        self.successors[edge.from].push(index);
+        self.predecessors[edge.to].push(index);
+        self.edges.push(edge);
+    }
+
+    fn last_instruction(&self, id: usize) -> (usize, &Bytecode) {
+        let index = self.blocks[id].instructions.end - 1;
+        (self.instructions.pc(index).unwrap(), self.instructions.get(index).unwrap())
+    }
+
+    /// The decoded instructions of the method.
+    pub fn instructions(&self) -> &InstructionList {
+        &self.instructions
+    }
+
+    /// The blocks in order of their start pc. The entry block, if the code is
+    /// not empty, is block 0.
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: usize) -> &BasicBlock {
+        &self.blocks[id]
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// The edges leaving block `id`.
+    pub fn successors(&self, id: usize) -> Vec<&Edge> {
+        self.edge_list(&self.successors, id)
+    }
+
+    /// The edges entering block `id`.
+    pub fn predecessors(&self, id: usize) -> Vec<&Edge> {
+        self.edge_list(&self.predecessors, id)
+    }
+
+    fn edge_list(&self, lists: &[Vec<usize>], id: usize) -> Vec<&Edge> {
+        lists[id].iter().map(|&index| &self.edges[index]).collect()
+    }
+
+    /// The block containing the instruction at `pc`.
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        if pc >= self.instructions.code_length() {
+            return None;
+        }
+        match self.blocks.binary_search_by(|block| block.start_pc.cmp(&pc)) {
+            Ok(id) => Some(id),
+            Err(id) => Some(id - 1),
+        }
+    }
+
+    /// Returns, for each block, whether it can be reached from the entry
+    /// block along any kind of edge.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending = if self.blocks.is_empty() { vec![] } else { vec![0] };
+        while let Some(id) = pending.pop() {
+            if reachable[id] {
+                continue;
+            }
+            reachable[id] = true;
+            pending.extend(self.successors(id).iter().map(|edge| edge.to));
+        }
+        reachable
+    }
+
+    /// The blocks that can never execute.
+    pub fn unreachable_blocks(&self) -> Vec<usize> {
+        self.reachable()
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, reachable)| !reachable)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Writes the graph in Graphviz DOT format, labelling each block with its
+    /// instructions. Exceptional edges are dashed and `jsr`/`ret` edges
+    /// dotted.
+    pub fn write_dot(&self, out: &mut io::Write, name: &str) -> io::Result<()> {
+        try!(write!(out, "digraph \"{}\" {{\n", escape(name)));
+        try!(write!(out, "    node [shape=box, fontname=\"monospace\"];\n"));
+        for block in self.blocks.iter() {
+            let mut label = String::new();
+            for index in block.instructions.clone() {
+                let pc = self.instructions.pc(index).unwrap();
+                let bytecode = self.instructions.get(index).unwrap();
+                label.push_str(&format!("{}: {}\\l", pc, bytecode.info().mnemonic()));
+            }
+            try!(write!(out, "    b{} [label=\"{}\"];\n", block.id, label));
+        }
+        for edge in self.edges.iter() {
+            let attributes = match edge.kind {
+                EdgeKind::Normal => String::new(),
+                EdgeKind::Conditional => " [label=\"T\"]".to_string(),
+                EdgeKind::Switch(Some(key)) => format!(" [label=\"{}\"]", key),
+                EdgeKind::Switch(None) => " [label=\"default\"]".to_string(),
+                EdgeKind::Exceptional { catch_type: 0 } => {
+                    " [style=dashed, label=\"any\"]".to_string()
+                }
+                EdgeKind::Exceptional { catch_type } => {
+                    format!(" [style=dashed, label=\"#{}\"]", catch_type)
+                }
+                EdgeKind::Jsr => " [style=dotted, label=\"jsr\"]".to_string(),
+                EdgeKind::Ret => " [style=dotted, label=\"ret\"]".to_string(),
+            };
+            try!(write!(out, "    b{} -> b{}{};\n", edge.from, edge.to, attributes));
+        }
+        write!(out, "}}\n")
+    }
+}
+
+fn escape(name: &str) -> String {
+    name.replace("\\", "\\\\").replace("\"", "\\\"")
+}
@@ -0,0 +1,816 @@
+//! A generic dataflow analysis that simulates the operand stack and local
+//! variables of a method.
+//!
+//! An `Analyzer` runs the instructions of a method to a fixpoint, following
+//! branches, subroutine calls and returns, and exception handler edges. What a
+//! value is, and how instructions produce values, is left to an
+//! `Interpreter`. The result is a `Frame` for every reachable instruction,
+//! describing the locals and the operand stack just before it executes.
+//!
+//! Values from different paths are merged where control flow joins, so the
+//! locals of a subroutine's callers are merged at each return site rather
+//! than tracked per caller.
+use std::fmt;
+
+use classfile::{Bytecode, ClassFile, ConstantPool, ExceptionInfo, FieldType, InstructionList,
+                MethodInfo};
+
+use cfg::{ControlFlowGraph, EdgeKind};
+use super::{Error, Result};
+
+/// An abstract value held in a local variable or on the operand stack.
+pub trait Value: Clone + Eq + fmt::Debug {
+    /// The number of slots the value occupies: 2 for `long` and `double`
+    /// values, and 1 for all others.
+    fn size(&self) -> usize;
+}
+
+/// Produces the values of instructions for an `Analyzer`.
+///
+/// Instructions are grouped by how many values they take from the stack.
+/// Methods that produce a value return `None` for instructions that push
+/// nothing; the value is ignored if the instruction does not push one.
+pub trait Interpreter<V: Value> {
+    /// A value of the given type, or of an unused local for `None`.
+    fn new_value(&mut self, field_type: Option<&FieldType>) -> V;
+
+    /// The value of a method parameter, or of `this`, on entry.
+    fn new_parameter_value(&mut self, _local: usize, field_type: &FieldType) -> V {
+        self.new_value(Some(field_type))
+    }
+
+    /// The value a method with the given return type must return.
+    fn new_return_type_value(&mut self, field_type: &FieldType) -> V {
+        self.new_value(Some(field_type))
+    }
+
+    /// The value of a local that holds nothing usable, such as the second
+    /// slot of a `long`.
+    fn new_empty_value(&mut self, _local: usize) -> V {
+        self.new_value(None)
+    }
+
+    /// The exception pushed on entry to `handler`, whose catch type is
+    /// `catch_type`, or any exception for `None`.
+    fn new_exception_value(&mut self, handler: &ExceptionInfo, catch_type: Option<&str>) -> V;
+
+    /// An instruction that pushes a value without popping any: constants,
+    /// `jsr`, `getstatic` and `new`.
+    fn new_operation(&mut self, pc: usize, insn: &Bytecode, cp: &ConstantPool) -> Result<V>;
+
+    /// An instruction that moves a value between a local and the stack, or
+    /// duplicates or swaps stack values.
+    fn copy_operation(&mut self, pc: usize, insn: &Bytecode, value: &V) -> Result<V>;
+
+    /// An instruction that pops one value.
+    fn unary_operation(&mut self,
+                       pc: usize,
+                       insn: &Bytecode,
+                       cp: &ConstantPool,
+                       value: &V)
+                       -> Result<Option<V>>;
+
+    /// An instruction that pops two values, given in the order they were
+    /// pushed.
+    fn binary_operation(&mut self,
+                        pc: usize,
+                        insn: &Bytecode,
+                        cp: &ConstantPool,
+                        value1: &V,
+                        value2: &V)
+                        -> Result<Option<V>>;
+
+    /// An array store, which pops an array, an index and a value.
+    fn ternary_operation(&mut self,
+                         pc: usize,
+                         insn: &Bytecode,
+                         value1: &V,
+                         value2: &V,
+                         value3: &V)
+                         -> Result<()>;
+
+    /// An invocation or `multianewarray`, which pops a variable number of
+    /// values, given in the order they were pushed.
+    fn nary_operation(&mut self,
+                      pc: usize,
+                      insn: &Bytecode,
+                      cp: &ConstantPool,
+                      values: &[V])
+                      -> Result<Option<V>>;
+
+    /// A return of `value` from a method that must return `expected`.
+    fn return_operation(&mut self, pc: usize, insn: &Bytecode, value: &V, expected: &V) -> Result<()>;
+
+    /// Merges two values reaching the same local or stack entry along
+    /// different paths.
+    fn merge(&mut self, value1: &V, value2: &V) -> V;
+}
+
+/// The locals and operand stack at an instruction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame<V> {
+    locals: Vec<V>,
+    stack: Vec<V>,
+    max_stack: usize,
+}
+
+impl<V: Value> Frame<V> {
+    /// A frame with the given locals, an empty stack, and room for
+    /// `max_stack` slots of stack values.
+    pub fn new(locals: Vec<V>, max_stack: usize) -> Frame<V> {
+        Frame {
+            locals: locals,
+            stack: vec![],
+            max_stack: max_stack,
+        }
+    }
+
+    /// The locals, one per slot. The slot after a `long` or `double` holds an
+    /// empty value.
+    pub fn locals(&self) -> &[V] {
+        &self.locals
+    }
+
+    pub fn local(&self, index: usize) -> Option<&V> {
+        self.locals.get(index)
+    }
+
+    /// The values on the operand stack, from the bottom up. A `long` or
+    /// `double` is a single value.
+    pub fn stack(&self) -> &[V] {
+        &self.stack
+    }
+
+    /// The value `depth` entries below the top of the stack.
+    pub fn peek(&self, depth: usize) -> Option<&V> {
+        if depth < self.stack.len() {
+            self.stack.get(self.stack.len() - 1 - depth)
+        } else {
+            None
+        }
+    }
+
+    /// The number of slots taken by the values on the stack.
+    pub fn stack_slots(&self) -> usize {
+        self.stack.iter().map(|value| value.size()).sum()
+    }
+
+    fn push(&mut self, pc: usize, value: V) -> Result<()> {
+        if self.stack_slots() + value.size() > self.max_stack {
+            return invalid(pc, "Insufficient maximum stack size");
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self, pc: usize) -> Result<V> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => invalid(pc, "Cannot pop operand off an empty stack"),
+        }
+    }
+
+    /// Pops a value that must take a single slot.
+    fn pop1(&mut self, pc: usize, insn: &Bytecode) -> Result<V> {
+        let value = try!(self.pop(pc));
+        if value.size() != 1 {
+            return invalid(pc, &format!("Illegal use of {}", insn.info().mnemonic()));
+        }
+        Ok(value)
+    }
+
+    fn load(&mut self,
+            pc: usize,
+            insn: &Bytecode,
+            index: usize,
+            interpreter: &mut Interpreter<V>)
+            -> Result<()> {
+        let value = match self.locals.get(index) {
+            Some(value) => value.clone(),
+            None => return invalid(pc, &format!("Illegal local variable number {}", index)),
+        };
+        let value = try!(interpreter.copy_operation(pc, insn, &value));
+        self.push(pc, value)
+    }
+
+    fn store(&mut self,
+             pc: usize,
+             insn: &Bytecode,
+             index: usize,
+             interpreter: &mut Interpreter<V>)
+             -> Result<()> {
+        let value = try!(self.pop(pc));
+        let value = try!(interpreter.copy_operation(pc, insn, &value));
+        self.set_local(pc, index, value, interpreter)
+    }
+
+    fn set_local(&mut self,
+                 pc: usize,
+                 index: usize,
+                 value: V,
+                 interpreter: &mut Interpreter<V>)
+                 -> Result<()> {
+        let size = value.size();
+        if index + size > self.locals.len() {
+            return invalid(pc, &format!("Illegal local variable number {}", index));
+        }
+        self.locals[index] = value;
+        if size == 2 {
+            self.locals[index + 1] = interpreter.new_empty_value(index + 1);
+        }
+        // Overwriting the second slot of a long or double invalidates it.
+        if index > 0 && self.locals[index - 1].size() == 2 {
+            self.locals[index - 1] = interpreter.new_empty_value(index - 1);
+        }
+        Ok(())
+    }
+
+    fn check_local(&self, pc: usize, index: usize) -> Result<()> {
+        if index < self.locals.len() {
+            Ok(())
+        } else {
+            invalid(pc, &format!("Illegal local variable number {}", index))
+        }
+    }
+
+    fn push_result(&mut self, pc: usize, insn: &Bytecode, value: Option<V>) -> Result<()> {
+        match value {
+            Some(value) => self.push(pc, value),
+            None => invalid(pc, &format!("No value produced by {}", insn.info().mnemonic())),
+        }
+    }
+
+    fn unary(&mut self,
+             pc: usize,
+             insn: &Bytecode,
+             cp: &ConstantPool,
+             interpreter: &mut Interpreter<V>,
+             pushes: bool)
+             -> Result<()> {
+        let value = try!(self.pop(pc));
+        let result = try!(interpreter.unary_operation(pc, insn, cp, &value));
+        if pushes {
+            try!(self.push_result(pc, insn, result));
+        }
+        Ok(())
+    }
+
+    fn binary(&mut self,
+              pc: usize,
+              insn: &Bytecode,
+              cp: &ConstantPool,
+              interpreter: &mut Interpreter<V>,
+              pushes: bool)
+              -> Result<()> {
+        let value2 = try!(self.pop(pc));
+        let value1 = try!(self.pop(pc));
+        let result = try!(interpreter.binary_operation(pc, insn, cp, &value1, &value2));
+        if pushes {
+            try!(self.push_result(pc, insn, result));
+        }
+        Ok(())
+    }
+
+    fn nary(&mut self,
+            pc: usize,
+            insn: &Bytecode,
+            cp: &ConstantPool,
+            interpreter: &mut Interpreter<V>,
+            count: usize,
+            pushes: bool)
+            -> Result<()> {
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(try!(self.pop(pc)));
+        }
+        values.reverse();
+        let result = try!(interpreter.nary_operation(pc, insn, cp, &values));
+        if pushes {
+            try!(self.push_result(pc, insn, result));
+        }
+        Ok(())
+    }
+
+    /// Simulates `insn`, the instruction at `pc`, on this frame. `ret` is the
+    /// value the method must return, or `None` for a `void` method.
+    pub fn execute(&mut self,
+                   pc: usize,
+                   insn: &Bytecode,
+                   cp: &ConstantPool,
+                   interpreter: &mut Interpreter<V>,
+                   ret: Option<&V>)
+                   -> Result<()> {
+        match *insn {
+            Bytecode::nop |
+            Bytecode::goto { .. } |
+            Bytecode::goto_w { .. } => Ok(()),
+
+            Bytecode::aconst_null |
+            Bytecode::iconst_i(_) |
+            Bytecode::lconst_l(_) |
+            Bytecode::fconst_f(_) |
+            Bytecode::dconst_d(_) |
+            Bytecode::bipush { .. } |
+            Bytecode::sipush { .. } |
+            Bytecode::ldc { .. } |
+            Bytecode::ldc_w { .. } |
+            Bytecode::ldc2_w { .. } |
+            Bytecode::jsr { .. } |
+            Bytecode::jsr_w { .. } |
+            Bytecode::getstatic { .. } |
+            Bytecode::new { .. } => {
+                let value = try!(interpreter.new_operation(pc, insn, cp));
+                self.push(pc, value)
+            }
+
+            Bytecode::iload { index } |
+            Bytecode::fload { index } |
+            Bytecode::aload { index } |
+            Bytecode::lload { index } |
+            Bytecode::dload { byte: index } => self.load(pc, insn, index as usize, interpreter),
+            Bytecode::iload_n(index) |
+            Bytecode::fload_n(index) |
+            Bytecode::aload_n(index) |
+            Bytecode::lload_n(index) |
+            Bytecode::dload_n(index) => self.load(pc, insn, index as usize, interpreter),
+            Bytecode::wide_iload { index } |
+            Bytecode::wide_fload { index } |
+            Bytecode::wide_aload { index } |
+            Bytecode::wide_lload { index } |
+            Bytecode::wide_dload { index } => self.load(pc, insn, index as usize, interpreter),
+
+            Bytecode::istore { index } |
+            Bytecode::fstore { index } |
+            Bytecode::astore { index } |
+            Bytecode::lstore { index } |
+            Bytecode::dstore { index } => self.store(pc, insn, index as usize, interpreter),
+            Bytecode::istore_n(index) |
+            Bytecode::fstore_n(index) |
+            Bytecode::astore_n(index) |
+            Bytecode::lstore_n(index) |
+            Bytecode::dstore_n(index) => self.store(pc, insn, index as usize, interpreter),
+            Bytecode::wide_istore { index } |
+            Bytecode::wide_fstore { index } |
+            Bytecode::wide_astore { index } |
+            Bytecode::wide_lstore { index } |
+            Bytecode::wide_dstore { index } => self.store(pc, insn, index as usize, interpreter),
+
+            Bytecode::iinc { index, .. } => self.increment(pc, insn, cp, index as usize, interpreter),
+            Bytecode::wide_iinc { index, .. } => {
+                self.increment(pc, insn, cp, index as usize, interpreter)
+            }
+
+            Bytecode::ret { index } => self.check_local(pc, index as usize),
+            Bytecode::wide_ret { index } => self.check_local(pc, index as usize),
+
+            Bytecode::iaload |
+            Bytecode::laload |
+            Bytecode::faload |
+            Bytecode::daload |
+            Bytecode::aaload |
+            Bytecode::baload |
+            Bytecode::caload |
+            Bytecode::saload => self.binary(pc, insn, cp, interpreter, true),
+
+            Bytecode::iastore |
+            Bytecode::lastore |
+            Bytecode::fastore |
+            Bytecode::dastore |
+            Bytecode::aastore |
+            Bytecode::bastore |
+            Bytecode::castore |
+            Bytecode::sastore => {
+                let value3 = try!(self.pop(pc));
+                let value2 = try!(self.pop(pc));
+                let value1 = try!(self.pop(pc));
+                interpreter.ternary_operation(pc, insn, &value1, &value2, &value3)
+            }
+
+            Bytecode::pop => self.pop1(pc, insn).map(|_| ()),
+            Bytecode::pop2 => {
+                if try!(self.pop(pc)).size() == 1 {
+                    try!(self.pop1(pc, insn));
+                }
+                Ok(())
+            }
+            Bytecode::dup => {
+                let value1 = try!(self.pop1(pc, insn));
+                let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                self.push_all(pc, vec![value1, copy1])
+            }
+            Bytecode::dup_x1 => {
+                let value1 = try!(self.pop1(pc, insn));
+                let value2 = try!(self.pop1(pc, insn));
+                let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                self.push_all(pc, vec![copy1, value2, value1])
+            }
+            Bytecode::dup_x2 => {
+                let value1 = try!(self.pop1(pc, insn));
+                let value2 = try!(self.pop(pc));
+                let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                if value2.size() == 1 {
+                    let value3 = try!(self.pop1(pc, insn));
+                    self.push_all(pc, vec![copy1, value3, value2, value1])
+                } else {
+                    self.push_all(pc, vec![copy1, value2, value1])
+                }
+            }
+            Bytecode::dup2 => {
+                let value1 = try!(self.pop(pc));
+                if value1.size() == 1 {
+                    let value2 = try!(self.pop1(pc, insn));
+                    let copy2 = try!(interpreter.copy_operation(pc, insn, &value2));
+                    let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                    self.push_all(pc, vec![value2, value1, copy2, copy1])
+                } else {
+                    let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                    self.push_all(pc, vec![value1, copy1])
+                }
+            }
+            Bytecode::dup2_x1 => {
+                let value1 = try!(self.pop(pc));
+                if value1.size() == 1 {
+                    let value2 = try!(self.pop1(pc, insn));
+                    let value3 = try!(self.pop1(pc, insn));
+                    let copy2 = try!(interpreter.copy_operation(pc, insn, &value2));
+                    let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                    self.push_all(pc, vec![copy2, copy1, value3, value2, value1])
+                } else {
+                    let value2 = try!(self.pop1(pc, insn));
+                    let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                    self.push_all(pc, vec![copy1, value2, value1])
+                }
+            }
+            Bytecode::dup2_x2 => {
+                let value1 = try!(self.pop(pc));
+                if value1.size() == 1 {
+                    let value2 = try!(self.pop1(pc, insn));
+                    let value3 = try!(self.pop(pc));
+                    let copy2 = try!(interpreter.copy_operation(pc, insn, &value2));
+                    let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                    if value3.size() == 1 {
+                        let value4 = try!(self.pop1(pc, insn));
+                        self.push_all(pc, vec![copy2, copy1, value4, value3, value2, value1])
+                    } else {
+                        self.push_all(pc, vec![copy2, copy1, value3, value2, value1])
+                    }
+                } else {
+                    let value2 = try!(self.pop(pc));
+                    let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                    if value2.size() == 1 {
+                        let value3 = try!(self.pop1(pc, insn));
+                        self.push_all(pc, vec![copy1, value3, value2, value1])
+                    } else {
+                        self.push_all(pc, vec![copy1, value2, value1])
+                    }
+                }
+            }
+            Bytecode::swap => {
+                let value1 = try!(self.pop1(pc, insn));
+                let value2 = try!(self.pop1(pc, insn));
+                let copy1 = try!(interpreter.copy_operation(pc, insn, &value1));
+                let copy2 = try!(interpreter.copy_operation(pc, insn, &value2));
+                self.push_all(pc, vec![copy1, copy2])
+            }
+
+            Bytecode::iadd | Bytecode::ladd | Bytecode::fadd | Bytecode::dadd |
+            Bytecode::isub | Bytecode::lsub | Bytecode::fsub | Bytecode::dsub |
+            Bytecode::imul | Bytecode::lmul | Bytecode::fmul | Bytecode::dmul |
+            Bytecode::idiv | Bytecode::ldiv | Bytecode::fdiv | Bytecode::ddiv |
+            Bytecode::irem | Bytecode::lrem | Bytecode::frem | Bytecode::drem |
+            Bytecode::ishl | Bytecode::lshl | Bytecode::ishr | Bytecode::lshr |
+            Bytecode::iushr | Bytecode::lushr |
+            Bytecode::iand | Bytecode::land | Bytecode::ior | Bytecode::lor |
+            Bytecode::ixor | Bytecode::lxor |
+            Bytecode::lcmp | Bytecode::fcmpl | Bytecode::fcmpg | Bytecode::dcmpl |
+            Bytecode::dcmpg => self.binary(pc, insn, cp, interpreter, true),
+
+            Bytecode::ineg | Bytecode::lneg | Bytecode::fneg | Bytecode::dneg |
+            Bytecode::i2l | Bytecode::i2f | Bytecode::i2d |
+            Bytecode::l2i | Bytecode::l2f | Bytecode::l2d |
+            Bytecode::f2i | Bytecode::f2l | Bytecode::f2d |
+            Bytecode::d2i | Bytecode::d2l | Bytecode::d2f |
+            Bytecode::i2b | Bytecode::i2c | Bytecode::i2s |
+            Bytecode::getfield { .. } |
+            Bytecode::newarray { .. } |
+            Bytecode::anewarray { .. } |
+            Bytecode::arraylength |
+            Bytecode::checkcast { .. } |
+            Bytecode::instanceof { .. } => self.unary(pc, insn, cp, interpreter, true),
+
+            Bytecode::ifeq { .. } |
+            Bytecode::ifne { .. } |
+            Bytecode::iflt { .. } |
+            Bytecode::ifge { .. } |
+            Bytecode::ifgt { .. } |
+            Bytecode::ifle { .. } |
+            Bytecode::ifnull { .. } |
+            Bytecode::ifnonnull { .. } |
+            Bytecode::tableswitch { .. } |
+            Bytecode::lookupswitch { .. } |
+            Bytecode::putstatic { .. } |
+            Bytecode::athrow |
+            Bytecode::monitorenter |
+            Bytecode::monitorexit => self.unary(pc, insn, cp, interpreter, false),
+
+            Bytecode::if_icmpeq { .. } |
+            Bytecode::if_icmpne { .. } |
+            Bytecode::if_icmplt { .. } |
+            Bytecode::if_icmpge { .. } |
+            Bytecode::if_icmpgt { .. } |
+            Bytecode::if_icmple { .. } |
+            Bytecode::if_acmpeq { .. } |
+            Bytecode::if_acmpne { .. } |
+            Bytecode::putfield { .. } => self.binary(pc, insn, cp, interpreter, false),
+
+            Bytecode::ireturn |
+            Bytecode::lreturn |
+            Bytecode::freturn |
+            Bytecode::dreturn |
+            Bytecode::areturn => {
+                let value = try!(self.pop(pc));
+                try!(interpreter.unary_operation(pc, insn, cp, &value));
+                match ret {
+                    Some(expected) => interpreter.return_operation(pc, insn, &value, expected),
+                    None => invalid(pc, "Method expects no return value"),
+                }
+            }
+            Bytecode::Return => {
+                match ret {
+                    Some(_) => invalid(pc, "Method expects a return value"),
+                    None => Ok(()),
+                }
+            }
+
+            Bytecode::invokevirtual { .. } |
+            Bytecode::invokespecial { .. } |
+            Bytecode::invokestatic { .. } |
+            Bytecode::invokeinterface { .. } |
+            Bytecode::invokedynamic { .. } => {
+                let descriptor = try!(insn.method_descriptor(cp).unwrap());
+                let receiver = match *insn {
+                    Bytecode::invokestatic { .. } |
+                    Bytecode::invokedynamic { .. } => 0,
+                    _ => 1,
+                };
+                let count = descriptor.params.len() + receiver;
+                self.nary(pc, insn, cp, interpreter, count, descriptor.ret.is_some())
+            }
+            Bytecode::multianewarray { dimensions, .. } => {
+                self.nary(pc, insn, cp, interpreter, dimensions as usize, true)
+            }
+
+            Bytecode::breakpoint |
+            Bytecode::impdep1 |
+            Bytecode::impdep2 => {
+                invalid(pc, &format!("Illegal instruction {}", insn.info().mnemonic()))
+            }
+        }
+    }
+
+    fn increment(&mut self,
+                 pc: usize,
+                 insn: &Bytecode,
+                 cp: &ConstantPool,
+                 index: usize,
+                 interpreter: &mut Interpreter<V>)
+                 -> Result<()> {
+        try!(self.check_local(pc, index));
+        let value = self.locals[index].clone();
+        let value = try!(interpreter.unary_operation(pc, insn, cp, &value));
+        match value {
+            Some(value) => self.set_local(pc, index, value, interpreter),
+            None => invalid(pc, "No value produced by iinc"),
+        }
+    }
+
+    fn push_all(&mut self, pc: usize, values: Vec<V>) -> Result<()> {
+        for value in values {
+            try!(self.push(pc, value));
+        }
+        Ok(())
+    }
+
+    /// Merges `other` into this frame, returning `true` if this frame
+    /// changed. `pc` is the instruction both frames are for.
+    pub fn merge(&mut self,
+                 pc: usize,
+                 other: &Frame<V>,
+                 interpreter: &mut Interpreter<V>)
+                 -> Result<bool> {
+        if self.stack.len() != other.stack.len() {
+            return invalid(pc, "Incompatible stack heights");
+        }
+        let mut changed = false;
+        let pairs = self.locals
+            .iter_mut()
+            .zip(other.locals.iter())
+            .chain(self.stack.iter_mut().zip(other.stack.iter()));
+        for (value, other) in pairs {
+            let merged = interpreter.merge(value, other);
+            if merged != *value {
+                *value = merged;
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// The frames computed by an `Analyzer` for each instruction of a method.
+#[derive(Clone, Debug)]
+pub struct Frames<V> {
+    instructions: InstructionList,
+    frames: Vec<Option<Frame<V>>>,
+}
+
+impl<V: Value> Frames<V> {
+    pub fn instructions(&self) -> &InstructionList {
+        &self.instructions
+    }
+
+    /// The frame before the instruction at `index` in the `InstructionList`,
+    /// or `None` if the instruction is unreachable.
+    pub fn get(&self, index: usize) -> Option<&Frame<V>> {
+        self.frames.get(index).and_then(|frame| frame.as_ref())
+    }
+
+    /// The frame before the instruction at `pc`, or `None` if the instruction
+    /// is unreachable or `pc` does not start an instruction.
+    pub fn at(&self, pc: usize) -> Option<&Frame<V>> {
+        self.instructions.index_of(pc).and_then(|index| self.get(index))
+    }
+}
+
+/// Runs an `Interpreter` over the instructions of a method to a fixpoint.
+pub struct Analyzer<'a, V: Value + 'a> {
+    interpreter: &'a mut Interpreter<V>,
+}
+
+impl<'a, V: Value> Analyzer<'a, V> {
+    pub fn new(interpreter: &'a mut Interpreter<V>) -> Analyzer<'a, V> {
+        Analyzer { interpreter: interpreter }
+    }
+
+    /// Computes the frame before every reachable instruction of `method`, a
+    /// method of `class`. Methods without code have no frames.
+    ///
+    /// Fails if the code cannot be decoded, the control flow graph cannot be
+    /// built, or an instruction cannot be simulated on its frame.
+    pub fn analyze(&mut self, class: &ClassFile, method: &MethodInfo) -> Result<Frames<V>> {
+        let cp = &class.constants;
+        let code = match method.attrs.code() {
+            Some(code) => code,
+            None => {
+                return Ok(Frames {
+                    instructions: try!(InstructionList::new(&[])),
+                    frames: vec![],
+                })
+            }
+        };
+        let cfg = try!(ControlFlowGraph::new(code));
+        let instructions = cfg.instructions().clone();
+        let count = instructions.len();
+
+        // The handlers covering each instruction, with their catch types.
+        let mut handlers: Vec<Vec<(usize, Option<&str>)>> = vec![vec![]; count];
+        for (index, handler) in code.exception_table.iter().enumerate() {
+            let catch_type = if handler.catch_type == 0 {
+                None
+            } else {
+                match cp.class_name(handler.catch_type) {
+                    Some(name) => Some(name.as_str()),
+                    None => return Err(Error::InvalidExceptionHandler { index: index }),
+                }
+            };
+            let start = instructions.index_of(handler.start_pc as usize).unwrap();
+            let end = instructions.index_of(handler.end_pc as usize).unwrap_or(count);
+            for covered in &mut handlers[start..end] {
+                covered.push((index, catch_type));
+            }
+        }
+
+        // The block ended by each instruction, if any.
+        let mut block_ends = vec![None; count];
+        for block in cfg.blocks() {
+            block_ends[block.instructions.end - 1] = Some(block.id);
+        }
+
+        let descriptor = try!(method.descriptor(cp));
+        let ret = descriptor.ret.as_ref().map(|ty| self.interpreter.new_return_type_value(ty));
+        let entry = try!(self.entry_frame(class, method, &descriptor.params, code.max_locals,
+                                          code.max_stack));
+
+        let mut frames: Vec<Option<Frame<V>>> = vec![None; count];
+        let mut pending = vec![];
+        let mut queued = vec![false; count];
+        if count > 0 {
+            try!(self.merge(&mut frames, &mut pending, &mut queued, &instructions, 0, entry));
+        }
+        while let Some(index) = pending.pop() {
+            queued[index] = false;
+            let pc = instructions.pc(index).unwrap();
+            let insn = instructions.get(index).unwrap();
+            let before = frames[index].clone().unwrap();
+            let mut after = before.clone();
+            try!(after.execute(pc, insn, cp, self.interpreter, ret.as_ref()));
+
+            match block_ends[index] {
+                Some(block) => {
+                    for edge in cfg.successors(block) {
+                        if let EdgeKind::Exceptional { .. } = edge.kind {
+                            continue;
+                        }
+                        let target = cfg.block(edge.to).instructions.start;
+                        try!(self.merge(&mut frames, &mut pending, &mut queued, &instructions,
+                                        target, after.clone()));
+                    }
+                    if insn.falls_through() && index + 1 == count {
+                        return invalid(pc, "Falling off the end of the code");
+                    }
+                }
+                None => {
+                    try!(self.merge(&mut frames, &mut pending, &mut queued, &instructions,
+                                    index + 1, after));
+                }
+            }
+
+            for &(handler_index, catch_type) in &handlers[index] {
+                let handler = &code.exception_table[handler_index];
+                let mut frame = before.clone();
+                frame.stack.clear();
+                let exception = self.interpreter.new_exception_value(handler, catch_type);
+                try!(frame.push(pc, exception));
+                let target = instructions.index_of(handler.handler_pc as usize).unwrap();
+                try!(self.merge(&mut frames, &mut pending, &mut queued, &instructions, target,
+                                frame));
+            }
+        }
+
+        Ok(Frames {
+            instructions: instructions,
+            frames: frames,
+        })
+    }
+
+    fn entry_frame(&mut self,
+                   class: &ClassFile,
+                   method: &MethodInfo,
+                   params: &[FieldType],
+                   max_locals: u16,
+                   max_stack: u16)
+                   -> Result<Frame<V>> {
+        let mut locals = vec![];
+        if !method.access_flags.is_static() {
+            let this = FieldType::Object(class.this_class_name().clone());
+            locals.push(self.interpreter.new_parameter_value(0, &this));
+        }
+        for param in params {
+            let local = locals.len();
+            locals.push(self.interpreter.new_parameter_value(local, param));
+            if param.slots() == 2 {
+                locals.push(self.interpreter.new_empty_value(local + 1));
+            }
+        }
+        if locals.len() > max_locals as usize {
+            return invalid(0, "Arguments can't fit into locals");
+        }
+        while locals.len() < max_locals as usize {
+            let local = locals.len();
+            locals.push(self.interpreter.new_empty_value(local));
+        }
+        Ok(Frame::new(locals, max_stack as usize))
+    }
+
+    fn merge(&mut self,
+             frames: &mut [Option<Frame<V>>],
+             pending: &mut Vec<usize>,
+             queued: &mut [bool],
+             instructions: &InstructionList,
+             index: usize,
+             frame: Frame<V>)
+             -> Result<()> {
+        let changed = match frames[index] {
+            Some(ref mut existing) => {
+                let pc = instructions.pc(index).unwrap();
+                try!(existing.merge(pc, &frame, self.interpreter))
+            }
+            None => true,
+        };
+        if frames[index].is_none() {
+            frames[index] = Some(frame);
+        }
+        if changed && !queued[index] {
+            queued[index] = true;
+            pending.push(index);
+        }
+        Ok(())
+    }
+}
+
+fn invalid<T>(pc: usize, message: &str) -> Result<T> {
+    Err(Error::InvalidFrame {
+        pc: pc,
+        message: message.to_string(),
+    })
+}
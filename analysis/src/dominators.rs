@@ -0,0 +1,305 @@
+//! Dominator and post-dominator trees of a control flow graph.
+//!
+//! Block `a` dominates block `b` if every path from the entry block to `b`
+//! passes through `a`, and post-dominates it if every path from `b` to an exit
+//! of the method passes through `a`. Both trees are computed with the
+//! iterative algorithm of Cooper, Harvey and Kennedy, treating every kind of
+//! edge, including exceptional ones, as a path control may take.
+use cfg::ControlFlowGraph;
+
+/// A dominator or post-dominator tree over the blocks of a
+/// `ControlFlowGraph`, along with the dominance frontier of each block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DominatorTree {
+    idom: Vec<Option<usize>>,
+    roots: Vec<usize>,
+    children: Vec<Vec<usize>>,
+    /// The preorder and postorder numbers of each block in the tree, used to
+    /// answer dominance queries in constant time.
+    order: Vec<Option<(usize, usize)>>,
+    frontiers: Vec<Vec<usize>>,
+}
+
+impl DominatorTree {
+    /// Computes the dominator tree of `cfg`, rooted at the entry block.
+    ///
+    /// Blocks that cannot be reached from the entry have no dominators and
+    /// are not part of the tree.
+    pub fn dominators(cfg: &ControlFlowGraph) -> DominatorTree {
+        let count = cfg.blocks().len();
+        let mut successors = vec![vec![]; count];
+        let mut predecessors = vec![vec![]; count];
+        for edge in cfg.edges() {
+            successors[edge.from].push(edge.to);
+            predecessors[edge.to].push(edge.from);
+        }
+        let root = if count == 0 { None } else { Some(0) };
+        DominatorTree::compute(&successors, &predecessors, root)
+    }
+
+    /// Computes the dominator tree of a graph given by the successors of each
+    /// node, rooted at node 0, for graphs other than a `ControlFlowGraph`.
+    pub fn from_successors(successors: &[Vec<usize>]) -> DominatorTree {
+        let mut predecessors = vec![vec![]; successors.len()];
+        for (from, targets) in successors.iter().enumerate() {
+            for &to in targets {
+                predecessors[to].push(from);
+            }
+        }
+        let root = if successors.is_empty() { None } else { Some(0) };
+        DominatorTree::compute(successors, &predecessors, root)
+    }
+
+    /// Computes the post-dominator tree of `cfg`.
+    ///
+    /// The exits of the method are the blocks without successors, which end
+    /// in a return or in an `athrow` that no handler catches. The roots of the
+    /// tree are the blocks that no other block post-dominates: the exits, and
+    /// any block whose paths to different exits have no block in common.
+    /// Blocks from which no exit can be reached, such as those in an infinite
+    /// loop, are not part of the tree.
+    pub fn post_dominators(cfg: &ControlFlowGraph) -> DominatorTree {
+        // Reverse the graph and add a virtual exit block that flows into
+        // every real exit, so that the tree has a single root.
+        let count = cfg.blocks().len();
+        let exit = count;
+        let mut successors = vec![vec![]; count + 1];
+        let mut predecessors = vec![vec![]; count + 1];
+        for edge in cfg.edges() {
+            successors[edge.to].push(edge.from);
+            predecessors[edge.from].push(edge.to);
+        }
+        for id in 0..count {
+            if predecessors[id].is_empty() {
+                successors[exit].push(id);
+                predecessors[id].push(exit);
+            }
+        }
+        let mut tree = DominatorTree::compute(&successors, &predecessors, Some(exit));
+
+        // Drop the virtual exit, making its children the roots.
+        tree.roots = tree.children.pop().unwrap();
+        tree.idom.pop();
+        tree.order.pop();
+        tree.frontiers.pop();
+        for &root in &tree.roots {
+            tree.idom[root] = None;
+        }
+        tree
+    }
+
+    fn compute(successors: &[Vec<usize>],
+               predecessors: &[Vec<usize>],
+               root: Option<usize>)
+               -> DominatorTree {
+        let count = successors.len();
+        let root = match root {
+            Some(root) => root,
+            None => {
+                return DominatorTree {
+                    idom: vec![],
+                    roots: vec![],
+                    children: vec![],
+                    order: vec![],
+                    frontiers: vec![],
+                }
+            }
+        };
+
+        // Number the blocks in postorder, then process them in reverse
+        // postorder until the immediate dominators stop changing.
+        let postorder = postorder(successors, root);
+        let mut number = vec![None; count];
+        for (index, &id) in postorder.iter().enumerate() {
+            number[id] = Some(index);
+        }
+        let mut idom = vec![None; count];
+        idom[root] = Some(root);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in postorder.iter().rev().skip(1) {
+                let mut new_idom = None;
+                for &pred in &predecessors[id] {
+                    if idom[pred].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(other) => intersect(&idom, &number, pred, other),
+                    });
+                }
+                if new_idom.is_some() && idom[id] != new_idom {
+                    idom[id] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        idom[root] = None;
+
+        let mut children = vec![vec![]; count];
+        for &id in postorder.iter().rev() {
+            if let Some(parent) = idom[id] {
+                children[parent].push(id);
+            }
+        }
+        for list in &mut children {
+            list.sort();
+        }
+
+        // A block is in the frontier of every block on the tree path from one
+        // of its predecessors up to, but excluding, its immediate dominator.
+        let mut frontiers = vec![vec![]; count];
+        for &id in &postorder {
+            if predecessors[id].len() < 2 {
+                continue;
+            }
+            for &pred in &predecessors[id] {
+                if number[pred].is_none() {
+                    continue;
+                }
+                let mut runner = pred;
+                while Some(runner) != idom[id] {
+                    if !frontiers[runner].contains(&id) {
+                        frontiers[runner].push(id);
+                    }
+                    match idom[runner] {
+                        Some(parent) => runner = parent,
+                        None => break,
+                    }
+                }
+            }
+        }
+        for frontier in &mut frontiers {
+            frontier.sort();
+        }
+
+        let mut tree = DominatorTree {
+            idom: idom,
+            roots: vec![root],
+            children: children,
+            order: vec![None; count],
+            frontiers: frontiers,
+        };
+        tree.number(root);
+        tree
+    }
+
+    /// Assigns preorder and postorder numbers to the subtree at `root`. For a
+    /// post-dominator tree this is the virtual exit, so blocks under
+    /// different exits are never related.
+    fn number(&mut self, root: usize) {
+        let mut pre = 0;
+        let mut post = 0;
+        let mut pending = vec![(root, 0)];
+        while let Some((id, child)) = pending.pop() {
+            if child == 0 {
+                self.order[id] = Some((pre, 0));
+                pre += 1;
+            }
+            if child < self.children[id].len() {
+                pending.push((id, child + 1));
+                pending.push((self.children[id][child], 0));
+            } else {
+                let (pre, _) = self.order[id].unwrap();
+                self.order[id] = Some((pre, post));
+                post += 1;
+            }
+        }
+    }
+
+    /// The roots of the tree: the entry block for a dominator tree, or the
+    /// blocks no other block post-dominates for a post-dominator tree.
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    /// Returns `true` if block `id` is part of the tree.
+    pub fn contains(&self, id: usize) -> bool {
+        self.order[id].is_some()
+    }
+
+    /// The immediate dominator of block `id`, or `None` for a root or a block
+    /// that is not part of the tree.
+    pub fn immediate_dominator(&self, id: usize) -> Option<usize> {
+        self.idom[id]
+    }
+
+    /// The blocks immediately dominated by block `id`, in order of their ids.
+    pub fn children(&self, id: usize) -> &[usize] {
+        &self.children[id]
+    }
+
+    /// The blocks dominating block `id`, starting with `id` itself and ending
+    /// with a root.
+    pub fn dominators_of(&self, id: usize) -> Vec<usize> {
+        let mut dominators = vec![];
+        if self.contains(id) {
+            let mut current = Some(id);
+            while let Some(id) = current {
+                dominators.push(id);
+                current = self.idom[id];
+            }
+        }
+        dominators
+    }
+
+    /// Returns `true` if block `a` dominates block `b`. Every block in the
+    /// tree dominates itself.
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        match (self.order[a], self.order[b]) {
+            (Some((a_pre, a_post)), Some((b_pre, b_post))) => {
+                a_pre <= b_pre && b_post <= a_post
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if block `a` dominates block `b` and they differ.
+    pub fn strictly_dominates(&self, a: usize, b: usize) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// The dominance frontier of block `id`: the blocks where its dominance
+    /// ends, in order of their ids.
+    pub fn frontier(&self, id: usize) -> &[usize] {
+        &self.frontiers[id]
+    }
+}
+
+/// Lists the blocks reachable from `root` in depth-first postorder.
+fn postorder(successors: &[Vec<usize>], root: usize) -> Vec<usize> {
+    let mut visited = vec![false; successors.len()];
+    let mut postorder = vec![];
+    let mut pending = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((id, next)) = pending.pop() {
+        if next < successors[id].len() {
+            pending.push((id, next + 1));
+            let successor = successors[id][next];
+            if !visited[successor] {
+                visited[successor] = true;
+                pending.push((successor, 0));
+            }
+        } else {
+            postorder.push(id);
+        }
+    }
+    postorder
+}
+
+fn intersect(idom: &[Option<usize>],
+             number: &[Option<usize>],
+             mut a: usize,
+             mut b: usize)
+             -> usize {
+    while a != b {
+        while number[a] < number[b] {
+            a = idom[a].unwrap();
+        }
+        while number[b] < number[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
@@ -0,0 +1,68 @@
+use std::error;
+use std::fmt;
+use std::result;
+
+use classfile;
+use verifier::VerifyError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The code could not be decoded.
+    ClassFile(classfile::Error),
+    /// Rewritten code no longer fits in a method, or one of its branches no
+    /// longer fits its offset.
+    CodeTooLarge,
+    /// The branch at `pc` targets a pc that does not start an instruction.
+    InvalidBranchTarget { pc: usize, target: usize },
+    /// The exception table entry at `index` has a range or handler that does
+    /// not fall on instruction boundaries.
+    InvalidExceptionHandler { index: usize },
+    /// Simulating the instruction at `pc` failed, for example because it
+    /// pops more values than are on the operand stack.
+    InvalidFrame { pc: usize, message: String },
+    /// The `jsr` at `pc` calls a subroutine that it is itself part of.
+    RecursiveSubroutine { pc: usize },
+    /// A `ClassHierarchy` was asked about a class it does not know.
+    UnknownClass(String),
+    /// The instruction at `pc` is valid, but cannot be handled by the
+    /// analysis, such as a `jsr` that has to be inlined first.
+    Unsupported { pc: usize, message: String },
+    /// A method failed verification.
+    Verify(VerifyError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ClassFile(ref err) => write!(f, "{}", err),
+            Error::CodeTooLarge => write!(f, "code too large"),
+            Error::InvalidBranchTarget { pc, target } => {
+                write!(f, "branch at pc {} targets invalid pc {}", pc, target)
+            }
+            Error::InvalidExceptionHandler { index } => {
+                write!(f, "exception table entry {} is invalid", index)
+            }
+            Error::InvalidFrame { pc, ref message } => write!(f, "pc {}: {}", pc, message),
+            Error::RecursiveSubroutine { pc } => write!(f, "recursive subroutine call at pc {}", pc),
+            Error::UnknownClass(ref name) => write!(f, "unknown class {}", name),
+            Error::Unsupported { pc, ref message } => {
+                write!(f, "pc {}: unsupported: {}", pc, message)
+            }
+            Error::Verify(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "analysis error"
+    }
+}
+
+impl From<classfile::Error> for Error {
+    fn from(err: classfile::Error) -> Error {
+        Error::ClassFile(err)
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
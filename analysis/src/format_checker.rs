@@ -0,0 +1,624 @@
+//! Structural checks of whole class files ([JVMS 4.8]
+//! (https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.8)).
+//!
+//! The format checker looks at everything the bytecode verifiers take for
+//! granted: that constant pool references point to constants of the right
+//! kind, that access flags are combined legally, that names and descriptors
+//! follow their grammar, that members are unique, and that `Code` attributes
+//! are well formed.
+use std::collections::HashSet;
+use std::fmt;
+
+use classfile::{ClassAccessFlags, ClassFile, CodeAttribute, Constant, ConstantPool, FieldInfo,
+                FieldType, MethodDescriptor, MethodInfo, AttributeInfo, FIELD_ACC_FINAL,
+                FIELD_ACC_PRIVATE, FIELD_ACC_PROTECTED, FIELD_ACC_PUBLIC, FIELD_ACC_STATIC,
+                FIELD_ACC_SYNTHETIC, METHOD_ACC_ABSTRACT, METHOD_ACC_BRIDGE, METHOD_ACC_FINAL,
+                METHOD_ACC_NATIVE, METHOD_ACC_PRIVATE, METHOD_ACC_PROTECTED, METHOD_ACC_PUBLIC,
+                METHOD_ACC_STATIC, METHOD_ACC_STRICT, METHOD_ACC_SYNCHRONIZED,
+                METHOD_ACC_SYNTHETIC, METHOD_ACC_VARARGS};
+
+/// The part of a class file a `Violation` was found in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Location {
+    /// The class itself: its header, access flags or super types.
+    Class,
+    /// The constant pool entry at the given index.
+    Constant(u16),
+    /// The field at the given index in `ClassFile::fields`.
+    Field(usize),
+    /// The method at the given index in `ClassFile::methods`.
+    Method(usize),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Location::Class => write!(f, "class"),
+            Location::Constant(index) => write!(f, "constant #{}", index),
+            Location::Field(index) => write!(f, "field {}", index),
+            Location::Method(index) => write!(f, "method {}", index),
+        }
+    }
+}
+
+/// A rule of the class file format that a class breaks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Violation {
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Checks `class` against the format rules of JVMS 4.8 and returns every
+/// violation found, in the order of the class file. An empty list means the
+/// class is well formed, though its methods may still fail verification.
+pub fn check(class: &ClassFile) -> Vec<Violation> {
+    let mut checker = Checker {
+        class: class,
+        cp: &class.constants,
+        violations: vec![],
+    };
+    checker.check_header();
+    checker.check_constants();
+    checker.check_class();
+    checker.check_fields();
+    checker.check_methods();
+    checker.violations
+}
+
+/// Returns `true` if `name` is a valid unqualified name (JVMS 4.2.2): a
+/// non-empty name without `.`, `;`, `[` or `/`.
+pub fn is_unqualified_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(|c| c == '.' || c == ';' || c == '[' || c == '/')
+}
+
+/// Returns `true` if `name` is a valid method name: `<init>`, `<clinit>`, or
+/// an unqualified name without `<` or `>`.
+pub fn is_method_name(name: &str) -> bool {
+    name == "<init>" || name == "<clinit>" ||
+    (is_unqualified_name(name) && !name.contains(|c| c == '<' || c == '>'))
+}
+
+/// Returns `true` if `name` is a valid binary class name in internal form,
+/// such as `java/lang/Object` (JVMS 4.2.1).
+pub fn is_class_name(name: &str) -> bool {
+    name.split('/').all(is_unqualified_name)
+}
+
+const CLASS_FILE_MAGIC: u32 = 0xcafebabe;
+
+/// The largest code array a method may have.
+const MAX_CODE_LENGTH: usize = 65535;
+
+/// The most local variable slots method arguments may take, including `this`.
+const MAX_ARG_SLOTS: u16 = 255;
+
+struct Checker<'a> {
+    class: &'a ClassFile,
+    cp: &'a ConstantPool,
+    violations: Vec<Violation>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, location: Location, message: String) {
+        self.violations.push(Violation {
+            location: location,
+            message: message,
+        });
+    }
+
+    /// Reports a reference from `what` to the constant at `index` that is not
+    /// of the `expected` kind.
+    fn wrong_kind(&mut self, location: Location, what: &str, index: u16, expected: &str) {
+        let message = match self.cp.get(index) {
+            Some(constant) => {
+                format!("{} refers to constant #{} ({}), not a {} constant",
+                        what,
+                        index,
+                        constant.name(),
+                        expected)
+            }
+            None => format!("{} refers to invalid constant #{}", what, index),
+        };
+        self.report(location, message);
+    }
+
+    /// Resolves the `Constant::Utf8` at `index`, or reports `what` as
+    /// referring to the wrong kind of constant.
+    fn utf8(&mut self, location: Location, what: &str, index: u16) -> Option<&'a str> {
+        match self.cp.get(index) {
+            Some(&Constant::Utf8(ref value)) => Some(value),
+            _ => {
+                self.wrong_kind(location, what, index, "Utf8");
+                None
+            }
+        }
+    }
+
+    /// Resolves the name of the `Constant::Class` at `index`, or reports
+    /// `what` as referring to the wrong kind of constant. The name itself is
+    /// checked along with the class constant.
+    fn class_ref(&mut self, location: Location, what: &str, index: u16) -> Option<&'a str> {
+        match self.cp.get(index) {
+            Some(&Constant::Class(_)) => self.cp.class_name(index).map(|name| name.as_ref()),
+            _ => {
+                self.wrong_kind(location, what, index, "Class");
+                None
+            }
+        }
+    }
+
+    /// Resolves the name and descriptor of the `Constant::NameAndType` at
+    /// `index`, or reports `what` as referring to the wrong kind of constant.
+    fn name_and_type(&mut self,
+                     location: Location,
+                     what: &str,
+                     index: u16)
+                     -> Option<(&'a str, &'a str)> {
+        match self.cp.get(index) {
+            Some(&Constant::NameAndType(ref name_and_type)) => {
+                match (self.cp.get(name_and_type.name_index),
+                       self.cp.get(name_and_type.descriptor_index)) {
+                    (Some(&Constant::Utf8(ref name)), Some(&Constant::Utf8(ref descriptor))) => {
+                        Some((name, descriptor))
+                    }
+                    _ => None,
+                }
+            }
+            _ => {
+                self.wrong_kind(location, what, index, "NameAndType");
+                None
+            }
+        }
+    }
+
+    fn check_header(&mut self) {
+        if self.class.magic != CLASS_FILE_MAGIC {
+            let message = format!("bad magic number {:#x}", self.class.magic);
+            self.report(Location::Class, message);
+        }
+    }
+
+    fn check_constants(&mut self) {
+        let cp = self.cp;
+        let mut previous: Option<&Constant> = None;
+        for (i, constant) in cp.iter().enumerate() {
+            let index = i as u16 + 1;
+            let location = Location::Constant(index);
+            let is_wide = previous.map_or(false, |previous| match *previous {
+                Constant::Long(_) | Constant::Double(_) => true,
+                _ => false,
+            });
+            if is_wide != (*constant == Constant::Skip) {
+                let message = if is_wide {
+                    "the entry after a Long or Double constant must be unusable".to_string()
+                } else {
+                    "unusable entry does not follow a Long or Double constant".to_string()
+                };
+                self.report(location, message);
+            }
+            previous = Some(constant);
+            match *constant {
+                Constant::Class(name_index) => {
+                    if let Some(name) = self.utf8(location, "class name", name_index) {
+                        let valid = if name.starts_with('[') {
+                            FieldType::parse(name).is_ok()
+                        } else {
+                            is_class_name(name)
+                        };
+                        if !valid {
+                            self.report(location, format!("invalid class name '{}'", name));
+                        }
+                    }
+                }
+                Constant::String(string_index) => {
+                    self.utf8(location, "string", string_index);
+                }
+                Constant::Fieldref(ref entity) |
+                Constant::Methodref(ref entity) |
+                Constant::InterfaceMethodref(ref entity) => {
+                    self.class_ref(location, "class_index", entity.class_index);
+                    if let Some((name, descriptor)) =
+                           self.name_and_type(location,
+                                              "name_and_type_index",
+                                              entity.name_and_type_index) {
+                        self.check_member_ref(location, constant, name, descriptor);
+                    }
+                }
+                Constant::NameAndType(ref name_and_type) => {
+                    self.utf8(location, "name_index", name_and_type.name_index);
+                    if let Some(descriptor) = self.utf8(location,
+                                                        "descriptor_index",
+                                                        name_and_type.descriptor_index) {
+                        if FieldType::parse(descriptor).is_err() &&
+                           MethodDescriptor::parse(descriptor).is_err() {
+                            self.report(location, format!("invalid descriptor '{}'", descriptor));
+                        }
+                    }
+                }
+                Constant::MethodHandle { reference_kind, reference_index } => {
+                    self.check_version(location, constant);
+                    self.check_method_handle(location, reference_kind, reference_index);
+                }
+                Constant::MethodType { descriptor_index } => {
+                    self.check_version(location, constant);
+                    if let Some(descriptor) = self.utf8(location,
+                                                        "descriptor_index",
+                                                        descriptor_index) {
+                        if MethodDescriptor::parse(descriptor).is_err() {
+                            self.report(location,
+                                        format!("invalid method descriptor '{}'", descriptor));
+                        }
+                    }
+                }
+                Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                    self.check_version(location, constant);
+                    let bootstrap_methods = self.class
+                        .attrs
+                        .bootstrap_methods()
+                        .map_or(0, |methods| methods.len());
+                    if bootstrap_method_attr_index as usize >= bootstrap_methods {
+                        self.report(location,
+                                    format!("bootstrap method {} does not exist",
+                                            bootstrap_method_attr_index));
+                    }
+                    if let Some((name, descriptor)) = self.name_and_type(location,
+                                                                         "name_and_type_index",
+                                                                         name_and_type_index) {
+                        if !is_method_name(name) || name.starts_with('<') {
+                            self.report(location, format!("invalid method name '{}'", name));
+                        }
+                        if MethodDescriptor::parse(descriptor).is_err() {
+                            self.report(location,
+                                        format!("invalid method descriptor '{}'", descriptor));
+                        }
+                    }
+                }
+                Constant::Integer(_) |
+                Constant::Float(_) |
+                Constant::Long(_) |
+                Constant::Double(_) |
+                Constant::Utf8(_) |
+                Constant::Skip => {}
+            }
+        }
+    }
+
+    /// Checks the name and descriptor of a `Fieldref`, `Methodref` or
+    /// `InterfaceMethodref` (JVMS 4.4.2).
+    fn check_member_ref(&mut self,
+                        location: Location,
+                        constant: &Constant,
+                        name: &str,
+                        descriptor: &str) {
+        if let Constant::Fieldref(_) = *constant {
+            if !is_unqualified_name(name) {
+                self.report(location, format!("invalid field name '{}'", name));
+            }
+            if FieldType::parse(descriptor).is_err() {
+                self.report(location, format!("invalid field descriptor '{}'", descriptor));
+            }
+            return;
+        }
+        let is_init = name == "<init>";
+        let is_method_ref = match *constant {
+            Constant::Methodref(_) => true,
+            _ => false,
+        };
+        if !is_method_name(name) || name == "<clinit>" || (is_init && !is_method_ref) {
+            self.report(location, format!("invalid method name '{}'", name));
+        }
+        match MethodDescriptor::parse(descriptor) {
+            Ok(ref parsed) if is_init && parsed.ret.is_some() => {
+                self.report(location, "<init> must return void".to_string());
+            }
+            Ok(_) => {}
+            Err(_) => {
+                self.report(location, format!("invalid method descriptor '{}'", descriptor))
+            }
+        }
+    }
+
+    /// Checks the reference kind and target of a `MethodHandle` (JVMS 4.4.8).
+    fn check_method_handle(&mut self, location: Location, kind: u8, index: u16) {
+        let (expected, allowed) = match kind {
+            1...4 => ("Fieldref", self.is_kind(index, "Fieldref")),
+            5 | 8 => ("Methodref", self.is_kind(index, "Methodref")),
+            6 | 7 if self.class.major_version >= 52 => {
+                ("Methodref or InterfaceMethodref",
+                 self.is_kind(index, "Methodref") || self.is_kind(index, "InterfaceMethodref"))
+            }
+            6 | 7 => ("Methodref", self.is_kind(index, "Methodref")),
+            9 => ("InterfaceMethodref", self.is_kind(index, "InterfaceMethodref")),
+            _ => {
+                self.report(location, format!("invalid reference kind {}", kind));
+                return;
+            }
+        };
+        if !allowed {
+            self.wrong_kind(location, "reference_index", index, expected);
+            return;
+        }
+        if kind < 5 {
+            return;
+        }
+        let name = match self.cp[index] {
+            Constant::Methodref(ref entity) |
+            Constant::InterfaceMethodref(ref entity) => {
+                self.cp
+                    .get(entity.name_and_type_index)
+                    .and_then(|constant| match *constant {
+                        Constant::NameAndType(ref name_and_type) => {
+                            self.cp.get(name_and_type.name_index)
+                        }
+                        _ => None,
+                    })
+            }
+            _ => None,
+        };
+        if let Some(&Constant::Utf8(ref name)) = name {
+            if (kind == 8) != (name == "<init>") || name == "<clinit>" {
+                self.report(location,
+                            format!("reference kind {} cannot refer to method '{}'", kind, name));
+            }
+        }
+    }
+
+    fn is_kind(&self, index: u16, kind: &str) -> bool {
+        self.cp.get(index).map_or(false, |constant| constant.name() == kind)
+    }
+
+    /// Reports constants that did not exist yet in the version of the class.
+    fn check_version(&mut self, location: Location, constant: &Constant) {
+        if self.class.major_version < 51 {
+            let message = format!("{} constants require class file version 51",
+                                  constant.name());
+            self.report(location, message);
+        }
+    }
+
+    fn check_class(&mut self) {
+        let class = self.class;
+        let flags = class.access_flags;
+        if flags.is_interface() {
+            let illegal = flags.is_final() || flags.is_super() || flags.is_enum() ||
+                          !flags.is_abstract();
+            if illegal {
+                let message = format!("illegal interface modifiers {:#06x}", flags.bits());
+                self.report(Location::Class, message);
+            }
+        } else if flags.is_annotation() || (flags.is_final() && flags.is_abstract()) {
+            let message = format!("illegal class modifiers {:#06x}", flags.bits());
+            self.report(Location::Class, message);
+        }
+
+        let this_class = self.class_ref(Location::Class, "this_class", class.this_class);
+        if let Some(name) = this_class {
+            if name.starts_with('[') {
+                self.report(Location::Class, format!("this_class is an array type '{}'", name));
+            }
+        }
+        if class.super_class == 0 {
+            if this_class.map_or(false, |name| name != "java/lang/Object") {
+                self.report(Location::Class, "class has no superclass".to_string());
+            }
+        } else if let Some(name) = self.class_ref(Location::Class,
+                                                  "super_class",
+                                                  class.super_class) {
+            if name.starts_with('[') {
+                self.report(Location::Class, format!("superclass is an array type '{}'", name));
+            } else if flags.is_interface() && name != "java/lang/Object" {
+                self.report(Location::Class,
+                            "interfaces must have java/lang/Object as superclass".to_string());
+            }
+        }
+        for &interface in class.interfaces.iter() {
+            if let Some(name) = self.class_ref(Location::Class, "interface", interface) {
+                if name.starts_with('[') {
+                    self.report(Location::Class, format!("interface is an array type '{}'", name));
+                }
+            }
+        }
+    }
+
+    fn check_fields(&mut self) {
+        let mut seen = HashSet::new();
+        for (i, field) in self.class.fields.iter().enumerate() {
+            let location = Location::Field(i);
+            self.check_field_flags(location, field);
+            let name = self.utf8(location, "name_index", field.name_index);
+            if let Some(name) = name {
+                if !is_unqualified_name(name) {
+                    self.report(location, format!("invalid field name '{}'", name));
+                }
+            }
+            let descriptor = self.utf8(location, "descriptor_index", field.descriptor_index);
+            if let Some(descriptor) = descriptor {
+                if FieldType::parse(descriptor).is_err() {
+                    self.report(location, format!("invalid field descriptor '{}'", descriptor));
+                }
+            }
+            if let (Some(name), Some(descriptor)) = (name, descriptor) {
+                if !seen.insert((name, descriptor)) {
+                    self.report(location,
+                                format!("duplicate field {} {}", name, descriptor));
+                }
+            }
+        }
+    }
+
+    fn check_field_flags(&mut self, location: Location, field: &FieldInfo) {
+        let flags = field.access_flags;
+        let access = FIELD_ACC_PUBLIC | FIELD_ACC_PRIVATE | FIELD_ACC_PROTECTED;
+        let illegal = if self.class.access_flags.is_interface() {
+            let required = FIELD_ACC_PUBLIC | FIELD_ACC_STATIC | FIELD_ACC_FINAL;
+            !flags.contains(required) || !(required | FIELD_ACC_SYNTHETIC).contains(flags)
+        } else {
+            (flags & access).bits().count_ones() > 1 || (flags.is_final() && flags.is_volatile())
+        };
+        if illegal {
+            self.report(location, format!("illegal field modifiers {:#06x}", flags.bits()));
+        }
+    }
+
+    fn check_methods(&mut self) {
+        let mut seen = HashSet::new();
+        for (i, method) in self.class.methods.iter().enumerate() {
+            let location = Location::Method(i);
+            let name = self.utf8(location, "name_index", method.name_index);
+            if let Some(name) = name {
+                if !is_method_name(name) {
+                    self.report(location, format!("invalid method name '{}'", name));
+                }
+            }
+            let descriptor = self.utf8(location, "descriptor_index", method.descriptor_index);
+            let parsed = descriptor.and_then(|descriptor| {
+                let parsed = MethodDescriptor::parse(descriptor).ok();
+                if parsed.is_none() {
+                    self.report(location,
+                                format!("invalid method descriptor '{}'", descriptor));
+                }
+                parsed
+            });
+            let name = name.unwrap_or("");
+            self.check_method_flags(location, method, name);
+            if let Some(ref parsed) = parsed {
+                self.check_descriptor(location, method, name, parsed);
+            }
+            if let Some(descriptor) = descriptor {
+                if !seen.insert((name, descriptor)) {
+                    self.report(location,
+                                format!("duplicate method {}{}", name, descriptor));
+                }
+            }
+            self.check_code(location, method, parsed.as_ref());
+        }
+    }
+
+    fn check_method_flags(&mut self, location: Location, method: &MethodInfo, name: &str) {
+        let flags = method.access_flags;
+        let class_flags: ClassAccessFlags = self.class.access_flags;
+        let version = self.class.major_version;
+        let access = METHOD_ACC_PUBLIC | METHOD_ACC_PRIVATE | METHOD_ACC_PROTECTED;
+        let illegal = if name == "<clinit>" {
+            // Other flags are ignored on class initializers.
+            version >= 51 && !flags.is_static()
+        } else if class_flags.is_interface() {
+            if name == "<init>" {
+                true
+            } else if version < 52 {
+                !flags.contains(METHOD_ACC_PUBLIC | METHOD_ACC_ABSTRACT) ||
+                !(METHOD_ACC_PUBLIC | METHOD_ACC_ABSTRACT | METHOD_ACC_VARARGS |
+                  METHOD_ACC_BRIDGE | METHOD_ACC_SYNTHETIC)
+                    .contains(flags)
+            } else {
+                flags.is_public() == flags.is_private() ||
+                flags.intersects(METHOD_ACC_PROTECTED | METHOD_ACC_FINAL |
+                                 METHOD_ACC_SYNCHRONIZED | METHOD_ACC_NATIVE) ||
+                (flags.is_abstract() && flags.intersects(METHOD_ACC_PRIVATE | METHOD_ACC_STATIC |
+                                                         METHOD_ACC_STRICT))
+            }
+        } else if (flags & access).bits().count_ones() > 1 {
+            true
+        } else if name == "<init>" {
+            !(access | METHOD_ACC_VARARGS | METHOD_ACC_STRICT | METHOD_ACC_SYNTHETIC)
+                .contains(flags)
+        } else {
+            flags.is_abstract() &&
+            flags.intersects(METHOD_ACC_PRIVATE | METHOD_ACC_STATIC | METHOD_ACC_FINAL |
+                             METHOD_ACC_SYNCHRONIZED |
+                             METHOD_ACC_NATIVE | METHOD_ACC_STRICT)
+        };
+        if illegal {
+            self.report(location, format!("illegal method modifiers {:#06x}", flags.bits()));
+        }
+    }
+
+    fn check_descriptor(&mut self,
+                        location: Location,
+                        method: &MethodInfo,
+                        name: &str,
+                        descriptor: &MethodDescriptor) {
+        if name == "<clinit>" && (descriptor.ret.is_some() || !descriptor.params.is_empty()) {
+            self.report(location, "<clinit> must have descriptor ()V".to_string());
+        } else if name == "<init>" && descriptor.ret.is_some() {
+            self.report(location, "<init> must return void".to_string());
+        }
+        if descriptor.arg_slots(method.access_flags.is_static()) > MAX_ARG_SLOTS {
+            self.report(location, "too many arguments".to_string());
+        }
+    }
+
+    fn check_code(&mut self,
+                  location: Location,
+                  method: &MethodInfo,
+                  descriptor: Option<&MethodDescriptor>) {
+        let codes = method.attrs
+            .iter()
+            .filter_map(|attr| match *attr {
+                AttributeInfo::Code(ref code) => Some(&**code),
+                _ => None,
+            })
+            .collect::<Vec<&CodeAttribute>>();
+        let flags = method.access_flags;
+        if flags.is_abstract() || flags.is_native() {
+            if !codes.is_empty() {
+                self.report(location,
+                            "abstract and native methods must not have code".to_string());
+            }
+            return;
+        }
+        if codes.len() != 1 {
+            self.report(location,
+                        format!("method must have exactly one Code attribute, found {}",
+                                codes.len()));
+        }
+        let code = match codes.first() {
+            Some(code) => code,
+            None => return,
+        };
+        if let Some(descriptor) = descriptor {
+            if code.max_locals < descriptor.arg_slots(flags.is_static()) {
+                self.report(location, "arguments can't fit into locals".to_string());
+            }
+        }
+        if code.code.is_empty() || code.code.len() > MAX_CODE_LENGTH {
+            self.report(location, format!("invalid code length {}", code.code.len()));
+            return;
+        }
+        let instructions = match code.instruction_list() {
+            Ok(instructions) => instructions,
+            Err(err) => {
+                self.report(location, format!("malformed code: {}", err));
+                return;
+            }
+        };
+        let code_length = code.code.len();
+        for (i, handler) in code.exception_table.iter().enumerate() {
+            let start = handler.start_pc as usize;
+            let end = handler.end_pc as usize;
+            if start >= end || !instructions.is_boundary(start) ||
+               !(end == code_length || instructions.is_boundary(end)) {
+                self.report(location,
+                            format!("exception table entry {} has invalid range {}..{}",
+                                    i,
+                                    start,
+                                    end));
+            }
+            if !instructions.is_boundary(handler.handler_pc as usize) {
+                self.report(location,
+                            format!("exception table entry {} has invalid handler {}",
+                                    i,
+                                    handler.handler_pc));
+            }
+            if handler.catch_type != 0 {
+                self.class_ref(location, "catch_type", handler.catch_type);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Questions about classes other than the one being analysed.
+//!
+//! Analyses such as verification need to know how classes relate to each
+//! other, but have no way to load classes themselves. They ask a
+//! `ClassHierarchy` instead, which may be backed by class files in memory, a
+//! class path, or hand-written stubs.
+use std::collections::HashMap;
+
+use classfile::ClassFile;
+
+use super::{Error, Result};
+
+pub trait ClassHierarchy {
+    /// The direct superclass of `class`, or `None` for `java/lang/Object`.
+    /// Interfaces have `java/lang/Object` as their superclass.
+    ///
+    /// Fails with `Error::UnknownClass` if the class cannot be found.
+    fn super_class(&self, class: &str) -> Result<Option<String>>;
+
+    /// Returns `true` if `class` is an interface.
+    fn is_interface(&self, class: &str) -> Result<bool>;
+
+    /// The access flags of the field or method named `name` with the given
+    /// descriptor declared by `class` itself, or `None` if it declares no such
+    /// member.
+    fn member_access(&self, class: &str, name: &str, descriptor: &str) -> Result<Option<u16>>;
+
+    /// Returns `true` if `class` is `superclass` or one of its subclasses.
+    fn is_subclass(&self, class: &str, superclass: &str) -> Result<bool> {
+        let mut current = Some(class.to_string());
+        while let Some(name) = current {
+            if name == superclass {
+                return Ok(true);
+            }
+            current = try!(self.super_class(&name));
+        }
+        Ok(false)
+    }
+
+    /// The superclasses of `class`, starting with its direct superclass and
+    /// ending with `java/lang/Object`.
+    fn super_classes(&self, class: &str) -> Result<Vec<String>> {
+        let mut supers = vec![];
+        let mut current = try!(self.super_class(class));
+        while let Some(name) = current {
+            current = try!(self.super_class(&name));
+            supers.push(name);
+        }
+        Ok(supers)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ClassEntry {
+    super_class: Option<String>,
+    is_interface: bool,
+    members: HashMap<(String, String), u16>,
+}
+
+/// A `ClassHierarchy` over a fixed set of classes.
+///
+/// Classes are added either from their class files, or declared by name for
+/// library classes whose class files are not at hand.
+#[derive(Clone, Debug, Default)]
+pub struct ClassSet {
+    classes: HashMap<String, ClassEntry>,
+}
+
+impl ClassSet {
+    pub fn new() -> ClassSet {
+        ClassSet { classes: HashMap::new() }
+    }
+
+    /// Adds `class`, including its fields and methods.
+    pub fn add(&mut self, class: &ClassFile) {
+        let cp = &class.constants;
+        let mut members = HashMap::new();
+        for field in &class.fields {
+            let key = (cp[field.name_index].as_utf8().clone(),
+                       cp[field.descriptor_index].as_utf8().clone());
+            members.insert(key, field.access_flags.bits());
+        }
+        for method in &class.methods {
+            let key = (cp[method.name_index].as_utf8().clone(),
+                       cp[method.descriptor_index].as_utf8().clone());
+            members.insert(key, method.access_flags.bits());
+        }
+        let super_class = match class.super_class_name() {
+            Some(name) => Some(name.clone()),
+            None if class.access_flags.is_interface() => Some("java/lang/Object".to_string()),
+            None => None,
+        };
+        self.classes.insert(class.this_class_name().clone(),
+                            ClassEntry {
+                                super_class: super_class,
+                                is_interface: class.access_flags.is_interface(),
+                                members: members,
+                            });
+    }
+
+    /// Declares a class that has no members of interest.
+    pub fn declare(&mut self, class: &str, super_class: Option<&str>, is_interface: bool) {
+        self.classes.insert(class.to_string(),
+                            ClassEntry {
+                                super_class: super_class.map(|name| name.to_string()),
+                                is_interface: is_interface,
+                                members: HashMap::new(),
+                            });
+    }
+
+    /// Declares a member of a class added earlier.
+    ///
+    /// # Panics
+    /// If `class` has not been added or declared.
+    pub fn declare_member(&mut self, class: &str, name: &str, descriptor: &str, access_flags: u16) {
+        let entry = self.classes.get_mut(class).expect("class must be declared first");
+        entry.members.insert((name.to_string(), descriptor.to_string()), access_flags);
+    }
+
+    fn entry(&self, class: &str) -> Result<&ClassEntry> {
+        self.classes.get(class).ok_or_else(|| Error::UnknownClass(class.to_string()))
+    }
+}
+
+impl ClassHierarchy for ClassSet {
+    fn super_class(&self, class: &str) -> Result<Option<String>> {
+        Ok(try!(self.entry(class)).super_class.clone())
+    }
+
+    fn is_interface(&self, class: &str) -> Result<bool> {
+        Ok(try!(self.entry(class)).is_interface)
+    }
+
+    fn member_access(&self, class: &str, name: &str, descriptor: &str) -> Result<Option<u16>> {
+        let key = (name.to_string(), descriptor.to_string());
+        Ok(try!(self.entry(class)).members.get(&key).cloned())
+    }
+}
@@ -0,0 +1,699 @@
+//! An intermediate representation of method bodies in static single
+//! assignment form.
+//!
+//! A `Function` is a graph of `Block`s. Each block starts with its phi nodes,
+//! runs a list of instructions and ends in a `Terminator`, optionally with
+//! exception edges to handler blocks. Values are numbered and typed; every
+//! value is defined exactly once, by a parameter, a phi node or an
+//! instruction, so the operand stack and local variables of the bytecode are
+//! gone. Constant pool references are resolved into symbolic operands.
+//!
+//! The `Display` implementation of `Function` prints the whole body, one
+//! instruction per line.
+use std::fmt;
+
+use classfile::{FieldType, MethodDescriptor};
+
+/// A value defined by a parameter, phi node or instruction, printed as `vN`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Value(pub usize);
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// The type of a value. `boolean`, `byte`, `char` and `short` values are
+/// widened to `Int`, as on the operand stack.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Type {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+}
+
+impl Type {
+    /// The type of a value of the given field type.
+    pub fn of(field_type: &FieldType) -> Type {
+        match *field_type {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short |
+            FieldType::Int => Type::Int,
+            FieldType::Float => Type::Float,
+            FieldType::Long => Type::Long,
+            FieldType::Double => Type::Double,
+            FieldType::Object(_) | FieldType::Array(_) => Type::Reference,
+        }
+    }
+
+    /// The number of stack or local variable slots a value of this type
+    /// takes in bytecode.
+    pub fn size(&self) -> usize {
+        match *self {
+            Type::Long | Type::Double => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Type::Int => "int",
+            Type::Long => "long",
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::Reference => "ref",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A field, printed as `owner.name:descriptor`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FieldRef {
+    pub owner: String,
+    pub name: String,
+    pub descriptor: FieldType,
+}
+
+impl fmt::Display for FieldRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.owner, self.name, self.descriptor)
+    }
+}
+
+/// A method, printed as `owner.name descriptor` without the space.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MethodRef {
+    pub owner: String,
+    pub name: String,
+    pub descriptor: MethodDescriptor,
+    /// Whether the reference is an `InterfaceMethodref`.
+    pub interface: bool,
+}
+
+impl fmt::Display for MethodRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}{}", self.owner, self.name, self.descriptor)
+    }
+}
+
+/// A constant value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    /// A `Class` object, given by its internal name or array descriptor.
+    Class(String),
+    MethodType(MethodDescriptor),
+    /// A method handle of the given reference kind (JVMS 5.4.3.5) to a field
+    /// or method, whose descriptor is kept as written.
+    MethodHandle {
+        kind: u8,
+        owner: String,
+        name: String,
+        descriptor: String,
+    },
+}
+
+impl Literal {
+    pub fn value_type(&self) -> Type {
+        match *self {
+            Literal::Int(_) => Type::Int,
+            Literal::Long(_) => Type::Long,
+            Literal::Float(_) => Type::Float,
+            Literal::Double(_) => Type::Double,
+            _ => Type::Reference,
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Literal::Null => write!(f, "null"),
+            Literal::Int(value) => write!(f, "{}", value),
+            Literal::Long(value) => write!(f, "{}L", value),
+            Literal::Float(value) => write!(f, "{:?}F", value),
+            Literal::Double(value) => write!(f, "{:?}D", value),
+            Literal::String(ref value) => write!(f, "{:?}", value),
+            Literal::Class(ref name) => write!(f, "class {}", name),
+            Literal::MethodType(ref descriptor) => write!(f, "methodtype {}", descriptor),
+            Literal::MethodHandle { kind, ref owner, ref name, ref descriptor } => {
+                write!(f, "methodhandle {} {}.{}:{}", kind, owner, name, descriptor)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    /// A shift, whose right operand is always an `int`.
+    Shl,
+    Shr,
+    UShr,
+    And,
+    Or,
+    Xor,
+}
+
+impl BinaryOp {
+    pub fn is_shift(&self) -> bool {
+        match *self {
+            BinaryOp::Shl | BinaryOp::Shr | BinaryOp::UShr => true,
+            _ => false,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+            BinaryOp::Rem => "rem",
+            BinaryOp::Shl => "shl",
+            BinaryOp::Shr => "shr",
+            BinaryOp::UShr => "ushr",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+            BinaryOp::Xor => "xor",
+        }
+    }
+}
+
+/// A three-way comparison, giving -1, 0 or 1. The `float` and `double`
+/// comparisons differ in the result for NaN operands.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Comparison {
+    Lcmp,
+    Fcmpl,
+    Fcmpg,
+    Dcmpl,
+    Dcmpg,
+}
+
+impl Comparison {
+    /// The type of both operands.
+    pub fn operand_type(&self) -> Type {
+        match *self {
+            Comparison::Lcmp => Type::Long,
+            Comparison::Fcmpl | Comparison::Fcmpg => Type::Float,
+            Comparison::Dcmpl | Comparison::Dcmpg => Type::Double,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Comparison::Lcmp => "lcmp",
+            Comparison::Fcmpl => "fcmpl",
+            Comparison::Fcmpg => "fcmpg",
+            Comparison::Dcmpl => "dcmpl",
+            Comparison::Dcmpg => "dcmpg",
+        }
+    }
+}
+
+/// The condition of a conditional branch.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Condition {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match *self {
+            Condition::Eq => "==",
+            Condition::Ne => "!=",
+            Condition::Lt => "<",
+            Condition::Ge => ">=",
+            Condition::Gt => ">",
+            Condition::Le => "<=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum InvokeKind {
+    Virtual,
+    Special,
+    Static,
+    Interface,
+}
+
+impl InvokeKind {
+    fn name(&self) -> &'static str {
+        match *self {
+            InvokeKind::Virtual => "invokevirtual",
+            InvokeKind::Special => "invokespecial",
+            InvokeKind::Static => "invokestatic",
+            InvokeKind::Interface => "invokeinterface",
+        }
+    }
+}
+
+/// The kind of array accessed by an array load or store, as given by the
+/// instruction. `Byte` covers both `byte` and `boolean` arrays.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ArrayKind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+    Byte,
+    Char,
+    Short,
+}
+
+impl ArrayKind {
+    /// The type of the elements once loaded.
+    pub fn element_type(&self) -> Type {
+        match *self {
+            ArrayKind::Long => Type::Long,
+            ArrayKind::Float => Type::Float,
+            ArrayKind::Double => Type::Double,
+            ArrayKind::Reference => Type::Reference,
+            _ => Type::Int,
+        }
+    }
+
+    fn prefix(&self) -> &'static str {
+        match *self {
+            ArrayKind::Int => "i",
+            ArrayKind::Long => "l",
+            ArrayKind::Float => "f",
+            ArrayKind::Double => "d",
+            ArrayKind::Reference => "a",
+            ArrayKind::Byte => "b",
+            ArrayKind::Char => "c",
+            ArrayKind::Short => "s",
+        }
+    }
+}
+
+/// The operation performed by an instruction, with its operands.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    /// The exception caught by a handler. Only valid as the first
+    /// instruction of a handler block.
+    CaughtException,
+    Literal(Literal),
+    Binary(BinaryOp, Value, Value),
+    Negate(Value),
+    /// A conversion to the given primitive type, which may be `byte`, `char`
+    /// or `short` for the narrowing `int` conversions.
+    Convert(Value, FieldType),
+    Compare(Comparison, Value, Value),
+    GetStatic(FieldRef),
+    PutStatic(FieldRef, Value),
+    /// A read of a field of the given object.
+    GetField(FieldRef, Value),
+    /// A write of the second value to a field of the first.
+    PutField(FieldRef, Value, Value),
+    /// A call with the given arguments, starting with the receiver for all
+    /// but static calls.
+    Invoke(InvokeKind, MethodRef, Vec<Value>),
+    /// A dynamic call site, with the index of its bootstrap method in the
+    /// `BootstrapMethods` attribute.
+    InvokeDynamic {
+        bootstrap: u16,
+        name: String,
+        descriptor: MethodDescriptor,
+        args: Vec<Value>,
+    },
+    New(String),
+    /// A new one-dimensional array with the given component type and length.
+    NewArray(FieldType, Value),
+    /// A new array of the given array type, with the lengths of its first
+    /// dimensions.
+    MultiNewArray(FieldType, Vec<Value>),
+    ArrayLength(Value),
+    /// A load from an array at an index.
+    ArrayLoad(ArrayKind, Value, Value),
+    /// A store to an array at an index of the third value.
+    ArrayStore(ArrayKind, Value, Value, Value),
+    /// A cast to the class or array type with the given internal name.
+    CheckCast(String, Value),
+    InstanceOf(String, Value),
+    MonitorEnter(Value),
+    MonitorExit(Value),
+}
+
+impl Op {
+    /// The values the operation reads, in order.
+    pub fn operands(&self) -> Vec<Value> {
+        match *self {
+            Op::CaughtException | Op::Literal(_) | Op::GetStatic(_) | Op::New(_) => vec![],
+            Op::Negate(value) |
+            Op::Convert(value, _) |
+            Op::PutStatic(_, value) |
+            Op::GetField(_, value) |
+            Op::NewArray(_, value) |
+            Op::ArrayLength(value) |
+            Op::CheckCast(_, value) |
+            Op::InstanceOf(_, value) |
+            Op::MonitorEnter(value) |
+            Op::MonitorExit(value) => vec![value],
+            Op::Binary(_, left, right) |
+            Op::Compare(_, left, right) |
+            Op::PutField(_, left, right) |
+            Op::ArrayLoad(_, left, right) => vec![left, right],
+            Op::ArrayStore(_, array, index, value) => vec![array, index, value],
+            Op::Invoke(_, _, ref args) |
+            Op::InvokeDynamic { ref args, .. } |
+            Op::MultiNewArray(_, ref args) => args.clone(),
+        }
+    }
+
+    /// Replaces every operand `value` with `f(value)`.
+    pub fn map_operands<F: FnMut(Value) -> Value>(&mut self, mut f: F) {
+        match *self {
+            Op::CaughtException | Op::Literal(_) | Op::GetStatic(_) | Op::New(_) => {}
+            Op::Negate(ref mut value) |
+            Op::Convert(ref mut value, _) |
+            Op::PutStatic(_, ref mut value) |
+            Op::GetField(_, ref mut value) |
+            Op::NewArray(_, ref mut value) |
+            Op::ArrayLength(ref mut value) |
+            Op::CheckCast(_, ref mut value) |
+            Op::InstanceOf(_, ref mut value) |
+            Op::MonitorEnter(ref mut value) |
+            Op::MonitorExit(ref mut value) => *value = f(*value),
+            Op::Binary(_, ref mut left, ref mut right) |
+            Op::Compare(_, ref mut left, ref mut right) |
+            Op::PutField(_, ref mut left, ref mut right) |
+            Op::ArrayLoad(_, ref mut left, ref mut right) => {
+                *left = f(*left);
+                *right = f(*right);
+            }
+            Op::ArrayStore(_, ref mut array, ref mut index, ref mut value) => {
+                *array = f(*array);
+                *index = f(*index);
+                *value = f(*value);
+            }
+            Op::Invoke(_, _, ref mut args) |
+            Op::InvokeDynamic { ref mut args, .. } |
+            Op::MultiNewArray(_, ref mut args) => {
+                for arg in args.iter_mut() {
+                    *arg = f(*arg);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Op::CaughtException => write!(f, "caught"),
+            Op::Literal(ref literal) => write!(f, "const {}", literal),
+            Op::Binary(op, left, right) => write!(f, "{} {}, {}", op.name(), left, right),
+            Op::Negate(value) => write!(f, "neg {}", value),
+            Op::Convert(value, ref to) => write!(f, "convert {} to {}", value, to.java_name()),
+            Op::Compare(op, left, right) => write!(f, "{} {}, {}", op.name(), left, right),
+            Op::GetStatic(ref field) => write!(f, "getstatic {}", field),
+            Op::PutStatic(ref field, value) => write!(f, "putstatic {}, {}", field, value),
+            Op::GetField(ref field, object) => write!(f, "getfield {}, {}", field, object),
+            Op::PutField(ref field, object, value) => {
+                write!(f, "putfield {}, {}, {}", field, object, value)
+            }
+            Op::Invoke(kind, ref method, ref args) => {
+                try!(write!(f, "{} {}", kind.name(), method));
+                write_values(f, args)
+            }
+            Op::InvokeDynamic { bootstrap, ref name, ref descriptor, ref args } => {
+                try!(write!(f, "invokedynamic #{} {}{}", bootstrap, name, descriptor));
+                write_values(f, args)
+            }
+            Op::New(ref class) => write!(f, "new {}", class),
+            Op::NewArray(ref component, length) => write!(f, "newarray {}, {}", component, length),
+            Op::MultiNewArray(ref array, ref lengths) => {
+                try!(write!(f, "multianewarray {}", array));
+                write_values(f, lengths)
+            }
+            Op::ArrayLength(array) => write!(f, "arraylength {}", array),
+            Op::ArrayLoad(kind, array, index) => {
+                write!(f, "{}aload {}, {}", kind.prefix(), array, index)
+            }
+            Op::ArrayStore(kind, array, index, value) => {
+                write!(f, "{}astore {}, {}, {}", kind.prefix(), array, index, value)
+            }
+            Op::CheckCast(ref class, value) => write!(f, "checkcast {}, {}", class, value),
+            Op::InstanceOf(ref class, value) => write!(f, "instanceof {}, {}", class, value),
+            Op::MonitorEnter(value) => write!(f, "monitorenter {}", value),
+            Op::MonitorExit(value) => write!(f, "monitorexit {}", value),
+        }
+    }
+}
+
+/// Writes `, v1, v2...`.
+fn write_values(f: &mut fmt::Formatter, values: &[Value]) -> fmt::Result {
+    for value in values {
+        try!(write!(f, ", {}", value));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    /// The pc of the bytecode instruction this was lifted from.
+    pub pc: usize,
+    /// The value defined, or `None` if the operation produces none.
+    pub result: Option<Value>,
+    pub op: Op,
+}
+
+/// A phi node, choosing a value by the predecessor control came from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Phi {
+    pub result: Value,
+    /// The value for each predecessor block, in order of their ids.
+    pub operands: Vec<(usize, Value)>,
+}
+
+/// How control leaves a block normally.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Terminator {
+    Goto(usize),
+    /// A branch to `then` if `left` compares to `right` as the condition
+    /// says, and to `otherwise` if not. A missing `right` is zero for an
+    /// `int` and `null` for a reference.
+    If {
+        condition: Condition,
+        left: Value,
+        right: Option<Value>,
+        then: usize,
+        otherwise: usize,
+    },
+    Switch {
+        value: Value,
+        cases: Vec<(i32, usize)>,
+        default: usize,
+    },
+    Return(Option<Value>),
+    Throw(Value),
+}
+
+impl Terminator {
+    /// The blocks control may continue in, in order and with duplicates.
+    pub fn successors(&self) -> Vec<usize> {
+        match *self {
+            Terminator::Goto(target) => vec![target],
+            Terminator::If { then, otherwise, .. } => vec![then, otherwise],
+            Terminator::Switch { ref cases, default, .. } => {
+                cases.iter().map(|&(_, target)| target).chain(Some(default)).collect()
+            }
+            Terminator::Return(_) |
+            Terminator::Throw(_) => vec![],
+        }
+    }
+
+    /// The values the terminator reads, in order.
+    pub fn operands(&self) -> Vec<Value> {
+        match *self {
+            Terminator::Goto(_) |
+            Terminator::Return(None) => vec![],
+            Terminator::If { left, right, .. } => Some(left).into_iter().chain(right).collect(),
+            Terminator::Switch { value, .. } |
+            Terminator::Return(Some(value)) |
+            Terminator::Throw(value) => vec![value],
+        }
+    }
+
+    /// Replaces every operand `value` with `f(value)`.
+    pub fn map_operands<F: FnMut(Value) -> Value>(&mut self, mut f: F) {
+        match *self {
+            Terminator::Goto(_) |
+            Terminator::Return(None) => {}
+            Terminator::If { ref mut left, ref mut right, .. } => {
+                *left = f(*left);
+                if let Some(ref mut right) = *right {
+                    *right = f(*right);
+                }
+            }
+            Terminator::Switch { ref mut value, .. } |
+            Terminator::Return(Some(ref mut value)) |
+            Terminator::Throw(ref mut value) => *value = f(*value),
+        }
+    }
+}
+
+/// An exception edge to a handler block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Handler {
+    /// The internal name of the class caught, or `None` for any exception.
+    pub catch_type: Option<String>,
+    pub target: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub id: usize,
+    /// The pc of the first bytecode instruction of the block.
+    pub pc: usize,
+    pub phis: Vec<Phi>,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+    /// The handlers an exception thrown in the block may go to, in the order
+    /// they are tried.
+    pub handlers: Vec<Handler>,
+}
+
+impl Block {
+    /// The blocks control may continue in, normally or through a handler,
+    /// without duplicates.
+    pub fn successors(&self) -> Vec<usize> {
+        let mut successors = vec![];
+        let targets = self.handlers.iter().map(|handler| handler.target);
+        for target in self.terminator.successors().into_iter().chain(targets) {
+            if !successors.contains(&target) {
+                successors.push(target);
+            }
+        }
+        successors
+    }
+}
+
+/// The body of a method, in SSA form. Block 0 is the entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    /// The internal name of the class declaring the method.
+    pub class: String,
+    pub name: String,
+    pub descriptor: MethodDescriptor,
+    pub is_static: bool,
+    /// The values of the arguments on entry, starting with `this` for
+    /// instance methods.
+    pub params: Vec<Value>,
+    /// The blocks, indexed by their ids. Empty for methods without code.
+    pub blocks: Vec<Block>,
+    /// The type of each value, indexed by its number.
+    pub types: Vec<Type>,
+}
+
+impl Function {
+    pub fn block(&self, id: usize) -> &Block {
+        &self.blocks[id]
+    }
+
+    pub fn type_of(&self, value: Value) -> Type {
+        self.types[value.0]
+    }
+
+    /// The blocks with an edge to block `id`, in order of their ids.
+    pub fn predecessors(&self, id: usize) -> Vec<usize> {
+        self.blocks
+            .iter()
+            .filter(|block| block.successors().contains(&id))
+            .map(|block| block.id)
+            .collect()
+    }
+
+    /// The number of values defined.
+    pub fn value_count(&self) -> usize {
+        self.types.len()
+    }
+
+    fn write_terminator(&self, f: &mut fmt::Formatter, terminator: &Terminator) -> fmt::Result {
+        match *terminator {
+            Terminator::Goto(target) => write!(f, "goto b{}", target),
+            Terminator::If { condition, left, right, then, otherwise } => {
+                try!(write!(f, "if {} {} ", left, condition));
+                match right {
+                    Some(right) => try!(write!(f, "{}", right)),
+                    None if self.type_of(left) == Type::Reference => try!(write!(f, "null")),
+                    None => try!(write!(f, "0")),
+                }
+                write!(f, " goto b{} else b{}", then, otherwise)
+            }
+            Terminator::Switch { value, ref cases, default } => {
+                try!(write!(f, "switch {} [", value));
+                for (i, &(key, target)) in cases.iter().enumerate() {
+                    try!(write!(f, "{}{}: b{}", if i == 0 { "" } else { ", " }, key, target));
+                }
+                write!(f, "] default b{}", default)
+            }
+            Terminator::Return(None) => write!(f, "return"),
+            Terminator::Return(Some(value)) => write!(f, "return {}", value),
+            Terminator::Throw(value) => write!(f, "throw {}", value),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_static {
+            try!(write!(f, "static "));
+        }
+        try!(write!(f, "{}.{}{}(", self.class, self.name, self.descriptor));
+        for (i, &param) in self.params.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            try!(write!(f, "{}{}: {}", separator, param, self.type_of(param)));
+        }
+        try!(writeln!(f, ") {{"));
+        for block in self.blocks.iter() {
+            try!(writeln!(f, "b{} (pc {}):", block.id, block.pc));
+            for phi in block.phis.iter() {
+                try!(write!(f, "    {}: {} = phi ", phi.result, self.type_of(phi.result)));
+                for (i, &(pred, value)) in phi.operands.iter().enumerate() {
+                    try!(write!(f, "{}b{}: {}", if i == 0 { "" } else { ", " }, pred, value));
+                }
+                try!(writeln!(f, ""));
+            }
+            for instruction in block.instructions.iter() {
+                try!(write!(f, "    "));
+                if let Some(result) = instruction.result {
+                    try!(write!(f, "{}: {} = ", result, self.type_of(result)));
+                }
+                try!(writeln!(f, "{}", instruction.op));
+            }
+            try!(write!(f, "    "));
+            try!(self.write_terminator(f, &block.terminator));
+            try!(writeln!(f, ""));
+            for handler in block.handlers.iter() {
+                let catch_type = handler.catch_type.as_ref().map_or("any", |name| name.as_str());
+                try!(writeln!(f, "    catch {} b{}", catch_type, handler.target));
+            }
+        }
+        write!(f, "}}")
+    }
+}
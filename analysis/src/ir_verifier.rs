@@ -0,0 +1,386 @@
+//! A verifier for functions in the SSA form of the `ir` module.
+//!
+//! The verifier checks that the blocks form a graph with the entry block at
+//! its root, that every value is defined once and dominates each of its
+//! uses, that the phi nodes of each block have one operand per predecessor,
+//! that handler blocks start by taking the caught exception, and that every
+//! operation is applied to values of the types it expects.
+use std::collections::BTreeSet;
+use std::fmt;
+
+use classfile::FieldType;
+
+use dominators::DominatorTree;
+use ir::*;
+
+/// A problem found by `verify`, in a block or in the function as a whole.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Violation {
+    pub block: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.block {
+            Some(block) => write!(f, "b{}: {}", block, self.message),
+            None => write!(f, "function: {}", self.message),
+        }
+    }
+}
+
+/// Checks that `function` is well formed, returning every problem found. If
+/// the blocks do not form a graph, the other checks are skipped.
+pub fn verify(function: &Function) -> Vec<Violation> {
+    let mut verifier = Verifier {
+        function: function,
+        violations: vec![],
+        definitions: vec![None; function.types.len()],
+    };
+    if verifier.check_graph() {
+        verifier.check_definitions();
+        verifier.check_blocks();
+    }
+    verifier.violations
+}
+
+/// Where a value is defined.
+#[derive(Clone, Copy, Debug)]
+enum Definition {
+    Param,
+    Phi(usize),
+    /// The instruction at the given position in a block.
+    Instruction(usize, usize),
+}
+
+struct Verifier<'a> {
+    function: &'a Function,
+    violations: Vec<Violation>,
+    definitions: Vec<Option<Definition>>,
+}
+
+impl<'a> Verifier<'a> {
+    fn report(&mut self, block: Option<usize>, message: String) {
+        self.violations.push(Violation {
+            block: block,
+            message: message,
+        });
+    }
+
+    /// Checks the ids of the blocks and the targets of their edges.
+    fn check_graph(&mut self) -> bool {
+        let function = self.function;
+        let count = function.blocks.len();
+        let mut valid = true;
+        for (index, block) in function.blocks.iter().enumerate() {
+            if block.id != index {
+                self.report(Some(index), format!("block has id {}", block.id));
+                valid = false;
+            }
+            for target in block.successors() {
+                if target >= count {
+                    self.report(Some(index), format!("edge to missing block b{}", target));
+                    valid = false;
+                }
+            }
+        }
+        valid
+    }
+
+    /// Records where each value is defined, and checks that it is defined
+    /// only once and that the parameters match the descriptor.
+    fn check_definitions(&mut self) {
+        let function = self.function;
+        let mut expected = vec![];
+        if !function.is_static {
+            expected.push(Type::Reference);
+        }
+        expected.extend(function.descriptor.params.iter().map(Type::of));
+        let types: Vec<Option<Type>> =
+            function.params.iter().map(|&param| self.type_of(param)).collect();
+        if types != expected.into_iter().map(Some).collect::<Vec<_>>() {
+            self.report(None, "parameters do not match the descriptor".to_string());
+        }
+
+        for &param in function.params.iter() {
+            self.define(None, param, Definition::Param);
+        }
+        for block in function.blocks.iter() {
+            for phi in block.phis.iter() {
+                self.define(Some(block.id), phi.result, Definition::Phi(block.id));
+            }
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                if let Some(result) = instruction.result {
+                    self.define(Some(block.id), result, Definition::Instruction(block.id, index));
+                }
+            }
+        }
+    }
+
+    fn define(&mut self, block: Option<usize>, value: Value, definition: Definition) {
+        match self.definitions.get(value.0).cloned() {
+            None => self.report(block, format!("{} has no type", value)),
+            Some(Some(_)) => self.report(block, format!("{} is defined more than once", value)),
+            Some(None) => self.definitions[value.0] = Some(definition),
+        }
+    }
+
+    fn type_of(&self, value: Value) -> Option<Type> {
+        self.function.types.get(value.0).cloned()
+    }
+
+    fn check_blocks(&mut self) {
+        let function = self.function;
+        let successors: Vec<Vec<usize>> = function.blocks.iter().map(Block::successors).collect();
+        let dominators = DominatorTree::from_successors(&successors);
+        let mut predecessors = vec![BTreeSet::new(); function.blocks.len()];
+        let mut normal_targets = BTreeSet::new();
+        let mut handler_targets = BTreeSet::new();
+        for block in function.blocks.iter() {
+            for &target in successors[block.id].iter() {
+                predecessors[target].insert(block.id);
+            }
+            normal_targets.extend(block.terminator.successors());
+            handler_targets.extend(block.handlers.iter().map(|handler| handler.target));
+        }
+        if !predecessors.is_empty() && !predecessors[0].is_empty() {
+            self.report(Some(0), "entry block has predecessors".to_string());
+        }
+
+        for block in function.blocks.iter() {
+            let id = Some(block.id);
+            if !dominators.contains(block.id) {
+                self.report(id, "block is unreachable".to_string());
+                continue;
+            }
+            let is_handler = handler_targets.contains(&block.id);
+            if is_handler && normal_targets.contains(&block.id) {
+                self.report(id, "handler block is also entered normally".to_string());
+            }
+
+            let preds: Vec<usize> = predecessors[block.id].iter().cloned().collect();
+            for phi in block.phis.iter() {
+                let operand_preds = phi.operands.iter().map(|&(pred, _)| pred);
+                if !operand_preds.eq(preds.iter().cloned()) {
+                    self.report(id, format!("{} needs one operand per predecessor", phi.result));
+                }
+                for &(pred, value) in phi.operands.iter() {
+                    if pred < function.blocks.len() {
+                        self.check_use(&dominators, pred, None, value);
+                    }
+                    self.check_type(block.id, value, self.type_of(phi.result));
+                }
+            }
+
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                if let Op::CaughtException = instruction.op {
+                    if !is_handler || index != 0 {
+                        let message = format!("pc {}: caught exception outside the start of a \
+                                               handler",
+                                              instruction.pc);
+                        self.report(id, message);
+                    }
+                } else if is_handler && index == 0 {
+                    self.report(id, "handler block does not start with caught".to_string());
+                }
+                for value in instruction.op.operands() {
+                    self.check_use(&dominators, block.id, Some(index), value);
+                }
+                self.check_instruction(block.id, instruction);
+            }
+            if is_handler && block.instructions.is_empty() {
+                self.report(id, "handler block does not start with caught".to_string());
+            }
+
+            for value in block.terminator.operands() {
+                self.check_use(&dominators, block.id, None, value);
+            }
+            self.check_terminator(block.id, &block.terminator);
+        }
+    }
+
+    /// Checks that `value` is defined where it is used: in block `id` before
+    /// the instruction at `position`, or before its end for `None`.
+    fn check_use(&mut self,
+                 dominators: &DominatorTree,
+                 id: usize,
+                 position: Option<usize>,
+                 value: Value) {
+        let available = match self.definitions.get(value.0).cloned() {
+            None | Some(None) => {
+                self.report(Some(id), format!("{} is not defined", value));
+                return;
+            }
+            Some(Some(Definition::Param)) => true,
+            Some(Some(Definition::Phi(block))) => dominators.dominates(block, id),
+            Some(Some(Definition::Instruction(block, index))) => {
+                if block == id {
+                    position.map_or(true, |position| index < position)
+                } else {
+                    dominators.strictly_dominates(block, id)
+                }
+            }
+        };
+        if !available {
+            self.report(Some(id), format!("{} does not dominate its use", value));
+        }
+    }
+
+    /// Checks that `value` has type `expected`, if known.
+    fn check_type(&mut self, id: usize, value: Value, expected: Option<Type>) {
+        if let (Some(ty), Some(expected)) = (self.type_of(value), expected) {
+            if ty != expected {
+                self.report(Some(id), format!("{} is {}, expected {}", value, ty, expected));
+            }
+        }
+    }
+
+    fn check_instruction(&mut self, id: usize, instruction: &Instruction) {
+        let pc = instruction.pc;
+        let operand_type = |value: Value| self.type_of(value);
+        let numeric = |ty: Option<Type>| ty != Some(Type::Reference);
+        let integral = |ty: Option<Type>| ty == Some(Type::Int) || ty == Some(Type::Long);
+        let (expected, result): (Vec<Type>, Option<Type>) = match instruction.op {
+            Op::CaughtException => (vec![], Some(Type::Reference)),
+            Op::Literal(ref literal) => (vec![], Some(literal.value_type())),
+            Op::Binary(op, left, _) => {
+                let ty = operand_type(left);
+                let valid = if op.is_shift() || op == BinaryOp::And || op == BinaryOp::Or ||
+                               op == BinaryOp::Xor {
+                    integral(ty)
+                } else {
+                    numeric(ty)
+                };
+                if !valid {
+                    self.report(Some(id), format!("pc {}: illegal operand type", pc));
+                }
+                let ty = ty.unwrap_or(Type::Int);
+                let right = if op.is_shift() { Type::Int } else { ty };
+                (vec![ty, right], Some(ty))
+            }
+            Op::Negate(value) => {
+                let ty = operand_type(value);
+                if !numeric(ty) {
+                    self.report(Some(id), format!("pc {}: illegal operand type", pc));
+                }
+                (vec![ty.unwrap_or(Type::Int)], ty)
+            }
+            Op::Convert(value, ref to) => {
+                let ty = operand_type(value);
+                let valid = match *to {
+                    FieldType::Byte | FieldType::Char | FieldType::Short => ty == Some(Type::Int),
+                    FieldType::Boolean | FieldType::Object(_) | FieldType::Array(_) => false,
+                    _ => numeric(ty),
+                };
+                if !valid {
+                    self.report(Some(id), format!("pc {}: illegal conversion", pc));
+                }
+                (vec![ty.unwrap_or(Type::Int)], Some(Type::of(to)))
+            }
+            Op::Compare(comparison, _, _) => {
+                let ty = comparison.operand_type();
+                (vec![ty, ty], Some(Type::Int))
+            }
+            Op::GetStatic(ref field) => (vec![], Some(Type::of(&field.descriptor))),
+            Op::PutStatic(ref field, _) => (vec![Type::of(&field.descriptor)], None),
+            Op::GetField(ref field, _) => {
+                (vec![Type::Reference], Some(Type::of(&field.descriptor)))
+            }
+            Op::PutField(ref field, _, _) => {
+                (vec![Type::Reference, Type::of(&field.descriptor)], None)
+            }
+            Op::Invoke(kind, ref method, _) => {
+                let mut expected = vec![];
+                if kind != InvokeKind::Static {
+                    expected.push(Type::Reference);
+                }
+                expected.extend(method.descriptor.params.iter().map(Type::of));
+                (expected, method.descriptor.ret.as_ref().map(Type::of))
+            }
+            Op::InvokeDynamic { ref descriptor, .. } => {
+                (descriptor.params.iter().map(Type::of).collect(),
+                 descriptor.ret.as_ref().map(Type::of))
+            }
+            Op::New(_) => (vec![], Some(Type::Reference)),
+            Op::NewArray(_, _) => (vec![Type::Int], Some(Type::Reference)),
+            Op::MultiNewArray(ref array, ref lengths) => {
+                if lengths.is_empty() || dimensions(array) < lengths.len() {
+                    self.report(Some(id), format!("pc {}: illegal dimensions", pc));
+                }
+                (vec![Type::Int; lengths.len()], Some(Type::Reference))
+            }
+            Op::ArrayLength(_) => (vec![Type::Reference], Some(Type::Int)),
+            Op::ArrayLoad(kind, _, _) => {
+                (vec![Type::Reference, Type::Int], Some(kind.element_type()))
+            }
+            Op::ArrayStore(kind, _, _, _) => {
+                (vec![Type::Reference, Type::Int, kind.element_type()], None)
+            }
+            Op::CheckCast(_, _) => (vec![Type::Reference], Some(Type::Reference)),
+            Op::InstanceOf(_, _) => (vec![Type::Reference], Some(Type::Int)),
+            Op::MonitorEnter(_) |
+            Op::MonitorExit(_) => (vec![Type::Reference], None),
+        };
+
+        let operands = instruction.op.operands();
+        if operands.len() != expected.len() {
+            let message = format!("pc {}: {} operands, expected {}",
+                                  pc,
+                                  operands.len(),
+                                  expected.len());
+            self.report(Some(id), message);
+        } else {
+            for (&value, &ty) in operands.iter().zip(expected.iter()) {
+                self.check_type(id, value, Some(ty));
+            }
+        }
+        match (instruction.result, result) {
+            (Some(value), Some(_)) => self.check_type(id, value, result),
+            (Some(value), None) => {
+                self.report(Some(id), format!("pc {}: {} is defined by no value", pc, value))
+            }
+            (None, Some(_)) => self.report(Some(id), format!("pc {}: result is dropped", pc)),
+            (None, None) => {}
+        }
+    }
+
+    fn check_terminator(&mut self, id: usize, terminator: &Terminator) {
+        match *terminator {
+            Terminator::Goto(_) => {}
+            Terminator::If { condition, left, right, .. } => {
+                let ty = self.type_of(left);
+                let valid = match ty {
+                    Some(Type::Int) => true,
+                    Some(Type::Reference) => {
+                        condition == Condition::Eq || condition == Condition::Ne
+                    }
+                    _ => false,
+                };
+                if !valid {
+                    self.report(Some(id), format!("illegal comparison of {}", left));
+                }
+                if let Some(right) = right {
+                    self.check_type(id, right, ty);
+                }
+            }
+            Terminator::Switch { value, .. } => self.check_type(id, value, Some(Type::Int)),
+            Terminator::Return(value) => {
+                let expected = self.function.descriptor.ret.as_ref().map(Type::of);
+                match (value, expected) {
+                    (Some(value), Some(_)) => self.check_type(id, value, expected),
+                    (None, None) => {}
+                    _ => self.report(Some(id), "return does not match the descriptor".to_string()),
+                }
+            }
+            Terminator::Throw(value) => self.check_type(id, value, Some(Type::Reference)),
+        }
+    }
+}
+
+/// The number of dimensions of an array type.
+fn dimensions(field_type: &FieldType) -> usize {
+    match *field_type {
+        FieldType::Array(ref component) => 1 + dimensions(component),
+        _ => 0,
+    }
+}
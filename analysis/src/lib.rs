@@ -0,0 +1,25 @@
+//! Analyses of method bytecode.
+extern crate classfile;
+
+pub mod basic_interpreter;
+pub mod cfg;
+pub mod dataflow;
+pub mod dominators;
+pub mod format_checker;
+pub mod hierarchy;
+pub mod ir;
+pub mod ir_verifier;
+pub mod lifter;
+pub mod liveness;
+pub mod loops;
+pub mod optimizer;
+pub mod reaching_definitions;
+pub mod source_interpreter;
+pub mod subroutines;
+pub mod type_checker;
+pub mod type_inferencer;
+pub mod verifier;
+
+mod error;
+mod method_verifier;
+pub use self::error::*;
@@ -0,0 +1,1167 @@
+//! Lifting of method bytecode into the SSA form of the `ir` module.
+//!
+//! The blocks of the `ControlFlowGraph` become the blocks of the function,
+//! and the operand stack and locals are simulated block by block, with every
+//! bytecode instruction replaced by the operation it performs on the values
+//! they hold. Where control flow joins, a phi node is placed for each stack
+//! entry and each local that is live and has a single type on all paths.
+//! Phi nodes that turn out to choose between a value and themselves only are
+//! removed, and the values are then numbered in order of their definition.
+//!
+//! An exception edge passes the locals at the end of its block to the
+//! handler. Within a block covered by a handler, that is only right if no
+//! local is written after an instruction that may throw, so such blocks are
+//! split before each of those writes. Blocks without an instruction that may
+//! throw have no exception edges, and handlers only reachable from them are
+//! dropped along with the other unreachable code.
+//!
+//! Subroutines are not supported; `subroutines::inline_subroutines` removes
+//! them.
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use classfile::{Bytecode, ClassFile, Constant, ConstantPool, FieldType, MethodDescriptor,
+                MethodInfo};
+
+use basic_interpreter::{BasicInterpreter, BasicValue};
+use cfg::{ControlFlowGraph, EdgeKind};
+use dataflow::Analyzer;
+use ir::*;
+use liveness::Liveness;
+use super::{Error, Result};
+
+/// Lifts the code of `method`, a method of `class`, into SSA form. Methods
+/// without code give a function without blocks.
+///
+/// Fails if the method cannot be analyzed by a `BasicInterpreter`, or if it
+/// calls a subroutine.
+pub fn lift(class: &ClassFile, method: &MethodInfo) -> Result<Function> {
+    let cp = &class.constants;
+    let descriptor = try!(method.descriptor(cp));
+    let is_static = method.access_flags.is_static();
+    let mut function = Function {
+        class: class.this_class_name().clone(),
+        name: cp[method.name_index].as_utf8().clone(),
+        descriptor: descriptor,
+        is_static: is_static,
+        params: vec![],
+        blocks: vec![],
+        types: vec![],
+    };
+    if !is_static {
+        function.params.push(Value(0));
+        function.types.push(Type::Reference);
+    }
+    for param in function.descriptor.params.iter() {
+        function.params.push(Value(function.types.len()));
+        function.types.push(Type::of(param));
+    }
+    let code = match method.attrs.code() {
+        Some(code) => code,
+        None => return Ok(function),
+    };
+
+    let cfg = try!(ControlFlowGraph::new(code));
+    let frames = try!(Analyzer::new(&mut BasicInterpreter::new()).analyze(class, method));
+    let instructions = cfg.instructions();
+    for (index, &(pc, ref bytecode)) in instructions.iter().enumerate() {
+        if frames.get(index).is_none() {
+            continue;
+        }
+        match *bytecode {
+            Bytecode::jsr { .. } |
+            Bytecode::jsr_w { .. } |
+            Bytecode::ret { .. } |
+            Bytecode::wide_ret { .. } => {
+                return unsupported(pc, "subroutines must be inlined before lifting")
+            }
+            _ => {}
+        }
+    }
+
+    // Split the reachable blocks into parts, the blocks of the function.
+    let mut parts: Vec<Part> = vec![];
+    let mut part_at = vec![None; instructions.len()];
+    for block in cfg.blocks() {
+        if frames.get(block.instructions.start).is_none() {
+            continue;
+        }
+        let protected = cfg.successors(block.id).iter().any(|edge| is_exceptional(edge.kind));
+        let mut start = block.instructions.start;
+        let mut throws = false;
+        for index in block.instructions.clone() {
+            let bytecode = instructions.get(index).unwrap();
+            let writes = bytecode.local_access().map_or(false, |access| access.writes);
+            if protected && throws && writes {
+                part_at[start] = Some(parts.len());
+                parts.push(Part::new(block.id, start..index, true));
+                start = index;
+                throws = false;
+            }
+            throws |= bytecode.info().can_throw();
+        }
+        part_at[start] = Some(parts.len());
+        parts.push(Part::new(block.id, start..block.instructions.end, protected && throws));
+    }
+    if parts.is_empty() {
+        return Ok(function);
+    }
+
+    // Find the parts reachable from the entry, following exception edges
+    // only from parts that may throw, and number them in order of their pcs
+    // after a block for the arguments if the first one has predecessors.
+    let mut normal = vec![vec![]; parts.len()];
+    let mut exceptional = vec![vec![]; parts.len()];
+    for (id, part) in parts.iter().enumerate() {
+        let part_of = |block: usize| part_at[cfg.block(block).instructions.start].unwrap();
+        let split = part.instructions.end < cfg.block(part.block).instructions.end;
+        if split {
+            normal[id].push(id + 1);
+        }
+        for edge in cfg.successors(part.block) {
+            match edge.kind {
+                EdgeKind::Exceptional { catch_type } => {
+                    if part.throws {
+                        let catch_type = cp.class_name(catch_type).cloned();
+                        exceptional[id].push((catch_type, part_of(edge.to)));
+                    }
+                }
+                _ if !split => normal[id].push(part_of(edge.to)),
+                _ => {}
+            }
+        }
+    }
+    let mut reachable = vec![false; parts.len()];
+    let mut pending = vec![0];
+    reachable[0] = true;
+    let mut entered = false;
+    while let Some(id) = pending.pop() {
+        let targets = exceptional[id].iter().map(|&(_, target)| target);
+        for successor in normal[id].iter().cloned().chain(targets) {
+            entered |= successor == 0;
+            if !reachable[successor] {
+                reachable[successor] = true;
+                pending.push(successor);
+            }
+        }
+    }
+    let mut ids = vec![None; parts.len()];
+    let mut count = if entered { 1 } else { 0 };
+    for part in 0..parts.len() {
+        if reachable[part] {
+            ids[part] = Some(count);
+            count += 1;
+        }
+    }
+
+    let mut blocks = vec![];
+    let mut part_of = vec![None; count];
+    let mut normal_preds = vec![BTreeSet::new(); count];
+    let mut handler_preds = vec![BTreeSet::new(); count];
+    let mut successors = vec![vec![]; count];
+    if entered {
+        blocks.push(Block {
+            id: 0,
+            pc: 0,
+            phis: vec![],
+            instructions: vec![],
+            terminator: Terminator::Goto(1),
+            handlers: vec![],
+        });
+        normal_preds[1].insert(0);
+        successors[0].push(1);
+    }
+    for part in 0..parts.len() {
+        let id = match ids[part] {
+            Some(id) => id,
+            None => continue,
+        };
+        part_of[id] = Some(part);
+        for &target in normal[part].iter() {
+            let target = ids[target].unwrap();
+            normal_preds[target].insert(id);
+            successors[id].push(target);
+        }
+        let mut handlers = vec![];
+        for &(ref catch_type, target) in exceptional[part].iter() {
+            let target = ids[target].unwrap();
+            handler_preds[target].insert(id);
+            successors[id].push(target);
+            handlers.push(Handler {
+                catch_type: catch_type.clone(),
+                target: target,
+            });
+        }
+        blocks.push(Block {
+            id: id,
+            pc: instructions.pc(parts[part].instructions.start).unwrap(),
+            phis: vec![],
+            instructions: vec![],
+            terminator: Terminator::Return(None),
+            handlers: handlers,
+        });
+    }
+    for id in 0..count {
+        if !normal_preds[id].is_empty() && !handler_preds[id].is_empty() {
+            return unsupported(blocks[id].pc, "exception handler is also entered normally");
+        }
+    }
+    let block_of = |pc: usize| ids[part_at[instructions.index_of(pc).unwrap()].unwrap()].unwrap();
+
+    // Simulate each block, in reverse postorder so that a block with a
+    // single predecessor can start from the state it ends in.
+    let mut entry = State {
+        locals: vec![None; code.max_locals as usize],
+        stack: vec![],
+    };
+    let mut slot = 0;
+    for &param in function.params.iter() {
+        if slot < entry.locals.len() {
+            entry.locals[slot] = Some(param);
+        }
+        slot += function.types[param.0].size();
+    }
+    let liveness = Liveness::new(&cfg);
+    let mut lifter = Lifter {
+        cp: cp,
+        types: function.types.clone(),
+        code: vec![],
+    };
+    let mut end_states: Vec<Option<State>> = vec![None; count];
+    let mut phi_slots: Vec<Vec<Slot>> = vec![vec![]; count];
+    for id in reverse_postorder(&successors) {
+        let preds: Vec<usize> = normal_preds[id].union(&handler_preds[id]).cloned().collect();
+        let part = match part_of[id] {
+            Some(part) => &parts[part],
+            None => {
+                end_states[id] = Some(entry.clone());
+                continue;
+            }
+        };
+        let pc = blocks[id].pc;
+        let mut state = if preds.is_empty() {
+            entry.clone()
+        } else if preds.len() == 1 && handler_preds[id].is_empty() {
+            end_states[preds[0]].clone().unwrap()
+        } else {
+            let frame = frames.get(part.instructions.start).unwrap();
+            let mut state = State {
+                locals: vec![None; code.max_locals as usize],
+                stack: vec![],
+            };
+            for (slot, value) in frame.locals().iter().enumerate() {
+                if let Some(ty) = type_of(value) {
+                    if liveness.is_live_before(pc, slot as u16) {
+                        let value = lifter.new_value(ty);
+                        state.locals[slot] = Some(value);
+                        blocks[id].phis.push(Phi {
+                            result: value,
+                            operands: vec![],
+                        });
+                        phi_slots[id].push(Slot::Local(slot));
+                    }
+                }
+            }
+            if handler_preds[id].is_empty() {
+                for (depth, value) in frame.stack().iter().enumerate() {
+                    let ty = match type_of(value) {
+                        Some(ty) => ty,
+                        None => return invalid(pc, "Illegal value on the stack at a join"),
+                    };
+                    let value = lifter.new_value(ty);
+                    state.stack.push(value);
+                    blocks[id].phis.push(Phi {
+                        result: value,
+                        operands: vec![],
+                    });
+                    phi_slots[id].push(Slot::Stack(depth));
+                }
+            }
+            state
+        };
+        if !handler_preds[id].is_empty() {
+            let exception = lifter.emit(pc, Op::CaughtException, Some(Type::Reference));
+            state.stack.push(exception.unwrap());
+        }
+
+        let mut terminator = None;
+        for index in part.instructions.clone() {
+            let pc = instructions.pc(index).unwrap();
+            let bytecode = instructions.get(index).unwrap();
+            let next = instructions.pc(index + 1);
+            terminator = try!(lifter.execute(&mut state, pc, bytecode, next, &block_of));
+        }
+        let last = part.instructions.end - 1;
+        blocks[id].terminator = match terminator {
+            Some(terminator) => terminator,
+            None => Terminator::Goto(block_of(instructions.pc(last + 1).unwrap())),
+        };
+        blocks[id].instructions = lifter.take_code();
+        end_states[id] = Some(state);
+    }
+
+    // Fill in the phi operands from the states the predecessors end in.
+    for id in 0..count {
+        let preds: BTreeSet<usize> = normal_preds[id].union(&handler_preds[id]).cloned().collect();
+        for (phi, &slot) in blocks[id].phis.iter_mut().zip(phi_slots[id].iter()) {
+            for &pred in preds.iter() {
+                let state = end_states[pred].as_ref().unwrap();
+                let value = match slot {
+                    Slot::Local(index) => state.locals[index],
+                    Slot::Stack(depth) => state.stack.get(depth).cloned(),
+                };
+                match value {
+                    Some(value) => phi.operands.push((pred, value)),
+                    None => {
+                        let message = format!("No value for {:?} from block {}", slot, pred);
+                        return invalid(blocks[id].pc, &message);
+                    }
+                }
+            }
+        }
+    }
+
+    function.types = lifter.types;
+    function.blocks = blocks;
+    remove_trivial_phis(&mut function);
+    renumber(&mut function);
+    Ok(function)
+}
+
+/// A run of instructions of a `ControlFlowGraph` block that becomes a
+/// block of the function.
+struct Part {
+    block: usize,
+    instructions: Range<usize>,
+    /// Whether the part is covered by a handler and has an instruction that
+    /// may throw.
+    throws: bool,
+}
+
+impl Part {
+    fn new(block: usize, instructions: Range<usize>, throws: bool) -> Part {
+        Part {
+            block: block,
+            instructions: instructions,
+            throws: throws,
+        }
+    }
+}
+
+/// A place a phi node chooses the value of.
+#[derive(Clone, Copy, Debug)]
+enum Slot {
+    Local(usize),
+    Stack(usize),
+}
+
+/// The values in the locals and on the operand stack. A `long` or `double`
+/// local takes its first slot, and is a single stack entry.
+#[derive(Clone, Debug)]
+struct State {
+    locals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+}
+
+impl State {
+    fn pop(&mut self, pc: usize) -> Result<Value> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => invalid(pc, "Cannot pop operand off an empty stack"),
+        }
+    }
+
+    /// Pops `count` values, returning them in the order they were pushed.
+    fn pop_n(&mut self, pc: usize, count: usize) -> Result<Vec<Value>> {
+        if count > self.stack.len() {
+            return invalid(pc, "Cannot pop operand off an empty stack");
+        }
+        let at = self.stack.len() - count;
+        Ok(self.stack.split_off(at))
+    }
+
+    fn load(&mut self, pc: usize, index: usize) -> Result<Value> {
+        match self.locals.get(index).cloned() {
+            Some(Some(value)) => Ok(value),
+            _ => invalid(pc, &format!("Illegal local variable number {}", index)),
+        }
+    }
+
+    fn store(&mut self, pc: usize, index: usize, value: Value, size: usize) -> Result<()> {
+        if index + size > self.locals.len() {
+            return invalid(pc, &format!("Illegal local variable number {}", index));
+        }
+        self.locals[index] = Some(value);
+        if size == 2 {
+            self.locals[index + 1] = None;
+        }
+        Ok(())
+    }
+}
+
+/// Emits the instructions of the block being lifted.
+struct Lifter<'a> {
+    cp: &'a ConstantPool,
+    types: Vec<Type>,
+    code: Vec<Instruction>,
+}
+
+impl<'a> Lifter<'a> {
+    fn new_value(&mut self, ty: Type) -> Value {
+        self.types.push(ty);
+        Value(self.types.len() - 1)
+    }
+
+    fn emit(&mut self, pc: usize, op: Op, ty: Option<Type>) -> Option<Value> {
+        let result = ty.map(|ty| self.new_value(ty));
+        self.code.push(Instruction {
+            pc: pc,
+            result: result,
+            op: op,
+        });
+        result
+    }
+
+    /// Emits an operation producing a value of type `ty` and pushes it.
+    fn push(&mut self, state: &mut State, pc: usize, op: Op, ty: Type) {
+        let value = self.emit(pc, op, Some(ty)).unwrap();
+        state.stack.push(value);
+    }
+
+    fn take_code(&mut self) -> Vec<Instruction> {
+        ::std::mem::replace(&mut self.code, vec![])
+    }
+
+    fn size(&self, value: Value) -> usize {
+        self.types[value.0].size()
+    }
+
+    /// Lifts the instruction at `pc`, followed by the one at `next`, and
+    /// returns the terminator if it ends the block. `block_of` gives the
+    /// block starting at a pc.
+    fn execute(&mut self,
+               state: &mut State,
+               pc: usize,
+               insn: &Bytecode,
+               next: Option<usize>,
+               block_of: &Fn(usize) -> usize)
+               -> Result<Option<Terminator>> {
+        if let Some(access) = insn.local_access() {
+            let index = access.index as usize;
+            match *insn {
+                Bytecode::iinc { constant, .. } => {
+                    return self.increment(state, pc, index, constant as i8 as i32)
+                }
+                Bytecode::wide_iinc { constant, .. } => {
+                    return self.increment(state, pc, index, constant as i16 as i32)
+                }
+                _ => {}
+            }
+            if access.writes {
+                let value = try!(state.pop(pc));
+                try!(state.store(pc, index, value, access.slots as usize));
+            } else {
+                let value = try!(state.load(pc, index));
+                state.stack.push(value);
+            }
+            return Ok(None);
+        }
+
+        match *insn {
+            Bytecode::nop => {}
+            Bytecode::aconst_null => {
+                self.push(state, pc, Op::Literal(Literal::Null), Type::Reference)
+            }
+            Bytecode::iconst_i(value) => self.literal(state, pc, Literal::Int(value as i32)),
+            Bytecode::lconst_l(value) => self.literal(state, pc, Literal::Long(value as i64)),
+            Bytecode::fconst_f(value) => self.literal(state, pc, Literal::Float(value as f32)),
+            Bytecode::dconst_d(value) => self.literal(state, pc, Literal::Double(value as f64)),
+            Bytecode::bipush { byte } => self.literal(state, pc, Literal::Int(byte as i8 as i32)),
+            Bytecode::sipush { short } => self.literal(state, pc, Literal::Int(short as i32)),
+            Bytecode::ldc { .. } |
+            Bytecode::ldc_w { .. } |
+            Bytecode::ldc2_w { .. } => {
+                let literal = try!(self.constant(pc, insn.constant_index().unwrap()));
+                self.literal(state, pc, literal);
+            }
+
+            Bytecode::iaload => try!(self.array_load(state, pc, ArrayKind::Int)),
+            Bytecode::laload => try!(self.array_load(state, pc, ArrayKind::Long)),
+            Bytecode::faload => try!(self.array_load(state, pc, ArrayKind::Float)),
+            Bytecode::daload => try!(self.array_load(state, pc, ArrayKind::Double)),
+            Bytecode::aaload => try!(self.array_load(state, pc, ArrayKind::Reference)),
+            Bytecode::baload => try!(self.array_load(state, pc, ArrayKind::Byte)),
+            Bytecode::caload => try!(self.array_load(state, pc, ArrayKind::Char)),
+            Bytecode::saload => try!(self.array_load(state, pc, ArrayKind::Short)),
+            Bytecode::iastore => try!(self.array_store(state, pc, ArrayKind::Int)),
+            Bytecode::lastore => try!(self.array_store(state, pc, ArrayKind::Long)),
+            Bytecode::fastore => try!(self.array_store(state, pc, ArrayKind::Float)),
+            Bytecode::dastore => try!(self.array_store(state, pc, ArrayKind::Double)),
+            Bytecode::aastore => try!(self.array_store(state, pc, ArrayKind::Reference)),
+            Bytecode::bastore => try!(self.array_store(state, pc, ArrayKind::Byte)),
+            Bytecode::castore => try!(self.array_store(state, pc, ArrayKind::Char)),
+            Bytecode::sastore => try!(self.array_store(state, pc, ArrayKind::Short)),
+
+            Bytecode::pop => {
+                try!(state.pop(pc));
+            }
+            Bytecode::pop2 => {
+                let value = try!(state.pop(pc));
+                if self.size(value) == 1 {
+                    try!(state.pop(pc));
+                }
+            }
+            Bytecode::dup => {
+                let value = try!(state.pop(pc));
+                state.stack.extend(&[value, value]);
+            }
+            Bytecode::dup_x1 => {
+                let value1 = try!(state.pop(pc));
+                let value2 = try!(state.pop(pc));
+                state.stack.extend(&[value1, value2, value1]);
+            }
+            Bytecode::dup_x2 => {
+                let value1 = try!(state.pop(pc));
+                let value2 = try!(state.pop(pc));
+                if self.size(value2) == 2 {
+                    state.stack.extend(&[value1, value2, value1]);
+                } else {
+                    let value3 = try!(state.pop(pc));
+                    state.stack.extend(&[value1, value3, value2, value1]);
+                }
+            }
+            Bytecode::dup2 => {
+                let value1 = try!(state.pop(pc));
+                if self.size(value1) == 2 {
+                    state.stack.extend(&[value1, value1]);
+                } else {
+                    let value2 = try!(state.pop(pc));
+                    state.stack.extend(&[value2, value1, value2, value1]);
+                }
+            }
+            Bytecode::dup2_x1 => {
+                let value1 = try!(state.pop(pc));
+                let value2 = try!(state.pop(pc));
+                if self.size(value1) == 2 {
+                    state.stack.extend(&[value1, value2, value1]);
+                } else {
+                    let value3 = try!(state.pop(pc));
+                    state.stack.extend(&[value2, value1, value3, value2, value1]);
+                }
+            }
+            Bytecode::dup2_x2 => {
+                let value1 = try!(state.pop(pc));
+                let value2 = try!(state.pop(pc));
+                if self.size(value1) == 2 {
+                    if self.size(value2) == 2 {
+                        state.stack.extend(&[value1, value2, value1]);
+                    } else {
+                        let value3 = try!(state.pop(pc));
+                        state.stack.extend(&[value1, value3, value2, value1]);
+                    }
+                } else {
+                    let value3 = try!(state.pop(pc));
+                    if self.size(value3) == 2 {
+                        state.stack.extend(&[value2, value1, value3, value2, value1]);
+                    } else {
+                        let value4 = try!(state.pop(pc));
+                        state.stack.extend(&[value2, value1, value4, value3, value2, value1]);
+                    }
+                }
+            }
+            Bytecode::swap => {
+                let value1 = try!(state.pop(pc));
+                let value2 = try!(state.pop(pc));
+                state.stack.extend(&[value1, value2]);
+            }
+
+            Bytecode::iadd | Bytecode::ladd | Bytecode::fadd | Bytecode::dadd => {
+                try!(self.binary(state, pc, BinaryOp::Add))
+            }
+            Bytecode::isub | Bytecode::lsub | Bytecode::fsub | Bytecode::dsub => {
+                try!(self.binary(state, pc, BinaryOp::Sub))
+            }
+            Bytecode::imul | Bytecode::lmul | Bytecode::fmul | Bytecode::dmul => {
+                try!(self.binary(state, pc, BinaryOp::Mul))
+            }
+            Bytecode::idiv | Bytecode::ldiv | Bytecode::fdiv | Bytecode::ddiv => {
+                try!(self.binary(state, pc, BinaryOp::Div))
+            }
+            Bytecode::irem | Bytecode::lrem | Bytecode::frem | Bytecode::drem => {
+                try!(self.binary(state, pc, BinaryOp::Rem))
+            }
+            Bytecode::ishl | Bytecode::lshl => try!(self.binary(state, pc, BinaryOp::Shl)),
+            Bytecode::ishr | Bytecode::lshr => try!(self.binary(state, pc, BinaryOp::Shr)),
+            Bytecode::iushr | Bytecode::lushr => try!(self.binary(state, pc, BinaryOp::UShr)),
+            Bytecode::iand | Bytecode::land => try!(self.binary(state, pc, BinaryOp::And)),
+            Bytecode::ior | Bytecode::lor => try!(self.binary(state, pc, BinaryOp::Or)),
+            Bytecode::ixor | Bytecode::lxor => try!(self.binary(state, pc, BinaryOp::Xor)),
+            Bytecode::ineg | Bytecode::lneg | Bytecode::fneg | Bytecode::dneg => {
+                let value = try!(state.pop(pc));
+                let ty = self.types[value.0];
+                self.push(state, pc, Op::Negate(value), ty);
+            }
+
+            Bytecode::i2l | Bytecode::f2l | Bytecode::d2l => {
+                try!(self.convert(state, pc, FieldType::Long))
+            }
+            Bytecode::i2f | Bytecode::l2f | Bytecode::d2f => {
+                try!(self.convert(state, pc, FieldType::Float))
+            }
+            Bytecode::i2d | Bytecode::l2d | Bytecode::f2d => {
+                try!(self.convert(state, pc, FieldType::Double))
+            }
+            Bytecode::l2i | Bytecode::f2i | Bytecode::d2i => {
+                try!(self.convert(state, pc, FieldType::Int))
+            }
+            Bytecode::i2b => try!(self.convert(state, pc, FieldType::Byte)),
+            Bytecode::i2c => try!(self.convert(state, pc, FieldType::Char)),
+            Bytecode::i2s => try!(self.convert(state, pc, FieldType::Short)),
+            Bytecode::lcmp => try!(self.compare(state, pc, Comparison::Lcmp)),
+            Bytecode::fcmpl => try!(self.compare(state, pc, Comparison::Fcmpl)),
+            Bytecode::fcmpg => try!(self.compare(state, pc, Comparison::Fcmpg)),
+            Bytecode::dcmpl => try!(self.compare(state, pc, Comparison::Dcmpl)),
+            Bytecode::dcmpg => try!(self.compare(state, pc, Comparison::Dcmpg)),
+
+            Bytecode::ifeq { .. } | Bytecode::ifnull { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Eq, false)
+            }
+            Bytecode::ifne { .. } | Bytecode::ifnonnull { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Ne, false)
+            }
+            Bytecode::iflt { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Lt, false)
+            }
+            Bytecode::ifge { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Ge, false)
+            }
+            Bytecode::ifgt { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Gt, false)
+            }
+            Bytecode::ifle { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Le, false)
+            }
+            Bytecode::if_icmpeq { .. } | Bytecode::if_acmpeq { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Eq, true)
+            }
+            Bytecode::if_icmpne { .. } | Bytecode::if_acmpne { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Ne, true)
+            }
+            Bytecode::if_icmplt { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Lt, true)
+            }
+            Bytecode::if_icmpge { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Ge, true)
+            }
+            Bytecode::if_icmpgt { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Gt, true)
+            }
+            Bytecode::if_icmple { .. } => {
+                return self.branch(state, pc, insn, next, block_of, Condition::Le, true)
+            }
+            Bytecode::goto { .. } |
+            Bytecode::goto_w { .. } => {
+                return Ok(Some(Terminator::Goto(block_of(insn.branch_targets(pc)[0]))))
+            }
+            Bytecode::tableswitch { low, .. } => {
+                let value = try!(state.pop(pc));
+                let targets = insn.branch_targets(pc);
+                let cases = targets[1..]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &target)| (low.wrapping_add(i as i32), block_of(target)))
+                    .collect();
+                return Ok(Some(Terminator::Switch {
+                    value: value,
+                    cases: cases,
+                    default: block_of(targets[0]),
+                }));
+            }
+            Bytecode::lookupswitch { ref pairs, .. } => {
+                let value = try!(state.pop(pc));
+                let targets = insn.branch_targets(pc);
+                let cases = pairs.iter()
+                    .zip(targets[1..].iter())
+                    .map(|(&(key, _), &target)| (key, block_of(target)))
+                    .collect();
+                return Ok(Some(Terminator::Switch {
+                    value: value,
+                    cases: cases,
+                    default: block_of(targets[0]),
+                }));
+            }
+            Bytecode::ireturn | Bytecode::lreturn | Bytecode::freturn | Bytecode::dreturn |
+            Bytecode::areturn => {
+                let value = try!(state.pop(pc));
+                return Ok(Some(Terminator::Return(Some(value))));
+            }
+            Bytecode::Return => return Ok(Some(Terminator::Return(None))),
+            Bytecode::athrow => {
+                let value = try!(state.pop(pc));
+                return Ok(Some(Terminator::Throw(value)));
+            }
+
+            Bytecode::getstatic { index } => {
+                let field = try!(self.field(pc, index));
+                let ty = Type::of(&field.descriptor);
+                self.push(state, pc, Op::GetStatic(field), ty);
+            }
+            Bytecode::putstatic { index } => {
+                let field = try!(self.field(pc, index));
+                let value = try!(state.pop(pc));
+                self.emit(pc, Op::PutStatic(field, value), None);
+            }
+            Bytecode::getfield { index } => {
+                let field = try!(self.field(pc, index));
+                let ty = Type::of(&field.descriptor);
+                let object = try!(state.pop(pc));
+                self.push(state, pc, Op::GetField(field, object), ty);
+            }
+            Bytecode::putfield { index } => {
+                let field = try!(self.field(pc, index));
+                let value = try!(state.pop(pc));
+                let object = try!(state.pop(pc));
+                self.emit(pc, Op::PutField(field, object, value), None);
+            }
+            Bytecode::invokevirtual { index } => {
+                try!(self.invoke(state, pc, index, InvokeKind::Virtual))
+            }
+            Bytecode::invokespecial { index } => {
+                try!(self.invoke(state, pc, index, InvokeKind::Special))
+            }
+            Bytecode::invokestatic { index } => {
+                try!(self.invoke(state, pc, index, InvokeKind::Static))
+            }
+            Bytecode::invokeinterface { index, .. } => {
+                try!(self.invoke(state, pc, index, InvokeKind::Interface))
+            }
+            Bytecode::invokedynamic { index } => {
+                let (bootstrap, name, descriptor) = try!(self.call_site(pc, index));
+                let descriptor = try!(MethodDescriptor::parse(descriptor));
+                let args = try!(state.pop_n(pc, descriptor.params.len()));
+                let ty = descriptor.ret.as_ref().map(Type::of);
+                let op = Op::InvokeDynamic {
+                    bootstrap: bootstrap,
+                    name: name.to_string(),
+                    descriptor: descriptor,
+                    args: args,
+                };
+                if let Some(value) = self.emit(pc, op, ty) {
+                    state.stack.push(value);
+                }
+            }
+
+            Bytecode::new { index } => {
+                let class = try!(self.class(pc, index));
+                self.push(state, pc, Op::New(class), Type::Reference);
+            }
+            Bytecode::newarray { atype } => {
+                let component = match atype {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => FieldType::Float,
+                    7 => FieldType::Double,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => FieldType::Int,
+                    11 => FieldType::Long,
+                    _ => return invalid(pc, &format!("Illegal array type {}", atype)),
+                };
+                let length = try!(state.pop(pc));
+                self.push(state, pc, Op::NewArray(component, length), Type::Reference);
+            }
+            Bytecode::anewarray { index } => {
+                let class = try!(self.class(pc, index));
+                let component = if class.starts_with('[') {
+                    try!(FieldType::parse(&class))
+                } else {
+                    FieldType::Object(class)
+                };
+                let length = try!(state.pop(pc));
+                self.push(state, pc, Op::NewArray(component, length), Type::Reference);
+            }
+            Bytecode::multianewarray { index, dimensions } => {
+                let array = try!(FieldType::parse(&try!(self.class(pc, index))));
+                let lengths = try!(state.pop_n(pc, dimensions as usize));
+                self.push(state, pc, Op::MultiNewArray(array, lengths), Type::Reference);
+            }
+            Bytecode::arraylength => {
+                let array = try!(state.pop(pc));
+                self.push(state, pc, Op::ArrayLength(array), Type::Int);
+            }
+            Bytecode::checkcast { index } => {
+                let class = try!(self.class(pc, index));
+                let value = try!(state.pop(pc));
+                self.push(state, pc, Op::CheckCast(class, value), Type::Reference);
+            }
+            Bytecode::instanceof { index } => {
+                let class = try!(self.class(pc, index));
+                let value = try!(state.pop(pc));
+                self.push(state, pc, Op::InstanceOf(class, value), Type::Int);
+            }
+            Bytecode::monitorenter => {
+                let value = try!(state.pop(pc));
+                self.emit(pc, Op::MonitorEnter(value), None);
+            }
+            Bytecode::monitorexit => {
+                let value = try!(state.pop(pc));
+                self.emit(pc, Op::MonitorExit(value), None);
+            }
+
+            _ => return unsupported(pc, insn.info().mnemonic()),
+        }
+        Ok(None)
+    }
+
+    fn literal(&mut self, state: &mut State, pc: usize, literal: Literal) {
+        let ty = literal.value_type();
+        self.push(state, pc, Op::Literal(literal), ty);
+    }
+
+    fn increment(&mut self,
+                 state: &mut State,
+                 pc: usize,
+                 index: usize,
+                 constant: i32)
+                 -> Result<Option<Terminator>> {
+        let value = try!(state.load(pc, index));
+        let constant = self.emit(pc, Op::Literal(Literal::Int(constant)), Some(Type::Int));
+        let op = Op::Binary(BinaryOp::Add, value, constant.unwrap());
+        let sum = self.emit(pc, op, Some(Type::Int)).unwrap();
+        try!(state.store(pc, index, sum, 1));
+        Ok(None)
+    }
+
+    fn array_load(&mut self, state: &mut State, pc: usize, kind: ArrayKind) -> Result<()> {
+        let index = try!(state.pop(pc));
+        let array = try!(state.pop(pc));
+        self.push(state, pc, Op::ArrayLoad(kind, array, index), kind.element_type());
+        Ok(())
+    }
+
+    fn array_store(&mut self, state: &mut State, pc: usize, kind: ArrayKind) -> Result<()> {
+        let value = try!(state.pop(pc));
+        let index = try!(state.pop(pc));
+        let array = try!(state.pop(pc));
+        self.emit(pc, Op::ArrayStore(kind, array, index, value), None);
+        Ok(())
+    }
+
+    fn binary(&mut self, state: &mut State, pc: usize, op: BinaryOp) -> Result<()> {
+        let right = try!(state.pop(pc));
+        let left = try!(state.pop(pc));
+        let ty = self.types[left.0];
+        self.push(state, pc, Op::Binary(op, left, right), ty);
+        Ok(())
+    }
+
+    fn convert(&mut self, state: &mut State, pc: usize, to: FieldType) -> Result<()> {
+        let value = try!(state.pop(pc));
+        let ty = Type::of(&to);
+        self.push(state, pc, Op::Convert(value, to), ty);
+        Ok(())
+    }
+
+    fn compare(&mut self, state: &mut State, pc: usize, comparison: Comparison) -> Result<()> {
+        let right = try!(state.pop(pc));
+        let left = try!(state.pop(pc));
+        self.push(state, pc, Op::Compare(comparison, left, right), Type::Int);
+        Ok(())
+    }
+
+    fn branch(&mut self,
+              state: &mut State,
+              pc: usize,
+              insn: &Bytecode,
+              next: Option<usize>,
+              block_of: &Fn(usize) -> usize,
+              condition: Condition,
+              binary: bool)
+              -> Result<Option<Terminator>> {
+        let right = if binary {
+            Some(try!(state.pop(pc)))
+        } else {
+            None
+        };
+        let left = try!(state.pop(pc));
+        let next = match next {
+            Some(next) => next,
+            None => return invalid(pc, "Falling off the end of the code"),
+        };
+        Ok(Some(Terminator::If {
+            condition: condition,
+            left: left,
+            right: right,
+            then: block_of(insn.branch_targets(pc)[0]),
+            otherwise: block_of(next),
+        }))
+    }
+
+    fn invoke(&mut self, state: &mut State, pc: usize, index: u16, kind: InvokeKind) -> Result<()> {
+        let (owner, name, descriptor, interface) = try!(self.member(pc, index));
+        let descriptor = try!(MethodDescriptor::parse(descriptor));
+        let receiver = if kind == InvokeKind::Static { 0 } else { 1 };
+        let args = try!(state.pop_n(pc, descriptor.params.len() + receiver));
+        let ty = descriptor.ret.as_ref().map(Type::of);
+        let method = MethodRef {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor,
+            interface: interface,
+        };
+        if let Some(value) = self.emit(pc, Op::Invoke(kind, method, args), ty) {
+            state.stack.push(value);
+        }
+        Ok(())
+    }
+
+    fn field(&self, pc: usize, index: u16) -> Result<FieldRef> {
+        let (owner, name, descriptor, _) = try!(self.member(pc, index));
+        Ok(FieldRef {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            descriptor: try!(FieldType::parse(descriptor)),
+        })
+    }
+
+    fn class(&self, pc: usize, index: u16) -> Result<String> {
+        match self.cp.class_name(index) {
+            Some(name) => Ok(name.clone()),
+            None => invalid(pc, &format!("Illegal constant pool index {} for class", index)),
+        }
+    }
+
+    fn utf8(&self, index: u16) -> Option<&'a str> {
+        match self.cp.get(index) {
+            Some(&Constant::Utf8(ref value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Resolves the name and descriptor of the `NameAndType` at `index`.
+    fn name_and_type(&self, pc: usize, index: u16) -> Result<(&'a str, &'a str)> {
+        if let Some(&Constant::NameAndType(ref name_and_type)) = self.cp.get(index) {
+            let name = self.utf8(name_and_type.name_index);
+            let descriptor = self.utf8(name_and_type.descriptor_index);
+            if let (Some(name), Some(descriptor)) = (name, descriptor) {
+                return Ok((name, descriptor));
+            }
+        }
+        invalid(pc, &format!("Illegal constant pool index {}", index))
+    }
+
+    /// Resolves the field or method reference at `index` into its owner,
+    /// name and descriptor, and whether it is an `InterfaceMethodref`.
+    fn member(&self, pc: usize, index: u16) -> Result<(&'a str, &'a str, &'a str, bool)> {
+        let (entity, interface) = match self.cp.get(index) {
+            Some(&Constant::Fieldref(ref entity)) |
+            Some(&Constant::Methodref(ref entity)) => (entity, false),
+            Some(&Constant::InterfaceMethodref(ref entity)) => (entity, true),
+            _ => return invalid(pc, &format!("Illegal constant pool index {}", index)),
+        };
+        let owner = match self.cp.class_name(entity.class_index) {
+            Some(owner) => owner,
+            None => return invalid(pc, &format!("Illegal constant pool index {}", index)),
+        };
+        let (name, descriptor) = try!(self.name_and_type(pc, entity.name_and_type_index));
+        Ok((owner, name, descriptor, interface))
+    }
+
+    fn call_site(&self, pc: usize, index: u16) -> Result<(u16, &'a str, &'a str)> {
+        match self.cp.get(index) {
+            Some(&Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index }) => {
+                let (name, descriptor) = try!(self.name_and_type(pc, name_and_type_index));
+                Ok((bootstrap_method_attr_index, name, descriptor))
+            }
+            _ => invalid(pc, &format!("Illegal constant pool index {}", index)),
+        }
+    }
+
+    /// Resolves the loadable constant at `index`.
+    fn constant(&self, pc: usize, index: u16) -> Result<Literal> {
+        let literal = match self.cp.get(index) {
+            Some(&Constant::Integer(value)) => Literal::Int(value),
+            Some(&Constant::Float(value)) => Literal::Float(value),
+            Some(&Constant::Long(value)) => Literal::Long(value),
+            Some(&Constant::Double(value)) => Literal::Double(value),
+            Some(&Constant::String(string_index)) => {
+                match self.utf8(string_index) {
+                    Some(value) => Literal::String(value.to_string()),
+                    None => return invalid(pc, "Illegal ldc constant"),
+                }
+            }
+            Some(&Constant::Class(_)) => Literal::Class(try!(self.class(pc, index))),
+            Some(&Constant::MethodType { descriptor_index }) => {
+                let descriptor = match self.utf8(descriptor_index) {
+                    Some(descriptor) => try!(MethodDescriptor::parse(descriptor)),
+                    None => return invalid(pc, "Illegal ldc constant"),
+                };
+                Literal::MethodType(descriptor)
+            }
+            Some(&Constant::MethodHandle { reference_kind, reference_index }) => {
+                let (owner, name, descriptor, _) = try!(self.member(pc, reference_index));
+                Literal::MethodHandle {
+                    kind: reference_kind,
+                    owner: owner.to_string(),
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                }
+            }
+            _ => return invalid(pc, "Illegal ldc constant"),
+        };
+        Ok(literal)
+    }
+}
+
+fn is_exceptional(kind: EdgeKind) -> bool {
+    match kind {
+        EdgeKind::Exceptional { .. } => true,
+        _ => false,
+    }
+}
+
+/// The type of a value a phi node can choose, or `None` for `Top` and return
+/// addresses.
+fn type_of(value: &BasicValue) -> Option<Type> {
+    match *value {
+        BasicValue::Integer => Some(Type::Int),
+        BasicValue::Float => Some(Type::Float),
+        BasicValue::Long => Some(Type::Long),
+        BasicValue::Double => Some(Type::Double),
+        BasicValue::Reference => Some(Type::Reference),
+        BasicValue::Top | BasicValue::ReturnAddress => None,
+    }
+}
+
+/// The ids of the blocks reachable from the entry, in reverse postorder.
+fn reverse_postorder(successors: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; successors.len()];
+    let mut postorder = vec![];
+    let mut pending = vec![(0, 0)];
+    visited[0] = true;
+    while let Some((id, next)) = pending.pop() {
+        if next < successors[id].len() {
+            pending.push((id, next + 1));
+            let successor = successors[id][next];
+            if !visited[successor] {
+                visited[successor] = true;
+                pending.push((successor, 0));
+            }
+        } else {
+            postorder.push(id);
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+/// Removes the phi nodes whose operands are all the same value or the phi
+/// node itself, replacing their uses with that value, until none are left.
+fn remove_trivial_phis(function: &mut Function) {
+    let mut replacements: Vec<Option<Value>> = vec![None; function.types.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in function.blocks.iter_mut() {
+            let mut kept = vec![];
+            for mut phi in ::std::mem::replace(&mut block.phis, vec![]) {
+                for operand in phi.operands.iter_mut() {
+                    operand.1 = resolve(&replacements, operand.1);
+                }
+                let mut values = phi.operands
+                    .iter()
+                    .map(|&(_, value)| value)
+                    .filter(|&value| value != phi.result);
+                let first = values.next();
+                match first {
+                    Some(value) if values.all(|other| other == value) => {
+                        replacements[phi.result.0] = Some(value);
+                        changed = true;
+                    }
+                    _ => kept.push(phi),
+                }
+            }
+            block.phis = kept;
+        }
+    }
+
+    let resolve = |value| resolve(&replacements, value);
+    for block in function.blocks.iter_mut() {
+        for phi in block.phis.iter_mut() {
+            for operand in phi.operands.iter_mut() {
+                operand.1 = resolve(operand.1);
+            }
+        }
+        for instruction in block.instructions.iter_mut() {
+            instruction.op.map_operands(&resolve);
+        }
+        block.terminator.map_operands(&resolve);
+    }
+}
+
+fn resolve(replacements: &[Option<Value>], mut value: Value) -> Value {
+    while let Some(replacement) = replacements[value.0] {
+        value = replacement;
+    }
+    value
+}
+
+/// Numbers the values in order of definition: the parameters, then the phi
+/// nodes and instructions of each block in turn. Values no longer defined
+/// are dropped.
+fn renumber(function: &mut Function) {
+    let mut numbers: Vec<Option<Value>> = vec![None; function.types.len()];
+    let mut types = vec![];
+    {
+        let mut number = |value: Value| {
+            numbers[value.0] = Some(Value(types.len()));
+            types.push(function.types[value.0]);
+        };
+        for &param in function.params.iter() {
+            number(param);
+        }
+        for block in function.blocks.iter() {
+            for phi in block.phis.iter() {
+                number(phi.result);
+            }
+            for instruction in block.instructions.iter() {
+                if let Some(result) = instruction.result {
+                    number(result);
+                }
+            }
+        }
+    }
+
+    let map = |value: Value| numbers[value.0].unwrap();
+    for param in function.params.iter_mut() {
+        *param = map(*param);
+    }
+    for block in function.blocks.iter_mut() {
+        for phi in block.phis.iter_mut() {
+            phi.result = map(phi.result);
+            for operand in phi.operands.iter_mut() {
+                operand.1 = map(operand.1);
+            }
+        }
+        for instruction in block.instructions.iter_mut() {
+            instruction.result = instruction.result.map(&map);
+            instruction.op.map_operands(&map);
+        }
+        block.terminator.map_operands(&map);
+    }
+    function.types = types;
+}
+
+fn invalid<T>(pc: usize, message: &str) -> Result<T> {
+    Err(Error::InvalidFrame {
+        pc: pc,
+        message: message.to_string(),
+    })
+}
+
+fn unsupported<T>(pc: usize, message: &str) -> Result<T> {
+    Err(Error::Unsupported {
+        pc: pc,
+        message: message.to_string(),
+    })
+}
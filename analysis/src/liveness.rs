@@ -0,0 +1,145 @@
+//! Liveness of local variables.
+//!
+//! A local variable slot is live at a point if some path from there reads
+//! the slot before writing it. `long` and `double` values occupy two slots,
+//! both of which are read by a load and written by a store. An instruction
+//! inside the range of an exception handler may transfer control to the
+//! handler before it executes, so everything live at the handler is also
+//! live before each such instruction.
+//!
+//! Control returns from a subroutine to the return sites of all its callers
+//! alike, so the locals live after any one call are taken to be live after
+//! all of them.
+use std::collections::BTreeSet;
+
+use classfile::{Bytecode, InstructionList};
+
+use cfg::{ControlFlowGraph, EdgeKind};
+
+/// The local variable slots live before and after each instruction of a
+/// method.
+#[derive(Clone, Debug)]
+pub struct Liveness {
+    instructions: InstructionList,
+    live_in: Vec<BTreeSet<u16>>,
+    live_out: Vec<BTreeSet<u16>>,
+}
+
+impl Liveness {
+    /// Computes the liveness of the locals of the method `cfg` was built for.
+    pub fn new(cfg: &ControlFlowGraph) -> Liveness {
+        let instructions = cfg.instructions().clone();
+        let blocks = cfg.blocks();
+
+        let mut block_in: Vec<BTreeSet<u16>> = vec![BTreeSet::new(); blocks.len()];
+        let mut pending: BTreeSet<usize> = (0..blocks.len()).collect();
+        while let Some(id) = pending.iter().next_back().cloned() {
+            pending.remove(&id);
+            let (ins, _) = transfer(cfg, &instructions, &block_in, id);
+            let live_in = ins.into_iter().next().unwrap_or_default();
+            if live_in != block_in[id] {
+                block_in[id] = live_in;
+                pending.extend(cfg.predecessors(id).iter().map(|edge| edge.from));
+            }
+        }
+
+        let count = instructions.len();
+        let mut live_in = vec![BTreeSet::new(); count];
+        let mut live_out = vec![BTreeSet::new(); count];
+        for block in blocks {
+            let (ins, outs) = transfer(cfg, &instructions, &block_in, block.id);
+            for ((index, live), out) in block.instructions.clone().zip(ins).zip(outs) {
+                live_in[index] = live;
+                live_out[index] = out;
+            }
+        }
+
+        Liveness {
+            instructions: instructions,
+            live_in: live_in,
+            live_out: live_out,
+        }
+    }
+
+    /// The slots live just before the instruction at `pc`, or `None` if `pc`
+    /// does not start an instruction.
+    pub fn live_before(&self, pc: usize) -> Option<&BTreeSet<u16>> {
+        self.instructions.index_of(pc).map(|index| &self.live_in[index])
+    }
+
+    /// The slots live just after the instruction at `pc` completes normally,
+    /// or `None` if `pc` does not start an instruction.
+    pub fn live_after(&self, pc: usize) -> Option<&BTreeSet<u16>> {
+        self.instructions.index_of(pc).map(|index| &self.live_out[index])
+    }
+
+    /// Returns `true` if `slot` is live just before the instruction at `pc`.
+    pub fn is_live_before(&self, pc: usize, slot: u16) -> bool {
+        self.live_before(pc).map_or(false, |live| live.contains(&slot))
+    }
+
+    /// The pcs of the stores and `iinc`s whose value is never read.
+    pub fn dead_stores(&self) -> Vec<usize> {
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter(|&(index, &(_, ref bytecode))| {
+                let live = &self.live_out[index];
+                bytecode.local_access().map_or(false, |access| {
+                    access.writes &&
+                    slots(access.index, access.slots).all(|slot| !live.contains(&slot))
+                })
+            })
+            .map(|(_, &(pc, _))| pc)
+            .collect()
+    }
+
+    /// The number of slots live at the point where the most are, which is
+    /// a lower bound for `max_locals` if every live value keeps its slot.
+    pub fn max_live(&self) -> usize {
+        self.live_in.iter().chain(self.live_out.iter()).map(|live| live.len()).max().unwrap_or(0)
+    }
+}
+
+/// The slots accessed by a local variable instruction.
+fn slots(index: u16, count: u8) -> ::std::ops::Range<u16> {
+    index..index.saturating_add(count as u16)
+}
+
+/// Runs block `id` backwards from the live-in sets of its successors, and
+/// returns the slots live before and after each of its instructions.
+fn transfer(cfg: &ControlFlowGraph,
+            instructions: &InstructionList,
+            block_in: &[BTreeSet<u16>],
+            id: usize)
+            -> (Vec<BTreeSet<u16>>, Vec<BTreeSet<u16>>) {
+    let mut live = BTreeSet::new();
+    let mut handlers = BTreeSet::new();
+    for edge in cfg.successors(id) {
+        let target = match edge.kind {
+            EdgeKind::Exceptional { .. } => &mut handlers,
+            _ => &mut live,
+        };
+        target.extend(block_in[edge.to].iter().cloned());
+    }
+
+    let range = cfg.block(id).instructions.clone();
+    let mut ins = vec![BTreeSet::new(); range.len()];
+    let mut outs = vec![BTreeSet::new(); range.len()];
+    for (i, index) in range.enumerate().rev() {
+        outs[i] = live.clone();
+        if let Some(access) = instructions.get(index).and_then(Bytecode::local_access) {
+            if access.writes {
+                for slot in slots(access.index, access.slots) {
+                    live.remove(&slot);
+                }
+            }
+            if access.reads {
+                live.extend(slots(access.index, access.slots));
+            }
+        }
+        live.extend(handlers.iter().cloned());
+        ins[i] = live.clone();
+    }
+    (ins, outs)
+}
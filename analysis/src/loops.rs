@@ -0,0 +1,208 @@
+//! Natural loops and irreducible control flow.
+//!
+//! An edge whose target dominates its source is a back edge, and the blocks
+//! that can reach its source without passing through its target form a
+//! natural loop headed by the target. Back edges sharing a header form a
+//! single loop. A cycle that can be entered at more than one block has no
+//! such header; javac never produces one, but obfuscators often do.
+use cfg::{ControlFlowGraph, Edge};
+use dominators::DominatorTree;
+
+/// A natural loop.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Loop {
+    /// The block every iteration enters through.
+    pub header: usize,
+    /// The blocks of the loop, including the header and those of any nested
+    /// loops, in order of their ids.
+    pub blocks: Vec<usize>,
+    /// The blocks with a back edge to the header.
+    pub latches: Vec<usize>,
+    /// The innermost loop containing this one.
+    pub parent: Option<usize>,
+    /// The loops directly nested in this one.
+    pub children: Vec<usize>,
+    /// The number of loops containing this one, counting itself.
+    pub depth: usize,
+}
+
+impl Loop {
+    pub fn contains(&self, block: usize) -> bool {
+        self.blocks.binary_search(&block).is_ok()
+    }
+}
+
+/// The natural loops of a method and how they nest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LoopForest {
+    loops: Vec<Loop>,
+    innermost: Vec<Option<usize>>,
+    irreducible_edges: Vec<Edge>,
+}
+
+impl LoopForest {
+    /// Finds the loops of `cfg`, given its dominator tree.
+    pub fn new(cfg: &ControlFlowGraph, dominators: &DominatorTree) -> LoopForest {
+        let count = cfg.blocks().len();
+
+        let mut headers: Vec<usize> = vec![];
+        let mut latches: Vec<Vec<usize>> = vec![vec![]; count];
+        for edge in cfg.edges() {
+            if dominators.dominates(edge.to, edge.from) {
+                if latches[edge.to].is_empty() {
+                    headers.push(edge.to);
+                }
+                if !latches[edge.to].contains(&edge.from) {
+                    latches[edge.to].push(edge.from);
+                }
+            }
+        }
+        headers.sort();
+
+        let mut loops: Vec<Loop> = headers.iter()
+            .map(|&header| {
+                let mut latches = latches[header].clone();
+                latches.sort();
+                Loop {
+                    header: header,
+                    blocks: loop_body(cfg, dominators, header, &latches),
+                    latches: latches,
+                    parent: None,
+                    children: vec![],
+                    depth: 0,
+                }
+            })
+            .collect();
+
+        // Loops with distinct headers are either disjoint or nested, so the
+        // parent of a loop is the smallest other loop containing its header.
+        for id in 0..loops.len() {
+            let header = loops[id].header;
+            loops[id].parent = (0..loops.len())
+                .filter(|&other| other != id && loops[other].contains(header))
+                .min_by_key(|&other| loops[other].blocks.len());
+        }
+        for id in 0..loops.len() {
+            if let Some(parent) = loops[id].parent {
+                loops[parent].children.push(id);
+            }
+            let mut depth = 1;
+            let mut current = loops[id].parent;
+            while let Some(parent) = current {
+                depth += 1;
+                current = loops[parent].parent;
+            }
+            loops[id].depth = depth;
+        }
+
+        let mut innermost: Vec<Option<usize>> = vec![None; count];
+        for (id, lp) in loops.iter().enumerate() {
+            for &block in &lp.blocks {
+                let deeper = match innermost[block] {
+                    Some(other) => loops[other].depth < lp.depth,
+                    None => true,
+                };
+                if deeper {
+                    innermost[block] = Some(id);
+                }
+            }
+        }
+
+        LoopForest {
+            loops: loops,
+            innermost: innermost,
+            irreducible_edges: irreducible_edges(cfg, dominators),
+        }
+    }
+
+    /// The loops in order of their headers' ids.
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    pub fn get(&self, id: usize) -> &Loop {
+        &self.loops[id]
+    }
+
+    /// The loops not nested in any other loop.
+    pub fn top_level(&self) -> Vec<usize> {
+        (0..self.loops.len()).filter(|&id| self.loops[id].parent.is_none()).collect()
+    }
+
+    /// The innermost loop containing block `id`.
+    pub fn innermost_loop(&self, id: usize) -> Option<usize> {
+        self.innermost[id]
+    }
+
+    /// The number of loops containing block `id`, or zero if it is not in a
+    /// loop.
+    pub fn depth(&self, id: usize) -> usize {
+        self.innermost[id].map_or(0, |lp| self.loops[lp].depth)
+    }
+
+    /// Returns `true` if every cycle in the graph belongs to a natural loop.
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible_edges.is_empty()
+    }
+
+    /// The edges that close a cycle but whose target does not dominate their
+    /// source. Each one enters a cycle with more than one entry, which is not
+    /// part of any natural loop.
+    pub fn irreducible_edges(&self) -> &[Edge] {
+        &self.irreducible_edges
+    }
+}
+
+/// Collects the blocks that reach a latch without passing through `header`.
+fn loop_body(cfg: &ControlFlowGraph,
+             dominators: &DominatorTree,
+             header: usize,
+             latches: &[usize])
+             -> Vec<usize> {
+    let mut in_loop = vec![false; cfg.blocks().len()];
+    in_loop[header] = true;
+    let mut pending = latches.to_vec();
+    while let Some(id) = pending.pop() {
+        if in_loop[id] || !dominators.contains(id) {
+            continue;
+        }
+        in_loop[id] = true;
+        pending.extend(cfg.predecessors(id).iter().map(|edge| edge.from));
+    }
+    (0..in_loop.len()).filter(|&id| in_loop[id]).collect()
+}
+
+/// Finds the retreating edges of a depth-first search from the entry whose
+/// target does not dominate their source. A graph is reducible exactly when
+/// there are none, whichever order the search visits successors in.
+fn irreducible_edges(cfg: &ControlFlowGraph, dominators: &DominatorTree) -> Vec<Edge> {
+    let count = cfg.blocks().len();
+    let mut edges = vec![];
+    if count == 0 {
+        return edges;
+    }
+    let mut visited = vec![false; count];
+    let mut on_stack = vec![false; count];
+    let mut pending = vec![(0, 0)];
+    visited[0] = true;
+    on_stack[0] = true;
+    while let Some((id, next)) = pending.pop() {
+        let successors = cfg.successors(id);
+        if next < successors.len() {
+            pending.push((id, next + 1));
+            let edge = successors[next];
+            if on_stack[edge.to] {
+                if !dominators.dominates(edge.to, edge.from) {
+                    edges.push(*edge);
+                }
+            } else if !visited[edge.to] {
+                visited[edge.to] = true;
+                on_stack[edge.to] = true;
+                pending.push((edge.to, 0));
+            }
+        } else {
+            on_stack[id] = false;
+        }
+    }
+    edges
+}
@@ -0,0 +1,914 @@
+//! Instruction semantics shared by the verifiers.
+//!
+//! Both verifiers simulate each instruction on the verification types of the
+//! locals and operand stack. They differ in where the frames at branch
+//! targets come from: the type checker reads them from the `StackMapTable`,
+//! while the type inferencer computes them by merging.
+use classfile::{Bytecode, ClassFile, CodeAttribute, Constant, ConstantPool, FieldType,
+                InstructionList, MethodDescriptor, MethodInfo};
+
+use hierarchy::ClassHierarchy;
+use verifier::{VerificationType, VerifyError};
+use super::{Error, Result};
+
+use self::VerificationType as Type;
+
+const ACC_PROTECTED: u16 = 0x0004;
+
+/// The types of the locals and operand stack before an instruction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TypeFrame {
+    /// One type per local, with `Top` after each `long` and `double`.
+    pub locals: Vec<Type>,
+    /// One type per stack slot, with `Top` after each `long` and `double`.
+    pub stack: Vec<Type>,
+    /// Set while `this` is uninitialized in a constructor.
+    pub this_uninit: bool,
+}
+
+/// A field or method reference.
+struct MemberRef<'a> {
+    class: &'a str,
+    name: &'a str,
+    descriptor: &'a str,
+}
+
+/// A method being verified. Each verifier adds its own way of finding the
+/// frame at each instruction in its module.
+pub struct MethodVerifier<'a> {
+    pub class_name: &'a str,
+    pub cp: &'a ConstantPool,
+    pub hierarchy: &'a ClassHierarchy,
+    pub method: &'a MethodInfo,
+    pub method_name: &'a str,
+    pub descriptor: &'a str,
+    pub signature: MethodDescriptor,
+    /// The type returned by the method, or `None` if it returns `void`.
+    pub ret: Option<Type>,
+    pub is_init: bool,
+    pub max_stack: usize,
+    pub max_locals: usize,
+    pub instructions: InstructionList,
+    /// The instruction being verified, for error reporting.
+    pub pc: Option<usize>,
+}
+
+impl<'a> MethodVerifier<'a> {
+    pub fn new(class: &'a ClassFile,
+               hierarchy: &'a ClassHierarchy,
+               method: &'a MethodInfo,
+               code: &CodeAttribute)
+               -> Result<MethodVerifier<'a>> {
+        let cp = &class.constants;
+        let class_name = class.this_class_name();
+        let method_name = cp[method.name_index].as_utf8();
+        let descriptor = cp[method.descriptor_index].as_utf8();
+        let fail = |message: String| {
+            Err(Error::Verify(VerifyError {
+                class: class_name.to_string(),
+                method: method_name.to_string(),
+                descriptor: descriptor.to_string(),
+                pc: None,
+                message: message,
+            }))
+        };
+        let signature = match MethodDescriptor::parse(descriptor) {
+            Ok(signature) => signature,
+            Err(_) => return fail("Illegal method signature".to_string()),
+        };
+        let ret = signature.ret.as_ref().map(Type::of);
+        let is_init = method_name == "<init>";
+        if is_init && ret.is_some() {
+            return fail("Method <init> must return void".to_string());
+        }
+        let instructions = match code.instruction_list() {
+            Ok(instructions) => instructions,
+            Err(err) => return fail(err.to_string()),
+        };
+        Ok(MethodVerifier {
+            class_name: class_name,
+            cp: cp,
+            hierarchy: hierarchy,
+            method: method,
+            method_name: method_name,
+            descriptor: descriptor,
+            signature: signature,
+            ret: ret,
+            is_init: is_init,
+            max_stack: code.max_stack as usize,
+            max_locals: code.max_locals as usize,
+            instructions: instructions,
+            pc: None,
+        })
+    }
+
+    pub fn fail<T>(&self, message: &str) -> Result<T> {
+        self.fail_at(self.pc, message)
+    }
+
+    /// Fails at `pc` rather than at the instruction being verified.
+    pub fn fail_at<T>(&self, pc: Option<usize>, message: &str) -> Result<T> {
+        Err(Error::Verify(VerifyError {
+            class: self.class_name.to_string(),
+            method: self.method_name.to_string(),
+            descriptor: self.descriptor.to_string(),
+            pc: pc,
+            message: message.to_string(),
+        }))
+    }
+
+    /// The frame on entry to the method.
+    pub fn initial_frame(&self) -> Result<TypeFrame> {
+        let mut frame = TypeFrame {
+            locals: vec![],
+            stack: vec![],
+            this_uninit: false,
+        };
+        if !self.method.access_flags.is_static() {
+            if self.is_init && self.class_name != "java/lang/Object" {
+                frame.locals.push(Type::UninitializedThis);
+                frame.this_uninit = true;
+            } else {
+                frame.locals.push(Type::Object(self.class_name.to_string()));
+            }
+        }
+        for param in &self.signature.params {
+            push_type(&mut frame.locals, Type::of(param));
+        }
+        if frame.locals.len() > self.max_locals {
+            return self.fail("Arguments can't fit into locals");
+        }
+        frame.locals.resize(self.max_locals, Type::Top);
+        Ok(frame)
+    }
+
+    /// The handlers covering each instruction, with the types of the
+    /// exceptions they catch.
+    pub fn handlers(&self, code: &CodeAttribute) -> Result<Vec<Vec<(usize, Type)>>> {
+        let instructions = &self.instructions;
+        let count = instructions.len();
+        let length = instructions.code_length();
+        let throwable = Type::Object("java/lang/Throwable".to_string());
+        let mut handlers = vec![vec![]; count];
+        for handler in &code.exception_table {
+            let (start, end, handler_pc) = (handler.start_pc as usize,
+                                            handler.end_pc as usize,
+                                            handler.handler_pc as usize);
+            let valid = start < end && instructions.is_boundary(start) &&
+                        (end == length || instructions.is_boundary(end)) &&
+                        instructions.is_boundary(handler_pc);
+            if !valid {
+                return self.fail(&format!("Illegal exception table range in handler {}",
+                                          handler_pc));
+            }
+            let catch_type = if handler.catch_type == 0 {
+                throwable.clone()
+            } else {
+                match self.cp.class_name(handler.catch_type) {
+                    Some(name) => Type::Object(name.clone()),
+                    None => return self.fail("Illegal catch type in exception table"),
+                }
+            };
+            if !try!(catch_type.is_assignable_to(&throwable, self.hierarchy)) {
+                return self.fail(&format!("Catch type is not a subclass of Throwable in \
+                                           exception handler {}",
+                                          handler_pc));
+            }
+            let start = instructions.index_of(start).unwrap();
+            let end = instructions.index_of(end).unwrap_or(count);
+            for covered in &mut handlers[start..end] {
+                covered.push((handler_pc, catch_type.clone()));
+            }
+        }
+        Ok(handlers)
+    }
+
+    /// The class instantiated by the `new` instruction at `pc`.
+    pub fn new_class(&self, pc: usize) -> Option<&'a str> {
+        let cp = self.cp;
+        match self.instructions.index_of(pc).and_then(|index| self.instructions.get(index)) {
+            Some(&Bytecode::new { index }) => cp.class_name(index).map(|name| name.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn push(&self, frame: &mut TypeFrame, value: Type) -> Result<()> {
+        push_type(&mut frame.stack, value);
+        if frame.stack.len() > self.max_stack {
+            return self.fail("Exceeded max stack size");
+        }
+        Ok(())
+    }
+
+    /// Pops a value of any type, returning it and its position on the stack.
+    fn pop_any(&self, frame: &mut TypeFrame, size: usize) -> Result<(Type, usize)> {
+        if frame.stack.len() < size {
+            return self.fail("Attempt to pop empty stack");
+        }
+        let position = frame.stack.len() - size;
+        let value = frame.stack[position].clone();
+        let split = if size == 2 {
+            frame.stack[position + 1] != Type::Top || value.size() != 2
+        } else {
+            value == Type::Top
+        };
+        if split {
+            return self.fail(&format!("Bad type on operand stack: Type {} (current frame, \
+                                       stack[{}]) is not a category {} value",
+                                      value,
+                                      position,
+                                      size));
+        }
+        frame.stack.truncate(position);
+        Ok((value, position))
+    }
+
+    fn pop(&self, frame: &mut TypeFrame, expected: &Type) -> Result<Type> {
+        let (value, position) = try!(self.pop_any(frame, expected.size()));
+        if !try!(value.is_assignable_to(expected, self.hierarchy)) {
+            return self.fail(&format!("Bad type on operand stack: Type {} (current frame, \
+                                       stack[{}]) is not assignable to {}",
+                                      value,
+                                      position,
+                                      expected));
+        }
+        Ok(value)
+    }
+
+    fn pop_reference(&self, frame: &mut TypeFrame) -> Result<Type> {
+        let (value, position) = try!(self.pop_any(frame, 1));
+        if !value.is_reference() {
+            return self.fail(&format!("Bad type on operand stack: Type {} (current frame, \
+                                       stack[{}]) is not assignable to reference type",
+                                      value,
+                                      position));
+        }
+        Ok(value)
+    }
+
+    /// Pops an array whose descriptor is one of `descriptors`, or any array
+    /// of references if `descriptors` is empty.
+    fn pop_array(&self, frame: &mut TypeFrame, descriptors: &[&str]) -> Result<Type> {
+        let (value, position) = try!(self.pop_any(frame, 1));
+        let valid = match value {
+            Type::Null => true,
+            Type::Object(ref name) if descriptors.is_empty() => {
+                name.starts_with("[L") || name.starts_with("[[")
+            }
+            Type::Object(ref name) => descriptors.contains(&name.as_str()),
+            _ => false,
+        };
+        if !valid {
+            return self.fail(&format!("Bad type on operand stack: Type {} (current frame, \
+                                       stack[{}]) is not an array of the right type",
+                                      value,
+                                      position));
+        }
+        Ok(value)
+    }
+
+    fn load(&self, frame: &mut TypeFrame, index: usize, expected: &Type) -> Result<()> {
+        let value = try!(self.local(frame, index, expected));
+        self.push(frame, value)
+    }
+
+    /// The type of the local at `index`, which must be assignable to
+    /// `expected`.
+    fn local(&self, frame: &TypeFrame, index: usize, expected: &Type) -> Result<Type> {
+        if index + expected.size() > frame.locals.len() {
+            return self.fail(&format!("Illegal local variable number {}", index));
+        }
+        let value = frame.locals[index].clone();
+        let valid = match *expected {
+            Type::Null => value.is_reference(),
+            _ => try!(value.is_assignable_to(expected, self.hierarchy)),
+        };
+        if !valid {
+            return self.fail(&format!("Bad local variable type: Type {} (current frame, \
+                                       locals[{}]) is not assignable to {}",
+                                      value,
+                                      index,
+                                      match *expected {
+                                          Type::Null => "reference type".to_string(),
+                                          ref expected => expected.to_string(),
+                                      }));
+        }
+        Ok(value)
+    }
+
+    fn store(&self, frame: &mut TypeFrame, index: usize, expected: &Type) -> Result<()> {
+        let value = match (expected, frame.stack.last()) {
+            // Subroutines keep their return address in a local.
+            (&Type::Null, Some(&Type::ReturnAddress(_))) => try!(self.pop_any(frame, 1)).0,
+            (&Type::Null, _) => try!(self.pop_reference(frame)),
+            _ => try!(self.pop(frame, expected)),
+        };
+        self.set_local(frame, index, value)
+    }
+
+    fn set_local(&self, frame: &mut TypeFrame, index: usize, value: Type) -> Result<()> {
+        let size = value.size();
+        if index + size > frame.locals.len() {
+            return self.fail(&format!("Illegal local variable number {}", index));
+        }
+        // Overwriting the second slot of a long or double invalidates it.
+        if index > 0 && frame.locals[index - 1].size() == 2 {
+            frame.locals[index - 1] = Type::Top;
+        }
+        frame.locals[index] = value;
+        if size == 2 {
+            frame.locals[index + 1] = Type::Top;
+        }
+        Ok(())
+    }
+
+    /// Resolves the field or method reference at `index`.
+    fn member(&self, index: u16) -> Result<MemberRef<'a>> {
+        let cp = self.cp;
+        let entity = match cp.get(index) {
+            Some(&Constant::Fieldref(ref entity)) |
+            Some(&Constant::Methodref(ref entity)) |
+            Some(&Constant::InterfaceMethodref(ref entity)) => entity,
+            _ => return self.fail(&format!("Illegal constant pool index {}", index)),
+        };
+        let class = cp.class_name(entity.class_index);
+        let name_and_type = match cp.get(entity.name_and_type_index) {
+            Some(&Constant::NameAndType(ref name_and_type)) => Some(name_and_type),
+            _ => None,
+        };
+        let utf8 = |index| match cp.get(index) {
+            Some(&Constant::Utf8(ref value)) => Some(value.as_str()),
+            _ => None,
+        };
+        match (class, name_and_type) {
+            (Some(class), Some(name_and_type)) => {
+                match (utf8(name_and_type.name_index), utf8(name_and_type.descriptor_index)) {
+                    (Some(name), Some(descriptor)) => {
+                        Ok(MemberRef {
+                            class: class,
+                            name: name,
+                            descriptor: descriptor,
+                        })
+                    }
+                    _ => self.fail(&format!("Illegal constant pool index {}", index)),
+                }
+            }
+            _ => self.fail(&format!("Illegal constant pool index {}", index)),
+        }
+    }
+
+    fn class_operand(&self, index: u16) -> Result<&'a str> {
+        let cp = self.cp;
+        match cp.class_name(index) {
+            Some(name) => Ok(name),
+            None => self.fail(&format!("Illegal constant pool index {} for class", index)),
+        }
+    }
+
+    /// Checks the access of a protected member of a superclass in another
+    /// package, which is only allowed through a reference to this class or
+    /// one of its subclasses (JVMS 4.10.1.8).
+    fn check_protected(&self, member: &MemberRef, receiver: &Type, insn: &Bytecode) -> Result<()> {
+        if member.class == self.class_name || package(member.class) == package(self.class_name) ||
+           member.class.starts_with('[') {
+            return Ok(());
+        }
+        if !try!(self.hierarchy.is_subclass(self.class_name, member.class)) {
+            return Ok(());
+        }
+        let access = try!(self.hierarchy.member_access(member.class, member.name, member.descriptor));
+        match access {
+            Some(flags) if flags & ACC_PROTECTED != 0 => {}
+            _ => return Ok(()),
+        }
+        let this = Type::Object(self.class_name.to_string());
+        if try!(receiver.is_assignable_to(&this, self.hierarchy)) {
+            Ok(())
+        } else {
+            self.fail(&format!("Bad access to protected data in {}", insn.info().mnemonic()))
+        }
+    }
+
+    fn field_type(&self, member: &MemberRef) -> Result<Type> {
+        match FieldType::parse(member.descriptor) {
+            Ok(field_type) => Ok(Type::of(&field_type)),
+            Err(_) => self.fail(&format!("Illegal field signature {}", member.descriptor)),
+        }
+    }
+
+    /// Simulates `insn` on `frame`. Branch targets and exception handlers are
+    /// left to the caller, as are `jsr` and `ret`, which are rejected.
+    pub fn execute(&self, frame: &mut TypeFrame, pc: usize, insn: &Bytecode) -> Result<()> {
+        let int = Type::Integer;
+        let float = Type::Float;
+        let long = Type::Long;
+        let double = Type::Double;
+        // `Null` stands for "any reference" when loading and storing locals.
+        let reference = Type::Null;
+
+        let simple: Option<(&[&Type], Option<&Type>)> = match *insn {
+            Bytecode::nop => Some((&[], None)),
+            Bytecode::aconst_null => Some((&[], Some(&Type::Null))),
+            Bytecode::iconst_i(_) |
+            Bytecode::bipush { .. } |
+            Bytecode::sipush { .. } => Some((&[], Some(&int))),
+            Bytecode::lconst_l(_) => Some((&[], Some(&long))),
+            Bytecode::fconst_f(_) => Some((&[], Some(&float))),
+            Bytecode::dconst_d(_) => Some((&[], Some(&double))),
+
+            Bytecode::iadd | Bytecode::isub | Bytecode::imul | Bytecode::idiv |
+            Bytecode::irem | Bytecode::ishl | Bytecode::ishr | Bytecode::iushr |
+            Bytecode::iand | Bytecode::ior | Bytecode::ixor => Some((&[&int, &int], Some(&int))),
+            Bytecode::ladd | Bytecode::lsub | Bytecode::lmul | Bytecode::ldiv |
+            Bytecode::lrem | Bytecode::land | Bytecode::lor | Bytecode::lxor => {
+                Some((&[&long, &long], Some(&long)))
+            }
+            Bytecode::lshl | Bytecode::lshr | Bytecode::lushr => Some((&[&int, &long], Some(&long))),
+            Bytecode::fadd | Bytecode::fsub | Bytecode::fmul | Bytecode::fdiv |
+            Bytecode::frem => Some((&[&float, &float], Some(&float))),
+            Bytecode::dadd | Bytecode::dsub | Bytecode::dmul | Bytecode::ddiv |
+            Bytecode::drem => Some((&[&double, &double], Some(&double))),
+            Bytecode::ineg | Bytecode::i2b | Bytecode::i2c | Bytecode::i2s => {
+                Some((&[&int], Some(&int)))
+            }
+            Bytecode::lneg => Some((&[&long], Some(&long))),
+            Bytecode::fneg => Some((&[&float], Some(&float))),
+            Bytecode::dneg => Some((&[&double], Some(&double))),
+            Bytecode::i2l => Some((&[&int], Some(&long))),
+            Bytecode::i2f => Some((&[&int], Some(&float))),
+            Bytecode::i2d => Some((&[&int], Some(&double))),
+            Bytecode::l2i => Some((&[&long], Some(&int))),
+            Bytecode::l2f => Some((&[&long], Some(&float))),
+            Bytecode::l2d => Some((&[&long], Some(&double))),
+            Bytecode::f2i => Some((&[&float], Some(&int))),
+            Bytecode::f2l => Some((&[&float], Some(&long))),
+            Bytecode::f2d => Some((&[&float], Some(&double))),
+            Bytecode::d2i => Some((&[&double], Some(&int))),
+            Bytecode::d2l => Some((&[&double], Some(&long))),
+            Bytecode::d2f => Some((&[&double], Some(&float))),
+            Bytecode::lcmp => Some((&[&long, &long], Some(&int))),
+            Bytecode::fcmpl | Bytecode::fcmpg => Some((&[&float, &float], Some(&int))),
+            Bytecode::dcmpl | Bytecode::dcmpg => Some((&[&double, &double], Some(&int))),
+
+            Bytecode::ifeq { .. } | Bytecode::ifne { .. } | Bytecode::iflt { .. } |
+            Bytecode::ifge { .. } | Bytecode::ifgt { .. } | Bytecode::ifle { .. } |
+            Bytecode::tableswitch { .. } | Bytecode::lookupswitch { .. } => {
+                Some((&[&int], None))
+            }
+            Bytecode::if_icmpeq { .. } | Bytecode::if_icmpne { .. } |
+            Bytecode::if_icmplt { .. } | Bytecode::if_icmpge { .. } |
+            Bytecode::if_icmpgt { .. } | Bytecode::if_icmple { .. } => {
+                Some((&[&int, &int], None))
+            }
+            Bytecode::goto { .. } | Bytecode::goto_w { .. } => Some((&[], None)),
+            _ => None,
+        };
+        if let Some((pops, push)) = simple {
+            for expected in pops {
+                try!(self.pop(frame, expected));
+            }
+            if let Some(value) = push {
+                try!(self.push(frame, value.clone()));
+            }
+            return Ok(());
+        }
+
+        match *insn {
+            Bytecode::ldc { .. } |
+            Bytecode::ldc_w { .. } |
+            Bytecode::ldc2_w { .. } => {
+                let index = insn.constant_index().unwrap();
+                let wide = match *insn {
+                    Bytecode::ldc2_w { .. } => true,
+                    _ => false,
+                };
+                let value = match (self.cp.get(index), wide) {
+                    (Some(&Constant::Integer(_)), false) => Type::Integer,
+                    (Some(&Constant::Float(_)), false) => Type::Float,
+                    (Some(&Constant::String(_)), false) => object("java/lang/String"),
+                    (Some(&Constant::Class(_)), false) => object("java/lang/Class"),
+                    (Some(&Constant::MethodType { .. }), false) => {
+                        object("java/lang/invoke/MethodType")
+                    }
+                    (Some(&Constant::MethodHandle { .. }), false) => {
+                        object("java/lang/invoke/MethodHandle")
+                    }
+                    (Some(&Constant::Long(_)), true) => Type::Long,
+                    (Some(&Constant::Double(_)), true) => Type::Double,
+                    _ => return self.fail(&format!("Invalid index {} in {}", index,
+                                                   insn.info().mnemonic())),
+                };
+                self.push(frame, value)
+            }
+
+            Bytecode::iload { index } => self.load(frame, index as usize, &int),
+            Bytecode::iload_n(index) => self.load(frame, index as usize, &int),
+            Bytecode::wide_iload { index } => self.load(frame, index as usize, &int),
+            Bytecode::fload { index } => self.load(frame, index as usize, &float),
+            Bytecode::fload_n(index) => self.load(frame, index as usize, &float),
+            Bytecode::wide_fload { index } => self.load(frame, index as usize, &float),
+            Bytecode::lload { index } => self.load(frame, index as usize, &long),
+            Bytecode::lload_n(index) => self.load(frame, index as usize, &long),
+            Bytecode::wide_lload { index } => self.load(frame, index as usize, &long),
+            Bytecode::dload { byte } => self.load(frame, byte as usize, &double),
+            Bytecode::dload_n(index) => self.load(frame, index as usize, &double),
+            Bytecode::wide_dload { index } => self.load(frame, index as usize, &double),
+            Bytecode::aload { index } => self.load(frame, index as usize, &reference),
+            Bytecode::aload_n(index) => self.load(frame, index as usize, &reference),
+            Bytecode::wide_aload { index } => self.load(frame, index as usize, &reference),
+
+            Bytecode::istore { index } => self.store(frame, index as usize, &int),
+            Bytecode::istore_n(index) => self.store(frame, index as usize, &int),
+            Bytecode::wide_istore { index } => self.store(frame, index as usize, &int),
+            Bytecode::fstore { index } => self.store(frame, index as usize, &float),
+            Bytecode::fstore_n(index) => self.store(frame, index as usize, &float),
+            Bytecode::wide_fstore { index } => self.store(frame, index as usize, &float),
+            Bytecode::lstore { index } => self.store(frame, index as usize, &long),
+            Bytecode::lstore_n(index) => self.store(frame, index as usize, &long),
+            Bytecode::wide_lstore { index } => self.store(frame, index as usize, &long),
+            Bytecode::dstore { index } => self.store(frame, index as usize, &double),
+            Bytecode::dstore_n(index) => self.store(frame, index as usize, &double),
+            Bytecode::wide_dstore { index } => self.store(frame, index as usize, &double),
+            Bytecode::astore { index } => self.store(frame, index as usize, &reference),
+            Bytecode::astore_n(index) => self.store(frame, index as usize, &reference),
+            Bytecode::wide_astore { index } => self.store(frame, index as usize, &reference),
+
+            Bytecode::iinc { index, .. } => self.increment(frame, index as usize),
+            Bytecode::wide_iinc { index, .. } => self.increment(frame, index as usize),
+
+            Bytecode::iaload => self.array_load(frame, &["[I"], int),
+            Bytecode::baload => self.array_load(frame, &["[B", "[Z"], int),
+            Bytecode::caload => self.array_load(frame, &["[C"], int),
+            Bytecode::saload => self.array_load(frame, &["[S"], int),
+            Bytecode::laload => self.array_load(frame, &["[J"], long),
+            Bytecode::faload => self.array_load(frame, &["[F"], float),
+            Bytecode::daload => self.array_load(frame, &["[D"], double),
+            Bytecode::aaload => {
+                try!(self.pop(frame, &int));
+                let array = try!(self.pop_array(frame, &[]));
+                self.push(frame, array.component().unwrap())
+            }
+            Bytecode::iastore => self.array_store(frame, &["[I"], &int),
+            Bytecode::bastore => self.array_store(frame, &["[B", "[Z"], &int),
+            Bytecode::castore => self.array_store(frame, &["[C"], &int),
+            Bytecode::sastore => self.array_store(frame, &["[S"], &int),
+            Bytecode::lastore => self.array_store(frame, &["[J"], &long),
+            Bytecode::fastore => self.array_store(frame, &["[F"], &float),
+            Bytecode::dastore => self.array_store(frame, &["[D"], &double),
+            Bytecode::aastore => {
+                try!(self.pop_reference(frame));
+                try!(self.pop(frame, &int));
+                self.pop_array(frame, &[]).map(|_| ())
+            }
+
+            Bytecode::pop => self.pop_any(frame, 1).map(|_| ()),
+            Bytecode::pop2 => {
+                if frame.stack.last() == Some(&Type::Top) {
+                    self.pop_any(frame, 2).map(|_| ())
+                } else {
+                    try!(self.pop_any(frame, 1));
+                    self.pop_any(frame, 1).map(|_| ())
+                }
+            }
+            Bytecode::dup => self.shuffle(frame, 1, 0),
+            Bytecode::dup_x1 => self.shuffle(frame, 1, 1),
+            Bytecode::dup_x2 => self.shuffle(frame, 1, 2),
+            Bytecode::dup2 => self.shuffle(frame, 2, 0),
+            Bytecode::dup2_x1 => self.shuffle(frame, 2, 1),
+            Bytecode::dup2_x2 => self.shuffle(frame, 2, 2),
+            Bytecode::swap => {
+                let (value1, _) = try!(self.pop_any(frame, 1));
+                let (value2, _) = try!(self.pop_any(frame, 1));
+                try!(self.push(frame, value1));
+                self.push(frame, value2)
+            }
+
+            Bytecode::if_acmpeq { .. } |
+            Bytecode::if_acmpne { .. } => {
+                try!(self.pop_reference(frame));
+                self.pop_reference(frame).map(|_| ())
+            }
+            Bytecode::ifnull { .. } |
+            Bytecode::ifnonnull { .. } |
+            Bytecode::monitorenter |
+            Bytecode::monitorexit => self.pop_reference(frame).map(|_| ()),
+
+            Bytecode::ireturn | Bytecode::lreturn | Bytecode::freturn | Bytecode::dreturn |
+            Bytecode::areturn => {
+                let expected = match self.ret {
+                    Some(ref expected) => expected,
+                    None => return self.fail("Method does not expect a return value"),
+                };
+                let matches = match (insn, expected) {
+                    (&Bytecode::ireturn, &Type::Integer) |
+                    (&Bytecode::lreturn, &Type::Long) |
+                    (&Bytecode::freturn, &Type::Float) |
+                    (&Bytecode::dreturn, &Type::Double) |
+                    (&Bytecode::areturn, &Type::Object(_)) => true,
+                    _ => false,
+                };
+                if !matches {
+                    return self.fail("Wrong return type in function");
+                }
+                self.pop(frame, expected).map(|_| ())
+            }
+            Bytecode::Return => {
+                if self.ret.is_some() {
+                    return self.fail("Method expects a return value");
+                }
+                if self.is_init && frame.this_uninit {
+                    return self.fail("Constructor must call super() or this() before return");
+                }
+                Ok(())
+            }
+            Bytecode::athrow => {
+                self.pop(frame, &object("java/lang/Throwable")).map(|_| ())
+            }
+
+            Bytecode::getstatic { index } => {
+                let member = try!(self.member(index));
+                let value = try!(self.field_type(&member));
+                self.push(frame, value)
+            }
+            Bytecode::putstatic { index } => {
+                let member = try!(self.member(index));
+                let value = try!(self.field_type(&member));
+                self.pop(frame, &value).map(|_| ())
+            }
+            Bytecode::getfield { index } => {
+                let member = try!(self.member(index));
+                let value = try!(self.field_type(&member));
+                let receiver = try!(self.pop(frame, &object(member.class)));
+                try!(self.check_protected(&member, &receiver, insn));
+                self.push(frame, value)
+            }
+            Bytecode::putfield { index } => {
+                let member = try!(self.member(index));
+                let value = try!(self.field_type(&member));
+                try!(self.pop(frame, &value));
+                // A constructor may set its own class's fields before calling
+                // the superclass constructor.
+                if frame.stack.last() == Some(&Type::UninitializedThis) &&
+                   member.class == self.class_name {
+                    frame.stack.pop();
+                    return Ok(());
+                }
+                let receiver = try!(self.pop(frame, &object(member.class)));
+                self.check_protected(&member, &receiver, insn)
+            }
+
+            Bytecode::invokevirtual { index } |
+            Bytecode::invokespecial { index } |
+            Bytecode::invokestatic { index } |
+            Bytecode::invokeinterface { index, .. } => self.invoke(frame, insn, index),
+            Bytecode::invokedynamic { index } => {
+                let descriptor = match insn.method_descriptor(self.cp).unwrap() {
+                    Ok(descriptor) => descriptor,
+                    Err(_) => return self.fail(&format!("Illegal constant pool index {}", index)),
+                };
+                try!(self.pop_arguments(frame, &descriptor));
+                match descriptor.ret {
+                    Some(ref ret) => self.push(frame, Type::of(ret)),
+                    None => Ok(()),
+                }
+            }
+
+            Bytecode::new { index } => {
+                let class = try!(self.class_operand(index));
+                if class.starts_with('[') {
+                    return self.fail("Illegal new instruction");
+                }
+                let value = Type::Uninitialized(pc);
+                if frame.stack.contains(&value) {
+                    return self.fail("Uninitialized object exists on the operand stack");
+                }
+                for local in &mut frame.locals {
+                    if *local == value {
+                        *local = Type::Top;
+                    }
+                }
+                self.push(frame, value)
+            }
+            Bytecode::newarray { atype } => {
+                let descriptor = match atype {
+                    4 => "[Z",
+                    5 => "[C",
+                    6 => "[F",
+                    7 => "[D",
+                    8 => "[B",
+                    9 => "[S",
+                    10 => "[I",
+                    11 => "[J",
+                    _ => return self.fail("Illegal newarray instruction"),
+                };
+                try!(self.pop(frame, &int));
+                self.push(frame, object(descriptor))
+            }
+            Bytecode::anewarray { index } => {
+                let class = try!(self.class_operand(index));
+                try!(self.pop(frame, &int));
+                let descriptor = if class.starts_with('[') {
+                    format!("[{}", class)
+                } else {
+                    format!("[L{};", class)
+                };
+                self.push(frame, Type::Object(descriptor))
+            }
+            Bytecode::multianewarray { index, dimensions } => {
+                let class = try!(self.class_operand(index));
+                let depth = class.chars().take_while(|&c| c == '[').count();
+                if dimensions == 0 || dimensions as usize > depth {
+                    return self.fail("Illegal dimension in multianewarray");
+                }
+                for _ in 0..dimensions {
+                    try!(self.pop(frame, &int));
+                }
+                self.push(frame, object(class))
+            }
+            Bytecode::arraylength => {
+                let (value, position) = try!(self.pop_any(frame, 1));
+                if !value.is_array() {
+                    return self.fail(&format!("Bad type on operand stack in arraylength: Type \
+                                               {} (current frame, stack[{}]) is not an array",
+                                              value,
+                                              position));
+                }
+                self.push(frame, int)
+            }
+            Bytecode::checkcast { index } => {
+                let class = try!(self.class_operand(index));
+                try!(self.pop_reference(frame));
+                self.push(frame, object(class))
+            }
+            Bytecode::instanceof { index } => {
+                try!(self.class_operand(index));
+                try!(self.pop_reference(frame));
+                self.push(frame, int)
+            }
+
+            Bytecode::jsr { .. } |
+            Bytecode::jsr_w { .. } |
+            Bytecode::ret { .. } |
+            Bytecode::wide_ret { .. } => {
+                self.fail(&format!("Illegal instruction {} in a method with a StackMapTable",
+                                   insn.info().mnemonic()))
+            }
+            _ => self.fail(&format!("Illegal instruction {}", insn.info().mnemonic())),
+        }
+    }
+
+    fn increment(&self, frame: &mut TypeFrame, index: usize) -> Result<()> {
+        self.local(frame, index, &Type::Integer).map(|_| ())
+    }
+
+    fn array_load(&self, frame: &mut TypeFrame, descriptors: &[&str], value: Type) -> Result<()> {
+        try!(self.pop(frame, &Type::Integer));
+        try!(self.pop_array(frame, descriptors));
+        self.push(frame, value)
+    }
+
+    fn array_store(&self, frame: &mut TypeFrame, descriptors: &[&str], value: &Type) -> Result<()> {
+        try!(self.pop(frame, value));
+        try!(self.pop(frame, &Type::Integer));
+        self.pop_array(frame, descriptors).map(|_| ())
+    }
+
+    /// Duplicates the top `count` slots, inserting the copy below the
+    /// `depth` slots beneath them. Slots are taken and inserted as whole
+    /// values, so a `long` or `double` is never split.
+    fn shuffle(&self, frame: &mut TypeFrame, count: usize, depth: usize) -> Result<()> {
+        let mut top = vec![];
+        while top.len() < count {
+            let size = if frame.stack.last() == Some(&Type::Top) { 2 } else { 1 };
+            if top.len() + size > count {
+                return self.fail("Bad type on operand stack: stack shuffle splits a long or double");
+            }
+            let (value, _) = try!(self.pop_any(frame, size));
+            top.insert(0, value);
+            if size == 2 {
+                top.insert(1, Type::Top);
+            }
+        }
+        let mut below = vec![];
+        while below.len() < depth {
+            let size = if frame.stack.last() == Some(&Type::Top) { 2 } else { 1 };
+            if below.len() + size > depth {
+                return self.fail("Bad type on operand stack: stack shuffle splits a long or double");
+            }
+            let (value, _) = try!(self.pop_any(frame, size));
+            below.insert(0, value);
+            if size == 2 {
+                below.insert(1, Type::Top);
+            }
+        }
+        frame.stack.extend(top.iter().cloned());
+        frame.stack.extend(below);
+        frame.stack.extend(top);
+        if frame.stack.len() > self.max_stack {
+            return self.fail("Exceeded max stack size");
+        }
+        Ok(())
+    }
+
+    fn pop_arguments(&self, frame: &mut TypeFrame, descriptor: &MethodDescriptor) -> Result<()> {
+        for param in descriptor.params.iter().rev() {
+            try!(self.pop(frame, &Type::of(param)));
+        }
+        Ok(())
+    }
+
+    fn invoke(&self, frame: &mut TypeFrame, insn: &Bytecode, index: u16) -> Result<()> {
+        let member = try!(self.member(index));
+        let descriptor = match MethodDescriptor::parse(member.descriptor) {
+            Ok(descriptor) => descriptor,
+            Err(_) => return self.fail(&format!("Illegal method signature {}", member.descriptor)),
+        };
+        let is_init = member.name == "<init>";
+        if member.name.starts_with('<') && !(is_init && *insn == Bytecode::invokespecial { index: index }) {
+            return self.fail(&format!("Illegal call to internal method {}", member.name));
+        }
+        if let Bytecode::invokeinterface { count, .. } = *insn {
+            if count as u16 != descriptor.arg_slots(false) {
+                return self.fail("Inconsistent args count operand in invokeinterface");
+            }
+        }
+        try!(self.pop_arguments(frame, &descriptor));
+
+        match *insn {
+            Bytecode::invokestatic { .. } => {}
+            Bytecode::invokespecial { .. } if is_init => {
+                if descriptor.ret.is_some() {
+                    return self.fail("Method <init> must return void");
+                }
+                let receiver = try!(self.pop_reference(frame));
+                let initialized = match receiver {
+                    Type::UninitializedThis => {
+                        let super_class = try!(self.hierarchy.super_class(self.class_name));
+                        if member.class != self.class_name &&
+                           super_class.as_ref().map(|name| name.as_str()) != Some(member.class) {
+                            return self.fail("Bad <init> method call");
+                        }
+                        frame.this_uninit = false;
+                        object(self.class_name)
+                    }
+                    Type::Uninitialized(new_pc) => {
+                        if self.new_class(new_pc) != Some(member.class) {
+                            return self.fail("Call to wrong <init> method");
+                        }
+                        object(member.class)
+                    }
+                    _ => return self.fail("Bad operand type when invoking <init>"),
+                };
+                for value in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+                    if *value == receiver {
+                        *value = initialized.clone();
+                    }
+                }
+            }
+            Bytecode::invokespecial { .. } => {
+                let this = object(self.class_name);
+                let receiver = try!(self.pop_reference(frame));
+                if !try!(receiver.is_assignable_to(&this, self.hierarchy)) {
+                    return self.fail("Bad invokespecial instruction: current class isn't \
+                                      assignable to reference class");
+                }
+            }
+            Bytecode::invokeinterface { .. } => {
+                try!(self.pop(frame, &object("java/lang/Object")));
+            }
+            _ => {
+                let receiver = try!(self.pop(frame, &object(member.class)));
+                let array_clone = receiver.is_array() && member.name == "clone";
+                if !array_clone {
+                    try!(self.check_protected(&member, &receiver, insn));
+                }
+            }
+        }
+
+        match descriptor.ret {
+            Some(ref ret) => self.push(frame, Type::of(ret)),
+            None => Ok(()),
+        }
+    }}
+
+/// Appends `value` to a list of slots, followed by `Top` for a `long` or
+/// `double`.
+pub fn push_type(slots: &mut Vec<Type>, value: Type) {
+    let size = value.size();
+    slots.push(value);
+    if size == 2 {
+        slots.push(Type::Top);
+    }
+}
+
+pub fn object(name: &str) -> Type {
+    Type::Object(name.to_string())
+}
+
+/// The package of a class, as the part of its name before the last `/`.
+fn package(class: &str) -> &str {
+    match class.rfind('/') {
+        Some(index) => &class[..index],
+        None => "",
+    }
+}
@@ -0,0 +1,1717 @@
+//! Optimization of method bytecode.
+//!
+//! `optimize` runs a pipeline of passes over the code of every method of a
+//! class until none of them finds anything more to do. The passes fold
+//! arithmetic on constants, replace branches on constants, thread jumps to
+//! jumps, remove unreachable code and `nop`s, and drop stack and local
+//! variable traffic that has no effect.
+//!
+//! The passes work on a `Method`, in which branch targets, exception table
+//! entries, line numbers, local variable ranges, type annotation offsets and
+//! stack map frames all refer to instructions rather than pcs. Removing an
+//! instruction moves whatever referred to it on to the instruction that
+//! follows, and the code is only laid out again once the pipeline is done.
+//! The passes only make changes that keep the declared stack map frames
+//! valid, so code that passed verification before still does.
+use std::collections::BTreeMap;
+
+use classfile::{AttributeInfo, AttributeLocation, Attributes, Bytecode, ClassFile, CodeAttribute,
+                Constant, ConstantPool, ExceptionInfo, FieldType, InstructionList,
+                LineNumberTableEntry, MethodInfo, ObjectVariableInfo, StackMapFrame, TargetInfo,
+                UninitializedVariableInfo, VerificationTypeInfo};
+
+use cfg::ControlFlowGraph;
+use liveness::Liveness;
+use super::{Error, Result};
+
+/// An optimization that `optimize` can run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Pass {
+    /// Folds arithmetic, comparisons and conversions of constants into a
+    /// single constant, such as `iconst_2; iconst_3; imul` into `bipush 6`.
+    /// Integer division by a constant zero is left alone, as it throws.
+    FoldConstants,
+    /// Replaces conditional branches and switches on constants with a `goto`
+    /// to the target they always take, or removes them if they never jump,
+    /// along with the code that can no longer be reached.
+    ConstantBranches,
+    /// Retargets jumps to a `goto` at the `goto`'s own target, turns a
+    /// conditional branch over a `goto` into the opposite branch, and removes
+    /// jumps to the next instruction.
+    ThreadJumps,
+    /// Removes unreachable instructions and `nop`s.
+    DeadCode,
+    /// Removes values that are popped straight after being pushed, such as
+    /// `dup; pop` and `iload_1; pop`, and pairs of `swap`s.
+    Peephole,
+    /// Removes loads of a local that are stored straight back, and stores
+    /// that are loaded straight back and never read again. Other stores
+    /// that are never read become pops, and such `iinc`s are removed.
+    LoadStore,
+}
+
+impl Pass {
+    /// Every pass, in the order they run in a round of the pipeline.
+    pub fn all() -> Vec<Pass> {
+        vec![Pass::FoldConstants,
+             Pass::ConstantBranches,
+             Pass::ThreadJumps,
+             Pass::DeadCode,
+             Pass::Peephole,
+             Pass::LoadStore]
+    }
+}
+
+/// Optimizes the code of every method of `class` by running `passes` in
+/// turn until none of them changes anything, and returns `true` if any
+/// method changed.
+///
+/// Folded constants that need an `ldc` are added to the constant pool, as
+/// are classes named by stack map frames that have to be written out in
+/// full. Methods that use subroutines are left alone; see
+/// `subroutines::inline_subroutines`. So are methods whose optimized code
+/// would need a conditional branch further than an `i16` offset reaches.
+///
+/// Fails if the code of a method cannot be decoded or falls off its end, or
+/// its exception table or stack map frames do not fall on instructions.
+/// Methods optimized before the failure keep their changes.
+pub fn optimize(class: &mut ClassFile, passes: &[Pass]) -> Result<bool> {
+    let mut changed = false;
+    for method in &mut class.methods {
+        if try!(optimize_method(&mut class.constants, class.this_class, method, passes)) {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+fn optimize_method(cp: &mut ConstantPool,
+                   this_class: u16,
+                   method: &mut MethodInfo,
+                   passes: &[Pass])
+                   -> Result<bool> {
+    let initial = try!(initial_locals(cp, this_class, method));
+    let code = match method.attrs.iter_mut().find(|attr| match **attr {
+        AttributeInfo::Code(_) => true,
+        _ => false,
+    }) {
+        Some(&mut AttributeInfo::Code(ref mut code)) => code,
+        _ => return Ok(false),
+    };
+
+    let mut optimized = match try!(Method::new(code, initial)) {
+        Some(method) => method,
+        None => return Ok(false),
+    };
+    let mut changed = false;
+    loop {
+        let mut round = false;
+        for &pass in passes {
+            if try!(optimized.run(pass, cp)) {
+                round = true;
+            }
+        }
+        if !round {
+            break;
+        }
+        changed = true;
+    }
+    if !changed {
+        return Ok(false);
+    }
+    match optimized.write(code, cp) {
+        Ok(()) => Ok(true),
+        Err(Error::CodeTooLarge) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// The type of a local or stack slot in a stack map frame.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// A class by its `Constant::Class` index.
+    Object(u16),
+    /// A class that has no `Constant::Class` yet, as it is only named by a
+    /// method's descriptor.
+    Class(String),
+    /// The object created by the `new` at an instruction index.
+    Uninitialized(usize),
+}
+
+impl Value {
+    fn is_wide(&self) -> bool {
+        *self == Value::Long || *self == Value::Double
+    }
+}
+
+/// A stack map frame. Longs and doubles take up two slots of `locals`, the
+/// second of which is `Top`, but only one of `stack`.
+#[derive(Clone, Debug)]
+struct Frame {
+    locals: Vec<Value>,
+    stack: Vec<Value>,
+}
+
+struct Handler {
+    start: usize,
+    /// The instruction after the range, or the number of instructions.
+    end: usize,
+    handler: usize,
+    catch_type: u16,
+    /// The position of the entry in the original exception table.
+    id: usize,
+}
+
+/// The range of a `LocalVariableTable` or `LocalVariableTypeTable` entry.
+#[derive(Clone, Copy)]
+struct LocalRange {
+    start: usize,
+    end: usize,
+    slot: u16,
+}
+
+/// The part of the code a type annotation applies to.
+enum AnnotationTarget {
+    /// An instruction, such as a `new` or `checkcast`.
+    Instruction(usize),
+    /// The ranges of a local variable, as start and end indexes.
+    Ranges(Vec<(usize, usize)>),
+    /// The exception table entry with the given id.
+    Handler(usize),
+    /// Something other than code, such as the method's return type.
+    Other,
+}
+
+/// The kind of value a load or store instruction moves.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+}
+
+/// A constant pushed by an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Literal {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+}
+
+/// The code of a method, with everything that depends on pcs held as
+/// instruction indexes.
+struct Method {
+    insns: Vec<Bytecode>,
+    /// The instructions each instruction may jump to, in the order of
+    /// `Bytecode::branch_targets`.
+    targets: Vec<Vec<usize>>,
+    handlers: Vec<Handler>,
+    lines: Vec<(usize, u16)>,
+    /// The `LocalVariableTable` ranges, or `None` for removed entries.
+    local_variables: Vec<Option<LocalRange>>,
+    /// The `LocalVariableTypeTable` ranges, or `None` for removed entries.
+    local_variable_types: Vec<Option<LocalRange>>,
+    /// The targets of the type annotations on the code, or `None` for
+    /// removed annotations.
+    annotations: Vec<Option<AnnotationTarget>>,
+    /// The locals on entry to the method.
+    initial: Vec<Value>,
+    frames: BTreeMap<usize, Frame>,
+    max_stack: u16,
+    max_locals: u16,
+}
+
+impl Method {
+    /// Decodes `code`, or returns `None` if it uses subroutines.
+    fn new(code: &CodeAttribute, initial: Vec<Value>) -> Result<Option<Method>> {
+        let instructions = try!(code.instruction_list());
+        let count = instructions.len();
+        let index_of = |pc: usize| {
+            if pc == instructions.code_length() {
+                Some(count)
+            } else {
+                instructions.index_of(pc)
+            }
+        };
+
+        let mut insns = Vec::with_capacity(count);
+        let mut targets = Vec::with_capacity(count);
+        for &(pc, ref insn) in instructions.iter() {
+            match *insn {
+                Bytecode::jsr { .. } |
+                Bytecode::jsr_w { .. } |
+                Bytecode::ret { .. } |
+                Bytecode::wide_ret { .. } => return Ok(None),
+                _ => {}
+            }
+            let mut indexes = vec![];
+            for target in insn.branch_targets(pc) {
+                match instructions.index_of(target) {
+                    Some(index) => indexes.push(index),
+                    None => return Err(Error::InvalidBranchTarget { pc: pc, target: target }),
+                }
+            }
+            insns.push(insn.clone());
+            targets.push(indexes);
+        }
+        match instructions.iter().last() {
+            Some(&(pc, ref insn)) if insn.falls_through() => {
+                return Err(Error::InvalidFrame {
+                    pc: pc,
+                    message: "Falling off the end of the code".to_string(),
+                })
+            }
+            _ => {}
+        }
+
+        let mut handlers = vec![];
+        for (id, info) in code.exception_table.iter().enumerate() {
+            match (instructions.index_of(info.start_pc as usize),
+                   index_of(info.end_pc as usize),
+                   instructions.index_of(info.handler_pc as usize)) {
+                (Some(start), Some(end), Some(handler)) if start < end => {
+                    handlers.push(Handler {
+                        start: start,
+                        end: end,
+                        handler: handler,
+                        catch_type: info.catch_type,
+                        id: id,
+                    })
+                }
+                _ => return Err(Error::InvalidExceptionHandler { index: id }),
+            }
+        }
+
+        // Debugging information that does not fall on instructions is
+        // dropped rather than guessed at.
+        let lines = code.attrs
+            .line_number_table()
+            .map(|table| {
+                table.iter()
+                    .filter_map(|entry| {
+                        instructions.index_of(entry.start_pc as usize)
+                            .map(|index| (index, entry.line_number))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let range = |start_pc: u16, length: u16, slot: u16| {
+            match (instructions.index_of(start_pc as usize),
+                   index_of(start_pc as usize + length as usize)) {
+                (Some(start), Some(end)) if start < end => {
+                    Some(LocalRange {
+                        start: start,
+                        end: end,
+                        slot: slot,
+                    })
+                }
+                _ => None,
+            }
+        };
+        let local_variables = code.attrs
+            .local_variable_table()
+            .map(|table| {
+                table.iter().map(|entry| range(entry.start_pc, entry.length, entry.index)).collect()
+            })
+            .unwrap_or_default();
+        let local_variable_types = code.attrs
+            .local_variable_type_table()
+            .map(|table| {
+                table.iter().map(|entry| range(entry.start_pc, entry.length, entry.index)).collect()
+            })
+            .unwrap_or_default();
+
+        let mut annotations = vec![];
+        for attr in code.attrs.iter() {
+            let annotated = match *attr {
+                AttributeInfo::RuntimeVisibleTypeAnnotations(ref annotated) |
+                AttributeInfo::RuntimeInvisibleTypeAnnotations(ref annotated) => annotated,
+                _ => continue,
+            };
+            for annotation in annotated {
+                let target = match annotation.target_info {
+                    TargetInfo::Offset(offset) |
+                    TargetInfo::TypeArgument { offset, .. } => {
+                        instructions.index_of(offset as usize).map(AnnotationTarget::Instruction)
+                    }
+                    TargetInfo::Localvar(ref table) => {
+                        let ranges: Vec<(usize, usize)> = table.iter()
+                            .filter_map(|info| range(info.start_pc, info.length, info.index))
+                            .map(|range| (range.start, range.end))
+                            .collect();
+                        if ranges.is_empty() {
+                            None
+                        } else {
+                            Some(AnnotationTarget::Ranges(ranges))
+                        }
+                    }
+                    TargetInfo::Catch(index) => {
+                        handlers.iter()
+                            .find(|handler| handler.id == index as usize)
+                            .map(|handler| AnnotationTarget::Handler(handler.id))
+                    }
+                    _ => Some(AnnotationTarget::Other),
+                };
+                annotations.push(target);
+            }
+        }
+
+        let frames = try!(decode_frames(code, &instructions, &initial));
+        Ok(Some(Method {
+            insns: insns,
+            targets: targets,
+            handlers: handlers,
+            lines: lines,
+            local_variables: local_variables,
+            local_variable_types: local_variable_types,
+            annotations: annotations,
+            initial: initial,
+            frames: frames,
+            max_stack: code.max_stack,
+            max_locals: code.max_locals,
+        }))
+    }
+
+    fn run(&mut self, pass: Pass, cp: &mut ConstantPool) -> Result<bool> {
+        match pass {
+            Pass::FoldConstants => Ok(self.fold_constants(cp)),
+            Pass::ConstantBranches => Ok(self.constant_branches(cp)),
+            Pass::ThreadJumps => Ok(self.thread_jumps()),
+            Pass::DeadCode => Ok(self.dead_code()),
+            Pass::Peephole => Ok(self.peephole(cp)),
+            Pass::LoadStore => self.load_store(),
+        }
+    }
+
+    /// Marks the instructions that something other than falling through
+    /// refers to: branch targets, the bounds and handlers of exception table
+    /// entries, and instructions with a stack map frame. Passes only merge
+    /// an instruction into the one before it if it is not a label.
+    fn labels(&self) -> Vec<bool> {
+        let mut labels = vec![false; self.insns.len() + 1];
+        for targets in &self.targets {
+            for &target in targets {
+                labels[target] = true;
+            }
+        }
+        for handler in &self.handlers {
+            labels[handler.start] = true;
+            labels[handler.end] = true;
+            labels[handler.handler] = true;
+        }
+        for &index in self.frames.keys() {
+            labels[index] = true;
+        }
+        labels
+    }
+
+    /// Replaces the instruction at `index`, which must not be a branch, with
+    /// one that does not branch either.
+    fn replace(&mut self, index: usize, insn: Bytecode) {
+        self.insns[index] = insn;
+        self.targets[index] = vec![];
+    }
+
+    fn fold_constants(&mut self, cp: &mut ConstantPool) -> bool {
+        let labels = self.labels();
+        let mut removed = vec![false; self.insns.len()];
+        let mut index = 0;
+        while index < self.insns.len() {
+            let first = match literal(&self.insns[index], cp) {
+                Some(first) => first,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let unary = self.insns.get(index + 1).and_then(|op| fold_unary(op, first));
+            let binary = match (self.insns.get(index + 1), self.insns.get(index + 2)) {
+                (Some(second), Some(op)) if !labels[index + 2] => {
+                    literal(second, cp).and_then(|second| fold_binary(op, first, second))
+                }
+                _ => None,
+            };
+            let (folded, length) = match (unary, binary) {
+                (Some(folded), _) if !labels[index + 1] => (folded, 2),
+                (_, Some(folded)) if !labels[index + 1] => (folded, 3),
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+            match push_literal(folded, cp) {
+                Some(insn) => {
+                    self.replace(index, insn);
+                    for removed in &mut removed[index + 1..index + length] {
+                        *removed = true;
+                    }
+                    index += length;
+                }
+                None => index += 1,
+            }
+        }
+        self.remove(&removed)
+    }
+
+    fn constant_branches(&mut self, cp: &ConstantPool) -> bool {
+        let labels = self.labels();
+        let count = self.insns.len();
+        let mut removed = vec![false; count];
+        let mut changed = false;
+        let mut index = 0;
+        while index < count {
+            let int = |index: usize| match literal(&self.insns[index], cp) {
+                Some(Literal::Int(value)) => Some(value),
+                _ => None,
+            };
+            let next = index + 1;
+            // The branch deciding on constants, whether it jumps and where
+            // to, as a position in its targets.
+            let decided = if next < count && !labels[next] {
+                let branch = &self.insns[next];
+                match (&self.insns[index], int(index)) {
+                    (&Bytecode::aconst_null, _) => {
+                        match *branch {
+                            Bytecode::ifnull { .. } => Some((next, Some(0))),
+                            Bytecode::ifnonnull { .. } => Some((next, None)),
+                            _ => None,
+                        }
+                    }
+                    (_, Some(value)) => {
+                        match (condition_holds(branch, value), switch_target(branch, value)) {
+                            (Some(taken), _) => Some((next, if taken { Some(0) } else { None })),
+                            (_, Some(target)) => Some((next, Some(target))),
+                            _ if next + 1 < count && !labels[next + 1] => {
+                                int(next)
+                                    .and_then(|second| {
+                                        comparison_holds(&self.insns[next + 1], value, second)
+                                    })
+                                    .map(|taken| (next + 1, if taken { Some(0) } else { None }))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            } else {
+                None
+            };
+            match decided {
+                Some((branch, Some(target))) => {
+                    let target = self.targets[branch][target];
+                    self.insns[index] = Bytecode::goto { branchoffset: 0 };
+                    self.targets[index] = vec![target];
+                    for removed in &mut removed[next..branch + 1] {
+                        *removed = true;
+                    }
+                    changed = true;
+                    index = branch + 1;
+                }
+                Some((branch, None)) => {
+                    for removed in &mut removed[index..branch + 1] {
+                        *removed = true;
+                    }
+                    changed = true;
+                    index = branch + 1;
+                }
+                None => index += 1,
+            }
+        }
+        if !changed {
+            return false;
+        }
+        self.remove(&removed);
+        self.remove_unreachable();
+        true
+    }
+
+    fn thread_jumps(&mut self) -> bool {
+        let mut changed = false;
+        for index in 0..self.insns.len() {
+            for slot in 0..self.targets[index].len() {
+                let target = self.targets[index][slot];
+                let mut end = target;
+                let mut seen = vec![index];
+                while is_goto(&self.insns[end]) && !seen.contains(&end) {
+                    seen.push(end);
+                    end = self.targets[end][0];
+                }
+                // A cycle of gotos is left as it is.
+                if end != target && !seen.contains(&end) {
+                    self.targets[index][slot] = end;
+                    changed = true;
+                }
+            }
+        }
+
+        let labels = self.labels();
+        let mut removed = vec![false; self.insns.len()];
+        for index in 0..self.insns.len() {
+            if removed[index] {
+                continue;
+            }
+            let next = index + 1;
+            if is_goto(&self.insns[index]) && self.targets[index][0] == next {
+                removed[index] = true;
+                changed = true;
+            } else if self.targets[index].len() == 1 && self.insns[index].falls_through() {
+                if self.targets[index][0] == next {
+                    // Both ways lead to the next instruction; only the
+                    // operands remain to be popped.
+                    let popped = match self.insns[index] {
+                        Bytecode::if_icmpeq { .. } |
+                        Bytecode::if_icmpne { .. } |
+                        Bytecode::if_icmplt { .. } |
+                        Bytecode::if_icmpge { .. } |
+                        Bytecode::if_icmpgt { .. } |
+                        Bytecode::if_icmple { .. } |
+                        Bytecode::if_acmpeq { .. } |
+                        Bytecode::if_acmpne { .. } => Bytecode::pop2,
+                        _ => Bytecode::pop,
+                    };
+                    self.replace(index, popped);
+                    changed = true;
+                } else if next < self.insns.len() && is_goto(&self.insns[next]) &&
+                          !labels[next] && self.targets[index][0] == next + 1 {
+                    if let Some(negated) = negated(&self.insns[index]) {
+                        self.insns[index] = negated;
+                        self.targets[index] = vec![self.targets[next][0]];
+                        removed[next] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        self.remove(&removed);
+        changed
+    }
+
+    fn dead_code(&mut self) -> bool {
+        let nops: Vec<bool> = self.insns.iter().map(|insn| *insn == Bytecode::nop).collect();
+        let removed_nops = self.remove(&nops);
+        let removed_unreachable = self.remove_unreachable();
+        removed_nops || removed_unreachable
+    }
+
+    /// Removes the instructions that cannot be reached from the start of the
+    /// method or from an exception handler covering reachable code.
+    fn remove_unreachable(&mut self) -> bool {
+        let count = self.insns.len();
+        let mut reachable = vec![false; count];
+        let mut pending = vec![0];
+        while let Some(index) = pending.pop() {
+            if index >= count || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            if self.insns[index].falls_through() {
+                pending.push(index + 1);
+            }
+            pending.extend(self.targets[index].iter().cloned());
+            for handler in &self.handlers {
+                if handler.start <= index && index < handler.end {
+                    pending.push(handler.handler);
+                }
+            }
+        }
+        let removed: Vec<bool> = reachable.iter().map(|&reachable| !reachable).collect();
+        self.remove(&removed)
+    }
+
+    fn peephole(&mut self, cp: &ConstantPool) -> bool {
+        let labels = self.labels();
+        let mut removed = vec![false; self.insns.len()];
+        let mut index = 0;
+        while index + 1 < self.insns.len() {
+            let next = index + 1;
+            let cancels = !labels[next] &&
+                          match (&self.insns[index], &self.insns[next]) {
+                (&Bytecode::dup, &Bytecode::pop) |
+                (&Bytecode::dup2, &Bytecode::pop2) |
+                (&Bytecode::swap, &Bytecode::swap) => true,
+                (push, &Bytecode::pop) => pushed(push, cp) == Some(1),
+                (push, &Bytecode::pop2) => pushed(push, cp) == Some(2),
+                _ => false,
+            };
+            if cancels {
+                removed[index] = true;
+                removed[next] = true;
+                index += 2;
+            } else {
+                index += 1;
+            }
+        }
+        self.remove(&removed)
+    }
+
+    fn load_store(&mut self) -> Result<bool> {
+        let labels = self.labels();
+        let count = self.insns.len();
+        let mut removed = vec![false; count];
+        let mut changed = false;
+
+        // A load stored straight back changes nothing.
+        for index in 0..count.saturating_sub(1) {
+            if let (Some(load), Some(store)) = (loaded(&self.insns[index]),
+                                                stored(&self.insns[index + 1])) {
+                if load == store && !labels[index + 1] && !removed[index] {
+                    removed[index] = true;
+                    removed[index + 1] = true;
+                }
+            }
+        }
+        if self.remove(&removed) {
+            return Ok(true);
+        }
+
+        let (code, pcs) = try!(self.analysis_code());
+        let liveness = Liveness::new(&try!(ControlFlowGraph::new(&code)));
+        let count = self.insns.len();
+        let live_after = |index: usize, slot: u16| {
+            liveness.live_after(pcs[index]).map_or(true, |live| live.contains(&slot))
+        };
+        for index in 0..count {
+            if removed[index] {
+                continue;
+            }
+            let access = self.insns[index].local_access();
+            if let Some(access) = access.filter(|access| access.reads && access.writes) {
+                if !live_after(index, access.index) {
+                    removed[index] = true;
+                    changed = true;
+                }
+                continue;
+            }
+            let (kind, slot) = match stored(&self.insns[index]) {
+                Some(store) => store,
+                None => continue,
+            };
+            let size = if kind == Kind::Long || kind == Kind::Double { 2 } else { 1 };
+            let slots: Vec<u16> = (slot..slot + size).collect();
+            let reloaded = index + 1 < count &&
+                           loaded(&self.insns[index + 1]) == Some((kind, slot)) &&
+                           !labels[index + 1];
+            let dead = if reloaded {
+                slots.iter().all(|&slot| !live_after(index + 1, slot))
+            } else {
+                slots.iter().all(|&slot| !live_after(index, slot))
+            };
+            if !dead || !self.forget_dead_slots(&slots, &pcs, &liveness) {
+                continue;
+            }
+            if reloaded {
+                removed[index] = true;
+                removed[index + 1] = true;
+            } else if size == 2 {
+                self.replace(index, Bytecode::pop2);
+            } else {
+                self.replace(index, Bytecode::pop);
+            }
+            // The variable held the stored value.
+            for range in self.local_variables
+                .iter_mut()
+                .chain(self.local_variable_types.iter_mut()) {
+                if range.map_or(false, |range| range.slot == slot && range.start == index + 1) {
+                    *range = None;
+                }
+            }
+            changed = true;
+        }
+        Ok(self.remove(&removed) || changed)
+    }
+
+    /// Sets `slots` to `Top` in every frame where they are not live, so that
+    /// frames do not require a store that is about to be removed. Returns
+    /// `false` without changing anything if one of them would lose an
+    /// `uninitializedThis`, which the frame needs to keep.
+    fn forget_dead_slots(&mut self, slots: &[u16], pcs: &[usize], liveness: &Liveness) -> bool {
+        let dead: Vec<(usize, u16)> = self.frames
+            .keys()
+            .flat_map(|&index| slots.iter().map(move |&slot| (index, slot)))
+            .filter(|&(index, slot)| !liveness.is_live_before(pcs[index], slot))
+            .collect();
+        for &(index, slot) in &dead {
+            if self.frames[&index].locals.get(slot as usize) == Some(&Value::UninitializedThis) {
+                return false;
+            }
+        }
+        for (index, slot) in dead {
+            let locals = &mut self.frames.get_mut(&index).unwrap().locals;
+            let slot = slot as usize;
+            if slot < locals.len() {
+                if slot > 0 && locals[slot - 1].is_wide() && locals[slot] == Value::Top {
+                    locals[slot - 1] = Value::Top;
+                }
+                locals[slot] = Value::Top;
+            }
+        }
+        true
+    }
+
+    /// Removes the marked instructions. Branch targets, exception table
+    /// entries, line numbers, local variable ranges, type annotations and
+    /// frames referring to a removed instruction move to the next one that
+    /// remains; ranges that end up empty are dropped, along with frames and
+    /// line numbers that move past the end of the code.
+    fn remove(&mut self, removed: &[bool]) -> bool {
+        if !removed.iter().any(|&removed| removed) {
+            return false;
+        }
+        // The index each instruction, or the one that takes its place,
+        // ends up at.
+        let count = self.insns.len();
+        let mut moved = Vec::with_capacity(count + 1);
+        let mut kept = 0;
+        for &removed in removed {
+            moved.push(kept);
+            if !removed {
+                kept += 1;
+            }
+        }
+        moved.push(kept);
+
+        let mut insns = Vec::with_capacity(kept);
+        let mut targets = Vec::with_capacity(kept);
+        for (index, (insn, old_targets)) in self.insns
+            .drain(..)
+            .zip(self.targets.drain(..))
+            .enumerate() {
+            if !removed[index] {
+                insns.push(insn);
+                targets.push(old_targets.into_iter().map(|target| moved[target]).collect());
+            }
+        }
+        self.insns = insns;
+        self.targets = targets;
+
+        for handler in &mut self.handlers {
+            handler.start = moved[handler.start];
+            handler.end = moved[handler.end];
+            handler.handler = moved[handler.handler];
+        }
+        self.handlers.retain(|handler| handler.start < handler.end && handler.handler < kept);
+
+        // The line of an instruction that remains wins over the lines of
+        // removed instructions before it.
+        let mut lines: Vec<(usize, u16)> = vec![];
+        for &(index, line) in &self.lines {
+            let index = moved[index];
+            if index == kept {
+                continue;
+            }
+            match lines.iter().position(|&(other, _)| other == index) {
+                Some(position) => lines[position].1 = line,
+                None => lines.push((index, line)),
+            }
+        }
+        self.lines = lines;
+
+        for range in self.local_variables
+            .iter_mut()
+            .chain(self.local_variable_types.iter_mut()) {
+            if let Some(mut moved_range) = *range {
+                moved_range.start = moved[moved_range.start];
+                moved_range.end = moved[moved_range.end];
+                *range = if moved_range.start < moved_range.end {
+                    Some(moved_range)
+                } else {
+                    None
+                };
+            }
+        }
+
+        let handlers: Vec<usize> = self.handlers.iter().map(|handler| handler.id).collect();
+        for annotation in &mut self.annotations {
+            let target = match annotation.take() {
+                Some(AnnotationTarget::Instruction(index)) => {
+                    if removed[index] {
+                        None
+                    } else {
+                        Some(AnnotationTarget::Instruction(moved[index]))
+                    }
+                }
+                Some(AnnotationTarget::Ranges(ranges)) => {
+                    let ranges: Vec<(usize, usize)> = ranges.into_iter()
+                        .map(|(start, end)| (moved[start], moved[end]))
+                        .filter(|&(start, end)| start < end)
+                        .collect();
+                    if ranges.is_empty() {
+                        None
+                    } else {
+                        Some(AnnotationTarget::Ranges(ranges))
+                    }
+                }
+                Some(AnnotationTarget::Handler(id)) => {
+                    if handlers.contains(&id) {
+                        Some(AnnotationTarget::Handler(id))
+                    } else {
+                        None
+                    }
+                }
+                other => other,
+            };
+            *annotation = target;
+        }
+
+        // A frame moves on to the next instruction if that has none of its
+        // own. The removed instructions did not change the frame: they were
+        // either unreachable, in which case so is the next instruction
+        // unless something jumps to it, or `nop`s.
+        let mut frames = BTreeMap::new();
+        for (index, mut frame) in ::std::mem::replace(&mut self.frames, BTreeMap::new()) {
+            let kept_frame = !removed[index];
+            let index = moved[index];
+            if index == kept {
+                continue;
+            }
+            for value in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+                if let Value::Uninitialized(new) = *value {
+                    *value = Value::Uninitialized(moved[new]);
+                }
+            }
+            if kept_frame || !frames.contains_key(&index) {
+                frames.insert(index, frame);
+            }
+        }
+        self.frames = frames;
+        true
+    }
+
+    /// Lays out the instructions, returning them with their branch offsets
+    /// filled in, and the pc of each followed by the length of the code.
+    ///
+    /// A `goto` whose offset does not fit becomes a `goto_w`. Fails with
+    /// `Error::CodeTooLarge` if the code is too long for a method, or
+    /// another branch's offset does not fit.
+    fn layout(&self) -> Result<(Vec<Bytecode>, Vec<usize>)> {
+        let mut insns = self.insns.clone();
+        loop {
+            let mut code = vec![];
+            let mut pcs = Vec::with_capacity(insns.len() + 1);
+            for insn in &insns {
+                pcs.push(code.len());
+                insn.encode(&mut code);
+            }
+            pcs.push(code.len());
+            if code.len() > 65535 {
+                return Err(Error::CodeTooLarge);
+            }
+
+            let mut widened = false;
+            for (index, insn) in insns.iter_mut().enumerate() {
+                if self.targets[index].is_empty() {
+                    continue;
+                }
+                let offsets: Vec<i32> = self.targets[index]
+                    .iter()
+                    .map(|&target| pcs[target] as i32 - pcs[index] as i32)
+                    .collect();
+                *insn = match (insn.with_branch_offsets(&offsets), &*insn) {
+                    (Some(retargeted), _) => retargeted,
+                    (None, &Bytecode::goto { .. }) => {
+                        widened = true;
+                        Bytecode::goto_w { branchoffset: offsets[0] }
+                    }
+                    (None, _) => return Err(Error::CodeTooLarge),
+                };
+            }
+            if !widened {
+                return Ok((insns, pcs));
+            }
+        }
+    }
+
+    fn exception_table(&self, pcs: &[usize]) -> Vec<ExceptionInfo> {
+        self.handlers
+            .iter()
+            .map(|handler| {
+                ExceptionInfo {
+                    start_pc: pcs[handler.start] as u16,
+                    end_pc: pcs[handler.end] as u16,
+                    handler_pc: pcs[handler.handler] as u16,
+                    catch_type: handler.catch_type,
+                }
+            })
+            .collect()
+    }
+
+    /// The code with its exception table but no other attributes, for the
+    /// analyses that passes rely on, along with the pc of each instruction.
+    fn analysis_code(&self) -> Result<(CodeAttribute, Vec<usize>)> {
+        let (insns, pcs) = try!(self.layout());
+        let mut code = vec![];
+        for insn in &insns {
+            insn.encode(&mut code);
+        }
+        let code = CodeAttribute {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code: code,
+            exception_table: self.exception_table(&pcs),
+            attrs: Attributes::new(AttributeLocation::Code, vec![]),
+        };
+        Ok((code, pcs))
+    }
+
+    /// Writes the optimized code back into `code`, leaving it unchanged on
+    /// failure.
+    fn write(&mut self, code: &mut CodeAttribute, cp: &mut ConstantPool) -> Result<()> {
+        self.prune_frames();
+        let (insns, pcs) = try!(self.layout());
+        let mut stack_map = if code.attrs.stack_map_table().is_some() {
+            Some(try!(self.encode_frames(&pcs, cp)))
+        } else {
+            None
+        };
+
+        let mut bytes = vec![];
+        for insn in &insns {
+            insn.encode(&mut bytes);
+        }
+        code.code = bytes;
+        code.exception_table = self.exception_table(&pcs);
+
+        let mut lines = Some(self.lines
+            .iter()
+            .map(|&(index, line)| {
+                LineNumberTableEntry {
+                    start_pc: pcs[index] as u16,
+                    line_number: line,
+                }
+            })
+            .collect::<Vec<_>>());
+        let handlers: Vec<usize> = self.handlers.iter().map(|handler| handler.id).collect();
+        let mut annotations = self.annotations.iter();
+        for attr in code.attrs.iter_mut() {
+            match *attr {
+                AttributeInfo::LineNumberTable(ref mut table) => {
+                    *table = lines.take().unwrap_or_default();
+                }
+                AttributeInfo::LocalVariableTable(ref mut table) => {
+                    let ranges = &self.local_variables;
+                    let entries = table.drain(..).zip(ranges.iter());
+                    *table = entries.filter_map(|(mut entry, range)| {
+                            range.map(|range| {
+                                entry.start_pc = pcs[range.start] as u16;
+                                entry.length = (pcs[range.end] - pcs[range.start]) as u16;
+                                entry
+                            })
+                        })
+                        .collect();
+                }
+                AttributeInfo::LocalVariableTypeTable(ref mut table) => {
+                    let ranges = &self.local_variable_types;
+                    let entries = table.drain(..).zip(ranges.iter());
+                    *table = entries.filter_map(|(mut entry, range)| {
+                            range.map(|range| {
+                                entry.start_pc = pcs[range.start] as u16;
+                                entry.length = (pcs[range.end] - pcs[range.start]) as u16;
+                                entry
+                            })
+                        })
+                        .collect();
+                }
+                AttributeInfo::StackMapTable(ref mut frames) => {
+                    *frames = stack_map.take().unwrap_or_default();
+                }
+                AttributeInfo::RuntimeVisibleTypeAnnotations(ref mut annotated) |
+                AttributeInfo::RuntimeInvisibleTypeAnnotations(ref mut annotated) => {
+                    let targets = annotated.len();
+                    let kept = annotated.drain(..)
+                        .zip(annotations.by_ref().take(targets))
+                        .filter_map(|(mut annotation, target)| {
+                            match (&mut annotation.target_info, target.as_ref()) {
+                                (&mut TargetInfo::Offset(ref mut offset),
+                                 Some(&AnnotationTarget::Instruction(index))) |
+                                (&mut TargetInfo::TypeArgument { ref mut offset, .. },
+                                 Some(&AnnotationTarget::Instruction(index))) => {
+                                    *offset = pcs[index] as u16;
+                                }
+                                (&mut TargetInfo::Localvar(ref mut table),
+                                 Some(&AnnotationTarget::Ranges(ref ranges))) => {
+                                    let slot = table[0].index;
+                                    *table = ranges.iter()
+                                        .map(|&(start, end)| {
+                                            ::classfile::LocalvarInfo {
+                                                start_pc: pcs[start] as u16,
+                                                length: (pcs[end] - pcs[start]) as u16,
+                                                index: slot,
+                                            }
+                                        })
+                                        .collect();
+                                }
+                                (&mut TargetInfo::Catch(ref mut index),
+                                 Some(&AnnotationTarget::Handler(id))) => {
+                                    *index = handlers.iter().position(|&other| other == id)
+                                        .unwrap() as u16;
+                                }
+                                (_, Some(&AnnotationTarget::Other)) => {}
+                                _ => return None,
+                            }
+                            Some(annotation)
+                        })
+                        .collect();
+                    *annotated = kept;
+                }
+                _ => {}
+            }
+        }
+        code.attrs.retain(|attr| match *attr {
+            AttributeInfo::StackMapTable(ref frames) => !frames.is_empty(),
+            _ => true,
+        });
+        Ok(())
+    }
+
+    /// Drops the frames of instructions that are no longer branch targets or
+    /// exception handlers, and do not follow an unconditional transfer of
+    /// control either.
+    fn prune_frames(&mut self) {
+        let mut needed = vec![false; self.insns.len()];
+        for targets in &self.targets {
+            for &target in targets {
+                needed[target] = true;
+            }
+        }
+        for handler in &self.handlers {
+            needed[handler.handler] = true;
+        }
+        for index in 1..self.insns.len() {
+            if !self.insns[index - 1].falls_through() {
+                needed[index] = true;
+            }
+        }
+        self.frames.retain(|&index, _| needed[index]);
+    }
+
+    /// Encodes the frames as compactly as the previous frame allows.
+    fn encode_frames(&self, pcs: &[usize], cp: &mut ConstantPool) -> Result<Vec<StackMapFrame>> {
+        let mut entries = vec![];
+        let mut previous = compress(&self.initial);
+        let mut previous_pc: Option<usize> = None;
+        for (&index, frame) in &self.frames {
+            let pc = pcs[index];
+            let delta = match previous_pc {
+                Some(previous_pc) => pc - previous_pc - 1,
+                None => pc,
+            };
+            let offset_delta = delta as u16;
+            let locals = compress(&frame.locals);
+            let entry = if locals == previous && frame.stack.is_empty() {
+                if delta < 64 {
+                    StackMapFrame::SameFrame { frame_type: delta as u8 }
+                } else {
+                    StackMapFrame::SameFrameExtended { offset_delta: offset_delta }
+                }
+            } else if locals == previous && frame.stack.len() == 1 {
+                let stack = [try!(self.info(&frame.stack[0], pcs, cp))];
+                if delta < 64 {
+                    StackMapFrame::SameLocals1StackItemFrame {
+                        frame_type: 64 + delta as u8,
+                        stack: stack,
+                    }
+                } else {
+                    StackMapFrame::SameLocals1StackItemFrameExtended {
+                        offset_delta: offset_delta,
+                        stack: stack,
+                    }
+                }
+            } else if frame.stack.is_empty() && locals.len() < previous.len() &&
+                      previous.len() - locals.len() <= 3 &&
+                      previous.starts_with(&locals) {
+                StackMapFrame::ChopFrame {
+                    frame_type: 251 - (previous.len() - locals.len()) as u8,
+                    offset_delta: offset_delta,
+                }
+            } else if frame.stack.is_empty() && locals.len() > previous.len() &&
+                      locals.len() - previous.len() <= 3 &&
+                      locals.starts_with(&previous) {
+                let mut appended = vec![];
+                for value in &locals[previous.len()..] {
+                    appended.push(try!(self.info(value, pcs, cp)));
+                }
+                StackMapFrame::AppendFrame {
+                    frame_type: 251 + appended.len() as u8,
+                    offset_delta: offset_delta,
+                    locals: appended,
+                }
+            } else {
+                let mut full_locals = vec![];
+                for value in &locals {
+                    full_locals.push(try!(self.info(value, pcs, cp)));
+                }
+                let mut stack = vec![];
+                for value in &frame.stack {
+                    stack.push(try!(self.info(value, pcs, cp)));
+                }
+                StackMapFrame::FullFrame {
+                    offset_delta: offset_delta,
+                    locals: full_locals,
+                    stack: stack,
+                }
+            };
+            entries.push(entry);
+            previous = locals;
+            previous_pc = Some(pc);
+        }
+        Ok(entries)
+    }
+
+    fn info(&self,
+            value: &Value,
+            pcs: &[usize],
+            cp: &mut ConstantPool)
+            -> Result<VerificationTypeInfo> {
+        let info = match *value {
+            Value::Top => VerificationTypeInfo::Top,
+            Value::Integer => VerificationTypeInfo::Integer,
+            Value::Float => VerificationTypeInfo::Float,
+            Value::Long => VerificationTypeInfo::Long,
+            Value::Double => VerificationTypeInfo::Double,
+            Value::Null => VerificationTypeInfo::Null,
+            Value::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+            Value::Object(index) => {
+                VerificationTypeInfo::Object(ObjectVariableInfo { cpool_index: index })
+            }
+            Value::Class(ref name) => {
+                let index = cp.intern(Constant::Utf8(name.clone()))
+                    .and_then(|name_index| cp.intern(Constant::Class(name_index)));
+                match index {
+                    Some(index) => {
+                        VerificationTypeInfo::Object(ObjectVariableInfo { cpool_index: index })
+                    }
+                    None => return Err(Error::CodeTooLarge),
+                }
+            }
+            Value::Uninitialized(index) => {
+                VerificationTypeInfo::Uninitialized(UninitializedVariableInfo {
+                    offset: pcs[index] as u16,
+                })
+            }
+        };
+        Ok(info)
+    }
+}
+
+/// The locals on entry to `method`, with two slots for each long or double.
+fn initial_locals(cp: &ConstantPool, this_class: u16, method: &MethodInfo) -> Result<Vec<Value>> {
+    let mut locals = vec![];
+    if !method.access_flags.is_static() {
+        let is_init = cp.get(method.name_index).map_or(false, |name| match *name {
+            Constant::Utf8(ref name) => name == "<init>",
+            _ => false,
+        });
+        if is_init && cp.class_name(this_class).map_or(false, |name| name != "java/lang/Object") {
+            locals.push(Value::UninitializedThis);
+        } else {
+            locals.push(Value::Object(this_class));
+        }
+    }
+    for param in &try!(method.descriptor(cp)).params {
+        let value = match *param {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short |
+            FieldType::Int => Value::Integer,
+            FieldType::Float => Value::Float,
+            FieldType::Long => Value::Long,
+            FieldType::Double => Value::Double,
+            FieldType::Object(ref name) => class_value(cp, name),
+            FieldType::Array(_) => class_value(cp, &param.to_string()),
+        };
+        let wide = value.is_wide();
+        locals.push(value);
+        if wide {
+            locals.push(Value::Top);
+        }
+    }
+    Ok(locals)
+}
+
+fn class_value(cp: &ConstantPool, name: &str) -> Value {
+    for index in 1..cp.len() {
+        if cp.class_name(index).map_or(false, |class| class == name) {
+            return Value::Object(index);
+        }
+    }
+    Value::Class(name.to_string())
+}
+
+/// Decodes the `StackMapTable` of `code` into a frame per instruction index.
+fn decode_frames(code: &CodeAttribute,
+                 instructions: &InstructionList,
+                 initial: &[Value])
+                 -> Result<BTreeMap<usize, Frame>> {
+    let mut frames = BTreeMap::new();
+    let entries = match code.attrs.stack_map_table() {
+        Some(entries) => entries,
+        None => return Ok(frames),
+    };
+    let values = |infos: &[VerificationTypeInfo], pc: usize| -> Result<Vec<Value>> {
+        let mut values = vec![];
+        for info in infos {
+            values.push(match *info {
+                VerificationTypeInfo::Top => Value::Top,
+                VerificationTypeInfo::Integer => Value::Integer,
+                VerificationTypeInfo::Float => Value::Float,
+                VerificationTypeInfo::Long => Value::Long,
+                VerificationTypeInfo::Double => Value::Double,
+                VerificationTypeInfo::Null => Value::Null,
+                VerificationTypeInfo::UninitializedThis => Value::UninitializedThis,
+                VerificationTypeInfo::Object(ref info) => Value::Object(info.cpool_index),
+                VerificationTypeInfo::Uninitialized(ref info) => {
+                    match instructions.index_of(info.offset as usize) {
+                        Some(index) => Value::Uninitialized(index),
+                        None => {
+                            return Err(Error::InvalidFrame {
+                                pc: pc,
+                                message: format!("uninitialized offset {} is not an instruction",
+                                                 info.offset),
+                            })
+                        }
+                    }
+                }
+            });
+        }
+        Ok(values)
+    };
+
+    // Chop and append frames work on whole types, so the locals are kept
+    // with one entry per long or double until each frame is complete.
+    let mut locals = compress(initial);
+    let mut previous_pc: Option<usize> = None;
+    for entry in entries {
+        // The pc of the frame, for errors, before its offset is known.
+        let pc_hint = previous_pc.map_or(0, |pc| pc + 1);
+        let (offset_delta, stack) = match *entry {
+            StackMapFrame::SameFrame { frame_type } => (frame_type as usize, vec![]),
+            StackMapFrame::SameLocals1StackItemFrame { frame_type, ref stack } => {
+                (frame_type as usize - 64, try!(values(stack, pc_hint)))
+            }
+            StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, ref stack } => {
+                (offset_delta as usize, try!(values(stack, pc_hint)))
+            }
+            StackMapFrame::ChopFrame { frame_type, offset_delta } => {
+                let chopped = 251 - frame_type as usize;
+                let len = locals.len().saturating_sub(chopped);
+                locals.truncate(len);
+                (offset_delta as usize, vec![])
+            }
+            StackMapFrame::SameFrameExtended { offset_delta } => (offset_delta as usize, vec![]),
+            StackMapFrame::AppendFrame { offset_delta, locals: ref appended, .. } => {
+                locals.extend(try!(values(appended, pc_hint)));
+                (offset_delta as usize, vec![])
+            }
+            StackMapFrame::FullFrame { offset_delta, locals: ref full, ref stack } => {
+                locals = try!(values(full, pc_hint));
+                (offset_delta as usize, try!(values(stack, pc_hint)))
+            }
+        };
+        let pc = match previous_pc {
+            Some(previous) => previous + offset_delta + 1,
+            None => offset_delta,
+        };
+        previous_pc = Some(pc);
+        let index = match instructions.index_of(pc) {
+            Some(index) => index,
+            None => {
+                return Err(Error::InvalidFrame {
+                    pc: pc,
+                    message: "stack map frame is not at an instruction".to_string(),
+                })
+            }
+        };
+        frames.insert(index,
+                      Frame {
+                          locals: expand(&locals),
+                          stack: stack,
+                      });
+    }
+    Ok(frames)
+}
+
+/// Drops the `Top` that follows each long and double in `locals`, and any
+/// trailing `Top`s, as a `StackMapTable` lists locals.
+fn compress(locals: &[Value]) -> Vec<Value> {
+    let mut compressed = vec![];
+    let mut slot = 0;
+    while slot < locals.len() {
+        compressed.push(locals[slot].clone());
+        slot += if locals[slot].is_wide() { 2 } else { 1 };
+    }
+    while compressed.last() == Some(&Value::Top) {
+        compressed.pop();
+    }
+    compressed
+}
+
+/// Gives each long and double in `locals` a second slot.
+fn expand(locals: &[Value]) -> Vec<Value> {
+    let mut expanded = vec![];
+    for value in locals {
+        expanded.push(value.clone());
+        if value.is_wide() {
+            expanded.push(Value::Top);
+        }
+    }
+    expanded
+}
+
+/// The constant `insn` pushes, if it pushes a number.
+fn literal(insn: &Bytecode, cp: &ConstantPool) -> Option<Literal> {
+    let literal = match *insn {
+        Bytecode::iconst_i(value) => Literal::Int(value as i32),
+        Bytecode::bipush { byte } => Literal::Int(byte as i8 as i32),
+        Bytecode::sipush { short } => Literal::Int(short as i32),
+        Bytecode::lconst_l(value) => Literal::Long(value as i64),
+        Bytecode::fconst_f(value) => Literal::Float(value as f32),
+        Bytecode::dconst_d(value) => Literal::Double(value as f64),
+        Bytecode::ldc { .. } |
+        Bytecode::ldc_w { .. } |
+        Bytecode::ldc2_w { .. } => {
+            match insn.constant_index().and_then(|index| cp.get(index)) {
+                Some(&Constant::Integer(value)) => Literal::Int(value),
+                Some(&Constant::Float(value)) => Literal::Float(value),
+                Some(&Constant::Long(value)) => Literal::Long(value),
+                Some(&Constant::Double(value)) => Literal::Double(value),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(literal)
+}
+
+/// The result of the negation or conversion `op` applied to `value`.
+///
+/// Rust's saturating float to integer casts match the JVM's, including
+/// turning NaN into zero.
+fn fold_unary(op: &Bytecode, value: Literal) -> Option<Literal> {
+    let folded = match (op, value) {
+        (&Bytecode::ineg, Literal::Int(a)) => Literal::Int(a.wrapping_neg()),
+        (&Bytecode::i2l, Literal::Int(a)) => Literal::Long(a as i64),
+        (&Bytecode::i2f, Literal::Int(a)) => Literal::Float(a as f32),
+        (&Bytecode::i2d, Literal::Int(a)) => Literal::Double(a as f64),
+        (&Bytecode::i2b, Literal::Int(a)) => Literal::Int(a as i8 as i32),
+        (&Bytecode::i2c, Literal::Int(a)) => Literal::Int(a as u16 as i32),
+        (&Bytecode::i2s, Literal::Int(a)) => Literal::Int(a as i16 as i32),
+        (&Bytecode::lneg, Literal::Long(a)) => Literal::Long(a.wrapping_neg()),
+        (&Bytecode::l2i, Literal::Long(a)) => Literal::Int(a as i32),
+        (&Bytecode::l2f, Literal::Long(a)) => Literal::Float(a as f32),
+        (&Bytecode::l2d, Literal::Long(a)) => Literal::Double(a as f64),
+        (&Bytecode::fneg, Literal::Float(a)) => Literal::Float(-a),
+        (&Bytecode::f2i, Literal::Float(a)) => Literal::Int(a as i32),
+        (&Bytecode::f2l, Literal::Float(a)) => Literal::Long(a as i64),
+        (&Bytecode::f2d, Literal::Float(a)) => Literal::Double(a as f64),
+        (&Bytecode::dneg, Literal::Double(a)) => Literal::Double(-a),
+        (&Bytecode::d2i, Literal::Double(a)) => Literal::Int(a as i32),
+        (&Bytecode::d2l, Literal::Double(a)) => Literal::Long(a as i64),
+        (&Bytecode::d2f, Literal::Double(a)) => Literal::Float(a as f32),
+        _ => return None,
+    };
+    Some(folded)
+}
+
+/// The result of the arithmetic or comparison `op` applied to `a` and `b`,
+/// or `None` if it throws.
+fn fold_binary(op: &Bytecode, a: Literal, b: Literal) -> Option<Literal> {
+    let folded = match (op, a, b) {
+        (&Bytecode::iadd, Literal::Int(a), Literal::Int(b)) => Literal::Int(a.wrapping_add(b)),
+        (&Bytecode::isub, Literal::Int(a), Literal::Int(b)) => Literal::Int(a.wrapping_sub(b)),
+        (&Bytecode::imul, Literal::Int(a), Literal::Int(b)) => Literal::Int(a.wrapping_mul(b)),
+        (&Bytecode::idiv, Literal::Int(a), Literal::Int(b)) if b != 0 => {
+            Literal::Int(a.wrapping_div(b))
+        }
+        (&Bytecode::irem, Literal::Int(a), Literal::Int(b)) if b != 0 => {
+            Literal::Int(a.wrapping_rem(b))
+        }
+        (&Bytecode::iand, Literal::Int(a), Literal::Int(b)) => Literal::Int(a & b),
+        (&Bytecode::ior, Literal::Int(a), Literal::Int(b)) => Literal::Int(a | b),
+        (&Bytecode::ixor, Literal::Int(a), Literal::Int(b)) => Literal::Int(a ^ b),
+        (&Bytecode::ishl, Literal::Int(a), Literal::Int(b)) => {
+            Literal::Int(a.wrapping_shl(b as u32))
+        }
+        (&Bytecode::ishr, Literal::Int(a), Literal::Int(b)) => {
+            Literal::Int(a.wrapping_shr(b as u32))
+        }
+        (&Bytecode::iushr, Literal::Int(a), Literal::Int(b)) => {
+            Literal::Int((a as u32).wrapping_shr(b as u32) as i32)
+        }
+        (&Bytecode::ladd, Literal::Long(a), Literal::Long(b)) => Literal::Long(a.wrapping_add(b)),
+        (&Bytecode::lsub, Literal::Long(a), Literal::Long(b)) => Literal::Long(a.wrapping_sub(b)),
+        (&Bytecode::lmul, Literal::Long(a), Literal::Long(b)) => Literal::Long(a.wrapping_mul(b)),
+        (&Bytecode::ldiv, Literal::Long(a), Literal::Long(b)) if b != 0 => {
+            Literal::Long(a.wrapping_div(b))
+        }
+        (&Bytecode::lrem, Literal::Long(a), Literal::Long(b)) if b != 0 => {
+            Literal::Long(a.wrapping_rem(b))
+        }
+        (&Bytecode::land, Literal::Long(a), Literal::Long(b)) => Literal::Long(a & b),
+        (&Bytecode::lor, Literal::Long(a), Literal::Long(b)) => Literal::Long(a | b),
+        (&Bytecode::lxor, Literal::Long(a), Literal::Long(b)) => Literal::Long(a ^ b),
+        (&Bytecode::lshl, Literal::Long(a), Literal::Int(b)) => {
+            Literal::Long(a.wrapping_shl(b as u32))
+        }
+        (&Bytecode::lshr, Literal::Long(a), Literal::Int(b)) => {
+            Literal::Long(a.wrapping_shr(b as u32))
+        }
+        (&Bytecode::lushr, Literal::Long(a), Literal::Int(b)) => {
+            Literal::Long((a as u64).wrapping_shr(b as u32) as i64)
+        }
+        (&Bytecode::lcmp, Literal::Long(a), Literal::Long(b)) => {
+            Literal::Int(if a < b { -1 } else if a > b { 1 } else { 0 })
+        }
+        (&Bytecode::fadd, Literal::Float(a), Literal::Float(b)) => Literal::Float(a + b),
+        (&Bytecode::fsub, Literal::Float(a), Literal::Float(b)) => Literal::Float(a - b),
+        (&Bytecode::fmul, Literal::Float(a), Literal::Float(b)) => Literal::Float(a * b),
+        (&Bytecode::fdiv, Literal::Float(a), Literal::Float(b)) => Literal::Float(a / b),
+        (&Bytecode::frem, Literal::Float(a), Literal::Float(b)) => Literal::Float(a % b),
+        (&Bytecode::fcmpl, Literal::Float(a), Literal::Float(b)) => {
+            Literal::Int(compare(a as f64, b as f64, -1))
+        }
+        (&Bytecode::fcmpg, Literal::Float(a), Literal::Float(b)) => {
+            Literal::Int(compare(a as f64, b as f64, 1))
+        }
+        (&Bytecode::dadd, Literal::Double(a), Literal::Double(b)) => Literal::Double(a + b),
+        (&Bytecode::dsub, Literal::Double(a), Literal::Double(b)) => Literal::Double(a - b),
+        (&Bytecode::dmul, Literal::Double(a), Literal::Double(b)) => Literal::Double(a * b),
+        (&Bytecode::ddiv, Literal::Double(a), Literal::Double(b)) => Literal::Double(a / b),
+        (&Bytecode::drem, Literal::Double(a), Literal::Double(b)) => Literal::Double(a % b),
+        (&Bytecode::dcmpl, Literal::Double(a), Literal::Double(b)) => {
+            Literal::Int(compare(a, b, -1))
+        }
+        (&Bytecode::dcmpg, Literal::Double(a), Literal::Double(b)) => {
+            Literal::Int(compare(a, b, 1))
+        }
+        _ => return None,
+    };
+    Some(folded)
+}
+
+/// Compares floating point values the way `dcmpl` and `dcmpg` do, giving
+/// `nan` if either is NaN.
+fn compare(a: f64, b: f64, nan: i32) -> i32 {
+    if a < b {
+        -1
+    } else if a > b {
+        1
+    } else if a == b {
+        0
+    } else {
+        nan
+    }
+}
+
+/// The shortest instruction pushing `value`, adding it to the constant pool
+/// if it needs an `ldc`. Returns `None` if the pool is full.
+fn push_literal(value: Literal, cp: &mut ConstantPool) -> Option<Bytecode> {
+    let constant = match value {
+        Literal::Int(value) if value >= -1 && value <= 5 => {
+            return Some(Bytecode::iconst_i(value as i8))
+        }
+        Literal::Int(value) if value >= i8::min_value() as i32 &&
+                               value <= i8::max_value() as i32 => {
+            return Some(Bytecode::bipush { byte: value as i8 as u8 })
+        }
+        Literal::Int(value) if value >= i16::min_value() as i32 &&
+                               value <= i16::max_value() as i32 => {
+            return Some(Bytecode::sipush { short: value as i16 })
+        }
+        Literal::Int(value) => Constant::Integer(value),
+        Literal::Long(value) if value == 0 || value == 1 => {
+            return Some(Bytecode::lconst_l(value as u8))
+        }
+        Literal::Long(value) => Constant::Long(value),
+        // Only positive zero has an `fconst` or `dconst`.
+        Literal::Float(value) if [0.0f32, 1.0, 2.0]
+            .iter()
+            .any(|c| c.to_bits() == value.to_bits()) => {
+            return Some(Bytecode::fconst_f(value as u8))
+        }
+        Literal::Float(value) => Constant::Float(value),
+        Literal::Double(value) if [0.0f64, 1.0].iter().any(|c| c.to_bits() == value.to_bits()) => {
+            return Some(Bytecode::dconst_d(value as u8))
+        }
+        Literal::Double(value) => Constant::Double(value),
+    };
+    let wide = match constant {
+        Constant::Long(_) | Constant::Double(_) => true,
+        _ => false,
+    };
+    cp.intern(constant).map(|index| {
+        if wide {
+            Bytecode::ldc2_w { index: index }
+        } else if index <= 255 {
+            Bytecode::ldc { index: index as u8 }
+        } else {
+            Bytecode::ldc_w { index: index }
+        }
+    })
+}
+
+/// Whether the conditional branch `branch` jumps when `value` is on top of
+/// the stack, or `None` if it is not a comparison against zero.
+fn condition_holds(branch: &Bytecode, value: i32) -> Option<bool> {
+    let holds = match *branch {
+        Bytecode::ifeq { .. } => value == 0,
+        Bytecode::ifne { .. } => value != 0,
+        Bytecode::iflt { .. } => value < 0,
+        Bytecode::ifge { .. } => value >= 0,
+        Bytecode::ifgt { .. } => value > 0,
+        Bytecode::ifle { .. } => value <= 0,
+        _ => return None,
+    };
+    Some(holds)
+}
+
+/// Whether the conditional branch `branch` jumps when `a` and then `b` are
+/// pushed, or `None` if it does not compare two ints.
+fn comparison_holds(branch: &Bytecode, a: i32, b: i32) -> Option<bool> {
+    let holds = match *branch {
+        Bytecode::if_icmpeq { .. } => a == b,
+        Bytecode::if_icmpne { .. } => a != b,
+        Bytecode::if_icmplt { .. } => a < b,
+        Bytecode::if_icmpge { .. } => a >= b,
+        Bytecode::if_icmpgt { .. } => a > b,
+        Bytecode::if_icmple { .. } => a <= b,
+        _ => return None,
+    };
+    Some(holds)
+}
+
+/// The position among its targets of the one the switch `branch` jumps to
+/// for `value`, or `None` if it is not a switch.
+fn switch_target(branch: &Bytecode, value: i32) -> Option<usize> {
+    match *branch {
+        Bytecode::tableswitch { low, high, .. } => {
+            if value >= low && value <= high {
+                Some((value as i64 - low as i64) as usize + 1)
+            } else {
+                Some(0)
+            }
+        }
+        Bytecode::lookupswitch { ref pairs, .. } => {
+            Some(pairs.iter().position(|&(key, _)| key == value).map_or(0, |position| position + 1))
+        }
+        _ => None,
+    }
+}
+
+fn is_goto(insn: &Bytecode) -> bool {
+    match *insn {
+        Bytecode::goto { .. } | Bytecode::goto_w { .. } => true,
+        _ => false,
+    }
+}
+
+/// The conditional branch jumping exactly when `branch` does not.
+fn negated(branch: &Bytecode) -> Option<Bytecode> {
+    let negated = match *branch {
+        Bytecode::ifeq { .. } => Bytecode::ifne { branchoffset: 0 },
+        Bytecode::ifne { .. } => Bytecode::ifeq { branchoffset: 0 },
+        Bytecode::iflt { .. } => Bytecode::ifge { branchoffset: 0 },
+        Bytecode::ifge { .. } => Bytecode::iflt { branchoffset: 0 },
+        Bytecode::ifgt { .. } => Bytecode::ifle { branchoffset: 0 },
+        Bytecode::ifle { .. } => Bytecode::ifgt { branchoffset: 0 },
+        Bytecode::if_icmpeq { .. } => Bytecode::if_icmpne { branchoffset: 0 },
+        Bytecode::if_icmpne { .. } => Bytecode::if_icmpeq { branchoffset: 0 },
+        Bytecode::if_icmplt { .. } => Bytecode::if_icmpge { branchoffset: 0 },
+        Bytecode::if_icmpge { .. } => Bytecode::if_icmplt { branchoffset: 0 },
+        Bytecode::if_icmpgt { .. } => Bytecode::if_icmple { branchoffset: 0 },
+        Bytecode::if_icmple { .. } => Bytecode::if_icmpgt { branchoffset: 0 },
+        Bytecode::if_acmpeq { .. } => Bytecode::if_acmpne { branchoffset: 0 },
+        Bytecode::if_acmpne { .. } => Bytecode::if_acmpeq { branchoffset: 0 },
+        Bytecode::ifnull { .. } => Bytecode::ifnonnull { branchoffset: 0 },
+        Bytecode::ifnonnull { .. } => Bytecode::ifnull { branchoffset: 0 },
+        _ => return None,
+    };
+    Some(negated)
+}
+
+/// The number of slots `insn` pushes if it pushes a value and has no other
+/// effect, so that popping it straight away can remove both.
+fn pushed(insn: &Bytecode, cp: &ConstantPool) -> Option<u8> {
+    match *insn {
+        Bytecode::aconst_null |
+        Bytecode::iconst_i(_) |
+        Bytecode::bipush { .. } |
+        Bytecode::sipush { .. } |
+        Bytecode::fconst_f(_) => Some(1),
+        Bytecode::lconst_l(_) |
+        Bytecode::dconst_d(_) => Some(2),
+        // Class, method type and method handle constants are resolved
+        // when loaded, which may fail.
+        Bytecode::ldc { .. } |
+        Bytecode::ldc_w { .. } |
+        Bytecode::ldc2_w { .. } => {
+            match insn.constant_index().and_then(|index| cp.get(index)) {
+                Some(&Constant::Integer(_)) |
+                Some(&Constant::Float(_)) |
+                Some(&Constant::String(_)) => Some(1),
+                Some(&Constant::Long(_)) |
+                Some(&Constant::Double(_)) => Some(2),
+                _ => None,
+            }
+        }
+        _ => {
+            loaded(insn).map(|(kind, _)| match kind {
+                Kind::Long | Kind::Double => 2,
+                _ => 1,
+            })
+        }
+    }
+}
+
+/// The kind of value and the local `insn` loads, if it is a load.
+fn loaded(insn: &Bytecode) -> Option<(Kind, u16)> {
+    let loaded = match *insn {
+        Bytecode::iload { index } => (Kind::Int, index as u16),
+        Bytecode::lload { index } => (Kind::Long, index as u16),
+        Bytecode::fload { index } => (Kind::Float, index as u16),
+        Bytecode::dload { byte } => (Kind::Double, byte as u16),
+        Bytecode::aload { index } => (Kind::Reference, index as u16),
+        Bytecode::iload_n(n) => (Kind::Int, n as u16),
+        Bytecode::lload_n(n) => (Kind::Long, n as u16),
+        Bytecode::fload_n(n) => (Kind::Float, n as u16),
+        Bytecode::dload_n(n) => (Kind::Double, n as u16),
+        Bytecode::aload_n(n) => (Kind::Reference, n as u16),
+        Bytecode::wide_iload { index } => (Kind::Int, index),
+        Bytecode::wide_lload { index } => (Kind::Long, index),
+        Bytecode::wide_fload { index } => (Kind::Float, index),
+        Bytecode::wide_dload { index } => (Kind::Double, index),
+        Bytecode::wide_aload { index } => (Kind::Reference, index),
+        _ => return None,
+    };
+    Some(loaded)
+}
+
+/// The kind of value and the local `insn` stores to, if it is a store.
+fn stored(insn: &Bytecode) -> Option<(Kind, u16)> {
+    let stored = match *insn {
+        Bytecode::istore { index } => (Kind::Int, index as u16),
+        Bytecode::lstore { index } => (Kind::Long, index as u16),
+        Bytecode::fstore { index } => (Kind::Float, index as u16),
+        Bytecode::dstore { index } => (Kind::Double, index as u16),
+        Bytecode::astore { index } => (Kind::Reference, index as u16),
+        Bytecode::istore_n(n) => (Kind::Int, n as u16),
+        Bytecode::lstore_n(n) => (Kind::Long, n as u16),
+        Bytecode::fstore_n(n) => (Kind::Float, n as u16),
+        Bytecode::dstore_n(n) => (Kind::Double, n as u16),
+        Bytecode::astore_n(n) => (Kind::Reference, n as u16),
+        Bytecode::wide_istore { index } => (Kind::Int, index),
+        Bytecode::wide_lstore { index } => (Kind::Long, index),
+        Bytecode::wide_fstore { index } => (Kind::Float, index),
+        Bytecode::wide_dstore { index } => (Kind::Double, index),
+        Bytecode::wide_astore { index } => (Kind::Reference, index),
+        _ => return None,
+    };
+    Some(stored)
+}
@@ -0,0 +1,270 @@
+//! Reaching definitions of local variables, and the def-use and use-def
+//! chains built from them.
+//!
+//! A definition is a store or `iinc`, or one of the method's arguments,
+//! which are defined on entry. A definition reaches a point if some path
+//! from it to there does not write any slot it defined. Like the liveness
+//! analysis, this one treats each instruction in the range of an exception
+//! handler as a possible jump to the handler, and merges the locals of all
+//! callers of a subroutine at each of its return sites.
+use std::collections::BTreeSet;
+
+use classfile::{InstructionList, MethodDescriptor};
+
+use cfg::{ControlFlowGraph, EdgeKind};
+
+/// A write to a local variable.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Definition {
+    /// The pc of the instruction writing the variable, or `None` for a
+    /// method argument.
+    pub pc: Option<usize>,
+    /// The first slot written.
+    pub index: u16,
+    /// The number of slots written: 2 for `long` and `double`, otherwise 1.
+    pub slots: u8,
+}
+
+impl Definition {
+    /// Returns `true` if this definition writes `slot`.
+    pub fn defines(&self, slot: u16) -> bool {
+        self.index <= slot && (slot as u32) < self.index as u32 + self.slots as u32
+    }
+}
+
+/// The definitions reaching each instruction of a method, and which
+/// definitions each local variable read uses.
+///
+/// Definitions are identified by their position in `definitions()`: the
+/// arguments come first, in slot order, followed by the writes in pc order.
+#[derive(Clone, Debug)]
+pub struct ReachingDefinitions {
+    instructions: InstructionList,
+    definitions: Vec<Definition>,
+    /// The definition made by each instruction, by instruction index.
+    defined_by: Vec<Option<usize>>,
+    /// The definitions reaching each instruction, by instruction index.
+    reaching: Vec<BTreeSet<usize>>,
+    /// The definitions read by each instruction, by instruction index.
+    used: Vec<Vec<usize>>,
+    /// The pcs of the instructions reading each definition.
+    uses: Vec<Vec<usize>>,
+}
+
+impl ReachingDefinitions {
+    /// Computes the reaching definitions of the method `cfg` was built for,
+    /// given its descriptor and whether it is static.
+    pub fn new(cfg: &ControlFlowGraph,
+               descriptor: &MethodDescriptor,
+               is_static: bool)
+               -> ReachingDefinitions {
+        let instructions = cfg.instructions().clone();
+
+        let mut definitions = vec![];
+        let mut slot = 0;
+        if !is_static {
+            definitions.push(argument(0, 1));
+            slot += 1;
+        }
+        for param in descriptor.params.iter() {
+            definitions.push(argument(slot, param.slots() as u8));
+            slot += param.slots();
+        }
+        let arguments: BTreeSet<usize> = (0..definitions.len()).collect();
+        let mut defined_by = vec![None; instructions.len()];
+        for (index, &(pc, ref bytecode)) in instructions.iter().enumerate() {
+            if let Some(access) = bytecode.local_access() {
+                if access.writes {
+                    defined_by[index] = Some(definitions.len());
+                    definitions.push(Definition {
+                        pc: Some(pc),
+                        index: access.index,
+                        slots: access.slots,
+                    });
+                }
+            }
+        }
+
+        let analysis = Analysis {
+            cfg: cfg,
+            definitions: &definitions,
+            defined_by: &defined_by,
+        };
+        let blocks = cfg.blocks();
+        let mut block_in: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); blocks.len()];
+        let mut pending = BTreeSet::new();
+        if !blocks.is_empty() {
+            block_in[0] = arguments;
+            pending.insert(0);
+        }
+        while let Some(id) = pending.iter().next().cloned() {
+            pending.remove(&id);
+            let (ins, out) = analysis.transfer(&block_in[id], id);
+            for edge in cfg.successors(id) {
+                let reaching = match edge.kind {
+                    EdgeKind::Exceptional { .. } => {
+                        ins.iter().fold(BTreeSet::new(), |all, set| &all | set)
+                    }
+                    _ => out.clone(),
+                };
+                let before = block_in[edge.to].len();
+                block_in[edge.to].extend(reaching);
+                if block_in[edge.to].len() != before {
+                    pending.insert(edge.to);
+                }
+            }
+        }
+
+        let count = instructions.len();
+        let mut reaching = vec![BTreeSet::new(); count];
+        for block in blocks {
+            let (ins, _) = analysis.transfer(&block_in[block.id], block.id);
+            for (index, set) in block.instructions.clone().zip(ins) {
+                reaching[index] = set;
+            }
+        }
+
+        let mut used = vec![vec![]; count];
+        let mut uses = vec![vec![]; definitions.len()];
+        for (index, &(pc, ref bytecode)) in instructions.iter().enumerate() {
+            let access = match bytecode.local_access() {
+                Some(access) if access.reads => access,
+                _ => continue,
+            };
+            for &id in reaching[index].iter() {
+                let definition = &definitions[id];
+                if (0..access.slots as u16).any(|i| definition.defines(access.index + i)) {
+                    used[index].push(id);
+                    uses[id].push(pc);
+                }
+            }
+        }
+
+        ReachingDefinitions {
+            instructions: instructions,
+            definitions: definitions,
+            defined_by: defined_by,
+            reaching: reaching,
+            used: used,
+            uses: uses,
+        }
+    }
+
+    /// Every definition of the method, arguments first.
+    pub fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+
+    pub fn definition(&self, id: usize) -> &Definition {
+        &self.definitions[id]
+    }
+
+    /// The definition made by the instruction at `pc`, if it writes a local.
+    pub fn definition_at(&self, pc: usize) -> Option<usize> {
+        self.instructions.index_of(pc).and_then(|index| self.defined_by[index])
+    }
+
+    /// The definitions reaching the instruction at `pc`, or `None` if `pc`
+    /// does not start an instruction. Unreachable instructions have none.
+    pub fn reaching(&self, pc: usize) -> Option<&BTreeSet<usize>> {
+        self.instructions.index_of(pc).map(|index| &self.reaching[index])
+    }
+
+    /// The use-def chain of the instruction at `pc`: the definitions whose
+    /// value it may read, in id order. Empty if it reads no local.
+    pub fn definitions_used_at(&self, pc: usize) -> &[usize] {
+        self.instructions.index_of(pc).map_or(&[], |index| &self.used[index])
+    }
+
+    /// The def-use chain of definition `id`: the pcs of the instructions that
+    /// may read its value, in ascending order.
+    pub fn uses_of(&self, id: usize) -> &[usize] {
+        &self.uses[id]
+    }
+
+    /// Groups the definitions into webs: sets of definitions that share a
+    /// use, directly or through other definitions. Each web is one variable
+    /// of the source, so a slot reused for unrelated variables falls into
+    /// several webs. Webs are listed by their smallest definition id, and
+    /// definitions that are never read are webs of their own.
+    pub fn webs(&self) -> Vec<Vec<usize>> {
+        let mut parent: Vec<usize> = (0..self.definitions.len()).collect();
+        for used in self.used.iter() {
+            for pair in used.windows(2) {
+                let (a, b) = (find(&mut parent, pair[0]), find(&mut parent, pair[1]));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+        let mut webs: Vec<Vec<usize>> = vec![];
+        let mut web_of: Vec<Option<usize>> = vec![None; self.definitions.len()];
+        for id in 0..self.definitions.len() {
+            let root = find(&mut parent, id);
+            match web_of[root] {
+                Some(web) => webs[web].push(id),
+                None => {
+                    web_of[root] = Some(webs.len());
+                    webs.push(vec![id]);
+                }
+            }
+        }
+        webs
+    }
+}
+
+fn argument(index: u16, slots: u8) -> Definition {
+    Definition {
+        pc: None,
+        index: index,
+        slots: slots,
+    }
+}
+
+/// Finds the representative of the set containing `id`, compressing the
+/// path to it.
+fn find(parent: &mut [usize], id: usize) -> usize {
+    let mut root = id;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut id = id;
+    while parent[id] != root {
+        let next = parent[id];
+        parent[id] = root;
+        id = next;
+    }
+    root
+}
+
+struct Analysis<'a> {
+    cfg: &'a ControlFlowGraph,
+    definitions: &'a [Definition],
+    defined_by: &'a [Option<usize>],
+}
+
+impl<'a> Analysis<'a> {
+    /// Runs block `id` forwards from the definitions reaching its start, and
+    /// returns the definitions reaching each of its instructions and those
+    /// reaching its end.
+    fn transfer(&self,
+                block_in: &BTreeSet<usize>,
+                id: usize)
+                -> (Vec<BTreeSet<usize>>, BTreeSet<usize>) {
+        let mut reaching = block_in.clone();
+        let mut ins = vec![];
+        for index in self.cfg.block(id).instructions.clone() {
+            ins.push(reaching.clone());
+            if let Some(id) = self.defined_by[index] {
+                let written = self.definitions[id];
+                // A write to either slot of a two-slot value kills it.
+                reaching = reaching.into_iter()
+                    .filter(|&other| {
+                        let other = &self.definitions[other];
+                        !(0..written.slots as u16).any(|i| other.defines(written.index + i))
+                    })
+                    .collect();
+                reaching.insert(id);
+            }
+        }
+        (ins, reaching)
+    }
+}
@@ -0,0 +1,140 @@
+//! An `Interpreter` that tracks which instructions produce each value.
+use std::collections::BTreeSet;
+
+use classfile::{Bytecode, ConstantPool, ExceptionInfo, FieldType};
+
+use dataflow::{Interpreter, Value};
+use super::Result;
+
+/// The instructions that may have produced a value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceValue {
+    /// The number of slots the value occupies.
+    pub size: usize,
+    /// The pcs of the instructions that may have pushed the value or stored
+    /// it in a local. Parameters, caught exceptions and unset locals have no
+    /// sources.
+    pub sources: BTreeSet<usize>,
+}
+
+impl SourceValue {
+    pub fn new(size: usize) -> SourceValue {
+        SourceValue {
+            size: size,
+            sources: BTreeSet::new(),
+        }
+    }
+
+    /// A value produced by the instruction at `pc`.
+    pub fn from_pc(size: usize, pc: usize) -> SourceValue {
+        let mut value = SourceValue::new(size);
+        value.sources.insert(pc);
+        value
+    }
+}
+
+impl Value for SourceValue {
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Computes a `SourceValue` for every value. Merging two values takes the
+/// union of their sources.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SourceInterpreter;
+
+impl SourceInterpreter {
+    pub fn new() -> SourceInterpreter {
+        SourceInterpreter
+    }
+
+    /// The value pushed by `insn`, or `None` if it pushes nothing.
+    fn produce(&self, pc: usize, insn: &Bytecode, cp: &ConstantPool) -> Result<Option<SourceValue>> {
+        let size = try!(insn.stack_effect(cp)).push as usize;
+        if size == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(SourceValue::from_pc(size, pc)))
+        }
+    }
+}
+
+impl Interpreter<SourceValue> for SourceInterpreter {
+    fn new_value(&mut self, field_type: Option<&FieldType>) -> SourceValue {
+        SourceValue::new(field_type.map_or(1, |field_type| field_type.slots() as usize))
+    }
+
+    fn new_exception_value(&mut self, _: &ExceptionInfo, _: Option<&str>) -> SourceValue {
+        SourceValue::new(1)
+    }
+
+    fn new_operation(&mut self, pc: usize, insn: &Bytecode, cp: &ConstantPool) -> Result<SourceValue> {
+        Ok(try!(self.produce(pc, insn, cp)).unwrap())
+    }
+
+    fn copy_operation(&mut self, pc: usize, _: &Bytecode, value: &SourceValue) -> Result<SourceValue> {
+        Ok(SourceValue::from_pc(value.size, pc))
+    }
+
+    fn unary_operation(&mut self,
+                       pc: usize,
+                       insn: &Bytecode,
+                       cp: &ConstantPool,
+                       _: &SourceValue)
+                       -> Result<Option<SourceValue>> {
+        match *insn {
+            Bytecode::iinc { .. } |
+            Bytecode::wide_iinc { .. } => Ok(Some(SourceValue::from_pc(1, pc))),
+            _ => self.produce(pc, insn, cp),
+        }
+    }
+
+    fn binary_operation(&mut self,
+                        pc: usize,
+                        insn: &Bytecode,
+                        cp: &ConstantPool,
+                        _: &SourceValue,
+                        _: &SourceValue)
+                        -> Result<Option<SourceValue>> {
+        self.produce(pc, insn, cp)
+    }
+
+    fn ternary_operation(&mut self,
+                         _: usize,
+                         _: &Bytecode,
+                         _: &SourceValue,
+                         _: &SourceValue,
+                         _: &SourceValue)
+                         -> Result<()> {
+        Ok(())
+    }
+
+    fn nary_operation(&mut self,
+                      pc: usize,
+                      insn: &Bytecode,
+                      cp: &ConstantPool,
+                      _: &[SourceValue])
+                      -> Result<Option<SourceValue>> {
+        self.produce(pc, insn, cp)
+    }
+
+    fn return_operation(&mut self,
+                        _: usize,
+                        _: &Bytecode,
+                        _: &SourceValue,
+                        _: &SourceValue)
+                        -> Result<()> {
+        Ok(())
+    }
+
+    fn merge(&mut self, value1: &SourceValue, value2: &SourceValue) -> SourceValue {
+        if value1.size == value2.size && value1.sources.is_superset(&value2.sources) {
+            return value1.clone();
+        }
+        SourceValue {
+            size: ::std::cmp::min(value1.size, value2.size),
+            sources: value1.sources.union(&value2.sources).cloned().collect(),
+        }
+    }
+}
@@ -0,0 +1,537 @@
+//! Inlining of subroutines.
+//!
+//! `jsr` and `ret` make control flow depend on values held in locals, which
+//! most analyses would rather not handle. `inline_subroutines` rewrites a
+//! method so that every call to a subroutine gets its own copy of the
+//! subroutine's instructions, as compilers have done since class file version
+//! 50. Each `jsr` becomes an `aconst_null`, standing in for the return
+//! address, followed by a `goto` to the copy, and each `ret` becomes a `goto`
+//! back to the instruction after the call.
+use std::collections::{BTreeSet, HashMap};
+
+use classfile::{AttributeInfo, Bytecode, CodeAttribute, ExceptionInfo, InstructionList,
+                LineNumberTableEntry, LocalVariableTableEntry, LocalVariableTypeTableEntry};
+
+use super::{Error, Result};
+
+/// Rewrites `code` so that it has no `jsr` or `ret` instructions, returning
+/// `false` if it had none to begin with.
+///
+/// Exception table entries, line numbers and local variable ranges are copied
+/// along with the instructions they cover. The `StackMapTable` and type
+/// annotations are dropped, as they no longer describe the code; code that
+/// uses subroutines is too old to need them.
+///
+/// Fails without changing `code` if it cannot be decoded, if a subroutine
+/// calls itself, or if the rewritten code is too large for a method.
+pub fn inline_subroutines(code: &mut CodeAttribute) -> Result<bool> {
+    let instructions = try!(code.instruction_list());
+    let uses_subroutines = instructions.iter().any(|&(_, ref insn)| match *insn {
+        Bytecode::jsr { .. } |
+        Bytecode::jsr_w { .. } |
+        Bytecode::ret { .. } |
+        Bytecode::wide_ret { .. } => true,
+        _ => false,
+    });
+    if !uses_subroutines {
+        return Ok(false);
+    }
+
+    let inliner = try!(Inliner::new(&instructions, code));
+    let instances = try!(inliner.instances());
+    let layout = try!(inliner.layout(&instances));
+    let exception_table = inliner.exception_table(&instances, &layout);
+    let mut line_numbers = code.attrs.line_number_table().map(|table| {
+        inliner.line_numbers(&instances, &layout, table)
+    });
+    let mut local_variables = code.attrs.local_variable_table().map(|table| {
+        table.iter()
+            .flat_map(|entry| {
+                inliner.ranges(&instances, &layout, entry.start_pc, entry.length)
+                    .into_iter()
+                    .map(move |(start_pc, length)| {
+                        LocalVariableTableEntry {
+                            start_pc: start_pc,
+                            length: length,
+                            name_index: entry.name_index,
+                            descriptor_index: entry.descriptor_index,
+                            index: entry.index,
+                        }
+                    })
+            })
+            .collect::<Vec<_>>()
+    });
+    let mut local_variable_types = code.attrs.local_variable_type_table().map(|table| {
+        table.iter()
+            .flat_map(|entry| {
+                inliner.ranges(&instances, &layout, entry.start_pc, entry.length)
+                    .into_iter()
+                    .map(move |(start_pc, length)| {
+                        LocalVariableTypeTableEntry {
+                            start_pc: start_pc,
+                            length: length,
+                            name_index: entry.name_index,
+                            signature_index: entry.signature_index,
+                            index: entry.index,
+                        }
+                    })
+            })
+            .collect::<Vec<_>>()
+    });
+
+    code.code = layout.code;
+    code.exception_table = exception_table;
+    code.attrs.retain(|attr| match *attr {
+        AttributeInfo::StackMapTable(_) |
+        AttributeInfo::RuntimeVisibleTypeAnnotations(_) |
+        AttributeInfo::RuntimeInvisibleTypeAnnotations(_) => false,
+        _ => true,
+    });
+    for attr in code.attrs.iter_mut() {
+        match *attr {
+            AttributeInfo::LineNumberTable(ref mut table) => {
+                *table = line_numbers.take().unwrap_or_default();
+            }
+            AttributeInfo::LocalVariableTable(ref mut table) => {
+                *table = local_variables.take().unwrap_or_default();
+            }
+            AttributeInfo::LocalVariableTypeTable(ref mut table) => {
+                *table = local_variable_types.take().unwrap_or_default();
+            }
+            _ => {}
+        }
+    }
+    Ok(true)
+}
+
+/// A copy of the main code or of a subroutine.
+struct Instance {
+    /// The index of the first instruction; 0 for the main code.
+    entry: usize,
+    /// The copy this one was called from, and the index of the calling
+    /// `jsr`. `None` for the main code.
+    caller: Option<(usize, usize)>,
+    /// The copies called by each `jsr` in this one, by the index of the
+    /// `jsr`.
+    callees: HashMap<usize, usize>,
+}
+
+/// An instruction of the rewritten code.
+struct Item {
+    insn: Bytecode,
+    /// The instructions branched to, as an instance and an index.
+    targets: Vec<(usize, usize)>,
+}
+
+struct Layout {
+    code: Vec<u8>,
+    /// The pc of each item, followed by the length of the code.
+    pcs: Vec<usize>,
+    /// The items an instruction of an instance was rewritten to, as a range
+    /// of item indexes.
+    items: HashMap<(usize, usize), (usize, usize)>,
+}
+
+impl Layout {
+    /// The pc of the copy of instruction `index` in `instance`.
+    fn start_pc(&self, instance: usize, index: usize) -> usize {
+        self.pcs[self.items[&(instance, index)].0]
+    }
+
+    /// The pc following the copy of instruction `index` in `instance`.
+    fn end_pc(&self, instance: usize, index: usize) -> usize {
+        self.pcs[self.items[&(instance, index)].1]
+    }
+}
+
+struct Inliner<'a> {
+    instructions: &'a InstructionList,
+    code: &'a CodeAttribute,
+    /// The exception table as ranges of instruction indexes and a handler
+    /// index.
+    handlers: Vec<(usize, usize, usize)>,
+    /// The instructions of the main code and of each subroutine, by entry
+    /// index.
+    bodies: HashMap<usize, BTreeSet<usize>>,
+}
+
+impl<'a> Inliner<'a> {
+    fn new(instructions: &'a InstructionList, code: &'a CodeAttribute) -> Result<Inliner<'a>> {
+        let mut handlers = vec![];
+        for (index, handler) in code.exception_table.iter().enumerate() {
+            match (instructions.index_of(handler.start_pc as usize),
+                   try!(index_or_end(instructions, handler.end_pc as usize)
+                       .ok_or(Error::InvalidExceptionHandler { index: index })),
+                   instructions.index_of(handler.handler_pc as usize)) {
+                (Some(start), end, Some(handler)) if start < end => {
+                    handlers.push((start, end, handler))
+                }
+                _ => return Err(Error::InvalidExceptionHandler { index: index }),
+            }
+        }
+        let mut inliner = Inliner {
+            instructions: instructions,
+            code: code,
+            handlers: handlers,
+            bodies: HashMap::new(),
+        };
+        let main = try!(inliner.body(0, None));
+        for &index in &main {
+            try!(inliner.add_subroutines(index, &main));
+        }
+        inliner.bodies.insert(0, main);
+        Ok(inliner)
+    }
+
+    /// Finds the bodies of the subroutines called from instruction `index`,
+    /// and of the subroutines they call in turn.
+    fn add_subroutines(&mut self, index: usize, main: &BTreeSet<usize>) -> Result<()> {
+        let entry = match try!(self.subroutine_called(index)) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        if self.bodies.contains_key(&entry) {
+            return Ok(());
+        }
+        let body = try!(self.body(entry, Some(main)));
+        self.bodies.insert(entry, body.clone());
+        for index in body {
+            try!(self.add_subroutines(index, main));
+        }
+        Ok(())
+    }
+
+    /// The entry index of the subroutine called by the instruction at
+    /// `index`, or `None` if it is not a `jsr`.
+    fn subroutine_called(&self, index: usize) -> Result<Option<usize>> {
+        let pc = self.instructions.pc(index).unwrap();
+        match *self.instructions.get(index).unwrap() {
+            ref insn @ Bytecode::jsr { .. } |
+            ref insn @ Bytecode::jsr_w { .. } => {
+                let target = insn.branch_targets(pc)[0];
+                match self.instructions.index_of(target) {
+                    Some(entry) => Ok(Some(entry)),
+                    None => Err(Error::InvalidBranchTarget { pc: pc, target: target }),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The instructions reachable from `entry` without entering subroutines
+    /// or following a `ret`. The handlers of instructions in the body are
+    /// part of it too, unless they belong to the main code.
+    fn body(&self, entry: usize, main: Option<&BTreeSet<usize>>) -> Result<BTreeSet<usize>> {
+        let count = self.instructions.len();
+        let mut body = BTreeSet::new();
+        let mut pending = vec![entry];
+        loop {
+            while let Some(index) = pending.pop() {
+                if !body.insert(index) {
+                    continue;
+                }
+                let pc = self.instructions.pc(index).unwrap();
+                let insn = self.instructions.get(index).unwrap();
+                match *insn {
+                    Bytecode::ret { .. } |
+                    Bytecode::wide_ret { .. } => continue,
+                    Bytecode::jsr { .. } |
+                    Bytecode::jsr_w { .. } => {}
+                    _ => {
+                        for target in insn.branch_targets(pc) {
+                            match self.instructions.index_of(target) {
+                                Some(target) => pending.push(target),
+                                None => {
+                                    return Err(Error::InvalidBranchTarget {
+                                        pc: pc,
+                                        target: target,
+                                    })
+                                }
+                            }
+                        }
+                        if !insn.falls_through() {
+                            continue;
+                        }
+                    }
+                }
+                if index + 1 < count {
+                    pending.push(index + 1);
+                }
+            }
+            for &(start, end, handler) in &self.handlers {
+                let owned_by_main = main.map_or(false, |main| main.contains(&handler));
+                if !body.contains(&handler) && !owned_by_main &&
+                   body.range(start..end).next().is_some() {
+                    pending.push(handler);
+                }
+            }
+            if pending.is_empty() {
+                return Ok(body);
+            }
+        }
+    }
+
+    /// Makes a copy of each subroutine for each `jsr` reaching it, starting
+    /// with the main code.
+    fn instances(&self) -> Result<Vec<Instance>> {
+        let mut instances = vec![Instance {
+                                     entry: 0,
+                                     caller: None,
+                                     callees: HashMap::new(),
+                                 }];
+        let mut size = 0;
+        let mut current = 0;
+        while current < instances.len() {
+            let entry = instances[current].entry;
+            size += self.bodies[&entry].len();
+            if size > 65535 {
+                return Err(Error::CodeTooLarge);
+            }
+            for &index in &self.bodies[&entry] {
+                let callee = match try!(self.subroutine_called(index)) {
+                    Some(callee) => callee,
+                    None => continue,
+                };
+                let mut ancestor = Some(current);
+                while let Some(instance) = ancestor {
+                    if instances[instance].caller.is_some() && instances[instance].entry == callee {
+                        return Err(Error::RecursiveSubroutine {
+                            pc: self.instructions.pc(index).unwrap(),
+                        });
+                    }
+                    ancestor = instances[instance].caller.map(|(caller, _)| caller);
+                }
+                let id = instances.len();
+                instances.push(Instance {
+                    entry: callee,
+                    caller: Some((current, index)),
+                    callees: HashMap::new(),
+                });
+                instances[current].callees.insert(index, id);
+            }
+            current += 1;
+        }
+        Ok(instances)
+    }
+
+    /// Lays out the copies one after the other, and encodes them.
+    fn layout(&self, instances: &[Instance]) -> Result<Layout> {
+        let mut items = vec![];
+        let mut ranges = HashMap::new();
+        for (id, instance) in instances.iter().enumerate() {
+            for &index in &self.bodies[&instance.entry] {
+                let first = items.len();
+                let pc = self.instructions.pc(index).unwrap();
+                let insn = self.instructions.get(index).unwrap();
+                match *insn {
+                    Bytecode::jsr { .. } |
+                    Bytecode::jsr_w { .. } => {
+                        let callee = instance.callees[&index];
+                        items.push(Item {
+                            insn: Bytecode::aconst_null,
+                            targets: vec![],
+                        });
+                        items.push(Item {
+                            insn: Bytecode::goto { branchoffset: 0 },
+                            targets: vec![(callee, instances[callee].entry)],
+                        });
+                    }
+                    Bytecode::ret { .. } |
+                    Bytecode::wide_ret { .. } => {
+                        let (caller, jsr) = match instance.caller {
+                            Some(caller) => caller,
+                            None => {
+                                return Err(Error::InvalidFrame {
+                                    pc: pc,
+                                    message: "ret outside of a subroutine".to_string(),
+                                })
+                            }
+                        };
+                        items.push(Item {
+                            insn: Bytecode::goto { branchoffset: 0 },
+                            targets: vec![(caller, jsr + 1)],
+                        });
+                    }
+                    _ => {
+                        let targets = insn.branch_targets(pc)
+                            .into_iter()
+                            .map(|target| (id, self.instructions.index_of(target).unwrap()))
+                            .collect();
+                        items.push(Item {
+                            insn: insn.clone(),
+                            targets: targets,
+                        });
+                    }
+                }
+                ranges.insert((id, index), (first, items.len()));
+            }
+        }
+
+        // Lengths depend only on the kind of each instruction and, for
+        // switches, its alignment. Widen any goto whose offset no longer
+        // fits, and lay out again.
+        loop {
+            let mut code = vec![];
+            let mut pcs = vec![];
+            for item in &items {
+                pcs.push(code.len());
+                item.insn.encode(&mut code);
+            }
+            pcs.push(code.len());
+            if code.len() > 65535 {
+                return Err(Error::CodeTooLarge);
+            }
+
+            let mut widened = false;
+            for (index, item) in items.iter_mut().enumerate() {
+                if item.targets.is_empty() {
+                    continue;
+                }
+                let offsets: Vec<i32> = item.targets
+                    .iter()
+                    .map(|target| {
+                        let (first, _) = ranges[target];
+                        pcs[first] as i32 - pcs[index] as i32
+                    })
+                    .collect();
+                item.insn = match (item.insn.with_branch_offsets(&offsets), &item.insn) {
+                    (Some(insn), _) => insn,
+                    (None, &Bytecode::goto { .. }) => {
+                        widened = true;
+                        Bytecode::goto_w { branchoffset: offsets[0] }
+                    }
+                    (None, _) => return Err(Error::CodeTooLarge),
+                };
+            }
+            if !widened {
+                let mut code = vec![];
+                for item in &items {
+                    item.insn.encode(&mut code);
+                }
+                return Ok(Layout {
+                    code: code,
+                    pcs: pcs,
+                    items: ranges,
+                });
+            }
+        }
+    }
+
+    /// Copies each exception table entry into every copy of the instructions
+    /// it covers. Entries keep their original order, so the innermost
+    /// handler still comes first.
+    fn exception_table(&self, instances: &[Instance], layout: &Layout) -> Vec<ExceptionInfo> {
+        let mut table = vec![];
+        for (&(start, end, handler), original) in self.handlers
+            .iter()
+            .zip(self.code.exception_table.iter()) {
+            for (id, instance) in instances.iter().enumerate() {
+                let body = &self.bodies[&instance.entry];
+                // The handler is in the innermost copy containing it.
+                let mut owner = Some(id);
+                while let Some(candidate) = owner {
+                    if self.bodies[&instances[candidate].entry].contains(&handler) {
+                        break;
+                    }
+                    owner = instances[candidate].caller.map(|(caller, _)| caller);
+                }
+                let owner = match owner {
+                    Some(owner) => owner,
+                    None => continue,
+                };
+                for (first, last) in runs(body, start, end) {
+                    table.push(ExceptionInfo {
+                        start_pc: layout.start_pc(id, first) as u16,
+                        end_pc: layout.end_pc(id, last) as u16,
+                        handler_pc: layout.start_pc(owner, handler) as u16,
+                        catch_type: original.catch_type,
+                    });
+                }
+            }
+        }
+        table
+    }
+
+    /// Maps a range of pcs to the ranges covering its copies, as start pcs
+    /// and lengths. Ranges that do not fall on instruction boundaries are
+    /// dropped.
+    fn ranges(&self,
+              instances: &[Instance],
+              layout: &Layout,
+              start_pc: u16,
+              length: u16)
+              -> Vec<(u16, u16)> {
+        let end_pc = start_pc as usize + length as usize;
+        let (start, end) = match (self.instructions.index_of(start_pc as usize),
+                                  index_or_end(self.instructions, end_pc)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return vec![],
+        };
+        let mut ranges = vec![];
+        for (id, instance) in instances.iter().enumerate() {
+            for (first, last) in runs(&self.bodies[&instance.entry], start, end) {
+                let start_pc = layout.start_pc(id, first);
+                ranges.push((start_pc as u16, (layout.end_pc(id, last) - start_pc) as u16));
+            }
+        }
+        ranges
+    }
+
+    /// Gives each copy the line numbers of the instructions it copies.
+    fn line_numbers(&self,
+                    instances: &[Instance],
+                    layout: &Layout,
+                    table: &[LineNumberTableEntry])
+                    -> Vec<LineNumberTableEntry> {
+        let line_at = |pc: usize| {
+            table.iter()
+                .filter(|entry| entry.start_pc as usize <= pc)
+                .max_by_key(|entry| entry.start_pc)
+                .map(|entry| entry.line_number)
+        };
+        let mut lines = vec![];
+        for (id, instance) in instances.iter().enumerate() {
+            let mut previous: Option<(usize, Option<u16>)> = None;
+            for &index in &self.bodies[&instance.entry] {
+                let line = line_at(self.instructions.pc(index).unwrap());
+                let continues = match previous {
+                    Some((previous_index, previous_line)) => {
+                        previous_index + 1 == index && previous_line == line
+                    }
+                    None => false,
+                };
+                if let (false, Some(line)) = (continues, line) {
+                    lines.push(LineNumberTableEntry {
+                        start_pc: layout.start_pc(id, index) as u16,
+                        line_number: line,
+                    });
+                }
+                previous = Some((index, line));
+            }
+        }
+        lines
+    }
+}
+
+/// The index of the instruction at `pc`, or the number of instructions if
+/// `pc` is the end of the code.
+fn index_or_end(instructions: &InstructionList, pc: usize) -> Option<usize> {
+    if pc == instructions.code_length() {
+        Some(instructions.len())
+    } else {
+        instructions.index_of(pc)
+    }
+}
+
+/// The runs of consecutive instructions of `body` in `start..end`, as the
+/// first and last index of each.
+fn runs(body: &BTreeSet<usize>, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for &index in body.range(start..end) {
+        match runs.last_mut() {
+            Some(&mut (_, ref mut last)) if *last + 1 == index => *last = index,
+            _ => runs.push((index, index)),
+        }
+    }
+    runs
+}
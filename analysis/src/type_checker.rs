@@ -0,0 +1,258 @@
+//! Verification by type checking (JVMS 4.10.1).
+//!
+//! Class files of version 50 and later declare the types of the locals and
+//! operand stack at every branch target and exception handler in a
+//! `StackMapTable` attribute. The type checker makes a single pass over each
+//! method, simulating every instruction on the types it operates on and
+//! checking that the types reaching each declared frame are assignable to it.
+//! Subroutines (`jsr` and `ret`) are not allowed.
+use std::collections::HashMap;
+
+use classfile::{ClassFile, CodeAttribute, MethodInfo, StackMapFrame, VerificationTypeInfo};
+
+use hierarchy::ClassHierarchy;
+use method_verifier::{MethodVerifier, TypeFrame, push_type};
+use verifier::VerificationType;
+use super::Result;
+
+use self::VerificationType as Type;
+
+/// Verifies the methods of a class against their `StackMapTable` frames.
+pub struct TypeChecker<'a> {
+    class: &'a ClassFile,
+    hierarchy: &'a ClassHierarchy,
+}
+
+impl<'a> TypeChecker<'a> {
+    /// A type checker for `class`. The hierarchy must know `class` itself
+    /// and its superclasses, as well as every class the checks ask about.
+    pub fn new(class: &'a ClassFile, hierarchy: &'a ClassHierarchy) -> TypeChecker<'a> {
+        TypeChecker {
+            class: class,
+            hierarchy: hierarchy,
+        }
+    }
+
+    /// Verifies every method of the class, stopping at the first failure.
+    ///
+    /// Fails with `Error::Verify` if a method fails verification, or
+    /// `Error::UnknownClass` if the hierarchy does not know a class the
+    /// checks depend on.
+    pub fn verify(&self) -> Result<()> {
+        for method in &self.class.methods {
+            try!(self.verify_method(method));
+        }
+        Ok(())
+    }
+
+    /// Verifies a single method. Methods without code always pass.
+    pub fn verify_method(&self, method: &MethodInfo) -> Result<()> {
+        let code = match method.attrs.code() {
+            Some(code) => code,
+            None => return Ok(()),
+        };
+        let mut verifier = try!(MethodVerifier::new(self.class, self.hierarchy, method, code));
+        verifier.check(code)
+    }
+}
+
+impl<'a> MethodVerifier<'a> {
+    /// Checks the method against its stack map frames.
+    fn check(&mut self, code: &CodeAttribute) -> Result<()> {
+        let initial = try!(self.initial_frame());
+        let stack_map = try!(self.stack_map(code, &initial));
+        let handlers = try!(self.handlers(code));
+
+        let count = self.instructions.len();
+        let mut frame = Some(initial);
+        for index in 0..count {
+            let pc = self.instructions.pc(index).unwrap();
+            let insn = self.instructions.get(index).unwrap().clone();
+            self.pc = Some(pc);
+
+            let current = match (frame, stack_map.get(&pc)) {
+                (Some(current), Some(declared)) => {
+                    if !try!(self.is_frame_assignable(&current, declared)) {
+                        return self.fail("Instruction type does not match stack map");
+                    }
+                    declared.clone()
+                }
+                (None, Some(declared)) => declared.clone(),
+                (Some(current), None) => current,
+                (None, None) => return self.fail("Expecting a stack map frame"),
+            };
+
+            for &(handler_pc, ref catch_type) in &handlers[index] {
+                let exception = TypeFrame {
+                    locals: current.locals.clone(),
+                    stack: vec![catch_type.clone()],
+                    this_uninit: current.this_uninit,
+                };
+                let assignable = match stack_map.get(&handler_pc) {
+                    Some(declared) => try!(self.is_frame_assignable(&exception, declared)),
+                    None => false,
+                };
+                if !assignable {
+                    return self.fail(&format!("Stack map does not match the one at exception \
+                                               handler {}",
+                                              handler_pc));
+                }
+            }
+
+            let mut next = current;
+            try!(self.execute(&mut next, pc, &insn));
+
+            for target in insn.branch_targets(pc) {
+                let assignable = match stack_map.get(&target) {
+                    Some(declared) => try!(self.is_frame_assignable(&next, declared)),
+                    None => {
+                        return self.fail(&format!("Expecting a stackmap frame at branch target {}",
+                                                  target))
+                    }
+                };
+                if !assignable {
+                    return self.fail(&format!("Inconsistent stackmap frames at branch target {}",
+                                              target));
+                }
+            }
+
+            frame = if insn.falls_through() {
+                if index + 1 == count {
+                    return self.fail("Control flow falls through code end");
+                }
+                Some(next)
+            } else {
+                None
+            };
+        }
+        Ok(())
+    }
+
+    /// Decodes the `StackMapTable` into a frame per pc.
+    fn stack_map(&self,
+                 code: &CodeAttribute,
+                 initial: &TypeFrame)
+                 -> Result<HashMap<usize, TypeFrame>> {
+        let mut frames = HashMap::new();
+        let entries = match code.attrs.stack_map_table() {
+            Some(entries) => entries,
+            None => return Ok(frames),
+        };
+
+        // Chop and append frames work on whole types, so keep the locals
+        // unexpanded: one entry per `long` or `double`.
+        let mut locals: Vec<Type> = vec![];
+        let mut slot = 0;
+        while slot < initial.locals.len() {
+            if initial.locals[slot] == Type::Top {
+                break;
+            }
+            locals.push(initial.locals[slot].clone());
+            slot += initial.locals[slot].size();
+        }
+        let mut this_uninit = initial.this_uninit;
+
+        let mut pc: Option<usize> = None;
+        for entry in entries {
+            let (offset_delta, stack) = match *entry {
+                StackMapFrame::SameFrame { frame_type } => (frame_type as usize, vec![]),
+                StackMapFrame::SameLocals1StackItemFrame { frame_type, ref stack } => {
+                    (frame_type as usize - 64, try!(self.types(stack)))
+                }
+                StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, ref stack } => {
+                    (offset_delta as usize, try!(self.types(stack)))
+                }
+                StackMapFrame::ChopFrame { frame_type, offset_delta } => {
+                    let chopped = 251 - frame_type as usize;
+                    if chopped > locals.len() {
+                        return self.fail("StackMapTable error: chop frame removes too many locals");
+                    }
+                    let len = locals.len() - chopped;
+                    locals.truncate(len);
+                    (offset_delta as usize, vec![])
+                }
+                StackMapFrame::SameFrameExtended { offset_delta } => (offset_delta as usize, vec![]),
+                StackMapFrame::AppendFrame { offset_delta, locals: ref appended, .. } => {
+                    locals.extend(try!(self.types(appended)));
+                    (offset_delta as usize, vec![])
+                }
+                StackMapFrame::FullFrame { offset_delta, locals: ref full, ref stack } => {
+                    locals = try!(self.types(full));
+                    (offset_delta as usize, try!(self.types(stack)))
+                }
+            };
+            let frame_pc = match pc {
+                Some(previous) => previous + offset_delta + 1,
+                None => offset_delta,
+            };
+            pc = Some(frame_pc);
+            if !self.instructions.is_boundary(frame_pc) {
+                return self.fail(&format!("StackMapTable error: bad offset {}", frame_pc));
+            }
+
+            let mut frame = TypeFrame {
+                locals: vec![],
+                stack: vec![],
+                this_uninit: false,
+            };
+            for local in &locals {
+                push_type(&mut frame.locals, local.clone());
+            }
+            for value in stack {
+                push_type(&mut frame.stack, value);
+            }
+            if frame.locals.len() > self.max_locals {
+                return self.fail(&format!("StackMapTable error: local size too big at {}",
+                                          frame_pc));
+            }
+            if frame.stack.len() > self.max_stack {
+                return self.fail(&format!("StackMapTable error: stack size too big at {}",
+                                          frame_pc));
+            }
+            frame.locals.resize(self.max_locals, Type::Top);
+            for value in frame.locals.iter().chain(frame.stack.iter()) {
+                if let Type::Uninitialized(new_pc) = *value {
+                    if self.new_class(new_pc).is_none() {
+                        return self.fail(&format!("StackMapTable error: uninitialized offset {} \
+                                                   is not a new instruction",
+                                                  new_pc));
+                    }
+                }
+            }
+            // The flag is set while uninitializedThis remains in the locals.
+            this_uninit = this_uninit && frame.locals.contains(&Type::UninitializedThis);
+            frame.this_uninit = this_uninit;
+            frames.insert(frame_pc, frame);
+        }
+        Ok(frames)
+    }
+
+    fn types(&self, infos: &[VerificationTypeInfo]) -> Result<Vec<Type>> {
+        let mut types = vec![];
+        for info in infos {
+            match Type::from_info(info, self.cp) {
+                Some(value) => types.push(value),
+                None => return self.fail("StackMapTable error: bad class index"),
+            }
+        }
+        Ok(types)
+    }
+
+    fn is_frame_assignable(&self, from: &TypeFrame, to: &TypeFrame) -> Result<bool> {
+        if from.stack.len() != to.stack.len() || from.locals.len() != to.locals.len() {
+            return Ok(false);
+        }
+        if from.this_uninit && !to.this_uninit {
+            return Ok(false);
+        }
+        for (from, to) in from.locals.iter().chain(from.stack.iter()).zip(to.locals
+            .iter()
+            .chain(to.stack.iter())) {
+            if !try!(from.is_assignable_to(to, self.hierarchy)) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+}
@@ -0,0 +1,364 @@
+//! Verification by type inference (JVMS 4.10.2).
+//!
+//! Class files older than version 50 carry no stack map frames, so the frame
+//! at each instruction has to be inferred. The type inferencer runs the
+//! instructions to a fixpoint, merging the frames of the paths that reach
+//! each instruction, and checks every instruction against the merged frame.
+//!
+//! Subroutines are handled as in JVMS 4.10.2.4. A `jsr` pushes a
+//! `ReturnAddress` for the subroutine, and a `ret` continues after every
+//! `jsr` to that subroutine. Locals that the subroutine never stores to keep
+//! the types they had at each call site, so they may hold different types at
+//! different calls.
+use std::collections::{BTreeMap, BTreeSet};
+
+use classfile::{Bytecode, ClassFile, CodeAttribute, MethodInfo};
+
+use hierarchy::ClassHierarchy;
+use method_verifier::{MethodVerifier, TypeFrame};
+use verifier::VerificationType;
+use super::Result;
+
+use self::VerificationType as Type;
+
+/// Verifies the methods of a class by inferring the types of their locals
+/// and operand stack.
+pub struct TypeInferencer<'a> {
+    class: &'a ClassFile,
+    hierarchy: &'a ClassHierarchy,
+}
+
+impl<'a> TypeInferencer<'a> {
+    /// A type inferencer for `class`. The hierarchy must know `class` itself
+    /// and its superclasses, as well as every class the checks ask about.
+    pub fn new(class: &'a ClassFile, hierarchy: &'a ClassHierarchy) -> TypeInferencer<'a> {
+        TypeInferencer {
+            class: class,
+            hierarchy: hierarchy,
+        }
+    }
+
+    /// Verifies every method of the class, stopping at the first failure.
+    ///
+    /// Fails with `Error::Verify` if a method fails verification, or
+    /// `Error::UnknownClass` if the hierarchy does not know a class the
+    /// checks depend on.
+    pub fn verify(&self) -> Result<()> {
+        for method in &self.class.methods {
+            try!(self.verify_method(method));
+        }
+        Ok(())
+    }
+
+    /// Verifies a single method. Methods without code always pass.
+    pub fn verify_method(&self, method: &MethodInfo) -> Result<()> {
+        let code = match method.attrs.code() {
+            Some(code) => code,
+            None => return Ok(()),
+        };
+        let mut verifier = try!(MethodVerifier::new(self.class, self.hierarchy, method, code));
+        verifier.infer(code)
+    }
+}
+
+/// A subroutine and the `jsr` instructions that call it.
+struct Subroutine {
+    /// The indexes of the `jsr` instructions calling the subroutine.
+    callers: Vec<usize>,
+    /// The entry pcs of the subroutines this one calls.
+    calls: BTreeSet<usize>,
+    /// The locals stored to by the subroutine or the subroutines it calls.
+    modified: Vec<bool>,
+    /// The merged frames of the `ret` instructions returning from the
+    /// subroutine, once one has been reached.
+    exit: Option<TypeFrame>,
+}
+
+impl<'a> MethodVerifier<'a> {
+    /// Infers the frame at each instruction, checking every instruction as it
+    /// is reached.
+    fn infer(&mut self, code: &CodeAttribute) -> Result<()> {
+        let count = self.instructions.len();
+        if count == 0 {
+            return self.fail("Method has no code");
+        }
+        let handlers = try!(self.handlers(code));
+        let mut subroutines = try!(self.subroutines());
+        let mut frames: Vec<Option<TypeFrame>> = vec![None; count];
+        frames[0] = Some(try!(self.initial_frame()));
+
+        // Visit instructions in code order, which converges quickly for the
+        // forward branches that make up most control flow.
+        let mut pending = BTreeSet::new();
+        pending.insert(0);
+        while let Some(index) = pending.iter().next().cloned() {
+            pending.remove(&index);
+            let pc = self.instructions.pc(index).unwrap();
+            let insn = self.instructions.get(index).unwrap().clone();
+            let frame = frames[index].clone().unwrap();
+            self.pc = Some(pc);
+
+            for &(handler_pc, ref catch_type) in &handlers[index] {
+                let exception = TypeFrame {
+                    locals: frame.locals.clone(),
+                    stack: vec![catch_type.clone()],
+                    this_uninit: frame.this_uninit,
+                };
+                try!(self.merge_into(&mut frames, &mut pending, handler_pc, &exception));
+            }
+
+            match insn {
+                Bytecode::jsr { .. } |
+                Bytecode::jsr_w { .. } => {
+                    let entry = insn.branch_targets(pc)[0];
+                    let mut called = frame.clone();
+                    try!(self.push(&mut called, Type::ReturnAddress(entry)));
+                    try!(self.merge_into(&mut frames, &mut pending, entry, &called));
+
+                    let subroutine = &subroutines[&entry];
+                    if let Some(ref exit) = subroutine.exit {
+                        let returned = return_frame(&frame, exit, &subroutine.modified);
+                        try!(self.merge_return(&mut frames, &mut pending, index, &returned));
+                    }
+                }
+                Bytecode::ret { .. } |
+                Bytecode::wide_ret { .. } => {
+                    let local = insn.local_access().unwrap().index as usize;
+                    let entry = match frame.locals.get(local) {
+                        Some(&Type::ReturnAddress(entry)) => entry,
+                        Some(value) => {
+                            return self.fail(&format!("Bad local variable type: Type {} \
+                                                       (current frame, locals[{}]) is not a \
+                                                       return address",
+                                                      value,
+                                                      local))
+                        }
+                        None => {
+                            return self.fail(&format!("Illegal local variable number {}", local))
+                        }
+                    };
+                    let subroutine = subroutines.get_mut(&entry).unwrap();
+                    let exit = match subroutine.exit {
+                        Some(ref exit) => try!(self.merge_frames(exit, &frame)),
+                        None => frame.clone(),
+                    };
+                    if subroutine.exit.as_ref() == Some(&exit) {
+                        continue;
+                    }
+                    for &caller in &subroutine.callers {
+                        if let Some(ref caller_frame) = frames[caller].clone() {
+                            let returned = return_frame(caller_frame, &exit, &subroutine.modified);
+                            try!(self.merge_return(&mut frames, &mut pending, caller, &returned));
+                        }
+                    }
+                    subroutine.exit = Some(exit);
+                }
+                _ => {
+                    let mut next = frame;
+                    try!(self.execute(&mut next, pc, &insn));
+                    for target in insn.branch_targets(pc) {
+                        try!(self.merge_into(&mut frames, &mut pending, target, &next));
+                    }
+                    if insn.falls_through() {
+                        let next_pc = match self.instructions.pc(index + 1) {
+                            Some(next_pc) => next_pc,
+                            None => return self.fail("Falling off the end of the code"),
+                        };
+                        try!(self.merge_into(&mut frames, &mut pending, next_pc, &next));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges `frame` into the frame at `pc`, queueing the instruction there
+    /// if its frame changed.
+    fn merge_into(&self,
+                  frames: &mut Vec<Option<TypeFrame>>,
+                  pending: &mut BTreeSet<usize>,
+                  pc: usize,
+                  frame: &TypeFrame)
+                  -> Result<()> {
+        let index = match self.instructions.index_of(pc) {
+            Some(index) => index,
+            None => return self.fail(&format!("Illegal target of jump or branch {}", pc)),
+        };
+        let merged = match frames[index] {
+            Some(ref existing) => try!(self.merge_frames(existing, frame)),
+            None => frame.clone(),
+        };
+        if frames[index].as_ref() != Some(&merged) {
+            frames[index] = Some(merged);
+            pending.insert(index);
+        }
+        Ok(())
+    }
+
+    /// Merges the frame returned to by a subroutine into the instruction
+    /// after the `jsr` at `caller`.
+    fn merge_return(&self,
+                    frames: &mut Vec<Option<TypeFrame>>,
+                    pending: &mut BTreeSet<usize>,
+                    caller: usize,
+                    frame: &TypeFrame)
+                    -> Result<()> {
+        match self.instructions.pc(caller + 1) {
+            Some(pc) => self.merge_into(frames, pending, pc, frame),
+            None => self.fail("Falling off the end of the code"),
+        }
+    }
+
+    fn merge_frames(&self, frame1: &TypeFrame, frame2: &TypeFrame) -> Result<TypeFrame> {
+        if frame1.stack.len() != frame2.stack.len() {
+            return self.fail(&format!("Inconsistent stack height {} != {}",
+                                      frame1.stack.len(),
+                                      frame2.stack.len()));
+        }
+        let mut merged = TypeFrame {
+            locals: Vec::with_capacity(frame1.locals.len()),
+            stack: Vec::with_capacity(frame1.stack.len()),
+            this_uninit: frame1.this_uninit || frame2.this_uninit,
+        };
+        for (value1, value2) in frame1.locals.iter().zip(frame2.locals.iter()) {
+            merged.locals.push(try!(value1.merge(value2, self.hierarchy)));
+        }
+        for (value1, value2) in frame1.stack.iter().zip(frame2.stack.iter()) {
+            let value = try!(value1.merge(value2, self.hierarchy));
+            if value == Type::Top && *value1 != Type::Top {
+                return self.fail(&format!("Mismatched stack types: Type {} cannot be merged \
+                                           with {}",
+                                          value1,
+                                          value2));
+            }
+            merged.stack.push(value);
+        }
+        Ok(merged)
+    }
+
+    /// Finds the subroutines called by `jsr` instructions, keyed by their
+    /// entry pcs.
+    ///
+    /// A subroutine is made up of the instructions reachable from its entry
+    /// without following exception handlers or entering nested subroutines;
+    /// a nested `jsr` continues with the instruction after it.
+    fn subroutines(&self) -> Result<BTreeMap<usize, Subroutine>> {
+        let count = self.instructions.len();
+        let mut subroutines = BTreeMap::new();
+        for (index, &(pc, ref insn)) in self.instructions.iter().enumerate() {
+            match *insn {
+                Bytecode::jsr { .. } |
+                Bytecode::jsr_w { .. } => {
+                    let entry = insn.branch_targets(pc)[0];
+                    subroutines.entry(entry)
+                        .or_insert_with(|| {
+                            Subroutine {
+                                callers: vec![],
+                                calls: BTreeSet::new(),
+                                modified: vec![false; self.max_locals],
+                                exit: None,
+                            }
+                        })
+                        .callers
+                        .push(index);
+                }
+                _ => {}
+            }
+        }
+
+        for (&entry, subroutine) in &mut subroutines {
+            let start = match self.instructions.index_of(entry) {
+                Some(start) => start,
+                None => return self.fail(&format!("Illegal target of jsr {}", entry)),
+            };
+            let mut visited = vec![false; count];
+            let mut pending = vec![start];
+            while let Some(index) = pending.pop() {
+                if visited[index] {
+                    continue;
+                }
+                visited[index] = true;
+                let pc = self.instructions.pc(index).unwrap();
+                let insn = self.instructions.get(index).unwrap();
+                if let Some(access) = insn.local_access() {
+                    if access.writes {
+                        let start = access.index as usize;
+                        let end = start + access.slots as usize;
+                        if end > self.max_locals {
+                            let message = format!("Illegal local variable number {}", start);
+                            return self.fail_at(Some(pc), &message);
+                        }
+                        for modified in &mut subroutine.modified[start..end] {
+                            *modified = true;
+                        }
+                    }
+                }
+                match *insn {
+                    Bytecode::ret { .. } |
+                    Bytecode::wide_ret { .. } => {}
+                    Bytecode::jsr { .. } |
+                    Bytecode::jsr_w { .. } => {
+                        subroutine.calls.insert(insn.branch_targets(pc)[0]);
+                        if index + 1 < count {
+                            pending.push(index + 1);
+                        }
+                    }
+                    _ => {
+                        for target in insn.branch_targets(pc) {
+                            if let Some(target) = self.instructions.index_of(target) {
+                                pending.push(target);
+                            }
+                        }
+                        if insn.falls_through() && index + 1 < count {
+                            pending.push(index + 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        // A subroutine also modifies whatever the subroutines it calls modify.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let entries: Vec<usize> = subroutines.keys().cloned().collect();
+            for entry in entries {
+                let calls: Vec<usize> = subroutines[&entry].calls.iter().cloned().collect();
+                for callee in calls {
+                    let modified = subroutines[&callee].modified.clone();
+                    let subroutine = subroutines.get_mut(&entry).unwrap();
+                    for (local, &callee_modified) in modified.iter().enumerate() {
+                        if callee_modified && !subroutine.modified[local] {
+                            subroutine.modified[local] = true;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(subroutines)
+    }
+}
+
+/// The frame after a subroutine returns to a caller whose frame before the
+/// `jsr` was `caller`. Locals the subroutine modified come from its exit
+/// frame; the others keep their types from the call site.
+fn return_frame(caller: &TypeFrame, exit: &TypeFrame, modified: &[bool]) -> TypeFrame {
+    let mut locals: Vec<Type> = caller.locals
+        .iter()
+        .zip(exit.locals.iter())
+        .zip(modified.iter())
+        .map(|((caller, exit), &modified)| if modified { exit } else { caller }.clone())
+        .collect();
+    // A long or double whose second slot was overwritten is unusable.
+    for local in 0..locals.len().saturating_sub(1) {
+        if locals[local].size() == 2 && locals[local + 1] != Type::Top {
+            locals[local] = Type::Top;
+        }
+    }
+    TypeFrame {
+        locals: locals,
+        stack: exit.stack.clone(),
+        this_uninit: exit.this_uninit,
+    }
+}
@@ -0,0 +1,284 @@
+//! Types and errors shared by the bytecode verifiers.
+use std::fmt;
+
+use classfile::{ClassFile, ConstantPool, FieldType, VerificationTypeInfo};
+
+use hierarchy::ClassHierarchy;
+use type_checker::TypeChecker;
+use type_inferencer::TypeInferencer;
+use super::{Error, Result};
+
+/// Verifies every method of `class` as the JVM does: by type checking for
+/// class files of version 50 and later, and by type inference for older
+/// ones. Version 50 class files that fail type checking are given a second
+/// chance with type inference.
+pub fn verify(class: &ClassFile, hierarchy: &ClassHierarchy) -> Result<()> {
+    if class.major_version < 50 {
+        return TypeInferencer::new(class, hierarchy).verify();
+    }
+    match TypeChecker::new(class, hierarchy).verify() {
+        Err(Error::Verify(_)) if class.major_version == 50 => {
+            TypeInferencer::new(class, hierarchy).verify()
+        }
+        result => result,
+    }
+}
+
+/// The type of a local variable or operand stack entry, as seen by the
+/// verifier (JVMS 4.10.1.2).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum VerificationType {
+    /// An unusable value, and the second slot of a `long` or `double`.
+    Top,
+    /// An `int`, or a `boolean`, `byte`, `char` or `short` widened to one.
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` in a constructor, before the superclass constructor has run.
+    UninitializedThis,
+    /// An object created by the `new` at the given pc, before its constructor
+    /// has run.
+    Uninitialized(usize),
+    /// An instance of the class with the given internal name, or of the array
+    /// type with the given descriptor, for example `[I`.
+    Object(String),
+    /// The address pushed by a `jsr` to the subroutine starting at the given
+    /// pc. Only found by the type inferencer.
+    ReturnAddress(usize),
+}
+
+impl VerificationType {
+    /// The type of values of `field_type` on the operand stack.
+    pub fn of(field_type: &FieldType) -> VerificationType {
+        match *field_type {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short |
+            FieldType::Int => VerificationType::Integer,
+            FieldType::Float => VerificationType::Float,
+            FieldType::Long => VerificationType::Long,
+            FieldType::Double => VerificationType::Double,
+            FieldType::Object(ref name) => VerificationType::Object(name.clone()),
+            FieldType::Array(_) => VerificationType::Object(field_type.to_string()),
+        }
+    }
+
+    /// The type described by a `StackMapTable` entry, or `None` if it refers
+    /// to a constant that is not a class.
+    pub fn from_info(info: &VerificationTypeInfo, cp: &ConstantPool) -> Option<VerificationType> {
+        let verification_type = match *info {
+            VerificationTypeInfo::Top => VerificationType::Top,
+            VerificationTypeInfo::Integer => VerificationType::Integer,
+            VerificationTypeInfo::Float => VerificationType::Float,
+            VerificationTypeInfo::Long => VerificationType::Long,
+            VerificationTypeInfo::Double => VerificationType::Double,
+            VerificationTypeInfo::Null => VerificationType::Null,
+            VerificationTypeInfo::UninitializedThis => VerificationType::UninitializedThis,
+            VerificationTypeInfo::Uninitialized(ref info) => {
+                VerificationType::Uninitialized(info.offset as usize)
+            }
+            VerificationTypeInfo::Object(ref info) => {
+                match cp.class_name(info.cpool_index) {
+                    Some(name) => VerificationType::Object(name.clone()),
+                    None => return None,
+                }
+            }
+        };
+        Some(verification_type)
+    }
+
+    /// The number of slots taken by a value of this type.
+    pub fn size(&self) -> usize {
+        match *self {
+            VerificationType::Long | VerificationType::Double => 2,
+            _ => 1,
+        }
+    }
+
+    /// Returns `true` for `null`, object, array and uninitialized types.
+    pub fn is_reference(&self) -> bool {
+        match *self {
+            VerificationType::Null |
+            VerificationType::UninitializedThis |
+            VerificationType::Uninitialized(_) |
+            VerificationType::Object(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` for `null` and array types.
+    pub fn is_array(&self) -> bool {
+        match *self {
+            VerificationType::Null => true,
+            VerificationType::Object(ref name) => name.starts_with('['),
+            _ => false,
+        }
+    }
+
+    /// The type of the elements of an array type, or `None` if this is not
+    /// an array type. The component of `null` is `null`.
+    pub fn component(&self) -> Option<VerificationType> {
+        match *self {
+            VerificationType::Null => Some(VerificationType::Null),
+            VerificationType::Object(ref name) if name.starts_with('[') => {
+                FieldType::parse(&name[1..]).ok().map(|field_type| VerificationType::of(&field_type))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if a value of this type may be used where a value of
+    /// type `to` is expected.
+    ///
+    /// As in the JVM's verifier, every reference type is assignable to an
+    /// interface type; the check is deferred to run time.
+    pub fn is_assignable_to(&self,
+                            to: &VerificationType,
+                            hierarchy: &ClassHierarchy)
+                            -> Result<bool> {
+        if self == to {
+            return Ok(true);
+        }
+        match (self, to) {
+            (_, &VerificationType::Top) => Ok(true),
+            (&VerificationType::Null, &VerificationType::Object(_)) => Ok(true),
+            (&VerificationType::Object(ref from), &VerificationType::Object(ref to)) => {
+                is_class_assignable(from, to, hierarchy)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// The most specific type that both this type and `other` are assignable
+    /// to, used where inferred frames meet (JVMS 4.10.2.2). Two classes merge
+    /// to their first common superclass, and anything merged with an interface
+    /// gives `java/lang/Object`. Values that have nothing in common merge to
+    /// `Top`.
+    pub fn merge(&self,
+                 other: &VerificationType,
+                 hierarchy: &ClassHierarchy)
+                 -> Result<VerificationType> {
+        if self == other {
+            return Ok(self.clone());
+        }
+        match (self, other) {
+            (&VerificationType::Null, &VerificationType::Object(_)) => Ok(other.clone()),
+            (&VerificationType::Object(_), &VerificationType::Null) => Ok(self.clone()),
+            (&VerificationType::Object(ref from), &VerificationType::Object(ref to)) => {
+                Ok(VerificationType::Object(try!(common_super_class(from, to, hierarchy))))
+            }
+            _ => Ok(VerificationType::Top),
+        }
+    }
+}
+
+/// Formats the type as HotSpot does in its verification errors.
+impl fmt::Display for VerificationType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerificationType::Top => write!(f, "top"),
+            VerificationType::Integer => write!(f, "integer"),
+            VerificationType::Float => write!(f, "float"),
+            VerificationType::Long => write!(f, "long"),
+            VerificationType::Double => write!(f, "double"),
+            VerificationType::Null => write!(f, "null"),
+            VerificationType::UninitializedThis => write!(f, "uninitializedThis"),
+            VerificationType::Uninitialized(pc) => write!(f, "uninitialized({})", pc),
+            VerificationType::Object(ref name) => write!(f, "'{}'", name),
+            VerificationType::ReturnAddress(_) => write!(f, "returnAddress"),
+        }
+    }
+}
+
+fn is_class_assignable(from: &str, to: &str, hierarchy: &ClassHierarchy) -> Result<bool> {
+    if to == "java/lang/Object" {
+        return Ok(true);
+    }
+    if to.starts_with('[') {
+        if !from.starts_with('[') {
+            return Ok(false);
+        }
+        let (from, to) = (&from[1..], &to[1..]);
+        return match (reference_component(from), reference_component(to)) {
+            (Some(from), Some(to)) => is_class_assignable(from, to, hierarchy),
+            _ => Ok(from == to),
+        };
+    }
+    if from.starts_with('[') {
+        return Ok(to == "java/lang/Cloneable" || to == "java/io/Serializable");
+    }
+    if try!(hierarchy.is_interface(to)) {
+        return Ok(true);
+    }
+    hierarchy.is_subclass(from, to)
+}
+
+fn common_super_class(class1: &str, class2: &str, hierarchy: &ClassHierarchy) -> Result<String> {
+    let object = "java/lang/Object".to_string();
+    if class1.starts_with('[') && class2.starts_with('[') {
+        return match (reference_component(&class1[1..]), reference_component(&class2[1..])) {
+            (Some(component1), Some(component2)) => {
+                let component = try!(common_super_class(component1, component2, hierarchy));
+                if component.starts_with('[') {
+                    Ok(format!("[{}", component))
+                } else {
+                    Ok(format!("[L{};", component))
+                }
+            }
+            _ => Ok(object),
+        };
+    }
+    if class1.starts_with('[') || class2.starts_with('[') {
+        return Ok(object);
+    }
+    if try!(hierarchy.is_interface(class1)) || try!(hierarchy.is_interface(class2)) {
+        return Ok(object);
+    }
+    let mut supers1 = try!(hierarchy.super_classes(class1));
+    supers1.insert(0, class1.to_string());
+    if supers1.iter().any(|name| name == class2) {
+        return Ok(class2.to_string());
+    }
+    for name in try!(hierarchy.super_classes(class2)) {
+        if supers1.contains(&name) {
+            return Ok(name);
+        }
+    }
+    Ok(object)
+}
+
+/// The class name or array descriptor in an array component descriptor, or
+/// `None` for primitive components.
+fn reference_component(descriptor: &str) -> Option<&str> {
+    if descriptor.starts_with('[') {
+        Some(descriptor)
+    } else if descriptor.starts_with('L') && descriptor.ends_with(';') {
+        Some(&descriptor[1..descriptor.len() - 1])
+    } else {
+        None
+    }
+}
+
+/// A method that failed verification, in the spirit of the JVM's
+/// `VerifyError`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VerifyError {
+    /// The internal name of the class declaring the method.
+    pub class: String,
+    pub method: String,
+    pub descriptor: String,
+    /// The instruction that failed, or `None` if the problem is not with a
+    /// particular instruction.
+    pub pc: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}.{}{}", self.class, self.method, self.descriptor));
+        if let Some(pc) = self.pc {
+            try!(write!(f, " @{}", pc));
+        }
+        write!(f, ": {}", self.message)
+    }
+}
@@ -0,0 +1,180 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::Error;
+use analysis::cfg::{ControlFlowGraph, Edge, EdgeKind};
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn control_flow_class() -> ClassFile {
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn cfg_of(class: &ClassFile, method: &str) -> ControlFlowGraph {
+    let code = class.find_method(method).unwrap().attrs.code().unwrap();
+    ControlFlowGraph::new(code).unwrap()
+}
+
+fn edge(from: usize, to: usize, kind: EdgeKind) -> Edge {
+    Edge {
+        from: from,
+        to: to,
+        kind: kind,
+    }
+}
+
+fn block_starts(cfg: &ControlFlowGraph) -> Vec<usize> {
+    cfg.blocks().iter().map(|block| block.start_pc).collect()
+}
+
+#[test]
+fn should_split_loop_into_blocks() {
+    // Given
+    let class = control_flow_class();
+
+    // When
+    let cfg = cfg_of(&class, "sum");
+
+    // Then
+    //  0: iconst_0, istore_1, iconst_0, istore_2
+    //  4: iload_2, iload_0, if_icmpge 19
+    //  9: iload_1, iload_2, iadd, istore_1, iinc 2 1, goto 4
+    // 19: iload_1, ireturn
+    assert_eq!(vec![0, 4, 9, 19], block_starts(&cfg));
+    assert_eq!(&[edge(0, 1, EdgeKind::Normal),
+                 edge(1, 3, EdgeKind::Conditional),
+                 edge(1, 2, EdgeKind::Normal),
+                 edge(2, 1, EdgeKind::Normal)],
+               cfg.edges());
+    assert_eq!(vec![&edge(0, 1, EdgeKind::Normal), &edge(2, 1, EdgeKind::Normal)],
+               cfg.predecessors(1));
+    assert_eq!(Some(2), cfg.block_at(17));
+    assert!(cfg.unreachable_blocks().is_empty());
+}
+
+#[test]
+fn should_add_an_edge_per_switch_case() {
+    // Given
+    let class = control_flow_class();
+
+    // When
+    let cfg = cfg_of(&class, "describe");
+
+    // Then
+    let kinds: Vec<EdgeKind> = cfg.successors(0).iter().map(|edge| edge.kind).collect();
+    assert_eq!(vec![EdgeKind::Switch(None),
+                    EdgeKind::Switch(Some(1)),
+                    EdgeKind::Switch(Some(2))],
+               kinds);
+    assert_eq!(4, cfg.blocks().len());
+}
+
+#[test]
+fn should_add_exceptional_edges_with_catch_types() {
+    // Given
+    let class = control_flow_class();
+    let cp = &class.constants;
+
+    // When
+    let cfg = cfg_of(&class, "parse");
+
+    // Then
+    assert_eq!(vec![0, 5, 15, 18, 28], block_starts(&cfg));
+    let name_index = cp.find_utf8("java/lang/NumberFormatException").unwrap();
+    let catch_type = cp.iter().position(|constant| *constant == Constant::Class(name_index));
+    let catch_type = catch_type.unwrap() as u16 + 1;
+    assert_eq!(&[edge(0, 1, EdgeKind::Normal),
+                 edge(0, 2, EdgeKind::Exceptional { catch_type: catch_type }),
+                 edge(0, 4, EdgeKind::Exceptional { catch_type: 0 }),
+                 edge(2, 3, EdgeKind::Normal),
+                 edge(2, 4, EdgeKind::Exceptional { catch_type: 0 })],
+               cfg.edges());
+}
+
+const SUBROUTINES: &'static str = r#"
+.version 49 0
+.class public super Subroutines
+.super java/lang/Object
+
+.method public static f ()I {
+    .code stack 1 locals 2 {
+        jsr sub
+        jsr sub
+        goto end
+        iconst_1
+        ireturn
+    end:
+        iconst_0
+        ireturn
+    sub:
+        astore_1
+        ret 1
+    }
+}
+"#;
+
+#[test]
+fn should_connect_subroutine_returns_to_each_call_site() {
+    // Given
+    let class = jasm::assemble(SUBROUTINES).unwrap();
+
+    // When
+    let cfg = cfg_of(&class, "f");
+
+    // Then
+    //  0: jsr 13
+    //  3: jsr 13
+    //  6: goto 11
+    //  9: iconst_1, ireturn
+    // 11: iconst_0, ireturn
+    // 13: astore_1, ret 1
+    assert_eq!(vec![0, 3, 6, 9, 11, 13], block_starts(&cfg));
+    assert_eq!(&[edge(0, 5, EdgeKind::Jsr),
+                 edge(1, 5, EdgeKind::Jsr),
+                 edge(2, 4, EdgeKind::Normal),
+                 edge(5, 1, EdgeKind::Ret),
+                 edge(5, 2, EdgeKind::Ret)],
+               cfg.edges());
+    assert_eq!(vec![3], cfg.unreachable_blocks());
+}
+
+#[test]
+fn should_export_graphviz_dot() {
+    // Given
+    let class = jasm::assemble(SUBROUTINES).unwrap();
+    let cfg = cfg_of(&class, "f");
+    let mut dot: Vec<u8> = vec![];
+
+    // When
+    cfg.write_dot(&mut dot, "f").unwrap();
+
+    // Then
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph \"f\" {\n"));
+    assert!(dot.contains("    b5 [label=\"13: astore_1\\l14: ret\\l\"];\n"));
+    assert!(dot.contains("    b0 -> b5 [style=dotted, label=\"jsr\"];\n"));
+    assert!(dot.contains("    b2 -> b4;\n"));
+    assert!(dot.ends_with("}\n"));
+}
+
+#[test]
+fn should_reject_branches_into_the_middle_of_instructions() {
+    // Given
+    let source = ".class Broken\n.method static m ()V {\n  .code stack 1 locals 0 {\n    goto \
+                  2\n    return\n  }\n}\n";
+    let class = jasm::assemble(source).unwrap();
+    let code = class.find_method("m").unwrap().attrs.code().unwrap();
+
+    // When
+    let result = ControlFlowGraph::new(code);
+
+    // Then
+    match result {
+        Err(Error::InvalidBranchTarget { pc: 0, target: 2 }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,173 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::collections::BTreeSet;
+use std::fs::File;
+
+use analysis::Error;
+use analysis::basic_interpreter::{BasicInterpreter, BasicValue};
+use analysis::dataflow::{Analyzer, Frames};
+use analysis::source_interpreter::{SourceInterpreter, SourceValue};
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn control_flow_class() -> ClassFile {
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn basic_frames(class: &ClassFile, method: &str) -> analysis::Result<Frames<BasicValue>> {
+    let mut interpreter = BasicInterpreter::new();
+    Analyzer::new(&mut interpreter).analyze(class, class.find_method(method).unwrap())
+}
+
+fn source_frames(class: &ClassFile, method: &str) -> Frames<SourceValue> {
+    let mut interpreter = SourceInterpreter::new();
+    Analyzer::new(&mut interpreter).analyze(class, class.find_method(method).unwrap()).unwrap()
+}
+
+fn sources(pcs: &[usize]) -> BTreeSet<usize> {
+    pcs.iter().cloned().collect()
+}
+
+#[test]
+fn should_track_basic_types_through_a_loop() {
+    // Given
+    //  0: lconst_0, lstore_1, lconst_1, lstore_3
+    //  4: iload_0, iinc 0 -1, ifle 24
+    // 11: lload_1, lload_3, ladd, lstore 5, lload_3, lstore_1, lload 5, lstore_3, goto 4
+    // 24: lload_1, lreturn
+    let class = control_flow_class();
+
+    // When
+    let frames = basic_frames(&class, "fib").unwrap();
+
+    // Then
+    use analysis::basic_interpreter::BasicValue::*;
+    let entry = frames.at(0).unwrap();
+    assert_eq!(&[Integer, Top, Top, Top, Top, Top, Top], entry.locals());
+    assert!(entry.stack().is_empty());
+
+    // Local 5 is only set inside the loop, so it is unusable at the header.
+    let header = frames.at(4).unwrap();
+    assert_eq!(&[Integer, Long, Top, Long, Top, Top, Top], header.locals());
+
+    let add = frames.at(13).unwrap();
+    assert_eq!(&[Long, Long], add.stack());
+    assert_eq!(4, add.stack_slots());
+    assert_eq!(Some(&Long), add.peek(0));
+    assert_eq!(&[Long], frames.at(25).unwrap().stack());
+    assert!(frames.at(6).is_none());
+}
+
+#[test]
+fn should_push_the_exception_on_entry_to_handlers() {
+    // Given
+    let class = control_flow_class();
+
+    // When
+    let frames = basic_frames(&class, "parse").unwrap();
+
+    // Then
+    use analysis::basic_interpreter::BasicValue::*;
+    for &pc in &[15, 28] {
+        let handler = frames.at(pc).unwrap();
+        assert_eq!(&[Reference], handler.stack());
+    }
+    // The finally handler is reached both before and after local 1 is set.
+    assert_eq!(&[Reference, Top, Top, Top], frames.at(28).unwrap().locals());
+}
+
+#[test]
+fn should_track_the_sources_of_values() {
+    // Given
+    //  0: iconst_0, istore_1, iconst_0, istore_2
+    //  4: iload_2, iload_0, if_icmpge 19
+    //  9: iload_1, iload_2, iadd, istore_1, iinc 2 1, goto 4
+    // 19: iload_1, ireturn
+    let class = control_flow_class();
+
+    // When
+    let frames = source_frames(&class, "sum");
+
+    // Then
+    let add = frames.at(11).unwrap();
+    assert_eq!(sources(&[9]), add.stack()[0].sources);
+    assert_eq!(sources(&[10]), add.stack()[1].sources);
+
+    // Local 1 comes from the store before the loop or the one inside it,
+    // and local 2 from its initial store or the increment.
+    let header = frames.at(4).unwrap();
+    assert_eq!(sources(&[1, 12]), header.locals()[1].sources);
+    assert_eq!(sources(&[3, 13]), header.locals()[2].sources);
+    assert!(header.locals()[0].sources.is_empty());
+}
+
+#[test]
+fn should_track_the_sources_of_duplicated_wide_values() {
+    // Given
+    let source = ".class Wide\n.method static m ()J {\n  .code stack 4 locals 0 {\n    \
+                  lconst_1\n    dup2\n    ladd\n    lreturn\n  }\n}\n";
+    let class = jasm::assemble(source).unwrap();
+
+    // When
+    let frames = source_frames(&class, "m");
+
+    // Then
+    let add = frames.at(2).unwrap();
+    assert_eq!(2, add.stack().len());
+    assert_eq!(SourceValue::from_pc(2, 0), add.stack()[0]);
+    assert_eq!(SourceValue::from_pc(2, 1), add.stack()[1]);
+}
+
+fn assert_invalid_frame(source: &str, expected_pc: usize, expected_message: &str) {
+    let class = jasm::assemble(source).unwrap();
+    match basic_frames(&class, "m") {
+        Err(Error::InvalidFrame { pc, ref message }) => {
+            assert_eq!(expected_pc, pc);
+            assert_eq!(expected_message, message);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_reject_stack_underflow() {
+    assert_invalid_frame(".class Bad\n.method static m ()V {\n  .code stack 1 locals 0 {\n    \
+                          nop\n    pop\n    return\n  }\n}\n",
+                         1,
+                         "Cannot pop operand off an empty stack");
+}
+
+#[test]
+fn should_reject_stack_overflow() {
+    assert_invalid_frame(".class Bad\n.method static m ()V {\n  .code stack 1 locals 0 {\n    \
+                          lconst_0\n    pop2\n    return\n  }\n}\n",
+                         0,
+                         "Insufficient maximum stack size");
+}
+
+#[test]
+fn should_reject_splitting_wide_values() {
+    assert_invalid_frame(".class Bad\n.method static m ()V {\n  .code stack 2 locals 0 {\n    \
+                          dconst_0\n    pop\n    return\n  }\n}\n",
+                         1,
+                         "Illegal use of pop");
+}
+
+#[test]
+fn should_reject_inconsistent_stack_heights() {
+    assert_invalid_frame(".class Bad\n.method static m (I)V {\n  .code stack 2 locals 1 {\n    \
+                          iload_0\n    ifeq skip\n    iconst_0\n  skip:\n    return\n  }\n}\n",
+                         5,
+                         "Incompatible stack heights");
+}
+
+#[test]
+fn should_reject_falling_off_the_end() {
+    assert_invalid_frame(".class Bad\n.method static m ()V {\n  .code stack 1 locals 0 {\n    \
+                          nop\n  }\n}\n",
+                         0,
+                         "Falling off the end of the code");
+}
@@ -0,0 +1,132 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::cfg::ControlFlowGraph;
+use analysis::dominators::DominatorTree;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn control_flow_class() -> ClassFile {
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn cfg_of(class: &ClassFile, method: &str) -> ControlFlowGraph {
+    let code = class.find_method(method).unwrap().attrs.code().unwrap();
+    ControlFlowGraph::new(code).unwrap()
+}
+
+#[test]
+fn should_compute_dominators_of_a_diamond() {
+    // Given
+    //  0: iload_0, ifge 9
+    //  4: iload_0, ineg, goto 10
+    //  9: iload_0
+    // 10: ireturn
+    let class = control_flow_class();
+    let cfg = cfg_of(&class, "abs");
+
+    // When
+    let tree = DominatorTree::dominators(&cfg);
+
+    // Then
+    assert_eq!(&[0], tree.roots());
+    assert_eq!(None, tree.immediate_dominator(0));
+    assert_eq!(Some(0), tree.immediate_dominator(1));
+    assert_eq!(Some(0), tree.immediate_dominator(2));
+    assert_eq!(Some(0), tree.immediate_dominator(3));
+    assert_eq!(&[1, 2, 3], tree.children(0));
+    assert!(tree.dominates(0, 3));
+    assert!(tree.dominates(3, 3));
+    assert!(!tree.strictly_dominates(3, 3));
+    assert!(!tree.dominates(1, 3));
+    assert_eq!(&[3], tree.frontier(1));
+    assert_eq!(&[3], tree.frontier(2));
+    assert!(tree.frontier(0).is_empty());
+}
+
+#[test]
+fn should_compute_post_dominators_of_a_diamond() {
+    // Given
+    let class = control_flow_class();
+    let cfg = cfg_of(&class, "abs");
+
+    // When
+    let tree = DominatorTree::post_dominators(&cfg);
+
+    // Then
+    assert_eq!(&[3], tree.roots());
+    assert_eq!(Some(3), tree.immediate_dominator(0));
+    assert_eq!(Some(3), tree.immediate_dominator(1));
+    assert_eq!(Some(3), tree.immediate_dominator(2));
+    assert!(tree.dominates(3, 0));
+    assert!(!tree.dominates(1, 0));
+    assert_eq!(&[0], tree.frontier(1));
+    assert_eq!(&[0], tree.frontier(2));
+}
+
+#[test]
+fn should_include_loop_headers_in_their_own_frontier() {
+    // Given
+    //  0: iconst_0, istore_1, iconst_0, istore_2
+    //  4: iload_2, iload_0, if_icmpge 19
+    //  9: iload_1, iload_2, iadd, istore_1, iinc 2 1, goto 4
+    // 19: iload_1, ireturn
+    let class = control_flow_class();
+    let cfg = cfg_of(&class, "sum");
+
+    // When
+    let tree = DominatorTree::dominators(&cfg);
+
+    // Then
+    assert_eq!(vec![3, 1, 0], tree.dominators_of(3));
+    assert_eq!(&[1], tree.frontier(1));
+    assert_eq!(&[1], tree.frontier(2));
+    assert!(tree.frontier(3).is_empty());
+}
+
+#[test]
+fn should_root_post_dominators_at_each_exit() {
+    // Given
+    //  0: try { aload_0, invokestatic parseInt, istore_1 }
+    //  5: finally, iload_1, ireturn
+    // 15: catch NumberFormatException { astore_1 }
+    // 18: finally, iconst_m1, ireturn
+    // 28: finally handler, rethrows
+    let class = control_flow_class();
+    let cfg = cfg_of(&class, "parse");
+
+    // When
+    let tree = DominatorTree::post_dominators(&cfg);
+
+    // Then
+    // Both blocks that may throw can leave through the finally handler or
+    // through a return, so nothing post-dominates them.
+    assert_eq!(&[0, 1, 2, 3, 4], tree.roots());
+    assert_eq!(None, tree.immediate_dominator(0));
+    assert_eq!(None, tree.immediate_dominator(2));
+    assert!(tree.contains(2));
+    assert!(!tree.dominates(1, 0));
+    assert!(!tree.dominates(3, 1));
+}
+
+#[test]
+fn should_leave_unreachable_blocks_out_of_the_tree() {
+    // Given
+    let source = ".class Dead\n.method static m ()I {\n  .code stack 1 locals 0 {\n    \
+                  iconst_0\n    ireturn\n    iconst_1\n    ireturn\n  }\n}\n";
+    let class = jasm::assemble(source).unwrap();
+    let cfg = cfg_of(&class, "m");
+
+    // When
+    let tree = DominatorTree::dominators(&cfg);
+
+    // Then
+    assert!(tree.contains(0));
+    assert!(!tree.contains(1));
+    assert!(!tree.dominates(0, 1));
+    assert!(tree.dominators_of(1).is_empty());
+}
@@ -0,0 +1,244 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::format_checker::{self, Location, Violation};
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn check(source: &str) -> Vec<Violation> {
+    format_checker::check(&jasm::assemble(source).unwrap())
+}
+
+fn violation(location: Location, message: &str) -> Violation {
+    Violation {
+        location: location,
+        message: message.to_string(),
+    }
+}
+
+#[test]
+fn should_accept_compiled_classes() {
+    for name in &["Constants", "ControlFlow", "Features", "Features$Inner", "Features$Marker",
+                  "HelloWorld", "Point"] {
+        // Given
+        let class = read_class(name);
+
+        // When
+        let violations = format_checker::check(&class);
+
+        // Then
+        assert!(violations.is_empty(), "{}: {:?}", name, violations);
+    }
+}
+
+#[test]
+fn should_check_the_kinds_of_constant_pool_references() {
+    // Given
+    let source = r#"
+.const #1 = Utf8 Bad
+.const #2 = Class #1
+.const #3 = Fieldref #1 #2
+.const #4 = String #2
+.const #5 = NameAndType #1 #9
+.class #1
+"#;
+
+    // When
+    let violations = check(source);
+
+    // Then
+    assert_eq!(vec![violation(Location::Constant(3),
+                              "class_index refers to constant #1 (Utf8), not a Class constant"),
+                    violation(Location::Constant(3),
+                              "name_and_type_index refers to constant #2 (Class), not a \
+                               NameAndType constant"),
+                    violation(Location::Constant(4),
+                              "string refers to constant #2 (Class), not a Utf8 constant"),
+                    violation(Location::Constant(5),
+                              "descriptor_index refers to invalid constant #9"),
+                    violation(Location::Class,
+                              "this_class refers to constant #1 (Utf8), not a Class constant")],
+               violations);
+}
+
+#[test]
+fn should_check_member_references() {
+    // Given
+    let source = r#"
+.class Bad
+.method static m ()V {
+    .code stack 1 locals 0 {
+        getstatic Field Bad "a.b" I
+        invokestatic Method Bad "<clinit>" ()V
+        invokeinterface InterfaceMethod Bad "<init>" ()V 1
+        return
+    }
+}
+"#;
+
+    // When
+    let violations = check(source);
+
+    // Then
+    let messages = violations.iter().map(|v| v.message.as_ref()).collect::<Vec<&str>>();
+    assert_eq!(vec!["invalid field name 'a.b'",
+                    "invalid method name '<clinit>'",
+                    "invalid method name '<init>'"],
+               messages);
+}
+
+#[test]
+fn should_reject_illegal_class_modifiers() {
+    // When
+    let final_abstract = check(".class final abstract Bad\n");
+    let interface = check(".class interface Bad\n");
+    let interface_super = check(".class interface abstract Bad\n.super java/lang/Number\n");
+
+    // Then
+    assert_eq!(vec![violation(Location::Class, "illegal class modifiers 0x0410")],
+               final_abstract);
+    assert_eq!(vec![violation(Location::Class, "illegal interface modifiers 0x0200")],
+               interface);
+    assert_eq!(vec![violation(Location::Class,
+                              "interfaces must have java/lang/Object as superclass")],
+               interface_super);
+}
+
+#[test]
+fn should_reject_illegal_member_modifiers() {
+    // Given
+    let source = r#"
+.class interface abstract Bad
+.field public static x I
+.field public static final y I
+.method public abstract m ()V
+.method protected abstract n ()V
+.method public static o ()V {
+    .code stack 0 locals 0 {
+        return
+    }
+}
+"#;
+
+    // When
+    let violations = check(source);
+
+    // Then
+    assert_eq!(vec![violation(Location::Field(0), "illegal field modifiers 0x0009"),
+                    violation(Location::Method(1), "illegal method modifiers 0x0404")],
+               violations);
+}
+
+#[test]
+fn should_check_member_names_and_descriptors() {
+    // Given
+    let source = r#"
+.class Bad
+.field "a;b" I
+.field c Q
+.method "<m>" ()V
+.method abstract n (I
+.method "<init>" ()I {
+    .code stack 1 locals 1 {
+        iconst_0
+        ireturn
+    }
+}
+"#;
+
+    // When
+    let violations = check(source);
+
+    // Then
+    assert_eq!(vec![violation(Location::Field(0), "invalid field name 'a;b'"),
+                    violation(Location::Field(1), "invalid field descriptor 'Q'"),
+                    violation(Location::Method(0), "invalid method name '<m>'"),
+                    violation(Location::Method(0),
+                              "method must have exactly one Code attribute, found 0"),
+                    violation(Location::Method(1), "invalid method descriptor '(I'"),
+                    violation(Location::Method(2), "<init> must return void")],
+               violations);
+}
+
+#[test]
+fn should_reject_duplicate_members() {
+    // Given
+    let source = r#"
+.class abstract Bad
+.field x I
+.field x J
+.field x I
+.method abstract m ()V
+.method abstract m ()V
+"#;
+
+    // When
+    let violations = check(source);
+
+    // Then
+    assert_eq!(vec![violation(Location::Field(2), "duplicate field x I"),
+                    violation(Location::Method(1), "duplicate method m()V")],
+               violations);
+}
+
+#[test]
+fn should_check_code_constraints() {
+    // Given
+    let source = r#"
+.class abstract Bad
+.method static m (J)V {
+    .code stack 1 locals 1 {
+    start:
+        sipush 1
+        return
+        .catch java/lang/Throwable 1 3 start
+        .catch java/lang/Throwable start start 2
+    }
+}
+.method static n ()V {
+    .code stack 0 locals 0 {
+    }
+}
+.method abstract o ()V {
+    .code stack 0 locals 1 {
+        return
+    }
+}
+"#;
+
+    // When
+    let violations = check(source);
+
+    // Then
+    assert_eq!(vec![violation(Location::Method(0), "arguments can't fit into locals"),
+                    violation(Location::Method(0),
+                              "exception table entry 0 has invalid range 1..3"),
+                    violation(Location::Method(0),
+                              "exception table entry 1 has invalid range 0..0"),
+                    violation(Location::Method(0), "exception table entry 1 has invalid handler 2"),
+                    violation(Location::Method(1), "invalid code length 0"),
+                    violation(Location::Method(2),
+                              "abstract and native methods must not have code")],
+               violations);
+}
+
+#[test]
+fn should_format_violations() {
+    // Given
+    let violations = check(".class final abstract Bad\n.field \"\" I\n");
+
+    // When
+    let messages = violations.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+
+    // Then
+    assert_eq!(vec!["class: illegal class modifiers 0x0410", "field 0: invalid field name ''"],
+               messages);
+}
@@ -0,0 +1,115 @@
+extern crate analysis;
+extern crate classfile;
+
+use std::fs::File;
+
+use analysis::ir::*;
+use analysis::ir_verifier::{verify, Violation};
+use analysis::lifter::lift;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn lift_sum() -> Function {
+    let class = read_class("ControlFlow");
+    lift(&class, class.find_method("sum").unwrap()).unwrap()
+}
+
+fn messages(violations: Vec<Violation>) -> Vec<String> {
+    violations.iter().map(|violation| violation.to_string()).collect()
+}
+
+#[test]
+fn should_accept_lifted_functions() {
+    for name in &["ControlFlow", "Features", "HelloWorld", "Point", "Constants"] {
+        // Given
+        let class = read_class(name);
+
+        for method in class.methods.iter() {
+            // When
+            let function = lift(&class, method).unwrap();
+
+            // Then
+            assert_eq!(Vec::<Violation>::new(), verify(&function), "{}", function);
+        }
+    }
+}
+
+#[test]
+fn should_report_uses_not_dominated_by_their_definition() {
+    // Given
+    let mut function = lift_sum();
+    function.blocks[1].terminator = Terminator::Return(Some(Value(5)));
+
+    // When
+    let violations = verify(&function);
+
+    // Then
+    assert_eq!(vec!["b1: v5 does not dominate its use".to_string(),
+                    "b2: block is unreachable".to_string(),
+                    "b3: block is unreachable".to_string()],
+               messages(violations));
+}
+
+#[test]
+fn should_report_phi_nodes_missing_an_operand() {
+    // Given
+    let mut function = lift_sum();
+    function.blocks[1].phis[0].operands.pop();
+
+    // When
+    let violations = verify(&function);
+
+    // Then
+    assert_eq!(vec!["b1: v3 needs one operand per predecessor".to_string()],
+               messages(violations));
+}
+
+#[test]
+fn should_report_operands_of_the_wrong_type() {
+    // Given
+    let mut function = lift_sum();
+    function.blocks[0].instructions[0].op = Op::Literal(Literal::Long(0));
+    function.types[1] = Type::Long;
+
+    // When
+    let violations = verify(&function);
+
+    // Then
+    assert_eq!(vec!["b1: v1 is long, expected int".to_string()],
+               messages(violations));
+}
+
+#[test]
+fn should_report_values_defined_twice() {
+    // Given
+    let mut function = lift_sum();
+    function.blocks[2].instructions[1].result = Some(Value(1));
+
+    // When
+    let violations = verify(&function);
+
+    // Then
+    assert_eq!(vec!["b2: v1 is defined more than once".to_string(),
+                    "b2: v6 is not defined".to_string()],
+               messages(violations));
+}
+
+#[test]
+fn should_require_handlers_to_start_with_the_caught_exception() {
+    // Given
+    let class = read_class("ControlFlow");
+    let mut function = lift(&class, class.find_method("parse").unwrap()).unwrap();
+    function.blocks[3].instructions.remove(0);
+
+    // When
+    let violations = verify(&function);
+
+    // Then
+    assert_eq!(vec!["b3: handler block does not start with caught".to_string()],
+               messages(violations));
+}
@@ -0,0 +1,260 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::Error;
+use analysis::ir::*;
+use analysis::lifter::lift;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn lift_method(class: &ClassFile, name: &str) -> Function {
+    lift(class, class.find_method(name).unwrap()).unwrap()
+}
+
+#[test]
+fn should_place_phi_nodes_at_loop_headers() {
+    // Given
+    let class = read_class("ControlFlow");
+
+    // When
+    let function = lift_method(&class, "sum");
+
+    // Then
+    assert_eq!(r#"static io/hcf/frappe/ControlFlow.sum(I)I(v0: int) {
+b0 (pc 0):
+    v1: int = const 0
+    v2: int = const 0
+    goto b1
+b1 (pc 4):
+    v3: int = phi b0: v1, b2: v5
+    v4: int = phi b0: v2, b2: v7
+    if v4 >= v0 goto b3 else b2
+b2 (pc 9):
+    v5: int = add v3, v4
+    v6: int = const 1
+    v7: int = add v4, v6
+    goto b1
+b3 (pc 19):
+    return v3
+}"#,
+               function.to_string());
+}
+
+#[test]
+fn should_merge_stack_values_at_joins() {
+    // Given
+    let class = read_class("ControlFlow");
+
+    // When
+    let function = lift_method(&class, "abs");
+
+    // Then
+    let join = function.block(3);
+    assert_eq!(vec![Phi {
+                        result: Value(2),
+                        operands: vec![(1, Value(1)), (2, Value(0))],
+                    }],
+               join.phis);
+    assert_eq!(Terminator::Return(Some(Value(2))), join.terminator);
+    assert_eq!(Type::Int, function.type_of(Value(2)));
+}
+
+#[test]
+fn should_resolve_constant_pool_references() {
+    // Given
+    let class = read_class("HelloWorld");
+
+    // When
+    let function = lift_method(&class, "main");
+
+    // Then
+    let ops: Vec<&Op> = function.block(0).instructions.iter().map(|insn| &insn.op).collect();
+    let out = FieldRef {
+        owner: "java/lang/System".to_string(),
+        name: "out".to_string(),
+        descriptor: FieldType::Object("java/io/PrintStream".to_string()),
+    };
+    let println = MethodRef {
+        owner: "java/io/PrintStream".to_string(),
+        name: "println".to_string(),
+        descriptor: MethodDescriptor::parse("(Ljava/lang/String;)V").unwrap(),
+        interface: false,
+    };
+    assert_eq!(vec![&Op::GetStatic(out),
+                    &Op::Literal(Literal::String("Hello World!".to_string())),
+                    &Op::Invoke(InvokeKind::Virtual, println, vec![Value(1), Value(2)])],
+               ops);
+    assert_eq!(None, function.block(0).instructions[2].result);
+}
+
+#[test]
+fn should_pass_locals_along_exception_edges() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Handler
+.method static m ()I {
+    .code stack 1 locals 1 {
+        iconst_0
+        istore_0
+    start:
+        iconst_1
+        istore_0
+        invokestatic Method Handler f ()V
+        iconst_2
+        istore_0
+        invokestatic Method Handler f ()V
+    end:
+        iload_0
+        ireturn
+    handler:
+        pop
+        iload_0
+        ireturn
+        .catch java/lang/RuntimeException start end handler
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let function = lift_method(&class, "m");
+
+    // Then
+    assert_eq!(r#"static Handler.m()I() {
+b0 (pc 0):
+    v0: int = const 0
+    goto b1
+b1 (pc 2):
+    v1: int = const 1
+    invokestatic Handler.f()V
+    v2: int = const 2
+    goto b2
+    catch java/lang/RuntimeException b4
+b2 (pc 8):
+    invokestatic Handler.f()V
+    goto b3
+    catch java/lang/RuntimeException b4
+b3 (pc 12):
+    return v2
+b4 (pc 14):
+    v3: int = phi b1: v1, b2: v2
+    v4: ref = caught
+    return v3
+}"#,
+               function.to_string());
+}
+
+#[test]
+fn should_add_an_entry_block_when_the_first_block_is_a_loop() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Loop
+.method static m (I)I {
+    .code stack 1 locals 1 {
+    loop:
+        iinc 0 -1
+        iload_0
+        ifne loop
+        iload_0
+        ireturn
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let function = lift_method(&class, "m");
+
+    // Then
+    assert_eq!(Terminator::Goto(1), function.block(0).terminator);
+    assert_eq!(vec![Phi {
+                        result: Value(1),
+                        operands: vec![(0, Value(0)), (1, Value(3))],
+                    }],
+               function.block(1).phis);
+}
+
+#[test]
+fn should_track_long_values_through_stack_manipulation() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Wide
+.method static m (JI)J {
+    .code stack 5 locals 3 {
+        iload_2
+        lload_0
+        dup2_x1
+        pop2
+        lshl
+        lload_0
+        ladd
+        lreturn
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let function = lift_method(&class, "m");
+
+    // Then
+    let ops: Vec<&Op> = function.block(0).instructions.iter().map(|insn| &insn.op).collect();
+    assert_eq!(vec![&Op::Binary(BinaryOp::Shl, Value(0), Value(1)),
+                    &Op::Binary(BinaryOp::Add, Value(2), Value(0))],
+               ops);
+    assert_eq!(vec![Type::Long, Type::Int, Type::Long, Type::Long], function.types);
+}
+
+#[test]
+fn should_give_methods_without_code_no_blocks() {
+    // Given
+    let class = jasm::assemble(r#"
+.class abstract Shape
+.method abstract area (D)D
+"#)
+        .unwrap();
+
+    // When
+    let function = lift_method(&class, "area");
+
+    // Then
+    assert!(function.blocks.is_empty());
+    assert_eq!(vec![Value(0), Value(1)], function.params);
+    assert_eq!(vec![Type::Reference, Type::Double], function.types);
+}
+
+#[test]
+fn should_reject_subroutines() {
+    // Given
+    let class = jasm::assemble(r#"
+.version 49 0
+.class Legacy
+.method static m ()V {
+    .code stack 1 locals 1 {
+        jsr sub
+        return
+    sub:
+        astore_0
+        ret 0
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let result = lift(&class, class.find_method("m").unwrap());
+
+    // Then
+    match result {
+        Err(Error::Unsupported { pc, .. }) => assert_eq!(0, pc),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,153 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::collections::BTreeSet;
+use std::fs::File;
+
+use analysis::cfg::ControlFlowGraph;
+use analysis::liveness::Liveness;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn control_flow_class() -> ClassFile {
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn liveness_of(class: &ClassFile, method: &str) -> Liveness {
+    let code = class.find_method(method).unwrap().attrs.code().unwrap();
+    Liveness::new(&ControlFlowGraph::new(code).unwrap())
+}
+
+fn slots(slots: &[u16]) -> BTreeSet<u16> {
+    slots.iter().cloned().collect()
+}
+
+#[test]
+fn should_compute_live_locals_of_a_loop() {
+    // Given
+    let class = control_flow_class();
+
+    // When
+    let liveness = liveness_of(&class, "sum");
+
+    // Then
+    assert_eq!(Some(&slots(&[0])), liveness.live_before(0));
+    assert_eq!(Some(&slots(&[0, 1])), liveness.live_after(1));
+    assert_eq!(Some(&slots(&[0, 1, 2])), liveness.live_before(4));
+    assert_eq!(Some(&slots(&[0, 1, 2])), liveness.live_after(13));
+    assert_eq!(Some(&slots(&[1])), liveness.live_before(19));
+    assert_eq!(Some(&slots(&[])), liveness.live_after(20));
+    assert_eq!(None, liveness.live_before(7));
+    assert!(liveness.dead_stores().is_empty());
+    assert_eq!(3, liveness.max_live());
+}
+
+#[test]
+fn should_track_both_slots_of_long_values() {
+    // Given
+    //  0: lconst_0, lstore_1, lconst_1, lstore_3
+    //  4: iload_0, iinc 0 -1, ifle 24
+    // 11: lload_1, lload_3, ladd, lstore 5, lload_3, lstore_1, lload 5, lstore_3, goto 4
+    // 24: lload_1, lreturn
+    let class = control_flow_class();
+
+    // When
+    let liveness = liveness_of(&class, "fib");
+
+    // Then
+    assert_eq!(Some(&slots(&[0, 1, 2])), liveness.live_after(1));
+    assert_eq!(Some(&slots(&[0, 1, 2, 3, 4])), liveness.live_before(4));
+    assert_eq!(Some(&slots(&[0, 3, 4, 5, 6])), liveness.live_after(14));
+    assert_eq!(Some(&slots(&[0, 1, 2, 5, 6])), liveness.live_after(17));
+    assert_eq!(Some(&slots(&[1, 2])), liveness.live_before(24));
+    assert!(liveness.is_live_before(11, 2));
+    assert!(!liveness.is_live_before(11, 5));
+    assert!(liveness.dead_stores().is_empty());
+}
+
+#[test]
+fn should_find_dead_stores() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Dead
+.method static m (I)I {
+    .code stack 1 locals 2 {
+        iconst_1
+        istore_1
+        iconst_2
+        istore_1
+        iinc 0 1
+        iload_1
+        ireturn
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let liveness = liveness_of(&class, "m");
+
+    // Then
+    assert_eq!(vec![1, 4], liveness.dead_stores());
+}
+
+#[test]
+fn should_keep_locals_read_by_exception_handlers_live() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Handler
+.method static m ()I {
+    .code stack 1 locals 1 {
+        iconst_1
+        istore_0
+    start:
+        invokestatic Method Handler f ()V
+    end:
+        iconst_0
+        ireturn
+    handler:
+        pop
+        iload_0
+        ireturn
+        .catch any start end handler
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let liveness = liveness_of(&class, "m");
+
+    // Then
+    assert!(liveness.is_live_before(2, 0));
+    assert_eq!(Some(&slots(&[])), liveness.live_after(2));
+    assert!(liveness.dead_stores().is_empty());
+}
+
+#[test]
+fn should_handle_wide_loads_and_stores() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Wide
+.method static m (J)J {
+    .code stack 2 locals 302 {
+        lload_0
+        wide lstore 300
+        wide lload 300
+        lreturn
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let liveness = liveness_of(&class, "m");
+
+    // Then
+    assert_eq!(Some(&slots(&[0, 1])), liveness.live_before(0));
+    assert_eq!(Some(&slots(&[300, 301])), liveness.live_after(1));
+    assert_eq!(Some(&slots(&[300, 301])), liveness.live_before(5));
+    assert!(liveness.dead_stores().is_empty());
+}
@@ -0,0 +1,104 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::cfg::{ControlFlowGraph, Edge, EdgeKind};
+use analysis::dominators::DominatorTree;
+use analysis::loops::LoopForest;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn control_flow_class() -> ClassFile {
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn loops_of(class: &ClassFile, method: &str) -> (ControlFlowGraph, LoopForest) {
+    let code = class.find_method(method).unwrap().attrs.code().unwrap();
+    let cfg = ControlFlowGraph::new(code).unwrap();
+    let loops = LoopForest::new(&cfg, &DominatorTree::dominators(&cfg));
+    (cfg, loops)
+}
+
+#[test]
+fn should_find_a_single_loop() {
+    // Given
+    let class = control_flow_class();
+
+    // When
+    let (_, forest) = loops_of(&class, "sum");
+
+    // Then
+    assert_eq!(1, forest.loops().len());
+    let lp = forest.get(0);
+    assert_eq!(1, lp.header);
+    assert_eq!(vec![1, 2], lp.blocks);
+    assert_eq!(vec![2], lp.latches);
+    assert_eq!(1, lp.depth);
+    assert_eq!(0, forest.depth(0));
+    assert_eq!(1, forest.depth(2));
+    assert!(forest.is_reducible());
+}
+
+#[test]
+fn should_nest_inner_loops() {
+    // Given
+    //  0: iconst_0, istore_1, iconst_0, istore_2
+    //  4: iload_2, iload_0, if_icmpge 31
+    //  9: iconst_0, istore_3
+    // 11: iload_3, iload_2, if_icmpge 25
+    // 16: iinc 1 1, iinc 3 1, goto 11
+    // 25: iinc 2 1, goto 4
+    // 31: iload_1, ireturn
+    let class = control_flow_class();
+
+    // When
+    let (cfg, forest) = loops_of(&class, "pairs");
+
+    // Then
+    assert_eq!(2, forest.loops().len());
+    let outer = forest.get(0);
+    let inner = forest.get(1);
+    assert_eq!(1, outer.header);
+    assert_eq!(vec![1, 2, 3, 4, 5], outer.blocks);
+    assert_eq!(vec![5], outer.latches);
+    assert_eq!(vec![1], outer.children);
+    assert_eq!(3, inner.header);
+    assert_eq!(vec![3, 4], inner.blocks);
+    assert_eq!(Some(0), inner.parent);
+    assert_eq!(2, inner.depth);
+    assert_eq!(vec![0], forest.top_level());
+
+    assert_eq!(Some(1), forest.innermost_loop(cfg.block_at(19).unwrap()));
+    assert_eq!(Some(0), forest.innermost_loop(cfg.block_at(25).unwrap()));
+    assert_eq!(None, forest.innermost_loop(cfg.block_at(31).unwrap()));
+    assert_eq!(2, forest.depth(4));
+}
+
+#[test]
+fn should_detect_cycles_with_two_entries() {
+    // Given
+    //  0: iload_0, ifeq 7
+    //  4: iinc 0 1
+    //  7: iload_0, ifne 4
+    // 11: return
+    let source = ".class Irreducible\n.method static m (I)V {\n  .code stack 1 locals 1 {\n    \
+                  iload_0\n    ifeq b\n  a:\n    iinc 0 1\n  b:\n    iload_0\n    ifne a\n    \
+                  return\n  }\n}\n";
+    let class = jasm::assemble(source).unwrap();
+
+    // When
+    let (_, forest) = loops_of(&class, "m");
+
+    // Then
+    assert!(forest.loops().is_empty());
+    assert!(!forest.is_reducible());
+    assert_eq!(&[Edge {
+                     from: 1,
+                     to: 2,
+                     kind: EdgeKind::Normal,
+                 }],
+               forest.irreducible_edges());
+}
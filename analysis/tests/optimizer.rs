@@ -0,0 +1,311 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::hierarchy::ClassSet;
+use analysis::optimizer::{Pass, optimize};
+use analysis::type_checker::TypeChecker;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn library() -> ClassSet {
+    let mut classes = ClassSet::new();
+    classes.declare("java/lang/Object", None, false);
+    classes.declare("java/lang/String", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Number", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Integer", Some("java/lang/Number"), false);
+    classes.declare("java/lang/Throwable", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Exception", Some("java/lang/Throwable"), false);
+    classes.declare("java/lang/RuntimeException", Some("java/lang/Exception"), false);
+    classes.declare("java/lang/IllegalArgumentException",
+                    Some("java/lang/RuntimeException"),
+                    false);
+    classes.declare("java/lang/NumberFormatException",
+                    Some("java/lang/IllegalArgumentException"),
+                    false);
+    classes
+}
+
+fn code_of(class: &ClassFile) -> &CodeAttribute {
+    class.methods[0].attrs.code().unwrap()
+}
+
+/// Wraps the body of a static method `m` taking an int and a long in a
+/// class.
+fn method(body: &str) -> String {
+    format!(".class Optimized\n.method static m (IJ)I {{\n    .code stack 4 locals 5 {{\n{}\n    \
+             }}\n}}\n",
+            body)
+}
+
+/// Optimizes `body` with `passes` and checks that the code and exception
+/// table come out as `expected` assembles.
+fn assert_optimized(passes: &[Pass], body: &str, expected: &str) {
+    let mut class = jasm::assemble(&method(body)).unwrap();
+    let expected = jasm::assemble(&method(expected)).unwrap();
+
+    let changed = optimize(&mut class, passes).unwrap();
+
+    assert!(changed);
+    let (code, expected) = (code_of(&class), code_of(&expected));
+    assert_eq!(expected.instruction_list().unwrap().iter().collect::<Vec<_>>(),
+               code.instruction_list().unwrap().iter().collect::<Vec<_>>());
+    assert_eq!(expected.exception_table, code.exception_table);
+}
+
+#[test]
+fn should_fold_constant_arithmetic() {
+    assert_optimized(&[Pass::FoldConstants],
+                     "iconst_2\niconst_3\nimul\nbipush 10\niadd\ni2b\nireturn",
+                     "bipush 16\nireturn");
+    assert_optimized(&[Pass::FoldConstants],
+                     "iconst_1\nbipush 33\nishl\nineg\nireturn",
+                     "bipush -2\nireturn");
+    assert_optimized(&[Pass::FoldConstants],
+                     "lconst_1\nldc2_w Long 3\nlcmp\nireturn",
+                     "iconst_m1\nireturn");
+}
+
+#[test]
+fn should_add_folded_constants_to_the_pool() {
+    // Given
+    let mut class = jasm::assemble(&method("sipush 1000\nsipush 1000\nimul\nireturn")).unwrap();
+
+    // When
+    optimize(&mut class, &[Pass::FoldConstants]).unwrap();
+
+    // Then
+    let code = code_of(&class);
+    let instructions = code.instruction_list().unwrap();
+    let index = match *instructions.get(0).unwrap() {
+        Bytecode::ldc { index } => index as u16,
+        ref other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(Constant::Integer(1000000), class.constants[index]);
+}
+
+#[test]
+fn should_not_fold_division_by_zero() {
+    // Given
+    let mut class = jasm::assemble(&method("iconst_1\niconst_0\nidiv\nireturn")).unwrap();
+
+    // When
+    let changed = optimize(&mut class, &Pass::all()).unwrap();
+
+    // Then
+    assert!(!changed);
+}
+
+#[test]
+fn should_replace_branches_on_constants() {
+    assert_optimized(&[Pass::ConstantBranches],
+                     r#"
+        iconst_0
+        ifeq yes
+        iconst_1
+        ireturn
+    yes:
+        iconst_2
+        ireturn
+        .stackmaptable {
+            same yes
+        }"#,
+                     "goto yes\nyes:\niconst_2\nireturn");
+    assert_optimized(&[Pass::ConstantBranches],
+                     r#"
+        iconst_3
+        iconst_4
+        if_icmpgt no
+        iconst_1
+        ireturn
+    no:
+        iconst_2
+        ireturn
+        .stackmaptable {
+            same no
+        }"#,
+                     "iconst_1\nireturn");
+}
+
+#[test]
+fn should_thread_jumps() {
+    assert_optimized(&[Pass::ThreadJumps],
+                     r#"
+        iload_0
+        ifeq first
+        iconst_1
+        ireturn
+    first:
+        goto second
+    second:
+        goto third
+    third:
+        iconst_2
+        ireturn
+        .stackmaptable {
+            same first
+            same second
+            same third
+        }"#,
+                     "iload_0\nifeq third\niconst_1\nireturn\nthird:\niconst_2\nireturn");
+    assert_optimized(&[Pass::ThreadJumps],
+                     r#"
+        iload_0
+        ifne skip
+        goto out
+    skip:
+        iconst_1
+        ireturn
+    out:
+        iconst_2
+        ireturn
+        .stackmaptable {
+            same skip
+            same out
+        }"#,
+                     "iload_0\nifeq out\niconst_1\nireturn\nout:\niconst_2\nireturn");
+}
+
+#[test]
+fn should_remove_dead_code_and_remap_tables() {
+    // Given
+    let mut class = jasm::assemble(r#"
+.class Optimized
+.method static m (IJ)I {
+    .code stack 1 locals 5 {
+    start:
+        nop
+        nop
+    body:
+        iload_0
+        ireturn
+    dead:
+        iconst_1
+        ireturn
+    handler:
+        pop
+        iconst_0
+        ireturn
+    end:
+        .catch java/lang/Exception body dead handler
+        .linenumbertable {
+            start 1
+            body 2
+            dead 3
+            handler 4
+        }
+        .localvariabletable {
+            start end 0 x I
+            dead handler 3 y I
+        }
+        .stackmaptable {
+            same dead
+            same_locals_1_stack_item handler object java/lang/Exception
+        }
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let changed = optimize(&mut class, &[Pass::DeadCode]).unwrap();
+
+    // Then
+    assert!(changed);
+    let code = code_of(&class);
+    assert_eq!(vec![0x1a, 0xac, 0x57, 0x03, 0xac], code.code);
+    assert_eq!(vec![ExceptionInfo {
+                        start_pc: 0,
+                        end_pc: 2,
+                        handler_pc: 2,
+                        catch_type: code.exception_table[0].catch_type,
+                    }],
+               code.exception_table);
+    let lines: Vec<(u16, u16)> = code.attrs
+        .line_number_table()
+        .unwrap()
+        .iter()
+        .map(|entry| (entry.start_pc, entry.line_number))
+        .collect();
+    assert_eq!(vec![(0, 2), (2, 4)], lines);
+    let locals: Vec<(u16, u16, u16)> = code.attrs
+        .local_variable_table()
+        .unwrap()
+        .iter()
+        .map(|entry| (entry.start_pc, entry.length, entry.index))
+        .collect();
+    assert_eq!(vec![(0, 5, 0)], locals);
+    match &code.attrs.stack_map_table().unwrap()[..] {
+        [StackMapFrame::SameLocals1StackItemFrame { frame_type: 66, .. }] => {}
+        frames => panic!("unexpected frames {:?}", frames),
+    }
+}
+
+#[test]
+fn should_remove_values_popped_straight_away() {
+    assert_optimized(&[Pass::Peephole],
+                     "iload_0\ndup\npop\nlload_1\npop2\nldc String \"s\"\npop\nswap\nswap\nireturn",
+                     "iload_0\nireturn");
+}
+
+#[test]
+fn should_remove_redundant_loads_and_stores() {
+    assert_optimized(&[Pass::LoadStore],
+                     "iload_0\nistore_0\niload_0\nistore_3\niload_3\nireturn",
+                     "iload_0\nireturn");
+    assert_optimized(&[Pass::LoadStore],
+                     "iload_0\nistore_3\niinc 3 1\nlload_1\nlstore_3\niload_0\nireturn",
+                     "iload_0\npop\nlload_1\npop2\niload_0\nireturn");
+}
+
+#[test]
+fn should_keep_compiled_classes_verifiable() {
+    for name in &["ControlFlow", "Point", "HelloWorld", "Constants"] {
+        // Given
+        let mut class = read_class(name);
+        let mut classes = library();
+
+        // When
+        optimize(&mut class, &Pass::all()).unwrap();
+
+        // Then
+        classes.add(&class);
+        let result = TypeChecker::new(&class, &classes).verify();
+        assert!(result.is_ok(), "{}: {:?}", name, result);
+    }
+}
+
+#[test]
+fn should_reject_code_falling_off_the_end() {
+    // Given
+    let mut class = jasm::assemble(&method(r#"
+        iload_0
+        ifeq out
+        iconst_1
+        ireturn
+    out:
+        iconst_0
+        pop
+        .stackmaptable {
+            same out
+        }"#))
+        .unwrap();
+
+    // When
+    let result = optimize(&mut class, &[Pass::Peephole, Pass::ThreadJumps]);
+
+    // Then
+    match result {
+        Err(analysis::Error::InvalidFrame { pc, ref message }) => {
+            assert_eq!((7, "Falling off the end of the code"), (pc, &message[..]))
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
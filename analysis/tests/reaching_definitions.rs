@@ -0,0 +1,162 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::cfg::ControlFlowGraph;
+use analysis::reaching_definitions::{Definition, ReachingDefinitions};
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn control_flow_class() -> ClassFile {
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn definitions_of(class: &ClassFile, name: &str) -> ReachingDefinitions {
+    let method = class.find_method(name).unwrap();
+    let cfg = ControlFlowGraph::new(method.attrs.code().unwrap()).unwrap();
+    let descriptor = method.descriptor(&class.constants).unwrap();
+    ReachingDefinitions::new(&cfg, &descriptor, method.access_flags.is_static())
+}
+
+fn definition(pc: Option<usize>, index: u16, slots: u8) -> Definition {
+    Definition {
+        pc: pc,
+        index: index,
+        slots: slots,
+    }
+}
+
+#[test]
+fn should_build_def_use_chains() {
+    // Given
+    //  0: iconst_0, istore_1, iconst_0, istore_2
+    //  4: iload_2, iload_0, if_icmpge 19
+    //  9: iload_1, iload_2, iadd, istore_1, iinc 2 1, goto 4
+    // 19: iload_1, ireturn
+    let class = control_flow_class();
+
+    // When
+    let definitions = definitions_of(&class, "sum");
+
+    // Then
+    assert_eq!(&[definition(None, 0, 1),
+                 definition(Some(1), 1, 1),
+                 definition(Some(3), 2, 1),
+                 definition(Some(12), 1, 1),
+                 definition(Some(13), 2, 1)],
+               definitions.definitions());
+    assert_eq!(Some(3), definitions.definition_at(12));
+    assert_eq!(None, definitions.definition_at(9));
+    assert_eq!(&[1, 3], definitions.definitions_used_at(9));
+    assert_eq!(&[2, 4], definitions.definitions_used_at(13));
+    assert_eq!(&[1, 3], definitions.definitions_used_at(19));
+    assert!(definitions.definitions_used_at(11).is_empty());
+    assert_eq!(&[5], definitions.uses_of(0));
+    assert_eq!(&[9, 19], definitions.uses_of(1));
+    assert_eq!(&[4, 10, 13], definitions.uses_of(4));
+    let reaching = definitions.reaching(4).unwrap().iter().cloned().collect::<Vec<usize>>();
+    assert_eq!(vec![0, 1, 2, 3, 4], reaching);
+}
+
+#[test]
+fn should_kill_both_slots_of_long_definitions() {
+    // Given
+    //  0: lconst_0, lstore_1, lconst_1, lstore_3
+    //  4: iload_0, iinc 0 -1, ifle 24
+    // 11: lload_1, lload_3, ladd, lstore 5, lload_3, lstore_1, lload 5, lstore_3, goto 4
+    // 24: lload_1, lreturn
+    let class = control_flow_class();
+
+    // When
+    let definitions = definitions_of(&class, "fib");
+
+    // Then
+    assert_eq!(definition(Some(14), 5, 2), *definitions.definition(4));
+    assert_eq!(&[0, 3], definitions.definitions_used_at(4));
+    assert_eq!(&[1, 5], definitions.definitions_used_at(11));
+    assert_eq!(&[2, 6], definitions.definitions_used_at(12));
+    assert_eq!(&[4], definitions.definitions_used_at(18));
+    assert_eq!(&[1, 5], definitions.definitions_used_at(24));
+}
+
+#[test]
+fn should_split_reused_slots_into_webs() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Webs
+.method static m (I)I {
+    .code stack 1 locals 2 {
+        iconst_1
+        istore_1
+        iload_1
+        pop
+        aconst_null
+        astore_1
+        aload_1
+        pop
+        iload_0
+        ifeq other
+        iconst_1
+        istore_1
+        goto join
+    other:
+        iconst_2
+        istore_1
+    join:
+        iload_1
+        ireturn
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let definitions = definitions_of(&class, "m");
+
+    // Then
+    let pcs = definitions.webs()
+        .iter()
+        .map(|web| web.iter().map(|&id| definitions.definition(id).pc).collect())
+        .collect::<Vec<Vec<Option<usize>>>>();
+    assert_eq!(vec![vec![None], vec![Some(1)], vec![Some(5)], vec![Some(13), Some(18)]],
+               pcs);
+}
+
+#[test]
+fn should_reach_exception_handlers_from_protected_instructions() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Handler
+.method static m ()I {
+    .code stack 1 locals 1 {
+        iconst_0
+        istore_0
+    start:
+        iconst_1
+        istore_0
+        invokestatic Method Handler f ()V
+        iconst_2
+        istore_0
+    end:
+        iload_0
+        ireturn
+    handler:
+        pop
+        iload_0
+        ireturn
+        .catch any start end handler
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let definitions = definitions_of(&class, "m");
+
+    // Then
+    assert_eq!(&[2], definitions.definitions_used_at(9));
+    assert_eq!(&[0, 1], definitions.definitions_used_at(12));
+}
@@ -0,0 +1,107 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use analysis::hierarchy::ClassSet;
+use analysis::subroutines::inline_subroutines;
+use analysis::type_inferencer::TypeInferencer;
+use classfile::*;
+
+const FINALLY: &'static str = r#"
+.version 49 0
+.class Legacy
+.method static m (I)I {
+    .code stack 2 locals 4 {
+    start:
+        iload_0
+        istore_1
+        jsr finally
+        iload_1
+        ireturn
+    handler:
+        astore_2
+        jsr finally
+        aload_2
+        athrow
+    finally:
+        astore_3
+        iinc 0 1
+        ret 3
+        .catch any start handler handler
+    }
+}
+"#;
+
+fn code_of(class: &mut ClassFile) -> &mut CodeAttribute {
+    for attr in class.methods[0].attrs.iter_mut() {
+        if let AttributeInfo::Code(ref mut code) = *attr {
+            return code;
+        }
+    }
+    panic!("method has no code")
+}
+
+#[test]
+fn should_copy_subroutines_into_each_caller() {
+    // Given
+    let mut class = jasm::assemble(FINALLY).unwrap();
+
+    // When
+    let inlined = inline_subroutines(code_of(&mut class)).unwrap();
+
+    // Then
+    assert!(inlined);
+    let code = code_of(&mut class);
+    let instructions = code.instruction_list().unwrap();
+    let mnemonics: Vec<&str> = instructions.iter()
+        .map(|&(_, ref insn)| insn.info().mnemonic())
+        .collect();
+    assert_eq!(vec!["iload_0", "istore_1", "aconst_null", "goto", "iload_1", "ireturn",
+                    "astore_2", "aconst_null", "goto", "aload_2", "athrow",
+                    "astore_3", "iinc", "goto",
+                    "astore_3", "iinc", "goto"],
+               mnemonics);
+    // Each copy of the subroutine returns to its own caller.
+    let (first, second) = (instructions.index_of(19).unwrap(), instructions.index_of(26).unwrap());
+    assert_eq!(vec![6], instructions.get(first).unwrap().branch_targets(19));
+    assert_eq!(vec![13], instructions.get(second).unwrap().branch_targets(26));
+    assert_eq!(vec![ExceptionInfo {
+                        start_pc: 0,
+                        end_pc: 8,
+                        handler_pc: 8,
+                        catch_type: 0,
+                    }],
+               code.exception_table);
+}
+
+#[test]
+fn should_produce_code_that_verifies() {
+    // Given
+    let mut class = jasm::assemble(FINALLY).unwrap();
+    inline_subroutines(code_of(&mut class)).unwrap();
+    let mut classes = ClassSet::new();
+    classes.declare("java/lang/Object", None, false);
+    classes.declare("java/lang/Throwable", Some("java/lang/Object"), false);
+    classes.add(&class);
+
+    // When
+    let result = TypeInferencer::new(&class, &classes).verify();
+
+    // Then
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn should_leave_code_without_subroutines_alone() {
+    // Given
+    let mut class = jasm::assemble(".class Plain\n.method static m ()V {\n  .code stack 0 \
+                                    locals 0 {\n    return\n  }\n}\n")
+        .unwrap();
+
+    // When
+    let inlined = inline_subroutines(code_of(&mut class)).unwrap();
+
+    // Then
+    assert!(!inlined);
+    assert_eq!(vec![0xb1], code_of(&mut class).code);
+}
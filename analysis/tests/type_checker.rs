@@ -0,0 +1,270 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::Error;
+use analysis::hierarchy::ClassSet;
+use analysis::type_checker::TypeChecker;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+/// The library classes the test classes refer to.
+fn library() -> ClassSet {
+    let mut classes = ClassSet::new();
+    classes.declare("java/lang/Object", None, false);
+    classes.declare("java/lang/String", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Number", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Integer", Some("java/lang/Number"), false);
+    classes.declare("java/lang/Throwable", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Exception", Some("java/lang/Throwable"), false);
+    classes.declare("java/lang/RuntimeException", Some("java/lang/Exception"), false);
+    classes.declare("java/lang/IllegalArgumentException",
+                    Some("java/lang/RuntimeException"),
+                    false);
+    classes.declare("java/lang/NumberFormatException",
+                    Some("java/lang/IllegalArgumentException"),
+                    false);
+    classes
+}
+
+fn verify(class: &ClassFile, classes: &mut ClassSet) -> analysis::Result<()> {
+    classes.add(class);
+    TypeChecker::new(class, classes).verify()
+}
+
+fn assert_verify_error(source: &str, expected_pc: usize, expected_message: &str) {
+    let class = jasm::assemble(source).unwrap();
+    match verify(&class, &mut library()) {
+        Err(Error::Verify(ref error)) => {
+            assert_eq!("m", error.method);
+            assert_eq!(Some(expected_pc), error.pc);
+            assert_eq!(expected_message, error.message);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_verify_compiled_classes() {
+    // Given
+    let mut classes = library();
+
+    // When
+    let control_flow = verify(&read_class("ControlFlow"), &mut classes);
+    let point = verify(&read_class("Point"), &mut classes);
+
+    // Then
+    assert!(control_flow.is_ok(), "{:?}", control_flow);
+    assert!(point.is_ok(), "{:?}", point);
+}
+
+#[test]
+fn should_track_object_initialization() {
+    // Given
+    let source = r#"
+.class Init
+.method static m ()Ljava/lang/Object; {
+    .code stack 2 locals 1 {
+        new Class java/lang/Object
+        astore_0
+        aload_0
+        invokespecial Method java/lang/Object <init> ()V
+        aload_0
+        areturn
+    }
+}
+"#;
+    let class = jasm::assemble(source).unwrap();
+
+    // When
+    let result = verify(&class, &mut library());
+
+    // Then
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn should_report_the_method_and_pc_of_errors() {
+    // Given
+    let class = jasm::assemble(".class Bad\n.method static m ()I {\n  .code stack 1 locals 0 \
+                                {\n    fconst_0\n    ireturn\n  }\n}\n")
+        .unwrap();
+
+    // When
+    let error = verify(&class, &mut library()).unwrap_err();
+
+    // Then
+    assert_eq!("Bad.m()I @1: Bad type on operand stack: Type float (current frame, stack[0]) \
+                is not assignable to integer",
+               error.to_string());
+}
+
+#[test]
+fn should_reject_branches_without_stack_map_frames() {
+    assert_verify_error(".class Bad\n.method static m (I)V {\n  .code stack 1 locals 1 {\n    \
+                         iload_0\n    ifeq skip\n    return\n  skip:\n    return\n  }\n}\n",
+                        1,
+                        "Expecting a stackmap frame at branch target 5");
+}
+
+#[test]
+fn should_reject_inconsistent_stack_map_frames() {
+    assert_verify_error(r#"
+.class Bad
+.method static m (I)V {
+    .code stack 1 locals 2 {
+        iload_0
+        ifeq skip
+        return
+    skip:
+        return
+        .stackmaptable {
+            full skip { int float } { }
+        }
+    }
+}
+"#,
+                        1,
+                        "Inconsistent stackmap frames at branch target 5");
+}
+
+#[test]
+fn should_reject_uses_of_uninitialized_objects() {
+    assert_verify_error(r#"
+.class Bad
+.method static m ()I {
+    .code stack 1 locals 0 {
+        new Class java/lang/Object
+        invokevirtual Method java/lang/Object hashCode ()I
+        ireturn
+    }
+}
+"#,
+                        3,
+                        "Bad type on operand stack: Type uninitialized(0) (current frame, \
+                         stack[0]) is not assignable to 'java/lang/Object'");
+}
+
+#[test]
+fn should_reject_constructors_that_do_not_call_super() {
+    let class = jasm::assemble(".class Bad\n.method <init> ()V {\n  .code stack 0 locals 1 {\n    \
+                                return\n  }\n}\n")
+        .unwrap();
+    match verify(&class, &mut library()) {
+        Err(Error::Verify(ref error)) => {
+            assert_eq!("<init>", error.method);
+            assert_eq!(Some(0), error.pc);
+            assert_eq!("Constructor must call super() or this() before return",
+                       error.message);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_check_protected_access_from_other_packages() {
+    // Given
+    let source = r#"
+.class a/Sub
+.super b/Base
+.method static m (Lb/Base;La/Sub;)I {
+    .code stack 2 locals 2 {
+        aload_1
+        getfield Field b/Base value I
+        aload_0
+        getfield Field b/Base value I
+        iadd
+        ireturn
+    }
+}
+"#;
+    let class = jasm::assemble(source).unwrap();
+    let mut classes = library();
+    classes.declare("b/Base", Some("java/lang/Object"), false);
+    classes.declare_member("b/Base", "value", "I", 0x0004);
+
+    // When
+    let result = verify(&class, &mut classes);
+
+    // Then
+    match result {
+        Err(Error::Verify(ref error)) => {
+            assert_eq!(Some(5), error.pc);
+            assert_eq!("Bad access to protected data in getfield", error.message);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_reject_wrong_return_types() {
+    assert_verify_error(".class Bad\n.method static m ()I {\n  .code stack 2 locals 0 {\n    \
+                         lconst_0\n    lreturn\n  }\n}\n",
+                        1,
+                        "Wrong return type in function");
+}
+
+#[test]
+fn should_check_exception_handler_frames() {
+    assert_verify_error(r#"
+.class Bad
+.method static m ()V {
+    .code stack 1 locals 0 {
+    start:
+        nop
+    end:
+        return
+    handler:
+        pop
+        return
+        .catch java/lang/Exception start end handler
+        .stackmaptable {
+            same_locals_1_stack_item handler object java/lang/String
+        }
+    }
+}
+"#,
+                        0,
+                        "Stack map does not match the one at exception handler 2");
+}
+
+#[test]
+fn should_pop_two_category_1_values_with_pop2() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Pop
+.method static m ()V {
+    .code stack 4 locals 0 {
+        iconst_1
+        iconst_2
+        pop2
+        lconst_0
+        pop2
+        return
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let result = verify(&class, &mut library());
+
+    // Then
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn should_reject_pop2_splitting_a_long() {
+    assert_verify_error(".class Bad\n.method static m ()V {\n  .code stack 3 locals 0 {\n    \
+                         lconst_0\n    iconst_1\n    pop2\n    return\n  }\n}\n",
+                        2,
+                        "Bad type on operand stack: Type top (current frame, stack[1]) is not \
+                         a category 1 value");
+}
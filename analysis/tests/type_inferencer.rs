@@ -0,0 +1,241 @@
+extern crate analysis;
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+
+use analysis::Error;
+use analysis::hierarchy::ClassSet;
+use analysis::type_inferencer::TypeInferencer;
+use analysis::verifier;
+use classfile::*;
+use classfile::reader::ClassReader;
+
+/// A try/finally as compiled before version 50, with the finally block in a
+/// subroutine called from both the normal and the exceptional path.
+const FINALLY: &'static str = r#"
+.version 49 0
+.class Legacy
+.method static m (I)I {
+    .code stack 2 locals 4 {
+    start:
+        iload_0
+        istore_1
+        jsr finally
+        iload_1
+        ireturn
+    handler:
+        astore_2
+        jsr finally
+        aload_2
+        athrow
+    finally:
+        astore_3
+        iinc 0 1
+        ret 3
+        .catch any start handler handler
+    }
+}
+"#;
+
+fn library() -> ClassSet {
+    let mut classes = ClassSet::new();
+    classes.declare("java/lang/Object", None, false);
+    classes.declare("java/lang/String", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Class", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Number", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Integer", Some("java/lang/Number"), false);
+    classes.declare("java/lang/Throwable", Some("java/lang/Object"), false);
+    classes.declare("java/lang/Exception", Some("java/lang/Throwable"), false);
+    classes.declare("java/lang/RuntimeException", Some("java/lang/Exception"), false);
+    classes.declare("java/lang/IllegalArgumentException",
+                    Some("java/lang/RuntimeException"),
+                    false);
+    classes.declare("java/lang/NumberFormatException",
+                    Some("java/lang/IllegalArgumentException"),
+                    false);
+    classes
+}
+
+fn infer(class: &ClassFile) -> analysis::Result<()> {
+    let mut classes = library();
+    classes.add(class);
+    TypeInferencer::new(class, &classes).verify()
+}
+
+fn assert_verify_error(source: &str, expected_pc: usize, expected_message: &str) {
+    let class = jasm::assemble(source).unwrap();
+    match infer(&class) {
+        Err(Error::Verify(ref error)) => {
+            assert_eq!(Some(expected_pc), error.pc);
+            assert_eq!(expected_message, error.message);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_infer_frames_of_compiled_classes() {
+    // Given
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    let class = ClassReader::new(&mut file).read_class().unwrap();
+
+    // When
+    let result = infer(&class);
+
+    // Then
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn should_verify_subroutines() {
+    // Given
+    let class = jasm::assemble(FINALLY).unwrap();
+
+    // When
+    let result = infer(&class);
+
+    // Then
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn should_keep_the_types_of_locals_a_subroutine_does_not_store() {
+    // Given
+    let source = r#"
+.class Poly
+.method static m (Ljava/lang/String;)I {
+    .code stack 1 locals 3 {
+        aload_0
+        astore_1
+        jsr sub
+        aload_1
+        invokevirtual Method java/lang/String length ()I
+        istore_1
+        jsr sub
+        iload_1
+        ireturn
+    sub:
+        astore_2
+        ret 2
+    }
+}
+"#;
+    let class = jasm::assemble(source).unwrap();
+
+    // When
+    let result = infer(&class);
+
+    // Then
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn should_take_locals_a_subroutine_stores_from_its_return() {
+    assert_verify_error(r#"
+.class Poly
+.method static m (Ljava/lang/String;)V {
+    .code stack 1 locals 3 {
+        aload_0
+        astore_1
+        jsr sub
+        aload_1
+        pop
+        return
+    sub:
+        astore_2
+        iconst_0
+        istore_1
+        ret 2
+    }
+}
+"#,
+                        5,
+                        "Bad local variable type: Type integer (current frame, locals[1]) is \
+                         not assignable to reference type");
+}
+
+#[test]
+fn should_reject_ret_without_a_return_address() {
+    assert_verify_error(".class Bad\n.method static m ()V {\n  .code stack 1 locals 1 {\n    \
+                         iconst_0\n    istore_0\n    ret 0\n  }\n}\n",
+                        2,
+                        "Bad local variable type: Type integer (current frame, locals[0]) is \
+                         not a return address");
+}
+
+#[test]
+fn should_merge_references_to_their_common_superclass() {
+    assert_verify_error(r#"
+.class Merge
+.method static m (Z)I {
+    .code stack 1 locals 1 {
+        iload_0
+        ifeq other
+        ldc String "a"
+        goto join
+    other:
+        ldc Class java/lang/Object
+    join:
+        invokevirtual Method java/lang/String length ()I
+        ireturn
+    }
+}
+"#,
+                        11,
+                        "Bad type on operand stack: Type 'java/lang/Object' (current frame, \
+                         stack[0]) is not assignable to 'java/lang/String'");
+}
+
+#[test]
+fn should_reject_inconsistent_stack_heights() {
+    assert_verify_error(".class Bad\n.method static m (I)V {\n  .code stack 1 locals 1 {\n    \
+                         iload_0\n    ifeq skip\n    iconst_0\n  skip:\n    return\n  }\n}\n",
+                        4,
+                        "Inconsistent stack height 0 != 1");
+}
+
+#[test]
+fn should_choose_the_verifier_by_class_file_version() {
+    // Given
+    let legacy = jasm::assemble(FINALLY).unwrap();
+    let modern = jasm::assemble(&FINALLY.replace(".version 49 0", ".version 52 0")).unwrap();
+    let mut classes = library();
+    classes.add(&legacy);
+
+    // When
+    let legacy_result = verifier::verify(&legacy, &classes);
+    let modern_result = verifier::verify(&modern, &classes);
+
+    // Then
+    assert!(legacy_result.is_ok(), "{:?}", legacy_result);
+    match modern_result {
+        Err(Error::Verify(ref error)) => {
+            assert_eq!("Stack map does not match the one at exception handler 7",
+                       error.message)
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_reject_subroutines_storing_beyond_max_locals() {
+    assert_verify_error(".class Bad\n.method static m ()V {\n  .code stack 1 locals 1 {\n    \
+                         jsr sub\n    return\n  sub:\n    astore 26\n    ret 26\n  }\n}\n",
+                        4,
+                        "Illegal local variable number 26");
+}
+
+#[test]
+fn should_pop_two_category_1_values_with_pop2() {
+    // Given
+    let class = jasm::assemble(".class Pop\n.method static m ()V {\n  .code stack 2 locals 0 \
+                                {\n    iconst_1\n    iconst_2\n    pop2\n    return\n  }\n}\n")
+        .unwrap();
+
+    // When
+    let result = infer(&class);
+
+    // Then
+    assert!(result.is_ok(), "{:?}", result);
+}
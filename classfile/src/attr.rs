@@ -1,19 +1,19 @@
 use std::vec::Vec;
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 
-use super::ConstantPool;
+use super::{CodeIndex, ConstantPool, InsnList, InstructionList, Instructions, Result};
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum TargetInfo {
     TypeParameter(u8),
-    Supertype(u8),
+    Supertype(u16),
     TypeParameterBound {
         type_parameter_index: u8,
         bound_index: u8,
     },
     Empty,
     MethodFormalParameter(u8),
-    Throws(u8),
+    Throws(u16),
     Localvar(Vec<LocalvarInfo>),
     Catch(u16),
     Offset(u16),
@@ -76,6 +76,9 @@ pub struct TypePathEntry {
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct TypeAnnotation {
+    /// The raw `target_type` tag. Several tags share a `TargetInfo` layout so the
+    /// tag is retained to distinguish them.
+    pub target_type: u8,
     pub target_info: TargetInfo,
     pub target_path: Vec<TypePathEntry>,
     pub type_index: u16,
@@ -223,6 +226,55 @@ pub struct CodeAttribute {
     pub attrs: Attributes,
 }
 
+impl CodeAttribute {
+    /// Iterates over the instructions of the code with their pcs.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # fn print(code: &classfile::CodeAttribute) -> classfile::Result<()> {
+    /// for instruction in code.instructions() {
+    ///     let (pc, bytecode) = try!(instruction);
+    ///     println!("{}: {:?}", pc, bytecode);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instructions<'a>(&'a self) -> Instructions<'a> {
+        Instructions::new(&self.code)
+    }
+
+    /// Decodes the code into an `InstructionList`.
+    pub fn instruction_list(&self) -> Result<InstructionList> {
+        InstructionList::new(&self.code)
+    }
+
+    /// Decodes the code into an `InsnList` for editing.
+    pub fn insn_list(&self) -> Result<InsnList> {
+        InsnList::new(self)
+    }
+
+    /// Indexes the exception table and debugging attributes of the code for
+    /// lookups by pc or line.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # fn print(code: &classfile::CodeAttribute, cp: &classfile::ConstantPool) {
+    /// let index = code.index(cp);
+    /// for handler in index.handlers_at(0) {
+    ///     println!("{:?} -> {}", handler.catch_class, handler.info.handler_pc);
+    /// }
+    /// println!("line {:?}", index.line_at(0));
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// If the tables refer to constants of the wrong kind; see
+    /// `CodeIndex::new`.
+    pub fn index<'a>(&'a self, cp: &'a ConstantPool) -> CodeIndex<'a> {
+        CodeIndex::new(self, cp)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct BootstrapMethodInfo {
     pub bootstrap_method_ref: u16,
@@ -256,6 +308,12 @@ impl Deref for Attributes {
     }
 }
 
+impl DerefMut for Attributes {
+    fn deref_mut(&mut self) -> &mut Vec<AttributeInfo> {
+        &mut self.attributes
+    }
+}
+
 impl Attributes {
     /// Constructs a new attribute collection.
     ///
@@ -496,6 +554,17 @@ impl Attributes {
         None
     }
 
+    /// Resolves the stack map table attribute and returns the list of frames if
+    /// it is present.
+    pub fn stack_map_table(&self) -> Option<&Vec<StackMapFrame>> {
+        for attr in self.attributes.iter() {
+            if let AttributeInfo::StackMapTable(ref table) = *attr {
+                return Some(table);
+            }
+        }
+        None
+    }
+
     /// Resolves the constant value attribute of a field info structure and returns the index
     /// of the value if present.
     pub fn constant_value(&self) -> Option<u16> {
@@ -533,5 +602,56 @@ pub enum AttributeInfo {
     RuntimeVisibleParameterAnnotations(Vec<Vec<Annotation>>),
     RuntimeInvisibleParameterAnnotations(Vec<Vec<Annotation>>),
     Deprecated,
-    Raw(Box<Vec<u8>>),
+    Raw(RawAttribute),
+}
+
+/// An attribute that is not recognized by the reader. The contents are kept
+/// verbatim so that the attribute can be written back out unchanged.
+#[derive(Debug, Eq, PartialEq)]
+pub struct RawAttribute {
+    /// An index into the constant pool that is of type `Constant::Utf8`.
+    pub name_index: u16,
+    /// The uninterpreted attribute body.
+    pub info: Vec<u8>,
+}
+
+impl AttributeInfo {
+    /// Returns the name of this attribute as it appears in the class file.
+    ///
+    /// `Raw` attributes are identified by their `name_index` instead, so the
+    /// placeholder `"Raw"` is returned for them.
+    pub fn name(&self) -> &str {
+        match *self {
+            AttributeInfo::SourceFile(_) => "SourceFile",
+            AttributeInfo::InnerClasses(_) => "InnerClasses",
+            AttributeInfo::EnclosingMethod(_) => "EnclosingMethod",
+            AttributeInfo::SourceDebugExtension(_) => "SourceDebugExtension",
+            AttributeInfo::BootstrapMethods(_) => "BootstrapMethods",
+            AttributeInfo::ConstantValue(_) => "ConstantValue",
+            AttributeInfo::Code(_) => "Code",
+            AttributeInfo::Exceptions(_) => "Exceptions",
+            AttributeInfo::LineNumberTable(_) => "LineNumberTable",
+            AttributeInfo::LocalVariableTable(_) => "LocalVariableTable",
+            AttributeInfo::LocalVariableTypeTable(_) => "LocalVariableTypeTable",
+            AttributeInfo::StackMapTable(_) => "StackMapTable",
+            AttributeInfo::Synthetic => "Synthetic",
+            AttributeInfo::Signature(_) => "Signature",
+            AttributeInfo::AnnotationDefault(_) => "AnnotationDefault",
+            AttributeInfo::MethodParameters(_) => "MethodParameters",
+            AttributeInfo::RuntimeVisibleAnnotations(_) => "RuntimeVisibleAnnotations",
+            AttributeInfo::RuntimeInvisibleAnnotations(_) => "RuntimeInvisibleAnnotations",
+            AttributeInfo::RuntimeVisibleTypeAnnotations(_) => "RuntimeVisibleTypeAnnotations",
+            AttributeInfo::RuntimeInvisibleTypeAnnotations(_) => {
+                "RuntimeInvisibleTypeAnnotations"
+            }
+            AttributeInfo::RuntimeVisibleParameterAnnotations(_) => {
+                "RuntimeVisibleParameterAnnotations"
+            }
+            AttributeInfo::RuntimeInvisibleParameterAnnotations(_) => {
+                "RuntimeInvisibleParameterAnnotations"
+            }
+            AttributeInfo::Deprecated => "Deprecated",
+            AttributeInfo::Raw(_) => "Raw",
+        }
+    }
 }
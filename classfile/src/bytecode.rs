@@ -1,3 +1,5 @@
+use super::{Error, InstructionLength, Result, opcode_info, wide_opcode_info};
+
 /// A single JVM bytecode.
 ///
 /// While most opcodes map 1:1 into this enum, some instructions
@@ -26,7 +28,7 @@
 /// }
 /// ```
 #[allow(non_camel_case_types)]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Bytecode {
     aaload,
     aastore,
@@ -114,10 +116,10 @@ pub enum Bytecode {
         index: u16,
     },
     goto {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     goto_w {
-        branchoffset: u32,
+        branchoffset: i32,
     },
     i2b,
     i2c,
@@ -133,52 +135,52 @@ pub enum Bytecode {
     iconst_i(i8),
     idiv,
     if_acmpeq {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     if_acmpne {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     if_icmpeq {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     if_icmpne {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     if_icmplt {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     if_icmpge {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     if_icmpgt {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     if_icmple {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     ifeq {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     ifne {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     iflt {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     ifge {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     ifgt {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     ifle {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     ifnonnull {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     ifnull {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     iinc {
         index: u8,
@@ -224,10 +226,10 @@ pub enum Bytecode {
     iushr,
     ixor,
     jsr {
-        branchoffset: u16,
+        branchoffset: i16,
     },
     jsr_w {
-        branchoffset: u32,
+        branchoffset: i32,
     },
     l2d,
     l2f,
@@ -350,7 +352,12 @@ pub enum Bytecode {
         constant: u16,
     },
 
-    invalid(u8),
+    /// Reserved for debuggers; never valid in a class file.
+    breakpoint,
+    /// Reserved for implementation-specific use; never valid in a class file.
+    impdep1,
+    /// Reserved for implementation-specific use; never valid in a class file.
+    impdep2,
 }
 
 macro_rules! fetch {
@@ -381,6 +388,28 @@ macro_rules! fetch {
     }};
 }
 
+/// Appends a big-endian operand to a code array.
+macro_rules! put {
+    (u32 $code:expr, $value:expr) => {{
+        let value: u32 = $value;
+        $code.push((value >> 24) as u8);
+        $code.push((value >> 16) as u8);
+        $code.push((value >> 8) as u8);
+        $code.push(value as u8);
+    }};
+    (i32 $code:expr, $value:expr) => {{
+        put!(u32 $code, $value as u32)
+    }};
+    (u16 $code:expr, $value:expr) => {{
+        let value: u16 = $value;
+        $code.push((value >> 8) as u8);
+        $code.push(value as u8);
+    }};
+    (i16 $code:expr, $value:expr) => {{
+        put!(u16 $code, $value as u16)
+    }};
+}
+
 /// Computes the number of pad bytes to align a given index to an alignment.
 macro_rules! pad_align {
     ($value:expr, 4) => {
@@ -431,6 +460,13 @@ macro_rules! bytecode {
             newpc: $pc,
         }
     }};
+    ($code:expr, $pc:expr, $name:ident, $field:ident : i32) => {{
+        let value = fetch!(i32 $code, $pc);
+        DecodeResult {
+            bytecode: Bytecode::$name { $field: value },
+            newpc: $pc,
+        }
+    }};
     ($code:expr, $pc:expr, $name:ident, $field:ident : u32) => {{
         let value = fetch!(u32 $code, $pc);
         DecodeResult {
@@ -440,6 +476,99 @@ macro_rules! bytecode {
     }};
 }
 
+fn i32_at(code: &[u8], i: usize) -> i32 {
+    ((code[i] as u32) << 24 | (code[i + 1] as u32) << 16 | (code[i + 2] as u32) << 8 |
+     code[i + 3] as u32) as i32
+}
+
+/// Returns the length in bytes of the instruction at `pc`, checking that it
+/// fits within `code`.
+fn instruction_length(code: &[u8], pc: usize) -> Result<usize> {
+    let opcode = match code.get(pc) {
+        Some(&opcode) => opcode,
+        None => return Err(Error::PcOutOfBounds(pc)),
+    };
+    let remaining = code.len() - pc;
+    let truncated = Error::TruncatedBytecode {
+        pc: pc,
+        opcode: opcode,
+    };
+    let info = match opcode_info(opcode) {
+        Some(info) => info,
+        None => {
+            return Err(Error::InvalidOpcode {
+                pc: pc,
+                opcode: opcode,
+            })
+        }
+    };
+    let length = match info.length() {
+        InstructionLength::Fixed(length) => length as usize,
+        rule @ InstructionLength::TableSwitch |
+        rule @ InstructionLength::LookupSwitch => {
+            let is_table = rule == InstructionLength::TableSwitch;
+            // The operands are 4-byte aligned relative to the start of the
+            // code array, so the padding depends on pc.
+            let header = 1 + pad_align!(pc + 1, 4) + if is_table { 12 } else { 8 };
+            if remaining < header {
+                return Err(truncated);
+            }
+            // Skip the opcode, padding and default offset.
+            let operand = pc + 1 + pad_align!(pc + 1, 4) + 4;
+            let entries = if is_table {
+                let low = i32_at(code, operand);
+                let high = i32_at(code, operand + 4);
+                if high < low {
+                    return Err(Error::InvalidSwitch {
+                        pc: pc,
+                        opcode: opcode,
+                    });
+                }
+                (high as i64 - low as i64 + 1) as u64 * 4
+            } else {
+                let npairs = i32_at(code, operand);
+                if npairs < 0 {
+                    return Err(Error::InvalidSwitch {
+                        pc: pc,
+                        opcode: opcode,
+                    });
+                }
+                npairs as u64 * 8
+            };
+            // Compare before converting so that a hostile size can never
+            // overflow or cause a large allocation while decoding.
+            if entries > (remaining - header) as u64 {
+                return Err(truncated);
+            }
+            header + entries as usize
+        }
+        InstructionLength::Wide => {
+            let modified = match code.get(pc + 1) {
+                Some(&modified) => modified,
+                None => return Err(truncated),
+            };
+            match wide_opcode_info(modified).map(|info| info.length()) {
+                Some(InstructionLength::Fixed(length)) => length as usize,
+                _ => {
+                    return Err(Error::InvalidWideOpcode {
+                        pc: pc,
+                        opcode: modified,
+                    })
+                }
+            }
+        }
+    };
+    if remaining < length {
+        return Err(truncated);
+    }
+    Ok(length)
+}
+
+/// Adds a signed branch offset to the pc of a branch instruction.
+fn offset_pc(pc: usize, offset: i32) -> usize {
+    pc.wrapping_add(offset as isize as usize)
+}
+
 /// The result of a bytecode decode operation.
 #[derive(Debug)]
 pub struct DecodeResult {
@@ -455,7 +584,9 @@ impl Bytecode {
     /// Decodes a single instruction from the code slice.
     ///
     /// Returns the decoded bytecode and the new pc that is the index of
-    /// the start of the next instruction in the stream.
+    /// the start of the next instruction in the stream. Truncated
+    /// instructions, undefined opcodes and switches with negative sizes are
+    /// reported as errors naming the pc and opcode of the instruction.
     ///
     /// # Examples
     /// ```rust
@@ -464,15 +595,19 @@ impl Bytecode {
     /// let code: Vec<u8> = vec![];
     /// let mut pc = 0;
     /// while pc < code.len() {
-    ///     let decode_result = Bytecode::decode(&code, pc);
+    ///     let decode_result = Bytecode::decode(&code, pc).unwrap();
     ///     println!("Decoded {:?}", decode_result.bytecode);
     ///     pc = decode_result.newpc;
     /// }
     /// ```
-    pub fn decode(code: &[u8], mut pc: usize) -> DecodeResult {
+    pub fn decode(code: &[u8], mut pc: usize) -> Result<DecodeResult> {
+        // Validating the length up front means the operand fetches below
+        // cannot index past the end of the code.
+        try!(instruction_length(code, pc));
+        let start = pc;
         let opcode = code[pc];
         pc = pc + 1;
-        match opcode {
+        let result = match opcode {
             0x00 => bytecode!(nop, pc),
             0x01 => bytecode!(aconst_null, pc),
             i @ 0x02...0x08 => bytecode!(iconst_i, i as i8 - 0x03, pc),
@@ -596,22 +731,22 @@ impl Bytecode {
             0x96 => bytecode!(fcmpg, pc),
             0x97 => bytecode!(dcmpl, pc),
             0x98 => bytecode!(dcmpg, pc),
-            0x99 => bytecode!(code, pc, ifeq, branchoffset: u16),
-            0x9a => bytecode!(code, pc, ifne, branchoffset: u16),
-            0x9b => bytecode!(code, pc, iflt, branchoffset: u16),
-            0x9c => bytecode!(code, pc, ifge, branchoffset: u16),
-            0x9d => bytecode!(code, pc, ifgt, branchoffset: u16),
-            0x9e => bytecode!(code, pc, ifle, branchoffset: u16),
-            0x9f => bytecode!(code, pc, if_icmpeq, branchoffset: u16),
-            0xa0 => bytecode!(code, pc, if_icmpne, branchoffset: u16),
-            0xa1 => bytecode!(code, pc, if_icmplt, branchoffset: u16),
-            0xa2 => bytecode!(code, pc, if_icmpge, branchoffset: u16),
-            0xa3 => bytecode!(code, pc, if_icmpgt, branchoffset: u16),
-            0xa4 => bytecode!(code, pc, if_icmple, branchoffset: u16),
-            0xa5 => bytecode!(code, pc, if_acmpeq, branchoffset: u16),
-            0xa6 => bytecode!(code, pc, if_acmpne, branchoffset: u16),
-            0xa7 => bytecode!(code, pc, goto, branchoffset: u16),
-            0xa8 => bytecode!(code, pc, jsr, branchoffset: u16),
+            0x99 => bytecode!(code, pc, ifeq, branchoffset: i16),
+            0x9a => bytecode!(code, pc, ifne, branchoffset: i16),
+            0x9b => bytecode!(code, pc, iflt, branchoffset: i16),
+            0x9c => bytecode!(code, pc, ifge, branchoffset: i16),
+            0x9d => bytecode!(code, pc, ifgt, branchoffset: i16),
+            0x9e => bytecode!(code, pc, ifle, branchoffset: i16),
+            0x9f => bytecode!(code, pc, if_icmpeq, branchoffset: i16),
+            0xa0 => bytecode!(code, pc, if_icmpne, branchoffset: i16),
+            0xa1 => bytecode!(code, pc, if_icmplt, branchoffset: i16),
+            0xa2 => bytecode!(code, pc, if_icmpge, branchoffset: i16),
+            0xa3 => bytecode!(code, pc, if_icmpgt, branchoffset: i16),
+            0xa4 => bytecode!(code, pc, if_icmple, branchoffset: i16),
+            0xa5 => bytecode!(code, pc, if_acmpeq, branchoffset: i16),
+            0xa6 => bytecode!(code, pc, if_acmpne, branchoffset: i16),
+            0xa7 => bytecode!(code, pc, goto, branchoffset: i16),
+            0xa8 => bytecode!(code, pc, jsr, branchoffset: i16),
             0xa9 => bytecode!(code, pc, ret, index: u8),
             0xaa => {
                 pc += pad_align!(pc, 4);
@@ -721,7 +856,12 @@ impl Bytecode {
                         }
                     }
                     0xa9 => bytecode!(code, pc, wide_ret, index: u16),
-                    op @ _ => bytecode!(invalid, op, pc),
+                    op => {
+                        return Err(Error::InvalidWideOpcode {
+                            pc: start,
+                            opcode: op,
+                        })
+                    }
                 }
             }
             0xc5 => {
@@ -735,22 +875,807 @@ impl Bytecode {
                     newpc: pc,
                 }
             }
-            0xc6 => bytecode!(code, pc, ifnull, branchoffset: u16),
-            0xc7 => bytecode!(code, pc, ifnonnull, branchoffset: u16),
-            0xc8 => bytecode!(code, pc, goto_w, branchoffset: u32),
-            0xc9 => bytecode!(code, pc, jsr_w, branchoffset: u32),
-            op @ _ => bytecode!(invalid, op, pc),
+            0xc6 => bytecode!(code, pc, ifnull, branchoffset: i16),
+            0xc7 => bytecode!(code, pc, ifnonnull, branchoffset: i16),
+            0xc8 => bytecode!(code, pc, goto_w, branchoffset: i32),
+            0xc9 => bytecode!(code, pc, jsr_w, branchoffset: i32),
+            0xca => bytecode!(breakpoint, pc),
+            0xfe => bytecode!(impdep1, pc),
+            0xff => bytecode!(impdep2, pc),
+            op => {
+                return Err(Error::InvalidOpcode {
+                    pc: start,
+                    opcode: op,
+                })
+            }
+        };
+        Ok(result)
+    }
+
+    /// Encodes this instruction and appends it to `code`.
+    ///
+    /// `code` is the code array being built; its current length is taken as
+    /// the pc of this instruction, which determines the padding that precedes
+    /// the operands of `tableswitch` and `lookupswitch`. This is the inverse
+    /// of `Bytecode::decode`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use classfile::Bytecode;
+    ///
+    /// let mut code: Vec<u8> = vec![];
+    /// Bytecode::sipush { short: 300 }.encode(&mut code);
+    /// Bytecode::ireturn.encode(&mut code);
+    /// assert_eq!(vec![0x11, 0x01, 0x2c, 0xac], code);
+    /// ```
+    pub fn encode(&self, code: &mut Vec<u8>) {
+        match *self {
+            Bytecode::nop => code.push(0x00),
+            Bytecode::aconst_null => code.push(0x01),
+            Bytecode::iaload => code.push(0x2e),
+            Bytecode::laload => code.push(0x2f),
+            Bytecode::faload => code.push(0x30),
+            Bytecode::daload => code.push(0x31),
+            Bytecode::aaload => code.push(0x32),
+            Bytecode::baload => code.push(0x33),
+            Bytecode::caload => code.push(0x34),
+            Bytecode::saload => code.push(0x35),
+            Bytecode::iastore => code.push(0x4f),
+            Bytecode::lastore => code.push(0x50),
+            Bytecode::fastore => code.push(0x51),
+            Bytecode::dastore => code.push(0x52),
+            Bytecode::aastore => code.push(0x53),
+            Bytecode::bastore => code.push(0x54),
+            Bytecode::castore => code.push(0x55),
+            Bytecode::sastore => code.push(0x56),
+            Bytecode::pop => code.push(0x57),
+            Bytecode::pop2 => code.push(0x58),
+            Bytecode::dup => code.push(0x59),
+            Bytecode::dup_x1 => code.push(0x5a),
+            Bytecode::dup_x2 => code.push(0x5b),
+            Bytecode::dup2 => code.push(0x5c),
+            Bytecode::dup2_x1 => code.push(0x5d),
+            Bytecode::dup2_x2 => code.push(0x5e),
+            Bytecode::swap => code.push(0x5f),
+            Bytecode::iadd => code.push(0x60),
+            Bytecode::ladd => code.push(0x61),
+            Bytecode::fadd => code.push(0x62),
+            Bytecode::dadd => code.push(0x63),
+            Bytecode::isub => code.push(0x64),
+            Bytecode::lsub => code.push(0x65),
+            Bytecode::fsub => code.push(0x66),
+            Bytecode::dsub => code.push(0x67),
+            Bytecode::imul => code.push(0x68),
+            Bytecode::lmul => code.push(0x69),
+            Bytecode::fmul => code.push(0x6a),
+            Bytecode::dmul => code.push(0x6b),
+            Bytecode::idiv => code.push(0x6c),
+            Bytecode::ldiv => code.push(0x6d),
+            Bytecode::fdiv => code.push(0x6e),
+            Bytecode::ddiv => code.push(0x6f),
+            Bytecode::irem => code.push(0x70),
+            Bytecode::lrem => code.push(0x71),
+            Bytecode::frem => code.push(0x72),
+            Bytecode::drem => code.push(0x73),
+            Bytecode::ineg => code.push(0x74),
+            Bytecode::lneg => code.push(0x75),
+            Bytecode::fneg => code.push(0x76),
+            Bytecode::dneg => code.push(0x77),
+            Bytecode::ishl => code.push(0x78),
+            Bytecode::lshl => code.push(0x79),
+            Bytecode::ishr => code.push(0x7a),
+            Bytecode::lshr => code.push(0x7b),
+            Bytecode::iushr => code.push(0x7c),
+            Bytecode::lushr => code.push(0x7d),
+            Bytecode::iand => code.push(0x7e),
+            Bytecode::land => code.push(0x7f),
+            Bytecode::ior => code.push(0x80),
+            Bytecode::lor => code.push(0x81),
+            Bytecode::ixor => code.push(0x82),
+            Bytecode::lxor => code.push(0x83),
+            Bytecode::i2l => code.push(0x85),
+            Bytecode::i2f => code.push(0x86),
+            Bytecode::i2d => code.push(0x87),
+            Bytecode::l2i => code.push(0x88),
+            Bytecode::l2f => code.push(0x89),
+            Bytecode::l2d => code.push(0x8a),
+            Bytecode::f2i => code.push(0x8b),
+            Bytecode::f2l => code.push(0x8c),
+            Bytecode::f2d => code.push(0x8d),
+            Bytecode::d2i => code.push(0x8e),
+            Bytecode::d2l => code.push(0x8f),
+            Bytecode::d2f => code.push(0x90),
+            Bytecode::i2b => code.push(0x91),
+            Bytecode::i2c => code.push(0x92),
+            Bytecode::i2s => code.push(0x93),
+            Bytecode::lcmp => code.push(0x94),
+            Bytecode::fcmpl => code.push(0x95),
+            Bytecode::fcmpg => code.push(0x96),
+            Bytecode::dcmpl => code.push(0x97),
+            Bytecode::dcmpg => code.push(0x98),
+            Bytecode::ireturn => code.push(0xac),
+            Bytecode::lreturn => code.push(0xad),
+            Bytecode::freturn => code.push(0xae),
+            Bytecode::dreturn => code.push(0xaf),
+            Bytecode::areturn => code.push(0xb0),
+            Bytecode::Return => code.push(0xb1),
+            Bytecode::arraylength => code.push(0xbe),
+            Bytecode::athrow => code.push(0xbf),
+            Bytecode::monitorenter => code.push(0xc2),
+            Bytecode::monitorexit => code.push(0xc3),
+            Bytecode::iconst_i(i) => code.push((i + 0x03) as u8),
+            Bytecode::lconst_l(l) => code.push(0x09 + l),
+            Bytecode::fconst_f(f) => code.push(0x0b + f),
+            Bytecode::dconst_d(d) => code.push(0x0e + d),
+            Bytecode::iload_n(n) => code.push(0x1a + n),
+            Bytecode::lload_n(n) => code.push(0x1e + n),
+            Bytecode::fload_n(n) => code.push(0x22 + n),
+            Bytecode::dload_n(n) => code.push(0x26 + n),
+            Bytecode::aload_n(n) => code.push(0x2a + n),
+            Bytecode::istore_n(n) => code.push(0x3b + n),
+            Bytecode::lstore_n(n) => code.push(0x3f + n),
+            Bytecode::fstore_n(n) => code.push(0x43 + n),
+            Bytecode::dstore_n(n) => code.push(0x47 + n),
+            Bytecode::astore_n(n) => code.push(0x4b + n),
+            Bytecode::bipush { byte } => {
+                code.push(0x10);
+                code.push(byte);
+            }
+            Bytecode::sipush { short } => {
+                code.push(0x11);
+                put!(i16 code, short);
+            }
+            Bytecode::ldc { index } => {
+                code.push(0x12);
+                code.push(index);
+            }
+            Bytecode::ldc_w { index } => {
+                code.push(0x13);
+                put!(u16 code, index);
+            }
+            Bytecode::ldc2_w { index } => {
+                code.push(0x14);
+                put!(u16 code, index);
+            }
+            Bytecode::iload { index } => {
+                code.push(0x15);
+                code.push(index);
+            }
+            Bytecode::lload { index } => {
+                code.push(0x16);
+                code.push(index);
+            }
+            Bytecode::fload { index } => {
+                code.push(0x17);
+                code.push(index);
+            }
+            Bytecode::dload { byte } => {
+                code.push(0x18);
+                code.push(byte);
+            }
+            Bytecode::aload { index } => {
+                code.push(0x19);
+                code.push(index);
+            }
+            Bytecode::istore { index } => {
+                code.push(0x36);
+                code.push(index);
+            }
+            Bytecode::lstore { index } => {
+                code.push(0x37);
+                code.push(index);
+            }
+            Bytecode::fstore { index } => {
+                code.push(0x38);
+                code.push(index);
+            }
+            Bytecode::dstore { index } => {
+                code.push(0x39);
+                code.push(index);
+            }
+            Bytecode::astore { index } => {
+                code.push(0x3a);
+                code.push(index);
+            }
+            Bytecode::ifeq { branchoffset } => {
+                code.push(0x99);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::ifne { branchoffset } => {
+                code.push(0x9a);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::iflt { branchoffset } => {
+                code.push(0x9b);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::ifge { branchoffset } => {
+                code.push(0x9c);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::ifgt { branchoffset } => {
+                code.push(0x9d);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::ifle { branchoffset } => {
+                code.push(0x9e);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_icmpeq { branchoffset } => {
+                code.push(0x9f);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_icmpne { branchoffset } => {
+                code.push(0xa0);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_icmplt { branchoffset } => {
+                code.push(0xa1);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_icmpge { branchoffset } => {
+                code.push(0xa2);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_icmpgt { branchoffset } => {
+                code.push(0xa3);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_icmple { branchoffset } => {
+                code.push(0xa4);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_acmpeq { branchoffset } => {
+                code.push(0xa5);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::if_acmpne { branchoffset } => {
+                code.push(0xa6);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::goto { branchoffset } => {
+                code.push(0xa7);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::jsr { branchoffset } => {
+                code.push(0xa8);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::ret { index } => {
+                code.push(0xa9);
+                code.push(index);
+            }
+            Bytecode::getstatic { index } => {
+                code.push(0xb2);
+                put!(u16 code, index);
+            }
+            Bytecode::putstatic { index } => {
+                code.push(0xb3);
+                put!(u16 code, index);
+            }
+            Bytecode::getfield { index } => {
+                code.push(0xb4);
+                put!(u16 code, index);
+            }
+            Bytecode::putfield { index } => {
+                code.push(0xb5);
+                put!(u16 code, index);
+            }
+            Bytecode::invokevirtual { index } => {
+                code.push(0xb6);
+                put!(u16 code, index);
+            }
+            Bytecode::invokespecial { index } => {
+                code.push(0xb7);
+                put!(u16 code, index);
+            }
+            Bytecode::invokestatic { index } => {
+                code.push(0xb8);
+                put!(u16 code, index);
+            }
+            Bytecode::new { index } => {
+                code.push(0xbb);
+                put!(u16 code, index);
+            }
+            Bytecode::newarray { atype } => {
+                code.push(0xbc);
+                code.push(atype);
+            }
+            Bytecode::anewarray { index } => {
+                code.push(0xbd);
+                put!(u16 code, index);
+            }
+            Bytecode::checkcast { index } => {
+                code.push(0xc0);
+                put!(u16 code, index);
+            }
+            Bytecode::instanceof { index } => {
+                code.push(0xc1);
+                put!(u16 code, index);
+            }
+            Bytecode::ifnull { branchoffset } => {
+                code.push(0xc6);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::ifnonnull { branchoffset } => {
+                code.push(0xc7);
+                put!(i16 code, branchoffset);
+            }
+            Bytecode::goto_w { branchoffset } => {
+                code.push(0xc8);
+                put!(i32 code, branchoffset);
+            }
+            Bytecode::jsr_w { branchoffset } => {
+                code.push(0xc9);
+                put!(i32 code, branchoffset);
+            }
+            Bytecode::iinc { index, constant } => {
+                code.push(0x84);
+                code.push(index);
+                code.push(constant);
+            }
+            Bytecode::tableswitch { default, low, high, ref offsets } => {
+                code.push(0xaa);
+                let padding = pad_align!(code.len(), 4);
+                for _ in 0..padding {
+                    code.push(0);
+                }
+                put!(i32 code, default);
+                put!(i32 code, low);
+                put!(i32 code, high);
+                for offset in offsets.iter() {
+                    put!(i32 code, *offset);
+                }
+            }
+            Bytecode::lookupswitch { default, npairs, ref pairs } => {
+                code.push(0xab);
+                let padding = pad_align!(code.len(), 4);
+                for _ in 0..padding {
+                    code.push(0);
+                }
+                put!(i32 code, default);
+                put!(i32 code, npairs);
+                for &(key, offset) in pairs.iter() {
+                    put!(i32 code, key);
+                    put!(i32 code, offset);
+                }
+            }
+            Bytecode::invokeinterface { index, count } => {
+                code.push(0xb9);
+                put!(u16 code, index);
+                code.push(count);
+                code.push(0);
+            }
+            Bytecode::invokedynamic { index } => {
+                code.push(0xba);
+                put!(u16 code, index);
+                code.push(0);
+                code.push(0);
+            }
+            Bytecode::multianewarray { index, dimensions } => {
+                code.push(0xc5);
+                put!(u16 code, index);
+                code.push(dimensions);
+            }
+            Bytecode::wide_iload { index } => {
+                code.push(0xc4);
+                code.push(0x15);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_lload { index } => {
+                code.push(0xc4);
+                code.push(0x16);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_fload { index } => {
+                code.push(0xc4);
+                code.push(0x17);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_dload { index } => {
+                code.push(0xc4);
+                code.push(0x18);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_aload { index } => {
+                code.push(0xc4);
+                code.push(0x19);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_istore { index } => {
+                code.push(0xc4);
+                code.push(0x36);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_lstore { index } => {
+                code.push(0xc4);
+                code.push(0x37);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_fstore { index } => {
+                code.push(0xc4);
+                code.push(0x38);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_dstore { index } => {
+                code.push(0xc4);
+                code.push(0x39);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_astore { index } => {
+                code.push(0xc4);
+                code.push(0x3a);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_ret { index } => {
+                code.push(0xc4);
+                code.push(0xa9);
+                put!(u16 code, index);
+            }
+            Bytecode::wide_iinc { index, constant } => {
+                code.push(0xc4);
+                code.push(0x84);
+                put!(u16 code, index);
+                put!(u16 code, constant);
+            }
+            Bytecode::breakpoint => code.push(0xca),
+            Bytecode::impdep1 => code.push(0xfe),
+            Bytecode::impdep2 => code.push(0xff),
+        }
+    }
+
+    /// The opcode byte this instruction is encoded with. This is `0xc4` for
+    /// the `wide_*` variants; see `wide_opcode` for the opcode they modify.
+    pub fn opcode(&self) -> u8 {
+        match *self {
+            Bytecode::nop => 0x00,
+            Bytecode::aconst_null => 0x01,
+            Bytecode::iaload => 0x2e,
+            Bytecode::laload => 0x2f,
+            Bytecode::faload => 0x30,
+            Bytecode::daload => 0x31,
+            Bytecode::aaload => 0x32,
+            Bytecode::baload => 0x33,
+            Bytecode::caload => 0x34,
+            Bytecode::saload => 0x35,
+            Bytecode::iastore => 0x4f,
+            Bytecode::lastore => 0x50,
+            Bytecode::fastore => 0x51,
+            Bytecode::dastore => 0x52,
+            Bytecode::aastore => 0x53,
+            Bytecode::bastore => 0x54,
+            Bytecode::castore => 0x55,
+            Bytecode::sastore => 0x56,
+            Bytecode::pop => 0x57,
+            Bytecode::pop2 => 0x58,
+            Bytecode::dup => 0x59,
+            Bytecode::dup_x1 => 0x5a,
+            Bytecode::dup_x2 => 0x5b,
+            Bytecode::dup2 => 0x5c,
+            Bytecode::dup2_x1 => 0x5d,
+            Bytecode::dup2_x2 => 0x5e,
+            Bytecode::swap => 0x5f,
+            Bytecode::iadd => 0x60,
+            Bytecode::ladd => 0x61,
+            Bytecode::fadd => 0x62,
+            Bytecode::dadd => 0x63,
+            Bytecode::isub => 0x64,
+            Bytecode::lsub => 0x65,
+            Bytecode::fsub => 0x66,
+            Bytecode::dsub => 0x67,
+            Bytecode::imul => 0x68,
+            Bytecode::lmul => 0x69,
+            Bytecode::fmul => 0x6a,
+            Bytecode::dmul => 0x6b,
+            Bytecode::idiv => 0x6c,
+            Bytecode::ldiv => 0x6d,
+            Bytecode::fdiv => 0x6e,
+            Bytecode::ddiv => 0x6f,
+            Bytecode::irem => 0x70,
+            Bytecode::lrem => 0x71,
+            Bytecode::frem => 0x72,
+            Bytecode::drem => 0x73,
+            Bytecode::ineg => 0x74,
+            Bytecode::lneg => 0x75,
+            Bytecode::fneg => 0x76,
+            Bytecode::dneg => 0x77,
+            Bytecode::ishl => 0x78,
+            Bytecode::lshl => 0x79,
+            Bytecode::ishr => 0x7a,
+            Bytecode::lshr => 0x7b,
+            Bytecode::iushr => 0x7c,
+            Bytecode::lushr => 0x7d,
+            Bytecode::iand => 0x7e,
+            Bytecode::land => 0x7f,
+            Bytecode::ior => 0x80,
+            Bytecode::lor => 0x81,
+            Bytecode::ixor => 0x82,
+            Bytecode::lxor => 0x83,
+            Bytecode::i2l => 0x85,
+            Bytecode::i2f => 0x86,
+            Bytecode::i2d => 0x87,
+            Bytecode::l2i => 0x88,
+            Bytecode::l2f => 0x89,
+            Bytecode::l2d => 0x8a,
+            Bytecode::f2i => 0x8b,
+            Bytecode::f2l => 0x8c,
+            Bytecode::f2d => 0x8d,
+            Bytecode::d2i => 0x8e,
+            Bytecode::d2l => 0x8f,
+            Bytecode::d2f => 0x90,
+            Bytecode::i2b => 0x91,
+            Bytecode::i2c => 0x92,
+            Bytecode::i2s => 0x93,
+            Bytecode::lcmp => 0x94,
+            Bytecode::fcmpl => 0x95,
+            Bytecode::fcmpg => 0x96,
+            Bytecode::dcmpl => 0x97,
+            Bytecode::dcmpg => 0x98,
+            Bytecode::ireturn => 0xac,
+            Bytecode::lreturn => 0xad,
+            Bytecode::freturn => 0xae,
+            Bytecode::dreturn => 0xaf,
+            Bytecode::areturn => 0xb0,
+            Bytecode::Return => 0xb1,
+            Bytecode::arraylength => 0xbe,
+            Bytecode::athrow => 0xbf,
+            Bytecode::monitorenter => 0xc2,
+            Bytecode::monitorexit => 0xc3,
+            Bytecode::iconst_i(i) => (i + 0x03) as u8,
+            Bytecode::lconst_l(l) => 0x09 + l,
+            Bytecode::fconst_f(f) => 0x0b + f,
+            Bytecode::dconst_d(d) => 0x0e + d,
+            Bytecode::iload_n(n) => 0x1a + n,
+            Bytecode::lload_n(n) => 0x1e + n,
+            Bytecode::fload_n(n) => 0x22 + n,
+            Bytecode::dload_n(n) => 0x26 + n,
+            Bytecode::aload_n(n) => 0x2a + n,
+            Bytecode::istore_n(n) => 0x3b + n,
+            Bytecode::lstore_n(n) => 0x3f + n,
+            Bytecode::fstore_n(n) => 0x43 + n,
+            Bytecode::dstore_n(n) => 0x47 + n,
+            Bytecode::astore_n(n) => 0x4b + n,
+            Bytecode::bipush { .. } => 0x10,
+            Bytecode::sipush { .. } => 0x11,
+            Bytecode::ldc { .. } => 0x12,
+            Bytecode::ldc_w { .. } => 0x13,
+            Bytecode::ldc2_w { .. } => 0x14,
+            Bytecode::iload { .. } => 0x15,
+            Bytecode::lload { .. } => 0x16,
+            Bytecode::fload { .. } => 0x17,
+            Bytecode::dload { .. } => 0x18,
+            Bytecode::aload { .. } => 0x19,
+            Bytecode::istore { .. } => 0x36,
+            Bytecode::lstore { .. } => 0x37,
+            Bytecode::fstore { .. } => 0x38,
+            Bytecode::dstore { .. } => 0x39,
+            Bytecode::astore { .. } => 0x3a,
+            Bytecode::ifeq { .. } => 0x99,
+            Bytecode::ifne { .. } => 0x9a,
+            Bytecode::iflt { .. } => 0x9b,
+            Bytecode::ifge { .. } => 0x9c,
+            Bytecode::ifgt { .. } => 0x9d,
+            Bytecode::ifle { .. } => 0x9e,
+            Bytecode::if_icmpeq { .. } => 0x9f,
+            Bytecode::if_icmpne { .. } => 0xa0,
+            Bytecode::if_icmplt { .. } => 0xa1,
+            Bytecode::if_icmpge { .. } => 0xa2,
+            Bytecode::if_icmpgt { .. } => 0xa3,
+            Bytecode::if_icmple { .. } => 0xa4,
+            Bytecode::if_acmpeq { .. } => 0xa5,
+            Bytecode::if_acmpne { .. } => 0xa6,
+            Bytecode::goto { .. } => 0xa7,
+            Bytecode::jsr { .. } => 0xa8,
+            Bytecode::ret { .. } => 0xa9,
+            Bytecode::getstatic { .. } => 0xb2,
+            Bytecode::putstatic { .. } => 0xb3,
+            Bytecode::getfield { .. } => 0xb4,
+            Bytecode::putfield { .. } => 0xb5,
+            Bytecode::invokevirtual { .. } => 0xb6,
+            Bytecode::invokespecial { .. } => 0xb7,
+            Bytecode::invokestatic { .. } => 0xb8,
+            Bytecode::new { .. } => 0xbb,
+            Bytecode::newarray { .. } => 0xbc,
+            Bytecode::anewarray { .. } => 0xbd,
+            Bytecode::checkcast { .. } => 0xc0,
+            Bytecode::instanceof { .. } => 0xc1,
+            Bytecode::ifnull { .. } => 0xc6,
+            Bytecode::ifnonnull { .. } => 0xc7,
+            Bytecode::goto_w { .. } => 0xc8,
+            Bytecode::jsr_w { .. } => 0xc9,
+            Bytecode::iinc { .. } => 0x84,
+            Bytecode::tableswitch { .. } => 0xaa,
+            Bytecode::lookupswitch { .. } => 0xab,
+            Bytecode::invokeinterface { .. } => 0xb9,
+            Bytecode::invokedynamic { .. } => 0xba,
+            Bytecode::multianewarray { .. } => 0xc5,
+            Bytecode::wide_iload { .. } => 0xc4,
+            Bytecode::wide_lload { .. } => 0xc4,
+            Bytecode::wide_fload { .. } => 0xc4,
+            Bytecode::wide_dload { .. } => 0xc4,
+            Bytecode::wide_aload { .. } => 0xc4,
+            Bytecode::wide_istore { .. } => 0xc4,
+            Bytecode::wide_lstore { .. } => 0xc4,
+            Bytecode::wide_fstore { .. } => 0xc4,
+            Bytecode::wide_dstore { .. } => 0xc4,
+            Bytecode::wide_astore { .. } => 0xc4,
+            Bytecode::wide_ret { .. } => 0xc4,
+            Bytecode::wide_iinc { .. } => 0xc4,
+            Bytecode::breakpoint => 0xca,
+            Bytecode::impdep1 => 0xfe,
+            Bytecode::impdep2 => 0xff,
+        }
+    }
+
+    /// Returns the absolute pcs this instruction may jump to, given that it
+    /// starts at `pc`.
+    ///
+    /// Conditional branches, `goto`, `goto_w`, `jsr` and `jsr_w` have a single
+    /// target; switches list their default target followed by the target of
+    /// each case in operand order, which may repeat. The next instruction is
+    /// not included for conditional branches; see `falls_through`. All other
+    /// instructions have no targets.
+    ///
+    /// Offsets that would land before the start of the code wrap around to
+    /// pcs past the end of any code array, so they fail the same bounds check
+    /// as targets past the end.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use classfile::Bytecode;
+    ///
+    /// let goto = Bytecode::goto { branchoffset: -6 };
+    /// assert_eq!(vec![4], goto.branch_targets(10));
+    /// ```
+    pub fn branch_targets(&self, pc: usize) -> Vec<usize> {
+        match *self {
+            Bytecode::ifeq { branchoffset } |
+            Bytecode::ifne { branchoffset } |
+            Bytecode::iflt { branchoffset } |
+            Bytecode::ifge { branchoffset } |
+            Bytecode::ifgt { branchoffset } |
+            Bytecode::ifle { branchoffset } |
+            Bytecode::if_icmpeq { branchoffset } |
+            Bytecode::if_icmpne { branchoffset } |
+            Bytecode::if_icmplt { branchoffset } |
+            Bytecode::if_icmpge { branchoffset } |
+            Bytecode::if_icmpgt { branchoffset } |
+            Bytecode::if_icmple { branchoffset } |
+            Bytecode::if_acmpeq { branchoffset } |
+            Bytecode::if_acmpne { branchoffset } |
+            Bytecode::ifnull { branchoffset } |
+            Bytecode::ifnonnull { branchoffset } |
+            Bytecode::goto { branchoffset } |
+            Bytecode::jsr { branchoffset } => vec![offset_pc(pc, branchoffset as i32)],
+            Bytecode::goto_w { branchoffset } |
+            Bytecode::jsr_w { branchoffset } => vec![offset_pc(pc, branchoffset)],
+            Bytecode::tableswitch { default, ref offsets, .. } => {
+                let mut targets = vec![offset_pc(pc, default)];
+                targets.extend(offsets.iter().map(|&offset| offset_pc(pc, offset)));
+                targets
+            }
+            Bytecode::lookupswitch { default, ref pairs, .. } => {
+                let mut targets = vec![offset_pc(pc, default)];
+                targets.extend(pairs.iter().map(|&(_, offset)| offset_pc(pc, offset)));
+                targets
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Returns a copy of this instruction jumping by `offsets` instead, given
+    /// in the order of `branch_targets`.
+    ///
+    /// Returns `None` if an offset does not fit the instruction, which for
+    /// everything but `goto_w`, `jsr_w` and switches means it is outside the
+    /// range of an `i16`, or if the number of offsets is wrong.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use classfile::Bytecode;
+    ///
+    /// let goto = Bytecode::goto { branchoffset: -6 };
+    /// assert_eq!(Some(Bytecode::goto { branchoffset: 9 }), goto.with_branch_offsets(&[9]));
+    /// assert_eq!(None, goto.with_branch_offsets(&[40000]));
+    /// ```
+    pub fn with_branch_offsets(&self, offsets: &[i32]) -> Option<Bytecode> {
+        if offsets.len() != self.branch_targets(0).len() || offsets.is_empty() {
+            return None;
+        }
+        match *self {
+            Bytecode::goto_w { .. } => return Some(Bytecode::goto_w { branchoffset: offsets[0] }),
+            Bytecode::jsr_w { .. } => return Some(Bytecode::jsr_w { branchoffset: offsets[0] }),
+            Bytecode::tableswitch { low, high, .. } => {
+                return Some(Bytecode::tableswitch {
+                    default: offsets[0],
+                    low: low,
+                    high: high,
+                    offsets: offsets[1..].to_vec(),
+                })
+            }
+            Bytecode::lookupswitch { npairs, ref pairs, .. } => {
+                return Some(Bytecode::lookupswitch {
+                    default: offsets[0],
+                    npairs: npairs,
+                    pairs: pairs.iter()
+                        .zip(offsets[1..].iter())
+                        .map(|(&(key, _), &offset)| (key, offset))
+                        .collect(),
+                })
+            }
+            _ => {}
+        }
+        if offsets[0] < i16::min_value() as i32 || offsets[0] > i16::max_value() as i32 {
+            return None;
+        }
+        let branchoffset = offsets[0] as i16;
+        let retargeted = match *self {
+            Bytecode::ifeq { .. } => Bytecode::ifeq { branchoffset: branchoffset },
+            Bytecode::ifne { .. } => Bytecode::ifne { branchoffset: branchoffset },
+            Bytecode::iflt { .. } => Bytecode::iflt { branchoffset: branchoffset },
+            Bytecode::ifge { .. } => Bytecode::ifge { branchoffset: branchoffset },
+            Bytecode::ifgt { .. } => Bytecode::ifgt { branchoffset: branchoffset },
+            Bytecode::ifle { .. } => Bytecode::ifle { branchoffset: branchoffset },
+            Bytecode::if_icmpeq { .. } => Bytecode::if_icmpeq { branchoffset: branchoffset },
+            Bytecode::if_icmpne { .. } => Bytecode::if_icmpne { branchoffset: branchoffset },
+            Bytecode::if_icmplt { .. } => Bytecode::if_icmplt { branchoffset: branchoffset },
+            Bytecode::if_icmpge { .. } => Bytecode::if_icmpge { branchoffset: branchoffset },
+            Bytecode::if_icmpgt { .. } => Bytecode::if_icmpgt { branchoffset: branchoffset },
+            Bytecode::if_icmple { .. } => Bytecode::if_icmple { branchoffset: branchoffset },
+            Bytecode::if_acmpeq { .. } => Bytecode::if_acmpeq { branchoffset: branchoffset },
+            Bytecode::if_acmpne { .. } => Bytecode::if_acmpne { branchoffset: branchoffset },
+            Bytecode::ifnull { .. } => Bytecode::ifnull { branchoffset: branchoffset },
+            Bytecode::ifnonnull { .. } => Bytecode::ifnonnull { branchoffset: branchoffset },
+            Bytecode::goto { .. } => Bytecode::goto { branchoffset: branchoffset },
+            Bytecode::jsr { .. } => Bytecode::jsr { branchoffset: branchoffset },
+            _ => return None,
+        };
+        Some(retargeted)
+    }
+
+    /// Returns `true` if this instruction ends a basic block: it branches,
+    /// switches, returns, throws or returns from a subroutine.
+    pub fn is_terminator(&self) -> bool {
+        !self.falls_through() || !self.branch_targets(0).is_empty()
+    }
+
+    /// Returns `true` if execution may continue with the next instruction
+    /// without jumping.
+    ///
+    /// This is `false` for unconditional transfers of control. That includes
+    /// `jsr` and `jsr_w`, whose following instruction is only reached through
+    /// the subroutine's `ret`.
+    pub fn falls_through(&self) -> bool {
+        match *self {
+            Bytecode::goto { .. } |
+            Bytecode::goto_w { .. } |
+            Bytecode::jsr { .. } |
+            Bytecode::jsr_w { .. } |
+            Bytecode::tableswitch { .. } |
+            Bytecode::lookupswitch { .. } |
+            Bytecode::ret { .. } |
+            Bytecode::wide_ret { .. } |
+            Bytecode::ireturn |
+            Bytecode::lreturn |
+            Bytecode::freturn |
+            Bytecode::dreturn |
+            Bytecode::areturn |
+            Bytecode::Return |
+            Bytecode::athrow => false,
+            _ => true,
         }
     }
+
 }
 
 #[cfg(test)]
 mod tests {
     use super::Bytecode;
+    use error::Error;
 
     macro_rules! test_parameterized_bytecode {
         ($opcode:expr => $bytecode:expr) => {{
-            let result = Bytecode::decode(&[$opcode], 0);
+            let result = Bytecode::decode(&[$opcode], 0).unwrap();
             assert_eq!(1, result.newpc);
             assert_eq!($bytecode, result.bytecode);
         }}
@@ -882,7 +1807,7 @@ mod tests {
         ]);
 
         // When
-        let result = Bytecode::decode(&code, pc);
+        let result = Bytecode::decode(&code, pc).unwrap();
 
         // Then
         let expected = Bytecode::tableswitch {
@@ -912,7 +1837,7 @@ mod tests {
         ]);
 
         // When
-        let result = Bytecode::decode(&code, pc);
+        let result = Bytecode::decode(&code, pc).unwrap();
 
         // Then
         let expected = Bytecode::tableswitch {
@@ -941,7 +1866,7 @@ mod tests {
         ]);
 
         // When
-        let result = Bytecode::decode(&code, pc);
+        let result = Bytecode::decode(&code, pc).unwrap();
 
         // Then
         let expected = Bytecode::lookupswitch {
@@ -970,7 +1895,7 @@ mod tests {
         ]);
 
         // When
-        let result = Bytecode::decode(&code, pc);
+        let result = Bytecode::decode(&code, pc).unwrap();
 
         // Then
         let expected = Bytecode::lookupswitch {
@@ -995,7 +1920,7 @@ mod tests {
         ]);
 
         // When
-        let result = Bytecode::decode(&code, pc);
+        let result = Bytecode::decode(&code, pc).unwrap();
 
         // Then
         let expected = Bytecode::invokeinterface {
@@ -1006,7 +1931,7 @@ mod tests {
         assert_eq!(pc + code.len() - 1, result.newpc);
 
         // When - next bytecode
-        let result = Bytecode::decode(&code, result.newpc);
+        let result = Bytecode::decode(&code, result.newpc).unwrap();
 
         // Then
         let expected = Bytecode::ireturn;
@@ -1026,7 +1951,7 @@ mod tests {
         ]);
 
         // When
-        let result = Bytecode::decode(&code, pc);
+        let result = Bytecode::decode(&code, pc).unwrap();
 
         // Then
         let expected = Bytecode::invokedynamic { index: 4095 };
@@ -1034,7 +1959,7 @@ mod tests {
         assert_eq!(pc + code.len() - 1, result.newpc);
 
         // When - next bytecode
-        let result = Bytecode::decode(&code, result.newpc);
+        let result = Bytecode::decode(&code, result.newpc).unwrap();
 
         // Then
         let expected = Bytecode::athrow;
@@ -1054,7 +1979,7 @@ mod tests {
                 ];
 
                 // When
-                let result = Bytecode::decode(&code, 0);
+                let result = Bytecode::decode(&code, 0).unwrap();
 
                 // Then
                 let expected = Bytecode::$opname { index: 0x1234 };
@@ -1087,11 +2012,281 @@ mod tests {
         ];
 
         // When
-        let result = Bytecode::decode(&code, 0);
+        let result = Bytecode::decode(&code, 0).unwrap();
 
         // Then
         let expected = Bytecode::wide_iinc{ index: 0x1234, constant: 0x3456 };
         assert_eq!(expected, result.bytecode);
         assert_eq!(code.len(), result.newpc);
     }
+
+    #[test]
+    fn test_encode_inverts_decode() {
+        for opcode in 0x00..0x100 {
+            let opcode = opcode as u8;
+            if opcode == 0xaa || opcode == 0xab || opcode == 0xc4 ||
+               (opcode >= 0xcb && opcode <= 0xfd) {
+                continue;
+            }
+            // Given
+            let code = vec![opcode, 0x01, 0x02, 0x00, 0x00];
+            let result = Bytecode::decode(&code, 0).unwrap();
+
+            // When
+            let mut encoded: Vec<u8> = vec![];
+            result.bytecode.encode(&mut encoded);
+
+            // Then
+            assert_eq!(&code[..result.newpc], &encoded[..]);
+        }
+    }
+
+    #[test]
+    fn test_encode_inverts_decode_wide() {
+        for &opcode in [0x15, 0x16, 0x17, 0x18, 0x19, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x84, 0xa9]
+            .iter() {
+            // Given
+            let code = vec![0xc4, opcode, 0x01, 0x02, 0x03, 0x04];
+            let result = Bytecode::decode(&code, 0).unwrap();
+
+            // When
+            let mut encoded: Vec<u8> = vec![];
+            result.bytecode.encode(&mut encoded);
+
+            // Then
+            assert_eq!(&code[..result.newpc], &encoded[..]);
+        }
+    }
+
+    #[test]
+    fn test_encode_switch_pads_to_pc() {
+        // Given
+        let mut code: Vec<u8> = vec![0x00];
+        let bytecode = Bytecode::lookupswitch {
+            default: 10,
+            npairs: 1,
+            pairs: vec![(-1, 20)],
+        };
+
+        // When
+        bytecode.encode(&mut code);
+
+        // Then
+        assert_eq!(vec![
+            0x00,                   // nop
+            0xab,                   // lookupswitch
+            0x00, 0x00,             // 2 pad bytes
+            0x00, 0x00, 0x00, 0x0a, // default    = 10
+            0x00, 0x00, 0x00, 0x01, // npairs     = 1
+            0xff, 0xff, 0xff, 0xff, // pairs[0].0 = -1
+            0x00, 0x00, 0x00, 0x14, // pairs[0].1 = 20
+        ], code);
+        assert_eq!(bytecode, Bytecode::decode(&code, 1).unwrap().bytecode);
+    }
+
+    #[test]
+    fn test_decode_reserved_opcodes() {
+        test_parameterized_bytecode!(0xca => Bytecode::breakpoint);
+        test_parameterized_bytecode!(0xfe => Bytecode::impdep1);
+        test_parameterized_bytecode!(0xff => Bytecode::impdep2);
+    }
+
+    #[test]
+    fn test_decode_undefined_opcode() {
+        // Given
+        let code = vec![0x00, 0xcb];
+
+        // When
+        let result = Bytecode::decode(&code, 1);
+
+        // Then
+        match result {
+            Err(Error::InvalidOpcode { pc: 1, opcode: 0xcb }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid_wide_opcode() {
+        // Given
+        let code = vec![0xc4, 0x00, 0x00, 0x00];
+
+        // When
+        let result = Bytecode::decode(&code, 0);
+
+        // Then
+        match result {
+            Err(Error::InvalidWideOpcode { pc: 0, opcode: 0x00 }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_truncated_operands() {
+        let truncated = [vec![0x11, 0x01], vec![0xb9, 0x00, 0x01, 0x01], vec![0xc4], vec![0xc4, 0x84, 0x00]];
+        for code in truncated.iter() {
+            // When
+            let result = Bytecode::decode(code, 0);
+
+            // Then
+            match result {
+                Err(Error::TruncatedBytecode { pc: 0, opcode }) => assert_eq!(code[0], opcode),
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_pc_out_of_bounds() {
+        match Bytecode::decode(&[0x00], 1) {
+            Err(Error::PcOutOfBounds(1)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_tableswitch_larger_than_code() {
+        // Given
+        let code = vec![
+            0xaa,                   // tableswitch
+            0x00, 0x00, 0x00,       // 3 pad bytes
+            0x00, 0x00, 0x00, 0x00, // default = 0
+            0x80, 0x00, 0x00, 0x00, // low     = i32::MIN
+            0x7f, 0xff, 0xff, 0xff, // high    = i32::MAX
+            0x00, 0x00, 0x00, 0x00, // offsets[0]
+        ];
+
+        // When
+        let result = Bytecode::decode(&code, 0);
+
+        // Then
+        match result {
+            Err(Error::TruncatedBytecode { pc: 0, opcode: 0xaa }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_tableswitch_high_below_low() {
+        // Given
+        let code = vec![
+            0x00,                   // nop
+            0xaa,                   // tableswitch
+            0x00, 0x00,             // 2 pad bytes
+            0x00, 0x00, 0x00, 0x00, // default = 0
+            0x00, 0x00, 0x00, 0x02, // low     = 2
+            0x00, 0x00, 0x00, 0x01, // high    = 1
+        ];
+
+        // When
+        let result = Bytecode::decode(&code, 1);
+
+        // Then
+        match result {
+            Err(Error::InvalidSwitch { pc: 1, opcode: 0xaa }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_lookupswitch_negative_npairs() {
+        // Given
+        let code = vec![
+            0xab,                   // lookupswitch
+            0x00, 0x00, 0x00,       // 3 pad bytes
+            0x00, 0x00, 0x00, 0x00, // default = 0
+            0xff, 0xff, 0xff, 0xff, // npairs  = -1
+        ];
+
+        // When
+        let result = Bytecode::decode(&code, 0);
+
+        // Then
+        match result {
+            Err(Error::InvalidSwitch { pc: 0, opcode: 0xab }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_backward_branch() {
+        // Given
+        let code = vec![
+            0x00,       // nop
+            0xa7,       // goto
+            0xff, 0xff, // -1
+        ];
+
+        // When
+        let result = Bytecode::decode(&code, 1).unwrap();
+
+        // Then
+        assert_eq!(Bytecode::goto { branchoffset: -1 }, result.bytecode);
+        assert_eq!(vec![0], result.bytecode.branch_targets(1));
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        assert_eq!(vec![13], Bytecode::ifeq { branchoffset: 3 }.branch_targets(10));
+        assert_eq!(vec![2], Bytecode::jsr { branchoffset: -8 }.branch_targets(10));
+        assert_eq!(vec![70010], Bytecode::goto_w { branchoffset: 70000 }.branch_targets(10));
+        assert_eq!(vec![0], Bytecode::jsr_w { branchoffset: -10 }.branch_targets(10));
+        assert!(Bytecode::athrow.branch_targets(10).is_empty());
+    }
+
+    #[test]
+    fn test_branch_targets_of_switches() {
+        // Given
+        let tableswitch = Bytecode::tableswitch {
+            default: 30,
+            low: 1,
+            high: 2,
+            offsets: vec![20, -4],
+        };
+        let lookupswitch = Bytecode::lookupswitch {
+            default: -2,
+            npairs: 2,
+            pairs: vec![(-5, 8), (100, 8)],
+        };
+
+        // Then
+        assert_eq!(vec![34, 24, 0], tableswitch.branch_targets(4));
+        assert_eq!(vec![2, 12, 12], lookupswitch.branch_targets(4));
+    }
+
+    #[test]
+    fn test_branch_target_before_code_start_is_out_of_bounds() {
+        let targets = Bytecode::goto { branchoffset: -5 }.branch_targets(2);
+        assert!(targets[0] > u16::max_value() as usize);
+    }
+
+    #[test]
+    fn test_control_flow_queries() {
+        let conditional = Bytecode::if_icmplt { branchoffset: 4 };
+        assert!(conditional.is_terminator());
+        assert!(conditional.falls_through());
+
+        for unconditional in [Bytecode::goto { branchoffset: 4 },
+                              Bytecode::jsr_w { branchoffset: 4 },
+                              Bytecode::ret { index: 1 },
+                              Bytecode::wide_ret { index: 1 },
+                              Bytecode::Return,
+                              Bytecode::areturn,
+                              Bytecode::athrow,
+                              Bytecode::lookupswitch {
+                                  default: 4,
+                                  npairs: 0,
+                                  pairs: vec![],
+                              }]
+            .iter() {
+            assert!(unconditional.is_terminator(), "{:?}", unconditional);
+            assert!(!unconditional.falls_through(), "{:?}", unconditional);
+        }
+
+        for straight_line in [Bytecode::nop, Bytecode::iadd, Bytecode::invokestatic { index: 1 }]
+            .iter() {
+            assert!(!straight_line.is_terminator(), "{:?}", straight_line);
+            assert!(straight_line.falls_through(), "{:?}", straight_line);
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use std::fmt;
 use std::vec::Vec;
 
-use super::{Attributes, ConstantPool, FieldInfo, MethodInfo};
+use super::{Attributes, ClassSignature, ConstantPool, FieldInfo, MethodInfo, Result};
 
 #[derive(Debug)]
 pub struct ClassFile {
@@ -53,6 +53,12 @@ impl ClassFile {
         Some(self.constants[name_index].as_utf8())
     }
 
+    /// Parses the generic signature of this class, if it has a `Signature`
+    /// attribute.
+    pub fn signature(&self) -> Option<Result<ClassSignature>> {
+        self.attrs.signature(&self.constants).map(|signature| ClassSignature::parse(signature))
+    }
+
     pub fn find_method(&self, method_name: &str) -> Option<&MethodInfo> {
         for method in self.methods.iter() {
             let name = self.constants[method.name_index].as_utf8();
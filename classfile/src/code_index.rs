@@ -0,0 +1,199 @@
+//! Lookups of exception handlers, line numbers and local variables by pc.
+use std::collections::{BTreeMap, HashMap};
+
+use super::{AttributeInfo, CodeAttribute, ConstantPool, ExceptionInfo};
+
+/// An exception table entry together with the name of the class it catches.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Handler<'a> {
+    pub info: &'a ExceptionInfo,
+    /// The internal name of the caught class, or `None` if the handler
+    /// catches everything, as for `finally` blocks.
+    pub catch_class: Option<&'a str>,
+}
+
+/// A local variable that is live at some pc, as described by the
+/// `LocalVariableTable` and `LocalVariableTypeTable` attributes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Local<'a> {
+    /// The local variable slot holding the variable.
+    pub index: u16,
+    pub name: &'a str,
+    /// The field descriptor of the variable's type.
+    pub descriptor: &'a str,
+    /// The generic signature of the variable's type, if it has one.
+    pub signature: Option<&'a str>,
+    /// The first pc at which the variable has a value.
+    pub start_pc: u16,
+    /// The number of bytes of code over which the variable has a value.
+    pub length: u16,
+}
+
+/// Per-pc views of the debugging and exception tables of a method, built
+/// once by `CodeAttribute::index` so that each query is a binary search
+/// rather than a scan of the tables.
+#[derive(Debug)]
+pub struct CodeIndex<'a> {
+    code_length: usize,
+    handlers: Intervals<Handler<'a>>,
+    /// The line of each pc that starts a `LineNumberTable` entry, by pc.
+    lines: Vec<(usize, u16)>,
+    /// The pcs that start each line, by line.
+    line_pcs: BTreeMap<u16, Vec<usize>>,
+    locals: Intervals<Local<'a>>,
+}
+
+impl<'a> CodeIndex<'a> {
+    /// Indexes the tables of `code`, resolving names in `cp`.
+    ///
+    /// # Panics
+    /// If a `catch_type` does not refer to a `Constant::Class`, or a local
+    /// variable name, descriptor or signature does not refer to a
+    /// `Constant::Utf8`.
+    pub fn new(code: &'a CodeAttribute, cp: &'a ConstantPool) -> CodeIndex<'a> {
+        let handlers = code.exception_table.iter().map(|info| {
+            let catch_class = if info.catch_type == 0 {
+                None
+            } else {
+                Some(cp[cp[info.catch_type].as_class()].as_utf8().as_ref())
+            };
+            let handler = Handler {
+                info: info,
+                catch_class: catch_class,
+            };
+            (info.start_pc as usize, info.end_pc as usize, handler)
+        });
+
+        // The first entry for a pc wins, as it does in the JVM.
+        let mut lines = code.attrs
+            .iter()
+            .filter_map(|attr| match *attr {
+                AttributeInfo::LineNumberTable(ref table) => Some(table),
+                _ => None,
+            })
+            .flat_map(|table| table.iter())
+            .map(|entry| (entry.start_pc as usize, entry.line_number))
+            .collect::<Vec<(usize, u16)>>();
+        lines.sort_by_key(|&(pc, _)| pc);
+        lines.dedup_by_key(|&mut (pc, _)| pc);
+        let mut line_pcs = BTreeMap::new();
+        for &(pc, line) in lines.iter() {
+            line_pcs.entry(line).or_insert_with(Vec::new).push(pc);
+        }
+
+        let mut signatures = HashMap::new();
+        for attr in code.attrs.iter() {
+            if let AttributeInfo::LocalVariableTypeTable(ref table) = *attr {
+                for entry in table.iter() {
+                    let signature = cp[entry.signature_index].as_utf8().as_ref();
+                    signatures.insert((entry.start_pc, entry.length, entry.index), signature);
+                }
+            }
+        }
+        let mut locals = vec![];
+        for attr in code.attrs.iter() {
+            if let AttributeInfo::LocalVariableTable(ref table) = *attr {
+                for entry in table.iter() {
+                    let local = Local {
+                        index: entry.index,
+                        name: cp[entry.name_index].as_utf8(),
+                        descriptor: cp[entry.descriptor_index].as_utf8(),
+                        signature: signatures.get(&(entry.start_pc, entry.length, entry.index))
+                            .map(|signature| *signature),
+                        start_pc: entry.start_pc,
+                        length: entry.length,
+                    };
+                    let start = entry.start_pc as usize;
+                    locals.push((start, start + entry.length as usize, local));
+                }
+            }
+        }
+        locals.sort_by_key(|&(_, _, local)| local.index);
+
+        CodeIndex {
+            code_length: code.code.len(),
+            handlers: Intervals::new(handlers),
+            lines: lines,
+            line_pcs: line_pcs,
+            locals: Intervals::new(locals),
+        }
+    }
+
+    /// The exception handlers whose range covers `pc`, in the order of the
+    /// exception table, which is the order in which the JVM tries them.
+    pub fn handlers_at(&self, pc: usize) -> &[Handler<'a>] {
+        self.handlers.at(pc)
+    }
+
+    /// The source line of the instruction at `pc`, or `None` if the code has
+    /// no line number for it.
+    pub fn line_at(&self, pc: usize) -> Option<u16> {
+        if pc >= self.code_length {
+            return None;
+        }
+        match self.lines.binary_search_by_key(&pc, |&(start, _)| start) {
+            Ok(i) => Some(self.lines[i].1),
+            Err(0) => None,
+            Err(i) => Some(self.lines[i - 1].1),
+        }
+    }
+
+    /// The pcs at which the code for `line` starts, in ascending order. A
+    /// line may start at several pcs, for example the condition of a loop
+    /// that the compiler moved to its end.
+    pub fn pcs_for_line(&self, line: u16) -> &[usize] {
+        self.line_pcs.get(&line).map_or(&[], |pcs| &pcs[..])
+    }
+
+    /// The local variables that have a value at `pc`, ordered by slot.
+    pub fn locals_at(&self, pc: usize) -> &[Local<'a>] {
+        self.locals.at(pc)
+    }
+}
+
+/// Values attached to half-open pc ranges, split into disjoint segments so
+/// that the values covering a pc can be found by binary search.
+#[derive(Debug)]
+struct Intervals<T> {
+    /// The start of each segment, ascending. Each segment ends where the
+    /// next one starts; the last one holds no values.
+    starts: Vec<usize>,
+    values: Vec<Vec<T>>,
+}
+
+impl<T: Copy> Intervals<T> {
+    /// Builds the segments of `ranges`, keeping the values of each segment
+    /// in the order of `ranges`. Empty ranges are ignored.
+    fn new<I>(ranges: I) -> Intervals<T>
+        where I: IntoIterator<Item = (usize, usize, T)>
+    {
+        let ranges = ranges.into_iter()
+            .filter(|&(start, end, _)| start < end)
+            .collect::<Vec<(usize, usize, T)>>();
+        let mut starts = ranges.iter()
+            .flat_map(|&(start, end, _)| vec![start, end])
+            .collect::<Vec<usize>>();
+        starts.sort();
+        starts.dedup();
+        let values = starts.iter()
+            .map(|&pc| {
+                ranges.iter()
+                    .filter(|&&(start, end, _)| start <= pc && pc < end)
+                    .map(|&(_, _, value)| value)
+                    .collect()
+            })
+            .collect();
+        Intervals {
+            starts: starts,
+            values: values,
+        }
+    }
+
+    fn at(&self, pc: usize) -> &[T] {
+        match self.starts.binary_search(&pc) {
+            Ok(i) => &self.values[i],
+            Err(0) => &[],
+            Err(i) => &self.values[i - 1],
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::ops::{Deref, Index};
 
+use super::{FieldType, MethodDescriptor, Result};
+
 #[derive(Debug)]
 pub struct ConstantPool {
     constants: Vec<Constant>,
@@ -13,6 +15,95 @@ impl ConstantPool {
     pub fn len(&self) -> u16 {
         self.constants.len() as u16 + 1
     }
+
+    /// Returns the constant at `index`, or `None` if `index` is zero or past
+    /// the end of the pool.
+    pub fn get(&self, index: u16) -> Option<&Constant> {
+        if index == 0 {
+            None
+        } else {
+            self.constants.get(index as usize - 1)
+        }
+    }
+
+    /// Resolves the name of the `Constant::Class` at `index`, or returns `None`
+    /// if `index` does not refer to a class whose name is a `Constant::Utf8`.
+    pub fn class_name(&self, index: u16) -> Option<&String> {
+        match self.get(index) {
+            Some(&Constant::Class(name_index)) => {
+                match self.get(name_index) {
+                    Some(&Constant::Utf8(ref name)) => Some(name),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Consumes the pool and returns the underlying list of constants.
+    pub fn into_constants(self) -> Vec<Constant> {
+        self.constants
+    }
+
+    /// Returns the index of the first `Constant::Utf8` entry with the given value,
+    /// or `None` if there is no such entry.
+    pub fn find_utf8(&self, value: &str) -> Option<u16> {
+        for (i, constant) in self.constants.iter().enumerate() {
+            if let Constant::Utf8(ref string) = *constant {
+                if string == value {
+                    return Some(i as u16 + 1);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the index of a constant equal to `constant`, appending it to
+    /// the pool if there is none. `Long` and `Double` constants are followed
+    /// by a `Constant::Skip`, as they take up two indexes.
+    ///
+    /// `Float` and `Double` constants are compared by their bits, so `0.0`
+    /// and `-0.0` are different constants and a NaN can be found again.
+    ///
+    /// Returns `None` if the pool has no room for the constant.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use classfile::{Constant, ConstantPool};
+    ///
+    /// let mut cp = ConstantPool::new(vec![Constant::Integer(7)]);
+    /// assert_eq!(Some(1), cp.intern(Constant::Integer(7)));
+    /// assert_eq!(Some(2), cp.intern(Constant::Long(7)));
+    /// assert_eq!(Some(4), cp.intern(Constant::Integer(8)));
+    /// ```
+    pub fn intern(&mut self, constant: Constant) -> Option<u16> {
+        for (i, existing) in self.constants.iter().enumerate() {
+            let same = match (existing, &constant) {
+                (&Constant::Float(a), &Constant::Float(b)) => a.to_bits() == b.to_bits(),
+                (&Constant::Double(a), &Constant::Double(b)) => a.to_bits() == b.to_bits(),
+                (&Constant::Skip, _) => false,
+                (existing, constant) => existing == constant,
+            };
+            if same {
+                return Some(i as u16 + 1);
+            }
+        }
+        let wide = match constant {
+            Constant::Long(_) |
+            Constant::Double(_) => true,
+            _ => false,
+        };
+        // Indexes run from 1 to 65534.
+        let size = if wide { 2 } else { 1 };
+        if self.constants.len() + size > 65534 {
+            return None;
+        }
+        self.constants.push(constant);
+        if wide {
+            self.constants.push(Constant::Skip);
+        }
+        Some((self.constants.len() + 1 - size) as u16)
+    }
 }
 
 impl Index<u16> for ConstantPool {
@@ -43,6 +134,31 @@ pub struct TypedEntityConstant {
     pub name_and_type_index: u16,
 }
 
+impl TypedEntityConstant {
+    /// Parses the descriptor of a `Fieldref`.
+    ///
+    /// # Panics
+    /// If `name_and_type_index` does not resolve to a `Constant::NameAndType`
+    /// whose descriptor is a `Constant::Utf8`.
+    pub fn field_type(&self, cp: &ConstantPool) -> Result<FieldType> {
+        FieldType::parse(self.descriptor(cp))
+    }
+
+    /// Parses the descriptor of a `Methodref` or `InterfaceMethodref`.
+    ///
+    /// # Panics
+    /// If `name_and_type_index` does not resolve to a `Constant::NameAndType`
+    /// whose descriptor is a `Constant::Utf8`.
+    pub fn method_descriptor(&self, cp: &ConstantPool) -> Result<MethodDescriptor> {
+        MethodDescriptor::parse(self.descriptor(cp))
+    }
+
+    fn descriptor<'a>(&self, cp: &'a ConstantPool) -> &'a String {
+        let name_and_type = cp[self.name_and_type_index].as_name_and_type();
+        cp[name_and_type.descriptor_index].as_utf8()
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct NameAndTypeConstant {
     /// An index into the constant pool that is of type `Constant::Utf8`.
@@ -0,0 +1,238 @@
+//! Parsing of [field and method descriptors]
+//! (https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.3).
+use std::fmt;
+use std::str::Chars;
+use std::iter::Peekable;
+
+use super::{Error, Result};
+
+/// The type of a field, parameter or local variable.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    /// An instance of the class with the given internal name, for example
+    /// `java/lang/String`.
+    Object(String),
+    /// An array with the given component type.
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    /// Parses a field descriptor such as `I` or `[Ljava/lang/String;`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use classfile::FieldType;
+    ///
+    /// let string_array = FieldType::parse("[Ljava/lang/String;").unwrap();
+    /// let string = FieldType::Object("java/lang/String".to_string());
+    /// assert_eq!(FieldType::Array(Box::new(string)), string_array);
+    /// ```
+    pub fn parse(descriptor: &str) -> Result<FieldType> {
+        let mut chars = descriptor.chars().peekable();
+        let field_type = try!(parse_field_type(&mut chars, descriptor));
+        if chars.next().is_some() {
+            return Err(invalid(descriptor));
+        }
+        Ok(field_type)
+    }
+
+    /// The number of local variable slots (or operand stack entries) taken by
+    /// a value of this type. `long` and `double` take two, all others one.
+    pub fn slots(&self) -> u16 {
+        match *self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1,
+        }
+    }
+
+    /// Returns `true` for primitive types.
+    pub fn is_primitive(&self) -> bool {
+        match *self {
+            FieldType::Object(_) | FieldType::Array(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Returns `true` for class and array types.
+    pub fn is_reference(&self) -> bool {
+        !self.is_primitive()
+    }
+
+    /// The number of array dimensions of this type; zero if this is not an
+    /// array type.
+    pub fn dimensions(&self) -> usize {
+        match *self {
+            FieldType::Array(ref component) => 1 + component.dimensions(),
+            _ => 0,
+        }
+    }
+
+    /// Formats this type as it would appear in Java source, for example
+    /// `java.lang.String[]`.
+    pub fn java_name(&self) -> String {
+        match *self {
+            FieldType::Byte => "byte".to_string(),
+            FieldType::Char => "char".to_string(),
+            FieldType::Double => "double".to_string(),
+            FieldType::Float => "float".to_string(),
+            FieldType::Int => "int".to_string(),
+            FieldType::Long => "long".to_string(),
+            FieldType::Short => "short".to_string(),
+            FieldType::Boolean => "boolean".to_string(),
+            FieldType::Object(ref name) => name.replace("/", "."),
+            FieldType::Array(ref component) => format!("{}[]", component.java_name()),
+        }
+    }
+}
+
+/// Formats the type as a descriptor.
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(ref name) => write!(f, "L{};", name),
+            FieldType::Array(ref component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+/// The parameter and return types of a method.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MethodDescriptor {
+    pub params: Vec<FieldType>,
+    /// The return type, or `None` for `void` methods.
+    pub ret: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    /// Parses a method descriptor such as `(I[Ljava/lang/String;)V`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use classfile::{FieldType, MethodDescriptor};
+    ///
+    /// let descriptor = MethodDescriptor::parse("(JI)V").unwrap();
+    /// assert_eq!(vec![FieldType::Long, FieldType::Int], descriptor.params);
+    /// assert_eq!(None, descriptor.ret);
+    /// assert_eq!(3, descriptor.param_slots());
+    /// ```
+    pub fn parse(descriptor: &str) -> Result<MethodDescriptor> {
+        let mut chars = descriptor.chars().peekable();
+        if chars.next() != Some('(') {
+            return Err(invalid(descriptor));
+        }
+        let mut params = vec![];
+        while chars.peek() != Some(&')') {
+            params.push(try!(parse_field_type(&mut chars, descriptor)));
+        }
+        chars.next();
+        let ret = if chars.peek() == Some(&'V') {
+            chars.next();
+            None
+        } else {
+            Some(try!(parse_field_type(&mut chars, descriptor)))
+        };
+        if chars.next().is_some() {
+            return Err(invalid(descriptor));
+        }
+        Ok(MethodDescriptor {
+            params: params,
+            ret: ret,
+        })
+    }
+
+    /// The number of local variable slots taken by the parameters.
+    pub fn param_slots(&self) -> u16 {
+        self.params.iter().map(|param| param.slots()).sum()
+    }
+
+    /// The number of local variable slots taken by the arguments of a method
+    /// with this descriptor, including `this` for instance methods.
+    pub fn arg_slots(&self, is_static: bool) -> u16 {
+        self.param_slots() + if is_static { 0 } else { 1 }
+    }
+
+    /// The number of operand stack entries taken by the return value.
+    pub fn ret_slots(&self) -> u16 {
+        self.ret.as_ref().map_or(0, |ret| ret.slots())
+    }
+}
+
+/// Formats the method as a descriptor.
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "("));
+        for param in self.params.iter() {
+            try!(write!(f, "{}", param));
+        }
+        try!(write!(f, ")"));
+        match self.ret {
+            Some(ref ret) => write!(f, "{}", ret),
+            None => write!(f, "V"),
+        }
+    }
+}
+
+fn invalid(descriptor: &str) -> Error {
+    Error::InvalidDescriptor(descriptor.to_string())
+}
+
+fn parse_field_type(chars: &mut Peekable<Chars>, descriptor: &str) -> Result<FieldType> {
+    // Counting the dimensions first keeps a descriptor with many `[` from
+    // recursing deeply before the limit is checked.
+    let mut dimensions = 0;
+    while chars.peek() == Some(&'[') {
+        chars.next();
+        dimensions += 1;
+        if dimensions > 255 {
+            return Err(invalid(descriptor));
+        }
+    }
+    let mut field_type = match chars.next() {
+        Some('B') => FieldType::Byte,
+        Some('C') => FieldType::Char,
+        Some('D') => FieldType::Double,
+        Some('F') => FieldType::Float,
+        Some('I') => FieldType::Int,
+        Some('J') => FieldType::Long,
+        Some('S') => FieldType::Short,
+        Some('Z') => FieldType::Boolean,
+        Some('L') => {
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some(';') => break,
+                    Some('.') | Some('[') | None => return Err(invalid(descriptor)),
+                    Some(c) => name.push(c),
+                }
+            }
+            // Each package and class name segment must be non-empty.
+            if name.split('/').any(|segment| segment.is_empty()) {
+                return Err(invalid(descriptor));
+            }
+            FieldType::Object(name)
+        }
+        _ => return Err(invalid(descriptor)),
+    };
+    for _ in 0..dimensions {
+        field_type = FieldType::Array(Box::new(field_type));
+    }
+    Ok(field_type)
+}
@@ -5,6 +5,8 @@ use std::result;
 use std::fmt;
 use std::io;
 
+use super::Label;
+
 #[derive(Debug)]
 pub enum Error {
     IOError,
@@ -14,11 +16,68 @@ pub enum Error {
     InvalidVerificationTypeInfoTag(u8),
     InvalidStackFrameType(u8),
     InvalidElementValueTag(u8),
+    MissingAttributeName(String),
+    InvalidDescriptor(String),
+    InvalidSignature(String),
+    /// `pc` is not the index of a byte in the code array.
+    PcOutOfBounds(usize),
+    /// The instruction at `pc` extends past the end of the code array.
+    TruncatedBytecode { pc: usize, opcode: u8 },
+    /// The byte at `pc` is not an opcode defined by the JVM specification.
+    InvalidOpcode { pc: usize, opcode: u8 },
+    /// The `wide` instruction at `pc` modifies an opcode that cannot be
+    /// widened.
+    InvalidWideOpcode { pc: usize, opcode: u8 },
+    /// The `tableswitch` or `lookupswitch` at `pc` has a negative size.
+    InvalidSwitch { pc: usize, opcode: u8 },
+    /// A constant pool index that is out of range or refers to a constant of
+    /// the wrong kind.
+    InvalidConstantReference(u16),
+    /// A branch, table or frame refers to `pc`, which does not start an
+    /// instruction, or a range ends at `pc` before it starts.
+    InvalidCodeOffset(usize),
+    /// A label is used by an `InsnList` but does not mark a position in it.
+    UndefinedLabel(Label),
+    /// A label marks more than one position in an `InsnList`.
+    DuplicateLabel(Label),
+    /// The node at `index` of an `InsnList` has the wrong number of branch
+    /// targets for its instruction.
+    MissingBranchTargets { index: usize },
+    /// The code is longer than 65535 bytes, or a conditional branch cannot
+    /// reach its target.
+    CodeTooLarge,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "BOOM")
+        match *self {
+            Error::PcOutOfBounds(pc) => write!(f, "pc {} is out of bounds", pc),
+            Error::TruncatedBytecode { pc, opcode } => {
+                write!(f, "truncated instruction (opcode 0x{:02x}) at pc {}", opcode, pc)
+            }
+            Error::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode 0x{:02x} at pc {}", opcode, pc)
+            }
+            Error::InvalidWideOpcode { pc, opcode } => {
+                write!(f, "invalid opcode 0x{:02x} following wide at pc {}", opcode, pc)
+            }
+            Error::InvalidSwitch { pc, opcode } => {
+                write!(f, "invalid switch size (opcode 0x{:02x}) at pc {}", opcode, pc)
+            }
+            Error::InvalidConstantReference(index) => {
+                write!(f, "invalid constant pool reference #{}", index)
+            }
+            Error::InvalidCodeOffset(pc) => write!(f, "pc {} is not an instruction boundary", pc),
+            Error::UndefinedLabel(label) => write!(f, "{:?} is not in the instruction list", label),
+            Error::DuplicateLabel(label) => {
+                write!(f, "{:?} is in the instruction list more than once", label)
+            }
+            Error::MissingBranchTargets { index } => {
+                write!(f, "wrong number of branch targets at node {}", index)
+            }
+            Error::CodeTooLarge => write!(f, "code is too large"),
+            _ => write!(f, "BOOM"),
+        }
     }
 }
 
@@ -1,4 +1,4 @@
-use super::Attributes;
+use super::{Attributes, ConstantPool, FieldSignature, FieldType, Result};
 
 bitflags! {
 /// Holds the
@@ -69,3 +69,20 @@ pub struct FieldInfo {
     /// Collection of attributes that are associated with this field.
     pub attrs: Attributes,
 }
+
+impl FieldInfo {
+    /// Parses the descriptor of this field.
+    ///
+    /// # Panics
+    /// If `descriptor_index` does not point to a `Constant::Utf8` entry in the
+    /// constant pool.
+    pub fn descriptor(&self, cp: &ConstantPool) -> Result<FieldType> {
+        FieldType::parse(cp[self.descriptor_index].as_utf8())
+    }
+
+    /// Parses the generic signature of this field, if it has a `Signature`
+    /// attribute.
+    pub fn signature(&self, cp: &ConstantPool) -> Option<Result<FieldSignature>> {
+        self.attrs.signature(cp).map(|signature| FieldSignature::parse(signature))
+    }
+}
@@ -0,0 +1,844 @@
+//! A mutable list of instructions for editing method bodies.
+//!
+//! An `InsnList` holds the instructions of a `CodeAttribute` as a list of
+//! nodes in which branch targets, exception table entries, line numbers,
+//! local variable ranges, type annotation offsets and stack map frames refer
+//! to labels rather than pcs. Nodes can be inserted, removed and replaced
+//! freely, and `InsnList::write` lays the code out again, computing every
+//! pc-dependent structure from the positions of the labels.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::slice;
+
+use super::{AttributeInfo, Bytecode, CodeAttribute, Error, ExceptionInfo, InstructionList,
+            LineNumberTableEntry, LocalVariableTableEntry, LocalVariableTypeTableEntry,
+            LocalvarInfo, ObjectVariableInfo, Result, StackMapFrame, TargetInfo, TypeAnnotation,
+            UninitializedVariableInfo, VerificationTypeInfo};
+
+/// A position in an `InsnList`, marked by a `Node::Label`. When the code is
+/// written, a label stands for the pc of the first instruction after it, or
+/// the length of the code if there is none.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Label(u32);
+
+/// An element of an `InsnList`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    /// Marks a position that other nodes and tables can refer to.
+    Label(Label),
+    /// Starts a line of source code at the next instruction.
+    LineNumber(u16),
+    /// The stack map frame at the next instruction.
+    Frame(FrameNode),
+    /// An instruction that does not branch.
+    Insn(Bytecode),
+    /// An instruction that branches to the given labels, in the order of
+    /// `Bytecode::branch_targets`: a single target for jumps, and the default
+    /// followed by the cases for switches. The offsets in the instruction
+    /// itself are ignored.
+    Branch(Bytecode, Vec<Label>),
+}
+
+/// A stack map frame, in the compressed forms of the `StackMapTable` but
+/// without an offset. `Chop` and `Append` frames, and `Same` frames, are
+/// relative to the previous frame in the list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameNode {
+    Same,
+    SameLocals1StackItem(FrameValue),
+    /// Removes the given number of locals, from one to three.
+    Chop(u8),
+    /// Adds one to three locals.
+    Append(Vec<FrameValue>),
+    Full {
+        locals: Vec<FrameValue>,
+        stack: Vec<FrameValue>,
+    },
+}
+
+/// The type of a local or stack slot in a `FrameNode`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FrameValue {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// A class by the index of its `Constant::Class`.
+    Object(u16),
+    /// The object created by the `new` instruction after the label.
+    Uninitialized(Label),
+}
+
+/// An entry of the exception table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TryCatchBlock {
+    pub start: Label,
+    /// The end of the range, which is exclusive.
+    pub end: Label,
+    pub handler: Label,
+    /// The index of the `Constant::Class` caught, or zero for any throwable.
+    pub catch_type: u16,
+}
+
+/// An entry of the `LocalVariableTable`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LocalVariableNode {
+    pub start: Label,
+    pub end: Label,
+    pub index: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+}
+
+/// An entry of the `LocalVariableTypeTable`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LocalVariableTypeNode {
+    pub start: Label,
+    pub end: Label,
+    pub index: u16,
+    pub name_index: u16,
+    pub signature_index: u16,
+}
+
+/// The part of a type annotation on the code that depends on pcs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum AnnotationOffsets {
+    /// The instruction after a label, such as a `new` or `checkcast`.
+    Instruction(Label),
+    /// The ranges of a local variable, with its slot in each.
+    Ranges(Vec<(Label, Label, u16)>),
+    /// No pcs, as for the `Catch` target, which is an exception table index.
+    None,
+}
+
+/// The instructions of a method as an editable list of nodes, with the
+/// exception table and local variable tables held in terms of labels.
+///
+/// # Examples
+/// ```rust
+/// use classfile::{Bytecode, CodeAttribute, InsnList, Node};
+///
+/// # fn prepend_nop(code: &mut CodeAttribute) -> classfile::Result<()> {
+/// let mut list = try!(code.insn_list());
+/// list.insert(0, Node::Insn(Bytecode::nop));
+/// try!(list.write(code));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct InsnList {
+    nodes: Vec<Node>,
+    next_label: u32,
+    pub try_catch_blocks: Vec<TryCatchBlock>,
+    pub local_variables: Vec<LocalVariableNode>,
+    pub local_variable_types: Vec<LocalVariableTypeNode>,
+    /// The offsets of each type annotation on the code, in the order of the
+    /// annotations in the code's attributes.
+    annotations: Vec<AnnotationOffsets>,
+}
+
+impl InsnList {
+    /// Decodes the instructions of `code`, labelling every pc its exception
+    /// table, attributes and frames refer to.
+    ///
+    /// Fails if an instruction cannot be decoded, or something refers to a pc
+    /// that neither starts an instruction nor ends the code.
+    pub fn new(code: &CodeAttribute) -> Result<InsnList> {
+        let instructions = try!(code.instruction_list());
+        let code_length = instructions.code_length();
+        let mut labels = Labels {
+            instructions: &instructions,
+            pcs: BTreeMap::new(),
+            next_label: 0,
+        };
+
+        let mut branches = Vec::with_capacity(instructions.len());
+        for &(pc, ref insn) in instructions.iter() {
+            let mut targets = vec![];
+            for target in insn.branch_targets(pc) {
+                targets.push(try!(labels.at(target)));
+            }
+            branches.push(targets);
+        }
+
+        let mut try_catch_blocks = vec![];
+        for info in &code.exception_table {
+            try_catch_blocks.push(TryCatchBlock {
+                start: try!(labels.at(info.start_pc as usize)),
+                end: try!(labels.at(info.end_pc as usize)),
+                handler: try!(labels.at(info.handler_pc as usize)),
+                catch_type: info.catch_type,
+            });
+        }
+
+        let mut lines: BTreeMap<usize, Vec<u16>> = BTreeMap::new();
+        let mut local_variables = vec![];
+        let mut local_variable_types = vec![];
+        let mut annotations = vec![];
+        let mut frames = BTreeMap::new();
+        for attr in code.attrs.iter() {
+            match *attr {
+                AttributeInfo::LineNumberTable(ref table) => {
+                    for entry in table {
+                        let pc = entry.start_pc as usize;
+                        if !instructions.is_boundary(pc) {
+                            return Err(Error::InvalidCodeOffset(pc));
+                        }
+                        lines.entry(pc).or_insert_with(Vec::new).push(entry.line_number);
+                    }
+                }
+                AttributeInfo::LocalVariableTable(ref table) => {
+                    for entry in table {
+                        let (start, end) = try!(labels.range(entry.start_pc, entry.length));
+                        local_variables.push(LocalVariableNode {
+                            start: start,
+                            end: end,
+                            index: entry.index,
+                            name_index: entry.name_index,
+                            descriptor_index: entry.descriptor_index,
+                        });
+                    }
+                }
+                AttributeInfo::LocalVariableTypeTable(ref table) => {
+                    for entry in table {
+                        let (start, end) = try!(labels.range(entry.start_pc, entry.length));
+                        local_variable_types.push(LocalVariableTypeNode {
+                            start: start,
+                            end: end,
+                            index: entry.index,
+                            name_index: entry.name_index,
+                            signature_index: entry.signature_index,
+                        });
+                    }
+                }
+                AttributeInfo::RuntimeVisibleTypeAnnotations(ref annotated) |
+                AttributeInfo::RuntimeInvisibleTypeAnnotations(ref annotated) => {
+                    for annotation in annotated {
+                        let offsets = match annotation.target_info {
+                            TargetInfo::Offset(offset) |
+                            TargetInfo::TypeArgument { offset, .. } => {
+                                AnnotationOffsets::Instruction(try!(labels.at(offset as usize)))
+                            }
+                            TargetInfo::Localvar(ref table) => {
+                                let mut ranges = vec![];
+                                for info in table {
+                                    let (start, end) = try!(labels.range(info.start_pc,
+                                                                         info.length));
+                                    ranges.push((start, end, info.index));
+                                }
+                                AnnotationOffsets::Ranges(ranges)
+                            }
+                            _ => AnnotationOffsets::None,
+                        };
+                        annotations.push(offsets);
+                    }
+                }
+                AttributeInfo::StackMapTable(ref entries) => {
+                    let mut pc: Option<usize> = None;
+                    for entry in entries {
+                        let (offset_delta, frame) = try!(frame_node(entry, &mut labels));
+                        let frame_pc = pc.map_or(offset_delta, |pc| pc + offset_delta + 1);
+                        if !instructions.is_boundary(frame_pc) {
+                            return Err(Error::InvalidCodeOffset(frame_pc));
+                        }
+                        frames.insert(frame_pc, frame);
+                        pc = Some(frame_pc);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut nodes = vec![];
+        for (&(pc, ref insn), targets) in instructions.iter().zip(branches) {
+            if let Some(&label) = labels.pcs.get(&pc) {
+                nodes.push(Node::Label(label));
+            }
+            for &line in lines.get(&pc).map_or(&[][..], |lines| &lines[..]) {
+                nodes.push(Node::LineNumber(line));
+            }
+            if let Some(frame) = frames.remove(&pc) {
+                nodes.push(Node::Frame(frame));
+            }
+            if targets.is_empty() {
+                nodes.push(Node::Insn(insn.clone()));
+            } else {
+                nodes.push(Node::Branch(insn.clone(), targets));
+            }
+        }
+        if let Some(&label) = labels.pcs.get(&code_length) {
+            nodes.push(Node::Label(label));
+        }
+
+        Ok(InsnList {
+            nodes: nodes,
+            next_label: labels.next_label,
+            try_catch_blocks: try_catch_blocks,
+            local_variables: local_variables,
+            local_variable_types: local_variable_types,
+            annotations: annotations,
+        })
+    }
+
+    /// The number of nodes, including labels, line numbers and frames.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The node at `index`.
+    pub fn get(&self, index: usize) -> Option<&Node> {
+        self.nodes.get(index)
+    }
+
+    pub fn iter<'a>(&'a self) -> slice::Iter<'a, Node> {
+        self.nodes.iter()
+    }
+
+    /// The index of the node marking `label`, if it is in the list.
+    pub fn position(&self, label: Label) -> Option<usize> {
+        self.nodes.iter().position(|node| *node == Node::Label(label))
+    }
+
+    /// Creates a label that is distinct from every other label of the list.
+    /// It does not mark a position until a `Node::Label` for it is inserted.
+    pub fn new_label(&mut self) -> Label {
+        let label = Label(self.next_label);
+        self.next_label += 1;
+        label
+    }
+
+    /// Inserts `node` before the node at `index`.
+    ///
+    /// # Panics
+    /// If `index` is greater than the number of nodes.
+    pub fn insert(&mut self, index: usize, node: Node) {
+        self.nodes.insert(index, node);
+    }
+
+    /// Inserts `nodes` before the node at `index`, in order.
+    ///
+    /// # Panics
+    /// If `index` is greater than the number of nodes.
+    pub fn insert_all(&mut self, index: usize, nodes: Vec<Node>) {
+        let tail = self.nodes.split_off(index);
+        self.nodes.extend(nodes);
+        self.nodes.extend(tail);
+    }
+
+    /// Adds `node` at the end of the list.
+    pub fn push(&mut self, node: Node) {
+        self.nodes.push(node);
+    }
+
+    /// Removes and returns the node at `index`.
+    ///
+    /// # Panics
+    /// If `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Node {
+        self.nodes.remove(index)
+    }
+
+    /// Replaces the node at `index` with `node`, returning the old one.
+    ///
+    /// # Panics
+    /// If `index` is out of bounds.
+    pub fn replace(&mut self, index: usize, node: Node) -> Node {
+        ::std::mem::replace(&mut self.nodes[index], node)
+    }
+
+    /// Lays out the instructions and writes them back into `code`, which
+    /// should be the attribute the list was built from.
+    ///
+    /// Branch offsets and switch tables are computed from the labels, and a
+    /// `goto` or `jsr` that cannot reach its target becomes a `goto_w` or
+    /// `jsr_w`. The exception table is replaced, as are the
+    /// `LineNumberTable`, `LocalVariableTable`, `LocalVariableTypeTable` and
+    /// `StackMapTable` attributes, which are added if the code did not have
+    /// them but the list has entries for them. The offsets of type
+    /// annotations are updated; other attributes are kept as they are.
+    ///
+    /// Fails without changing `code` if a label is used but not in the list
+    /// or is in it twice, a conditional branch cannot reach its target, the
+    /// code is longer than 65535 bytes, or two frames or a frame and the end
+    /// of the code fall at the same pc.
+    pub fn write(&self, code: &mut CodeAttribute) -> Result<()> {
+        let layout = try!(self.layout());
+        let pc_of = |label: Label| layout.labels[&label];
+        let range = |start: Label, end: Label| {
+            let (start, end) = (pc_of(start), pc_of(end));
+            if end < start {
+                Err(Error::InvalidCodeOffset(end))
+            } else {
+                Ok((start as u16, (end - start) as u16))
+            }
+        };
+
+        let mut exception_table = vec![];
+        for block in &self.try_catch_blocks {
+            exception_table.push(ExceptionInfo {
+                start_pc: pc_of(block.start) as u16,
+                end_pc: pc_of(block.end) as u16,
+                handler_pc: pc_of(block.handler) as u16,
+                catch_type: block.catch_type,
+            });
+        }
+        let lines: Vec<LineNumberTableEntry> = layout.lines
+            .iter()
+            .map(|&(pc, line)| {
+                LineNumberTableEntry {
+                    start_pc: pc as u16,
+                    line_number: line,
+                }
+            })
+            .collect();
+        let mut local_variables = vec![];
+        for local in &self.local_variables {
+            let (start_pc, length) = try!(range(local.start, local.end));
+            local_variables.push(LocalVariableTableEntry {
+                start_pc: start_pc,
+                length: length,
+                name_index: local.name_index,
+                descriptor_index: local.descriptor_index,
+                index: local.index,
+            });
+        }
+        let mut local_variable_types = vec![];
+        for local in &self.local_variable_types {
+            let (start_pc, length) = try!(range(local.start, local.end));
+            local_variable_types.push(LocalVariableTypeTableEntry {
+                start_pc: start_pc,
+                length: length,
+                name_index: local.name_index,
+                signature_index: local.signature_index,
+                index: local.index,
+            });
+        }
+        let mut annotations = vec![];
+        for offsets in &self.annotations {
+            annotations.push(match *offsets {
+                AnnotationOffsets::Instruction(label) => Some(vec![(pc_of(label) as u16, 0, 0)]),
+                AnnotationOffsets::Ranges(ref ranges) => {
+                    let mut infos = vec![];
+                    for &(start, end, index) in ranges {
+                        let (start_pc, length) = try!(range(start, end));
+                        infos.push((start_pc, length, index));
+                    }
+                    Some(infos)
+                }
+                AnnotationOffsets::None => None,
+            });
+        }
+        let mut stack_map = vec![];
+        let mut previous: Option<usize> = None;
+        for &(pc, ref frame) in &layout.frames {
+            if pc == layout.code.len() || previous.map_or(false, |previous| pc <= previous) {
+                return Err(Error::InvalidCodeOffset(pc));
+            }
+            let offset_delta = previous.map_or(pc, |previous| pc - previous - 1) as u16;
+            stack_map.push(stack_map_frame(frame, offset_delta, &layout.labels));
+            previous = Some(pc);
+        }
+
+        code.code = layout.code;
+        code.exception_table = exception_table;
+        // Each table replaces the first attribute of its kind, and any
+        // others are dropped.
+        let mut lines = Some(lines);
+        let mut local_variables = Some(local_variables);
+        let mut local_variable_types = Some(local_variable_types);
+        let mut stack_map = Some(stack_map);
+        let mut annotations = annotations.into_iter();
+        let mut attrs = vec![];
+        for mut attr in code.attrs.drain(..) {
+            attr = match attr {
+                AttributeInfo::LineNumberTable(_) => {
+                    match lines.take() {
+                        Some(lines) => AttributeInfo::LineNumberTable(lines),
+                        None => continue,
+                    }
+                }
+                AttributeInfo::LocalVariableTable(_) => {
+                    match local_variables.take() {
+                        Some(locals) => AttributeInfo::LocalVariableTable(locals),
+                        None => continue,
+                    }
+                }
+                AttributeInfo::LocalVariableTypeTable(_) => {
+                    match local_variable_types.take() {
+                        Some(locals) => AttributeInfo::LocalVariableTypeTable(locals),
+                        None => continue,
+                    }
+                }
+                AttributeInfo::StackMapTable(_) => {
+                    match stack_map.take() {
+                        Some(frames) => AttributeInfo::StackMapTable(frames),
+                        None => continue,
+                    }
+                }
+                AttributeInfo::RuntimeVisibleTypeAnnotations(mut annotated) => {
+                    update_annotations(&mut annotated, &mut annotations);
+                    AttributeInfo::RuntimeVisibleTypeAnnotations(annotated)
+                }
+                AttributeInfo::RuntimeInvisibleTypeAnnotations(mut annotated) => {
+                    update_annotations(&mut annotated, &mut annotations);
+                    AttributeInfo::RuntimeInvisibleTypeAnnotations(annotated)
+                }
+                other => other,
+            };
+            attrs.push(attr);
+        }
+        match lines {
+            Some(ref lines) if lines.is_empty() => {}
+            Some(lines) => attrs.push(AttributeInfo::LineNumberTable(lines)),
+            None => {}
+        }
+        match local_variables {
+            Some(ref locals) if locals.is_empty() => {}
+            Some(locals) => attrs.push(AttributeInfo::LocalVariableTable(locals)),
+            None => {}
+        }
+        match local_variable_types {
+            Some(ref locals) if locals.is_empty() => {}
+            Some(locals) => attrs.push(AttributeInfo::LocalVariableTypeTable(locals)),
+            None => {}
+        }
+        match stack_map {
+            Some(ref frames) if frames.is_empty() => {}
+            Some(frames) => attrs.push(AttributeInfo::StackMapTable(frames)),
+            None => {}
+        }
+        code.attrs.extend(attrs);
+        Ok(())
+    }
+
+    /// Assigns pcs to the nodes, widening jumps until every branch reaches
+    /// its target.
+    fn layout<'a>(&'a self) -> Result<Layout<'a>> {
+        let mut wide = vec![false; self.nodes.len()];
+        let mut labels: HashMap<Label, usize> = HashMap::new();
+        loop {
+            let mut layout = Layout {
+                code: vec![],
+                labels: HashMap::new(),
+                lines: vec![],
+                frames: vec![],
+            };
+            let mut widened = false;
+            for (index, node) in self.nodes.iter().enumerate() {
+                let pc = layout.code.len();
+                match *node {
+                    Node::Label(label) => {
+                        if layout.labels.insert(label, pc).is_some() {
+                            return Err(Error::DuplicateLabel(label));
+                        }
+                    }
+                    Node::LineNumber(line) => layout.lines.push((pc, line)),
+                    Node::Frame(ref frame) => layout.frames.push((pc, frame)),
+                    Node::Insn(ref insn) => {
+                        if !insn.branch_targets(pc).is_empty() {
+                            return Err(Error::MissingBranchTargets { index: index });
+                        }
+                        insn.encode(&mut layout.code);
+                    }
+                    Node::Branch(ref insn, ref targets) => {
+                        // Targets not yet placed, or not in the list at all,
+                        // get their final pc on a later round.
+                        let offsets: Vec<i32> = targets.iter()
+                            .map(|target| labels.get(target).map_or(0, |&target| target as i32) -
+                                          pc as i32)
+                            .collect();
+                        let insn = if wide[index] { widen(insn) } else { insn.clone() };
+                        let insn = match insn.with_branch_offsets(&offsets) {
+                            Some(insn) => insn,
+                            None if insn.branch_targets(0).len() != targets.len() => {
+                                return Err(Error::MissingBranchTargets { index: index });
+                            }
+                            None if widen(&insn) != insn => {
+                                wide[index] = true;
+                                widened = true;
+                                widen(&insn)
+                            }
+                            None => return Err(Error::CodeTooLarge),
+                        };
+                        insn.encode(&mut layout.code);
+                    }
+                }
+            }
+            if layout.code.len() > 65535 {
+                return Err(Error::CodeTooLarge);
+            }
+            for node in &self.nodes {
+                if let Node::Branch(_, ref targets) = *node {
+                    for target in targets {
+                        if !layout.labels.contains_key(target) {
+                            return Err(Error::UndefinedLabel(*target));
+                        }
+                    }
+                }
+            }
+            if !widened && layout.labels == labels {
+                try!(self.check_labels(&layout.labels));
+                return Ok(layout);
+            }
+            labels = layout.labels;
+        }
+    }
+
+    /// Checks that every label the tables and frames use is in the list.
+    fn check_labels(&self, labels: &HashMap<Label, usize>) -> Result<()> {
+        let mut used = BTreeSet::new();
+        for block in &self.try_catch_blocks {
+            used.extend(vec![block.start, block.end, block.handler]);
+        }
+        for local in &self.local_variables {
+            used.extend(vec![local.start, local.end]);
+        }
+        for local in &self.local_variable_types {
+            used.extend(vec![local.start, local.end]);
+        }
+        for offsets in &self.annotations {
+            match *offsets {
+                AnnotationOffsets::Instruction(label) => {
+                    used.insert(label);
+                }
+                AnnotationOffsets::Ranges(ref ranges) => {
+                    for &(start, end, _) in ranges {
+                        used.extend(vec![start, end]);
+                    }
+                }
+                AnnotationOffsets::None => {}
+            }
+        }
+        for node in &self.nodes {
+            if let Node::Frame(ref frame) = *node {
+                let values: Vec<&FrameValue> = match *frame {
+                    FrameNode::SameLocals1StackItem(ref value) => vec![value],
+                    FrameNode::Append(ref locals) => locals.iter().collect(),
+                    FrameNode::Full { ref locals, ref stack } => {
+                        locals.iter().chain(stack.iter()).collect()
+                    }
+                    _ => vec![],
+                };
+                for value in values {
+                    if let FrameValue::Uninitialized(label) = *value {
+                        used.insert(label);
+                    }
+                }
+            }
+        }
+        match used.into_iter().find(|label| !labels.contains_key(label)) {
+            Some(label) => Err(Error::UndefinedLabel(label)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a InsnList {
+    type Item = &'a Node;
+    type IntoIter = slice::Iter<'a, Node>;
+
+    fn into_iter(self) -> slice::Iter<'a, Node> {
+        self.nodes.iter()
+    }
+}
+
+/// The labels created while decoding, one per pc.
+struct Labels<'a> {
+    instructions: &'a InstructionList,
+    pcs: BTreeMap<usize, Label>,
+    next_label: u32,
+}
+
+impl<'a> Labels<'a> {
+    /// The label of `pc`, which must start an instruction or end the code.
+    fn at(&mut self, pc: usize) -> Result<Label> {
+        if !self.instructions.is_boundary(pc) && pc != self.instructions.code_length() {
+            return Err(Error::InvalidCodeOffset(pc));
+        }
+        let next_label = &mut self.next_label;
+        Ok(*self.pcs.entry(pc).or_insert_with(|| {
+            *next_label += 1;
+            Label(*next_label - 1)
+        }))
+    }
+
+    fn range(&mut self, start_pc: u16, length: u16) -> Result<(Label, Label)> {
+        let start = try!(self.at(start_pc as usize));
+        let end = try!(self.at(start_pc as usize + length as usize));
+        Ok((start, end))
+    }
+}
+
+/// The code laid out by `InsnList::layout`, with the pcs of its labels,
+/// line numbers and frames.
+struct Layout<'a> {
+    code: Vec<u8>,
+    labels: HashMap<Label, usize>,
+    lines: Vec<(usize, u16)>,
+    frames: Vec<(usize, &'a FrameNode)>,
+}
+
+/// Sets the offsets of the type annotations in `annotated` to the next ones
+/// from `offsets`, which are `None` for annotations without any.
+fn update_annotations<I>(annotated: &mut [TypeAnnotation], offsets: &mut I)
+    where I: Iterator<Item = Option<Vec<(u16, u16, u16)>>>
+{
+    for (annotation, offsets) in annotated.iter_mut().zip(offsets) {
+        let offsets = match offsets {
+            Some(offsets) => offsets,
+            None => continue,
+        };
+        match annotation.target_info {
+            TargetInfo::Offset(ref mut offset) |
+            TargetInfo::TypeArgument { ref mut offset, .. } => *offset = offsets[0].0,
+            TargetInfo::Localvar(ref mut table) => {
+                *table = offsets.into_iter()
+                    .map(|(start_pc, length, index)| {
+                        LocalvarInfo {
+                            start_pc: start_pc,
+                            length: length,
+                            index: index,
+                        }
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The form of a jump that takes a 32-bit offset, or the instruction itself
+/// if it has none.
+fn widen(insn: &Bytecode) -> Bytecode {
+    match *insn {
+        Bytecode::goto { .. } => Bytecode::goto_w { branchoffset: 0 },
+        Bytecode::jsr { .. } => Bytecode::jsr_w { branchoffset: 0 },
+        ref other => other.clone(),
+    }
+}
+
+/// Decodes a `StackMapTable` entry into its offset delta and frame.
+fn frame_node(entry: &StackMapFrame, labels: &mut Labels) -> Result<(usize, FrameNode)> {
+    let frame = match *entry {
+        StackMapFrame::SameFrame { frame_type } => (frame_type as usize, FrameNode::Same),
+        StackMapFrame::SameFrameExtended { offset_delta } => {
+            (offset_delta as usize, FrameNode::Same)
+        }
+        StackMapFrame::SameLocals1StackItemFrame { frame_type, ref stack } => {
+            (frame_type as usize - 64,
+             FrameNode::SameLocals1StackItem(try!(frame_value(&stack[0], labels))))
+        }
+        StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, ref stack } => {
+            (offset_delta as usize,
+             FrameNode::SameLocals1StackItem(try!(frame_value(&stack[0], labels))))
+        }
+        StackMapFrame::ChopFrame { frame_type, offset_delta } => {
+            (offset_delta as usize, FrameNode::Chop(251 - frame_type))
+        }
+        StackMapFrame::AppendFrame { offset_delta, ref locals, .. } => {
+            (offset_delta as usize, FrameNode::Append(try!(frame_values(locals, labels))))
+        }
+        StackMapFrame::FullFrame { offset_delta, ref locals, ref stack } => {
+            (offset_delta as usize,
+             FrameNode::Full {
+                locals: try!(frame_values(locals, labels)),
+                stack: try!(frame_values(stack, labels)),
+            })
+        }
+    };
+    Ok(frame)
+}
+
+fn frame_values(infos: &[VerificationTypeInfo], labels: &mut Labels) -> Result<Vec<FrameValue>> {
+    let mut values = vec![];
+    for info in infos {
+        values.push(try!(frame_value(info, labels)));
+    }
+    Ok(values)
+}
+
+fn frame_value(info: &VerificationTypeInfo, labels: &mut Labels) -> Result<FrameValue> {
+    let value = match *info {
+        VerificationTypeInfo::Top => FrameValue::Top,
+        VerificationTypeInfo::Integer => FrameValue::Integer,
+        VerificationTypeInfo::Float => FrameValue::Float,
+        VerificationTypeInfo::Long => FrameValue::Long,
+        VerificationTypeInfo::Double => FrameValue::Double,
+        VerificationTypeInfo::Null => FrameValue::Null,
+        VerificationTypeInfo::UninitializedThis => FrameValue::UninitializedThis,
+        VerificationTypeInfo::Object(ref info) => FrameValue::Object(info.cpool_index),
+        VerificationTypeInfo::Uninitialized(ref info) => {
+            FrameValue::Uninitialized(try!(labels.at(info.offset as usize)))
+        }
+    };
+    Ok(value)
+}
+
+/// Encodes `frame` as a `StackMapTable` entry, choosing the extended forms
+/// of `Same` and `SameLocals1StackItem` frames when the offset needs them.
+fn stack_map_frame(frame: &FrameNode,
+                   offset_delta: u16,
+                   labels: &HashMap<Label, usize>)
+                   -> StackMapFrame {
+    let info = |value: &FrameValue| match *value {
+        FrameValue::Top => VerificationTypeInfo::Top,
+        FrameValue::Integer => VerificationTypeInfo::Integer,
+        FrameValue::Float => VerificationTypeInfo::Float,
+        FrameValue::Long => VerificationTypeInfo::Long,
+        FrameValue::Double => VerificationTypeInfo::Double,
+        FrameValue::Null => VerificationTypeInfo::Null,
+        FrameValue::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+        FrameValue::Object(index) => {
+            VerificationTypeInfo::Object(ObjectVariableInfo { cpool_index: index })
+        }
+        FrameValue::Uninitialized(label) => {
+            VerificationTypeInfo::Uninitialized(UninitializedVariableInfo {
+                offset: labels[&label] as u16,
+            })
+        }
+    };
+    match *frame {
+        FrameNode::Same if offset_delta < 64 => {
+            StackMapFrame::SameFrame { frame_type: offset_delta as u8 }
+        }
+        FrameNode::Same => StackMapFrame::SameFrameExtended { offset_delta: offset_delta },
+        FrameNode::SameLocals1StackItem(ref value) if offset_delta < 64 => {
+            StackMapFrame::SameLocals1StackItemFrame {
+                frame_type: 64 + offset_delta as u8,
+                stack: [info(value)],
+            }
+        }
+        FrameNode::SameLocals1StackItem(ref value) => {
+            StackMapFrame::SameLocals1StackItemFrameExtended {
+                offset_delta: offset_delta,
+                stack: [info(value)],
+            }
+        }
+        FrameNode::Chop(count) => {
+            StackMapFrame::ChopFrame {
+                frame_type: 251 - count,
+                offset_delta: offset_delta,
+            }
+        }
+        FrameNode::Append(ref locals) => {
+            StackMapFrame::AppendFrame {
+                frame_type: 251 + locals.len() as u8,
+                offset_delta: offset_delta,
+                locals: locals.iter().map(&info).collect(),
+            }
+        }
+        FrameNode::Full { ref locals, ref stack } => {
+            StackMapFrame::FullFrame {
+                offset_delta: offset_delta,
+                locals: locals.iter().map(&info).collect(),
+                stack: stack.iter().map(&info).collect(),
+            }
+        }
+    }
+}
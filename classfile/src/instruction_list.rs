@@ -0,0 +1,208 @@
+//! Iteration over the instructions of a code array, and an indexed list of
+//! them for analyses that need to move between pcs and instructions.
+use super::{Bytecode, Result};
+
+/// An iterator over the instructions of a code array, yielding each
+/// instruction with its pc.
+///
+/// Iteration stops after the first instruction that cannot be decoded, which
+/// is yielded as an error.
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    pc: usize,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(code: &'a [u8]) -> Instructions<'a> {
+        Instructions { code: code, pc: 0 }
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<(usize, Bytecode)>;
+
+    fn next(&mut self) -> Option<Result<(usize, Bytecode)>> {
+        if self.pc >= self.code.len() {
+            return None;
+        }
+        let pc = self.pc;
+        match Bytecode::decode(self.code, pc) {
+            Ok(result) => {
+                self.pc = result.newpc;
+                Some(Ok((pc, result.bytecode)))
+            }
+            Err(err) => {
+                self.pc = self.code.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// The decoded instructions of a code array, indexed both by position in the
+/// list and by pc.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstructionList {
+    instructions: Vec<(usize, Bytecode)>,
+    /// The index of the instruction containing each byte of the code.
+    containing: Vec<u32>,
+}
+
+impl InstructionList {
+    /// Decodes every instruction in `code`, failing on the first one that
+    /// cannot be decoded.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use classfile::InstructionList;
+    ///
+    /// // bipush 7; ireturn
+    /// let list = InstructionList::new(&[0x10, 0x07, 0xac]).unwrap();
+    /// assert_eq!(2, list.len());
+    /// assert_eq!(Some(1), list.index_of(2));
+    /// assert_eq!(Some(0), list.containing(1));
+    /// assert!(!list.is_boundary(1));
+    /// ```
+    pub fn new(code: &[u8]) -> Result<InstructionList> {
+        let mut instructions = vec![];
+        for instruction in Instructions::new(code) {
+            instructions.push(try!(instruction));
+        }
+        let mut containing = Vec::with_capacity(code.len());
+        for (index, &(pc, _)) in instructions.iter().enumerate() {
+            let end = instructions.get(index + 1).map_or(code.len(), |&(next, _)| next);
+            for _ in pc..end {
+                containing.push(index as u32);
+            }
+        }
+        Ok(InstructionList {
+            instructions: instructions,
+            containing: containing,
+        })
+    }
+
+    /// The number of instructions.
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// The length of the code array in bytes.
+    pub fn code_length(&self) -> usize {
+        self.containing.len()
+    }
+
+    /// The instruction at `index`.
+    pub fn get(&self, index: usize) -> Option<&Bytecode> {
+        self.instructions.get(index).map(|&(_, ref bytecode)| bytecode)
+    }
+
+    /// The pc of the instruction at `index`.
+    pub fn pc(&self, index: usize) -> Option<usize> {
+        self.instructions.get(index).map(|&(pc, _)| pc)
+    }
+
+    /// The pc just past the instruction at `index`: the pc of the following
+    /// instruction, or the code length for the last one.
+    pub fn end_pc(&self, index: usize) -> Option<usize> {
+        if index < self.instructions.len() {
+            Some(self.instructions.get(index + 1).map_or(self.code_length(), |&(pc, _)| pc))
+        } else {
+            None
+        }
+    }
+
+    /// The index of the instruction that starts at `pc`, or `None` if `pc` is
+    /// not an instruction boundary.
+    pub fn index_of(&self, pc: usize) -> Option<usize> {
+        self.containing(pc).and_then(|index| if self.instructions[index].0 == pc {
+            Some(index)
+        } else {
+            None
+        })
+    }
+
+    /// The index of the instruction whose bytes include `offset`, or `None`
+    /// if `offset` is past the end of the code.
+    pub fn containing(&self, offset: usize) -> Option<usize> {
+        self.containing.get(offset).map(|&index| index as usize)
+    }
+
+    /// Returns `true` if an instruction starts at `pc`.
+    ///
+    /// The code length itself is not a boundary, even though it is a valid
+    /// exclusive end for exception handler and local variable ranges.
+    pub fn is_boundary(&self, pc: usize) -> bool {
+        self.index_of(pc).is_some()
+    }
+
+    /// Iterates over the instructions with their pcs.
+    pub fn iter<'a>(&'a self) -> ::std::slice::Iter<'a, (usize, Bytecode)> {
+        self.instructions.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::Error;
+
+    #[test]
+    fn test_instructions_stop_after_error() {
+        // Given
+        let code = vec![
+            0x00,       // nop
+            0x11, 0x01, // truncated sipush
+        ];
+
+        // When
+        let instructions: Vec<_> = Instructions::new(&code).collect();
+
+        // Then
+        assert_eq!(2, instructions.len());
+        assert_eq!((0, Bytecode::nop), *instructions[0].as_ref().unwrap());
+        match instructions[1] {
+            Err(Error::TruncatedBytecode { pc: 1, opcode: 0x11 }) => {}
+            ref other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_instruction_list_maps_pcs_and_indices() {
+        // Given
+        let code = vec![
+            0x03,             // 0: iconst_0
+            0x3c,             // 1: istore_1
+            0x84, 0x01, 0x01, // 2: iinc 1, 1
+            0xa7, 0xff, 0xfd, // 5: goto 2
+            0xb1,             // 8: return
+        ];
+
+        // When
+        let list = InstructionList::new(&code).unwrap();
+
+        // Then
+        assert_eq!(5, list.len());
+        assert_eq!(9, list.code_length());
+        let pcs: Vec<usize> = (0..list.len()).map(|index| list.pc(index).unwrap()).collect();
+        assert_eq!(vec![0, 1, 2, 5, 8], pcs);
+        assert_eq!(Some(8), list.end_pc(3));
+        assert_eq!(Some(9), list.end_pc(4));
+        assert_eq!(None, list.end_pc(5));
+        assert_eq!(Some(&Bytecode::goto { branchoffset: -3 }), list.get(3));
+
+        assert_eq!(Some(2), list.index_of(2));
+        assert_eq!(None, list.index_of(3));
+        assert_eq!(Some(2), list.containing(3));
+        assert_eq!(Some(2), list.containing(4));
+        assert_eq!(Some(3), list.containing(7));
+        assert_eq!(None, list.containing(9));
+
+        assert!(list.is_boundary(5));
+        assert!(!list.is_boundary(6));
+        assert!(!list.is_boundary(9));
+    }
+}
@@ -6,6 +6,8 @@ extern crate bitflags;
 extern crate log;
 
 pub mod reader;
+pub mod writer;
+pub mod strip;
 
 mod classfile;
 pub use self::classfile::*;
@@ -19,5 +21,17 @@ mod attr;
 pub use self::attr::*;
 mod bytecode;
 pub use self::bytecode::*;
+mod opcode;
+pub use self::opcode::*;
+mod code_index;
+pub use self::code_index::*;
+mod instruction_list;
+pub use self::instruction_list::*;
+mod insn_list;
+pub use self::insn_list::*;
+mod descriptor;
+pub use self::descriptor::*;
+mod signature;
+pub use self::signature::*;
 mod error;
 pub use self::error::*;
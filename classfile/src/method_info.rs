@@ -1,4 +1,4 @@
-use super::Attributes;
+use super::{Attributes, ConstantPool, MethodDescriptor, MethodSignature, Result};
 
 #[derive(Debug)]
 pub struct MethodInfo {
@@ -8,6 +8,30 @@ pub struct MethodInfo {
     pub attrs: Attributes,
 }
 
+impl MethodInfo {
+    /// Parses the descriptor of this method.
+    ///
+    /// # Panics
+    /// If `descriptor_index` does not point to a `Constant::Utf8` entry in the
+    /// constant pool.
+    pub fn descriptor(&self, cp: &ConstantPool) -> Result<MethodDescriptor> {
+        MethodDescriptor::parse(cp[self.descriptor_index].as_utf8())
+    }
+
+    /// The number of local variable slots taken by the arguments of this
+    /// method, including `this` for instance methods.
+    pub fn arg_slots(&self, cp: &ConstantPool) -> Result<u16> {
+        let descriptor = try!(self.descriptor(cp));
+        Ok(descriptor.arg_slots(self.access_flags.is_static()))
+    }
+
+    /// Parses the generic signature of this method, if it has a `Signature`
+    /// attribute.
+    pub fn signature(&self, cp: &ConstantPool) -> Option<Result<MethodSignature>> {
+        self.attrs.signature(cp).map(|signature| MethodSignature::parse(signature))
+    }
+}
+
 bitflags! {
     pub flags MethodAccessFlags: u16 {
         const METHOD_ACC_PUBLIC        = 0x0001,
@@ -0,0 +1,862 @@
+//! Static metadata about each opcode: its mnemonic, encoded length, operand
+//! stack effect and properties.
+use super::{Bytecode, Constant, ConstantPool, Error, MethodDescriptor, FieldType, Result};
+
+/// How the encoded length of an instruction is determined.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InstructionLength {
+    /// The instruction, including its opcode, is always this many bytes.
+    Fixed(u8),
+    /// Padding to a 4-byte boundary followed by a default offset, bounds and
+    /// one offset per case.
+    TableSwitch,
+    /// Padding to a 4-byte boundary followed by a default offset, a count and
+    /// one key/offset pair per case.
+    LookupSwitch,
+    /// Determined by the instruction that `wide` modifies.
+    Wide,
+}
+
+/// The number of operand stack slots an instruction pops and then pushes.
+/// `long` and `double` values take two slots.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StackEffect {
+    pub pop: u16,
+    pub push: u16,
+}
+
+impl StackEffect {
+    /// The net change in stack depth.
+    pub fn delta(&self) -> i32 {
+        self.push as i32 - self.pop as i32
+    }
+}
+
+/// The kind of constant that an instruction's constant pool operand must refer
+/// to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConstantKind {
+    Class,
+    Fieldref,
+    Methodref,
+    InterfaceMethodref,
+    /// A `Methodref`, or an `InterfaceMethodref` in class files of version 52
+    /// and above (`invokespecial` and `invokestatic`).
+    AnyMethodref,
+    /// An `Integer`, `Float`, `String`, `Class`, `MethodType` or
+    /// `MethodHandle` (`ldc` and `ldc_w`).
+    Loadable,
+    /// A `Long` or `Double` (`ldc2_w`).
+    LoadableWide,
+    InvokeDynamic,
+}
+
+impl ConstantKind {
+    /// Returns `true` if `constant` is of this kind. Version restrictions are
+    /// not checked.
+    pub fn matches(&self, constant: &Constant) -> bool {
+        match (*self, constant) {
+            (ConstantKind::Class, &Constant::Class(_)) |
+            (ConstantKind::Fieldref, &Constant::Fieldref(_)) |
+            (ConstantKind::Methodref, &Constant::Methodref(_)) |
+            (ConstantKind::InterfaceMethodref, &Constant::InterfaceMethodref(_)) |
+            (ConstantKind::AnyMethodref, &Constant::Methodref(_)) |
+            (ConstantKind::AnyMethodref, &Constant::InterfaceMethodref(_)) |
+            (ConstantKind::Loadable, &Constant::Integer(_)) |
+            (ConstantKind::Loadable, &Constant::Float(_)) |
+            (ConstantKind::Loadable, &Constant::String(_)) |
+            (ConstantKind::Loadable, &Constant::Class(_)) |
+            (ConstantKind::Loadable, &Constant::MethodType { .. }) |
+            (ConstantKind::Loadable, &Constant::MethodHandle { .. }) |
+            (ConstantKind::LoadableWide, &Constant::Long(_)) |
+            (ConstantKind::LoadableWide, &Constant::Double(_)) |
+            (ConstantKind::InvokeDynamic, &Constant::InvokeDynamic { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+// Shorthands for the property bits used in the tables below.
+const THROWS: u8 = 0x01;
+const BRANCH: u8 = 0x02;
+const INVOKE: u8 = 0x04;
+const FIELD: u8 = 0x08;
+const ARRAY: u8 = 0x10;
+const RETURN: u8 = 0x20;
+const CONSTANT: u8 = 0x40;
+
+bitflags! {
+    pub flags OpcodeProperties: u8 {
+        /// May complete abruptly by throwing an exception, other than a
+        /// `VirtualMachineError`.
+        const OPCODE_CAN_THROW      = THROWS,
+        /// Transfers control to an operand-specified target: conditional and
+        /// unconditional branches, subroutine calls and returns, and switches.
+        const OPCODE_BRANCH         = BRANCH,
+        const OPCODE_INVOKE         = INVOKE,
+        /// Reads or writes a static or instance field.
+        const OPCODE_FIELD_ACCESS   = FIELD,
+        /// Loads or stores an array element.
+        const OPCODE_ARRAY_ACCESS   = ARRAY,
+        /// Returns from the method.
+        const OPCODE_RETURN         = RETURN,
+        /// Has a constant pool index operand.
+        const OPCODE_CONSTANT_POOL  = CONSTANT,
+    }
+}
+
+/// A local variable read or written by an instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LocalAccess {
+    pub index: u16,
+    /// The number of slots accessed: 2 for `long` and `double`, otherwise 1.
+    pub slots: u8,
+    /// Set for loads, `iinc` and `ret`.
+    pub reads: bool,
+    /// Set for stores and `iinc`.
+    pub writes: bool,
+}
+
+/// Metadata about one opcode.
+#[derive(Debug)]
+pub struct OpcodeInfo {
+    mnemonic: &'static str,
+    opcode: u8,
+    length: InstructionLength,
+    stack_effect: Option<StackEffect>,
+    properties: OpcodeProperties,
+    constant_kind: Option<ConstantKind>,
+}
+
+impl OpcodeInfo {
+    pub fn mnemonic(&self) -> &'static str {
+        self.mnemonic
+    }
+
+    pub fn opcode(&self) -> u8 {
+        self.opcode
+    }
+
+    pub fn length(&self) -> InstructionLength {
+        self.length
+    }
+
+    /// The stack effect, or `None` for instructions whose effect depends on
+    /// their operands: field accesses, invocations and `multianewarray`. Use
+    /// `Bytecode::stack_effect` to resolve those.
+    pub fn stack_effect(&self) -> Option<StackEffect> {
+        self.stack_effect
+    }
+
+    pub fn properties(&self) -> OpcodeProperties {
+        self.properties
+    }
+
+    /// The kind of constant the instruction's constant pool operand refers
+    /// to, or `None` if it has no such operand.
+    pub fn constant_kind(&self) -> Option<ConstantKind> {
+        self.constant_kind
+    }
+
+    pub fn can_throw(&self) -> bool {
+        self.properties.contains(OPCODE_CAN_THROW)
+    }
+
+    pub fn is_branch(&self) -> bool {
+        self.properties.contains(OPCODE_BRANCH)
+    }
+
+    pub fn is_invoke(&self) -> bool {
+        self.properties.contains(OPCODE_INVOKE)
+    }
+
+    pub fn is_field_access(&self) -> bool {
+        self.properties.contains(OPCODE_FIELD_ACCESS)
+    }
+
+    pub fn is_array_access(&self) -> bool {
+        self.properties.contains(OPCODE_ARRAY_ACCESS)
+    }
+
+    pub fn is_return(&self) -> bool {
+        self.properties.contains(OPCODE_RETURN)
+    }
+}
+
+/// Returns the metadata for `opcode`, or `None` if it is not defined by the
+/// JVM specification.
+///
+/// # Examples
+/// ```rust
+/// use classfile::{opcode_info, StackEffect};
+///
+/// let ladd = opcode_info(0x61).unwrap();
+/// assert_eq!("ladd", ladd.mnemonic());
+/// assert_eq!(Some(StackEffect { pop: 4, push: 2 }), ladd.stack_effect());
+/// ```
+pub fn opcode_info(opcode: u8) -> Option<&'static OpcodeInfo> {
+    match opcode {
+        0x00...0xca => Some(&OPCODES[opcode as usize]),
+        0xfe => Some(&OPCODES[0xcb]),
+        0xff => Some(&OPCODES[0xcc]),
+        _ => None,
+    }
+}
+
+/// Returns the metadata for the `wide` form of `opcode`, or `None` if it
+/// cannot be modified by `wide`. The mnemonic and stack effect are those of
+/// the modified instruction; the length includes the `wide` prefix.
+pub fn wide_opcode_info(opcode: u8) -> Option<&'static OpcodeInfo> {
+    WIDE_OPCODES.iter().find(|info| info.opcode == opcode)
+}
+
+impl Bytecode {
+    /// The metadata for this instruction. For the `wide_*` variants this is
+    /// the metadata of the wide form; see `wide_opcode_info`.
+    pub fn info(&self) -> &'static OpcodeInfo {
+        let info = match self.wide_opcode() {
+            Some(opcode) => wide_opcode_info(opcode),
+            None => opcode_info(self.opcode()),
+        };
+        info.expect("every bytecode has an opcode in the table")
+    }
+
+    /// The opcode that a `wide_*` variant modifies, or `None` for other
+    /// instructions.
+    pub fn wide_opcode(&self) -> Option<u8> {
+        match *self {
+            Bytecode::wide_iload { .. } => Some(0x15),
+            Bytecode::wide_lload { .. } => Some(0x16),
+            Bytecode::wide_fload { .. } => Some(0x17),
+            Bytecode::wide_dload { .. } => Some(0x18),
+            Bytecode::wide_aload { .. } => Some(0x19),
+            Bytecode::wide_istore { .. } => Some(0x36),
+            Bytecode::wide_lstore { .. } => Some(0x37),
+            Bytecode::wide_fstore { .. } => Some(0x38),
+            Bytecode::wide_dstore { .. } => Some(0x39),
+            Bytecode::wide_astore { .. } => Some(0x3a),
+            Bytecode::wide_iinc { .. } => Some(0x84),
+            Bytecode::wide_ret { .. } => Some(0xa9),
+            _ => None,
+        }
+    }
+
+    /// The constant pool index operand, if the instruction has one.
+    pub fn constant_index(&self) -> Option<u16> {
+        match *self {
+            Bytecode::ldc { index } => Some(index as u16),
+            Bytecode::ldc_w { index } |
+            Bytecode::ldc2_w { index } |
+            Bytecode::getstatic { index } |
+            Bytecode::putstatic { index } |
+            Bytecode::getfield { index } |
+            Bytecode::putfield { index } |
+            Bytecode::invokevirtual { index } |
+            Bytecode::invokespecial { index } |
+            Bytecode::invokestatic { index } |
+            Bytecode::invokeinterface { index, .. } |
+            Bytecode::invokedynamic { index } |
+            Bytecode::new { index } |
+            Bytecode::anewarray { index } |
+            Bytecode::checkcast { index } |
+            Bytecode::instanceof { index } |
+            Bytecode::multianewarray { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The local variable this instruction loads, stores, increments or
+    /// returns through, or `None` if it accesses no local variable.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use classfile::Bytecode;
+    ///
+    /// let access = Bytecode::dstore_n(2).local_access().unwrap();
+    /// assert_eq!((2, 2, false, true), (access.index, access.slots, access.reads, access.writes));
+    /// ```
+    pub fn local_access(&self) -> Option<LocalAccess> {
+        let (index, slots, reads, writes) = match *self {
+            Bytecode::iload { index } |
+            Bytecode::fload { index } |
+            Bytecode::aload { index } => (index as u16, 1, true, false),
+            Bytecode::lload { index } => (index as u16, 2, true, false),
+            Bytecode::dload { byte } => (byte as u16, 2, true, false),
+            Bytecode::iload_n(n) |
+            Bytecode::fload_n(n) |
+            Bytecode::aload_n(n) => (n as u16, 1, true, false),
+            Bytecode::lload_n(n) |
+            Bytecode::dload_n(n) => (n as u16, 2, true, false),
+            Bytecode::wide_iload { index } |
+            Bytecode::wide_fload { index } |
+            Bytecode::wide_aload { index } => (index, 1, true, false),
+            Bytecode::wide_lload { index } |
+            Bytecode::wide_dload { index } => (index, 2, true, false),
+            Bytecode::istore { index } |
+            Bytecode::fstore { index } |
+            Bytecode::astore { index } => (index as u16, 1, false, true),
+            Bytecode::lstore { index } |
+            Bytecode::dstore { index } => (index as u16, 2, false, true),
+            Bytecode::istore_n(n) |
+            Bytecode::fstore_n(n) |
+            Bytecode::astore_n(n) => (n as u16, 1, false, true),
+            Bytecode::lstore_n(n) |
+            Bytecode::dstore_n(n) => (n as u16, 2, false, true),
+            Bytecode::wide_istore { index } |
+            Bytecode::wide_fstore { index } |
+            Bytecode::wide_astore { index } => (index, 1, false, true),
+            Bytecode::wide_lstore { index } |
+            Bytecode::wide_dstore { index } => (index, 2, false, true),
+            Bytecode::iinc { index, .. } => (index as u16, 1, true, true),
+            Bytecode::wide_iinc { index, .. } => (index, 1, true, true),
+            Bytecode::ret { index } => (index as u16, 1, true, false),
+            Bytecode::wide_ret { index } => (index, 1, true, false),
+            _ => return None,
+        };
+        Some(LocalAccess {
+            index: index,
+            slots: slots,
+            reads: reads,
+            writes: writes,
+        })
+    }
+
+    /// The operand stack effect of this instruction. Field accesses and
+    /// invocations are resolved against the descriptor they refer to in `cp`.
+    ///
+    /// Fails if the constant pool operand does not refer to a constant of the
+    /// expected kind, or its descriptor is malformed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use classfile::{Bytecode, ConstantPool, StackEffect};
+    ///
+    /// let cp = ConstantPool::new(vec![]);
+    /// let effect = Bytecode::multianewarray { index: 1, dimensions: 3 }.stack_effect(&cp);
+    /// assert_eq!(StackEffect { pop: 3, push: 1 }, effect.unwrap());
+    /// ```
+    pub fn stack_effect(&self, cp: &ConstantPool) -> Result<StackEffect> {
+        if let Some(effect) = self.info().stack_effect() {
+            return Ok(effect);
+        }
+        let effect = match *self {
+            Bytecode::multianewarray { dimensions, .. } => {
+                StackEffect {
+                    pop: dimensions as u16,
+                    push: 1,
+                }
+            }
+            Bytecode::getstatic { index } => {
+                StackEffect {
+                    pop: 0,
+                    push: try!(field_type(cp, index)).slots(),
+                }
+            }
+            Bytecode::putstatic { index } => {
+                StackEffect {
+                    pop: try!(field_type(cp, index)).slots(),
+                    push: 0,
+                }
+            }
+            Bytecode::getfield { index } => {
+                StackEffect {
+                    pop: 1,
+                    push: try!(field_type(cp, index)).slots(),
+                }
+            }
+            Bytecode::putfield { index } => {
+                StackEffect {
+                    pop: 1 + try!(field_type(cp, index)).slots(),
+                    push: 0,
+                }
+            }
+            Bytecode::invokevirtual { index } |
+            Bytecode::invokespecial { index } |
+            Bytecode::invokeinterface { index, .. } => {
+                let descriptor = try!(method_descriptor(cp, index, self.info()));
+                StackEffect {
+                    pop: descriptor.arg_slots(false),
+                    push: descriptor.ret_slots(),
+                }
+            }
+            Bytecode::invokestatic { index } |
+            Bytecode::invokedynamic { index } => {
+                let descriptor = try!(method_descriptor(cp, index, self.info()));
+                StackEffect {
+                    pop: descriptor.arg_slots(true),
+                    push: descriptor.ret_slots(),
+                }
+            }
+            _ => unreachable!(),
+        };
+        Ok(effect)
+    }
+
+    /// Resolves the type of the field accessed by a `getstatic`, `putstatic`,
+    /// `getfield` or `putfield`, or returns `None` for other instructions.
+    pub fn field_type(&self, cp: &ConstantPool) -> Option<Result<FieldType>> {
+        if self.info().is_field_access() {
+            self.constant_index().map(|index| field_type(cp, index))
+        } else {
+            None
+        }
+    }
+
+    /// Resolves the descriptor of the method an invoke instruction calls, or
+    /// returns `None` for other instructions.
+    pub fn method_descriptor(&self, cp: &ConstantPool) -> Option<Result<MethodDescriptor>> {
+        if self.info().is_invoke() {
+            self.constant_index().map(|index| method_descriptor(cp, index, self.info()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Resolves the descriptor of the `NameAndType` at `index`.
+fn name_and_type_descriptor(cp: &ConstantPool, index: u16) -> Result<&String> {
+    let descriptor_index = match cp.get(index) {
+        Some(&Constant::NameAndType(ref name_and_type)) => name_and_type.descriptor_index,
+        _ => return Err(Error::InvalidConstantReference(index)),
+    };
+    match cp.get(descriptor_index) {
+        Some(&Constant::Utf8(ref descriptor)) => Ok(descriptor),
+        _ => Err(Error::InvalidConstantReference(descriptor_index)),
+    }
+}
+
+fn field_type(cp: &ConstantPool, index: u16) -> Result<FieldType> {
+    let name_and_type_index = match cp.get(index) {
+        Some(&Constant::Fieldref(ref entity)) => entity.name_and_type_index,
+        _ => return Err(Error::InvalidConstantReference(index)),
+    };
+    FieldType::parse(try!(name_and_type_descriptor(cp, name_and_type_index)))
+}
+
+fn method_descriptor(cp: &ConstantPool, index: u16, info: &OpcodeInfo) -> Result<MethodDescriptor> {
+    let kind = info.constant_kind().unwrap();
+    let name_and_type_index = match cp.get(index) {
+        Some(constant) if kind.matches(constant) => {
+            match *constant {
+                Constant::Methodref(ref entity) |
+                Constant::InterfaceMethodref(ref entity) => entity.name_and_type_index,
+                Constant::InvokeDynamic { name_and_type_index, .. } => name_and_type_index,
+                _ => unreachable!(),
+            }
+        }
+        _ => return Err(Error::InvalidConstantReference(index)),
+    };
+    MethodDescriptor::parse(try!(name_and_type_descriptor(cp, name_and_type_index)))
+}
+
+macro_rules! opcode {
+    ($mnemonic:expr, $opcode:expr, $length:expr, dynamic, $properties:expr, $constant:expr) => {
+        OpcodeInfo {
+            mnemonic: $mnemonic,
+            opcode: $opcode,
+            length: $length,
+            stack_effect: None,
+            properties: OpcodeProperties { bits: $properties },
+            constant_kind: $constant,
+        }
+    };
+    ($mnemonic:expr, $opcode:expr, $length:expr, [$pop:expr, $push:expr], $properties:expr,
+     $constant:expr) => {
+        OpcodeInfo {
+            mnemonic: $mnemonic,
+            opcode: $opcode,
+            length: $length,
+            stack_effect: Some(StackEffect { pop: $pop, push: $push }),
+            properties: OpcodeProperties { bits: $properties },
+            constant_kind: $constant,
+        }
+    };
+}
+
+use self::InstructionLength::*;
+use self::ConstantKind::*;
+
+/// Opcodes `0x00` through `0xca`, followed by `0xfe` and `0xff`.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+static OPCODES: [OpcodeInfo; 0xcd] = [
+    opcode!("nop", 0x00, Fixed(1), [0, 0], 0, None),
+    opcode!("aconst_null", 0x01, Fixed(1), [0, 1], 0, None),
+    opcode!("iconst_m1", 0x02, Fixed(1), [0, 1], 0, None),
+    opcode!("iconst_0", 0x03, Fixed(1), [0, 1], 0, None),
+    opcode!("iconst_1", 0x04, Fixed(1), [0, 1], 0, None),
+    opcode!("iconst_2", 0x05, Fixed(1), [0, 1], 0, None),
+    opcode!("iconst_3", 0x06, Fixed(1), [0, 1], 0, None),
+    opcode!("iconst_4", 0x07, Fixed(1), [0, 1], 0, None),
+    opcode!("iconst_5", 0x08, Fixed(1), [0, 1], 0, None),
+    opcode!("lconst_0", 0x09, Fixed(1), [0, 2], 0, None),
+    opcode!("lconst_1", 0x0a, Fixed(1), [0, 2], 0, None),
+    opcode!("fconst_0", 0x0b, Fixed(1), [0, 1], 0, None),
+    opcode!("fconst_1", 0x0c, Fixed(1), [0, 1], 0, None),
+    opcode!("fconst_2", 0x0d, Fixed(1), [0, 1], 0, None),
+    opcode!("dconst_0", 0x0e, Fixed(1), [0, 2], 0, None),
+    opcode!("dconst_1", 0x0f, Fixed(1), [0, 2], 0, None),
+    opcode!("bipush", 0x10, Fixed(2), [0, 1], 0, None),
+    opcode!("sipush", 0x11, Fixed(3), [0, 1], 0, None),
+    opcode!("ldc", 0x12, Fixed(2), [0, 1], THROWS | CONSTANT, Some(Loadable)),
+    opcode!("ldc_w", 0x13, Fixed(3), [0, 1], THROWS | CONSTANT, Some(Loadable)),
+    opcode!("ldc2_w", 0x14, Fixed(3), [0, 2], CONSTANT, Some(LoadableWide)),
+    opcode!("iload", 0x15, Fixed(2), [0, 1], 0, None),
+    opcode!("lload", 0x16, Fixed(2), [0, 2], 0, None),
+    opcode!("fload", 0x17, Fixed(2), [0, 1], 0, None),
+    opcode!("dload", 0x18, Fixed(2), [0, 2], 0, None),
+    opcode!("aload", 0x19, Fixed(2), [0, 1], 0, None),
+    opcode!("iload_0", 0x1a, Fixed(1), [0, 1], 0, None),
+    opcode!("iload_1", 0x1b, Fixed(1), [0, 1], 0, None),
+    opcode!("iload_2", 0x1c, Fixed(1), [0, 1], 0, None),
+    opcode!("iload_3", 0x1d, Fixed(1), [0, 1], 0, None),
+    opcode!("lload_0", 0x1e, Fixed(1), [0, 2], 0, None),
+    opcode!("lload_1", 0x1f, Fixed(1), [0, 2], 0, None),
+    opcode!("lload_2", 0x20, Fixed(1), [0, 2], 0, None),
+    opcode!("lload_3", 0x21, Fixed(1), [0, 2], 0, None),
+    opcode!("fload_0", 0x22, Fixed(1), [0, 1], 0, None),
+    opcode!("fload_1", 0x23, Fixed(1), [0, 1], 0, None),
+    opcode!("fload_2", 0x24, Fixed(1), [0, 1], 0, None),
+    opcode!("fload_3", 0x25, Fixed(1), [0, 1], 0, None),
+    opcode!("dload_0", 0x26, Fixed(1), [0, 2], 0, None),
+    opcode!("dload_1", 0x27, Fixed(1), [0, 2], 0, None),
+    opcode!("dload_2", 0x28, Fixed(1), [0, 2], 0, None),
+    opcode!("dload_3", 0x29, Fixed(1), [0, 2], 0, None),
+    opcode!("aload_0", 0x2a, Fixed(1), [0, 1], 0, None),
+    opcode!("aload_1", 0x2b, Fixed(1), [0, 1], 0, None),
+    opcode!("aload_2", 0x2c, Fixed(1), [0, 1], 0, None),
+    opcode!("aload_3", 0x2d, Fixed(1), [0, 1], 0, None),
+    opcode!("iaload", 0x2e, Fixed(1), [2, 1], THROWS | ARRAY, None),
+    opcode!("laload", 0x2f, Fixed(1), [2, 2], THROWS | ARRAY, None),
+    opcode!("faload", 0x30, Fixed(1), [2, 1], THROWS | ARRAY, None),
+    opcode!("daload", 0x31, Fixed(1), [2, 2], THROWS | ARRAY, None),
+    opcode!("aaload", 0x32, Fixed(1), [2, 1], THROWS | ARRAY, None),
+    opcode!("baload", 0x33, Fixed(1), [2, 1], THROWS | ARRAY, None),
+    opcode!("caload", 0x34, Fixed(1), [2, 1], THROWS | ARRAY, None),
+    opcode!("saload", 0x35, Fixed(1), [2, 1], THROWS | ARRAY, None),
+    opcode!("istore", 0x36, Fixed(2), [1, 0], 0, None),
+    opcode!("lstore", 0x37, Fixed(2), [2, 0], 0, None),
+    opcode!("fstore", 0x38, Fixed(2), [1, 0], 0, None),
+    opcode!("dstore", 0x39, Fixed(2), [2, 0], 0, None),
+    opcode!("astore", 0x3a, Fixed(2), [1, 0], 0, None),
+    opcode!("istore_0", 0x3b, Fixed(1), [1, 0], 0, None),
+    opcode!("istore_1", 0x3c, Fixed(1), [1, 0], 0, None),
+    opcode!("istore_2", 0x3d, Fixed(1), [1, 0], 0, None),
+    opcode!("istore_3", 0x3e, Fixed(1), [1, 0], 0, None),
+    opcode!("lstore_0", 0x3f, Fixed(1), [2, 0], 0, None),
+    opcode!("lstore_1", 0x40, Fixed(1), [2, 0], 0, None),
+    opcode!("lstore_2", 0x41, Fixed(1), [2, 0], 0, None),
+    opcode!("lstore_3", 0x42, Fixed(1), [2, 0], 0, None),
+    opcode!("fstore_0", 0x43, Fixed(1), [1, 0], 0, None),
+    opcode!("fstore_1", 0x44, Fixed(1), [1, 0], 0, None),
+    opcode!("fstore_2", 0x45, Fixed(1), [1, 0], 0, None),
+    opcode!("fstore_3", 0x46, Fixed(1), [1, 0], 0, None),
+    opcode!("dstore_0", 0x47, Fixed(1), [2, 0], 0, None),
+    opcode!("dstore_1", 0x48, Fixed(1), [2, 0], 0, None),
+    opcode!("dstore_2", 0x49, Fixed(1), [2, 0], 0, None),
+    opcode!("dstore_3", 0x4a, Fixed(1), [2, 0], 0, None),
+    opcode!("astore_0", 0x4b, Fixed(1), [1, 0], 0, None),
+    opcode!("astore_1", 0x4c, Fixed(1), [1, 0], 0, None),
+    opcode!("astore_2", 0x4d, Fixed(1), [1, 0], 0, None),
+    opcode!("astore_3", 0x4e, Fixed(1), [1, 0], 0, None),
+    opcode!("iastore", 0x4f, Fixed(1), [3, 0], THROWS | ARRAY, None),
+    opcode!("lastore", 0x50, Fixed(1), [4, 0], THROWS | ARRAY, None),
+    opcode!("fastore", 0x51, Fixed(1), [3, 0], THROWS | ARRAY, None),
+    opcode!("dastore", 0x52, Fixed(1), [4, 0], THROWS | ARRAY, None),
+    opcode!("aastore", 0x53, Fixed(1), [3, 0], THROWS | ARRAY, None),
+    opcode!("bastore", 0x54, Fixed(1), [3, 0], THROWS | ARRAY, None),
+    opcode!("castore", 0x55, Fixed(1), [3, 0], THROWS | ARRAY, None),
+    opcode!("sastore", 0x56, Fixed(1), [3, 0], THROWS | ARRAY, None),
+    opcode!("pop", 0x57, Fixed(1), [1, 0], 0, None),
+    opcode!("pop2", 0x58, Fixed(1), [2, 0], 0, None),
+    opcode!("dup", 0x59, Fixed(1), [1, 2], 0, None),
+    opcode!("dup_x1", 0x5a, Fixed(1), [2, 3], 0, None),
+    opcode!("dup_x2", 0x5b, Fixed(1), [3, 4], 0, None),
+    opcode!("dup2", 0x5c, Fixed(1), [2, 4], 0, None),
+    opcode!("dup2_x1", 0x5d, Fixed(1), [3, 5], 0, None),
+    opcode!("dup2_x2", 0x5e, Fixed(1), [4, 6], 0, None),
+    opcode!("swap", 0x5f, Fixed(1), [2, 2], 0, None),
+    opcode!("iadd", 0x60, Fixed(1), [2, 1], 0, None),
+    opcode!("ladd", 0x61, Fixed(1), [4, 2], 0, None),
+    opcode!("fadd", 0x62, Fixed(1), [2, 1], 0, None),
+    opcode!("dadd", 0x63, Fixed(1), [4, 2], 0, None),
+    opcode!("isub", 0x64, Fixed(1), [2, 1], 0, None),
+    opcode!("lsub", 0x65, Fixed(1), [4, 2], 0, None),
+    opcode!("fsub", 0x66, Fixed(1), [2, 1], 0, None),
+    opcode!("dsub", 0x67, Fixed(1), [4, 2], 0, None),
+    opcode!("imul", 0x68, Fixed(1), [2, 1], 0, None),
+    opcode!("lmul", 0x69, Fixed(1), [4, 2], 0, None),
+    opcode!("fmul", 0x6a, Fixed(1), [2, 1], 0, None),
+    opcode!("dmul", 0x6b, Fixed(1), [4, 2], 0, None),
+    opcode!("idiv", 0x6c, Fixed(1), [2, 1], THROWS, None),
+    opcode!("ldiv", 0x6d, Fixed(1), [4, 2], THROWS, None),
+    opcode!("fdiv", 0x6e, Fixed(1), [2, 1], 0, None),
+    opcode!("ddiv", 0x6f, Fixed(1), [4, 2], 0, None),
+    opcode!("irem", 0x70, Fixed(1), [2, 1], THROWS, None),
+    opcode!("lrem", 0x71, Fixed(1), [4, 2], THROWS, None),
+    opcode!("frem", 0x72, Fixed(1), [2, 1], 0, None),
+    opcode!("drem", 0x73, Fixed(1), [4, 2], 0, None),
+    opcode!("ineg", 0x74, Fixed(1), [1, 1], 0, None),
+    opcode!("lneg", 0x75, Fixed(1), [2, 2], 0, None),
+    opcode!("fneg", 0x76, Fixed(1), [1, 1], 0, None),
+    opcode!("dneg", 0x77, Fixed(1), [2, 2], 0, None),
+    opcode!("ishl", 0x78, Fixed(1), [2, 1], 0, None),
+    opcode!("lshl", 0x79, Fixed(1), [3, 2], 0, None),
+    opcode!("ishr", 0x7a, Fixed(1), [2, 1], 0, None),
+    opcode!("lshr", 0x7b, Fixed(1), [3, 2], 0, None),
+    opcode!("iushr", 0x7c, Fixed(1), [2, 1], 0, None),
+    opcode!("lushr", 0x7d, Fixed(1), [3, 2], 0, None),
+    opcode!("iand", 0x7e, Fixed(1), [2, 1], 0, None),
+    opcode!("land", 0x7f, Fixed(1), [4, 2], 0, None),
+    opcode!("ior", 0x80, Fixed(1), [2, 1], 0, None),
+    opcode!("lor", 0x81, Fixed(1), [4, 2], 0, None),
+    opcode!("ixor", 0x82, Fixed(1), [2, 1], 0, None),
+    opcode!("lxor", 0x83, Fixed(1), [4, 2], 0, None),
+    opcode!("iinc", 0x84, Fixed(3), [0, 0], 0, None),
+    opcode!("i2l", 0x85, Fixed(1), [1, 2], 0, None),
+    opcode!("i2f", 0x86, Fixed(1), [1, 1], 0, None),
+    opcode!("i2d", 0x87, Fixed(1), [1, 2], 0, None),
+    opcode!("l2i", 0x88, Fixed(1), [2, 1], 0, None),
+    opcode!("l2f", 0x89, Fixed(1), [2, 1], 0, None),
+    opcode!("l2d", 0x8a, Fixed(1), [2, 2], 0, None),
+    opcode!("f2i", 0x8b, Fixed(1), [1, 1], 0, None),
+    opcode!("f2l", 0x8c, Fixed(1), [1, 2], 0, None),
+    opcode!("f2d", 0x8d, Fixed(1), [1, 2], 0, None),
+    opcode!("d2i", 0x8e, Fixed(1), [2, 1], 0, None),
+    opcode!("d2l", 0x8f, Fixed(1), [2, 2], 0, None),
+    opcode!("d2f", 0x90, Fixed(1), [2, 1], 0, None),
+    opcode!("i2b", 0x91, Fixed(1), [1, 1], 0, None),
+    opcode!("i2c", 0x92, Fixed(1), [1, 1], 0, None),
+    opcode!("i2s", 0x93, Fixed(1), [1, 1], 0, None),
+    opcode!("lcmp", 0x94, Fixed(1), [4, 1], 0, None),
+    opcode!("fcmpl", 0x95, Fixed(1), [2, 1], 0, None),
+    opcode!("fcmpg", 0x96, Fixed(1), [2, 1], 0, None),
+    opcode!("dcmpl", 0x97, Fixed(1), [4, 1], 0, None),
+    opcode!("dcmpg", 0x98, Fixed(1), [4, 1], 0, None),
+    opcode!("ifeq", 0x99, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("ifne", 0x9a, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("iflt", 0x9b, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("ifge", 0x9c, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("ifgt", 0x9d, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("ifle", 0x9e, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("if_icmpeq", 0x9f, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("if_icmpne", 0xa0, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("if_icmplt", 0xa1, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("if_icmpge", 0xa2, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("if_icmpgt", 0xa3, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("if_icmple", 0xa4, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("if_acmpeq", 0xa5, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("if_acmpne", 0xa6, Fixed(3), [2, 0], BRANCH, None),
+    opcode!("goto", 0xa7, Fixed(3), [0, 0], BRANCH, None),
+    opcode!("jsr", 0xa8, Fixed(3), [0, 1], BRANCH, None),
+    opcode!("ret", 0xa9, Fixed(2), [0, 0], BRANCH, None),
+    opcode!("tableswitch", 0xaa, TableSwitch, [1, 0], BRANCH, None),
+    opcode!("lookupswitch", 0xab, LookupSwitch, [1, 0], BRANCH, None),
+    opcode!("ireturn", 0xac, Fixed(1), [1, 0], THROWS | RETURN, None),
+    opcode!("lreturn", 0xad, Fixed(1), [2, 0], THROWS | RETURN, None),
+    opcode!("freturn", 0xae, Fixed(1), [1, 0], THROWS | RETURN, None),
+    opcode!("dreturn", 0xaf, Fixed(1), [2, 0], THROWS | RETURN, None),
+    opcode!("areturn", 0xb0, Fixed(1), [1, 0], THROWS | RETURN, None),
+    opcode!("return", 0xb1, Fixed(1), [0, 0], THROWS | RETURN, None),
+    opcode!("getstatic", 0xb2, Fixed(3), dynamic, THROWS | FIELD | CONSTANT, Some(Fieldref)),
+    opcode!("putstatic", 0xb3, Fixed(3), dynamic, THROWS | FIELD | CONSTANT, Some(Fieldref)),
+    opcode!("getfield", 0xb4, Fixed(3), dynamic, THROWS | FIELD | CONSTANT, Some(Fieldref)),
+    opcode!("putfield", 0xb5, Fixed(3), dynamic, THROWS | FIELD | CONSTANT, Some(Fieldref)),
+    opcode!("invokevirtual", 0xb6, Fixed(3), dynamic, THROWS | INVOKE | CONSTANT, Some(Methodref)),
+    opcode!("invokespecial", 0xb7, Fixed(3), dynamic, THROWS | INVOKE | CONSTANT, Some(AnyMethodref)),
+    opcode!("invokestatic", 0xb8, Fixed(3), dynamic, THROWS | INVOKE | CONSTANT, Some(AnyMethodref)),
+    opcode!("invokeinterface", 0xb9, Fixed(5), dynamic, THROWS | INVOKE | CONSTANT, Some(InterfaceMethodref)),
+    opcode!("invokedynamic", 0xba, Fixed(5), dynamic, THROWS | INVOKE | CONSTANT, Some(InvokeDynamic)),
+    opcode!("new", 0xbb, Fixed(3), [0, 1], THROWS | CONSTANT, Some(Class)),
+    opcode!("newarray", 0xbc, Fixed(2), [1, 1], THROWS, None),
+    opcode!("anewarray", 0xbd, Fixed(3), [1, 1], THROWS | CONSTANT, Some(Class)),
+    opcode!("arraylength", 0xbe, Fixed(1), [1, 1], THROWS, None),
+    opcode!("athrow", 0xbf, Fixed(1), [1, 0], THROWS, None),
+    opcode!("checkcast", 0xc0, Fixed(3), [1, 1], THROWS | CONSTANT, Some(Class)),
+    opcode!("instanceof", 0xc1, Fixed(3), [1, 1], THROWS | CONSTANT, Some(Class)),
+    opcode!("monitorenter", 0xc2, Fixed(1), [1, 0], THROWS, None),
+    opcode!("monitorexit", 0xc3, Fixed(1), [1, 0], THROWS, None),
+    opcode!("wide", 0xc4, Wide, dynamic, 0, None),
+    opcode!("multianewarray", 0xc5, Fixed(4), dynamic, THROWS | CONSTANT, Some(Class)),
+    opcode!("ifnull", 0xc6, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("ifnonnull", 0xc7, Fixed(3), [1, 0], BRANCH, None),
+    opcode!("goto_w", 0xc8, Fixed(5), [0, 0], BRANCH, None),
+    opcode!("jsr_w", 0xc9, Fixed(5), [0, 1], BRANCH, None),
+    opcode!("breakpoint", 0xca, Fixed(1), [0, 0], 0, None),
+    opcode!("impdep1", 0xfe, Fixed(1), [0, 0], 0, None),
+    opcode!("impdep2", 0xff, Fixed(1), [0, 0], 0, None),
+];
+
+/// The `wide` forms of the instructions that `wide` can modify.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+static WIDE_OPCODES: [OpcodeInfo; 12] = [
+    opcode!("iload", 0x15, Fixed(4), [0, 1], 0, None),
+    opcode!("lload", 0x16, Fixed(4), [0, 2], 0, None),
+    opcode!("fload", 0x17, Fixed(4), [0, 1], 0, None),
+    opcode!("dload", 0x18, Fixed(4), [0, 2], 0, None),
+    opcode!("aload", 0x19, Fixed(4), [0, 1], 0, None),
+    opcode!("istore", 0x36, Fixed(4), [1, 0], 0, None),
+    opcode!("lstore", 0x37, Fixed(4), [2, 0], 0, None),
+    opcode!("fstore", 0x38, Fixed(4), [1, 0], 0, None),
+    opcode!("dstore", 0x39, Fixed(4), [2, 0], 0, None),
+    opcode!("astore", 0x3a, Fixed(4), [1, 0], 0, None),
+    opcode!("iinc", 0x84, Fixed(6), [0, 0], 0, None),
+    opcode!("ret", 0xa9, Fixed(4), [0, 0], BRANCH, None),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Bytecode, Constant, ConstantPool, NameAndTypeConstant, TypedEntityConstant};
+
+    #[test]
+    fn test_table_is_indexed_by_opcode() {
+        for opcode in 0x00..0x100 {
+            let opcode = opcode as u8;
+            if let Some(info) = opcode_info(opcode) {
+                assert_eq!(opcode, info.opcode());
+            }
+        }
+        assert!(opcode_info(0xcb).is_none());
+        assert!(opcode_info(0xfd).is_none());
+        assert_eq!("impdep2", opcode_info(0xff).unwrap().mnemonic());
+    }
+
+    #[test]
+    fn test_table_agrees_with_decode() {
+        for opcode in 0x00..0x100 {
+            let opcode = opcode as u8;
+            let info = match opcode_info(opcode) {
+                Some(info) => info,
+                None => continue,
+            };
+            if let InstructionLength::Fixed(length) = info.length() {
+                // Given
+                let mut code = vec![opcode];
+                code.resize(length as usize, 0);
+
+                // When
+                let result = Bytecode::decode(&code, 0).unwrap();
+
+                // Then
+                assert_eq!(length as usize, result.newpc, "{}", info.mnemonic());
+                assert_eq!(opcode, result.bytecode.opcode());
+                assert_eq!(info.mnemonic(), result.bytecode.info().mnemonic());
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_forms() {
+        // Given
+        let code = vec![0xc4, 0x84, 0x01, 0x00, 0xff, 0xff];
+
+        // When
+        let bytecode = Bytecode::decode(&code, 0).unwrap().bytecode;
+
+        // Then
+        assert_eq!(0xc4, bytecode.opcode());
+        assert_eq!(Some(0x84), bytecode.wide_opcode());
+        assert_eq!("iinc", bytecode.info().mnemonic());
+        assert_eq!(InstructionLength::Fixed(6), bytecode.info().length());
+        assert!(wide_opcode_info(0x60).is_none());
+    }
+
+    #[test]
+    fn test_properties() {
+        let iaload = opcode_info(0x2e).unwrap();
+        assert!(iaload.can_throw() && iaload.is_array_access());
+        assert!(!opcode_info(0x60).unwrap().can_throw());
+        assert!(opcode_info(0x6c).unwrap().can_throw());
+        assert!(opcode_info(0xa7).unwrap().is_branch());
+        assert!(opcode_info(0xb1).unwrap().is_return());
+        assert!(opcode_info(0xb4).unwrap().is_field_access());
+        let invokeinterface = opcode_info(0xb9).unwrap();
+        assert!(invokeinterface.is_invoke());
+        assert_eq!(Some(ConstantKind::InterfaceMethodref), invokeinterface.constant_kind());
+        assert!(invokeinterface.properties().contains(OPCODE_CONSTANT_POOL));
+    }
+
+    fn pool() -> ConstantPool {
+        ConstantPool::new(vec![
+            Constant::Utf8("(JLjava/lang/String;)D".to_string()),  // #1
+            Constant::Utf8("J".to_string()),                        // #2
+            Constant::NameAndType(NameAndTypeConstant {             // #3
+                name_index: 2,
+                descriptor_index: 1,
+            }),
+            Constant::NameAndType(NameAndTypeConstant {             // #4
+                name_index: 2,
+                descriptor_index: 2,
+            }),
+            Constant::Methodref(TypedEntityConstant {               // #5
+                class_index: 0,
+                name_and_type_index: 3,
+            }),
+            Constant::Fieldref(TypedEntityConstant {                // #6
+                class_index: 0,
+                name_and_type_index: 4,
+            }),
+            Constant::InvokeDynamic {                               // #7
+                bootstrap_method_attr_index: 0,
+                name_and_type_index: 3,
+            },
+        ])
+    }
+
+    #[test]
+    fn test_stack_effect_of_fixed_instructions() {
+        let cp = ConstantPool::new(vec![]);
+        assert_eq!(StackEffect { pop: 4, push: 6 }, Bytecode::dup2_x2.stack_effect(&cp).unwrap());
+        assert_eq!(StackEffect { pop: 3, push: 2 }, Bytecode::lshl.stack_effect(&cp).unwrap());
+        assert_eq!(-2, Bytecode::lreturn.stack_effect(&cp).unwrap().delta());
+    }
+
+    #[test]
+    fn test_stack_effect_resolves_descriptors() {
+        let cp = pool();
+        let effect = |bytecode: Bytecode| bytecode.stack_effect(&cp).unwrap();
+        assert_eq!(StackEffect { pop: 4, push: 2 }, effect(Bytecode::invokevirtual { index: 5 }));
+        assert_eq!(StackEffect { pop: 3, push: 2 }, effect(Bytecode::invokestatic { index: 5 }));
+        assert_eq!(StackEffect { pop: 3, push: 2 }, effect(Bytecode::invokedynamic { index: 7 }));
+        assert_eq!(StackEffect { pop: 0, push: 2 }, effect(Bytecode::getstatic { index: 6 }));
+        assert_eq!(StackEffect { pop: 3, push: 0 }, effect(Bytecode::putfield { index: 6 }));
+    }
+
+    #[test]
+    fn test_stack_effect_rejects_wrong_constant_kind() {
+        let cp = pool();
+        for &(ref bytecode, index) in [(Bytecode::getfield { index: 5 }, 5),
+                                       (Bytecode::invokeinterface { index: 5, count: 1 }, 5),
+                                       (Bytecode::invokevirtual { index: 6 }, 6),
+                                       (Bytecode::invokestatic { index: 0 }, 0),
+                                       (Bytecode::invokestatic { index: 99 }, 99)]
+            .iter() {
+            match bytecode.stack_effect(&cp) {
+                Err(Error::InvalidConstantReference(i)) => assert_eq!(index, i),
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolves_referenced_types() {
+        let cp = pool();
+        assert_eq!(FieldType::Long,
+                   Bytecode::getfield { index: 6 }.field_type(&cp).unwrap().unwrap());
+        let descriptor = Bytecode::invokestatic { index: 5 }.method_descriptor(&cp).unwrap();
+        assert_eq!(Some(FieldType::Double), descriptor.unwrap().ret);
+        assert!(Bytecode::invokestatic { index: 5 }.field_type(&cp).is_none());
+        assert!(Bytecode::nop.method_descriptor(&cp).is_none());
+    }
+
+    #[test]
+    fn test_local_access() {
+        let load = Bytecode::wide_lload { index: 300 }.local_access().unwrap();
+        assert_eq!((300, 2, true, false), (load.index, load.slots, load.reads, load.writes));
+        let increment = Bytecode::iinc { index: 4, constant: 1 }.local_access().unwrap();
+        assert_eq!((4, 1, true, true),
+                   (increment.index, increment.slots, increment.reads, increment.writes));
+        assert_eq!(1, Bytecode::ret { index: 1 }.local_access().unwrap().index);
+        assert!(Bytecode::iadd.local_access().is_none());
+    }
+}
@@ -417,13 +417,16 @@ impl<T: io::Read> ClassReader<T> {
                     Ok(AttributeInfo::RuntimeInvisibleParameterAnnotations(annotations))
                 }
                 attr_name => {
-                    println!("UNKNOWN ATTRIBUTE {}", attr_name);
+                    debug!("Unknown attribute {}", attr_name);
                     let mut info: Vec<u8> = vec![];
                     for _ in 0..attribute_length {
                         let byte = try!(self.read_u8());
                         info.push(byte);
                     }
-                    Ok(AttributeInfo::Raw(Box::new(info)))
+                    Ok(AttributeInfo::Raw(RawAttribute {
+                        name_index: name_index,
+                        info: info,
+                    }))
                 }
             }
         } else {
@@ -618,7 +621,7 @@ impl<T: io::Read> ClassReader<T> {
     }
 
     fn read_type_annotations(&mut self) -> Result<Vec<TypeAnnotation>> {
-        let num_annotations = try!(self.read_u8());
+        let num_annotations = try!(self.read_u16());
         let mut annotations: Vec<TypeAnnotation> = vec![];
         for _ in 0..num_annotations {
             let annotation = try!(self.read_type_annotation());
@@ -628,7 +631,8 @@ impl<T: io::Read> ClassReader<T> {
     }
 
     fn read_type_annotation(&mut self) -> Result<TypeAnnotation> {
-        let target_info = try!(self.read_target_info());
+        let target_type = try!(self.read_u8());
+        let target_info = try!(self.read_target_info(target_type));
         let target_path = try!(self.read_type_path());
         let type_index = try!(self.read_u16());
         let num_element_value_pairs = try!(self.read_u16());
@@ -638,6 +642,7 @@ impl<T: io::Read> ClassReader<T> {
             element_value_pairs.push(element_value_pair);
         }
         Ok(TypeAnnotation {
+            target_type: target_type,
             target_info: target_info,
             target_path: target_path,
             type_index: type_index,
@@ -645,15 +650,14 @@ impl<T: io::Read> ClassReader<T> {
         })
     }
 
-    fn read_target_info(&mut self) -> Result<TargetInfo> {
-        let target_type = try!(self.read_u8());
+    fn read_target_info(&mut self, target_type: u8) -> Result<TargetInfo> {
         match target_type {
             0x00...0x01 => {
                 let type_parameter_index = try!(self.read_u8());
                 Ok(TargetInfo::TypeParameter(type_parameter_index))
             }
             0x10 => {
-                let supertype_index = try!(self.read_u8());
+                let supertype_index = try!(self.read_u16());
                 Ok(TargetInfo::Supertype(supertype_index))
             }
             0x11...0x12 => {
@@ -670,7 +674,7 @@ impl<T: io::Read> ClassReader<T> {
                 Ok(TargetInfo::MethodFormalParameter(formal_parameter_index))
             }
             0x17 => {
-                let throws_type_index = try!(self.read_u8());
+                let throws_type_index = try!(self.read_u16());
                 Ok(TargetInfo::Throws(throws_type_index))
             }
             0x40...0x41 => {
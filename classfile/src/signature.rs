@@ -0,0 +1,494 @@
+//! Parsing of generic [signatures]
+//! (https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.9.1)
+//! as found in `Signature` attributes.
+use super::{Error, FieldType, Result};
+
+/// The most dimensions an array type may have.
+const MAX_DIMENSIONS: usize = 255;
+
+/// The most type argument lists that may be nested in one another, which
+/// bounds the recursion of the parser.
+const MAX_TYPE_ARGUMENT_DEPTH: usize = 255;
+
+/// A Java type as it appears in a signature.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TypeSignature {
+    /// A primitive type. The value is never `FieldType::Object` or
+    /// `FieldType::Array`.
+    Base(FieldType),
+    Class(ClassTypeSignature),
+    /// A reference to a type variable, for example `T`.
+    TypeVariable(String),
+    /// An array with the given component type.
+    Array(Box<TypeSignature>),
+}
+
+impl TypeSignature {
+    /// Formats this type as it would appear in Java source, for example
+    /// `java.util.List<? extends T>[]`.
+    pub fn java_name(&self) -> String {
+        match *self {
+            TypeSignature::Base(ref base) => base.java_name(),
+            TypeSignature::Class(ref class) => class.java_name(),
+            TypeSignature::TypeVariable(ref name) => name.clone(),
+            TypeSignature::Array(ref component) => format!("{}[]", component.java_name()),
+        }
+    }
+}
+
+/// A possibly parameterized class type, such as `java/util/Map<TK;TV;>.Entry`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ClassTypeSignature {
+    /// The package of the outermost class, in internal form with a trailing
+    /// `/` (for example `java/util/`), or empty for the unnamed package.
+    pub package: String,
+    /// The outermost class followed by any nested classes, each with its own
+    /// type arguments.
+    pub classes: Vec<SimpleClassTypeSignature>,
+}
+
+impl ClassTypeSignature {
+    /// The binary name of the class in internal form, for example
+    /// `java/util/Map$Entry`.
+    pub fn binary_name(&self) -> String {
+        let names: Vec<&str> = self.classes.iter().map(|class| class.name.as_ref()).collect();
+        format!("{}{}", self.package, names.join("$"))
+    }
+
+    pub fn java_name(&self) -> String {
+        let classes: Vec<String> = self.classes
+            .iter()
+            .map(|class| {
+                if class.type_arguments.is_empty() {
+                    return class.name.clone();
+                }
+                let arguments: Vec<String> = class.type_arguments
+                    .iter()
+                    .map(|argument| argument.java_name())
+                    .collect();
+                format!("{}<{}>", class.name, arguments.join(", "))
+            })
+            .collect();
+        format!("{}{}", self.package.replace("/", "."), classes.join("."))
+    }
+
+    /// Returns `true` if this is exactly `java.lang.Object`.
+    fn is_object(&self) -> bool {
+        self.package == "java/lang/" && self.classes.len() == 1 &&
+        self.classes[0].name == "Object" && self.classes[0].type_arguments.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+/// An argument to a parameterized type.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TypeArgument {
+    /// `?`
+    Any,
+    /// `? extends T`
+    Extends(TypeSignature),
+    /// `? super T`
+    Super(TypeSignature),
+    /// `T`
+    Exact(TypeSignature),
+}
+
+impl TypeArgument {
+    pub fn java_name(&self) -> String {
+        match *self {
+            TypeArgument::Any => "?".to_string(),
+            TypeArgument::Extends(ref bound) => format!("? extends {}", bound.java_name()),
+            TypeArgument::Super(ref bound) => format!("? super {}", bound.java_name()),
+            TypeArgument::Exact(ref argument) => argument.java_name(),
+        }
+    }
+}
+
+/// A type parameter declared by a generic class or method.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TypeParameter {
+    pub name: String,
+    /// The class bound. This is `None` when the only bounds are interfaces.
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+impl TypeParameter {
+    /// Formats this parameter as it would appear in Java source, for example
+    /// `T extends java.lang.Comparable<T>`. A lone bound of `java.lang.Object`
+    /// is omitted.
+    pub fn java_name(&self) -> String {
+        let mut bounds: Vec<String> = vec![];
+        match self.class_bound {
+            Some(TypeSignature::Class(ref class)) if class.is_object() &&
+                                                     self.interface_bounds.is_empty() => {}
+            Some(ref bound) => bounds.push(bound.java_name()),
+            None => {}
+        }
+        bounds.extend(self.interface_bounds.iter().map(|bound| bound.java_name()));
+        if bounds.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} extends {}", self.name, bounds.join(" & "))
+        }
+    }
+}
+
+fn type_parameters_java_name(parameters: &[TypeParameter]) -> String {
+    if parameters.is_empty() {
+        return String::new();
+    }
+    let parameters: Vec<String> = parameters.iter().map(|p| p.java_name()).collect();
+    format!("<{}>", parameters.join(", "))
+}
+
+/// The signature of a generic class or interface.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+impl ClassSignature {
+    /// Parses a class signature such as
+    /// `<T:Ljava/lang/Object;>Ljava/lang/Object;Ljava/lang/Iterable<TT;>;`.
+    pub fn parse(signature: &str) -> Result<ClassSignature> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = try!(parser.type_parameters());
+        let super_class = try!(parser.class_type());
+        let mut interfaces = vec![];
+        while !parser.at_end() {
+            interfaces.push(try!(parser.class_type()));
+        }
+        Ok(ClassSignature {
+            type_parameters: type_parameters,
+            super_class: super_class,
+            interfaces: interfaces,
+        })
+    }
+
+    /// Formats the type parameters and supertypes of a class named `name` as
+    /// they would appear in Java source, for example
+    /// `Box<T> implements java.lang.Iterable<T>`.
+    ///
+    /// An interface `extends` its superinterfaces. As in javap, an implicit
+    /// `extends java.lang.Object` is left out.
+    pub fn java_declaration(&self, name: &str, is_interface: bool) -> String {
+        let mut declaration = format!("{}{}",
+                                      name,
+                                      type_parameters_java_name(&self.type_parameters));
+        let interfaces: Vec<String> = self.interfaces
+            .iter()
+            .map(|interface| interface.java_name())
+            .collect();
+        if is_interface {
+            if !interfaces.is_empty() {
+                declaration.push_str(&format!(" extends {}", interfaces.join(", ")));
+            }
+            return declaration;
+        }
+        if self.super_class.binary_name() != "java/lang/Object" {
+            declaration.push_str(&format!(" extends {}", self.super_class.java_name()));
+        }
+        if !interfaces.is_empty() {
+            declaration.push_str(&format!(" implements {}", interfaces.join(", ")));
+        }
+        declaration
+    }
+}
+
+/// The signature of a generic method or constructor.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub params: Vec<TypeSignature>,
+    /// The return type, or `None` for `void` methods.
+    pub ret: Option<TypeSignature>,
+    /// Class types or type variables in the `throws` clause.
+    pub throws: Vec<TypeSignature>,
+}
+
+impl MethodSignature {
+    /// Parses a method signature such as
+    /// `<T:Ljava/lang/Object;>(TT;)Ljava/util/List<TT;>;^Ljava/io/IOException;`.
+    pub fn parse(signature: &str) -> Result<MethodSignature> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = try!(parser.type_parameters());
+        try!(parser.expect('('));
+        let mut params = vec![];
+        while !parser.eat(')') {
+            params.push(try!(parser.java_type()));
+        }
+        let ret = if parser.eat('V') {
+            None
+        } else {
+            Some(try!(parser.java_type()))
+        };
+        let mut throws = vec![];
+        while parser.eat('^') {
+            let thrown = match parser.peek() {
+                Some('T') => try!(parser.reference_type()),
+                _ => TypeSignature::Class(try!(parser.class_type())),
+            };
+            throws.push(thrown);
+        }
+        try!(parser.finish());
+        Ok(MethodSignature {
+            type_parameters: type_parameters,
+            params: params,
+            ret: ret,
+            throws: throws,
+        })
+    }
+
+    /// Formats a method named `name` with this signature as it would appear
+    /// in Java source, for example
+    /// `<T> java.util.List<T> wrap(T) throws java.io.IOException`.
+    ///
+    /// Constructors and static initializers should be passed their source
+    /// names, and are formatted without a return type.
+    pub fn java_declaration(&self, name: &str, is_constructor: bool) -> String {
+        let mut declaration = type_parameters_java_name(&self.type_parameters);
+        if !declaration.is_empty() {
+            declaration.push(' ');
+        }
+        if !is_constructor {
+            match self.ret {
+                Some(ref ret) => declaration.push_str(&ret.java_name()),
+                None => declaration.push_str("void"),
+            }
+            declaration.push(' ');
+        }
+        let params: Vec<String> = self.params.iter().map(|param| param.java_name()).collect();
+        declaration.push_str(&format!("{}({})", name, params.join(", ")));
+        if !self.throws.is_empty() {
+            let throws: Vec<String> = self.throws.iter().map(|t| t.java_name()).collect();
+            declaration.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+        declaration
+    }
+}
+
+/// The signature of a field, parameter or local variable whose type is
+/// generic.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FieldSignature {
+    /// A class type, type variable or array type.
+    pub field_type: TypeSignature,
+}
+
+impl FieldSignature {
+    /// Parses a field signature such as `Ljava/util/List<TT;>;`.
+    pub fn parse(signature: &str) -> Result<FieldSignature> {
+        let mut parser = Parser::new(signature);
+        let field_type = try!(parser.reference_type());
+        try!(parser.finish());
+        Ok(FieldSignature { field_type: field_type })
+    }
+}
+
+struct Parser<'a> {
+    signature: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+    /// The number of type argument lists being parsed.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(signature: &'a str) -> Parser<'a> {
+        Parser {
+            signature: signature,
+            chars: signature.chars().collect(),
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn error(&self) -> Error {
+        Error::InvalidSignature(self.signature.to_string())
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Result<char> {
+        match self.peek() {
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+            None => Err(self.error()),
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) { Ok(()) } else { Err(self.error()) }
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.at_end() { Ok(()) } else { Err(self.error()) }
+    }
+
+    /// Parses an identifier, which ends at any of `. ; [ / < > :`.
+    fn identifier(&mut self) -> Result<String> {
+        let mut identifier = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '.' | ';' | '[' | '/' | '<' | '>' | ':' => break,
+                _ => identifier.push(c),
+            }
+            self.pos += 1;
+        }
+        if identifier.is_empty() {
+            return Err(self.error());
+        }
+        Ok(identifier)
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>> {
+        let mut parameters = vec![];
+        if !self.eat('<') {
+            return Ok(parameters);
+        }
+        loop {
+            let name = try!(self.identifier());
+            try!(self.expect(':'));
+            let class_bound = match self.peek() {
+                Some(':') | Some('>') => None,
+                _ => Some(try!(self.reference_type())),
+            };
+            let mut interface_bounds = vec![];
+            while self.eat(':') {
+                interface_bounds.push(try!(self.reference_type()));
+            }
+            parameters.push(TypeParameter {
+                name: name,
+                class_bound: class_bound,
+                interface_bounds: interface_bounds,
+            });
+            if self.eat('>') {
+                return Ok(parameters);
+            }
+        }
+    }
+
+    fn java_type(&mut self) -> Result<TypeSignature> {
+        let base = match self.peek() {
+            Some('B') => FieldType::Byte,
+            Some('C') => FieldType::Char,
+            Some('D') => FieldType::Double,
+            Some('F') => FieldType::Float,
+            Some('I') => FieldType::Int,
+            Some('J') => FieldType::Long,
+            Some('S') => FieldType::Short,
+            Some('Z') => FieldType::Boolean,
+            _ => return self.reference_type(),
+        };
+        self.pos += 1;
+        Ok(TypeSignature::Base(base))
+    }
+
+    fn reference_type(&mut self) -> Result<TypeSignature> {
+        match self.peek() {
+            Some('L') => Ok(TypeSignature::Class(try!(self.class_type()))),
+            Some('T') => {
+                self.pos += 1;
+                let name = try!(self.identifier());
+                try!(self.expect(';'));
+                Ok(TypeSignature::TypeVariable(name))
+            }
+            Some('[') => {
+                let mut dimensions = 0;
+                while self.eat('[') {
+                    dimensions += 1;
+                    if dimensions > MAX_DIMENSIONS {
+                        return Err(self.error());
+                    }
+                }
+                let mut array = try!(self.java_type());
+                for _ in 0..dimensions {
+                    array = TypeSignature::Array(Box::new(array));
+                }
+                Ok(array)
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature> {
+        try!(self.expect('L'));
+        let mut package = String::new();
+        let mut name = try!(self.identifier());
+        while self.eat('/') {
+            package.push_str(&name);
+            package.push('/');
+            name = try!(self.identifier());
+        }
+        let mut classes = vec![];
+        loop {
+            let type_arguments = try!(self.type_arguments());
+            classes.push(SimpleClassTypeSignature {
+                name: name,
+                type_arguments: type_arguments,
+            });
+            if !self.eat('.') {
+                break;
+            }
+            name = try!(self.identifier());
+        }
+        try!(self.expect(';'));
+        Ok(ClassTypeSignature {
+            package: package,
+            classes: classes,
+        })
+    }
+
+    fn type_arguments(&mut self) -> Result<Vec<TypeArgument>> {
+        let mut arguments = vec![];
+        if !self.eat('<') {
+            return Ok(arguments);
+        }
+        if self.depth == MAX_TYPE_ARGUMENT_DEPTH {
+            return Err(self.error());
+        }
+        self.depth += 1;
+        while !self.eat('>') {
+            let argument = match try!(self.next()) {
+                '*' => TypeArgument::Any,
+                '+' => TypeArgument::Extends(try!(self.reference_type())),
+                '-' => TypeArgument::Super(try!(self.reference_type())),
+                _ => {
+                    self.pos -= 1;
+                    TypeArgument::Exact(try!(self.reference_type()))
+                }
+            };
+            arguments.push(argument);
+        }
+        self.depth -= 1;
+        if arguments.is_empty() {
+            return Err(self.error());
+        }
+        Ok(arguments)
+    }
+}
@@ -0,0 +1,495 @@
+//! Removal of debugging and other optional attributes from class files.
+//!
+//! Stripping is done in two steps. `strip` drops attributes selected by a
+//! `StripPolicy` from every attribute collection in a class, and
+//! `compact_constant_pool` then removes any constants that are no longer
+//! referenced and renumbers the remaining entries.
+//!
+//! Attributes the reader does not decode are kept as raw bytes, which may hold
+//! constant pool indices that cannot be found and renumbered. The pool of a
+//! class that keeps any of them is therefore never compacted.
+use std::mem;
+
+use super::*;
+
+/// Attributes the reader does not decode that are never stripped, since the
+/// JVM relies on them to link the class: without `NestHost` and
+/// `NestMembers`, the classes of a nest compiled for Java 11 or later lose
+/// access to each other's private members.
+pub const PRESERVED_UNKNOWN: &'static [&'static str] = &["NestHost", "NestMembers"];
+
+/// Selects which attributes are removed by `strip`.
+///
+/// Each flag removes the named attribute wherever it appears. The default
+/// policy removes everything.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StripPolicy {
+    pub line_number_table: bool,
+    pub local_variable_table: bool,
+    pub local_variable_type_table: bool,
+    pub source_file: bool,
+    pub source_debug_extension: bool,
+    pub deprecated: bool,
+    /// Removes attributes the reader did not recognize (`AttributeInfo::Raw`),
+    /// except for those in `PRESERVED_UNKNOWN`.
+    pub unknown: bool,
+}
+
+impl StripPolicy {
+    /// A policy that removes nothing.
+    pub fn none() -> StripPolicy {
+        StripPolicy {
+            line_number_table: false,
+            local_variable_table: false,
+            local_variable_type_table: false,
+            source_file: false,
+            source_debug_extension: false,
+            deprecated: false,
+            unknown: false,
+        }
+    }
+
+    /// Returns `true` if `attr` should be removed under this policy.
+    pub fn should_strip(&self, attr: &AttributeInfo) -> bool {
+        match *attr {
+            AttributeInfo::LineNumberTable(_) => self.line_number_table,
+            AttributeInfo::LocalVariableTable(_) => self.local_variable_table,
+            AttributeInfo::LocalVariableTypeTable(_) => self.local_variable_type_table,
+            AttributeInfo::SourceFile(_) => self.source_file,
+            AttributeInfo::SourceDebugExtension(_) => self.source_debug_extension,
+            AttributeInfo::Deprecated => self.deprecated,
+            AttributeInfo::Raw(_) => self.unknown,
+            _ => false,
+        }
+    }
+}
+
+impl Default for StripPolicy {
+    fn default() -> StripPolicy {
+        StripPolicy {
+            line_number_table: true,
+            local_variable_table: true,
+            local_variable_type_table: true,
+            source_file: true,
+            source_debug_extension: true,
+            deprecated: true,
+            unknown: true,
+        }
+    }
+}
+
+/// Removes the attributes selected by `policy` from the class, its fields, its
+/// methods and their code attributes.
+///
+/// The constant pool is left untouched; call `compact_constant_pool` afterwards
+/// to drop the constants that were only used by the removed attributes.
+pub fn strip(class: &mut ClassFile, policy: &StripPolicy) {
+    let cp = &class.constants;
+    strip_attributes(&mut class.attrs, policy, cp);
+    for field in class.fields.iter_mut() {
+        strip_attributes(&mut field.attrs, policy, cp);
+    }
+    for method in class.methods.iter_mut() {
+        strip_attributes(&mut method.attrs, policy, cp);
+    }
+}
+
+fn strip_attributes(attrs: &mut Attributes, policy: &StripPolicy, cp: &ConstantPool) {
+    attrs.retain(|attr| !policy.should_strip(attr) || is_preserved(attr, cp));
+    for attr in attrs.iter_mut() {
+        if let AttributeInfo::Code(ref mut code) = *attr {
+            strip_attributes(&mut code.attrs, policy, cp);
+        }
+    }
+}
+
+/// Returns `true` for the raw attributes named in `PRESERVED_UNKNOWN`.
+fn is_preserved(attr: &AttributeInfo, cp: &ConstantPool) -> bool {
+    match *attr {
+        AttributeInfo::Raw(ref raw) => {
+            match cp.get(raw.name_index) {
+                Some(&Constant::Utf8(ref name)) => PRESERVED_UNKNOWN.contains(&&name[..]),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if the class or any of its members or code attributes has
+/// an attribute the reader did not decode.
+fn has_raw_attributes(class: &ClassFile) -> bool {
+    fn any_raw(attrs: &Attributes) -> bool {
+        attrs.iter().any(|attr| match *attr {
+            AttributeInfo::Raw(_) => true,
+            AttributeInfo::Code(ref code) => any_raw(&code.attrs),
+            _ => false,
+        })
+    }
+    any_raw(&class.attrs) || class.fields.iter().any(|field| any_raw(&field.attrs)) ||
+    class.methods.iter().any(|method| any_raw(&method.attrs))
+}
+
+/// Removes every constant that is not referenced from the class and renumbers
+/// the remaining constants, updating all references to them.
+///
+/// The relative order of the remaining constants is preserved, so indices only
+/// ever decrease. This keeps `ldc` operands within their single byte.
+///
+/// Leaves the class untouched if it has any attribute the reader did not
+/// decode, since the constants its raw bytes refer to could not be renumbered.
+///
+/// Fails, leaving the class untouched, if any method has code that cannot be
+/// decoded, since the constants it refers to could not be found.
+pub fn compact_constant_pool(class: &mut ClassFile) -> Result<()> {
+    if has_raw_attributes(class) {
+        return Ok(());
+    }
+    for method in class.methods.iter() {
+        if let Some(code) = method.attrs.code() {
+            for instruction in code.instructions() {
+                try!(instruction);
+            }
+        }
+    }
+
+    let constants = mem::replace(&mut class.constants, ConstantPool::new(vec![]));
+
+    let mut marker = Marker {
+        constants: &constants,
+        marked: vec![false; constants.len() as usize],
+        pending: vec![],
+    };
+    walk_class(class, &mut marker);
+    marker.close();
+    let marked = marker.marked;
+
+    let mut remap: Vec<u16> = vec![0; marked.len()];
+    let mut compacted: Vec<Constant> = vec![];
+    for (i, constant) in constants.into_constants().into_iter().enumerate() {
+        let index = i + 1;
+        if !marked[index] {
+            continue;
+        }
+        remap[index] = compacted.len() as u16 + 1;
+        let is_wide = match constant {
+            Constant::Long(_) | Constant::Double(_) => true,
+            _ => false,
+        };
+        compacted.push(constant);
+        if is_wide {
+            compacted.push(Constant::Skip);
+        }
+    }
+
+    let mut remapper = Remapper { remap: &remap };
+    for constant in compacted.iter_mut() {
+        visit_constant(constant, &mut remapper);
+    }
+    walk_class(class, &mut remapper);
+    class.constants = ConstantPool::new(compacted);
+    Ok(())
+}
+
+/// Receives every constant pool reference found while walking a class.
+trait IndexVisitor {
+    fn visit(&mut self, index: &mut u16);
+
+    /// Called for every attribute that refers to its name implicitly.
+    fn visit_attribute_name(&mut self, name: &str);
+}
+
+/// Records the set of constants reachable from a class.
+struct Marker<'a> {
+    constants: &'a ConstantPool,
+    marked: Vec<bool>,
+    pending: Vec<u16>,
+}
+
+impl<'a> Marker<'a> {
+    /// Marks everything transitively referenced by the marked constants.
+    fn close(&mut self) {
+        while let Some(index) = self.pending.pop() {
+            for mut index in constant_refs(&self.constants[index]) {
+                self.visit(&mut index);
+            }
+        }
+    }
+}
+
+impl<'a> IndexVisitor for Marker<'a> {
+    fn visit(&mut self, index: &mut u16) {
+        let index = *index;
+        if index == 0 || index >= self.constants.len() || self.marked[index as usize] {
+            return;
+        }
+        self.marked[index as usize] = true;
+        self.pending.push(index);
+    }
+
+    fn visit_attribute_name(&mut self, name: &str) {
+        if let Some(mut index) = self.constants.find_utf8(name) {
+            self.visit(&mut index);
+        }
+    }
+}
+
+/// Rewrites references using a table from old to new indices.
+struct Remapper<'a> {
+    remap: &'a [u16],
+}
+
+impl<'a> IndexVisitor for Remapper<'a> {
+    fn visit(&mut self, index: &mut u16) {
+        if *index != 0 && (*index as usize) < self.remap.len() {
+            *index = self.remap[*index as usize];
+        }
+    }
+
+    fn visit_attribute_name(&mut self, _: &str) {}
+}
+
+/// Returns the constant pool indices referenced by a single constant.
+fn constant_refs(constant: &Constant) -> Vec<u16> {
+    match *constant {
+        Constant::Class(name_index) => vec![name_index],
+        Constant::String(string_index) => vec![string_index],
+        Constant::Fieldref(ref entity) |
+        Constant::Methodref(ref entity) |
+        Constant::InterfaceMethodref(ref entity) => {
+            vec![entity.class_index, entity.name_and_type_index]
+        }
+        Constant::NameAndType(ref name_and_type) => {
+            vec![name_and_type.name_index, name_and_type.descriptor_index]
+        }
+        Constant::MethodHandle { reference_index, .. } => vec![reference_index],
+        Constant::MethodType { descriptor_index } => vec![descriptor_index],
+        Constant::InvokeDynamic { name_and_type_index, .. } => vec![name_and_type_index],
+        _ => vec![],
+    }
+}
+
+fn visit_constant(constant: &mut Constant, visitor: &mut IndexVisitor) {
+    match *constant {
+        Constant::Class(ref mut name_index) => visitor.visit(name_index),
+        Constant::String(ref mut string_index) => visitor.visit(string_index),
+        Constant::Fieldref(ref mut entity) |
+        Constant::Methodref(ref mut entity) |
+        Constant::InterfaceMethodref(ref mut entity) => {
+            visitor.visit(&mut entity.class_index);
+            visitor.visit(&mut entity.name_and_type_index);
+        }
+        Constant::NameAndType(ref mut name_and_type) => {
+            visitor.visit(&mut name_and_type.name_index);
+            visitor.visit(&mut name_and_type.descriptor_index);
+        }
+        Constant::MethodHandle { ref mut reference_index, .. } => visitor.visit(reference_index),
+        Constant::MethodType { ref mut descriptor_index } => visitor.visit(descriptor_index),
+        Constant::InvokeDynamic { ref mut name_and_type_index, .. } => {
+            visitor.visit(name_and_type_index)
+        }
+        _ => {}
+    }
+}
+
+fn walk_class(class: &mut ClassFile, visitor: &mut IndexVisitor) {
+    visitor.visit(&mut class.this_class);
+    visitor.visit(&mut class.super_class);
+    for interface in class.interfaces.iter_mut() {
+        visitor.visit(interface);
+    }
+    for field in class.fields.iter_mut() {
+        visitor.visit(&mut field.name_index);
+        visitor.visit(&mut field.descriptor_index);
+        walk_attributes(&mut field.attrs, visitor);
+    }
+    for method in class.methods.iter_mut() {
+        visitor.visit(&mut method.name_index);
+        visitor.visit(&mut method.descriptor_index);
+        walk_attributes(&mut method.attrs, visitor);
+    }
+    walk_attributes(&mut class.attrs, visitor);
+}
+
+fn walk_attributes(attrs: &mut Attributes, visitor: &mut IndexVisitor) {
+    for attr in attrs.iter_mut() {
+        // Classes with raw attributes are not compacted.
+        if let AttributeInfo::Raw(_) = *attr {
+            continue;
+        }
+        visitor.visit_attribute_name(attr.name());
+        match *attr {
+            AttributeInfo::SourceFile(ref mut sourcefile_index) => visitor.visit(sourcefile_index),
+            AttributeInfo::InnerClasses(ref mut inner_classes) => {
+                for inner_class in inner_classes.iter_mut() {
+                    visitor.visit(&mut inner_class.inner_class_info_index);
+                    visitor.visit(&mut inner_class.outer_class_info_index);
+                    visitor.visit(&mut inner_class.inner_name_index);
+                }
+            }
+            AttributeInfo::EnclosingMethod(ref mut enclosing_method) => {
+                visitor.visit(&mut enclosing_method.class_index);
+                visitor.visit(&mut enclosing_method.method_index);
+            }
+            AttributeInfo::BootstrapMethods(ref mut bootstrap_methods) => {
+                for bootstrap_method in bootstrap_methods.iter_mut() {
+                    visitor.visit(&mut bootstrap_method.bootstrap_method_ref);
+                    for argument in bootstrap_method.bootstrap_arguments.iter_mut() {
+                        visitor.visit(argument);
+                    }
+                }
+            }
+            AttributeInfo::ConstantValue(ref mut constantvalue_index) => {
+                visitor.visit(constantvalue_index)
+            }
+            AttributeInfo::Code(ref mut code) => {
+                walk_code(&mut code.code, visitor);
+                for exception_info in code.exception_table.iter_mut() {
+                    visitor.visit(&mut exception_info.catch_type);
+                }
+                walk_attributes(&mut code.attrs, visitor);
+            }
+            AttributeInfo::Exceptions(ref mut exception_index_table) => {
+                for exception_index in exception_index_table.iter_mut() {
+                    visitor.visit(exception_index);
+                }
+            }
+            AttributeInfo::LocalVariableTable(ref mut table) => {
+                for entry in table.iter_mut() {
+                    visitor.visit(&mut entry.name_index);
+                    visitor.visit(&mut entry.descriptor_index);
+                }
+            }
+            AttributeInfo::LocalVariableTypeTable(ref mut table) => {
+                for entry in table.iter_mut() {
+                    visitor.visit(&mut entry.name_index);
+                    visitor.visit(&mut entry.signature_index);
+                }
+            }
+            AttributeInfo::StackMapTable(ref mut entries) => {
+                for entry in entries.iter_mut() {
+                    walk_stack_map_frame(entry, visitor);
+                }
+            }
+            AttributeInfo::Signature(ref mut signature_index) => visitor.visit(signature_index),
+            AttributeInfo::AnnotationDefault(ref mut element_value) => {
+                walk_element_value(element_value, visitor)
+            }
+            AttributeInfo::MethodParameters(ref mut parameters) => {
+                for parameter in parameters.iter_mut() {
+                    visitor.visit(&mut parameter.name_index);
+                }
+            }
+            AttributeInfo::RuntimeVisibleAnnotations(ref mut annotations) |
+            AttributeInfo::RuntimeInvisibleAnnotations(ref mut annotations) => {
+                for annotation in annotations.iter_mut() {
+                    walk_annotation(annotation, visitor);
+                }
+            }
+            AttributeInfo::RuntimeVisibleTypeAnnotations(ref mut annotations) |
+            AttributeInfo::RuntimeInvisibleTypeAnnotations(ref mut annotations) => {
+                for annotation in annotations.iter_mut() {
+                    visitor.visit(&mut annotation.type_index);
+                    walk_element_value_pairs(&mut annotation.element_value_pairs, visitor);
+                }
+            }
+            AttributeInfo::RuntimeVisibleParameterAnnotations(ref mut parameter_annotations) |
+            AttributeInfo::RuntimeInvisibleParameterAnnotations(ref mut parameter_annotations) => {
+                for annotations in parameter_annotations.iter_mut() {
+                    for annotation in annotations.iter_mut() {
+                        walk_annotation(annotation, visitor);
+                    }
+                }
+            }
+            AttributeInfo::SourceDebugExtension(_) |
+            AttributeInfo::LineNumberTable(_) |
+            AttributeInfo::Synthetic |
+            AttributeInfo::Deprecated |
+            AttributeInfo::Raw(_) => {}
+        }
+    }
+}
+
+/// Visits the constant pool operands of every instruction in `code`, patching
+/// the operand bytes in place. Stops at the first instruction that cannot be
+/// decoded.
+fn walk_code(code: &mut Vec<u8>, visitor: &mut IndexVisitor) {
+    let mut pc = 0;
+    while pc < code.len() {
+        let result = match Bytecode::decode(code, pc) {
+            Ok(result) => result,
+            Err(_) => return,
+        };
+        if let Some(index) = result.bytecode.constant_index() {
+            let mut index = index;
+            visitor.visit(&mut index);
+            if let Bytecode::ldc { .. } = result.bytecode {
+                code[pc + 1] = index as u8;
+            } else {
+                code[pc + 1] = (index >> 8) as u8;
+                code[pc + 2] = index as u8;
+            }
+        }
+        pc = result.newpc;
+    }
+}
+
+fn walk_stack_map_frame(frame: &mut StackMapFrame, visitor: &mut IndexVisitor) {
+    match *frame {
+        StackMapFrame::SameLocals1StackItemFrame { ref mut stack, .. } |
+        StackMapFrame::SameLocals1StackItemFrameExtended { ref mut stack, .. } => {
+            walk_verification_type_info(&mut stack[0], visitor);
+        }
+        StackMapFrame::AppendFrame { ref mut locals, .. } => {
+            for local in locals.iter_mut() {
+                walk_verification_type_info(local, visitor);
+            }
+        }
+        StackMapFrame::FullFrame { ref mut locals, ref mut stack, .. } => {
+            for local in locals.iter_mut() {
+                walk_verification_type_info(local, visitor);
+            }
+            for item in stack.iter_mut() {
+                walk_verification_type_info(item, visitor);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn walk_verification_type_info(info: &mut VerificationTypeInfo, visitor: &mut IndexVisitor) {
+    if let VerificationTypeInfo::Object(ref mut object) = *info {
+        visitor.visit(&mut object.cpool_index);
+    }
+}
+
+fn walk_annotation(annotation: &mut Annotation, visitor: &mut IndexVisitor) {
+    visitor.visit(&mut annotation.type_index);
+    walk_element_value_pairs(&mut annotation.element_value_pairs, visitor);
+}
+
+fn walk_element_value_pairs(pairs: &mut Vec<ElementValuePair>, visitor: &mut IndexVisitor) {
+    for pair in pairs.iter_mut() {
+        visitor.visit(&mut pair.element_name_index);
+        walk_element_value(&mut pair.value, visitor);
+    }
+}
+
+fn walk_element_value(element_value: &mut ElementValue, visitor: &mut IndexVisitor) {
+    match *element_value {
+        ElementValue::ConstantValue(ref mut const_value) => {
+            visitor.visit(&mut const_value.const_value_index)
+        }
+        ElementValue::EnumConstValue(ref mut enum_value) => {
+            visitor.visit(&mut enum_value.type_name_index);
+            visitor.visit(&mut enum_value.const_name_index);
+        }
+        ElementValue::ClassInfo(ref mut class_info) => visitor.visit(class_info),
+        ElementValue::AnnotationValue(ref mut annotation) => walk_annotation(annotation, visitor),
+        ElementValue::ArrayValue(ref mut array_value) => {
+            for value in array_value.values.iter_mut() {
+                walk_element_value(value, visitor);
+            }
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use std::io;
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use super::*;
+
+const CONSTANT_UTF8: u8 = 1;
+const CONSTANT_INTEGER: u8 = 3;
+const CONSTANT_FLOAT: u8 = 4;
+const CONSTANT_LONG: u8 = 5;
+const CONSTANT_DOUBLE: u8 = 6;
+const CONSTANT_CLASS: u8 = 7;
+const CONSTANT_STRING: u8 = 8;
+const CONSTANT_FIELDREF: u8 = 9;
+const CONSTANT_METHODREF: u8 = 10;
+const CONSTANT_INTERFACE_METHODREF: u8 = 11;
+const CONSTANT_NAME_AND_TYPE: u8 = 12;
+const CONSTANT_METHOD_HANDLE: u8 = 15;
+const CONSTANT_METHOD_TYPE: u8 = 16;
+const CONSTANT_INVOKE_DYNAMIC: u8 = 18;
+
+/// Serializes a `ClassFile` into the binary class file format.
+///
+/// This is the inverse of `ClassReader`; reading a class and writing it back
+/// out produces the same bytes.
+///
+/// Attribute names are not stored on `AttributeInfo` values, so the constant
+/// pool of the class being written must contain a `Constant::Utf8` entry for
+/// the name of every attribute that is present.
+pub struct ClassWriter<T: io::Write> {
+    writer: Box<T>,
+}
+
+impl<T: io::Write> ClassWriter<T> {
+    pub fn new(writer: T) -> ClassWriter<T> {
+        ClassWriter { writer: Box::new(writer) }
+    }
+
+    /// Consumes this writer, returning the underlying output.
+    pub fn into_inner(self) -> T {
+        *self.writer
+    }
+
+    pub fn write_class(&mut self, class: &ClassFile) -> Result<()> {
+        try!(self.write_u32(class.magic));
+        try!(self.write_u16(class.minor_version));
+        try!(self.write_u16(class.major_version));
+        try!(self.write_constant_pool(&class.constants));
+        try!(self.write_u16(class.access_flags.bits()));
+        try!(self.write_u16(class.this_class));
+        try!(self.write_u16(class.super_class));
+        try!(self.write_u16(class.interfaces.len() as u16));
+        for interface in class.interfaces.iter() {
+            try!(self.write_u16(*interface));
+        }
+        try!(self.write_u16(class.fields.len() as u16));
+        for field in class.fields.iter() {
+            try!(self.write_u16(field.access_flags.bits()));
+            try!(self.write_u16(field.name_index));
+            try!(self.write_u16(field.descriptor_index));
+            try!(self.write_attributes(&class.constants, &field.attrs));
+        }
+        try!(self.write_u16(class.methods.len() as u16));
+        for method in class.methods.iter() {
+            try!(self.write_u16(method.access_flags.bits()));
+            try!(self.write_u16(method.name_index));
+            try!(self.write_u16(method.descriptor_index));
+            try!(self.write_attributes(&class.constants, &method.attrs));
+        }
+        try!(self.write_attributes(&class.constants, &class.attrs));
+        Ok(())
+    }
+
+    fn write_constant_pool(&mut self, constants: &ConstantPool) -> Result<()> {
+        try!(self.write_u16(constants.len()));
+        for constant in constants.iter() {
+            match *constant {
+                Constant::Utf8(ref value) => {
+                    try!(self.write_u8(CONSTANT_UTF8));
+                    try!(self.write_u16(value.len() as u16));
+                    try!(self.write_bytes(value.as_bytes()));
+                }
+                Constant::Integer(value) => {
+                    try!(self.write_u8(CONSTANT_INTEGER));
+                    try!(self.write_u32(value as u32));
+                }
+                Constant::Float(value) => {
+                    try!(self.write_u8(CONSTANT_FLOAT));
+                    try!(self.write_u32(value.to_bits()));
+                }
+                Constant::Long(value) => {
+                    try!(self.write_u8(CONSTANT_LONG));
+                    try!(self.write_u64(value as u64));
+                }
+                Constant::Double(value) => {
+                    try!(self.write_u8(CONSTANT_DOUBLE));
+                    try!(self.write_u64(value.to_bits()));
+                }
+                Constant::Class(name_index) => {
+                    try!(self.write_u8(CONSTANT_CLASS));
+                    try!(self.write_u16(name_index));
+                }
+                Constant::String(string_index) => {
+                    try!(self.write_u8(CONSTANT_STRING));
+                    try!(self.write_u16(string_index));
+                }
+                Constant::Fieldref(ref entity) => {
+                    try!(self.write_u8(CONSTANT_FIELDREF));
+                    try!(self.write_u16(entity.class_index));
+                    try!(self.write_u16(entity.name_and_type_index));
+                }
+                Constant::Methodref(ref entity) => {
+                    try!(self.write_u8(CONSTANT_METHODREF));
+                    try!(self.write_u16(entity.class_index));
+                    try!(self.write_u16(entity.name_and_type_index));
+                }
+                Constant::InterfaceMethodref(ref entity) => {
+                    try!(self.write_u8(CONSTANT_INTERFACE_METHODREF));
+                    try!(self.write_u16(entity.class_index));
+                    try!(self.write_u16(entity.name_and_type_index));
+                }
+                Constant::NameAndType(ref name_and_type) => {
+                    try!(self.write_u8(CONSTANT_NAME_AND_TYPE));
+                    try!(self.write_u16(name_and_type.name_index));
+                    try!(self.write_u16(name_and_type.descriptor_index));
+                }
+                Constant::MethodHandle { reference_kind, reference_index } => {
+                    try!(self.write_u8(CONSTANT_METHOD_HANDLE));
+                    try!(self.write_u8(reference_kind));
+                    try!(self.write_u16(reference_index));
+                }
+                Constant::MethodType { descriptor_index } => {
+                    try!(self.write_u8(CONSTANT_METHOD_TYPE));
+                    try!(self.write_u16(descriptor_index));
+                }
+                Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                    try!(self.write_u8(CONSTANT_INVOKE_DYNAMIC));
+                    try!(self.write_u16(bootstrap_method_attr_index));
+                    try!(self.write_u16(name_and_type_index));
+                }
+                // The slot following a Long or Double has no representation
+                // in the class file.
+                Constant::Skip => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn write_attributes(&mut self, constants: &ConstantPool, attrs: &Attributes) -> Result<()> {
+        try!(self.write_u16(attrs.len() as u16));
+        for attr in attrs.iter() {
+            try!(self.write_attribute(constants, attr));
+        }
+        Ok(())
+    }
+
+    fn write_attribute(&mut self, constants: &ConstantPool, attr: &AttributeInfo) -> Result<()> {
+        let name_index = match *attr {
+            AttributeInfo::Raw(ref raw) => raw.name_index,
+            _ => {
+                match constants.find_utf8(attr.name()) {
+                    Some(index) => index,
+                    None => return Err(Error::MissingAttributeName(attr.name().to_string())),
+                }
+            }
+        };
+        // Attribute bodies are length-prefixed, so serialize into a buffer first.
+        let mut body = ClassWriter::new(Vec::new());
+        try!(body.write_attribute_body(constants, attr));
+        let body = body.into_inner();
+        try!(self.write_u16(name_index));
+        try!(self.write_u32(body.len() as u32));
+        try!(self.write_bytes(&body));
+        Ok(())
+    }
+
+    fn write_attribute_body(&mut self,
+                            constants: &ConstantPool,
+                            attr: &AttributeInfo)
+                            -> Result<()> {
+        match *attr {
+            AttributeInfo::SourceFile(sourcefile_index) => {
+                try!(self.write_u16(sourcefile_index));
+            }
+            AttributeInfo::InnerClasses(ref inner_classes) => {
+                try!(self.write_u16(inner_classes.len() as u16));
+                for inner_class in inner_classes.iter() {
+                    try!(self.write_u16(inner_class.inner_class_info_index));
+                    try!(self.write_u16(inner_class.outer_class_info_index));
+                    try!(self.write_u16(inner_class.inner_name_index));
+                    try!(self.write_u16(inner_class.inner_class_access_flags.bits()));
+                }
+            }
+            AttributeInfo::EnclosingMethod(ref enclosing_method) => {
+                try!(self.write_u16(enclosing_method.class_index));
+                try!(self.write_u16(enclosing_method.method_index));
+            }
+            AttributeInfo::SourceDebugExtension(ref debug_extension) => {
+                try!(self.write_bytes(debug_extension));
+            }
+            AttributeInfo::BootstrapMethods(ref bootstrap_methods) => {
+                try!(self.write_u16(bootstrap_methods.len() as u16));
+                for bootstrap_method in bootstrap_methods.iter() {
+                    try!(self.write_u16(bootstrap_method.bootstrap_method_ref));
+                    try!(self.write_u16(bootstrap_method.bootstrap_arguments.len() as u16));
+                    for argument in bootstrap_method.bootstrap_arguments.iter() {
+                        try!(self.write_u16(*argument));
+                    }
+                }
+            }
+            AttributeInfo::ConstantValue(constantvalue_index) => {
+                try!(self.write_u16(constantvalue_index));
+            }
+            AttributeInfo::Code(ref code) => {
+                try!(self.write_u16(code.max_stack));
+                try!(self.write_u16(code.max_locals));
+                try!(self.write_u32(code.code.len() as u32));
+                try!(self.write_bytes(&code.code));
+                try!(self.write_u16(code.exception_table.len() as u16));
+                for exception_info in code.exception_table.iter() {
+                    try!(self.write_u16(exception_info.start_pc));
+                    try!(self.write_u16(exception_info.end_pc));
+                    try!(self.write_u16(exception_info.handler_pc));
+                    try!(self.write_u16(exception_info.catch_type));
+                }
+                try!(self.write_attributes(constants, &code.attrs));
+            }
+            AttributeInfo::Exceptions(ref exception_index_table) => {
+                try!(self.write_u16(exception_index_table.len() as u16));
+                for exception_index in exception_index_table.iter() {
+                    try!(self.write_u16(*exception_index));
+                }
+            }
+            AttributeInfo::LineNumberTable(ref line_number_table) => {
+                try!(self.write_u16(line_number_table.len() as u16));
+                for entry in line_number_table.iter() {
+                    try!(self.write_u16(entry.start_pc));
+                    try!(self.write_u16(entry.line_number));
+                }
+            }
+            AttributeInfo::LocalVariableTable(ref local_variable_table) => {
+                try!(self.write_u16(local_variable_table.len() as u16));
+                for entry in local_variable_table.iter() {
+                    try!(self.write_u16(entry.start_pc));
+                    try!(self.write_u16(entry.length));
+                    try!(self.write_u16(entry.name_index));
+                    try!(self.write_u16(entry.descriptor_index));
+                    try!(self.write_u16(entry.index));
+                }
+            }
+            AttributeInfo::LocalVariableTypeTable(ref local_variable_type_table) => {
+                try!(self.write_u16(local_variable_type_table.len() as u16));
+                for entry in local_variable_type_table.iter() {
+                    try!(self.write_u16(entry.start_pc));
+                    try!(self.write_u16(entry.length));
+                    try!(self.write_u16(entry.name_index));
+                    try!(self.write_u16(entry.signature_index));
+                    try!(self.write_u16(entry.index));
+                }
+            }
+            AttributeInfo::StackMapTable(ref entries) => {
+                try!(self.write_u16(entries.len() as u16));
+                for entry in entries.iter() {
+                    try!(self.write_stack_map_frame(entry));
+                }
+            }
+            AttributeInfo::Synthetic => {}
+            AttributeInfo::Deprecated => {}
+            AttributeInfo::Signature(signature_index) => {
+                try!(self.write_u16(signature_index));
+            }
+            AttributeInfo::AnnotationDefault(ref element_value) => {
+                try!(self.write_element_value(element_value));
+            }
+            AttributeInfo::MethodParameters(ref parameters) => {
+                try!(self.write_u8(parameters.len() as u8));
+                for parameter in parameters.iter() {
+                    try!(self.write_u16(parameter.name_index));
+                    try!(self.write_u16(parameter.access_flags.bits()));
+                }
+            }
+            AttributeInfo::RuntimeVisibleAnnotations(ref annotations) |
+            AttributeInfo::RuntimeInvisibleAnnotations(ref annotations) => {
+                try!(self.write_annotations(annotations));
+            }
+            AttributeInfo::RuntimeVisibleTypeAnnotations(ref annotations) |
+            AttributeInfo::RuntimeInvisibleTypeAnnotations(ref annotations) => {
+                try!(self.write_u16(annotations.len() as u16));
+                for annotation in annotations.iter() {
+                    try!(self.write_type_annotation(annotation));
+                }
+            }
+            AttributeInfo::RuntimeVisibleParameterAnnotations(ref parameter_annotations) |
+            AttributeInfo::RuntimeInvisibleParameterAnnotations(ref parameter_annotations) => {
+                try!(self.write_u8(parameter_annotations.len() as u8));
+                for annotations in parameter_annotations.iter() {
+                    try!(self.write_annotations(annotations));
+                }
+            }
+            AttributeInfo::Raw(ref raw) => {
+                try!(self.write_bytes(&raw.info));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_stack_map_frame(&mut self, frame: &StackMapFrame) -> Result<()> {
+        match *frame {
+            StackMapFrame::SameFrame { frame_type } => {
+                try!(self.write_u8(frame_type));
+            }
+            StackMapFrame::SameLocals1StackItemFrame { frame_type, ref stack } => {
+                try!(self.write_u8(frame_type));
+                try!(self.write_verification_type_info(&stack[0]));
+            }
+            StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, ref stack } => {
+                try!(self.write_u8(247));
+                try!(self.write_u16(offset_delta));
+                try!(self.write_verification_type_info(&stack[0]));
+            }
+            StackMapFrame::ChopFrame { frame_type, offset_delta } => {
+                try!(self.write_u8(frame_type));
+                try!(self.write_u16(offset_delta));
+            }
+            StackMapFrame::SameFrameExtended { offset_delta } => {
+                try!(self.write_u8(251));
+                try!(self.write_u16(offset_delta));
+            }
+            StackMapFrame::AppendFrame { frame_type, offset_delta, ref locals } => {
+                try!(self.write_u8(frame_type));
+                try!(self.write_u16(offset_delta));
+                for local in locals.iter() {
+                    try!(self.write_verification_type_info(local));
+                }
+            }
+            StackMapFrame::FullFrame { offset_delta, ref locals, ref stack } => {
+                try!(self.write_u8(255));
+                try!(self.write_u16(offset_delta));
+                try!(self.write_u16(locals.len() as u16));
+                for local in locals.iter() {
+                    try!(self.write_verification_type_info(local));
+                }
+                try!(self.write_u16(stack.len() as u16));
+                for item in stack.iter() {
+                    try!(self.write_verification_type_info(item));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_verification_type_info(&mut self, info: &VerificationTypeInfo) -> Result<()> {
+        match *info {
+            VerificationTypeInfo::Top => try!(self.write_u8(0x0)),
+            VerificationTypeInfo::Integer => try!(self.write_u8(0x1)),
+            VerificationTypeInfo::Float => try!(self.write_u8(0x2)),
+            VerificationTypeInfo::Double => try!(self.write_u8(0x3)),
+            VerificationTypeInfo::Long => try!(self.write_u8(0x4)),
+            VerificationTypeInfo::Null => try!(self.write_u8(0x5)),
+            VerificationTypeInfo::UninitializedThis => try!(self.write_u8(0x6)),
+            VerificationTypeInfo::Object(ref object) => {
+                try!(self.write_u8(0x7));
+                try!(self.write_u16(object.cpool_index));
+            }
+            VerificationTypeInfo::Uninitialized(ref uninitialized) => {
+                try!(self.write_u8(0x8));
+                try!(self.write_u16(uninitialized.offset));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_annotations(&mut self, annotations: &[Annotation]) -> Result<()> {
+        try!(self.write_u16(annotations.len() as u16));
+        for annotation in annotations.iter() {
+            try!(self.write_annotation(annotation));
+        }
+        Ok(())
+    }
+
+    fn write_annotation(&mut self, annotation: &Annotation) -> Result<()> {
+        try!(self.write_u16(annotation.type_index));
+        try!(self.write_element_value_pairs(&annotation.element_value_pairs));
+        Ok(())
+    }
+
+    fn write_element_value_pairs(&mut self, pairs: &[ElementValuePair]) -> Result<()> {
+        try!(self.write_u16(pairs.len() as u16));
+        for pair in pairs.iter() {
+            try!(self.write_u16(pair.element_name_index));
+            try!(self.write_element_value(&pair.value));
+        }
+        Ok(())
+    }
+
+    fn write_type_annotation(&mut self, annotation: &TypeAnnotation) -> Result<()> {
+        try!(self.write_u8(annotation.target_type));
+        try!(self.write_target_info(&annotation.target_info));
+        try!(self.write_u8(annotation.target_path.len() as u8));
+        for entry in annotation.target_path.iter() {
+            try!(self.write_u8(entry.type_path_kind));
+            try!(self.write_u8(entry.type_argument_index));
+        }
+        try!(self.write_u16(annotation.type_index));
+        try!(self.write_element_value_pairs(&annotation.element_value_pairs));
+        Ok(())
+    }
+
+    fn write_target_info(&mut self, target_info: &TargetInfo) -> Result<()> {
+        match *target_info {
+            TargetInfo::TypeParameter(type_parameter_index) => {
+                try!(self.write_u8(type_parameter_index));
+            }
+            TargetInfo::Supertype(supertype_index) => {
+                try!(self.write_u16(supertype_index));
+            }
+            TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+                try!(self.write_u8(type_parameter_index));
+                try!(self.write_u8(bound_index));
+            }
+            TargetInfo::Empty => {}
+            TargetInfo::MethodFormalParameter(formal_parameter_index) => {
+                try!(self.write_u8(formal_parameter_index));
+            }
+            TargetInfo::Throws(throws_type_index) => {
+                try!(self.write_u16(throws_type_index));
+            }
+            TargetInfo::Localvar(ref table) => {
+                try!(self.write_u16(table.len() as u16));
+                for entry in table.iter() {
+                    try!(self.write_u16(entry.start_pc));
+                    try!(self.write_u16(entry.length));
+                    try!(self.write_u16(entry.index));
+                }
+            }
+            TargetInfo::Catch(exception_table_index) => {
+                try!(self.write_u16(exception_table_index));
+            }
+            TargetInfo::Offset(offset) => {
+                try!(self.write_u16(offset));
+            }
+            TargetInfo::TypeArgument { offset, type_argument_index } => {
+                try!(self.write_u16(offset));
+                try!(self.write_u8(type_argument_index));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_element_value(&mut self, element_value: &ElementValue) -> Result<()> {
+        match *element_value {
+            ElementValue::ConstantValue(ref const_value) => {
+                try!(self.write_u8(const_value.tag));
+                try!(self.write_u16(const_value.const_value_index));
+            }
+            ElementValue::EnumConstValue(ref enum_value) => {
+                try!(self.write_u8(b'e'));
+                try!(self.write_u16(enum_value.type_name_index));
+                try!(self.write_u16(enum_value.const_name_index));
+            }
+            ElementValue::ClassInfo(class_info) => {
+                try!(self.write_u8(b'c'));
+                try!(self.write_u16(class_info));
+            }
+            ElementValue::AnnotationValue(ref annotation) => {
+                try!(self.write_u8(b'@'));
+                try!(self.write_annotation(annotation));
+            }
+            ElementValue::ArrayValue(ref array_value) => {
+                try!(self.write_u8(b'['));
+                try!(self.write_u16(array_value.values.len() as u16));
+                for value in array_value.values.iter() {
+                    try!(self.write_element_value(value));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)
+    }
+
+    fn write_u8(&mut self, value: u8) -> io::Result<()> {
+        self.writer.write_u8(value)
+    }
+
+    fn write_u16(&mut self, value: u16) -> io::Result<()> {
+        self.writer.write_u16::<BigEndian>(value)
+    }
+
+    fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        self.writer.write_u32::<BigEndian>(value)
+    }
+
+    fn write_u64(&mut self, value: u64) -> io::Result<()> {
+        self.writer.write_u64::<BigEndian>(value)
+    }
+}
@@ -0,0 +1,99 @@
+extern crate classfile;
+
+use std::fs::File;
+
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn read_control_flow() -> ClassFile {
+    let mut file = File::open("../test-classes/ControlFlow.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+#[test]
+fn should_find_handlers_in_exception_table_order() {
+    // Given
+    let class = read_control_flow();
+    let code = class.find_method("parse").unwrap().attrs.code().unwrap();
+
+    // When
+    let index = code.index(&class.constants);
+
+    // Then
+    let catches = |pc| {
+        index.handlers_at(pc)
+            .iter()
+            .map(|handler| (handler.info.handler_pc, handler.catch_class))
+            .collect::<Vec<(u16, Option<&str>)>>()
+    };
+    assert_eq!(vec![(15, Some("java/lang/NumberFormatException")), (28, None)],
+               catches(0));
+    assert_eq!(catches(0), catches(4));
+    assert_eq!(Vec::<(u16, Option<&str>)>::new(), catches(5));
+    assert_eq!(vec![(28, None)], catches(15));
+    assert_eq!(vec![(28, None)], catches(17));
+    assert!(index.handlers_at(18).is_empty());
+    assert!(index.handlers_at(1000).is_empty());
+}
+
+#[test]
+fn should_map_pcs_to_lines_and_back() {
+    // Given
+    let class = read_control_flow();
+    let code = class.find_method("parse").unwrap().attrs.code().unwrap();
+
+    // When
+    let index = code.index(&class.constants);
+
+    // Then
+    assert_eq!(Some(38), index.line_at(0));
+    assert_eq!(Some(38), index.line_at(4));
+    assert_eq!(Some(42), index.line_at(5));
+    assert_eq!(Some(38), index.line_at(14));
+    assert_eq!(Some(43), index.line_at(38));
+    assert_eq!(None, index.line_at(39));
+    assert_eq!(&[0, 13], index.pcs_for_line(38));
+    assert_eq!(&[5, 18, 28], index.pcs_for_line(42));
+    assert!(index.pcs_for_line(1).is_empty());
+}
+
+#[test]
+fn should_resolve_live_local_variables() {
+    // Given
+    let mut class = read_control_flow();
+    let signature_index = class.constants.find_utf8("Ljava/lang/String;").unwrap();
+    let name_index = class.constants.find_utf8("s").unwrap();
+    let parse = class.constants.find_utf8("parse").unwrap();
+    let method = class.methods.iter_mut().find(|method| method.name_index == parse).unwrap();
+    for attr in method.attrs.iter_mut() {
+        if let AttributeInfo::Code(ref mut code) = *attr {
+            code.attrs.push(AttributeInfo::LocalVariableTypeTable(vec![
+                LocalVariableTypeTableEntry {
+                    start_pc: 0,
+                    length: 39,
+                    name_index: name_index,
+                    signature_index: signature_index,
+                    index: 0,
+                },
+            ]));
+        }
+    }
+    let code = method.attrs.code().unwrap();
+
+    // When
+    let index = code.index(&class.constants);
+
+    // Then
+    let names = |pc| index.locals_at(pc).iter().map(|local| local.name).collect::<Vec<&str>>();
+    assert_eq!(vec!["s"], names(0));
+    assert_eq!(vec!["s"], names(15));
+    assert_eq!(vec!["s", "e"], names(16));
+    assert_eq!(vec!["s", "e"], names(27));
+    assert_eq!(vec!["s"], names(28));
+    assert!(names(39).is_empty());
+    let e = index.locals_at(16)[1];
+    assert_eq!(1, e.index);
+    assert_eq!("Ljava/lang/NumberFormatException;", e.descriptor);
+    assert_eq!(None, e.signature);
+    assert_eq!(Some("Ljava/lang/String;"), index.locals_at(0)[0].signature);
+}
@@ -0,0 +1,86 @@
+extern crate classfile;
+
+use std::fs::File;
+
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn object(name: &str) -> FieldType {
+    FieldType::Object(name.to_string())
+}
+
+fn array(component: FieldType) -> FieldType {
+    FieldType::Array(Box::new(component))
+}
+
+#[test]
+fn should_parse_method_descriptor() {
+    // When
+    let descriptor = MethodDescriptor::parse("(I[Ljava/lang/String;[[DJ)Ljava/lang/Object;")
+        .unwrap();
+
+    // Then
+    assert_eq!(vec![FieldType::Int,
+                    array(object("java/lang/String")),
+                    array(array(FieldType::Double)),
+                    FieldType::Long],
+               descriptor.params);
+    assert_eq!(Some(object("java/lang/Object")), descriptor.ret);
+    assert_eq!(5, descriptor.param_slots());
+    assert_eq!(6, descriptor.arg_slots(false));
+    assert_eq!(5, descriptor.arg_slots(true));
+    assert_eq!("(I[Ljava/lang/String;[[DJ)Ljava/lang/Object;",
+               descriptor.to_string());
+}
+
+#[test]
+fn should_reject_invalid_descriptors() {
+    for descriptor in &["", "V", "Q", "II", "Ljava/lang/String", "Ljava.lang.String;", "L;",
+                        "Ljava//String;", "["] {
+        assert!(FieldType::parse(descriptor).is_err(), "{}", descriptor);
+    }
+    for descriptor in &["", "()", "(V)V", "I", "(I", "()VV", "(I)[V"] {
+        assert!(MethodDescriptor::parse(descriptor).is_err(), "{}", descriptor);
+    }
+    let deep = format!("{}I", "[".repeat(256));
+    assert!(FieldType::parse(&deep).is_err());
+    let deepest = format!("{}I", "[".repeat(255));
+    assert!(FieldType::parse(&deepest).is_ok());
+    let huge = format!("{}I", "[".repeat(100000));
+    assert!(FieldType::parse(&huge).is_err());
+    assert!(MethodDescriptor::parse(&format!("({})V", huge)).is_err());
+}
+
+#[test]
+fn should_format_java_names() {
+    assert_eq!("java.lang.String[][]",
+               FieldType::parse("[[Ljava/lang/String;").unwrap().java_name());
+    assert_eq!("boolean", FieldType::Boolean.java_name());
+}
+
+#[test]
+fn should_parse_member_descriptors() {
+    // Given
+    let mut file = File::open("../test-classes/Point.class").unwrap();
+
+    // When
+    let class = ClassReader::new(&mut file).read_class().unwrap();
+
+    // Then
+    for field in class.fields.iter() {
+        assert_eq!(FieldType::Int, field.descriptor(&class.constants).unwrap());
+    }
+    let constructor = class.find_method("<init>").unwrap();
+    assert_eq!(3, constructor.arg_slots(&class.constants).unwrap());
+    for constant in class.constants.iter() {
+        match *constant {
+            Constant::Fieldref(ref entity) => {
+                assert!(entity.field_type(&class.constants).is_ok());
+            }
+            Constant::Methodref(ref entity) => {
+                assert!(entity.method_descriptor(&class.constants).is_ok());
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,239 @@
+extern crate classfile;
+
+use std::fs::File;
+use std::io::Read;
+
+use classfile::*;
+use classfile::reader::ClassReader;
+use classfile::writer::ClassWriter;
+
+fn read_class(name: &str) -> (Vec<u8>, ClassFile) {
+    let mut bytes = vec![];
+    File::open(format!("../test-classes/{}.class", name)).unwrap().read_to_end(&mut bytes).unwrap();
+    let class = ClassReader::new(&bytes[..]).read_class().unwrap();
+    (bytes, class)
+}
+
+fn code_mut<'a>(class: &'a mut ClassFile, name: &str) -> &'a mut CodeAttribute {
+    let cp = &class.constants;
+    let method = class.methods
+        .iter_mut()
+        .find(|method| cp[method.name_index].as_utf8() == name)
+        .unwrap();
+    for attr in method.attrs.iter_mut() {
+        if let AttributeInfo::Code(ref mut code) = *attr {
+            return code;
+        }
+    }
+    panic!("{} has no code", name)
+}
+
+/// A code attribute holding just `return`.
+fn empty_code() -> CodeAttribute {
+    CodeAttribute {
+        max_stack: 0,
+        max_locals: 0,
+        code: vec![0xb1],
+        exception_table: vec![],
+        attrs: Attributes::new(AttributeLocation::Code, vec![]),
+    }
+}
+
+#[test]
+fn should_write_unchanged_code_back_as_it_was() {
+    for name in &["HelloWorld", "ControlFlow", "Point", "Features", "Constants"] {
+        // Given
+        let (bytes, mut class) = read_class(name);
+
+        // When
+        for method in class.methods.iter_mut() {
+            for attr in method.attrs.iter_mut() {
+                if let AttributeInfo::Code(ref mut code) = *attr {
+                    let list = code.insn_list().unwrap();
+                    list.write(code).unwrap();
+                }
+            }
+        }
+
+        // Then
+        let mut writer = ClassWriter::new(Vec::new());
+        writer.write_class(&class).unwrap();
+        assert!(bytes == writer.into_inner(), "{} changed", name);
+    }
+}
+
+#[test]
+fn should_shift_tables_and_frames_past_inserted_instructions() {
+    // Given
+    let (_, mut class) = read_class("ControlFlow");
+    let code = code_mut(&mut class, "parse");
+    let mut list = code.insn_list().unwrap();
+    let old_handlers: Vec<(u16, u16, u16)> = code.exception_table
+        .iter()
+        .map(|info| (info.start_pc, info.end_pc, info.handler_pc))
+        .collect();
+    let old_lines: Vec<u16> = code.attrs
+        .line_number_table()
+        .unwrap()
+        .iter()
+        .map(|entry| entry.start_pc)
+        .collect();
+    let old_frames = code.attrs.stack_map_table().unwrap().len();
+
+    // When
+    list.insert_all(0, vec![Node::Insn(Bytecode::iconst_i(0)), Node::Insn(Bytecode::pop)]);
+    list.write(code).unwrap();
+
+    // Then
+    let handlers: Vec<(u16, u16, u16)> = code.exception_table
+        .iter()
+        .map(|info| (info.start_pc - 2, info.end_pc - 2, info.handler_pc - 2))
+        .collect();
+    assert_eq!(old_handlers, handlers);
+    let lines: Vec<u16> = code.attrs
+        .line_number_table()
+        .unwrap()
+        .iter()
+        .map(|entry| entry.start_pc - 2)
+        .collect();
+    assert_eq!(old_lines, lines);
+    let local = code.attrs.local_variable_table().unwrap().iter().find(|e| e.index == 0).unwrap();
+    assert_eq!((2, code.code.len() as u16 - 2), (local.start_pc, local.length));
+    let frames = code.attrs.stack_map_table().unwrap();
+    assert_eq!(old_frames, frames.len());
+    match frames[0] {
+        StackMapFrame::SameLocals1StackItemFrame { frame_type, .. } => {
+            assert_eq!(64 + handlers[0].2 + 2, frame_type as u16)
+        }
+        ref frame => panic!("unexpected frame {:?}", frame),
+    }
+}
+
+#[test]
+fn should_realign_switches() {
+    // Given
+    let (_, mut class) = read_class("ControlFlow");
+    let code = code_mut(&mut class, "describe");
+    let old = code.instruction_list().unwrap();
+    let is_switch = |&&(_, ref insn): &&(usize, Bytecode)| insn.branch_targets(0).len() > 1;
+    let (switch_pc, switch) = old.iter().find(&is_switch).unwrap().clone();
+    let old_targets = switch.branch_targets(switch_pc);
+    let mut list = code.insn_list().unwrap();
+
+    // When
+    list.insert(0, Node::Insn(Bytecode::nop));
+    list.write(code).unwrap();
+
+    // Then
+    let new = code.instruction_list().unwrap();
+    let &(pc, ref insn) = new.iter().find(&is_switch).unwrap();
+    // The switch moves along by one byte, and its padding shrinks by one.
+    assert_eq!(switch_pc + 1, pc);
+    assert_eq!(old_targets, insn.branch_targets(pc));
+    assert_eq!(old.code_length(), new.code_length());
+}
+
+#[test]
+fn should_widen_gotos_that_cannot_reach() {
+    // Given
+    let mut code = empty_code();
+    let mut list = code.insn_list().unwrap();
+    let end = list.new_label();
+    list.insert(0, Node::Label(end));
+    let mut nodes = vec![Node::Branch(Bytecode::goto { branchoffset: 0 }, vec![end])];
+    nodes.extend((0..40000).map(|_| Node::Insn(Bytecode::nop)));
+    list.insert_all(0, nodes);
+
+    // When
+    list.write(&mut code).unwrap();
+
+    // Then
+    let instructions = code.instruction_list().unwrap();
+    assert_eq!(Some(&Bytecode::goto_w { branchoffset: 40005 }), instructions.get(0));
+    assert_eq!(Some(&Bytecode::Return), instructions.get(40001));
+}
+
+#[test]
+fn should_reject_conditional_branches_that_cannot_reach() {
+    // Given
+    let mut code = empty_code();
+    let mut list = code.insn_list().unwrap();
+    let end = list.new_label();
+    list.insert(0, Node::Label(end));
+    let mut nodes = vec![Node::Insn(Bytecode::iconst_i(0)),
+                         Node::Branch(Bytecode::ifeq { branchoffset: 0 }, vec![end])];
+    nodes.extend((0..40000).map(|_| Node::Insn(Bytecode::nop)));
+    list.insert_all(0, nodes);
+
+    // When
+    let result = list.write(&mut code);
+
+    // Then
+    match result {
+        Err(Error::CodeTooLarge) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(vec![0xb1], code.code);
+}
+
+#[test]
+fn should_reject_labels_missing_from_the_list() {
+    // Given
+    let mut code = empty_code();
+    let mut list = code.insn_list().unwrap();
+    let nowhere = list.new_label();
+    list.insert(0, Node::Branch(Bytecode::goto { branchoffset: 0 }, vec![nowhere]));
+
+    // When
+    let result = list.write(&mut code);
+
+    // Then
+    match result {
+        Err(Error::UndefinedLabel(label)) => assert_eq!(nowhere, label),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_move_type_annotation_offsets() {
+    // Given
+    let mut code = empty_code();
+    // new #1; pop; return
+    code.code = vec![0xbb, 0x00, 0x01, 0x57, 0xb1];
+    let annotation = |target_type, target_info| {
+        TypeAnnotation {
+            target_type: target_type,
+            target_info: target_info,
+            target_path: vec![],
+            type_index: 2,
+            element_value_pairs: vec![],
+        }
+    };
+    code.attrs.push(AttributeInfo::RuntimeVisibleTypeAnnotations(vec![
+        annotation(0x44, TargetInfo::Offset(0)),
+        annotation(0x40, TargetInfo::Localvar(vec![LocalvarInfo {
+            start_pc: 3,
+            length: 2,
+            index: 0,
+        }])),
+    ]));
+    let mut list = code.insn_list().unwrap();
+
+    // When
+    list.insert(0, Node::Insn(Bytecode::nop));
+    list.write(&mut code).unwrap();
+
+    // Then
+    match code.attrs[0] {
+        AttributeInfo::RuntimeVisibleTypeAnnotations(ref annotated) => {
+            assert_eq!(TargetInfo::Offset(1), annotated[0].target_info);
+            assert_eq!(TargetInfo::Localvar(vec![LocalvarInfo {
+                           start_pc: 4,
+                           length: 2,
+                           index: 0,
+                       }]),
+                       annotated[1].target_info);
+        }
+        ref other => panic!("unexpected attribute {:?}", other),
+    }
+}
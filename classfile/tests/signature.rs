@@ -0,0 +1,147 @@
+extern crate classfile;
+
+use std::fs::File;
+
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn load_features() -> ClassFile {
+    let mut file = File::open("../test-classes/Features.class").unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+#[test]
+fn should_parse_class_signature() {
+    // Given
+    let class = load_features();
+
+    // When
+    let signature = class.signature().unwrap().unwrap();
+
+    // Then
+    assert_eq!(1, signature.type_parameters.len());
+    let parameter = &signature.type_parameters[0];
+    assert_eq!("T", parameter.name);
+    assert_eq!(None, parameter.class_bound);
+    assert_eq!("java/lang/Object", signature.super_class.binary_name());
+    assert_eq!("Features<T extends java.lang.Comparable<T>> \
+                implements java.lang.Comparable<io.hcf.frappe.Features<T>>",
+               signature.java_declaration("Features", false));
+}
+
+#[test]
+fn should_declare_superinterfaces_of_interfaces_as_extended() {
+    // Given
+    let signature = ClassSignature::parse("<T:Ljava/lang/Object;>Ljava/lang/Object;\
+                                           Ljava/util/Collection<TT;>;Ljava/lang/Runnable;")
+        .unwrap();
+
+    // When
+    let declaration = signature.java_declaration("Tasks", true);
+
+    // Then
+    assert_eq!("Tasks<T> extends java.util.Collection<T>, java.lang.Runnable", declaration);
+}
+
+#[test]
+fn should_parse_method_signatures() {
+    // Given
+    let class = load_features();
+
+    // When
+    let widen = class.find_method("widen").unwrap();
+    let signature = widen.signature(&class.constants).unwrap().unwrap();
+
+    // Then
+    assert_eq!("<R extends java.lang.Number> java.util.List<? super R> \
+                widen(java.util.List<? extends R>)",
+               signature.java_declaration("widen", false));
+    let formatter = class.find_method("formatter").unwrap();
+    let signature = formatter.signature(&class.constants).unwrap().unwrap();
+    assert_eq!("java.util.function.Function<java.lang.Integer, java.lang.String> \
+                formatter(java.lang.String)",
+               signature.java_declaration("formatter", false));
+    let size = class.find_method("size").unwrap();
+    assert!(size.signature(&class.constants).is_none());
+}
+
+#[test]
+fn should_parse_field_signature() {
+    // Given
+    let class = load_features();
+
+    // When
+    let items = class.find_field("items").unwrap();
+    let signature = items.signature(&class.constants).unwrap().unwrap();
+
+    // Then
+    assert_eq!("java.util.List<T>", signature.field_type.java_name());
+}
+
+#[test]
+fn should_parse_inner_classes_wildcards_and_throws() {
+    // When
+    let signature = MethodSignature::parse("<E:Ljava/lang/Exception;K::Ljava/lang/Comparable<TK;>;\
+                                            :Ljava/io/Serializable;>\
+                                            ([Ljava/util/Map<TK;*>.Entry<TK;[I>;J)V^TE;\
+                                            ^Ljava/io/IOException;")
+        .unwrap();
+
+    // Then
+    assert_eq!(2, signature.type_parameters.len());
+    assert_eq!(2, signature.type_parameters[1].interface_bounds.len());
+    assert_eq!(vec![TypeSignature::TypeVariable("E".to_string()),
+                    TypeSignature::Class(ClassTypeSignature {
+                        package: "java/io/".to_string(),
+                        classes: vec![SimpleClassTypeSignature {
+                                          name: "IOException".to_string(),
+                                          type_arguments: vec![],
+                                      }],
+                    })],
+               signature.throws);
+    assert_eq!("<E extends java.lang.Exception, K extends java.lang.Comparable<K> & \
+                java.io.Serializable> void run(java.util.Map<K, ?>.Entry<K, int[]>[], long) \
+                throws E, java.io.IOException",
+               signature.java_declaration("run", false));
+    match signature.params[0] {
+        TypeSignature::Array(ref component) => {
+            match **component {
+                TypeSignature::Class(ref class) => {
+                    assert_eq!("java/util/Map$Entry", class.binary_name())
+                }
+                _ => panic!("expected a class type"),
+            }
+        }
+        _ => panic!("expected an array type"),
+    }
+}
+
+#[test]
+fn should_reject_invalid_signatures() {
+    for signature in &["", "Ljava/lang/Object", "<>Ljava/lang/Object;", "Ljava/util/List<>;",
+                       "<T>Ljava/lang/Object;", "Ljava//Object;"] {
+        assert!(ClassSignature::parse(signature).is_err(), "{}", signature);
+    }
+    for signature in &["()", "(V)V", "()V^I", "()VV"] {
+        assert!(MethodSignature::parse(signature).is_err(), "{}", signature);
+    }
+    for signature in &["I", "TT", "Ljava/lang/Object;I"] {
+        assert!(FieldSignature::parse(signature).is_err(), "{}", signature);
+    }
+}
+
+#[test]
+fn should_reject_deeply_nested_signatures() {
+    let deepest = format!("{}I", "[".repeat(255));
+    assert!(FieldSignature::parse(&deepest).is_ok());
+    let deep = format!("{}I", "[".repeat(256));
+    assert!(FieldSignature::parse(&deep).is_err());
+    let huge = format!("{}I", "[".repeat(100000));
+    assert!(FieldSignature::parse(&huge).is_err());
+    let nested = |depth| {
+        format!("{}Ljava/lang/Object;{}", "Ljava/util/List<".repeat(depth), ">;".repeat(depth))
+    };
+    assert!(FieldSignature::parse(&nested(255)).is_ok());
+    assert!(FieldSignature::parse(&nested(256)).is_err());
+    assert!(FieldSignature::parse(&nested(100000)).is_err());
+}
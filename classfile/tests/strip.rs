@@ -0,0 +1,148 @@
+extern crate classfile;
+
+use std::fs::File;
+
+use classfile::*;
+use classfile::reader::ClassReader;
+use classfile::strip::{self, StripPolicy};
+use classfile::writer::ClassWriter;
+
+fn load(path: &str) -> ClassFile {
+    let mut file = File::open(path).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+fn to_bytes(class: &ClassFile) -> Vec<u8> {
+    let mut writer = ClassWriter::new(Vec::new());
+    writer.write_class(class).unwrap();
+    writer.into_inner()
+}
+
+#[test]
+fn should_strip_debug_attributes() {
+    // Given
+    let mut class = load("../test-classes/Features.class");
+    let original_size = to_bytes(&class).len();
+
+    // When
+    strip::strip(&mut class, &StripPolicy::default());
+    strip::compact_constant_pool(&mut class).unwrap();
+
+    // Then
+    assert!(class.attrs.source_file(&class.constants).is_none());
+    assert!(!class.attrs.is_deprecated());
+    for method in class.methods.iter() {
+        if let Some(code) = method.attrs.code() {
+            assert!(code.attrs.line_number_table().is_none());
+            assert!(code.attrs.local_variable_table().is_none());
+            assert!(code.attrs.local_variable_type_table().is_none());
+        }
+    }
+    assert!(class.constants.find_utf8("LineNumberTable").is_none());
+    assert!(class.constants.find_utf8("Features.java").is_none());
+    let bytes = to_bytes(&class);
+    assert!(bytes.len() < original_size);
+
+    // The stripped class is still readable and keeps its signatures.
+    let reread = ClassReader::new(&bytes[..]).read_class().unwrap();
+    assert_eq!("io/hcf/frappe/Features", reread.this_class_name());
+    assert!(reread.attrs.signature(&reread.constants).is_some());
+    let method = reread.find_method("parse").unwrap();
+    assert_eq!("(Ljava/lang/String;)I",
+               reread.constants[method.descriptor_index].as_utf8());
+}
+
+#[test]
+fn should_respect_policy() {
+    // Given
+    let mut class = load("../test-classes/Point.class");
+    let mut policy = StripPolicy::none();
+    policy.source_file = true;
+
+    // When
+    strip::strip(&mut class, &policy);
+    strip::compact_constant_pool(&mut class).unwrap();
+
+    // Then
+    assert!(class.attrs.source_file(&class.constants).is_none());
+    let code = class.find_method("<init>").unwrap().attrs.code().unwrap();
+    assert_eq!(4, code.attrs.line_number_table().unwrap().len());
+}
+
+#[test]
+fn should_keep_pool_unchanged_when_nothing_is_unused() {
+    // Given
+    let mut class = load("../test-classes/HelloWorld.class");
+    let original = to_bytes(&class);
+
+    // When
+    strip::compact_constant_pool(&mut class).unwrap();
+
+    // Then
+    assert!(original == to_bytes(&class));
+}
+
+/// Adds a raw attribute named `name` to the class, holding the index of the
+/// class constant for the class itself.
+fn add_raw_attribute(class: &mut ClassFile, name: &str) {
+    let name_index = class.constants.intern(Constant::Utf8(name.to_string())).unwrap();
+    let info = vec![(class.this_class >> 8) as u8, class.this_class as u8];
+    class.attrs.push(AttributeInfo::Raw(RawAttribute {
+        name_index: name_index,
+        info: info,
+    }));
+}
+
+fn raw_attribute_names(class: &ClassFile) -> Vec<String> {
+    class.attrs
+        .iter()
+        .filter_map(|attr| match *attr {
+            AttributeInfo::Raw(ref raw) => Some(class.constants[raw.name_index].as_utf8().clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn should_keep_nest_attributes() {
+    // Given
+    let mut class = load("../test-classes/Point.class");
+    add_raw_attribute(&mut class, "NestHost");
+    add_raw_attribute(&mut class, "Custom");
+
+    // When
+    strip::strip(&mut class, &StripPolicy::default());
+
+    // Then
+    assert_eq!(vec!["NestHost".to_string()], raw_attribute_names(&class));
+}
+
+#[test]
+fn should_not_compact_pool_with_raw_attributes() {
+    // Given
+    let mut class = load("../test-classes/Features.class");
+    add_raw_attribute(&mut class, "Custom");
+    strip::strip(&mut class, &StripPolicy::none());
+    let original = to_bytes(&class);
+
+    // When
+    strip::compact_constant_pool(&mut class).unwrap();
+
+    // Then
+    assert!(original == to_bytes(&class));
+}
+
+#[test]
+fn should_compact_pool_once_raw_attributes_are_stripped() {
+    // Given
+    let mut class = load("../test-classes/Features.class");
+    add_raw_attribute(&mut class, "Custom");
+
+    // When
+    strip::strip(&mut class, &StripPolicy::default());
+    strip::compact_constant_pool(&mut class).unwrap();
+
+    // Then
+    assert!(raw_attribute_names(&class).is_empty());
+    assert!(class.constants.find_utf8("Custom").is_none());
+}
@@ -0,0 +1,43 @@
+extern crate classfile;
+
+use std::fs::File;
+use std::io::Read;
+
+use classfile::reader::ClassReader;
+use classfile::writer::ClassWriter;
+
+fn assert_round_trip(path: &str) {
+    // Given
+    let mut bytes: Vec<u8> = vec![];
+    File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+    let class = ClassReader::new(&bytes[..]).read_class().unwrap();
+
+    // When
+    let mut writer = ClassWriter::new(Vec::new());
+    writer.write_class(&class).unwrap();
+
+    // Then
+    assert!(bytes == writer.into_inner(), "{} did not round trip", path);
+}
+
+#[test]
+fn should_round_trip_hello_world() {
+    assert_round_trip("../test-classes/HelloWorld.class");
+}
+
+#[test]
+fn should_round_trip_constants() {
+    assert_round_trip("../test-classes/Constants.class");
+}
+
+#[test]
+fn should_round_trip_point() {
+    assert_round_trip("../test-classes/Point.class");
+}
+
+#[test]
+fn should_round_trip_features() {
+    assert_round_trip("../test-classes/Features.class");
+    assert_round_trip("../test-classes/Features$Inner.class");
+    assert_round_trip("../test-classes/Features$Marker.class");
+}
@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use classfile::ClassFile;
+use classfile::reader::ClassReader;
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use super::{Error, Result};
+
+/// A directory root or jar file on a class path.
+enum Entry {
+    Directory(PathBuf),
+    /// An opened jar. Its central directory is read when it is added, its
+    /// classes only when they are looked up.
+    Jar(PathBuf, RefCell<ZipArchive<File>>),
+}
+
+/// An ordered list of directory roots and jar files in which to look for
+/// classes by binary name.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use classpath::ClassPath;
+///
+/// let classpath = ClassPath::parse("classes:lib/rt.jar").unwrap();
+/// let string = classpath.load("java/lang/String").unwrap();
+/// assert_eq!("java/lang/String", string.this_class_name());
+/// ```
+pub struct ClassPath {
+    entries: Vec<Entry>,
+}
+
+impl ClassPath {
+    /// Creates an empty class path, on which no class is found.
+    pub fn new() -> ClassPath {
+        ClassPath { entries: vec![] }
+    }
+
+    /// Parses a list of paths separated as in the `PATH` environment
+    /// variable, by `:` on Unix.
+    ///
+    /// As with the `java` launcher, entries that do not exist are skipped.
+    pub fn parse(paths: &str) -> Result<ClassPath> {
+        let mut classpath = ClassPath::new();
+        for path in env::split_paths(paths) {
+            if path.exists() {
+                try!(classpath.push(path));
+            }
+        }
+        Ok(classpath)
+    }
+
+    /// Adds a directory root or jar file to the end of the class path.
+    pub fn push<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref().to_path_buf();
+        let entry = if try!(path.metadata()).is_dir() {
+            Entry::Directory(path)
+        } else {
+            let archive = try!(ZipArchive::new(try!(File::open(&path))));
+            Entry::Jar(path, RefCell::new(archive))
+        };
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Returns the bytes of the class file for the binary name `name`, such
+    /// as `java/lang/String`, from the first entry that has one.
+    pub fn find(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        if !is_binary_name(name) {
+            return Ok(None);
+        }
+        let file_name = format!("{}.class", name);
+        for entry in &self.entries {
+            let mut bytes = vec![];
+            match *entry {
+                Entry::Directory(ref root) => {
+                    match File::open(root.join(&file_name)) {
+                        Ok(mut file) => try!(file.read_to_end(&mut bytes)),
+                        Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                        Err(err) => return Err(Error::Io(err)),
+                    };
+                }
+                Entry::Jar(_, ref archive) => {
+                    let mut archive = archive.borrow_mut();
+                    match archive.by_name(&file_name) {
+                        Ok(mut file) => try!(file.read_to_end(&mut bytes)),
+                        Err(ZipError::FileNotFound) => continue,
+                        Err(err) => return Err(Error::Zip(err)),
+                    };
+                }
+            }
+            return Ok(Some(bytes));
+        }
+        Ok(None)
+    }
+
+    /// Finds and parses the class with the binary name `name`, checking that
+    /// it is the class the file declares.
+    ///
+    /// Classes are not cached; see `ClassLoaders` for that.
+    pub fn load(&self, name: &str) -> Result<ClassFile> {
+        match try!(self.find(name)) {
+            Some(bytes) => parse_class(name, &bytes),
+            None => Err(Error::NotFound(name.to_string())),
+        }
+    }
+}
+
+impl fmt::Debug for ClassPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let paths: Vec<&PathBuf> = self.entries
+            .iter()
+            .map(|entry| match *entry {
+                Entry::Directory(ref path) |
+                Entry::Jar(ref path, _) => path,
+            })
+            .collect();
+        f.debug_tuple("ClassPath").field(&paths).finish()
+    }
+}
+
+/// Parses the class file `bytes`, which must declare the class `name`.
+pub fn parse_class(name: &str, bytes: &[u8]) -> Result<ClassFile> {
+    let class = try!(ClassReader::new(bytes).read_class());
+    if class.this_class_name() != name {
+        return Err(Error::WrongName {
+            name: name.to_string(),
+            found: class.this_class_name().clone(),
+        });
+    }
+    Ok(class)
+}
+
+/// Returns `true` if `name` is a binary name in internal form, made of
+/// non-empty identifiers separated by `/`. Anything else could not name a
+/// class, and might name a file outside of a directory root.
+fn is_binary_name(name: &str) -> bool {
+    name.split('/').all(|part| !part.is_empty() && !part.contains(|c| ".;[\\".contains(c)))
+}
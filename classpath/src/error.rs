@@ -0,0 +1,91 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+use classfile;
+use zip::result::ZipError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A class file was found, but could not be parsed.
+    ClassFile(classfile::Error),
+    Io(io::Error),
+    /// A jar file could not be read.
+    Zip(ZipError),
+    /// No class with this binary name is on the class path.
+    NotFound(String),
+    /// The class file found for `name` declares a different class, `found`,
+    /// as `this_class`.
+    WrongName { name: String, found: String },
+    /// The loader has already loaded a class with this name, so it cannot
+    /// define another.
+    DuplicateDefinition(String),
+    /// Field resolution found no field with this name and descriptor.
+    NoSuchField { class: String, name: String, descriptor: String },
+    /// Method resolution found no method with this name and descriptor.
+    NoSuchMethod { class: String, name: String, descriptor: String },
+    /// A reference expected a class but found an interface, or the other
+    /// way around.
+    IncompatibleClassChange(String),
+    /// A referenced class or member is not accessible from the referencing
+    /// class.
+    IllegalAccess(String),
+    /// The class is its own superclass or superinterface, directly or
+    /// through other classes.
+    ClassCircularity(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ClassFile(ref err) => write!(f, "{}", err),
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Zip(ref err) => write!(f, "{}", err),
+            Error::NotFound(ref name) => write!(f, "class {} not found", name),
+            Error::WrongName { ref name, ref found } => {
+                write!(f, "{} (wrong name: {})", name, found)
+            }
+            Error::DuplicateDefinition(ref name) => {
+                write!(f, "duplicate class definition: {}", name)
+            }
+            Error::NoSuchField { ref class, ref name, ref descriptor } => {
+                write!(f, "no such field {}.{}:{}", class, name, descriptor)
+            }
+            Error::NoSuchMethod { ref class, ref name, ref descriptor } => {
+                write!(f, "no such method {}.{}{}", class, name, descriptor)
+            }
+            Error::IncompatibleClassChange(ref message) => {
+                write!(f, "incompatible class change: {}", message)
+            }
+            Error::IllegalAccess(ref message) => write!(f, "illegal access: {}", message),
+            Error::ClassCircularity(ref name) => write!(f, "class circularity: {}", name),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "class loading or resolution error"
+    }
+}
+
+impl From<classfile::Error> for Error {
+    fn from(err: classfile::Error) -> Error {
+        Error::ClassFile(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(err: ZipError) -> Error {
+        Error::Zip(err)
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
@@ -0,0 +1,19 @@
+//! Finding and loading classes by name.
+//!
+//! A `ClassPath` resolves binary names such as `java/lang/String` to class
+//! files in directory roots and jar files. `ClassLoaders` builds a tree of
+//! loaders on top, each caching the classes it loads and delegating to its
+//! parent first, as the JVM's class loaders do. The loaders also resolve
+//! the field and method references in the constant pools of the classes
+//! they load to the members they refer to.
+extern crate classfile;
+extern crate zip;
+
+mod class_path;
+pub use self::class_path::*;
+mod loader;
+pub use self::loader::*;
+mod resolve;
+pub use self::resolve::*;
+mod error;
+pub use self::error::*;
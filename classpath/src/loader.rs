@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use classfile::ClassFile;
+
+use super::{ClassPath, Error, Result, parse_class};
+
+/// Identifies a loader within its `ClassLoaders`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LoaderId(usize);
+
+/// A class, together with the loader that defined it.
+///
+/// As in the JVM, a class is identified by its name and its defining loader:
+/// two loaders that each define a class from the same file define two
+/// distinct classes.
+#[derive(Debug)]
+pub struct Class {
+    pub file: ClassFile,
+    loader: LoaderId,
+}
+
+impl Class {
+    /// The binary name of the class, such as `java/lang/String`.
+    pub fn name(&self) -> &str {
+        self.file.this_class_name()
+    }
+
+    /// The loader that defined the class.
+    pub fn loader(&self) -> LoaderId {
+        self.loader
+    }
+}
+
+struct Loader {
+    parent: Option<LoaderId>,
+    classpath: ClassPath,
+    /// The classes this loader has defined, or has returned after delegating
+    /// to its parent, by name.
+    classes: HashMap<String, Rc<Class>>,
+}
+
+/// A tree of class loaders, each with its own class path, that load classes
+/// lazily and cache them.
+///
+/// A loader asked for a class first delegates to its parent, and only looks
+/// on its own class path if the parent cannot find the class. Once it has
+/// returned a class for a name, it returns the same class for that name from
+/// then on.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::rc::Rc;
+///
+/// use classpath::{ClassLoaders, ClassPath};
+///
+/// let mut loaders = ClassLoaders::new();
+/// let boot = loaders.add(None, ClassPath::parse("rt.jar").unwrap());
+/// let app = loaders.add(Some(boot), ClassPath::parse("classes").unwrap());
+/// let string = loaders.load(app, "java/lang/String").unwrap();
+/// assert_eq!(boot, string.loader());
+/// assert!(Rc::ptr_eq(&string, &loaders.load(boot, "java/lang/String").unwrap()));
+/// ```
+pub struct ClassLoaders {
+    loaders: Vec<Loader>,
+}
+
+impl ClassLoaders {
+    pub fn new() -> ClassLoaders {
+        ClassLoaders { loaders: vec![] }
+    }
+
+    /// Adds a loader that looks for classes on `classpath`, after delegating
+    /// to `parent`, if it has one.
+    pub fn add(&mut self, parent: Option<LoaderId>, classpath: ClassPath) -> LoaderId {
+        self.loaders.push(Loader {
+            parent: parent,
+            classpath: classpath,
+            classes: HashMap::new(),
+        });
+        LoaderId(self.loaders.len() - 1)
+    }
+
+    /// The parent that `loader` delegates to.
+    ///
+    /// # Panics
+    /// If `loader` was not added to these loaders.
+    pub fn parent(&self, loader: LoaderId) -> Option<LoaderId> {
+        self.loaders[loader.0].parent
+    }
+
+    /// Returns the class that `loader` has already loaded as `name`, without
+    /// loading it.
+    ///
+    /// # Panics
+    /// If `loader` was not added to these loaders.
+    pub fn find_loaded(&self, loader: LoaderId, name: &str) -> Option<Rc<Class>> {
+        self.loaders[loader.0].classes.get(name).cloned()
+    }
+
+    /// Loads the class with the binary name `name` through `loader`.
+    ///
+    /// # Panics
+    /// If `loader` was not added to these loaders.
+    pub fn load(&mut self, loader: LoaderId, name: &str) -> Result<Rc<Class>> {
+        if let Some(class) = self.find_loaded(loader, name) {
+            return Ok(class);
+        }
+        if let Some(parent) = self.parent(loader) {
+            match self.load(parent, name) {
+                Ok(class) => {
+                    self.loaders[loader.0].classes.insert(name.to_string(), class.clone());
+                    return Ok(class);
+                }
+                Err(Error::NotFound(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let bytes = match try!(self.loaders[loader.0].classpath.find(name)) {
+            Some(bytes) => bytes,
+            None => return Err(Error::NotFound(name.to_string())),
+        };
+        let class = try!(parse_class(name, &bytes));
+        self.insert(loader, class)
+    }
+
+    /// Defines a class from the class file `bytes` with `loader` as its
+    /// defining loader, without delegating to the parent.
+    ///
+    /// Fails if the class file does not declare the class `name`, or if
+    /// `loader` has already loaded a class with that name.
+    ///
+    /// # Panics
+    /// If `loader` was not added to these loaders.
+    pub fn define(&mut self, loader: LoaderId, name: &str, bytes: &[u8]) -> Result<Rc<Class>> {
+        if self.find_loaded(loader, name).is_some() {
+            return Err(Error::DuplicateDefinition(name.to_string()));
+        }
+        let class = try!(parse_class(name, bytes));
+        self.insert(loader, class)
+    }
+
+    /// Defines the already parsed class `file` with `loader` as its defining
+    /// loader, as `define` does for a class file's bytes.
+    ///
+    /// # Panics
+    /// If `loader` was not added to these loaders.
+    pub fn define_class(&mut self, loader: LoaderId, file: ClassFile) -> Result<Rc<Class>> {
+        if self.find_loaded(loader, file.this_class_name()).is_some() {
+            return Err(Error::DuplicateDefinition(file.this_class_name().clone()));
+        }
+        self.insert(loader, file)
+    }
+
+    fn insert(&mut self, loader: LoaderId, file: ClassFile) -> Result<Rc<Class>> {
+        let class = Rc::new(Class {
+            file: file,
+            loader: loader,
+        });
+        self.loaders[loader.0].classes.insert(class.name().to_string(), class.clone());
+        Ok(class)
+    }
+}
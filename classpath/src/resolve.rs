@@ -0,0 +1,547 @@
+//! Resolution of symbolic references to classes, fields and methods, as in
+//! [JVMS 5.4.3](https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.4.3).
+use std::rc::Rc;
+
+use classfile::{AttributeInfo, Constant, ConstantPool, FieldInfo, MethodInfo};
+
+use super::{Class, ClassLoaders, Error, Result};
+
+/// A method found by resolving a symbolic reference.
+#[derive(Clone, Debug)]
+pub struct ResolvedMethod {
+    /// The class or interface that declares the method.
+    pub class: Rc<Class>,
+    /// The index of the method in the class's `methods`.
+    pub index: usize,
+}
+
+impl ResolvedMethod {
+    pub fn info(&self) -> &MethodInfo {
+        &self.class.file.methods[self.index]
+    }
+
+    pub fn name(&self) -> &str {
+        self.class.file.constants[self.info().name_index].as_utf8()
+    }
+
+    pub fn descriptor(&self) -> &str {
+        self.class.file.constants[self.info().descriptor_index].as_utf8()
+    }
+}
+
+/// A field found by resolving a symbolic reference.
+#[derive(Clone, Debug)]
+pub struct ResolvedField {
+    /// The class or interface that declares the field.
+    pub class: Rc<Class>,
+    /// The index of the field in the class's `fields`.
+    pub index: usize,
+}
+
+impl ResolvedField {
+    pub fn info(&self) -> &FieldInfo {
+        &self.class.file.fields[self.index]
+    }
+
+    pub fn name(&self) -> &str {
+        self.class.file.constants[self.info().name_index].as_utf8()
+    }
+
+    pub fn descriptor(&self) -> &str {
+        self.class.file.constants[self.info().descriptor_index].as_utf8()
+    }
+}
+
+/// The accessibility of a class member.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Access {
+    Public,
+    Protected,
+    Package,
+    Private,
+}
+
+impl ClassLoaders {
+    /// Resolves a class by name on behalf of `from`, loading it through the
+    /// defining loader of `from` and checking that `from` may access it.
+    ///
+    /// Array classes are not `Class`es, and are not found.
+    pub fn resolve_class(&mut self, from: &Class, name: &str) -> Result<Rc<Class>> {
+        let class = try!(self.load(from.loader(), name));
+        if !class.file.access_flags.is_public() && !same_package(from, &class) {
+            return Err(Error::IllegalAccess(format!("class {} cannot access class {}",
+                                                    from.name(),
+                                                    class.name())));
+        }
+        Ok(class)
+    }
+
+    /// Resolves the `Fieldref` at `index` in the constant pool of `from`.
+    ///
+    /// The field is looked for in the referenced class, then in its
+    /// superinterfaces, then in its superclass and the superclass's
+    /// superinterfaces, and so on.
+    pub fn resolve_field(&mut self, from: &Class, index: u16) -> Result<ResolvedField> {
+        let (owner, name, descriptor) = match from.file.constants.get(index) {
+            Some(&Constant::Fieldref(ref field)) => {
+                try!(member_ref(&from.file.constants,
+                                field.class_index,
+                                field.name_and_type_index))
+            }
+            _ => return Err(invalid_reference(index)),
+        };
+        let class = try!(self.resolve_class(from, &owner));
+        let field = match try!(self.lookup_field(&class, &name, &descriptor)) {
+            Some(field) => field,
+            None => {
+                return Err(Error::NoSuchField {
+                    class: owner,
+                    name: name,
+                    descriptor: descriptor,
+                })
+            }
+        };
+        let flags = field.info().access_flags;
+        let access = if flags.is_public() {
+            Access::Public
+        } else if flags.is_protected() {
+            Access::Protected
+        } else if flags.is_private() {
+            Access::Private
+        } else {
+            Access::Package
+        };
+        if !try!(self.is_accessible(from, &field.class, access)) {
+            return Err(Error::IllegalAccess(format!("class {} cannot access field {}.{}",
+                                                    from.name(),
+                                                    field.class.name(),
+                                                    name)));
+        }
+        Ok(field)
+    }
+
+    /// Resolves the `Methodref` or `InterfaceMethodref` at `index` in the
+    /// constant pool of `from`.
+    ///
+    /// A `Methodref` must refer to a class and an `InterfaceMethodref` to an
+    /// interface. Methods of array classes are looked up in
+    /// `java/lang/Object`.
+    pub fn resolve_method(&mut self, from: &Class, index: u16) -> Result<ResolvedMethod> {
+        let cp = &from.file.constants;
+        let ((owner, name, descriptor), interface) = match cp.get(index) {
+            Some(&Constant::Methodref(ref method)) => {
+                (try!(member_ref(cp, method.class_index, method.name_and_type_index)), false)
+            }
+            Some(&Constant::InterfaceMethodref(ref method)) => {
+                (try!(member_ref(cp, method.class_index, method.name_and_type_index)), true)
+            }
+            _ => return Err(invalid_reference(index)),
+        };
+        let array = owner.starts_with('[');
+        let class = if array {
+            let element = owner.trim_start_matches('[');
+            if element.starts_with('L') && element.ends_with(';') {
+                try!(self.resolve_class(from, &element[1..element.len() - 1]));
+            }
+            try!(self.load(from.loader(), "java/lang/Object"))
+        } else {
+            try!(self.resolve_class(from, &owner))
+        };
+        let method = if interface {
+            try!(self.lookup_interface_method(&class, &name, &descriptor))
+        } else {
+            try!(self.lookup_method(&class, &name, &descriptor))
+        };
+        let method = match method {
+            Some(method) => method,
+            None => {
+                return Err(Error::NoSuchMethod {
+                    class: owner,
+                    name: name,
+                    descriptor: descriptor,
+                })
+            }
+        };
+        let flags = method.info().access_flags;
+        let access = if flags.is_public() || array && name == "clone" {
+            // Arrays override `clone` with a public method.
+            Access::Public
+        } else if flags.is_protected() {
+            Access::Protected
+        } else if flags.is_private() {
+            Access::Private
+        } else {
+            Access::Package
+        };
+        if !try!(self.is_accessible(from, &method.class, access)) {
+            return Err(Error::IllegalAccess(format!("class {} cannot access method {}.{}{}",
+                                                    from.name(),
+                                                    method.class.name(),
+                                                    name,
+                                                    descriptor)));
+        }
+        Ok(method)
+    }
+
+    /// Looks up a method of the class `class` (JVMS 5.4.3.3), without
+    /// checking access: first in the class and its superclasses, then among
+    /// the methods of its superinterfaces.
+    ///
+    /// A signature polymorphic method of `MethodHandle` or `VarHandle` is
+    /// found whatever the descriptor.
+    pub fn lookup_method(&mut self,
+                         class: &Rc<Class>,
+                         name: &str,
+                         descriptor: &str)
+                         -> Result<Option<ResolvedMethod>> {
+        if class.file.access_flags.is_interface() {
+            return Err(Error::IncompatibleClassChange(format!("found interface {}, but class \
+                                                               was expected",
+                                                              class.name())));
+        }
+        for class in try!(self.class_chain(class)) {
+            let found = signature_polymorphic(&class, name)
+                .or_else(|| declared_method(&class, name, descriptor));
+            if let Some(index) = found {
+                return Ok(Some(ResolvedMethod {
+                    class: class,
+                    index: index,
+                }));
+            }
+        }
+        self.superinterface_method(class, name, descriptor)
+    }
+
+    /// Looks up a method of the interface `interface` (JVMS 5.4.3.4), without
+    /// checking access: first in the interface, then among the public
+    /// instance methods of `java/lang/Object`, then among the methods of its
+    /// superinterfaces.
+    pub fn lookup_interface_method(&mut self,
+                                   interface: &Rc<Class>,
+                                   name: &str,
+                                   descriptor: &str)
+                                   -> Result<Option<ResolvedMethod>> {
+        if !interface.file.access_flags.is_interface() {
+            return Err(Error::IncompatibleClassChange(format!("found class {}, but interface \
+                                                               was expected",
+                                                              interface.name())));
+        }
+        if let Some(index) = declared_method(interface, name, descriptor) {
+            return Ok(Some(ResolvedMethod {
+                class: interface.clone(),
+                index: index,
+            }));
+        }
+        // The superclass of an interface is `java/lang/Object`.
+        if let Some(object) = try!(self.superclass(interface)) {
+            if let Some(index) = declared_method(&object, name, descriptor) {
+                let flags = object.file.methods[index].access_flags;
+                if flags.is_public() && !flags.is_static() {
+                    return Ok(Some(ResolvedMethod {
+                        class: object,
+                        index: index,
+                    }));
+                }
+            }
+        }
+        self.superinterface_method(interface, name, descriptor)
+    }
+
+    /// Looks up a field of `class` (JVMS 5.4.3.2), without checking access.
+    pub fn lookup_field(&mut self,
+                        class: &Rc<Class>,
+                        name: &str,
+                        descriptor: &str)
+                        -> Result<Option<ResolvedField>> {
+        self.lookup_field_from(class, name, descriptor, &mut vec![])
+    }
+
+    /// Looks up a field of `class`, which `path` is the chain of subclasses
+    /// and subinterfaces the lookup came through.
+    fn lookup_field_from(&mut self,
+                         class: &Rc<Class>,
+                         name: &str,
+                         descriptor: &str,
+                         path: &mut Vec<Rc<Class>>)
+                         -> Result<Option<ResolvedField>> {
+        if contains(path, class) {
+            return Err(Error::ClassCircularity(class.name().to_string()));
+        }
+        let cp = &class.file.constants;
+        let declared = class.file.fields.iter().position(|field| {
+            cp[field.name_index].as_utf8() == name &&
+            cp[field.descriptor_index].as_utf8() == descriptor
+        });
+        if let Some(index) = declared {
+            return Ok(Some(ResolvedField {
+                class: class.clone(),
+                index: index,
+            }));
+        }
+        path.push(class.clone());
+        for interface in try!(self.interfaces(class)) {
+            if let Some(field) = try!(self.lookup_field_from(&interface, name, descriptor, path)) {
+                return Ok(Some(field));
+            }
+        }
+        let field = match try!(self.superclass(class)) {
+            Some(superclass) => try!(self.lookup_field_from(&superclass, name, descriptor, path)),
+            None => None,
+        };
+        path.pop();
+        Ok(field)
+    }
+
+    /// Loads the superclass of `class`, through its defining loader.
+    pub fn superclass(&mut self, class: &Class) -> Result<Option<Rc<Class>>> {
+        match class.file.super_class_name() {
+            Some(name) => Ok(Some(try!(self.load(class.loader(), name)))),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads the direct superinterfaces of `class`, through its defining
+    /// loader.
+    pub fn interfaces(&mut self, class: &Class) -> Result<Vec<Rc<Class>>> {
+        let cp = &class.file.constants;
+        let mut interfaces = vec![];
+        for &index in &class.file.interfaces {
+            let name = try!(cp.class_name(index).ok_or_else(|| invalid_reference(index)));
+            interfaces.push(try!(self.load(class.loader(), name)));
+        }
+        Ok(interfaces)
+    }
+
+    /// Returns `class` followed by its superclasses, nearest first.
+    ///
+    /// Fails with `ClassCircularity` if a class is its own superclass.
+    fn class_chain(&mut self, class: &Rc<Class>) -> Result<Vec<Rc<Class>>> {
+        let mut chain = vec![class.clone()];
+        while let Some(superclass) = try!(self.superclass(chain.last().unwrap())) {
+            if contains(&chain, &superclass) {
+                return Err(Error::ClassCircularity(superclass.name().to_string()));
+            }
+            chain.push(superclass);
+        }
+        Ok(chain)
+    }
+
+    /// Returns every interface that `class` implements or extends, directly
+    /// or through its superclasses or other interfaces, each once.
+    fn superinterfaces(&mut self, class: &Rc<Class>) -> Result<Vec<Rc<Class>>> {
+        let mut found = vec![];
+        for class in try!(self.class_chain(class)) {
+            for interface in try!(self.interfaces(&class)) {
+                try!(self.collect_interfaces(interface, &mut vec![], &mut found));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Adds `interface` and then its superinterfaces, depth first, to `found`
+    /// unless they are there already. `path` holds the subinterfaces the
+    /// search came through, and fails with `ClassCircularity` if `interface`
+    /// is among them.
+    fn collect_interfaces(&mut self,
+                          interface: Rc<Class>,
+                          path: &mut Vec<Rc<Class>>,
+                          found: &mut Vec<Rc<Class>>)
+                          -> Result<()> {
+        if contains(path, &interface) {
+            return Err(Error::ClassCircularity(interface.name().to_string()));
+        }
+        if contains(found, &interface) {
+            return Ok(());
+        }
+        found.push(interface.clone());
+        path.push(interface.clone());
+        for superinterface in try!(self.interfaces(&interface)) {
+            try!(self.collect_interfaces(superinterface, path, found));
+        }
+        path.pop();
+        Ok(())
+    }
+
+    /// Chooses among the instance methods that the superinterfaces of `class`
+    /// declare with this name and descriptor: the only non-abstract one of
+    /// the maximally-specific methods if there is exactly one, and otherwise
+    /// the first maximally-specific one.
+    fn superinterface_method(&mut self,
+                             class: &Rc<Class>,
+                             name: &str,
+                             descriptor: &str)
+                             -> Result<Option<ResolvedMethod>> {
+        let mut candidates = vec![];
+        for interface in try!(self.superinterfaces(class)) {
+            if let Some(index) = declared_method(&interface, name, descriptor) {
+                let flags = interface.file.methods[index].access_flags;
+                if !flags.is_private() && !flags.is_static() {
+                    let supers = try!(self.superinterfaces(&interface));
+                    let method = ResolvedMethod {
+                        class: interface,
+                        index: index,
+                    };
+                    candidates.push((method, supers));
+                }
+            }
+        }
+        // A method is maximally specific unless another candidate is declared
+        // in a subinterface of its interface.
+        let maximal: Vec<&ResolvedMethod> = candidates.iter()
+            .filter(|&&(ref method, _)| {
+                !candidates.iter().any(|&(_, ref supers)| {
+                    supers.iter().any(|interface| Rc::ptr_eq(interface, &method.class))
+                })
+            })
+            .map(|&(ref method, _)| method)
+            .collect();
+        let concrete: Vec<&&ResolvedMethod> = maximal.iter()
+            .filter(|method| !method.info().access_flags.is_abstract())
+            .collect();
+        if concrete.len() == 1 {
+            return Ok(Some((*concrete[0]).clone()));
+        }
+        Ok(maximal.first().map(|&method| method.clone()))
+    }
+
+    /// Returns `true` if a member of `declaring` with the given access is
+    /// accessible from `from` (JVMS 5.4.4).
+    fn is_accessible(&mut self,
+                     from: &Class,
+                     declaring: &Rc<Class>,
+                     access: Access)
+                     -> Result<bool> {
+        match access {
+            Access::Public => Ok(true),
+            Access::Package => Ok(same_package(from, declaring)),
+            Access::Private => Ok(is_same(from, declaring) || are_nestmates(from, declaring)),
+            Access::Protected => {
+                if same_package(from, declaring) {
+                    return Ok(true);
+                }
+                let mut visited: Vec<Rc<Class>> = vec![];
+                let mut current = try!(self.superclass(from));
+                while let Some(class) = current {
+                    if Rc::ptr_eq(&class, declaring) {
+                        return Ok(true);
+                    }
+                    if contains(&visited, &class) {
+                        return Err(Error::ClassCircularity(class.name().to_string()));
+                    }
+                    current = try!(self.superclass(&class));
+                    visited.push(class);
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Returns the class name, member name and descriptor of a member reference.
+fn member_ref(cp: &ConstantPool,
+              class_index: u16,
+              name_and_type_index: u16)
+              -> Result<(String, String, String)> {
+    let class = try!(cp.class_name(class_index).ok_or_else(|| invalid_reference(class_index)));
+    let name_and_type = match cp.get(name_and_type_index) {
+        Some(&Constant::NameAndType(ref name_and_type)) => name_and_type,
+        _ => return Err(invalid_reference(name_and_type_index)),
+    };
+    match (cp.get(name_and_type.name_index), cp.get(name_and_type.descriptor_index)) {
+        (Some(&Constant::Utf8(ref name)), Some(&Constant::Utf8(ref descriptor))) => {
+            Ok((class.clone(), name.clone(), descriptor.clone()))
+        }
+        _ => Err(invalid_reference(name_and_type_index)),
+    }
+}
+
+/// Returns `true` if `classes` holds `class` itself.
+fn contains(classes: &[Rc<Class>], class: &Rc<Class>) -> bool {
+    classes.iter().any(|other| Rc::ptr_eq(other, class))
+}
+
+fn invalid_reference(index: u16) -> Error {
+    Error::ClassFile(::classfile::Error::InvalidConstantReference(index))
+}
+
+/// The index of the method that `class` itself declares with this name and
+/// descriptor.
+fn declared_method(class: &Class, name: &str, descriptor: &str) -> Option<usize> {
+    let cp = &class.file.constants;
+    class.file.methods.iter().position(|method| {
+        cp[method.name_index].as_utf8() == name &&
+        cp[method.descriptor_index].as_utf8() == descriptor
+    })
+}
+
+/// The index of the method `name` of `class` if it is signature polymorphic:
+/// the only method of that name in `MethodHandle` or `VarHandle`, native,
+/// variable arity and taking a single `Object[]`.
+fn signature_polymorphic(class: &Class, name: &str) -> Option<usize> {
+    if class.name() != "java/lang/invoke/MethodHandle" &&
+       class.name() != "java/lang/invoke/VarHandle" {
+        return None;
+    }
+    let cp = &class.file.constants;
+    let mut named = class.file
+        .methods
+        .iter()
+        .enumerate()
+        .filter(|&(_, method)| cp[method.name_index].as_utf8() == name);
+    match (named.next(), named.next()) {
+        (Some((index, method)), None) => {
+            let flags = method.access_flags;
+            let descriptor = cp[method.descriptor_index].as_utf8();
+            if flags.is_native() && flags.is_varargs() &&
+               descriptor.starts_with("([Ljava/lang/Object;)") {
+                Some(index)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_same(a: &Class, b: &Class) -> bool {
+    a.loader() == b.loader() && a.name() == b.name()
+}
+
+/// The package part of a binary name, which is empty for the unnamed
+/// package.
+fn package(name: &str) -> &str {
+    name.rfind('/').map_or("", |slash| &name[..slash])
+}
+
+/// Returns `true` if the classes are in the same run-time package: the same
+/// package, defined by the same loader.
+fn same_package(a: &Class, b: &Class) -> bool {
+    a.loader() == b.loader() && package(a.name()) == package(b.name())
+}
+
+/// Returns `true` if the classes belong to the same nest, going by the
+/// `NestHost` attributes of their class files.
+fn are_nestmates(a: &Class, b: &Class) -> bool {
+    same_package(a, b) && nest_host(a) == nest_host(b)
+}
+
+/// The name of the host of the nest a class belongs to, from its `NestHost`
+/// attribute, or its own name if it has none.
+fn nest_host(class: &Class) -> &str {
+    let cp = &class.file.constants;
+    for attr in class.file.attrs.iter() {
+        if let AttributeInfo::Raw(ref raw) = *attr {
+            let is_nest_host = match cp.get(raw.name_index) {
+                Some(&Constant::Utf8(ref name)) => name == "NestHost",
+                _ => false,
+            };
+            if is_nest_host && raw.info.len() == 2 {
+                let index = (raw.info[0] as u16) << 8 | raw.info[1] as u16;
+                if let Some(host) = cp.class_name(index) {
+                    return host;
+                }
+            }
+        }
+    }
+    class.name()
+}
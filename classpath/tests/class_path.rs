@@ -0,0 +1,126 @@
+extern crate classpath;
+extern crate zip;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process;
+
+use classpath::{ClassPath, Error};
+use zip::{CompressionMethod, ZipWriter};
+
+fn class_bytes(name: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    File::open(format!("../test-classes/{}.class", name)).unwrap().read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+/// Creates an empty scratch directory for the test `name`.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("classpath-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Lays out the test classes `names` in a package directory under `root`.
+fn directory(root: PathBuf, names: &[&str]) -> PathBuf {
+    fs::create_dir_all(root.join("io/hcf/frappe")).unwrap();
+    for name in names {
+        let path = root.join(format!("io/hcf/frappe/{}.class", name));
+        File::create(path).unwrap().write_all(&class_bytes(name)).unwrap();
+    }
+    root
+}
+
+/// Writes a jar holding each test class under the given entry name.
+fn jar(path: PathBuf, entries: &[(&str, &str)]) -> PathBuf {
+    let mut writer = ZipWriter::new(File::create(&path).unwrap());
+    for &(entry, name) in entries {
+        writer.start_file(entry, CompressionMethod::Deflated).unwrap();
+        writer.write_all(&class_bytes(name)).unwrap();
+    }
+    writer.finish().unwrap();
+    path
+}
+
+#[test]
+fn should_find_classes_in_directories_and_jars() {
+    // Given
+    let dir = scratch("find");
+    let classes = directory(dir.join("classes"), &["Point"]);
+    let jar = jar(dir.join("lib.jar"), &[("io/hcf/frappe/HelloWorld.class", "HelloWorld")]);
+    let paths = env::join_paths(&[classes, jar]).unwrap();
+    let classpath = ClassPath::parse(paths.to_str().unwrap()).unwrap();
+
+    // When
+    let point = classpath.load("io/hcf/frappe/Point").unwrap();
+    let hello = classpath.load("io/hcf/frappe/HelloWorld").unwrap();
+
+    // Then
+    assert_eq!("io/hcf/frappe/Point", point.this_class_name());
+    assert_eq!("io/hcf/frappe/HelloWorld", hello.this_class_name());
+    assert_eq!(Some(class_bytes("HelloWorld")),
+               classpath.find("io/hcf/frappe/HelloWorld").unwrap());
+}
+
+#[test]
+fn should_prefer_earlier_entries() {
+    // Given
+    let dir = scratch("order");
+    let classes = directory(dir.join("classes"), &["Point"]);
+    // The jar's Point is really HelloWorld, so loading it would fail.
+    let jar = jar(dir.join("lib.jar"), &[("io/hcf/frappe/Point.class", "HelloWorld")]);
+    let mut classpath = ClassPath::new();
+    classpath.push(&classes).unwrap();
+    classpath.push(&jar).unwrap();
+
+    // When
+    let point = classpath.load("io/hcf/frappe/Point");
+
+    // Then
+    assert_eq!("io/hcf/frappe/Point", point.unwrap().this_class_name());
+}
+
+#[test]
+fn should_reject_classes_under_the_wrong_name() {
+    // Given
+    let mut classpath = ClassPath::new();
+    classpath.push("../test-classes").unwrap();
+
+    // When
+    let result = classpath.load("HelloWorld");
+
+    // Then
+    match result {
+        Err(Error::WrongName { ref name, ref found }) => {
+            assert_eq!("HelloWorld", name);
+            assert_eq!("io/hcf/frappe/HelloWorld", found);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_report_missing_classes() {
+    // Given
+    let dir = scratch("missing");
+    let classes = directory(dir.join("classes"), &["Point"]);
+    let missing = dir.join("missing.jar");
+    let paths = env::join_paths(&[missing, classes]).unwrap();
+    let classpath = ClassPath::parse(paths.to_str().unwrap()).unwrap();
+
+    // When
+    let results = vec![classpath.load("io/hcf/frappe/Missing"),
+                       classpath.load("io/hcf/frappe/../frappe/Point"),
+                       classpath.load("/io/hcf/frappe/Point")];
+
+    // Then
+    for result in results {
+        match result {
+            Err(Error::NotFound(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+extern crate classpath;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::process;
+use std::rc::Rc;
+
+use classpath::{ClassLoaders, ClassPath, Error};
+
+fn class_bytes(name: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    File::open(format!("../test-classes/{}.class", name)).unwrap().read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+/// Lays out the test classes `names` in a package directory under a scratch
+/// directory for the test `name`, and returns a class path of it.
+fn classpath(name: &str, names: &[&str]) -> ClassPath {
+    let root = env::temp_dir().join(format!("loader-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("io/hcf/frappe")).unwrap();
+    for name in names {
+        let path = root.join(format!("io/hcf/frappe/{}.class", name));
+        File::create(path).unwrap().write_all(&class_bytes(name)).unwrap();
+    }
+    let mut classpath = ClassPath::new();
+    classpath.push(root).unwrap();
+    classpath
+}
+
+#[test]
+fn should_delegate_to_the_parent_first() {
+    // Given
+    let mut loaders = ClassLoaders::new();
+    let boot = loaders.add(None, classpath("boot", &["Point"]));
+    let app = loaders.add(Some(boot), classpath("app", &["Point", "HelloWorld"]));
+
+    // When
+    let point = loaders.load(app, "io/hcf/frappe/Point").unwrap();
+    let hello = loaders.load(app, "io/hcf/frappe/HelloWorld").unwrap();
+    let hidden = loaders.load(boot, "io/hcf/frappe/HelloWorld");
+
+    // Then
+    assert_eq!(boot, point.loader());
+    assert_eq!(app, hello.loader());
+    assert!(Rc::ptr_eq(&point, &loaders.load(boot, "io/hcf/frappe/Point").unwrap()));
+    assert!(Rc::ptr_eq(&hello, &loaders.load(app, "io/hcf/frappe/HelloWorld").unwrap()));
+    assert!(Rc::ptr_eq(&point, &loaders.find_loaded(app, "io/hcf/frappe/Point").unwrap()));
+    match hidden {
+        Err(Error::NotFound(ref name)) => assert_eq!("io/hcf/frappe/HelloWorld", name),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_define_distinct_classes_in_sibling_loaders() {
+    // Given
+    let mut loaders = ClassLoaders::new();
+    let first = loaders.add(None, classpath("first", &["Point"]));
+    let second = loaders.add(None, classpath("second", &["Point"]));
+
+    // When
+    let a = loaders.load(first, "io/hcf/frappe/Point").unwrap();
+    let b = loaders.load(second, "io/hcf/frappe/Point").unwrap();
+
+    // Then
+    assert_eq!(a.name(), b.name());
+    assert!(!Rc::ptr_eq(&a, &b));
+    assert_eq!((first, second), (a.loader(), b.loader()));
+}
+
+#[test]
+fn should_reject_duplicate_definitions() {
+    // Given
+    let mut loaders = ClassLoaders::new();
+    let loader = loaders.add(None, ClassPath::new());
+    let bytes = class_bytes("Point");
+    let point = loaders.define(loader, "io/hcf/frappe/Point", &bytes).unwrap();
+
+    // When
+    let result = loaders.define(loader, "io/hcf/frappe/Point", &bytes);
+
+    // Then
+    match result {
+        Err(Error::DuplicateDefinition(ref name)) => assert_eq!("io/hcf/frappe/Point", name),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(Rc::ptr_eq(&point, &loaders.load(loader, "io/hcf/frappe/Point").unwrap()));
+}
+
+#[test]
+fn should_reject_definitions_under_the_wrong_name() {
+    // Given
+    let mut loaders = ClassLoaders::new();
+    let loader = loaders.add(None, ClassPath::new());
+
+    // When
+    let result = loaders.define(loader, "io/hcf/frappe/Point", &class_bytes("HelloWorld"));
+
+    // Then
+    match result {
+        Err(Error::WrongName { ref found, .. }) => assert_eq!("io/hcf/frappe/HelloWorld", found),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(loaders.find_loaded(loader, "io/hcf/frappe/Point").is_none());
+}
@@ -0,0 +1,314 @@
+extern crate classfile;
+extern crate classpath;
+extern crate jasm;
+
+use std::rc::Rc;
+
+use classfile::Constant;
+use classfile::writer::ClassWriter;
+use classpath::{Class, ClassLoaders, ClassPath, Error, LoaderId, ResolvedField, ResolvedMethod};
+
+const OBJECT: &'static str = r#"
+.class public java/lang/Object
+.super none
+.method public native hashCode ()I
+.method protected native clone ()Ljava/lang/Object;
+"#;
+
+const METHOD_HANDLE: &'static str = r#"
+.class public abstract java/lang/invoke/MethodHandle
+.method public final native varargs invokeExact ([Ljava/lang/Object;)Ljava/lang/Object;
+"#;
+
+const A: &'static str = r#"
+.class public p/A
+.field public x I
+.field private secret I
+.method public native a ()V
+.method protected native prot ()V
+.method private native priv ()V
+.method native pkg ()V
+"#;
+
+const I: &'static str = r#"
+.class public interface abstract p/I
+.field public static final K I
+.method public abstract m ()V
+"#;
+
+const J: &'static str = r#"
+.class public interface abstract p/J
+.implements p/I
+.method public m ()V {
+    .code stack 0 locals 1 {
+        return
+    }
+}
+"#;
+
+const K: &'static str = r#"
+.class public interface abstract p/K
+.implements p/I
+"#;
+
+const B: &'static str = r#"
+.class public p/B
+.super p/A
+.implements p/J
+.implements p/K
+"#;
+
+const HIDDEN: &'static str = r#"
+.class p/Hidden
+.method public static native h ()V
+"#;
+
+/// A subclass of `p/A` in another package, referring to everything the
+/// tests resolve.
+const C: &'static str = r#"
+.class public q/C
+.super p/A
+.method static refs ()V {
+    .code stack 10 locals 10 {
+        invokevirtual Method p/B a ()V
+        invokevirtual Method p/B m ()V
+        invokevirtual Method p/B missing ()V
+        invokevirtual Method p/I m ()V
+        invokeinterface InterfaceMethod p/J hashCode ()I 1
+        invokeinterface InterfaceMethod p/A a ()V 1
+        invokevirtual Method p/A prot ()V
+        invokevirtual Method p/A priv ()V
+        invokevirtual Method p/A pkg ()V
+        invokestatic Method p/Hidden h ()V
+        invokevirtual Method java/lang/invoke/MethodHandle invokeExact (I)V
+        invokevirtual Method "[I" clone ()Ljava/lang/Object;
+        getstatic Field p/B K I
+        getfield Field p/B x I
+        getfield Field p/B secret I
+        getfield Field p/B missing I
+        return
+    }
+}
+"#;
+
+/// A class in another package that is not a subclass of `p/A`.
+const D: &'static str = r#"
+.class public q/D
+.method static refs ()V {
+    .code stack 10 locals 10 {
+        invokevirtual Method p/A prot ()V
+        return
+    }
+}
+"#;
+
+fn define(loaders: &mut ClassLoaders, loader: LoaderId, source: &str) -> Rc<Class> {
+    let class = jasm::assemble(source).unwrap();
+    let mut writer = ClassWriter::new(Vec::new());
+    writer.write_class(&class).unwrap();
+    let name = class.this_class_name().clone();
+    loaders.define(loader, &name, &writer.into_inner()).unwrap()
+}
+
+/// Defines the test classes in a single loader, returning it with `q/C`
+/// and `q/D`.
+fn classes() -> (ClassLoaders, Rc<Class>, Rc<Class>) {
+    let mut loaders = ClassLoaders::new();
+    let loader = loaders.add(None, ClassPath::new());
+    for source in &[OBJECT, METHOD_HANDLE, A, I, J, K, B, HIDDEN] {
+        define(&mut loaders, loader, source);
+    }
+    let c = define(&mut loaders, loader, C);
+    let d = define(&mut loaders, loader, D);
+    (loaders, c, d)
+}
+
+/// The index of the member reference to `owner.name` in the pool of `class`.
+fn reference(class: &Class, owner: &str, name: &str) -> u16 {
+    let cp = &class.file.constants;
+    (1..cp.len())
+        .find(|&index| {
+            let member = match cp.get(index) {
+                Some(&Constant::Fieldref(ref member)) |
+                Some(&Constant::Methodref(ref member)) |
+                Some(&Constant::InterfaceMethodref(ref member)) => member,
+                _ => return false,
+            };
+            let name_and_type = cp[member.name_and_type_index].as_name_and_type();
+            cp.class_name(member.class_index).unwrap() == owner &&
+            cp[name_and_type.name_index].as_utf8() == name
+        })
+        .unwrap()
+}
+
+fn method(loaders: &mut ClassLoaders, from: &Class, owner: &str, name: &str) -> ResolvedMethod {
+    loaders.resolve_method(from, reference(from, owner, name)).unwrap()
+}
+
+fn field(loaders: &mut ClassLoaders, from: &Class, owner: &str, name: &str) -> ResolvedField {
+    loaders.resolve_field(from, reference(from, owner, name)).unwrap()
+}
+
+#[test]
+fn should_find_methods_in_superclasses() {
+    // Given
+    let (mut loaders, c, _) = classes();
+
+    // When
+    let a = method(&mut loaders, &c, "p/B", "a");
+
+    // Then
+    assert_eq!("p/A", a.class.name());
+    assert_eq!(("a", "()V"), (a.name(), a.descriptor()));
+}
+
+#[test]
+fn should_prefer_the_maximally_specific_default_method() {
+    // Given
+    let (mut loaders, c, _) = classes();
+
+    // When
+    let m = method(&mut loaders, &c, "p/B", "m");
+
+    // Then
+    assert_eq!("p/J", m.class.name());
+    assert!(!m.info().access_flags.is_abstract());
+}
+
+#[test]
+fn should_find_object_methods_through_interfaces() {
+    // Given
+    let (mut loaders, c, _) = classes();
+
+    // When
+    let hash_code = method(&mut loaders, &c, "p/J", "hashCode");
+
+    // Then
+    assert_eq!("java/lang/Object", hash_code.class.name());
+}
+
+#[test]
+fn should_find_fields_in_superinterfaces_and_superclasses() {
+    // Given
+    let (mut loaders, c, _) = classes();
+
+    // When
+    let constant = field(&mut loaders, &c, "p/B", "K");
+    let x = field(&mut loaders, &c, "p/B", "x");
+
+    // Then
+    assert_eq!(("p/I", "K", "I"), (constant.class.name(), constant.name(), constant.descriptor()));
+    assert_eq!("p/A", x.class.name());
+}
+
+#[test]
+fn should_resolve_signature_polymorphic_methods() {
+    // Given
+    let (mut loaders, c, _) = classes();
+
+    // When
+    let invoke = method(&mut loaders, &c, "java/lang/invoke/MethodHandle", "invokeExact");
+
+    // Then
+    assert_eq!("([Ljava/lang/Object;)Ljava/lang/Object;", invoke.descriptor());
+}
+
+#[test]
+fn should_resolve_array_methods_in_object() {
+    // Given
+    let (mut loaders, c, _) = classes();
+
+    // When
+    let clone = method(&mut loaders, &c, "[I", "clone");
+
+    // Then
+    assert_eq!("java/lang/Object", clone.class.name());
+}
+
+#[test]
+fn should_allow_protected_access_from_subclasses_only() {
+    // Given
+    let (mut loaders, c, d) = classes();
+
+    // When
+    let from_subclass = loaders.resolve_method(&c, reference(&c, "p/A", "prot"));
+    let from_elsewhere = loaders.resolve_method(&d, reference(&d, "p/A", "prot"));
+
+    // Then
+    assert!(from_subclass.is_ok());
+    match from_elsewhere {
+        Err(Error::IllegalAccess(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_report_resolution_errors() {
+    // Given
+    let (mut loaders, c, _) = classes();
+    let mut resolve = |owner, name| {
+        let index = reference(&c, owner, name);
+        match c.file.constants.get(index) {
+            Some(&Constant::Fieldref(_)) => loaders.resolve_field(&c, index).map(|_| ()),
+            _ => loaders.resolve_method(&c, index).map(|_| ()),
+        }
+    };
+
+    // When
+    let results = vec![resolve("p/B", "missing"),
+                       resolve("p/I", "m"),
+                       resolve("p/A", "a"),
+                       resolve("p/A", "priv"),
+                       resolve("p/A", "pkg"),
+                       resolve("p/B", "secret"),
+                       resolve("p/Hidden", "h")];
+
+    // Then
+    let errors: Vec<String> = results.into_iter()
+        .map(|result| match result {
+            Err(Error::NoSuchMethod { .. }) => "NoSuchMethodError".to_string(),
+            Err(Error::NoSuchField { .. }) => "NoSuchFieldError".to_string(),
+            Err(Error::IncompatibleClassChange(_)) => "IncompatibleClassChangeError".to_string(),
+            Err(Error::IllegalAccess(_)) => "IllegalAccessError".to_string(),
+            other => format!("{:?}", other),
+        })
+        .collect();
+    assert_eq!(vec!["NoSuchMethodError",
+                    "IncompatibleClassChangeError",
+                    "IncompatibleClassChangeError",
+                    "IllegalAccessError",
+                    "IllegalAccessError",
+                    "IllegalAccessError",
+                    "IllegalAccessError"],
+               errors);
+}
+
+#[test]
+fn should_report_class_circularity() {
+    // Given
+    let mut loaders = ClassLoaders::new();
+    let loader = loaders.add(None, ClassPath::new());
+    define(&mut loaders, loader, OBJECT);
+    let x = define(&mut loaders, loader, ".class public r/X\n.super r/Y\n");
+    define(&mut loaders, loader, ".class public r/Y\n.super r/X\n");
+    let p = define(&mut loaders,
+                   loader,
+                   ".class public interface abstract r/P\n.implements r/Q\n");
+    define(&mut loaders,
+           loader,
+           ".class public interface abstract r/Q\n.implements r/P\n");
+
+    // When
+    let results = vec![loaders.lookup_method(&x, "missing", "()V").map(|_| ()),
+                       loaders.lookup_field(&x, "missing", "I").map(|_| ()),
+                       loaders.lookup_interface_method(&p, "missing", "()V").map(|_| ()),
+                       loaders.lookup_field(&p, "missing", "I").map(|_| ())];
+
+    // Then
+    for result in results {
+        match result {
+            Err(Error::ClassCircularity(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::error;
+use std::fmt;
+use std::result;
+
+use classfile;
+use classpath;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The class or one of its methods could not be decoded.
+    ClassFile(classfile::Error),
+    /// A class could not be loaded, for a reason other than a linkage error
+    /// that the JVM would throw as a Java exception.
+    ClassPath(classpath::Error),
+    /// The class has no method with this name and descriptor.
+    NoSuchMethod { name: String, descriptor: String },
+    /// A method was invoked in a way its declaration does not allow, such as
+    /// an instance method through `invokestatic`.
+    IncompatibleClassChange(String),
+    /// The arguments passed to a method do not match its descriptor.
+    IllegalArgument(String),
+    /// A Java exception was thrown and not caught. `class` is the internal
+    /// name of the exception class.
+    Exception { class: String, message: Option<String> },
+    /// The code at `pc` is invalid and would have been rejected by the
+    /// verifier, for example because it pops a `long` where it expects an
+    /// `int`.
+    InvalidCode { pc: usize, message: String },
+    /// The instruction at `pc` is valid, but cannot be run by the
+    /// interpreter yet.
+    Unsupported { pc: usize, message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ClassFile(ref err) => write!(f, "{}", err),
+            Error::ClassPath(ref err) => write!(f, "{}", err),
+            Error::NoSuchMethod { ref name, ref descriptor } => {
+                write!(f, "no such method {}{}", name, descriptor)
+            }
+            Error::IncompatibleClassChange(ref message) => {
+                write!(f, "incompatible class change: {}", message)
+            }
+            Error::IllegalArgument(ref message) => write!(f, "illegal argument: {}", message),
+            Error::Exception { ref class, message: Some(ref message) } => {
+                write!(f, "{}: {}", class.replace('/', "."), message)
+            }
+            Error::Exception { ref class, message: None } => {
+                write!(f, "{}", class.replace('/', "."))
+            }
+            Error::InvalidCode { pc, ref message } => write!(f, "pc {}: {}", pc, message),
+            Error::Unsupported { pc, ref message } => {
+                write!(f, "pc {}: unsupported: {}", pc, message)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "interpreter error"
+    }
+}
+
+impl From<classfile::Error> for Error {
+    fn from(err: classfile::Error) -> Error {
+        Error::ClassFile(err)
+    }
+}
+
+/// Turns the errors the JVM reports as linkage errors into the exceptions it
+/// throws for them.
+impl From<classpath::Error> for Error {
+    fn from(err: classpath::Error) -> Error {
+        let (class, message) = match err {
+            classpath::Error::NotFound(name) => ("java/lang/NoClassDefFoundError", name),
+            classpath::Error::WrongName { .. } => {
+                ("java/lang/NoClassDefFoundError", err.to_string())
+            }
+            classpath::Error::NoSuchField { .. } => ("java/lang/NoSuchFieldError", err.to_string()),
+            classpath::Error::NoSuchMethod { .. } => {
+                ("java/lang/NoSuchMethodError", err.to_string())
+            }
+            classpath::Error::IncompatibleClassChange(message) => {
+                ("java/lang/IncompatibleClassChangeError", message)
+            }
+            classpath::Error::IllegalAccess(message) => ("java/lang/IllegalAccessError", message),
+            classpath::Error::ClassCircularity(name) => ("java/lang/ClassCircularityError", name),
+            err => return Error::ClassPath(err),
+        };
+        Error::Exception {
+            class: class.to_string(),
+            message: Some(message),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
@@ -0,0 +1,186 @@
+//! The activation record of a method being interpreted.
+use std::rc::Rc;
+
+use classpath::Class;
+
+use value::{Kind, Value};
+use super::{Error, Result};
+
+/// The local variables and operand stack of one method invocation.
+///
+/// A `long` or `double` takes two local variable slots, of which only the
+/// first holds the value; the second is left empty. On the operand stack it
+/// is a single entry, but counts twice against the maximum depth.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The class that declares the method.
+    pub class: Rc<Class>,
+    /// The index of the method in its class's `methods`.
+    pub method: usize,
+    /// The pc of the instruction being run.
+    pub pc: usize,
+    locals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    /// The number of slots taken by `stack`.
+    depth: usize,
+    max_stack: usize,
+}
+
+impl Frame {
+    /// Creates a frame for the method at index `method` in `class`, with
+    /// `max_locals` empty local variables and an empty operand stack that
+    /// may grow to `max_stack` slots.
+    pub fn new(class: Rc<Class>, method: usize, max_locals: u16, max_stack: u16) -> Frame {
+        Frame {
+            class: class,
+            method: method,
+            pc: 0,
+            locals: vec![None; max_locals as usize],
+            stack: Vec::with_capacity(max_stack as usize),
+            depth: 0,
+            max_stack: max_stack as usize,
+        }
+    }
+
+    /// The local variables, with `None` for those never stored to and the
+    /// second slots of `long` and `double` values.
+    pub fn locals(&self) -> &[Option<Value>] {
+        &self.locals
+    }
+
+    /// The operand stack, from the bottom up.
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// Loads the local variable at `index`, which must hold a value of `kind`.
+    pub fn load(&self, index: u16, kind: Kind) -> Result<Value> {
+        match self.locals.get(index as usize) {
+            Some(&Some(value)) if value.kind() == kind => Ok(value),
+            Some(&Some(value)) => {
+                Err(self.invalid(format!("local {} holds {:?}, not {:?}", index, value, kind)))
+            }
+            Some(&None) => Err(self.invalid(format!("local {} is not set", index))),
+            None => Err(self.invalid(format!("local {} is out of range", index))),
+        }
+    }
+
+    /// Stores `value` to the local variable at `index`, and to the slot after
+    /// it for a `long` or `double`.
+    ///
+    /// A `long` or `double` whose second slot is overwritten becomes unusable.
+    pub fn store(&mut self, index: u16, value: Value) -> Result<()> {
+        let index = index as usize;
+        let slots = value.slots() as usize;
+        if index + slots > self.locals.len() {
+            return Err(self.invalid(format!("local {} is out of range", index)));
+        }
+        if index > 0 {
+            if let Some(previous) = self.locals[index - 1] {
+                if previous.slots() == 2 {
+                    self.locals[index - 1] = None;
+                }
+            }
+        }
+        self.locals[index] = Some(value);
+        if slots == 2 {
+            self.locals[index + 1] = None;
+        }
+        Ok(())
+    }
+
+    /// Pushes `value` onto the operand stack.
+    pub fn push(&mut self, value: Value) -> Result<()> {
+        let slots = value.slots() as usize;
+        if self.depth + slots > self.max_stack {
+            return Err(self.invalid("operand stack overflow".to_string()));
+        }
+        self.stack.push(value);
+        self.depth += slots;
+        Ok(())
+    }
+
+    /// Pops the value on top of the operand stack, whatever its kind.
+    pub fn pop(&mut self) -> Result<Value> {
+        match self.stack.pop() {
+            Some(value) => {
+                self.depth -= value.slots() as usize;
+                Ok(value)
+            }
+            None => Err(self.invalid("operand stack underflow".to_string())),
+        }
+    }
+
+    /// Pops the value on top of the operand stack, which must be of `kind`.
+    pub fn pop_kind(&mut self, kind: Kind) -> Result<Value> {
+        match self.stack.last() {
+            Some(value) if value.kind() != kind => {
+                return Err(self.invalid(format!("expected {:?} on the stack, found {:?}",
+                                                kind,
+                                                value)))
+            }
+            _ => {}
+        }
+        self.pop()
+    }
+
+    pub fn pop_int(&mut self) -> Result<i32> {
+        match try!(self.pop_kind(Kind::Int)) {
+            Value::Int(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn pop_long(&mut self) -> Result<i64> {
+        match try!(self.pop_kind(Kind::Long)) {
+            Value::Long(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn pop_float(&mut self) -> Result<f32> {
+        match try!(self.pop_kind(Kind::Float)) {
+            Value::Float(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn pop_double(&mut self) -> Result<f64> {
+        match try!(self.pop_kind(Kind::Double)) {
+            Value::Double(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Pops a value that takes a single stack slot, as the stack manipulation
+    /// instructions require for their category 1 forms.
+    pub fn pop_category1(&mut self) -> Result<Value> {
+        match self.stack.last() {
+            Some(value) if value.slots() == 2 => {
+                return Err(self.invalid(format!("expected a category 1 value, found {:?}",
+                                                value)))
+            }
+            _ => {}
+        }
+        self.pop()
+    }
+
+    /// Pops the values taking the top two stack slots: either a single
+    /// `long` or `double`, or two category 1 values. They are returned in
+    /// the order they were pushed.
+    pub fn pop_slots2(&mut self) -> Result<Vec<Value>> {
+        let top = try!(self.pop());
+        if top.slots() == 2 {
+            return Ok(vec![top]);
+        }
+        let below = try!(self.pop_category1());
+        Ok(vec![below, top])
+    }
+
+    fn invalid(&self, message: String) -> Error {
+        Error::InvalidCode {
+            pc: self.pc,
+            message: message,
+        }
+    }
+}
@@ -0,0 +1,484 @@
+//! The objects and arrays created by running code, and the garbage collector
+//! that frees them.
+use std::mem;
+use std::rc::Rc;
+
+use classfile::FieldType;
+use classpath::Class;
+
+use value::Value;
+use super::{Error, Result};
+
+/// The default limit on the bytes taken by the objects on the heap, past
+/// which allocations throw an `OutOfMemoryError`.
+pub const DEFAULT_HEAP_LIMIT: usize = 64 << 20;
+
+/// The bytes taken by an object besides its fields or elements.
+const HEADER_SIZE: usize = 16;
+
+/// A reference to an object on a `Heap`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Reference(usize);
+
+impl Reference {
+    /// The number of the slot that holds the object on the heap, which an
+    /// object allocated after this one is collected may reuse.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// The instance fields of a class, in the order they are laid out in its
+/// instances: those of its superclasses first, then its own.
+///
+/// A field is identified by the class that declares it as well as its name
+/// and descriptor, since a subclass may declare a field that hides one of
+/// its superclass's.
+#[derive(Debug)]
+pub struct Layout {
+    class: Rc<Class>,
+    fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+struct Field {
+    owner: String,
+    name: String,
+    descriptor: String,
+    /// The value of the field in a new instance.
+    default: Value,
+}
+
+impl Layout {
+    /// Lays out the instance fields of `class` after those of `superclass`,
+    /// which must be the layout of its superclass.
+    pub fn new(class: Rc<Class>, superclass: Option<&Layout>) -> Result<Layout> {
+        let mut fields = vec![];
+        if let Some(superclass) = superclass {
+            for field in &superclass.fields {
+                fields.push(Field {
+                    owner: field.owner.clone(),
+                    name: field.name.clone(),
+                    descriptor: field.descriptor.clone(),
+                    default: field.default,
+                });
+            }
+        }
+        let cp = &class.file.constants;
+        for field in class.file.fields.iter().filter(|field| !field.access_flags.is_static()) {
+            fields.push(Field {
+                owner: class.name().to_string(),
+                name: cp[field.name_index].as_utf8().clone(),
+                descriptor: cp[field.descriptor_index].as_utf8().clone(),
+                default: Value::default_of(&try!(field.descriptor(cp))),
+            });
+        }
+        Ok(Layout {
+            class: class,
+            fields: fields,
+        })
+    }
+
+    /// The class whose instances have this layout.
+    pub fn class(&self) -> &Rc<Class> {
+        &self.class
+    }
+
+    /// The number of instance fields, including inherited ones.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The index of the field declared by the class `owner` with the given
+    /// name and descriptor.
+    pub fn index_of(&self, owner: &str, name: &str, descriptor: &str) -> Option<usize> {
+        self.fields.iter().position(|field| {
+            field.owner == owner && field.name == name && field.descriptor == descriptor
+        })
+    }
+
+    /// The fields of a new instance, each holding its default value.
+    fn defaults(&self) -> Vec<Value> {
+        self.fields.iter().map(|field| field.default).collect()
+    }
+}
+
+/// An instance of a class.
+#[derive(Debug)]
+pub struct Instance {
+    pub layout: Rc<Layout>,
+    /// The values of the fields, in the order of the layout.
+    pub fields: Vec<Value>,
+}
+
+impl Instance {
+    /// Creates an instance whose fields hold their default values.
+    pub fn new(layout: Rc<Layout>) -> Instance {
+        Instance {
+            fields: layout.defaults(),
+            layout: layout,
+        }
+    }
+
+    pub fn class(&self) -> &Rc<Class> {
+        self.layout.class()
+    }
+}
+
+/// The elements of an array, stored as compactly as their type allows.
+///
+/// `boolean` arrays are stored as `Byte`s, as `baload` and `bastore` treat
+/// them alike.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Elements {
+    Byte(Vec<i8>),
+    Char(Vec<u16>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    /// The elements of an array of references, each `Null` or a `Reference`.
+    Reference(Vec<Value>),
+}
+
+/// An array, of primitive values or of references.
+#[derive(Debug)]
+pub struct Array {
+    /// The declared type of the elements.
+    pub component: FieldType,
+    pub elements: Elements,
+}
+
+impl Array {
+    /// Creates an array of `length` elements of type `component`, each
+    /// holding its default value.
+    pub fn new(component: FieldType, length: usize) -> Array {
+        let elements = match component {
+            FieldType::Boolean | FieldType::Byte => Elements::Byte(vec![0; length]),
+            FieldType::Char => Elements::Char(vec![0; length]),
+            FieldType::Short => Elements::Short(vec![0; length]),
+            FieldType::Int => Elements::Int(vec![0; length]),
+            FieldType::Long => Elements::Long(vec![0; length]),
+            FieldType::Float => Elements::Float(vec![0.0; length]),
+            FieldType::Double => Elements::Double(vec![0.0; length]),
+            FieldType::Object(_) | FieldType::Array(_) => {
+                Elements::Reference(vec![Value::Null; length])
+            }
+        };
+        Array {
+            component: component,
+            elements: elements,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self.elements {
+            Elements::Byte(ref elements) => elements.len(),
+            Elements::Char(ref elements) => elements.len(),
+            Elements::Short(ref elements) => elements.len(),
+            Elements::Int(ref elements) => elements.len(),
+            Elements::Long(ref elements) => elements.len(),
+            Elements::Float(ref elements) => elements.len(),
+            Elements::Double(ref elements) => elements.len(),
+            Elements::Reference(ref elements) => elements.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The element at `index`, widened to the value an array load pushes,
+    /// or `None` if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> Option<Value> {
+        match self.elements {
+            Elements::Byte(ref elements) => elements.get(index).map(|&e| Value::Int(e as i32)),
+            Elements::Char(ref elements) => elements.get(index).map(|&e| Value::Int(e as i32)),
+            Elements::Short(ref elements) => elements.get(index).map(|&e| Value::Int(e as i32)),
+            Elements::Int(ref elements) => elements.get(index).map(|&e| Value::Int(e)),
+            Elements::Long(ref elements) => elements.get(index).map(|&e| Value::Long(e)),
+            Elements::Float(ref elements) => elements.get(index).map(|&e| Value::Float(e)),
+            Elements::Double(ref elements) => elements.get(index).map(|&e| Value::Double(e)),
+            Elements::Reference(ref elements) => elements.get(index).cloned(),
+        }
+    }
+
+    /// Stores `value` at `index`, narrowing an `Int` to the element type as
+    /// an array store does. `boolean` arrays keep only the lowest bit.
+    ///
+    /// Returns `false` if `index` is out of bounds, or if `value` is not of
+    /// the kind the array holds.
+    pub fn set(&mut self, index: usize, value: Value) -> bool {
+        if index >= self.len() {
+            return false;
+        }
+        match (&mut self.elements, value) {
+            (&mut Elements::Byte(ref mut elements), Value::Int(value)) => {
+                elements[index] = match self.component {
+                    FieldType::Boolean => (value & 1) as i8,
+                    _ => value as i8,
+                }
+            }
+            (&mut Elements::Char(ref mut elements), Value::Int(value)) => {
+                elements[index] = value as u16
+            }
+            (&mut Elements::Short(ref mut elements), Value::Int(value)) => {
+                elements[index] = value as i16
+            }
+            (&mut Elements::Int(ref mut elements), Value::Int(value)) => elements[index] = value,
+            (&mut Elements::Long(ref mut elements), Value::Long(value)) => elements[index] = value,
+            (&mut Elements::Float(ref mut elements), Value::Float(value)) => {
+                elements[index] = value
+            }
+            (&mut Elements::Double(ref mut elements), Value::Double(value)) => {
+                elements[index] = value
+            }
+            (&mut Elements::Reference(ref mut elements), value @ Value::Null) |
+            (&mut Elements::Reference(ref mut elements), value @ Value::Reference(_)) => {
+                elements[index] = value
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// The number of bytes an array of `length` elements of type
+    /// `component` counts against the heap limit.
+    pub fn size_of(component: &FieldType, length: usize) -> usize {
+        let element_size = match *component {
+            FieldType::Boolean | FieldType::Byte => 1,
+            FieldType::Char | FieldType::Short => 2,
+            FieldType::Int | FieldType::Float => 4,
+            _ => 8,
+        };
+        HEADER_SIZE.saturating_add(length.saturating_mul(element_size))
+    }
+}
+
+/// An object on the heap.
+#[derive(Debug)]
+pub enum Object {
+    Instance(Instance),
+    Array(Array),
+}
+
+impl Object {
+    /// The number of bytes the object counts against the heap limit.
+    pub fn size(&self) -> usize {
+        match *self {
+            Object::Instance(ref instance) => HEADER_SIZE + 8 * instance.fields.len(),
+            Object::Array(ref array) => Array::size_of(&array.component, array.len()),
+        }
+    }
+
+    /// Adds the references the object holds, in its fields or elements, to
+    /// `references`.
+    fn references(&self, references: &mut Vec<Reference>) {
+        let values = match *self {
+            Object::Instance(ref instance) => &instance.fields,
+            Object::Array(Array { elements: Elements::Reference(ref elements), .. }) => elements,
+            Object::Array(_) => return,
+        };
+        for value in values {
+            if let Value::Reference(reference) = *value {
+                references.push(reference);
+            }
+        }
+    }
+}
+
+struct Slot {
+    object: Object,
+    size: usize,
+    marked: bool,
+}
+
+/// The objects created by running code.
+///
+/// Objects live until `collect` finds them unreachable from the roots it is
+/// given. The heap itself never collects: it only refuses allocations that
+/// would exceed its limit, leaving the caller to collect and retry.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate classfile;
+/// # extern crate interpreter;
+/// use classfile::FieldType;
+/// use interpreter::heap::{Array, Heap, Object};
+/// use interpreter::value::Value;
+///
+/// # fn main() {
+/// let mut heap = Heap::new(1024);
+/// let kept = heap.allocate(Object::Array(Array::new(FieldType::Int, 10))).unwrap();
+/// let garbage = heap.allocate(Object::Array(Array::new(FieldType::Int, 10))).unwrap();
+/// assert_eq!(2, heap.len());
+///
+/// assert_eq!(1, heap.collect(vec![kept]));
+/// assert_eq!(Some(Value::Int(0)), heap.array(kept).unwrap().get(9));
+/// assert!(!heap.contains(garbage));
+/// # }
+/// ```
+pub struct Heap {
+    slots: Vec<Option<Slot>>,
+    /// The indices of the empty slots, reused before new ones are added.
+    free: Vec<usize>,
+    /// The bytes taken by the objects on the heap.
+    used: usize,
+    limit: usize,
+}
+
+impl Heap {
+    /// Creates an empty heap whose objects may take up to `limit` bytes.
+    pub fn new(limit: usize) -> Heap {
+        Heap {
+            slots: vec![],
+            free: vec![],
+            used: 0,
+            limit: limit,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Sets the limit on the bytes taken by the objects on the heap. Objects
+    /// already allocated are kept even if they exceed it.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// The bytes taken by the objects on the heap.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Checks that `size` more bytes can be allocated without exceeding the
+    /// limit, or throws an `OutOfMemoryError`.
+    pub fn ensure_room(&self, size: usize) -> Result<()> {
+        if self.used.saturating_add(size) > self.limit {
+            return Err(Error::Exception {
+                class: "java/lang/OutOfMemoryError".to_string(),
+                message: Some("Java heap space".to_string()),
+            });
+        }
+        Ok(())
+    }
+
+    /// The number of objects on the heap.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds `object` to the heap, or throws an `OutOfMemoryError` if that
+    /// would exceed the limit.
+    pub fn allocate(&mut self, object: Object) -> Result<Reference> {
+        let size = object.size();
+        try!(self.ensure_room(size));
+        self.used += size;
+        let slot = Some(Slot {
+            object: object,
+            size: size,
+            marked: false,
+        });
+        match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = slot;
+                Ok(Reference(index))
+            }
+            None => {
+                self.slots.push(slot);
+                Ok(Reference(self.slots.len() - 1))
+            }
+        }
+    }
+
+    /// Returns `true` if `reference` refers to an object that has not been
+    /// collected.
+    pub fn contains(&self, reference: Reference) -> bool {
+        match self.slots.get(reference.0) {
+            Some(&Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// The object `reference` refers to.
+    ///
+    /// # Panics
+    /// If the object has been collected.
+    pub fn get(&self, reference: Reference) -> &Object {
+        match self.slots[reference.0] {
+            Some(ref slot) => &slot.object,
+            None => panic!("{:?} has been collected", reference),
+        }
+    }
+
+    /// The object `reference` refers to.
+    ///
+    /// # Panics
+    /// If the object has been collected.
+    pub fn get_mut(&mut self, reference: Reference) -> &mut Object {
+        match self.slots[reference.0] {
+            Some(ref mut slot) => &mut slot.object,
+            None => panic!("{:?} has been collected", reference),
+        }
+    }
+
+    /// The instance `reference` refers to, or `None` if it is an array.
+    pub fn instance(&self, reference: Reference) -> Option<&Instance> {
+        match *self.get(reference) {
+            Object::Instance(ref instance) => Some(instance),
+            Object::Array(_) => None,
+        }
+    }
+
+    /// The array `reference` refers to, or `None` if it is an instance.
+    pub fn array(&self, reference: Reference) -> Option<&Array> {
+        match *self.get(reference) {
+            Object::Array(ref array) => Some(array),
+            Object::Instance(_) => None,
+        }
+    }
+
+    /// Frees the objects that cannot be reached from `roots`, and returns
+    /// how many were freed.
+    ///
+    /// Reachable objects are marked by following the references in their
+    /// fields and elements, then the unmarked ones are swept.
+    pub fn collect<I>(&mut self, roots: I) -> usize
+        where I: IntoIterator<Item = Reference>
+    {
+        let mut pending: Vec<Reference> = roots.into_iter().collect();
+        while let Some(reference) = pending.pop() {
+            let slot = match self.slots.get_mut(reference.0) {
+                Some(&mut Some(ref mut slot)) if !slot.marked => slot,
+                _ => continue,
+            };
+            slot.marked = true;
+            slot.object.references(&mut pending);
+        }
+        let mut freed = 0;
+        for (index, entry) in self.slots.iter_mut().enumerate() {
+            let live = match *entry {
+                Some(ref mut slot) => mem::replace(&mut slot.marked, false),
+                None => continue,
+            };
+            if !live {
+                self.used -= entry.take().unwrap().size;
+                self.free.push(index);
+                freed += 1;
+            }
+        }
+        freed
+    }
+}
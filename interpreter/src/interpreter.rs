@@ -0,0 +1,1698 @@
+//! Runs methods by interpreting their bytecode.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use classfile::*;
+use classpath::{Class, ClassLoaders, LoaderId, ResolvedField, ResolvedMethod};
+
+use frame::Frame;
+use heap::{Array, DEFAULT_HEAP_LIMIT, Elements, Heap, Instance, Layout, Object, Reference};
+use natives::{NativeMethod, Natives};
+use value::{Kind, Value};
+use super::{Error, Result};
+
+/// The default limit on the number of frames, past which invocations throw
+/// a `StackOverflowError`.
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+/// Identifies a class by its address. Loaders keep the classes they load
+/// for as long as they live, so the address of a class is never reused.
+type ClassKey = *const Class;
+
+fn key(class: &Class) -> ClassKey {
+    class as *const Class
+}
+
+/// The decoded code of a method.
+struct Method {
+    instructions: InstructionList,
+    max_stack: u16,
+    max_locals: u16,
+    /// The kind of the return value, or `None` for `void` methods.
+    ret: Option<Kind>,
+}
+
+/// How far a class has got with running its static initializer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Initialization {
+    /// The initializer is running. Uses of the class while it runs, by the
+    /// initializer itself or the methods it calls, do not wait for it.
+    Running,
+    Done,
+    /// The initializer threw an exception, so the class cannot be used.
+    Failed,
+}
+
+/// What to do once an instruction has run.
+enum Flow {
+    /// Continue with the following instruction.
+    Next,
+    /// Continue with the instruction at this pc.
+    Jump(usize),
+    /// Return from the method, with a value unless it is `void`.
+    Return(Option<Value>),
+    /// The instruction uses the heap or other classes, and is run by the
+    /// `Interpreter` itself.
+    Runtime,
+    /// A frame was pushed for an invoked method. The instruction following
+    /// the invocation runs once it returns.
+    Call,
+}
+
+/// The kinds of method invocation instructions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Invocation {
+    Static,
+    Special,
+    Virtual,
+    Interface,
+}
+
+/// Runs methods of the classes that a set of class loaders load, creating
+/// their objects on a garbage collected heap.
+///
+/// Classes are initialized as the JVM initializes them: the first time one
+/// of their static methods or fields is used or they are instantiated.
+/// Methods with an implementation registered in `natives_mut()` run that
+/// instead of their bytecode. The `runtime` module registers those of a
+/// minimal `java.lang`, and defines its classes.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate classpath;
+/// # extern crate interpreter;
+/// use classpath::{ClassLoaders, ClassPath};
+/// use interpreter::interpreter::Interpreter;
+/// use interpreter::value::Value;
+///
+/// # fn main() {
+/// let mut loaders = ClassLoaders::new();
+/// let loader = loaders.add(None, ClassPath::parse("classes").unwrap());
+/// let class = loaders.load(loader, "MathUtils").unwrap();
+/// let mut interpreter = Interpreter::new(loaders);
+/// let args = [Value::Int(12), Value::Int(18)];
+/// let result = interpreter.invoke_static(&class, "gcd", "(II)I", &args);
+/// assert_eq!(Some(Value::Int(6)), result.unwrap());
+/// # }
+/// ```
+pub struct Interpreter {
+    loaders: ClassLoaders,
+    heap: Heap,
+    /// The decoded code of the methods run so far, by class and index in its
+    /// `methods`.
+    methods: HashMap<(ClassKey, usize), Rc<Method>>,
+    /// The instance layouts of the classes instantiated so far.
+    layouts: HashMap<ClassKey, Rc<Layout>>,
+    /// The values of static fields, by class and index in its `fields`. A
+    /// field that is absent holds its default value.
+    statics: HashMap<(ClassKey, usize), Value>,
+    initialization: HashMap<ClassKey, Initialization>,
+    natives: Natives,
+    /// The interned strings, by value.
+    strings: HashMap<String, Reference>,
+    /// The arguments of the native methods being run, which are roots for
+    /// the garbage collector until they return.
+    pinned: Vec<Value>,
+    stdout: Box<Write>,
+    stderr: Box<Write>,
+    frames: Vec<Frame>,
+    max_depth: usize,
+}
+
+impl Interpreter {
+    /// Creates an interpreter for the classes that `loaders` load, with an
+    /// empty heap of `DEFAULT_HEAP_LIMIT` bytes.
+    pub fn new(loaders: ClassLoaders) -> Interpreter {
+        Interpreter {
+            loaders: loaders,
+            heap: Heap::new(DEFAULT_HEAP_LIMIT),
+            methods: HashMap::new(),
+            layouts: HashMap::new(),
+            statics: HashMap::new(),
+            initialization: HashMap::new(),
+            natives: Natives::new(),
+            strings: HashMap::new(),
+            pinned: vec![],
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            frames: vec![],
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    pub fn loaders(&self) -> &ClassLoaders {
+        &self.loaders
+    }
+
+    pub fn loaders_mut(&mut self) -> &mut ClassLoaders {
+        &mut self.loaders
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// The heap, for example to change its limit.
+    pub fn heap_mut(&mut self) -> &mut Heap {
+        &mut self.heap
+    }
+
+    pub fn natives(&self) -> &Natives {
+        &self.natives
+    }
+
+    /// The native methods, for example to register more.
+    pub fn natives_mut(&mut self) -> &mut Natives {
+        &mut self.natives
+    }
+
+    /// The stream that `System.out` writes to; the process's standard output
+    /// unless replaced.
+    pub fn stdout(&mut self) -> &mut Write {
+        &mut *self.stdout
+    }
+
+    pub fn set_stdout<W: Write + 'static>(&mut self, stdout: W) {
+        self.stdout = Box::new(stdout);
+    }
+
+    /// The stream that `System.err` writes to; the process's standard error
+    /// unless replaced.
+    pub fn stderr(&mut self) -> &mut Write {
+        &mut *self.stderr
+    }
+
+    pub fn set_stderr<W: Write + 'static>(&mut self, stderr: W) {
+        self.stderr = Box::new(stderr);
+    }
+
+    /// Sets the limit on the number of frames, past which invocations throw
+    /// a `StackOverflowError`.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// The frames of the methods being run, from the outermost in.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Runs the static method `name` with the given descriptor, declared by
+    /// `class` or one of its superclasses, and returns its result, or `None`
+    /// for a `void` method.
+    ///
+    /// The arguments must match the parameter types of the descriptor, with
+    /// one `Value` per parameter. A reference returned is only kept alive by
+    /// the garbage collector while it is reachable from the static fields or
+    /// the arguments of methods still running.
+    pub fn invoke_static(&mut self,
+                         class: &Rc<Class>,
+                         name: &str,
+                         descriptor: &str,
+                         args: &[Value])
+                         -> Result<Option<Value>> {
+        let method = match try!(self.loaders.lookup_method(class, name, descriptor)) {
+            Some(ref method) if !method.info().access_flags.is_static() => {
+                return Err(Error::IncompatibleClassChange(format!("{}{} is not static",
+                                                                  name,
+                                                                  descriptor)))
+            }
+            Some(method) => method,
+            None => {
+                return Err(Error::NoSuchMethod {
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                })
+            }
+        };
+        let params = try!(MethodDescriptor::parse(descriptor)).params;
+        let kinds = args.iter().map(|arg| arg.kind());
+        if params.len() != args.len() || !params.iter().map(Kind::of).eq(kinds) {
+            return Err(Error::IllegalArgument(format!("{:?} do not match {}", args, descriptor)));
+        }
+        try!(self.initialize(&method.class));
+        self.invoke_method(&method, args)
+    }
+
+    /// Runs the instance method `name` with the given descriptor on
+    /// `receiver`, selecting it from the class of the receiver as
+    /// `invokevirtual` does, and returns its result.
+    ///
+    /// The arguments do not include the receiver, and are not checked
+    /// against the descriptor.
+    pub fn invoke_virtual(&mut self,
+                          receiver: Reference,
+                          name: &str,
+                          descriptor: &str,
+                          args: &[Value])
+                          -> Result<Option<Value>> {
+        let class = try!(self.class_of(receiver));
+        let method = match try!(self.loaders.lookup_method(&class, name, descriptor)) {
+            Some(method) => method,
+            None => {
+                return Err(Error::NoSuchMethod {
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                })
+            }
+        };
+        let method = try!(self.check_selected(Some(method.clone()), &method));
+        let mut all = vec![Value::Reference(receiver)];
+        all.extend_from_slice(args);
+        self.invoke_method(&method, &all)
+    }
+
+    /// Runs `method`, natively if an implementation is registered for it,
+    /// with `args` starting with the receiver for an instance method, and
+    /// returns its result.
+    pub fn invoke_method(&mut self,
+                         method: &ResolvedMethod,
+                         args: &[Value])
+                         -> Result<Option<Value>> {
+        if let Some(native) = self.native(method) {
+            return self.call_native(native, &method.class, args);
+        }
+        let base = self.frames.len();
+        let result = self.push_frame(method.class.clone(), method.index, args)
+            .and_then(|_| self.run(base));
+        self.frames.truncate(base);
+        result
+    }
+
+    /// Makes room for objects of `size` bytes in all, collecting garbage if
+    /// there is not enough, or throws an `OutOfMemoryError`.
+    ///
+    /// Allocating them directly on `heap_mut()` then never collects, so
+    /// objects only referenced from Rust survive until all are allocated.
+    pub fn reserve(&mut self, size: usize) -> Result<()> {
+        if self.heap.ensure_room(size).is_err() {
+            self.collect_garbage();
+        }
+        self.heap.ensure_room(size)
+    }
+
+    /// Adds `object` to the heap, collecting garbage first if there is not
+    /// enough room for it.
+    ///
+    /// Objects not reachable from the static fields, the frames, interned
+    /// strings or the arguments of running native methods may be freed.
+    pub fn allocate(&mut self, object: Object) -> Result<Reference> {
+        try!(self.reserve(object.size()));
+        self.heap.allocate(object)
+    }
+
+    /// Creates an instance of `class`, initializing the class first, with
+    /// its fields holding their default values.
+    pub fn instantiate(&mut self, class: &Rc<Class>) -> Result<Reference> {
+        try!(self.initialize(class));
+        let layout = try!(self.layout(class));
+        self.allocate(Object::Instance(Instance::new(layout)))
+    }
+
+    /// The value of the field `name` with the given descriptor, declared by
+    /// the class `owner`, of the instance `reference`.
+    pub fn field(&self,
+                 reference: Reference,
+                 owner: &str,
+                 name: &str,
+                 descriptor: &str)
+                 -> Result<Value> {
+        let slot = try!(self.named_field_slot(reference, owner, name, descriptor));
+        Ok(self.heap.instance(reference).unwrap().fields[slot])
+    }
+
+    /// Sets the field `name` with the given descriptor, declared by the
+    /// class `owner`, of the instance `reference`.
+    pub fn set_field(&mut self,
+                     reference: Reference,
+                     owner: &str,
+                     name: &str,
+                     descriptor: &str,
+                     value: Value)
+                     -> Result<()> {
+        let slot = try!(self.named_field_slot(reference, owner, name, descriptor));
+        match *self.heap.get_mut(reference) {
+            Object::Instance(ref mut instance) => instance.fields[slot] = value,
+            Object::Array(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn named_field_slot(&self,
+                        reference: Reference,
+                        owner: &str,
+                        name: &str,
+                        descriptor: &str)
+                        -> Result<usize> {
+        let slot = self.heap
+            .instance(reference)
+            .and_then(|instance| instance.layout.index_of(owner, name, descriptor));
+        slot.ok_or_else(|| {
+            let message = format!("{} has no field {}.{}:{}",
+                                  self.type_of(reference).java_name(),
+                                  owner.replace('/', "."),
+                                  name,
+                                  descriptor);
+            exception("java/lang/NoSuchFieldError", Some(message))
+        })
+    }
+
+    /// Creates a `java.lang.String` holding `value`, with the `String` class
+    /// that `loader` loads. The class must have a `value` field holding the
+    /// characters as a `char[]`.
+    pub fn new_string(&mut self, loader: LoaderId, value: &str) -> Result<Reference> {
+        let class = try!(self.loaders.load(loader, "java/lang/String"));
+        try!(self.initialize(&class));
+        let layout = try!(self.layout(&class));
+        let chars: Vec<u16> = value.encode_utf16().collect();
+        let array = Object::Array(Array {
+            component: FieldType::Char,
+            elements: Elements::Char(chars),
+        });
+        let string = Object::Instance(Instance::new(layout));
+        // The array is only referenced from here until the string is set up.
+        try!(self.reserve(array.size() + string.size()));
+        let array = try!(self.heap.allocate(array));
+        let string = try!(self.heap.allocate(string));
+        try!(self.set_field(string,
+                            "java/lang/String",
+                            "value",
+                            "[C",
+                            Value::Reference(array)));
+        Ok(string)
+    }
+
+    /// Returns the interned `java.lang.String` holding `value`, creating it
+    /// the first time. Interned strings are never collected.
+    pub fn intern(&mut self, loader: LoaderId, value: &str) -> Result<Reference> {
+        if let Some(&string) = self.strings.get(value) {
+            return Ok(string);
+        }
+        let string = try!(self.new_string(loader, value));
+        self.strings.insert(value.to_string(), string);
+        Ok(string)
+    }
+
+    /// Creates a `java.lang.String[]` holding strings with `values`, with
+    /// the `String` class that `loader` loads.
+    pub fn new_string_array<S: AsRef<str>>(&mut self,
+                                           loader: LoaderId,
+                                           values: &[S])
+                                           -> Result<Reference> {
+        let component = FieldType::Object("java/lang/String".to_string());
+        let array = try!(self.allocate(Object::Array(Array::new(component, values.len()))));
+        // The array is only referenced from here until it is returned.
+        let pinned = self.pinned.len();
+        self.pinned.push(Value::Reference(array));
+        let mut result = Ok(array);
+        for (index, value) in values.iter().enumerate() {
+            let string = match self.new_string(loader, value.as_ref()) {
+                Ok(string) => string,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            };
+            match *self.heap.get_mut(array) {
+                Object::Array(ref mut array) => array.set(index, Value::Reference(string)),
+                Object::Instance(_) => unreachable!(),
+            };
+        }
+        self.pinned.truncate(pinned);
+        result
+    }
+
+    /// The value of the `java.lang.String` `reference` refers to.
+    pub fn string_value(&self, reference: Reference) -> Result<String> {
+        let chars = match try!(self.field(reference, "java/lang/String", "value", "[C")) {
+            Value::Reference(chars) => chars,
+            _ => return Err(exception("java/lang/NullPointerException", None)),
+        };
+        match self.heap.array(chars) {
+            Some(&Array { elements: Elements::Char(ref chars), .. }) => {
+                Ok(String::from_utf16_lossy(chars))
+            }
+            _ => {
+                let message = "java.lang.String.value is not a char[]".to_string();
+                Err(exception("java/lang/IncompatibleClassChangeError", Some(message)))
+            }
+        }
+    }
+
+    /// The class of the object `reference` refers to, which for an array is
+    /// `java.lang.Object` as far as method selection is concerned.
+    fn class_of(&mut self, reference: Reference) -> Result<Rc<Class>> {
+        if let Some(instance) = self.heap.instance(reference) {
+            return Ok(instance.class().clone());
+        }
+        let loader = match self.frames.last() {
+            Some(frame) => frame.class.loader(),
+            None => return Err(exception("java/lang/NoClassDefFoundError", None)),
+        };
+        Ok(try!(self.loaders.load(loader, "java/lang/Object")))
+    }
+
+    /// The native implementation registered for `method`.
+    fn native(&self, method: &ResolvedMethod) -> Option<NativeMethod> {
+        self.natives.get(method.class.name(), method.name(), method.descriptor())
+    }
+
+    /// Runs a native method, keeping its arguments alive while it runs.
+    fn call_native(&mut self,
+                   native: NativeMethod,
+                   class: &Rc<Class>,
+                   args: &[Value])
+                   -> Result<Option<Value>> {
+        let pinned = self.pinned.len();
+        self.pinned.extend_from_slice(args);
+        let result = native(self, class, args);
+        self.pinned.truncate(pinned);
+        result
+    }
+
+    /// Frees the objects on the heap that are not reachable from the static
+    /// fields, the frames, interned strings or the arguments of running
+    /// native methods, and returns how many were freed.
+    ///
+    /// This happens by itself when an allocation would otherwise exceed the
+    /// heap limit.
+    pub fn collect_garbage(&mut self) -> usize {
+        let roots = self.roots();
+        self.heap.collect(roots)
+    }
+
+    /// The references held by static fields, by the local variables and
+    /// operand stacks of the frames and by the arguments of running native
+    /// methods, and the interned strings.
+    fn roots(&self) -> Vec<Reference> {
+        let frames = self.frames.iter().flat_map(|frame| {
+            frame.locals().iter().filter_map(|&local| local).chain(frame.stack().iter().cloned())
+        });
+        let strings = self.strings.values().map(|&string| Value::Reference(string));
+        frames.chain(self.statics.values().cloned())
+            .chain(self.pinned.iter().cloned())
+            .chain(strings)
+            .filter_map(|value| match value {
+                Value::Reference(reference) => Some(reference),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The frame of the method being run.
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    /// Returns the decoded code of the method at `index` in `class`, decoding
+    /// it the first time.
+    fn method(&mut self, class: &Rc<Class>, index: usize) -> Result<Rc<Method>> {
+        if let Some(method) = self.methods.get(&(key(class), index)) {
+            return Ok(method.clone());
+        }
+        let cp = &class.file.constants;
+        let info = &class.file.methods[index];
+        let code = match info.attrs.code() {
+            Some(code) => code,
+            None => {
+                let name = format!("{}.{}{}",
+                                   class.name().replace('/', "."),
+                                   cp[info.name_index].as_utf8(),
+                                   cp[info.descriptor_index].as_utf8());
+                let class = if info.access_flags.is_native() {
+                    "java/lang/UnsatisfiedLinkError"
+                } else {
+                    "java/lang/AbstractMethodError"
+                };
+                return Err(exception(class, Some(name)));
+            }
+        };
+        let method = Rc::new(Method {
+            instructions: try!(code.instruction_list()),
+            max_stack: code.max_stack,
+            max_locals: code.max_locals,
+            ret: try!(info.descriptor(cp)).ret.as_ref().map(Kind::of),
+        });
+        self.methods.insert((key(class), index), method.clone());
+        Ok(method)
+    }
+
+    /// Pushes a frame for the method at `index` in `class`, with `args` in
+    /// its first local variables.
+    fn push_frame(&mut self, class: Rc<Class>, index: usize, args: &[Value]) -> Result<()> {
+        if self.frames.len() >= self.max_depth {
+            return Err(exception("java/lang/StackOverflowError", None));
+        }
+        let method = try!(self.method(&class, index));
+        let mut frame = Frame::new(class, index, method.max_locals, method.max_stack);
+        let mut local = 0;
+        for &arg in args {
+            try!(frame.store(local, arg));
+            local += arg.slots();
+        }
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    /// Runs instructions until the frame at `base` returns.
+    fn run(&mut self, base: usize) -> Result<Option<Value>> {
+        loop {
+            let (class, index, pc) = {
+                let frame = self.frame();
+                (frame.class.clone(), frame.method, frame.pc)
+            };
+            let method = try!(self.method(&class, index));
+            let index = try!(instruction_at(&method, pc));
+            let insn = method.instructions.get(index).unwrap();
+            let flow = match try!(execute(self.frame(), insn, &class.file.constants)) {
+                Flow::Runtime => try!(self.execute_runtime(insn)),
+                flow => flow,
+            };
+            match flow {
+                Flow::Next => self.frame().pc = method.instructions.end_pc(index).unwrap(),
+                Flow::Jump(pc) => self.frame().pc = pc,
+                Flow::Call => {}
+                Flow::Return(value) => {
+                    let frame = self.frames.pop().unwrap();
+                    if value.map(|value| value.kind()) != method.ret {
+                        return Err(Error::InvalidCode {
+                            pc: frame.pc,
+                            message: format!("cannot return {:?} from this method", value),
+                        });
+                    }
+                    if self.frames.len() == base {
+                        return Ok(value);
+                    }
+                    let (class, index) = {
+                        let frame = self.frame();
+                        (frame.class.clone(), frame.method)
+                    };
+                    let caller = try!(self.method(&class, index));
+                    let frame = self.frame();
+                    let index = try!(instruction_at(&caller, frame.pc));
+                    if let Some(value) = value {
+                        try!(frame.push(value));
+                    }
+                    frame.pc = caller.instructions.end_pc(index).unwrap();
+                }
+                Flow::Runtime => unreachable!(),
+            }
+        }
+    }
+
+    /// Runs the instructions that create and use objects, access fields or
+    /// invoke methods.
+    fn execute_runtime(&mut self, insn: &Bytecode) -> Result<Flow> {
+        match *insn {
+            Bytecode::ldc { index } => try!(self.load_string(index as u16)),
+            Bytecode::ldc_w { index } => try!(self.load_string(index)),
+
+            // Objects and fields.
+            Bytecode::new { index } => try!(self.new_instance(index)),
+            Bytecode::getfield { index } => try!(self.get_field(index)),
+            Bytecode::putfield { index } => try!(self.put_field(index)),
+            Bytecode::getstatic { index } => try!(self.get_static(index)),
+            Bytecode::putstatic { index } => try!(self.put_static(index)),
+            Bytecode::checkcast { index } => {
+                let value = try!(self.frame().pop_kind(Kind::Reference));
+                if let Value::Reference(reference) = value {
+                    let target = try!(self.type_ref(index));
+                    if !try!(self.is_instance(reference, &target)) {
+                        let message = format!("class {} cannot be cast to class {}",
+                                              self.type_of(reference).java_name(),
+                                              target.java_name());
+                        return Err(exception("java/lang/ClassCastException", Some(message)));
+                    }
+                }
+                try!(self.frame().push(value));
+            }
+            Bytecode::instanceof { index } => {
+                let result = match try!(self.frame().pop_kind(Kind::Reference)) {
+                    Value::Reference(reference) => {
+                        let target = try!(self.type_ref(index));
+                        try!(self.is_instance(reference, &target))
+                    }
+                    _ => false,
+                };
+                try!(self.frame().push(Value::Int(result as i32)));
+            }
+            Bytecode::monitorenter | Bytecode::monitorexit => {
+                // With a single thread, holding a monitor has no effect.
+                try!(self.pop_reference());
+            }
+
+            // Arrays.
+            Bytecode::newarray { atype } => {
+                let component = match atype {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => FieldType::Float,
+                    7 => FieldType::Double,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => FieldType::Int,
+                    11 => FieldType::Long,
+                    _ => {
+                        let message = format!("invalid array type {}", atype);
+                        return Err(self.invalid(message));
+                    }
+                };
+                try!(self.new_array(FieldType::Array(Box::new(component)), 1));
+            }
+            Bytecode::anewarray { index } => {
+                let component = try!(self.type_ref(index));
+                try!(self.new_array(FieldType::Array(Box::new(component)), 1));
+            }
+            Bytecode::multianewarray { index, dimensions } => {
+                let array_type = try!(self.type_ref(index));
+                if dimensions == 0 || array_type.dimensions() < dimensions as usize {
+                    let message = format!("{} does not have {} dimensions", array_type, dimensions);
+                    return Err(self.invalid(message));
+                }
+                try!(self.new_array(array_type, dimensions as usize));
+            }
+            Bytecode::arraylength => {
+                let reference = try!(self.pop_reference());
+                let length = try!(self.array(reference)).len();
+                try!(self.frame().push(Value::Int(length as i32)));
+            }
+            Bytecode::iaload | Bytecode::baload | Bytecode::caload | Bytecode::saload => {
+                try!(self.array_load(Kind::Int))
+            }
+            Bytecode::laload => try!(self.array_load(Kind::Long)),
+            Bytecode::faload => try!(self.array_load(Kind::Float)),
+            Bytecode::daload => try!(self.array_load(Kind::Double)),
+            Bytecode::aaload => try!(self.array_load(Kind::Reference)),
+            Bytecode::iastore | Bytecode::bastore | Bytecode::castore | Bytecode::sastore => {
+                try!(self.array_store(Kind::Int))
+            }
+            Bytecode::lastore => try!(self.array_store(Kind::Long)),
+            Bytecode::fastore => try!(self.array_store(Kind::Float)),
+            Bytecode::dastore => try!(self.array_store(Kind::Double)),
+            Bytecode::aastore => try!(self.array_store(Kind::Reference)),
+
+            // Invocations.
+            Bytecode::invokestatic { index } => return self.invoke(Invocation::Static, index),
+            Bytecode::invokespecial { index } => return self.invoke(Invocation::Special, index),
+            Bytecode::invokevirtual { index } => return self.invoke(Invocation::Virtual, index),
+            Bytecode::invokeinterface { index, .. } => {
+                return self.invoke(Invocation::Interface, index)
+            }
+
+            _ => {
+                let pc = self.frame().pc;
+                return Err(Error::Unsupported {
+                    pc: pc,
+                    message: format!("{} is not supported", insn.info().mnemonic()),
+                });
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Initializes `class`, unless it has been already: initializes its
+    /// superclass, sets its constant static fields and runs its static
+    /// initializer.
+    fn initialize(&mut self, class: &Rc<Class>) -> Result<()> {
+        match self.initialization.get(&key(class)) {
+            Some(&Initialization::Running) |
+            Some(&Initialization::Done) => return Ok(()),
+            Some(&Initialization::Failed) => {
+                let message = format!("Could not initialize class {}",
+                                      class.name().replace('/', "."));
+                return Err(exception("java/lang/NoClassDefFoundError", Some(message)));
+            }
+            None => {}
+        }
+        self.initialization.insert(key(class), Initialization::Running);
+        let result = self.run_initializer(class);
+        let state = if result.is_ok() {
+            Initialization::Done
+        } else {
+            Initialization::Failed
+        };
+        self.initialization.insert(key(class), state);
+        result
+    }
+
+    fn run_initializer(&mut self, class: &Rc<Class>) -> Result<()> {
+        if !class.file.access_flags.is_interface() {
+            if let Some(superclass) = try!(self.loaders.superclass(class)) {
+                try!(self.initialize(&superclass));
+            }
+        }
+        let cp = &class.file.constants;
+        for (index, field) in class.file.fields.iter().enumerate() {
+            if !field.access_flags.is_static() {
+                continue;
+            }
+            let value = match field.attrs.constant_value().and_then(|index| cp.get(index)) {
+                Some(&Constant::Integer(value)) => Value::Int(value),
+                Some(&Constant::Long(value)) => Value::Long(value),
+                Some(&Constant::Float(value)) => Value::Float(value),
+                Some(&Constant::Double(value)) => Value::Double(value),
+                _ => continue,
+            };
+            self.statics.insert((key(class), index), value);
+        }
+        let initializer = class.file.methods.iter().position(|method| {
+            cp[method.name_index].as_utf8() == "<clinit>" &&
+            cp[method.descriptor_index].as_utf8() == "()V"
+        });
+        let initializer = match initializer {
+            Some(initializer) => initializer,
+            None => return Ok(()),
+        };
+        let base = self.frames.len();
+        let result = self.push_frame(class.clone(), initializer, &[]).and_then(|_| self.run(base));
+        self.frames.truncate(base);
+        match result {
+            Ok(_) => Ok(()),
+            Err(Error::Exception { ref class, ref message }) if !class.ends_with("Error") => {
+                let cause = match *message {
+                    Some(ref message) => format!("{}: {}", class.replace('/', "."), message),
+                    None => class.replace('/', "."),
+                };
+                Err(exception("java/lang/ExceptionInInitializerError", Some(cause)))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the instance layout of `class`, computing it and those of its
+    /// superclasses the first time.
+    fn layout(&mut self, class: &Rc<Class>) -> Result<Rc<Layout>> {
+        if let Some(layout) = self.layouts.get(&key(class)) {
+            return Ok(layout.clone());
+        }
+        let superclass = match try!(self.loaders.superclass(class)) {
+            Some(superclass) => Some(try!(self.layout(&superclass))),
+            None => None,
+        };
+        let layout = Rc::new(try!(Layout::new(class.clone(), superclass.as_ref().map(|l| &**l))));
+        self.layouts.insert(key(class), layout.clone());
+        Ok(layout)
+    }
+
+    fn new_instance(&mut self, index: u16) -> Result<()> {
+        let class = try!(self.class_ref(index));
+        if class.file.access_flags.is_interface() || class.file.access_flags.is_abstract() {
+            let message = class.name().replace('/', ".");
+            return Err(exception("java/lang/InstantiationError", Some(message)));
+        }
+        let reference = try!(self.instantiate(&class));
+        self.frame().push(Value::Reference(reference))
+    }
+
+    /// Pushes the interned string for the `String` constant at `index`.
+    fn load_string(&mut self, index: u16) -> Result<()> {
+        let class = self.frame().class.clone();
+        let cp = &class.file.constants;
+        let value = cp[cp[index].as_string()].as_utf8();
+        let string = try!(self.intern(class.loader(), value));
+        self.frame().push(Value::Reference(string))
+    }
+
+    /// Creates an array of type `array_type` with `dimensions` of its
+    /// dimensions given by lengths popped from the stack, and pushes it.
+    fn new_array(&mut self, array_type: FieldType, dimensions: usize) -> Result<()> {
+        let mut lengths = vec![];
+        for _ in 0..dimensions {
+            let length = try!(self.frame().pop_int());
+            if length < 0 {
+                let message = length.to_string();
+                return Err(exception("java/lang/NegativeArraySizeException", Some(message)));
+            }
+            lengths.push(length as usize);
+        }
+        lengths.reverse();
+        // Make room for all the arrays at once, so that no collection runs
+        // while those created so far are only referenced from here.
+        try!(self.reserve(array_size(&array_type, &lengths)));
+        let reference = try!(self.allocate_array(&array_type, &lengths));
+        self.frame().push(Value::Reference(reference))
+    }
+
+    fn allocate_array(&mut self, array_type: &FieldType, lengths: &[usize]) -> Result<Reference> {
+        let component = match *array_type {
+            FieldType::Array(ref component) => component,
+            _ => unreachable!(),
+        };
+        let mut array = Array::new((**component).clone(), lengths[0]);
+        if lengths.len() > 1 {
+            for index in 0..lengths[0] {
+                let element = try!(self.allocate_array(component, &lengths[1..]));
+                array.set(index, Value::Reference(element));
+            }
+        }
+        self.heap.allocate(Object::Array(array))
+    }
+
+    fn array_load(&mut self, kind: Kind) -> Result<()> {
+        let index = try!(self.frame().pop_int());
+        let reference = try!(self.pop_reference());
+        let value = {
+            let array = try!(self.array(reference));
+            match array.get(index as usize) {
+                Some(value) if index >= 0 => value,
+                _ => return Err(index_out_of_bounds(index, array.len())),
+            }
+        };
+        if value.kind() != kind {
+            let message = format!("cannot load {:?} from {}", kind, self.type_of(reference));
+            return Err(self.invalid(message));
+        }
+        self.frame().push(value)
+    }
+
+    fn array_store(&mut self, kind: Kind) -> Result<()> {
+        let value = try!(self.frame().pop_kind(kind));
+        let index = try!(self.frame().pop_int());
+        let reference = try!(self.pop_reference());
+        let (length, component) = {
+            let array = try!(self.array(reference));
+            (array.len(), array.component.clone())
+        };
+        if index < 0 || index as usize >= length {
+            return Err(index_out_of_bounds(index, length));
+        }
+        if let Value::Reference(element) = value {
+            if component.is_reference() && !try!(self.is_instance(element, &component)) {
+                let message = self.type_of(element).java_name();
+                return Err(exception("java/lang/ArrayStoreException", Some(message)));
+            }
+        }
+        let stored = match *self.heap.get_mut(reference) {
+            Object::Array(ref mut array) => array.set(index as usize, value),
+            Object::Instance(_) => unreachable!(),
+        };
+        if !stored {
+            let message = format!("cannot store {:?} in {}", value, self.type_of(reference));
+            return Err(self.invalid(message));
+        }
+        Ok(())
+    }
+
+    /// The array `reference` refers to.
+    fn array(&self, reference: Reference) -> Result<&Array> {
+        match self.heap.array(reference) {
+            Some(array) => Ok(array),
+            None => {
+                let message = format!("{} is not an array", self.type_of(reference));
+                Err(self.invalid(message))
+            }
+        }
+    }
+
+    fn get_field(&mut self, index: u16) -> Result<()> {
+        let field = try!(self.resolve_field(index, false));
+        let reference = try!(self.pop_reference());
+        let slot = try!(self.field_slot(reference, &field));
+        let value = self.heap.instance(reference).unwrap().fields[slot];
+        self.frame().push(value)
+    }
+
+    fn put_field(&mut self, index: u16) -> Result<()> {
+        let field = try!(self.resolve_field(index, false));
+        let kind = Kind::of(&try!(FieldType::parse(field.descriptor())));
+        let value = try!(self.frame().pop_kind(kind));
+        let reference = try!(self.pop_reference());
+        let slot = try!(self.field_slot(reference, &field));
+        match *self.heap.get_mut(reference) {
+            Object::Instance(ref mut instance) => instance.fields[slot] = value,
+            Object::Array(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// The index of `field` in the fields of the instance `reference`.
+    fn field_slot(&self, reference: Reference, field: &ResolvedField) -> Result<usize> {
+        let slot = self.heap.instance(reference).and_then(|instance| {
+            instance.layout.index_of(field.class.name(), field.name(), field.descriptor())
+        });
+        match slot {
+            Some(slot) => Ok(slot),
+            None => {
+                let message = format!("{} has no field {}.{}",
+                                      self.type_of(reference),
+                                      field.class.name(),
+                                      field.name());
+                Err(self.invalid(message))
+            }
+        }
+    }
+
+    fn get_static(&mut self, index: u16) -> Result<()> {
+        let field = try!(self.resolve_field(index, true));
+        try!(self.initialize(&field.class));
+        let value = match self.statics.get(&(key(&field.class), field.index)) {
+            Some(&value) => value,
+            None => Value::default_of(&try!(FieldType::parse(field.descriptor()))),
+        };
+        self.frame().push(value)
+    }
+
+    fn put_static(&mut self, index: u16) -> Result<()> {
+        let field = try!(self.resolve_field(index, true));
+        if field.info().access_flags.is_final() && !Rc::ptr_eq(&field.class, &self.frame().class) {
+            let message = format!("cannot assign the final field {}.{}",
+                                  field.class.name(),
+                                  field.name());
+            return Err(exception("java/lang/IllegalAccessError", Some(message)));
+        }
+        try!(self.initialize(&field.class));
+        let kind = Kind::of(&try!(FieldType::parse(field.descriptor())));
+        let value = try!(self.frame().pop_kind(kind));
+        self.statics.insert((key(&field.class), field.index), value);
+        Ok(())
+    }
+
+    /// Resolves the field reference at `index`, which must refer to a static
+    /// field if `is_static` and an instance field otherwise.
+    fn resolve_field(&mut self, index: u16, is_static: bool) -> Result<ResolvedField> {
+        let from = self.frame().class.clone();
+        let field = try!(self.loaders.resolve_field(&from, index));
+        if field.info().access_flags.is_static() != is_static {
+            return Err(incompatible_class_change(&field.class,
+                                                 field.name(),
+                                                 field.descriptor(),
+                                                 is_static));
+        }
+        Ok(field)
+    }
+
+    /// Invokes the method referred to by the constant at `index` with
+    /// arguments popped from the current frame.
+    fn invoke(&mut self, invocation: Invocation, index: u16) -> Result<Flow> {
+        let from = self.frame().class.clone();
+        let resolved = try!(self.loaders.resolve_method(&from, index));
+        let is_static = invocation == Invocation::Static;
+        if resolved.info().access_flags.is_static() != is_static {
+            return Err(incompatible_class_change(&resolved.class,
+                                                 resolved.name(),
+                                                 resolved.descriptor(),
+                                                 is_static));
+        }
+        let params = try!(MethodDescriptor::parse(resolved.descriptor())).params;
+        let mut args = vec![];
+        for param in params.iter().rev() {
+            args.push(try!(self.frame().pop_kind(Kind::of(param))));
+        }
+        let receiver = if is_static {
+            None
+        } else {
+            let receiver = try!(self.pop_reference());
+            args.push(Value::Reference(receiver));
+            Some(receiver)
+        };
+        args.reverse();
+        let method = match (invocation, receiver) {
+            (Invocation::Static, _) => {
+                try!(self.initialize(&resolved.class));
+                resolved
+            }
+            (Invocation::Special, _) => try!(self.select_special(&from, resolved)),
+            (_, Some(receiver)) => try!(self.select_virtual(receiver, resolved, invocation)),
+            (_, None) => unreachable!(),
+        };
+        if let Some(native) = self.native(&method) {
+            if let Some(value) = try!(self.call_native(native, &method.class, &args)) {
+                try!(self.frame().push(value));
+            }
+            return Ok(Flow::Next);
+        }
+        try!(self.push_frame(method.class, method.index, &args));
+        Ok(Flow::Call)
+    }
+
+    /// Selects the method that `invokespecial` runs for the method `resolved`
+    /// from the class `from`: for a method of a superclass of a class with
+    /// `ACC_SUPER` set, the one that the direct superclass inherits, so that
+    /// `super.m()` calls the closest override.
+    fn select_special(&mut self,
+                      from: &Rc<Class>,
+                      resolved: ResolvedMethod)
+                      -> Result<ResolvedMethod> {
+        let info = resolved.info();
+        if resolved.name() == "<init>" || info.access_flags.is_private() ||
+           !from.file.access_flags.is_super() ||
+           resolved.class.file.access_flags.is_interface() ||
+           Rc::ptr_eq(&resolved.class, from) ||
+           !try!(self.is_subclass(from, &resolved.class)) {
+            return Ok(resolved);
+        }
+        let superclass = match try!(self.loaders.superclass(from)) {
+            Some(superclass) => superclass,
+            None => return Ok(resolved),
+        };
+        let selected = try!(self.loaders
+            .lookup_method(&superclass, resolved.name(), resolved.descriptor()));
+        self.check_selected(selected, &resolved)
+    }
+
+    /// Selects the method that `invokevirtual` or `invokeinterface` runs for
+    /// the method `resolved` on `receiver`: the one that the class of the
+    /// receiver declares or inherits.
+    fn select_virtual(&mut self,
+                      receiver: Reference,
+                      resolved: ResolvedMethod,
+                      invocation: Invocation)
+                      -> Result<ResolvedMethod> {
+        if resolved.info().access_flags.is_private() {
+            return Ok(resolved);
+        }
+        let class = try!(self.class_of(receiver));
+        if invocation == Invocation::Interface && !try!(self.is_subclass(&class, &resolved.class)) {
+            let message = format!("class {} does not implement the interface {}",
+                                  class.name().replace('/', "."),
+                                  resolved.class.name().replace('/', "."));
+            return Err(exception("java/lang/IncompatibleClassChangeError", Some(message)));
+        }
+        let selected = try!(self.loaders
+            .lookup_method(&class, resolved.name(), resolved.descriptor()));
+        self.check_selected(selected, &resolved)
+    }
+
+    /// Checks that the method selected for an invocation of `resolved` is
+    /// one that can be run.
+    fn check_selected(&self,
+                      selected: Option<ResolvedMethod>,
+                      resolved: &ResolvedMethod)
+                      -> Result<ResolvedMethod> {
+        match selected {
+            Some(ref method) if method.info().access_flags.is_static() => {
+                Err(incompatible_class_change(&method.class,
+                                              method.name(),
+                                              method.descriptor(),
+                                              false))
+            }
+            Some(ref method) if !method.info().access_flags.is_abstract() => Ok(method.clone()),
+            _ => {
+                let message = format!("{}.{}{}",
+                                      resolved.class.name().replace('/', "."),
+                                      resolved.name(),
+                                      resolved.descriptor());
+                Err(exception("java/lang/AbstractMethodError", Some(message)))
+            }
+        }
+    }
+
+    /// Pops a reference, throwing a `NullPointerException` if it is `null`.
+    fn pop_reference(&mut self) -> Result<Reference> {
+        match try!(self.frame().pop_kind(Kind::Reference)) {
+            Value::Reference(reference) => Ok(reference),
+            _ => Err(exception("java/lang/NullPointerException", None)),
+        }
+    }
+
+    /// Resolves the class constant at `index`.
+    fn class_ref(&mut self, index: u16) -> Result<Rc<Class>> {
+        let from = self.frame().class.clone();
+        let name = match from.file.constants.class_name(index) {
+            Some(name) => name,
+            None => {
+                let message = format!("constant {} is not a class", index);
+                return Err(self.invalid(message));
+            }
+        };
+        Ok(try!(self.loaders.resolve_class(&from, name)))
+    }
+
+    /// Resolves the class constant at `index`, which may name an array type,
+    /// and returns the type it names.
+    fn type_ref(&mut self, index: u16) -> Result<FieldType> {
+        let from = self.frame().class.clone();
+        let name = match from.file.constants.class_name(index) {
+            Some(name) => name,
+            None => {
+                let message = format!("constant {} is not a class", index);
+                return Err(self.invalid(message));
+            }
+        };
+        if !name.starts_with('[') {
+            try!(self.loaders.resolve_class(&from, name));
+            return Ok(FieldType::Object(name.clone()));
+        }
+        let array_type = try!(FieldType::parse(name));
+        let mut element = &array_type;
+        while let FieldType::Array(ref component) = *element {
+            element = component;
+        }
+        if let FieldType::Object(ref name) = *element {
+            try!(self.loaders.resolve_class(&from, name));
+        }
+        Ok(array_type)
+    }
+
+    /// The type of the object `reference` refers to.
+    fn type_of(&self, reference: Reference) -> FieldType {
+        match *self.heap.get(reference) {
+            Object::Instance(ref instance) => {
+                FieldType::Object(instance.class().name().to_string())
+            }
+            Object::Array(ref array) => FieldType::Array(Box::new(array.component.clone())),
+        }
+    }
+
+    /// Returns `true` if the object `reference` refers to can be assigned to
+    /// the type `target`.
+    fn is_instance(&mut self, reference: Reference, target: &FieldType) -> Result<bool> {
+        let class = self.heap.instance(reference).map(|instance| instance.class().clone());
+        match (class, target) {
+            (Some(class), &FieldType::Object(ref name)) => {
+                let target = try!(self.loaders.load(class.loader(), name));
+                self.is_subclass(&class, &target)
+            }
+            (Some(_), _) => Ok(false),
+            (None, target) => {
+                let from = self.type_of(reference);
+                self.is_assignable(&from, target)
+            }
+        }
+    }
+
+    /// Returns `true` if a value of type `from`, a reference type, can be
+    /// assigned to the type `to`.
+    fn is_assignable(&mut self, from: &FieldType, to: &FieldType) -> Result<bool> {
+        let loader = self.frame().class.loader();
+        match (from, to) {
+            (&FieldType::Object(ref from), &FieldType::Object(ref to)) => {
+                let from = try!(self.loaders.load(loader, from));
+                let to = try!(self.loaders.load(loader, to));
+                self.is_subclass(&from, &to)
+            }
+            (&FieldType::Array(_), &FieldType::Object(ref to)) => {
+                Ok(to == "java/lang/Object" || to == "java/lang/Cloneable" ||
+                   to == "java/io/Serializable")
+            }
+            (&FieldType::Array(ref from), &FieldType::Array(ref to)) => {
+                if from.is_primitive() || to.is_primitive() {
+                    Ok(from == to)
+                } else {
+                    self.is_assignable(from, to)
+                }
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns `true` if `class` is `target`, or extends or implements it.
+    fn is_subclass(&mut self, class: &Rc<Class>, target: &Rc<Class>) -> Result<bool> {
+        if Rc::ptr_eq(class, target) {
+            return Ok(true);
+        }
+        if let Some(superclass) = try!(self.loaders.superclass(class)) {
+            if try!(self.is_subclass(&superclass, target)) {
+                return Ok(true);
+            }
+        }
+        for interface in try!(self.loaders.interfaces(class)) {
+            if try!(self.is_subclass(&interface, target)) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn invalid(&self, message: String) -> Error {
+        Error::InvalidCode {
+            pc: self.frames.last().unwrap().pc,
+            message: message,
+        }
+    }
+}
+
+/// The number of bytes taken by an array of type `array_type` whose first
+/// dimensions have the given lengths, including the arrays it holds.
+fn array_size(array_type: &FieldType, lengths: &[usize]) -> usize {
+    let component = match *array_type {
+        FieldType::Array(ref component) => component,
+        _ => unreachable!(),
+    };
+    let size = Array::size_of(component, lengths[0]);
+    if lengths.len() == 1 {
+        return size;
+    }
+    size.saturating_add(lengths[0].saturating_mul(array_size(component, &lengths[1..])))
+}
+
+/// The `IncompatibleClassChangeError` thrown when an instruction for static
+/// members uses an instance member or the other way around.
+fn incompatible_class_change(class: &Class,
+                             name: &str,
+                             descriptor: &str,
+                             expected_static: bool)
+                             -> Error {
+    let message = format!("Expected {} {}.{}{}",
+                          if expected_static { "static" } else { "non-static" },
+                          class.name().replace('/', "."),
+                          name,
+                          descriptor);
+    exception("java/lang/IncompatibleClassChangeError", Some(message))
+}
+
+fn index_out_of_bounds(index: i32, length: usize) -> Error {
+    let message = format!("Index {} out of bounds for length {}", index, length);
+    exception("java/lang/ArrayIndexOutOfBoundsException", Some(message))
+}
+
+/// A Java exception of the class `class`.
+fn exception(class: &str, message: Option<String>) -> Error {
+    Error::Exception {
+        class: class.to_string(),
+        message: message,
+    }
+}
+
+/// The index of the instruction at `pc`.
+fn instruction_at(method: &Method, pc: usize) -> Result<usize> {
+    method.instructions.index_of(pc).ok_or_else(|| {
+        Error::InvalidCode {
+            pc: pc,
+            message: "no instruction starts here".to_string(),
+        }
+    })
+}
+
+/// Pops two operands of the same kind, applies `$op` to them and pushes the
+/// result.
+macro_rules! binary {
+    ($frame:expr, $pop:ident, $variant:ident, |$a:ident, $b:ident| $op:expr) => {{
+        let $b = try!($frame.$pop());
+        let $a = try!($frame.$pop());
+        try!($frame.push(Value::$variant($op)));
+    }};
+}
+
+/// Pops an operand, applies `$op` to it and pushes the result.
+macro_rules! unary {
+    ($frame:expr, $pop:ident, $variant:ident, |$a:ident| $op:expr) => {{
+        let $a = try!($frame.$pop());
+        try!($frame.push(Value::$variant($op)));
+    }};
+}
+
+/// Jumps by `$offset` if `$condition` holds.
+macro_rules! branch_if {
+    ($frame:expr, $condition:expr, $offset:expr) => {{
+        if $condition {
+            return jump($frame, $offset as i32);
+        }
+    }};
+}
+
+/// Runs `insn` on `frame`, whose pc is that of `insn`, unless it needs the
+/// heap or other classes.
+fn execute(frame: &mut Frame, insn: &Bytecode, cp: &ConstantPool) -> Result<Flow> {
+    match *insn {
+        Bytecode::nop => {}
+
+        // Constants.
+        Bytecode::aconst_null => try!(frame.push(Value::Null)),
+        Bytecode::iconst_i(i) => try!(frame.push(Value::Int(i as i32))),
+        Bytecode::lconst_l(l) => try!(frame.push(Value::Long(l as i64))),
+        Bytecode::fconst_f(f) => try!(frame.push(Value::Float(f as f32))),
+        Bytecode::dconst_d(d) => try!(frame.push(Value::Double(d as f64))),
+        Bytecode::bipush { byte } => try!(frame.push(Value::Int(byte as i8 as i32))),
+        Bytecode::sipush { short } => try!(frame.push(Value::Int(short as i32))),
+        Bytecode::ldc { index } => return ldc(frame, cp, index as u16, false),
+        Bytecode::ldc_w { index } => return ldc(frame, cp, index, false),
+        Bytecode::ldc2_w { index } => return ldc(frame, cp, index, true),
+
+        // Loads.
+        Bytecode::iload { index } => try!(load(frame, index as u16, Kind::Int)),
+        Bytecode::lload { index } => try!(load(frame, index as u16, Kind::Long)),
+        Bytecode::fload { index } => try!(load(frame, index as u16, Kind::Float)),
+        Bytecode::dload { byte } => try!(load(frame, byte as u16, Kind::Double)),
+        Bytecode::aload { index } => try!(load(frame, index as u16, Kind::Reference)),
+        Bytecode::iload_n(n) => try!(load(frame, n as u16, Kind::Int)),
+        Bytecode::lload_n(n) => try!(load(frame, n as u16, Kind::Long)),
+        Bytecode::fload_n(n) => try!(load(frame, n as u16, Kind::Float)),
+        Bytecode::dload_n(n) => try!(load(frame, n as u16, Kind::Double)),
+        Bytecode::aload_n(n) => try!(load(frame, n as u16, Kind::Reference)),
+        Bytecode::wide_iload { index } => try!(load(frame, index, Kind::Int)),
+        Bytecode::wide_lload { index } => try!(load(frame, index, Kind::Long)),
+        Bytecode::wide_fload { index } => try!(load(frame, index, Kind::Float)),
+        Bytecode::wide_dload { index } => try!(load(frame, index, Kind::Double)),
+        Bytecode::wide_aload { index } => try!(load(frame, index, Kind::Reference)),
+
+        // Stores.
+        Bytecode::istore { index } => try!(store(frame, index as u16, Kind::Int)),
+        Bytecode::lstore { index } => try!(store(frame, index as u16, Kind::Long)),
+        Bytecode::fstore { index } => try!(store(frame, index as u16, Kind::Float)),
+        Bytecode::dstore { index } => try!(store(frame, index as u16, Kind::Double)),
+        Bytecode::astore { index } => try!(store(frame, index as u16, Kind::Reference)),
+        Bytecode::istore_n(n) => try!(store(frame, n as u16, Kind::Int)),
+        Bytecode::lstore_n(n) => try!(store(frame, n as u16, Kind::Long)),
+        Bytecode::fstore_n(n) => try!(store(frame, n as u16, Kind::Float)),
+        Bytecode::dstore_n(n) => try!(store(frame, n as u16, Kind::Double)),
+        Bytecode::astore_n(n) => try!(store(frame, n as u16, Kind::Reference)),
+        Bytecode::wide_istore { index } => try!(store(frame, index, Kind::Int)),
+        Bytecode::wide_lstore { index } => try!(store(frame, index, Kind::Long)),
+        Bytecode::wide_fstore { index } => try!(store(frame, index, Kind::Float)),
+        Bytecode::wide_dstore { index } => try!(store(frame, index, Kind::Double)),
+        Bytecode::wide_astore { index } => try!(store(frame, index, Kind::Reference)),
+        Bytecode::iinc { index, constant } => {
+            try!(increment(frame, index as u16, constant as i8 as i32))
+        }
+        Bytecode::wide_iinc { index, constant } => {
+            try!(increment(frame, index, constant as i16 as i32))
+        }
+
+        // Operand stack manipulation.
+        Bytecode::pop => {
+            try!(frame.pop_category1());
+        }
+        Bytecode::pop2 => {
+            try!(frame.pop_slots2());
+        }
+        Bytecode::dup => {
+            let value = try!(frame.pop_category1());
+            try!(push_all(frame, &[value, value]));
+        }
+        Bytecode::dup_x1 => {
+            let value1 = try!(frame.pop_category1());
+            let value2 = try!(frame.pop_category1());
+            try!(push_all(frame, &[value1, value2, value1]));
+        }
+        Bytecode::dup_x2 => {
+            let value1 = try!(frame.pop_category1());
+            let below = try!(frame.pop_slots2());
+            try!(frame.push(value1));
+            try!(push_all(frame, &below));
+            try!(frame.push(value1));
+        }
+        Bytecode::dup2 => {
+            let top = try!(frame.pop_slots2());
+            try!(push_all(frame, &top));
+            try!(push_all(frame, &top));
+        }
+        Bytecode::dup2_x1 => {
+            let top = try!(frame.pop_slots2());
+            let value3 = try!(frame.pop_category1());
+            try!(push_all(frame, &top));
+            try!(frame.push(value3));
+            try!(push_all(frame, &top));
+        }
+        Bytecode::dup2_x2 => {
+            let top = try!(frame.pop_slots2());
+            let below = try!(frame.pop_slots2());
+            try!(push_all(frame, &top));
+            try!(push_all(frame, &below));
+            try!(push_all(frame, &top));
+        }
+        Bytecode::swap => {
+            let value1 = try!(frame.pop_category1());
+            let value2 = try!(frame.pop_category1());
+            try!(push_all(frame, &[value1, value2]));
+        }
+
+        // Arithmetic.
+        Bytecode::iadd => binary!(frame, pop_int, Int, |a, b| a.wrapping_add(b)),
+        Bytecode::ladd => binary!(frame, pop_long, Long, |a, b| a.wrapping_add(b)),
+        Bytecode::fadd => binary!(frame, pop_float, Float, |a, b| a + b),
+        Bytecode::dadd => binary!(frame, pop_double, Double, |a, b| a + b),
+        Bytecode::isub => binary!(frame, pop_int, Int, |a, b| a.wrapping_sub(b)),
+        Bytecode::lsub => binary!(frame, pop_long, Long, |a, b| a.wrapping_sub(b)),
+        Bytecode::fsub => binary!(frame, pop_float, Float, |a, b| a - b),
+        Bytecode::dsub => binary!(frame, pop_double, Double, |a, b| a - b),
+        Bytecode::imul => binary!(frame, pop_int, Int, |a, b| a.wrapping_mul(b)),
+        Bytecode::lmul => binary!(frame, pop_long, Long, |a, b| a.wrapping_mul(b)),
+        Bytecode::fmul => binary!(frame, pop_float, Float, |a, b| a * b),
+        Bytecode::dmul => binary!(frame, pop_double, Double, |a, b| a * b),
+        Bytecode::idiv | Bytecode::irem => {
+            let b = try!(frame.pop_int());
+            let a = try!(frame.pop_int());
+            if b == 0 {
+                return Err(divide_by_zero());
+            }
+            let result = match *insn {
+                Bytecode::idiv => a.wrapping_div(b),
+                _ => a.wrapping_rem(b),
+            };
+            try!(frame.push(Value::Int(result)));
+        }
+        Bytecode::ldiv | Bytecode::lrem => {
+            let b = try!(frame.pop_long());
+            let a = try!(frame.pop_long());
+            if b == 0 {
+                return Err(divide_by_zero());
+            }
+            let result = match *insn {
+                Bytecode::ldiv => a.wrapping_div(b),
+                _ => a.wrapping_rem(b),
+            };
+            try!(frame.push(Value::Long(result)));
+        }
+        Bytecode::fdiv => binary!(frame, pop_float, Float, |a, b| a / b),
+        Bytecode::ddiv => binary!(frame, pop_double, Double, |a, b| a / b),
+        Bytecode::frem => binary!(frame, pop_float, Float, |a, b| a % b),
+        Bytecode::drem => binary!(frame, pop_double, Double, |a, b| a % b),
+        Bytecode::ineg => unary!(frame, pop_int, Int, |a| a.wrapping_neg()),
+        Bytecode::lneg => unary!(frame, pop_long, Long, |a| a.wrapping_neg()),
+        Bytecode::fneg => unary!(frame, pop_float, Float, |a| -a),
+        Bytecode::dneg => unary!(frame, pop_double, Double, |a| -a),
+
+        // Shifts and bitwise operations. Shift distances are masked to the
+        // width of the shifted value, as they are by `wrapping_shl`.
+        Bytecode::ishl => binary!(frame, pop_int, Int, |a, b| a.wrapping_shl(b as u32)),
+        Bytecode::ishr => binary!(frame, pop_int, Int, |a, b| a.wrapping_shr(b as u32)),
+        Bytecode::iushr => {
+            binary!(frame, pop_int, Int, |a, b| (a as u32).wrapping_shr(b as u32) as i32)
+        }
+        Bytecode::lshl | Bytecode::lshr | Bytecode::lushr => {
+            let b = try!(frame.pop_int()) as u32;
+            let a = try!(frame.pop_long());
+            let result = match *insn {
+                Bytecode::lshl => a.wrapping_shl(b),
+                Bytecode::lshr => a.wrapping_shr(b),
+                _ => (a as u64).wrapping_shr(b) as i64,
+            };
+            try!(frame.push(Value::Long(result)));
+        }
+        Bytecode::iand => binary!(frame, pop_int, Int, |a, b| a & b),
+        Bytecode::land => binary!(frame, pop_long, Long, |a, b| a & b),
+        Bytecode::ior => binary!(frame, pop_int, Int, |a, b| a | b),
+        Bytecode::lor => binary!(frame, pop_long, Long, |a, b| a | b),
+        Bytecode::ixor => binary!(frame, pop_int, Int, |a, b| a ^ b),
+        Bytecode::lxor => binary!(frame, pop_long, Long, |a, b| a ^ b),
+
+        // Conversions. Rust's `as` rounds and saturates exactly as the JVM
+        // does, including turning NaN into zero.
+        Bytecode::i2l => unary!(frame, pop_int, Long, |a| a as i64),
+        Bytecode::i2f => unary!(frame, pop_int, Float, |a| a as f32),
+        Bytecode::i2d => unary!(frame, pop_int, Double, |a| a as f64),
+        Bytecode::l2i => unary!(frame, pop_long, Int, |a| a as i32),
+        Bytecode::l2f => unary!(frame, pop_long, Float, |a| a as f32),
+        Bytecode::l2d => unary!(frame, pop_long, Double, |a| a as f64),
+        Bytecode::f2i => unary!(frame, pop_float, Int, |a| a as i32),
+        Bytecode::f2l => unary!(frame, pop_float, Long, |a| a as i64),
+        Bytecode::f2d => unary!(frame, pop_float, Double, |a| a as f64),
+        Bytecode::d2i => unary!(frame, pop_double, Int, |a| a as i32),
+        Bytecode::d2l => unary!(frame, pop_double, Long, |a| a as i64),
+        Bytecode::d2f => unary!(frame, pop_double, Float, |a| a as f32),
+        Bytecode::i2b => unary!(frame, pop_int, Int, |a| a as i8 as i32),
+        Bytecode::i2c => unary!(frame, pop_int, Int, |a| a as u16 as i32),
+        Bytecode::i2s => unary!(frame, pop_int, Int, |a| a as i16 as i32),
+
+        // Comparisons.
+        Bytecode::lcmp => binary!(frame, pop_long, Int, |a, b| ordering(a.partial_cmp(&b), 0)),
+        Bytecode::fcmpl => binary!(frame, pop_float, Int, |a, b| ordering(a.partial_cmp(&b), -1)),
+        Bytecode::fcmpg => binary!(frame, pop_float, Int, |a, b| ordering(a.partial_cmp(&b), 1)),
+        Bytecode::dcmpl => {
+            binary!(frame, pop_double, Int, |a, b| ordering(a.partial_cmp(&b), -1))
+        }
+        Bytecode::dcmpg => binary!(frame, pop_double, Int, |a, b| ordering(a.partial_cmp(&b), 1)),
+
+        // Branches.
+        Bytecode::ifeq { branchoffset } |
+        Bytecode::ifne { branchoffset } |
+        Bytecode::iflt { branchoffset } |
+        Bytecode::ifge { branchoffset } |
+        Bytecode::ifgt { branchoffset } |
+        Bytecode::ifle { branchoffset } => {
+            let value = try!(frame.pop_int());
+            let taken = match *insn {
+                Bytecode::ifeq { .. } => value == 0,
+                Bytecode::ifne { .. } => value != 0,
+                Bytecode::iflt { .. } => value < 0,
+                Bytecode::ifge { .. } => value >= 0,
+                Bytecode::ifgt { .. } => value > 0,
+                _ => value <= 0,
+            };
+            branch_if!(frame, taken, branchoffset);
+        }
+        Bytecode::if_icmpeq { branchoffset } |
+        Bytecode::if_icmpne { branchoffset } |
+        Bytecode::if_icmplt { branchoffset } |
+        Bytecode::if_icmpge { branchoffset } |
+        Bytecode::if_icmpgt { branchoffset } |
+        Bytecode::if_icmple { branchoffset } => {
+            let b = try!(frame.pop_int());
+            let a = try!(frame.pop_int());
+            let taken = match *insn {
+                Bytecode::if_icmpeq { .. } => a == b,
+                Bytecode::if_icmpne { .. } => a != b,
+                Bytecode::if_icmplt { .. } => a < b,
+                Bytecode::if_icmpge { .. } => a >= b,
+                Bytecode::if_icmpgt { .. } => a > b,
+                _ => a <= b,
+            };
+            branch_if!(frame, taken, branchoffset);
+        }
+        Bytecode::if_acmpeq { branchoffset } | Bytecode::if_acmpne { branchoffset } => {
+            let b = try!(frame.pop_kind(Kind::Reference));
+            let a = try!(frame.pop_kind(Kind::Reference));
+            let equal = match *insn {
+                Bytecode::if_acmpeq { .. } => true,
+                _ => false,
+            };
+            branch_if!(frame, (a == b) == equal, branchoffset);
+        }
+        Bytecode::ifnull { branchoffset } => {
+            let value = try!(frame.pop_kind(Kind::Reference));
+            branch_if!(frame, value == Value::Null, branchoffset);
+        }
+        Bytecode::ifnonnull { branchoffset } => {
+            let value = try!(frame.pop_kind(Kind::Reference));
+            branch_if!(frame, value != Value::Null, branchoffset);
+        }
+        Bytecode::goto { branchoffset } => return jump(frame, branchoffset as i32),
+        Bytecode::goto_w { branchoffset } => return jump(frame, branchoffset),
+        Bytecode::jsr { branchoffset } => {
+            try!(frame.push(Value::ReturnAddress(frame.pc + 3)));
+            return jump(frame, branchoffset as i32);
+        }
+        Bytecode::jsr_w { branchoffset } => {
+            try!(frame.push(Value::ReturnAddress(frame.pc + 5)));
+            return jump(frame, branchoffset);
+        }
+        Bytecode::ret { index } => return ret(frame, index as u16),
+        Bytecode::wide_ret { index } => return ret(frame, index),
+        Bytecode::tableswitch { default, low, high, ref offsets } => {
+            let key = try!(frame.pop_int());
+            if low <= key && key <= high {
+                return jump(frame, offsets[(key as i64 - low as i64) as usize]);
+            }
+            return jump(frame, default);
+        }
+        Bytecode::lookupswitch { default, ref pairs, .. } => {
+            let key = try!(frame.pop_int());
+            let offset = pairs.iter().find(|&&(value, _)| value == key).map_or(default, |p| p.1);
+            return jump(frame, offset);
+        }
+
+        // Returns.
+        Bytecode::ireturn => return Ok(Flow::Return(Some(Value::Int(try!(frame.pop_int()))))),
+        Bytecode::lreturn => return Ok(Flow::Return(Some(Value::Long(try!(frame.pop_long()))))),
+        Bytecode::freturn => {
+            return Ok(Flow::Return(Some(Value::Float(try!(frame.pop_float())))))
+        }
+        Bytecode::dreturn => {
+            return Ok(Flow::Return(Some(Value::Double(try!(frame.pop_double())))))
+        }
+        Bytecode::areturn => {
+            return Ok(Flow::Return(Some(try!(frame.pop_kind(Kind::Reference)))))
+        }
+        Bytecode::Return => return Ok(Flow::Return(None)),
+
+        _ => return Ok(Flow::Runtime),
+    }
+    Ok(Flow::Next)
+}
+
+/// Pushes the constant at `index`, which must be a `long` or `double` for
+/// `ldc2_w` and any other loadable constant for `ldc` and `ldc_w`. Strings
+/// are left to the `Interpreter`, which interns them.
+fn ldc(frame: &mut Frame, cp: &ConstantPool, index: u16, wide: bool) -> Result<Flow> {
+    let value = match (cp.get(index), wide) {
+        (Some(&Constant::Integer(value)), false) => Value::Int(value),
+        (Some(&Constant::Float(value)), false) => Value::Float(value),
+        (Some(&Constant::Long(value)), true) => Value::Long(value),
+        (Some(&Constant::Double(value)), true) => Value::Double(value),
+        (Some(&Constant::String(_)), false) => return Ok(Flow::Runtime),
+        (Some(constant @ &Constant::Class(_)), false) |
+        (Some(constant @ &Constant::MethodType { .. }), false) |
+        (Some(constant @ &Constant::MethodHandle { .. }), false) => {
+            return Err(Error::Unsupported {
+                pc: frame.pc,
+                message: format!("loading a {} constant", constant.name()),
+            })
+        }
+        _ => {
+            return Err(Error::InvalidCode {
+                pc: frame.pc,
+                message: format!("constant {} cannot be loaded", index),
+            })
+        }
+    };
+    try!(frame.push(value));
+    Ok(Flow::Next)
+}
+
+fn load(frame: &mut Frame, index: u16, kind: Kind) -> Result<()> {
+    let value = try!(frame.load(index, kind));
+    frame.push(value)
+}
+
+/// Pops a value of `kind` and stores it to the local at `index`. Like the
+/// JVM, `astore` also stores return addresses.
+fn store(frame: &mut Frame, index: u16, kind: Kind) -> Result<()> {
+    let value = match (kind, frame.stack().last().map(|value| value.kind())) {
+        (Kind::Reference, Some(Kind::ReturnAddress)) => try!(frame.pop()),
+        _ => try!(frame.pop_kind(kind)),
+    };
+    frame.store(index, value)
+}
+
+fn increment(frame: &mut Frame, index: u16, constant: i32) -> Result<()> {
+    match try!(frame.load(index, Kind::Int)) {
+        Value::Int(value) => frame.store(index, Value::Int(value.wrapping_add(constant))),
+        _ => unreachable!(),
+    }
+}
+
+fn push_all(frame: &mut Frame, values: &[Value]) -> Result<()> {
+    for &value in values {
+        try!(frame.push(value));
+    }
+    Ok(())
+}
+
+/// Continues at the pc `offset` bytes from the current instruction.
+fn jump(frame: &Frame, offset: i32) -> Result<Flow> {
+    let target = frame.pc as i64 + offset as i64;
+    if target < 0 {
+        return Err(Error::InvalidCode {
+            pc: frame.pc,
+            message: format!("branch to negative pc {}", target),
+        });
+    }
+    Ok(Flow::Jump(target as usize))
+}
+
+fn ret(frame: &Frame, index: u16) -> Result<Flow> {
+    match try!(frame.load(index, Kind::ReturnAddress)) {
+        Value::ReturnAddress(pc) => Ok(Flow::Jump(pc)),
+        _ => unreachable!(),
+    }
+}
+
+/// The result of a comparison instruction: `-1`, `0` or `1`, or `nan` if
+/// either operand is NaN.
+fn ordering(ordering: Option<Ordering>, nan: i32) -> i32 {
+    match ordering {
+        Some(Ordering::Less) => -1,
+        Some(Ordering::Equal) => 0,
+        Some(Ordering::Greater) => 1,
+        None => nan,
+    }
+}
+
+/// The `ArithmeticException` thrown for an integer division by zero.
+fn divide_by_zero() -> Error {
+    exception("java/lang/ArithmeticException", Some("/ by zero".to_string()))
+}
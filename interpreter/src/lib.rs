@@ -0,0 +1,23 @@
+//! An interpreter for JVM bytecode.
+//!
+//! Methods run on an explicit stack of `Frame`s rather than on the Rust
+//! stack, so deep Java recursion ends in a `StackOverflowError` instead of
+//! aborting the process. Classes are loaded and their references resolved
+//! through `classpath::ClassLoaders`, and the objects and arrays they create
+//! live on a `Heap` whose unreachable objects are freed by a mark-and-sweep
+//! garbage collector. Native methods are implemented in Rust, and the
+//! `runtime` module provides enough of `java.lang` to run small programs
+//! without a JDK.
+extern crate classfile;
+extern crate classpath;
+extern crate jasm;
+
+pub mod frame;
+pub mod heap;
+pub mod interpreter;
+pub mod natives;
+pub mod runtime;
+pub mod value;
+
+mod error;
+pub use self::error::*;
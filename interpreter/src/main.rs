@@ -0,0 +1,51 @@
+extern crate classpath;
+extern crate interpreter;
+
+use std::env;
+use std::process;
+
+use classpath::ClassPath;
+use interpreter::Error;
+use interpreter::runtime;
+
+const USAGE: &'static str = "usage: interpreter [-classpath <path>] <class> [args...]";
+
+fn usage() -> ! {
+    println!("{}", USAGE);
+    process::exit(1);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut classpath = ".".to_string();
+    let mut class = None;
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "-classpath" | "-cp" => classpath = args.next().unwrap_or_else(|| usage()),
+            _ => {
+                class = Some(arg);
+                break;
+            }
+        }
+    }
+    let class = class.unwrap_or_else(|| usage()).replace('.', "/");
+    let args: Vec<String> = args.collect();
+    let classpath = ClassPath::parse(&classpath)
+        .unwrap_or_else(|e| fail(format!("{}: {}", classpath, e)));
+    let (mut interpreter, app) = runtime::boot(classpath)
+        .unwrap_or_else(|e| fail(format!("cannot start the runtime: {}", e)));
+    let result = runtime::run_main(&mut interpreter, app, &class, &args);
+    let _ = interpreter.stdout().flush();
+    match result {
+        Ok(()) => {}
+        Err(err @ Error::Exception { .. }) => {
+            fail(format!("Exception in thread \"main\" {}", err))
+        }
+        Err(err) => fail(format!("{}: {}", class.replace('/', "."), err)),
+    }
+}
@@ -0,0 +1,78 @@
+//! Rust implementations of Java methods.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use classpath::Class;
+
+use interpreter::Interpreter;
+use value::Value;
+use super::Result;
+
+/// The implementation of a method in Rust.
+///
+/// It is called with the class that declares the method and the arguments,
+/// starting with the receiver for instance methods, and returns the result,
+/// or `None` for a `void` method. The arguments are kept alive by the garbage
+/// collector until it returns.
+pub type NativeMethod = Rc<Fn(&mut Interpreter, &Rc<Class>, &[Value]) -> Result<Option<Value>>>;
+
+/// Native methods, by the class that declares them and their name and
+/// descriptor.
+///
+/// A registered method is run in place of the method it is registered for,
+/// whether that method is `native` or has code of its own.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate interpreter;
+/// use interpreter::natives::Natives;
+/// use interpreter::value::Value;
+///
+/// # fn main() {
+/// let mut natives = Natives::new();
+/// natives.register("java/lang/Math", "abs", "(I)I", |_, _, args| match args[0] {
+///     Value::Int(value) => Ok(Some(Value::Int(value.wrapping_abs()))),
+///     _ => unreachable!(),
+/// });
+/// assert!(natives.get("java/lang/Math", "abs", "(I)I").is_some());
+/// assert!(natives.get("java/lang/Math", "abs", "(J)J").is_none());
+/// # }
+/// ```
+pub struct Natives {
+    methods: HashMap<String, NativeMethod>,
+}
+
+impl Natives {
+    pub fn new() -> Natives {
+        Natives { methods: HashMap::new() }
+    }
+
+    /// Registers `method` as the implementation of the method `name` with
+    /// the given descriptor in the class `owner`, replacing any registered
+    /// before.
+    pub fn register<F>(&mut self, owner: &str, name: &str, descriptor: &str, method: F)
+        where F: Fn(&mut Interpreter, &Rc<Class>, &[Value]) -> Result<Option<Value>> + 'static
+    {
+        self.methods.insert(key(owner, name, descriptor), Rc::new(method));
+    }
+
+    /// The implementation registered for the method `name` with the given
+    /// descriptor in the class `owner`.
+    pub fn get(&self, owner: &str, name: &str, descriptor: &str) -> Option<NativeMethod> {
+        self.methods.get(&key(owner, name, descriptor)).cloned()
+    }
+
+    /// The number of methods registered.
+    pub fn len(&self) -> usize {
+        self.methods.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+fn key(owner: &str, name: &str, descriptor: &str) -> String {
+    format!("{}.{}{}", owner, name, descriptor)
+}
@@ -0,0 +1,1194 @@
+//! A minimal `java.lang` runtime, enough to run small programs without a JDK.
+//!
+//! The runtime defines `java.lang.Object`, `String`, `StringBuilder`,
+//! `System`, `Math`, `Number`, `Integer` and `Long`, and
+//! `java.io.PrintStream`, with a subset of the methods of the JDK's. They are
+//! assembled from the jasm sources below, and most of their methods are
+//! native ones implemented here. `System.out` and `System.err` write to the
+//! interpreter's `stdout()` and `stderr()`.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! # extern crate classpath;
+//! # extern crate interpreter;
+//! use classpath::ClassPath;
+//! use interpreter::runtime;
+//!
+//! # fn main() {
+//! let (mut interpreter, app) = runtime::boot(ClassPath::parse("classes").unwrap()).unwrap();
+//! let args = vec!["world".to_string()];
+//! runtime::run_main(&mut interpreter, app, "io/hcf/frappe/HelloWorld", &args).unwrap();
+//! # }
+//! ```
+use std::char;
+use std::cmp::Ordering;
+use std::f64;
+use std::fmt::{Display, LowerExp};
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use classfile::{ClassFile, FieldType};
+use classpath::{Class, ClassLoaders, ClassPath, LoaderId};
+use jasm;
+
+use heap::{Array, Elements, Object, Reference};
+use interpreter::Interpreter;
+use natives::Natives;
+use value::Value;
+use super::{Error, Result};
+
+const OBJECT: &'static str = r#"
+.class public super java/lang/Object
+.super none
+.method public "<init>" ()V {
+    .code stack 0 locals 1 {
+        return
+    }
+}
+.method public native hashCode ()I
+.method public native equals (Ljava/lang/Object;)Z
+.method public native toString ()Ljava/lang/String;
+"#;
+
+const STRING: &'static str = r#"
+.class public final super java/lang/String
+.field private final value [C
+.method public native "<init>" ()V
+.method public native "<init>" ([C)V
+.method public native "<init>" ([CII)V
+.method public native length ()I
+.method public native isEmpty ()Z
+.method public native charAt (I)C
+.method public native equals (Ljava/lang/Object;)Z
+.method public native hashCode ()I
+.method public native compareTo (Ljava/lang/String;)I
+.method public native toString ()Ljava/lang/String;
+.method public native concat (Ljava/lang/String;)Ljava/lang/String;
+.method public native substring (I)Ljava/lang/String;
+.method public native substring (II)Ljava/lang/String;
+.method public native indexOf (I)I
+.method public native indexOf (Ljava/lang/String;)I
+.method public native startsWith (Ljava/lang/String;)Z
+.method public native endsWith (Ljava/lang/String;)Z
+.method public native toCharArray ()[C
+.method public native toUpperCase ()Ljava/lang/String;
+.method public native toLowerCase ()Ljava/lang/String;
+.method public native trim ()Ljava/lang/String;
+.method public native intern ()Ljava/lang/String;
+.method public static native valueOf (Z)Ljava/lang/String;
+.method public static native valueOf (C)Ljava/lang/String;
+.method public static native valueOf (I)Ljava/lang/String;
+.method public static native valueOf (J)Ljava/lang/String;
+.method public static native valueOf (F)Ljava/lang/String;
+.method public static native valueOf (D)Ljava/lang/String;
+.method public static native valueOf ([C)Ljava/lang/String;
+.method public static native valueOf (Ljava/lang/Object;)Ljava/lang/String;
+"#;
+
+const STRING_BUILDER: &'static str = r#"
+.class public final super java/lang/StringBuilder
+.field private value [C
+.field private count I
+.method public native "<init>" ()V
+.method public native "<init>" (I)V
+.method public native "<init>" (Ljava/lang/String;)V
+.method public native append (Z)Ljava/lang/StringBuilder;
+.method public native append (C)Ljava/lang/StringBuilder;
+.method public native append (I)Ljava/lang/StringBuilder;
+.method public native append (J)Ljava/lang/StringBuilder;
+.method public native append (F)Ljava/lang/StringBuilder;
+.method public native append (D)Ljava/lang/StringBuilder;
+.method public native append ([C)Ljava/lang/StringBuilder;
+.method public native append (Ljava/lang/String;)Ljava/lang/StringBuilder;
+.method public native append (Ljava/lang/Object;)Ljava/lang/StringBuilder;
+.method public native length ()I
+.method public native charAt (I)C
+.method public native reverse ()Ljava/lang/StringBuilder;
+.method public native toString ()Ljava/lang/String;
+"#;
+
+const PRINT_STREAM: &'static str = r#"
+.class public super java/io/PrintStream
+.field private final fd I
+.method public "<init>" (I)V {
+    .code stack 2 locals 2 {
+        aload_0
+        invokespecial Method java/lang/Object "<init>" ()V
+        aload_0
+        iload_1
+        putfield Field java/io/PrintStream fd I
+        return
+    }
+}
+.method public native print (Z)V
+.method public native print (C)V
+.method public native print (I)V
+.method public native print (J)V
+.method public native print (F)V
+.method public native print (D)V
+.method public native print ([C)V
+.method public native print (Ljava/lang/String;)V
+.method public native print (Ljava/lang/Object;)V
+.method public native println ()V
+.method public native println (Z)V
+.method public native println (C)V
+.method public native println (I)V
+.method public native println (J)V
+.method public native println (F)V
+.method public native println (D)V
+.method public native println ([C)V
+.method public native println (Ljava/lang/String;)V
+.method public native println (Ljava/lang/Object;)V
+.method public native flush ()V
+"#;
+
+const SYSTEM: &'static str = r#"
+.class public final super java/lang/System
+.field public static final out Ljava/io/PrintStream;
+.field public static final err Ljava/io/PrintStream;
+.method static "<clinit>" ()V {
+    .code stack 3 locals 0 {
+        new Class java/io/PrintStream
+        dup
+        iconst_1
+        invokespecial Method java/io/PrintStream "<init>" (I)V
+        putstatic Field java/lang/System out Ljava/io/PrintStream;
+        new Class java/io/PrintStream
+        dup
+        iconst_2
+        invokespecial Method java/io/PrintStream "<init>" (I)V
+        putstatic Field java/lang/System err Ljava/io/PrintStream;
+        return
+    }
+}
+.method public static native currentTimeMillis ()J
+.method public static native nanoTime ()J
+.method public static native arraycopy (Ljava/lang/Object;ILjava/lang/Object;II)V
+.method public static native identityHashCode (Ljava/lang/Object;)I
+.method public static native lineSeparator ()Ljava/lang/String;
+"#;
+
+const MATH: &'static str = r#"
+.class public final super java/lang/Math
+.field public static final PI D {
+    .constantvalue Double 3.141592653589793
+}
+.field public static final E D {
+    .constantvalue Double 2.718281828459045
+}
+.method public static native abs (I)I
+.method public static native abs (J)J
+.method public static native abs (F)F
+.method public static native abs (D)D
+.method public static native max (II)I
+.method public static native max (JJ)J
+.method public static native max (FF)F
+.method public static native max (DD)D
+.method public static native min (II)I
+.method public static native min (JJ)J
+.method public static native min (FF)F
+.method public static native min (DD)D
+.method public static native round (F)I
+.method public static native round (D)J
+.method public static native sqrt (D)D
+.method public static native pow (DD)D
+.method public static native floor (D)D
+.method public static native ceil (D)D
+.method public static native sin (D)D
+.method public static native cos (D)D
+.method public static native tan (D)D
+.method public static native exp (D)D
+.method public static native log (D)D
+"#;
+
+const NUMBER: &'static str = r#"
+.class public abstract super java/lang/Number
+.method public "<init>" ()V {
+    .code stack 1 locals 1 {
+        aload_0
+        invokespecial Method java/lang/Object "<init>" ()V
+        return
+    }
+}
+.method public abstract intValue ()I
+.method public abstract longValue ()J
+.method public abstract doubleValue ()D
+"#;
+
+const INTEGER: &'static str = r#"
+.class public final super java/lang/Integer
+.super java/lang/Number
+.field public static final MIN_VALUE I {
+    .constantvalue Integer -2147483648
+}
+.field public static final MAX_VALUE I {
+    .constantvalue Integer 2147483647
+}
+.field private final value I
+.method public "<init>" (I)V {
+    .code stack 2 locals 2 {
+        aload_0
+        invokespecial Method java/lang/Number "<init>" ()V
+        aload_0
+        iload_1
+        putfield Field java/lang/Integer value I
+        return
+    }
+}
+.method public intValue ()I {
+    .code stack 1 locals 1 {
+        aload_0
+        getfield Field java/lang/Integer value I
+        ireturn
+    }
+}
+.method public longValue ()J {
+    .code stack 2 locals 1 {
+        aload_0
+        getfield Field java/lang/Integer value I
+        i2l
+        lreturn
+    }
+}
+.method public doubleValue ()D {
+    .code stack 2 locals 1 {
+        aload_0
+        getfield Field java/lang/Integer value I
+        i2d
+        dreturn
+    }
+}
+.method public hashCode ()I {
+    .code stack 1 locals 1 {
+        aload_0
+        getfield Field java/lang/Integer value I
+        ireturn
+    }
+}
+.method public native equals (Ljava/lang/Object;)Z
+.method public native toString ()Ljava/lang/String;
+.method public static native valueOf (I)Ljava/lang/Integer;
+.method public static native toString (I)Ljava/lang/String;
+.method public static native parseInt (Ljava/lang/String;)I
+"#;
+
+const LONG: &'static str = r#"
+.class public final super java/lang/Long
+.super java/lang/Number
+.field public static final MIN_VALUE J {
+    .constantvalue Long -9223372036854775808
+}
+.field public static final MAX_VALUE J {
+    .constantvalue Long 9223372036854775807
+}
+.field private final value J
+.method public "<init>" (J)V {
+    .code stack 3 locals 3 {
+        aload_0
+        invokespecial Method java/lang/Number "<init>" ()V
+        aload_0
+        lload_1
+        putfield Field java/lang/Long value J
+        return
+    }
+}
+.method public intValue ()I {
+    .code stack 2 locals 1 {
+        aload_0
+        getfield Field java/lang/Long value J
+        l2i
+        ireturn
+    }
+}
+.method public longValue ()J {
+    .code stack 2 locals 1 {
+        aload_0
+        getfield Field java/lang/Long value J
+        lreturn
+    }
+}
+.method public doubleValue ()D {
+    .code stack 2 locals 1 {
+        aload_0
+        getfield Field java/lang/Long value J
+        l2d
+        dreturn
+    }
+}
+.method public hashCode ()I {
+    .code stack 5 locals 1 {
+        aload_0
+        getfield Field java/lang/Long value J
+        aload_0
+        getfield Field java/lang/Long value J
+        bipush 32
+        lushr
+        lxor
+        l2i
+        ireturn
+    }
+}
+.method public native equals (Ljava/lang/Object;)Z
+.method public native toString ()Ljava/lang/String;
+.method public static native valueOf (J)Ljava/lang/Long;
+.method public static native toString (J)Ljava/lang/String;
+.method public static native parseLong (Ljava/lang/String;)J
+"#;
+
+/// The jasm sources of the classes of the runtime.
+const CLASSES: &'static [&'static str] = &[OBJECT,
+                                            STRING,
+                                            STRING_BUILDER,
+                                            PRINT_STREAM,
+                                            SYSTEM,
+                                            MATH,
+                                            NUMBER,
+                                            INTEGER,
+                                            LONG];
+
+/// The parameter types that `String.valueOf`, `StringBuilder.append` and
+/// `PrintStream.print` and `println` are overloaded for, which `format`
+/// formats.
+const FORMATTED: &'static [&'static str] = &["Z",
+                                              "C",
+                                              "I",
+                                              "J",
+                                              "F",
+                                              "D",
+                                              "[C",
+                                              "Ljava/lang/String;",
+                                              "Ljava/lang/Object;"];
+
+/// The initial capacity of a `StringBuilder`.
+const DEFAULT_CAPACITY: usize = 16;
+
+/// Assembles the classes of the runtime.
+pub fn classes() -> Vec<ClassFile> {
+    CLASSES.iter()
+        .map(|source| jasm::assemble(source).expect("the runtime classes are valid"))
+        .collect()
+}
+
+/// Creates an interpreter running the runtime, with a boot class loader that
+/// defines the classes of the runtime and an application class loader that
+/// delegates to it and loads classes from `classpath`, which it returns.
+pub fn boot(classpath: ClassPath) -> Result<(Interpreter, LoaderId)> {
+    let mut loaders = ClassLoaders::new();
+    let boot = loaders.add(None, ClassPath::new());
+    let app = loaders.add(Some(boot), classpath);
+    for class in classes() {
+        try!(loaders.define_class(boot, class));
+    }
+    let mut interpreter = Interpreter::new(loaders);
+    register(interpreter.natives_mut());
+    Ok((interpreter, app))
+}
+
+/// Runs the `main` method of the class `name`, which `loader` loads, with
+/// `args` as its arguments.
+pub fn run_main(interpreter: &mut Interpreter,
+                loader: LoaderId,
+                name: &str,
+                args: &[String])
+                -> Result<()> {
+    let class = try!(interpreter.loaders_mut().load(loader, name));
+    let args = try!(interpreter.new_string_array(loader, args));
+    let args = [Value::Reference(args)];
+    try!(interpreter.invoke_static(&class, "main", "([Ljava/lang/String;)V", &args));
+    Ok(())
+}
+
+/// Registers the native methods of the runtime.
+pub fn register(natives: &mut Natives) {
+    register_object(natives);
+    register_string(natives);
+    register_string_builder(natives);
+    register_print_stream(natives);
+    register_system(natives);
+    register_math(natives);
+    register_boxes(natives);
+}
+
+fn register_object(natives: &mut Natives) {
+    let object = "java/lang/Object";
+    natives.register(object, "hashCode", "()I", |_, _, args| {
+        let this = try!(object_arg(args, 0));
+        Ok(Some(Value::Int(identity_hash(this))))
+    });
+    natives.register(object, "equals", "(Ljava/lang/Object;)Z", |_, _, args| {
+        let equal = try!(reference_arg(args, 0)) == try!(reference_arg(args, 1));
+        Ok(Some(boolean(equal)))
+    });
+    natives.register(object, "toString", "()Ljava/lang/String;", |interpreter, class, args| {
+        let this = try!(object_arg(args, 0));
+        // Class.getName() for an array is its descriptor, with dots.
+        let name = match *interpreter.heap().get(this) {
+            Object::Instance(ref instance) => instance.class().name().to_string(),
+            Object::Array(ref array) => format!("[{}", array.component),
+        };
+        let hash = match try!(interpreter.invoke_virtual(this, "hashCode", "()I", &[])) {
+            Some(Value::Int(hash)) => hash,
+            other => return Err(Error::IllegalArgument(format!("hashCode returned {:?}", other))),
+        };
+        let value = format!("{}@{:x}", name.replace('/', "."), hash);
+        new_string(interpreter, class, &value)
+    });
+}
+
+fn register_string(natives: &mut Natives) {
+    let string = "java/lang/String";
+    natives.register(string, "<init>", "()V", |interpreter, _, args| {
+        let this = try!(object_arg(args, 0));
+        let chars = try!(new_chars(interpreter, vec![]));
+        try!(set_string_chars(interpreter, this, chars));
+        Ok(None)
+    });
+    natives.register(string, "<init>", "([C)V", |interpreter, _, args| {
+        let this = try!(object_arg(args, 0));
+        let chars = try!(char_array(interpreter, try!(object_arg(args, 1))));
+        let chars = try!(new_chars(interpreter, chars));
+        try!(set_string_chars(interpreter, this, chars));
+        Ok(None)
+    });
+    natives.register(string, "<init>", "([CII)V", |interpreter, _, args| {
+        let this = try!(object_arg(args, 0));
+        let chars = try!(char_array(interpreter, try!(object_arg(args, 1))));
+        let (offset, count) = (try!(int_arg(args, 2)), try!(int_arg(args, 3)));
+        let range = try!(range(offset, offset.wrapping_add(count), chars.len()));
+        let chars = try!(new_chars(interpreter, chars[range].to_vec()));
+        try!(set_string_chars(interpreter, this, chars));
+        Ok(None)
+    });
+    natives.register(string, "length", "()I", |interpreter, _, args| {
+        let chars = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        Ok(Some(Value::Int(chars.len() as i32)))
+    });
+    natives.register(string, "isEmpty", "()Z", |interpreter, _, args| {
+        let chars = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        Ok(Some(boolean(chars.is_empty())))
+    });
+    natives.register(string, "charAt", "(I)C", |interpreter, _, args| {
+        let chars = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        char_at(&chars, try!(int_arg(args, 1)))
+    });
+    natives.register(string, "equals", "(Ljava/lang/Object;)Z", |interpreter, _, args| {
+        let this = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        let equal = match try!(reference_arg(args, 1)) {
+            Some(other) if is_string(interpreter, other) => {
+                this == try!(string_chars(interpreter, other))
+            }
+            _ => false,
+        };
+        Ok(Some(boolean(equal)))
+    });
+    natives.register(string, "hashCode", "()I", |interpreter, _, args| {
+        let chars = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        let hash = chars.iter()
+            .fold(0i32, |hash, &c| hash.wrapping_mul(31).wrapping_add(c as i32));
+        Ok(Some(Value::Int(hash)))
+    });
+    natives.register(string, "compareTo", "(Ljava/lang/String;)I", |interpreter, _, args| {
+        let this = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        let other = try!(string_chars(interpreter, try!(object_arg(args, 1))));
+        let difference = this.iter()
+            .zip(other.iter())
+            .map(|(&a, &b)| a as i32 - b as i32)
+            .find(|&difference| difference != 0)
+            .unwrap_or(this.len() as i32 - other.len() as i32);
+        Ok(Some(Value::Int(difference)))
+    });
+    natives.register(string, "toString", "()Ljava/lang/String;", |_, _, args| {
+        Ok(Some(Value::Reference(try!(object_arg(args, 0)))))
+    });
+    natives.register(string,
+                     "concat",
+                     "(Ljava/lang/String;)Ljava/lang/String;",
+                     |interpreter, class, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        let other = try!(string_arg(interpreter, args, 1));
+        new_string(interpreter, class, &(this + &other))
+    });
+    natives.register(string, "substring", "(I)Ljava/lang/String;", |interpreter, class, args| {
+        let chars = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        let range = try!(range(try!(int_arg(args, 1)), chars.len() as i32, chars.len()));
+        new_string(interpreter, class, &String::from_utf16_lossy(&chars[range]))
+    });
+    natives.register(string,
+                     "substring",
+                     "(II)Ljava/lang/String;",
+                     |interpreter, class, args| {
+        let chars = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        let range = try!(range(try!(int_arg(args, 1)), try!(int_arg(args, 2)), chars.len()));
+        new_string(interpreter, class, &String::from_utf16_lossy(&chars[range]))
+    });
+    natives.register(string, "indexOf", "(I)I", |interpreter, _, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        let index = char::from_u32(try!(int_arg(args, 1)) as u32)
+            .and_then(|c| this.find(c))
+            .map_or(-1, |index| utf16_len(&this[..index]));
+        Ok(Some(Value::Int(index)))
+    });
+    natives.register(string, "indexOf", "(Ljava/lang/String;)I", |interpreter, _, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        let other = try!(string_arg(interpreter, args, 1));
+        let index = this.find(&other[..]).map_or(-1, |index| utf16_len(&this[..index]));
+        Ok(Some(Value::Int(index)))
+    });
+    natives.register(string, "startsWith", "(Ljava/lang/String;)Z", |interpreter, _, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        let prefix = try!(string_arg(interpreter, args, 1));
+        Ok(Some(boolean(this.starts_with(&prefix[..]))))
+    });
+    natives.register(string, "endsWith", "(Ljava/lang/String;)Z", |interpreter, _, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        let suffix = try!(string_arg(interpreter, args, 1));
+        Ok(Some(boolean(this.ends_with(&suffix[..]))))
+    });
+    natives.register(string, "toCharArray", "()[C", |interpreter, _, args| {
+        let chars = try!(string_chars(interpreter, try!(object_arg(args, 0))));
+        Ok(Some(Value::Reference(try!(new_chars(interpreter, chars)))))
+    });
+    natives.register(string, "toUpperCase", "()Ljava/lang/String;", |interpreter, class, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        new_string(interpreter, class, &this.to_uppercase())
+    });
+    natives.register(string, "toLowerCase", "()Ljava/lang/String;", |interpreter, class, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        new_string(interpreter, class, &this.to_lowercase())
+    });
+    natives.register(string, "trim", "()Ljava/lang/String;", |interpreter, class, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        new_string(interpreter, class, this.trim_matches(|c| c <= ' '))
+    });
+    natives.register(string, "intern", "()Ljava/lang/String;", |interpreter, class, args| {
+        let this = try!(string_arg(interpreter, args, 0));
+        let string = try!(interpreter.intern(class.loader(), &this));
+        Ok(Some(Value::Reference(string)))
+    });
+    for &descriptor in FORMATTED.iter().filter(|&&descriptor| descriptor != "Ljava/lang/String;") {
+        let method = format!("({})Ljava/lang/String;", descriptor);
+        natives.register(string, "valueOf", &method, move |interpreter, class, args| {
+            let value = try!(format(interpreter, descriptor, args[0]));
+            new_string(interpreter, class, &value)
+        });
+    }
+}
+
+fn register_string_builder(natives: &mut Natives) {
+    let builder = "java/lang/StringBuilder";
+    natives.register(builder, "<init>", "()V", |interpreter, _, args| {
+        let this = try!(object_arg(args, 0));
+        try!(init_builder(interpreter, this, DEFAULT_CAPACITY));
+        Ok(None)
+    });
+    natives.register(builder, "<init>", "(I)V", |interpreter, _, args| {
+        let this = try!(object_arg(args, 0));
+        let capacity = try!(int_arg(args, 1));
+        if capacity < 0 {
+            return Err(exception("java/lang/NegativeArraySizeException", capacity.to_string()));
+        }
+        try!(init_builder(interpreter, this, capacity as usize));
+        Ok(None)
+    });
+    natives.register(builder, "<init>", "(Ljava/lang/String;)V", |interpreter, _, args| {
+        let this = try!(object_arg(args, 0));
+        let value = try!(string_arg(interpreter, args, 1));
+        try!(init_builder(interpreter, this, utf16_len(&value) as usize + DEFAULT_CAPACITY));
+        try!(append(interpreter, this, &value));
+        Ok(None)
+    });
+    for &descriptor in FORMATTED {
+        let method = format!("({})Ljava/lang/StringBuilder;", descriptor);
+        natives.register(builder, "append", &method, move |interpreter, _, args| {
+            let this = try!(object_arg(args, 0));
+            let value = try!(format(interpreter, descriptor, args[1]));
+            try!(append(interpreter, this, &value));
+            Ok(Some(Value::Reference(this)))
+        });
+    }
+    natives.register(builder, "length", "()I", |interpreter, _, args| {
+        let chars = try!(builder_chars(interpreter, try!(object_arg(args, 0))));
+        Ok(Some(Value::Int(chars.len() as i32)))
+    });
+    natives.register(builder, "charAt", "(I)C", |interpreter, _, args| {
+        let chars = try!(builder_chars(interpreter, try!(object_arg(args, 0))));
+        char_at(&chars, try!(int_arg(args, 1)))
+    });
+    natives.register(builder,
+                     "reverse",
+                     "()Ljava/lang/StringBuilder;",
+                     |interpreter, _, args| {
+        let this = try!(object_arg(args, 0));
+        let chars = try!(builder_chars(interpreter, this));
+        let reversed: String = String::from_utf16_lossy(&chars).chars().rev().collect();
+        try!(interpreter.set_field(this, "java/lang/StringBuilder", "count", "I", Value::Int(0)));
+        try!(append(interpreter, this, &reversed));
+        Ok(Some(Value::Reference(this)))
+    });
+    natives.register(builder,
+                     "toString",
+                     "()Ljava/lang/String;",
+                     |interpreter, class, args| {
+        let chars = try!(builder_chars(interpreter, try!(object_arg(args, 0))));
+        new_string(interpreter, class, &String::from_utf16_lossy(&chars))
+    });
+}
+
+fn register_print_stream(natives: &mut Natives) {
+    let stream = "java/io/PrintStream";
+    for &descriptor in FORMATTED {
+        natives.register(stream,
+                         "print",
+                         &format!("({})V", descriptor),
+                         move |interpreter, _, args| {
+            let value = try!(format(interpreter, descriptor, args[1]));
+            print(interpreter, try!(object_arg(args, 0)), &value)
+        });
+        natives.register(stream,
+                         "println",
+                         &format!("({})V", descriptor),
+                         move |interpreter, _, args| {
+            let value = try!(format(interpreter, descriptor, args[1]));
+            print(interpreter, try!(object_arg(args, 0)), &(value + "\n"))
+        });
+    }
+    natives.register(stream, "println", "()V", |interpreter, _, args| {
+        print(interpreter, try!(object_arg(args, 0)), "\n")
+    });
+    natives.register(stream, "flush", "()V", |interpreter, _, args| {
+        let result = match try!(stream_fd(interpreter, try!(object_arg(args, 0)))) {
+            2 => interpreter.stderr().flush(),
+            _ => interpreter.stdout().flush(),
+        };
+        try!(result.map_err(|err| exception("java/io/IOException", err.to_string())));
+        Ok(None)
+    });
+}
+
+fn register_system(natives: &mut Natives) {
+    let system = "java/lang/System";
+    natives.register(system, "currentTimeMillis", "()J", |_, _, _| {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let millis = elapsed.as_secs() as i64 * 1000 + elapsed.subsec_millis() as i64;
+        Ok(Some(Value::Long(millis)))
+    });
+    let start = Instant::now();
+    natives.register(system, "nanoTime", "()J", move |_, _, _| {
+        let elapsed = start.elapsed();
+        let nanos = elapsed.as_secs() as i64 * 1_000_000_000 + elapsed.subsec_nanos() as i64;
+        Ok(Some(Value::Long(nanos)))
+    });
+    natives.register(system,
+                     "arraycopy",
+                     "(Ljava/lang/Object;ILjava/lang/Object;II)V",
+                     |interpreter, _, args| {
+        let source = try!(object_arg(args, 0));
+        let destination = try!(object_arg(args, 2));
+        let (source_pos, destination_pos) = (try!(int_arg(args, 1)), try!(int_arg(args, 3)));
+        let length = try!(int_arg(args, 4));
+        try!(array_copy(interpreter, source, source_pos, destination, destination_pos, length));
+        Ok(None)
+    });
+    natives.register(system, "identityHashCode", "(Ljava/lang/Object;)I", |_, _, args| {
+        let hash = try!(reference_arg(args, 0)).map_or(0, identity_hash);
+        Ok(Some(Value::Int(hash)))
+    });
+    natives.register(system,
+                     "lineSeparator",
+                     "()Ljava/lang/String;",
+                     |interpreter, class, _| {
+        let string = try!(interpreter.intern(class.loader(), "\n"));
+        Ok(Some(Value::Reference(string)))
+    });
+}
+
+fn register_math(natives: &mut Natives) {
+    let math = "java/lang/Math";
+    natives.register(math, "abs", "(I)I", |_, _, args| {
+        Ok(Some(Value::Int(try!(int_arg(args, 0)).wrapping_abs())))
+    });
+    natives.register(math, "abs", "(J)J", |_, _, args| {
+        Ok(Some(Value::Long(try!(long_arg(args, 0)).wrapping_abs())))
+    });
+    natives.register(math, "abs", "(F)F", |_, _, args| {
+        Ok(Some(Value::Float(try!(float_arg(args, 0)).abs())))
+    });
+    natives.register(math, "abs", "(D)D", |_, _, args| {
+        Ok(Some(Value::Double(try!(double_arg(args, 0)).abs())))
+    });
+    natives.register(math, "max", "(II)I", |_, _, args| {
+        Ok(Some(Value::Int(try!(int_arg(args, 0)).max(try!(int_arg(args, 1))))))
+    });
+    natives.register(math, "max", "(JJ)J", |_, _, args| {
+        Ok(Some(Value::Long(try!(long_arg(args, 0)).max(try!(long_arg(args, 1))))))
+    });
+    natives.register(math, "max", "(FF)F", |_, _, args| {
+        let (a, b) = (try!(float_arg(args, 0)), try!(float_arg(args, 1)));
+        Ok(Some(Value::Float(extremum(a as f64, b as f64, Ordering::Greater) as f32)))
+    });
+    natives.register(math, "max", "(DD)D", |_, _, args| {
+        let (a, b) = (try!(double_arg(args, 0)), try!(double_arg(args, 1)));
+        Ok(Some(Value::Double(extremum(a, b, Ordering::Greater))))
+    });
+    natives.register(math, "min", "(II)I", |_, _, args| {
+        Ok(Some(Value::Int(try!(int_arg(args, 0)).min(try!(int_arg(args, 1))))))
+    });
+    natives.register(math, "min", "(JJ)J", |_, _, args| {
+        Ok(Some(Value::Long(try!(long_arg(args, 0)).min(try!(long_arg(args, 1))))))
+    });
+    natives.register(math, "min", "(FF)F", |_, _, args| {
+        let (a, b) = (try!(float_arg(args, 0)), try!(float_arg(args, 1)));
+        Ok(Some(Value::Float(extremum(a as f64, b as f64, Ordering::Less) as f32)))
+    });
+    natives.register(math, "min", "(DD)D", |_, _, args| {
+        let (a, b) = (try!(double_arg(args, 0)), try!(double_arg(args, 1)));
+        Ok(Some(Value::Double(extremum(a, b, Ordering::Less))))
+    });
+    natives.register(math, "round", "(F)I", |_, _, args| {
+        let value = try!(float_arg(args, 0));
+        Ok(Some(Value::Int(round(value as f64) as i32)))
+    });
+    natives.register(math, "round", "(D)J", |_, _, args| {
+        Ok(Some(Value::Long(round(try!(double_arg(args, 0))))))
+    });
+    natives.register(math, "pow", "(DD)D", |_, _, args| {
+        let (base, exponent) = (try!(double_arg(args, 0)), try!(double_arg(args, 1)));
+        Ok(Some(Value::Double(base.powf(exponent))))
+    });
+    let functions: &[(&str, fn(f64) -> f64)] = &[("sqrt", f64::sqrt),
+                                                 ("floor", f64::floor),
+                                                 ("ceil", f64::ceil),
+                                                 ("sin", f64::sin),
+                                                 ("cos", f64::cos),
+                                                 ("tan", f64::tan),
+                                                 ("exp", f64::exp),
+                                                 ("log", f64::ln)];
+    for &(name, function) in functions {
+        natives.register(math, name, "(D)D", move |_, _, args| {
+            Ok(Some(Value::Double(function(try!(double_arg(args, 0))))))
+        });
+    }
+}
+
+fn register_boxes(natives: &mut Natives) {
+    let integer = "java/lang/Integer";
+    natives.register(integer,
+                     "valueOf",
+                     "(I)Ljava/lang/Integer;",
+                     |interpreter, class, args| {
+        let value = try!(int_arg(args, 0));
+        new_box(interpreter, class, "I", Value::Int(value))
+    });
+    natives.register(integer, "toString", "(I)Ljava/lang/String;", |interpreter, class, args| {
+        let value = try!(int_arg(args, 0));
+        new_string(interpreter, class, &value.to_string())
+    });
+    natives.register(integer, "parseInt", "(Ljava/lang/String;)I", |interpreter, _, args| {
+        let value = try!(string_arg(interpreter, args, 0));
+        Ok(Some(Value::Int(try!(parse_number(&value)))))
+    });
+    let long = "java/lang/Long";
+    natives.register(long, "valueOf", "(J)Ljava/lang/Long;", |interpreter, class, args| {
+        let value = try!(long_arg(args, 0));
+        new_box(interpreter, class, "J", Value::Long(value))
+    });
+    natives.register(long, "toString", "(J)Ljava/lang/String;", |interpreter, class, args| {
+        let value = try!(long_arg(args, 0));
+        new_string(interpreter, class, &value.to_string())
+    });
+    natives.register(long, "parseLong", "(Ljava/lang/String;)J", |interpreter, _, args| {
+        let value = try!(string_arg(interpreter, args, 0));
+        Ok(Some(Value::Long(try!(parse_number(&value)))))
+    });
+    for &(owner, descriptor) in &[(integer, "I"), (long, "J")] {
+        natives.register(owner,
+                         "toString",
+                         "()Ljava/lang/String;",
+                         move |interpreter, class, args| {
+            let this = try!(object_arg(args, 0));
+            let value = try!(interpreter.field(this, owner, "value", descriptor));
+            let value = try!(format(interpreter, descriptor, value));
+            new_string(interpreter, class, &value)
+        });
+        natives.register(owner,
+                         "equals",
+                         "(Ljava/lang/Object;)Z",
+                         move |interpreter, _, args| {
+            let this = try!(object_arg(args, 0));
+            let equal = match try!(reference_arg(args, 1)) {
+                Some(other) if is_instance_of(interpreter, other, owner) => {
+                    try!(interpreter.field(this, owner, "value", descriptor)) ==
+                    try!(interpreter.field(other, owner, "value", descriptor))
+                }
+                _ => false,
+            };
+            Ok(Some(boolean(equal)))
+        });
+    }
+}
+
+/// Formats `value`, passed as a parameter of type `descriptor`, one of
+/// `FORMATTED`, as `String.valueOf` does.
+fn format(interpreter: &mut Interpreter, descriptor: &str, value: Value) -> Result<String> {
+    let formatted = match (descriptor, value) {
+        ("Z", Value::Int(value)) => (value != 0).to_string(),
+        ("C", Value::Int(value)) => String::from_utf16_lossy(&[value as u16]),
+        ("I", Value::Int(value)) => value.to_string(),
+        ("J", Value::Long(value)) => value.to_string(),
+        ("F", Value::Float(value)) => format_floating(value),
+        ("D", Value::Double(value)) => format_floating(value),
+        ("[C", Value::Reference(chars)) => {
+            String::from_utf16_lossy(&try!(char_array(interpreter, chars)))
+        }
+        ("[C", Value::Null) => {
+            return Err(exception_without_message("java/lang/NullPointerException"))
+        }
+        (_, Value::Null) => "null".to_string(),
+        ("Ljava/lang/String;", Value::Reference(string)) => try!(interpreter.string_value(string)),
+        ("Ljava/lang/Object;", Value::Reference(object)) => {
+            let descriptor = "()Ljava/lang/String;";
+            match try!(interpreter.invoke_virtual(object, "toString", descriptor, &[])) {
+                Some(Value::Reference(string)) => try!(interpreter.string_value(string)),
+                _ => "null".to_string(),
+            }
+        }
+        _ => {
+            let message = format!("cannot format {:?} as {}", value, descriptor);
+            return Err(Error::IllegalArgument(message));
+        }
+    };
+    Ok(formatted)
+}
+
+/// Formats a `float` or `double` as `Double.toString` does: in decimal
+/// notation with at least one digit after the point for magnitudes from
+/// 10^-3 to 10^7, and in scientific notation otherwise.
+fn format_floating<F>(value: F) -> String
+    where F: Copy + Display + LowerExp + Into<f64>
+{
+    let double = value.into();
+    if double.is_nan() {
+        return "NaN".to_string();
+    }
+    if double.is_infinite() {
+        return if double > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if double == 0.0 || (double.abs() >= 1e-3 && double.abs() < 1e7) {
+        let decimal = value.to_string();
+        return if decimal.contains('.') { decimal } else { decimal + ".0" };
+    }
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, &exponent[1..])
+    } else {
+        format!("{}.0E{}", mantissa, &exponent[1..])
+    }
+}
+
+/// Returns the greater or lesser of `a` and `b` as `Math.max` and `min` do,
+/// with `NaN` if either is and with -0.0 less than 0.0.
+fn extremum(a: f64, b: f64, ordering: Ordering) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        return f64::NAN;
+    }
+    let order = a.partial_cmp(&b)
+        .unwrap()
+        .then(a.is_sign_positive().cmp(&b.is_sign_positive()));
+    if order == ordering { a } else { b }
+}
+
+/// Rounds `value` to the closest integer, with ties rounding up, as
+/// `Math.round` does.
+fn round(value: f64) -> i64 {
+    if value.is_nan() {
+        0
+    } else {
+        (value + 0.5).floor() as i64
+    }
+}
+
+/// Parses a decimal integer as `Integer.parseInt` and `Long.parseLong` do.
+fn parse_number<N: ::std::str::FromStr>(value: &str) -> Result<N> {
+    let valid = !value.starts_with('+') || !value[1..].starts_with('-');
+    match value.parse() {
+        Ok(number) if valid => Ok(number),
+        _ => {
+            let message = format!("For input string: \"{}\"", value);
+            Err(exception("java/lang/NumberFormatException", message))
+        }
+    }
+}
+
+/// Creates an instance of the box class `class` holding `value` in its
+/// `value` field.
+fn new_box(interpreter: &mut Interpreter,
+           class: &Rc<Class>,
+           descriptor: &str,
+           value: Value)
+           -> Result<Option<Value>> {
+    let instance = try!(interpreter.instantiate(class));
+    try!(interpreter.set_field(instance, class.name(), "value", descriptor, value));
+    Ok(Some(Value::Reference(instance)))
+}
+
+/// Writes `value` to the stream that the `PrintStream` `stream` writes to.
+fn print(interpreter: &mut Interpreter, stream: Reference, value: &str) -> Result<Option<Value>> {
+    let result = match try!(stream_fd(interpreter, stream)) {
+        2 => interpreter.stderr().write_all(value.as_bytes()),
+        _ => interpreter.stdout().write_all(value.as_bytes()),
+    };
+    try!(result.map_err(|err| exception("java/io/IOException", err.to_string())));
+    Ok(None)
+}
+
+/// The file descriptor that the `PrintStream` `stream` writes to.
+fn stream_fd(interpreter: &Interpreter, stream: Reference) -> Result<i32> {
+    match try!(interpreter.field(stream, "java/io/PrintStream", "fd", "I")) {
+        Value::Int(fd) => Ok(fd),
+        other => Err(Error::IllegalArgument(format!("invalid file descriptor {:?}", other))),
+    }
+}
+
+/// Copies `length` elements as `System.arraycopy` does, as if through a
+/// temporary array so that the arrays may overlap.
+fn array_copy(interpreter: &mut Interpreter,
+              source: Reference,
+              source_pos: i32,
+              destination: Reference,
+              destination_pos: i32,
+              length: i32)
+              -> Result<()> {
+    let values = {
+        let (source, destination) =
+            match (interpreter.heap().array(source), interpreter.heap().array(destination)) {
+                (Some(source), Some(destination)) => (source, destination),
+                _ => {
+                    let message = "arraycopy: source or destination is not an array";
+                    return Err(exception("java/lang/ArrayStoreException", message.to_string()));
+                }
+            };
+        if source.component != destination.component &&
+           (source.component.is_primitive() || destination.component.is_primitive()) {
+            let message = "arraycopy: type mismatch";
+            return Err(exception("java/lang/ArrayStoreException", message.to_string()));
+        }
+        try!(range(source_pos, source_pos.wrapping_add(length), source.len()));
+        try!(range(destination_pos, destination_pos.wrapping_add(length), destination.len()));
+        (0..length as usize)
+            .map(|index| source.get(source_pos as usize + index).unwrap())
+            .collect::<Vec<_>>()
+    };
+    if let Object::Array(ref mut destination) = *interpreter.heap_mut().get_mut(destination) {
+        for (index, value) in values.into_iter().enumerate() {
+            destination.set(destination_pos as usize + index, value);
+        }
+    }
+    Ok(())
+}
+
+/// Sets up the `StringBuilder` `builder` with room for `capacity`
+/// characters.
+fn init_builder(interpreter: &mut Interpreter, builder: Reference, capacity: usize) -> Result<()> {
+    let chars = try!(new_chars(interpreter, vec![0; capacity]));
+    let owner = "java/lang/StringBuilder";
+    try!(interpreter.set_field(builder, owner, "value", "[C", Value::Reference(chars)));
+    interpreter.set_field(builder, owner, "count", "I", Value::Int(0))
+}
+
+/// Appends `value` to the `StringBuilder` `builder`, growing its array if
+/// there is not room for it.
+fn append(interpreter: &mut Interpreter, builder: Reference, value: &str) -> Result<()> {
+    let owner = "java/lang/StringBuilder";
+    let mut chars = try!(builder_chars(interpreter, builder));
+    chars.extend(value.encode_utf16());
+    let count = chars.len();
+    let array = try!(object_field(interpreter, builder, owner, "value", "[C"));
+    let capacity = interpreter.heap().array(array).map_or(0, |array| array.len());
+    if count <= capacity {
+        if let Object::Array(Array { elements: Elements::Char(ref mut elements), .. }) =
+               *interpreter.heap_mut().get_mut(array) {
+            elements[..count].copy_from_slice(&chars);
+        }
+    } else {
+        let capacity = count.max(capacity * 2 + 2);
+        chars.resize(capacity, 0);
+        let array = try!(new_chars(interpreter, chars));
+        try!(interpreter.set_field(builder, owner, "value", "[C", Value::Reference(array)));
+    }
+    interpreter.set_field(builder, owner, "count", "I", Value::Int(count as i32))
+}
+
+/// The characters that the `StringBuilder` `builder` holds.
+fn builder_chars(interpreter: &Interpreter, builder: Reference) -> Result<Vec<u16>> {
+    let owner = "java/lang/StringBuilder";
+    let array = try!(object_field(interpreter, builder, owner, "value", "[C"));
+    let mut chars = try!(char_array(interpreter, array));
+    match try!(interpreter.field(builder, owner, "count", "I")) {
+        Value::Int(count) => chars.truncate(count as usize),
+        other => return Err(Error::IllegalArgument(format!("invalid count {:?}", other))),
+    }
+    Ok(chars)
+}
+
+/// The characters of the `String` `string`.
+fn string_chars(interpreter: &Interpreter, string: Reference) -> Result<Vec<u16>> {
+    let array = try!(object_field(interpreter, string, "java/lang/String", "value", "[C"));
+    char_array(interpreter, array)
+}
+
+fn set_string_chars(interpreter: &mut Interpreter,
+                    string: Reference,
+                    chars: Reference)
+                    -> Result<()> {
+    interpreter.set_field(string, "java/lang/String", "value", "[C", Value::Reference(chars))
+}
+
+fn is_string(interpreter: &Interpreter, reference: Reference) -> bool {
+    is_instance_of(interpreter, reference, "java/lang/String")
+}
+
+/// Whether `reference` refers to an instance of exactly the class `name`.
+fn is_instance_of(interpreter: &Interpreter, reference: Reference, name: &str) -> bool {
+    interpreter.heap().instance(reference).map_or(false, |instance| instance.class().name() == name)
+}
+
+/// The value of a reference field, throwing a `NullPointerException` if it is
+/// `null`.
+fn object_field(interpreter: &Interpreter,
+                reference: Reference,
+                owner: &str,
+                name: &str,
+                descriptor: &str)
+                -> Result<Reference> {
+    match try!(interpreter.field(reference, owner, name, descriptor)) {
+        Value::Reference(value) => Ok(value),
+        _ => Err(exception_without_message("java/lang/NullPointerException")),
+    }
+}
+
+/// The elements of the `char[]` `array`.
+fn char_array(interpreter: &Interpreter, array: Reference) -> Result<Vec<u16>> {
+    match interpreter.heap().array(array) {
+        Some(&Array { elements: Elements::Char(ref chars), .. }) => Ok(chars.clone()),
+        _ => Err(Error::IllegalArgument("expected a char[]".to_string())),
+    }
+}
+
+fn new_chars(interpreter: &mut Interpreter, chars: Vec<u16>) -> Result<Reference> {
+    interpreter.allocate(Object::Array(Array {
+        component: FieldType::Char,
+        elements: Elements::Char(chars),
+    }))
+}
+
+fn new_string(interpreter: &mut Interpreter,
+              class: &Rc<Class>,
+              value: &str)
+              -> Result<Option<Value>> {
+    let string = try!(interpreter.new_string(class.loader(), value));
+    Ok(Some(Value::Reference(string)))
+}
+
+fn char_at(chars: &[u16], index: i32) -> Result<Option<Value>> {
+    match chars.get(index as usize) {
+        Some(&c) if index >= 0 => Ok(Some(Value::Int(c as i32))),
+        _ => {
+            let message = format!("index {}, length {}", index, chars.len());
+            Err(exception("java/lang/StringIndexOutOfBoundsException", message))
+        }
+    }
+}
+
+/// Checks that `begin..end` is a range of indices of a sequence of `length`
+/// elements.
+fn range(begin: i32, end: i32, length: usize) -> Result<::std::ops::Range<usize>> {
+    if begin < 0 || end < begin || end as usize > length {
+        let message = format!("begin {}, end {}, length {}", begin, end, length);
+        return Err(exception("java/lang/IndexOutOfBoundsException", message));
+    }
+    Ok(begin as usize..end as usize)
+}
+
+/// The length of `value` in UTF-16 code units, as Java counts it.
+fn utf16_len(value: &str) -> i32 {
+    value.encode_utf16().count() as i32
+}
+
+/// A hash code for an object that does not change while it lives.
+fn identity_hash(reference: Reference) -> i32 {
+    ((reference.index() as u32).wrapping_mul(0x9e37_79b9) >> 1) as i32
+}
+
+fn boolean(value: bool) -> Value {
+    Value::Int(value as i32)
+}
+
+fn int_arg(args: &[Value], index: usize) -> Result<i32> {
+    match args.get(index) {
+        Some(&Value::Int(value)) => Ok(value),
+        other => Err(invalid_arg(index, "an int", other)),
+    }
+}
+
+fn long_arg(args: &[Value], index: usize) -> Result<i64> {
+    match args.get(index) {
+        Some(&Value::Long(value)) => Ok(value),
+        other => Err(invalid_arg(index, "a long", other)),
+    }
+}
+
+fn float_arg(args: &[Value], index: usize) -> Result<f32> {
+    match args.get(index) {
+        Some(&Value::Float(value)) => Ok(value),
+        other => Err(invalid_arg(index, "a float", other)),
+    }
+}
+
+fn double_arg(args: &[Value], index: usize) -> Result<f64> {
+    match args.get(index) {
+        Some(&Value::Double(value)) => Ok(value),
+        other => Err(invalid_arg(index, "a double", other)),
+    }
+}
+
+/// The reference at `index`, or `None` for `null`.
+fn reference_arg(args: &[Value], index: usize) -> Result<Option<Reference>> {
+    match args.get(index) {
+        Some(&Value::Reference(value)) => Ok(Some(value)),
+        Some(&Value::Null) => Ok(None),
+        other => Err(invalid_arg(index, "a reference", other)),
+    }
+}
+
+/// The reference at `index`, throwing a `NullPointerException` if it is
+/// `null`.
+fn object_arg(args: &[Value], index: usize) -> Result<Reference> {
+    try!(reference_arg(args, index))
+        .ok_or_else(|| exception_without_message("java/lang/NullPointerException"))
+}
+
+/// The value of the `String` at `index`, throwing a `NullPointerException`
+/// if it is `null`.
+fn string_arg(interpreter: &Interpreter, args: &[Value], index: usize) -> Result<String> {
+    interpreter.string_value(try!(object_arg(args, index)))
+}
+
+fn invalid_arg(index: usize, expected: &str, actual: Option<&Value>) -> Error {
+    Error::IllegalArgument(format!("argument {} is not {}: {:?}", index, expected, actual))
+}
+
+fn exception(class: &str, message: String) -> Error {
+    Error::Exception {
+        class: class.to_string(),
+        message: Some(message),
+    }
+}
+
+fn exception_without_message(class: &str) -> Error {
+    Error::Exception {
+        class: class.to_string(),
+        message: None,
+    }
+}
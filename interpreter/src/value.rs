@@ -0,0 +1,85 @@
+//! The values the interpreter computes with.
+use classfile::FieldType;
+
+use heap::Reference;
+
+/// A value held in a local variable or on the operand stack.
+///
+/// `boolean`, `byte`, `char` and `short` values are held as `Int`s, as they
+/// are by the JVM.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    /// The `null` reference.
+    Null,
+    /// A reference to an object on the heap.
+    Reference(Reference),
+    /// The pc following a `jsr`, for `ret` to return to.
+    ReturnAddress(usize),
+}
+
+/// The computational type of a value: what instructions may operate on it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+    ReturnAddress,
+}
+
+impl Value {
+    /// The value a field or array element of type `field_type` holds until
+    /// it is first assigned: zero, or `null` for references.
+    pub fn default_of(field_type: &FieldType) -> Value {
+        match Kind::of(field_type) {
+            Kind::Long => Value::Long(0),
+            Kind::Float => Value::Float(0.0),
+            Kind::Double => Value::Double(0.0),
+            Kind::Reference => Value::Null,
+            _ => Value::Int(0),
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        match *self {
+            Value::Int(_) => Kind::Int,
+            Value::Long(_) => Kind::Long,
+            Value::Float(_) => Kind::Float,
+            Value::Double(_) => Kind::Double,
+            Value::Null | Value::Reference(_) => Kind::Reference,
+            Value::ReturnAddress(_) => Kind::ReturnAddress,
+        }
+    }
+
+    /// The number of local variable slots (or operand stack entries) taken by
+    /// this value. `long` and `double` take two, all others one.
+    pub fn slots(&self) -> u16 {
+        self.kind().slots()
+    }
+}
+
+impl Kind {
+    /// The kind of values of a field, parameter or return type.
+    pub fn of(field_type: &FieldType) -> Kind {
+        match *field_type {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short |
+            FieldType::Int => Kind::Int,
+            FieldType::Long => Kind::Long,
+            FieldType::Float => Kind::Float,
+            FieldType::Double => Kind::Double,
+            FieldType::Object(_) | FieldType::Array(_) => Kind::Reference,
+        }
+    }
+
+    pub fn slots(&self) -> u16 {
+        match *self {
+            Kind::Long | Kind::Double => 2,
+            _ => 1,
+        }
+    }
+}
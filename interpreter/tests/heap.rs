@@ -0,0 +1,126 @@
+extern crate classfile;
+extern crate classpath;
+extern crate interpreter;
+extern crate jasm;
+
+use classfile::FieldType;
+use classpath::{ClassLoaders, ClassPath};
+use interpreter::Error;
+use interpreter::heap::{Array, Heap, Instance, Layout, Object};
+use interpreter::value::Value;
+
+fn array(component: FieldType, length: usize) -> Object {
+    Object::Array(Array::new(component, length))
+}
+
+#[test]
+fn should_lay_out_inherited_fields_first() {
+    // Given
+    let mut loaders = ClassLoaders::new();
+    let loader = loaders.add(None, ClassPath::new());
+    let a = jasm::assemble(r#"
+.class p/A
+.super none
+.field x I
+.field static s I
+.field y J
+"#)
+        .unwrap();
+    let b = jasm::assemble(r#"
+.class p/B
+.super p/A
+.field x I
+.field next Lp/B;
+"#)
+        .unwrap();
+    let a = loaders.define_class(loader, a).unwrap();
+    let b = loaders.define_class(loader, b).unwrap();
+
+    // When
+    let a = Layout::new(a, None).unwrap();
+    let b = Layout::new(b, Some(&a)).unwrap();
+
+    // Then
+    assert_eq!(2, a.len());
+    assert_eq!(4, b.len());
+    assert_eq!(Some(0), b.index_of("p/A", "x", "I"));
+    assert_eq!(Some(1), b.index_of("p/A", "y", "J"));
+    assert_eq!(Some(2), b.index_of("p/B", "x", "I"));
+    assert_eq!(None, b.index_of("p/A", "s", "I"));
+    assert_eq!(vec![Value::Int(0), Value::Long(0), Value::Int(0), Value::Null],
+               Instance::new(b.into()).fields);
+}
+
+#[test]
+fn should_narrow_values_stored_in_arrays() {
+    // Given
+    let mut booleans = Array::new(FieldType::Boolean, 2);
+    let mut bytes = Array::new(FieldType::Byte, 2);
+    let mut chars = Array::new(FieldType::Char, 2);
+    let mut longs = Array::new(FieldType::Long, 2);
+
+    // When
+    let stored = [booleans.set(0, Value::Int(3)),
+                  bytes.set(1, Value::Int(200)),
+                  chars.set(0, Value::Int(-1)),
+                  longs.set(2, Value::Long(1)),
+                  longs.set(1, Value::Int(1))];
+
+    // Then
+    assert_eq!([true, true, true, false, false], stored);
+    assert_eq!(Some(Value::Int(1)), booleans.get(0));
+    assert_eq!(Some(Value::Int(-56)), bytes.get(1));
+    assert_eq!(Some(Value::Int(65535)), chars.get(0));
+    assert_eq!(Some(Value::Long(0)), longs.get(1));
+    assert_eq!(None, longs.get(2));
+}
+
+#[test]
+fn should_collect_unreachable_objects_including_cycles() {
+    // Given
+    let object = FieldType::Object("java/lang/Object".to_string());
+    let mut heap = Heap::new(1 << 20);
+    let root = heap.allocate(array(object.clone(), 1)).unwrap();
+    let child = heap.allocate(array(FieldType::Int, 8)).unwrap();
+    let cycle = heap.allocate(array(object.clone(), 1)).unwrap();
+    let other = heap.allocate(array(object.clone(), 1)).unwrap();
+    for &(from, to) in &[(root, child), (cycle, other), (other, cycle)] {
+        match *heap.get_mut(from) {
+            Object::Array(ref mut array) => assert!(array.set(0, Value::Reference(to))),
+            Object::Instance(_) => unreachable!(),
+        }
+    }
+    let used = heap.used();
+
+    // When
+    let freed = heap.collect(vec![root]);
+
+    // Then
+    assert_eq!(2, freed);
+    assert!(heap.contains(root) && heap.contains(child));
+    assert!(!heap.contains(cycle) && !heap.contains(other));
+    assert_eq!(used - 2 * heap.get(root).size(), heap.used());
+    assert_eq!(0, heap.collect(vec![root]));
+    assert_eq!(2, heap.collect(vec![]));
+    assert!(heap.is_empty());
+}
+
+#[test]
+fn should_refuse_allocations_past_the_limit() {
+    // Given
+    let mut heap = Heap::new(Array::size_of(&FieldType::Int, 100));
+    let first = heap.allocate(array(FieldType::Int, 50)).unwrap();
+
+    // When
+    let refused = heap.allocate(array(FieldType::Int, 50));
+    heap.collect(vec![]);
+    let second = heap.allocate(array(FieldType::Int, 100));
+
+    // Then
+    match refused {
+        Err(Error::Exception { ref class, .. }) if class == "java/lang/OutOfMemoryError" => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(first, second.unwrap());
+    assert_eq!(heap.limit(), heap.used());
+}
@@ -0,0 +1,362 @@
+extern crate classfile;
+extern crate classpath;
+extern crate interpreter;
+extern crate jasm;
+
+use std::fs::File;
+use std::rc::Rc;
+
+use classfile::ClassFile;
+use classfile::reader::ClassReader;
+use classpath::{Class, ClassLoaders, ClassPath};
+use interpreter::Error;
+use interpreter::interpreter::Interpreter;
+use interpreter::value::Value;
+
+/// The minimal `java/lang/Object` that the test classes extend.
+const OBJECT: &'static str = r#"
+.class public java/lang/Object
+.super none
+.method public "<init>" ()V {
+    .code stack 0 locals 1 {
+        return
+    }
+}
+"#;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+/// Creates an interpreter for `classes`, defined by a single loader along
+/// with `java/lang/Object`, and returns it with the first of the classes.
+fn interpreter(classes: Vec<ClassFile>) -> (Interpreter, Rc<Class>) {
+    let mut loaders = ClassLoaders::new();
+    let loader = loaders.add(None, ClassPath::new());
+    loaders.define_class(loader, jasm::assemble(OBJECT).unwrap()).unwrap();
+    let classes: Vec<Rc<Class>> = classes.into_iter()
+        .map(|class| loaders.define_class(loader, class).unwrap())
+        .collect();
+    (Interpreter::new(loaders), classes[0].clone())
+}
+
+/// Runs the static method `name` of the test class `class`.
+fn run(class: &str, name: &str, descriptor: &str, args: &[Value]) -> Option<Value> {
+    let (mut interpreter, class) = interpreter(vec![read_class(class)]);
+    interpreter.invoke_static(&class, name, descriptor, args).unwrap()
+}
+
+fn assert_thrown(result: Result<Option<Value>, Error>, expected: &str) {
+    match result {
+        Err(Error::Exception { ref class, .. }) if class == expected => {}
+        other => panic!("expected {}, got {:?}", expected, other),
+    }
+}
+
+#[test]
+fn should_run_recursive_static_methods() {
+    assert_eq!(Some(Value::Int(6765)),
+               run("MathUtils", "fibonacci", "(I)I", &[Value::Int(20)]));
+}
+
+#[test]
+fn should_run_integer_arithmetic() {
+    assert_eq!(Some(Value::Int(21)),
+               run("MathUtils", "gcd", "(II)I", &[Value::Int(1071), Value::Int(462)]));
+    assert_eq!(Some(Value::Int(1)), run("MathUtils", "isPrime", "(I)Z", &[Value::Int(97)]));
+    assert_eq!(Some(Value::Int(0)), run("MathUtils", "isPrime", "(I)Z", &[Value::Int(91)]));
+    assert_eq!(Some(Value::Int('Q' as i32)),
+               run("MathUtils", "toUpper", "(C)C", &[Value::Int('q' as i32)]));
+    let min = Value::Int(i32::min_value());
+    assert_eq!(Some(min), run("MathUtils", "divide", "(II)I", &[min, Value::Int(-1)]));
+}
+
+#[test]
+fn should_run_long_arithmetic() {
+    assert_eq!(Some(Value::Long(2432902008176640000)),
+               run("MathUtils", "factorial", "(I)J", &[Value::Int(20)]));
+    assert_eq!(Some(Value::Int(64)), run("MathUtils", "bitCount", "(J)I", &[Value::Long(-1)]));
+    assert_eq!(Some(Value::Long(2880067194370816120)),
+               run("ControlFlow", "fib", "(I)J", &[Value::Int(90)]));
+}
+
+#[test]
+fn should_run_floating_point_arithmetic() {
+    match run("MathUtils", "sqrt", "(D)D", &[Value::Double(2.0)]) {
+        Some(Value::Double(root)) => assert!((root - 2f64.sqrt()).abs() < 1e-9, "{}", root),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(Some(Value::Int(3)), run("MathUtils", "round", "(F)I", &[Value::Float(2.5)]));
+    assert_eq!(Some(Value::Double(3.5)),
+               run("MathUtils", "mean", "(JI)D", &[Value::Long(7), Value::Int(2)]));
+    let compare = |a, b| {
+        run("MathUtils", "compare", "(DD)I", &[Value::Double(a), Value::Double(b)])
+    };
+    assert_eq!(Some(Value::Int(-1)), compare(1.0, 2.0));
+    assert_eq!(Some(Value::Int(1)), compare(2.0, 1.0));
+    assert_eq!(Some(Value::Int(0)), compare(::std::f64::NAN, 1.0));
+}
+
+#[test]
+fn should_run_loops_and_switches() {
+    assert_eq!(Some(Value::Int(45)), run("ControlFlow", "sum", "(I)I", &[Value::Int(10)]));
+    assert_eq!(Some(Value::Int(10)), run("ControlFlow", "pairs", "(I)I", &[Value::Int(5)]));
+    assert_eq!(Some(Value::Int(7)), run("ControlFlow", "abs", "(I)I", &[Value::Int(-7)]));
+    let (mut interpreter, class) = interpreter(vec![read_class("Features")]);
+    let mut call = |name, value| {
+        interpreter.invoke_static(&class, name, "(I)I", &[Value::Int(value)])
+    };
+    assert_eq!(Some(Value::Int(30)), call("classify", 2).unwrap());
+    assert_eq!(Some(Value::Int(-1)), call("classify", 3).unwrap());
+    assert_eq!(Some(Value::Int(1)), call("lookup", -100).unwrap());
+    assert_eq!(Some(Value::Int(3)), call("lookup", 1000).unwrap());
+    assert_eq!(Some(Value::Int(0)), call("lookup", 5).unwrap());
+}
+
+#[test]
+fn should_throw_arithmetic_exceptions() {
+    // Given
+    let (mut interpreter, class) = interpreter(vec![read_class("MathUtils")]);
+
+    // When
+    let args = [Value::Int(1), Value::Int(0)];
+    let result = interpreter.invoke_static(&class, "divide", "(II)I", &args);
+
+    // Then
+    assert_thrown(result, "java/lang/ArithmeticException");
+    assert!(interpreter.frames().is_empty());
+}
+
+#[test]
+fn should_overflow_the_stack() {
+    // Given
+    let (mut interpreter, class) = interpreter(vec![read_class("MathUtils")]);
+    interpreter.set_max_depth(100);
+
+    // When
+    let shallow = interpreter.invoke_static(&class, "depth", "(I)I", &[Value::Int(99)]);
+    let deep = interpreter.invoke_static(&class, "depth", "(I)I", &[Value::Int(100)]);
+
+    // Then
+    assert_eq!(Some(Value::Int(99)), shallow.unwrap());
+    assert_thrown(deep, "java/lang/StackOverflowError");
+}
+
+#[test]
+fn should_run_subroutines_and_stack_manipulation() {
+    // Given
+    let (mut interpreter, class) = interpreter(vec![jasm::assemble(r#"
+.version 49 0
+.class Legacy
+.method static m (JI)J {
+    .code stack 8 locals 5 {
+        jsr double
+        lload_0
+        iload_2
+        i2l
+        dup2_x2
+        pop2
+        lsub
+        lreturn
+    double:
+        astore 4
+        iload_2
+        dup
+        iadd
+        istore_2
+        ret 4
+    }
+}
+"#)
+        .unwrap()]);
+
+    // When
+    let result = interpreter
+        .invoke_static(&class, "m", "(JI)J", &[Value::Long(100), Value::Int(3)])
+        .unwrap();
+
+    // Then
+    assert_eq!(Some(Value::Long(6 - 100)), result);
+}
+
+#[test]
+fn should_reject_values_of_the_wrong_kind() {
+    // Given
+    let (mut interpreter, class) = interpreter(vec![jasm::assemble(r#"
+.class Broken
+.method static m (J)I {
+    .code stack 3 locals 2 {
+        lload_0
+        iconst_1
+        iadd
+        ireturn
+    }
+}
+"#)
+        .unwrap()]);
+
+    // When
+    let result = interpreter.invoke_static(&class, "m", "(J)I", &[Value::Long(1)]);
+
+    // Then
+    match result {
+        Err(Error::InvalidCode { pc: 2, .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_check_arguments_against_the_descriptor() {
+    // Given
+    let (mut interpreter, class) = interpreter(vec![read_class("MathUtils")]);
+
+    // When
+    let args = [Value::Long(1), Value::Int(1)];
+    let wrong_kind = interpreter.invoke_static(&class, "gcd", "(II)I", &args);
+    let missing = interpreter.invoke_static(&class, "gcd", "(I)I", &[Value::Int(1)]);
+
+    // Then
+    match wrong_kind {
+        Err(Error::IllegalArgument(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match missing {
+        Err(Error::NoSuchMethod { ref name, .. }) if name == "gcd" => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_report_unsupported_instructions() {
+    // Given
+    let (mut interpreter, class) = interpreter(vec![jasm::assemble(r#"
+.class Reflective
+.method static m ()Ljava/lang/Object; {
+    .code stack 1 locals 0 {
+        ldc Class java/lang/Object
+        areturn
+    }
+}
+"#)
+        .unwrap()]);
+
+    // When
+    let result = interpreter.invoke_static(&class, "m", "()Ljava/lang/Object;", &[]);
+
+    // Then
+    match result {
+        Err(Error::Unsupported { .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+/// Creates an interpreter for the `Shapes` test class and its nested
+/// classes.
+fn shapes() -> (Interpreter, Rc<Class>) {
+    let classes = ["Shapes", "Shapes$Shape", "Shapes$Rect", "Shapes$Square"];
+    interpreter(classes.iter().map(|name| read_class(name)).collect())
+}
+
+#[test]
+fn should_create_objects_and_dispatch_methods() {
+    // Given
+    let (mut interpreter, class) = shapes();
+
+    // When
+    let total = interpreter.invoke_static(&class, "totalArea", "(I)I", &[Value::Int(4)]);
+    let args = [Value::Int(2), Value::Int(3), Value::Int(2)];
+    let scaled = interpreter.invoke_static(&class, "scaled", "(III)I", &args);
+    let hidden = interpreter.invoke_static(&class, "hiddenField", "(I)I", &[Value::Int(3)]);
+
+    // Then
+    assert_eq!(Some(Value::Int(4000 + 2 + 5 + 12 + 17)), total.unwrap());
+    assert_eq!(Some(Value::Int(24)), scaled.unwrap());
+    assert_eq!(Some(Value::Int(-300 + 3)), hidden.unwrap());
+}
+
+#[test]
+fn should_create_and_access_arrays() {
+    // Given
+    let (mut interpreter, class) = shapes();
+    let mut call = |name, descriptor, args: &[Value]| {
+        interpreter.invoke_static(&class, name, descriptor, args).unwrap()
+    };
+
+    // When
+    let sum = call("sumOfSquares", "(I)J", &[Value::Int(10)]);
+    let grid = call("grid", "(II)I", &[Value::Int(3), Value::Int(4)]);
+    let bytes = call("bytes", "(I)I", &[Value::Int(4)]);
+
+    // Then
+    assert_eq!(Some(Value::Long(285)), sum);
+    assert_eq!(Some(Value::Int(18 + 3000 + 400)), grid);
+    assert_eq!(Some(Value::Int((0 + 100 - 56 + 44) + (0 + 65535 + 65534 + 65533) + 2)),
+               bytes);
+}
+
+#[test]
+fn should_check_types_and_bounds() {
+    // Given
+    let (mut interpreter, class) = shapes();
+    let two = [Value::Int(2), Value::Int(2)];
+    let two_by_three = [Value::Int(2), Value::Int(3)];
+
+    // When
+    let square = interpreter.invoke_static(&class, "isSquare", "(II)Z", &two);
+    let rect = interpreter.invoke_static(&class, "isSquare", "(II)Z", &two_by_three);
+    let cast = interpreter.invoke_static(&class, "cast", "(II)I", &two_by_three);
+    let store = interpreter.invoke_static(&class, "store", "(I)I", &[Value::Int(0)]);
+    let element = interpreter.invoke_static(&class, "element", "(I)I", &[Value::Int(2)]);
+    let length = interpreter.invoke_static(&class, "length", "(I)I", &[Value::Int(-1)]);
+    let width = interpreter.invoke_static(&class,
+                                          "width",
+                                          "(Lio/hcf/frappe/Shapes$Rect;)I",
+                                          &[Value::Null]);
+
+    // Then
+    assert_eq!(Some(Value::Int(1)), square.unwrap());
+    assert_eq!(Some(Value::Int(0)), rect.unwrap());
+    assert_thrown(cast, "java/lang/ClassCastException");
+    assert_thrown(store, "java/lang/ArrayStoreException");
+    assert_thrown(element, "java/lang/ArrayIndexOutOfBoundsException");
+    assert_thrown(length, "java/lang/NegativeArraySizeException");
+    assert_thrown(width, "java/lang/NullPointerException");
+}
+
+#[test]
+fn should_keep_objects_reachable_from_static_fields() {
+    // Given
+    let (mut interpreter, class) = shapes();
+    let first = interpreter.invoke_static(&class, "cached", "(I)I", &[Value::Int(0)]);
+    interpreter.invoke_static(&class, "hiddenField", "(I)I", &[Value::Int(3)]).unwrap();
+
+    // When
+    let freed = interpreter.collect_garbage();
+    let second = interpreter.invoke_static(&class, "cached", "(I)I", &[Value::Int(0)]);
+
+    // Then
+    assert_eq!(Some(Value::Int(2)), first.unwrap());
+    assert_eq!(1, freed);
+    assert_eq!(2, interpreter.heap().len());
+    assert_eq!(Some(Value::Int(2)), second.unwrap());
+}
+
+#[test]
+fn should_collect_garbage_to_stay_within_the_heap_limit() {
+    // Given
+    let (mut interpreter, class) = shapes();
+    interpreter.heap_mut().set_limit(64 * 1024);
+    let args = [Value::Int(1000), Value::Int(1000)];
+
+    // When
+    let garbage = interpreter.invoke_static(&class, "garbage", "(II)I", &args);
+    let retained = interpreter.invoke_static(&class, "retain", "(II)I", &args);
+
+    // Then
+    assert_eq!(Some(Value::Int(999 * 1000 / 2)), garbage.unwrap());
+    assert_thrown(retained, "java/lang/OutOfMemoryError");
+    assert!(interpreter.heap().used() <= 64 * 1024);
+    interpreter.collect_garbage();
+    assert_eq!(1, interpreter.heap().len());
+}
@@ -0,0 +1,221 @@
+extern crate classfile;
+extern crate classpath;
+extern crate interpreter;
+extern crate jasm;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use classfile::ClassFile;
+use classfile::reader::ClassReader;
+use classpath::{ClassPath, LoaderId};
+use interpreter::Error;
+use interpreter::interpreter::Interpreter;
+use interpreter::runtime;
+use interpreter::value::Value;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+/// A stream that keeps what is written to it, shared with the test.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Output {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Boots the runtime with the test classes `classes` defined by the
+/// application class loader, capturing what they write to `System.out` and
+/// `System.err`.
+fn boot(classes: &[&str]) -> (Interpreter, LoaderId, Output, Output) {
+    let (mut interpreter, app) = runtime::boot(ClassPath::new()).unwrap();
+    for name in classes {
+        interpreter.loaders_mut().define_class(app, read_class(name)).unwrap();
+    }
+    let (stdout, stderr) = (Output::default(), Output::default());
+    interpreter.set_stdout(stdout.clone());
+    interpreter.set_stderr(stderr.clone());
+    (interpreter, app, stdout, stderr)
+}
+
+#[test]
+fn should_run_hello_world() {
+    // Given
+    let (mut interpreter, app, stdout, _) = boot(&["HelloWorld"]);
+
+    // When
+    runtime::run_main(&mut interpreter, app, "io/hcf/frappe/HelloWorld", &[]).unwrap();
+
+    // Then
+    assert_eq!("Hello World!\n", stdout.text());
+}
+
+#[test]
+fn should_run_programs_using_the_runtime() {
+    // Given
+    let (mut interpreter, app, stdout, stderr) = boot(&["Programs", "Programs$Pair", "Point"]);
+    let args = vec!["a".to_string(), "b".to_string()];
+
+    // When
+    runtime::run_main(&mut interpreter, app, "io/hcf/frappe/Programs", &args).unwrap();
+
+    // Then
+    assert_eq!("point 3,4\n\
+                distance 5.0\n\
+                98765432109876543210\n\
+                42\n\
+                1099511627776\n\
+                (41, x)\n\
+                13 f 7\n\
+                FRAPPEHello!\n\
+                true\n\
+                9 3.141592653589793 1.0E10 0.5\n\
+                a b 2\n",
+               stdout.text());
+    assert_eq!("done\n", stderr.text());
+}
+
+#[test]
+fn should_load_string_constants() {
+    // Given
+    let (mut interpreter, app, _, _) = boot(&["ControlFlow"]);
+    let class = interpreter.loaders_mut().load(app, "io/hcf/frappe/ControlFlow").unwrap();
+
+    // When
+    let descriptor = "(I)Ljava/lang/String;";
+    let args = [Value::Int(1)];
+    let one = interpreter.invoke_static(&class, "describe", descriptor, &args).unwrap();
+    let again = interpreter.invoke_static(&class, "describe", descriptor, &args).unwrap();
+
+    // Then
+    match one {
+        Some(Value::Reference(string)) => {
+            assert_eq!("one", interpreter.string_value(string).unwrap());
+            assert_eq!(Some(Value::Reference(string)), again);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_collect_garbage_while_building_strings() {
+    // Given
+    let (mut interpreter, app, _, _) = boot(&["Programs", "Programs$Pair", "Point"]);
+    let class = interpreter.loaders_mut().load(app, "io/hcf/frappe/Programs").unwrap();
+    interpreter.heap_mut().set_limit(64 << 10);
+
+    // When
+    let args = [Value::Int(300)];
+    let joined = interpreter.invoke_static(&class, "join", "(I)Ljava/lang/String;", &args);
+
+    // Then
+    let expected: String = (0..300).map(|i| i.to_string()).collect();
+    match joined.unwrap() {
+        Some(Value::Reference(string)) => {
+            assert_eq!(expected, interpreter.string_value(string).unwrap())
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_throw_exceptions_from_native_methods() {
+    // Given
+    let (mut interpreter, app, _, _) = boot(&["Programs", "Programs$Pair", "Point"]);
+    let class = interpreter.loaders_mut().load(app, "io/hcf/frappe/Programs").unwrap();
+    let valid = interpreter.new_string(app, "-12").unwrap();
+    let invalid = interpreter.new_string(app, "12a").unwrap();
+
+    // When
+    let parsed = interpreter.invoke_static(&class,
+                                           "parse",
+                                           "(Ljava/lang/String;)I",
+                                           &[Value::Reference(valid)]);
+    let result = interpreter.invoke_static(&class,
+                                           "parse",
+                                           "(Ljava/lang/String;)I",
+                                           &[Value::Reference(invalid)]);
+
+    // Then
+    assert_eq!(Some(Value::Int(-12)), parsed.unwrap());
+    match result {
+        Err(Error::Exception { ref class, message: Some(ref message) }) => {
+            assert_eq!("java/lang/NumberFormatException", class);
+            assert_eq!("For input string: \"12a\"", message);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn should_register_every_native_method_of_the_runtime() {
+    // Given
+    let (interpreter, _, _, _) = boot(&[]);
+
+    // Then
+    for class in runtime::classes() {
+        let cp = &class.constants;
+        for method in class.methods.iter().filter(|method| method.access_flags.is_native()) {
+            let name = cp[method.name_index].as_utf8();
+            let descriptor = cp[method.descriptor_index].as_utf8();
+            assert!(interpreter.natives().get(class.this_class_name(), name, descriptor).is_some(),
+                    "{}.{}{} is not registered",
+                    class.this_class_name(),
+                    name,
+                    descriptor);
+        }
+    }
+}
+
+#[test]
+fn should_run_registered_native_methods() {
+    // Given
+    let (mut interpreter, app, _, _) = boot(&[]);
+    let counter = jasm::assemble(r#"
+.class Counter
+.method static native next ()I
+.method static twice ()I {
+    .code stack 2 locals 0 {
+        invokestatic Method Counter next ()I
+        invokestatic Method Counter next ()I
+        iadd
+        ireturn
+    }
+}
+"#)
+        .unwrap();
+    let class = interpreter.loaders_mut().define_class(app, counter).unwrap();
+    let unlinked = interpreter.invoke_static(&class, "twice", "()I", &[]);
+    interpreter.natives_mut().register("Counter", "next", "()I", |_, _, _| {
+        Ok(Some(Value::Int(21)))
+    });
+
+    // When
+    let result = interpreter.invoke_static(&class, "twice", "()I", &[]);
+
+    // Then
+    match unlinked {
+        Err(Error::Exception { ref class, .. }) => {
+            assert_eq!("java/lang/UnsatisfiedLinkError", class)
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(Some(Value::Int(42)), result.unwrap());
+}
@@ -0,0 +1,1057 @@
+use std::collections::{BTreeMap, HashMap};
+
+use classfile::*;
+
+use opcodes::{self, Operands};
+use pool::PoolBuilder;
+use syntax;
+use tokenizer::{self, Spanned, Token};
+use super::{Error, Result};
+
+/// Assembles the textual form of a class.
+pub fn assemble(source: &str) -> Result<ClassFile> {
+    let tokens = try!(tokenizer::tokenize(source));
+    let constants = try!(explicit_constants(&tokens));
+    let mut parser = Parser {
+        tokens: tokens,
+        pos: 0,
+        pool: PoolBuilder::new(constants),
+        labels: HashMap::new(),
+        resolving: true,
+    };
+    parser.class()
+}
+
+/// Collects the `.const` declarations in `tokens` into a list of constants.
+///
+/// Declarations may appear anywhere in the source, so they are gathered before
+/// anything else is parsed and symbolic references can be resolved against
+/// them. The declared indices must be contiguous from `#1`.
+fn explicit_constants(tokens: &[Spanned]) -> Result<Vec<Constant>> {
+    let mut parser = Parser {
+        tokens: tokens.to_vec(),
+        pos: 0,
+        pool: PoolBuilder::new(vec![]),
+        labels: HashMap::new(),
+        resolving: true,
+    };
+    let mut entries: BTreeMap<u16, (Constant, usize)> = BTreeMap::new();
+    while parser.pos < parser.tokens.len() {
+        if parser.tokens[parser.pos].token != Token::Word(".const".to_string()) {
+            parser.pos += 1;
+            continue;
+        }
+        parser.pos += 1;
+        let (index, constant) = try!(parser.constant_declaration());
+        if entries.contains_key(&index) {
+            return Err(parser.error(&format!("constant #{} is declared twice", index)));
+        }
+        entries.insert(index, (constant, parser.line()));
+    }
+    let mut constants = vec![];
+    for (index, (constant, line)) in entries.into_iter() {
+        let expected = constants.len() + 1;
+        if index as usize != expected {
+            return Err(Error::new(line, &format!("expected constant #{}", expected)));
+        }
+        let wide = match constant {
+            Constant::Long(_) | Constant::Double(_) => true,
+            _ => false,
+        };
+        constants.push(constant);
+        if wide {
+            constants.push(Constant::Skip);
+        }
+    }
+    Ok(constants)
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    pool: PoolBuilder,
+    /// Label definitions in the current code block.
+    labels: HashMap<String, usize>,
+    /// `false` while a code block is being scanned for label definitions, in
+    /// which case label references resolve to zero and range checks that
+    /// depend on them are skipped.
+    resolving: bool,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        if self.tokens.is_empty() {
+            return 1;
+        }
+        let pos = if self.pos == 0 { 0 } else { self.pos - 1 };
+        self.tokens[pos.min(self.tokens.len() - 1)].line
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::new(self.line(), message)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        if self.at_end() {
+            return Err(self.error("unexpected end of input"));
+        }
+        self.pos += 1;
+        Ok(self.tokens[self.pos - 1].token.clone())
+    }
+
+    fn peek_word(&self) -> Option<&str> {
+        match self.tokens.get(self.pos) {
+            Some(&Spanned { token: Token::Word(ref word), .. }) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn peek_is(&self, word: &str) -> bool {
+        self.peek_word() == Some(word)
+    }
+
+    fn word(&mut self) -> Result<String> {
+        match try!(self.next()) {
+            Token::Word(word) => Ok(word),
+            Token::Str(_) => Err(self.error("expected a word, found a string")),
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        let word = try!(self.word());
+        if word != expected {
+            return Err(self.error(&format!("expected '{}', found '{}'", expected, word)));
+        }
+        Ok(())
+    }
+
+    /// Parses a word or quoted string.
+    fn string(&mut self) -> Result<String> {
+        match try!(self.next()) {
+            Token::Word(word) | Token::Str(word) => Ok(word),
+        }
+    }
+
+    fn integer(&mut self, min: i64, max: i64) -> Result<i64> {
+        let word = try!(self.word());
+        match syntax::integer(&word) {
+            Some(value) if value >= min && value <= max => Ok(value),
+            Some(_) => Err(self.error(&format!("{} is out of range", word))),
+            None => Err(self.error(&format!("expected an integer, found '{}'", word))),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.integer(0, 0xff).map(|v| v as u8)
+    }
+
+    fn i8(&mut self) -> Result<i8> {
+        self.integer(-0x80, 0x7f).map(|v| v as i8)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.integer(0, 0xffff).map(|v| v as u16)
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        self.integer(-0x8000, 0x7fff).map(|v| v as i16)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        self.integer(i32::min_value() as i64, i32::max_value() as i64).map(|v| v as i32)
+    }
+
+    fn float(&mut self) -> Result<f32> {
+        let word = try!(self.word());
+        if word.starts_with("0x") {
+            if let Ok(bits) = u32::from_str_radix(&word[2..], 16) {
+                return Ok(f32::from_bits(bits));
+            }
+        } else if let Ok(value) = word.parse::<f32>() {
+            return Ok(value);
+        }
+        Err(self.error(&format!("expected a float, found '{}'", word)))
+    }
+
+    fn double(&mut self) -> Result<f64> {
+        let word = try!(self.word());
+        if word.starts_with("0x") {
+            if let Ok(bits) = u64::from_str_radix(&word[2..], 16) {
+                return Ok(f64::from_bits(bits));
+            }
+        } else if let Ok(value) = word.parse::<f64>() {
+            return Ok(value);
+        }
+        Err(self.error(&format!("expected a double, found '{}'", word)))
+    }
+
+    fn intern(&mut self, constant: Constant) -> Result<u16> {
+        match self.pool.intern(constant) {
+            Some(index) => Ok(index),
+            None => Err(self.error("the constant pool is full")),
+        }
+    }
+
+    /// Parses an explicit constant pool index, `#n`.
+    fn index(&mut self) -> Result<u16> {
+        let word = try!(self.word());
+        if word.starts_with('#') {
+            if let Ok(index) = word[1..].parse::<u16>() {
+                return Ok(index);
+            }
+        }
+        Err(self.error(&format!("expected a constant pool index, found '{}'", word)))
+    }
+
+    fn peek_index(&self) -> bool {
+        self.peek_word().map_or(false, |word| word.starts_with('#'))
+    }
+
+    /// Parses a reference to a `Utf8` constant.
+    fn utf8(&mut self) -> Result<u16> {
+        if self.peek_index() {
+            return self.index();
+        }
+        let value = try!(self.string());
+        self.intern(Constant::Utf8(value))
+    }
+
+    /// Parses a reference to a `Class` constant.
+    fn class_ref(&mut self) -> Result<u16> {
+        if self.peek_index() {
+            return self.index();
+        }
+        let name = try!(self.utf8());
+        self.intern(Constant::Class(name))
+    }
+
+    fn optional_class_ref(&mut self) -> Result<u16> {
+        if self.peek_is("none") {
+            self.pos += 1;
+            return Ok(0);
+        }
+        self.class_ref()
+    }
+
+    fn optional_utf8(&mut self) -> Result<u16> {
+        if self.peek_is("none") {
+            self.pos += 1;
+            return Ok(0);
+        }
+        self.utf8()
+    }
+
+    fn name_and_type(&mut self) -> Result<u16> {
+        let name = try!(self.utf8());
+        let descriptor = try!(self.utf8());
+        self.intern(Constant::NameAndType(NameAndTypeConstant {
+            name_index: name,
+            descriptor_index: descriptor,
+        }))
+    }
+
+    fn reference_kind(&mut self) -> Result<u8> {
+        let word = try!(self.word());
+        if let Some(i) = syntax::REFERENCE_KINDS.iter().position(|kind| *kind == word) {
+            return Ok(i as u8 + 1);
+        }
+        match syntax::integer(&word) {
+            Some(kind) if kind >= 0 && kind <= 0xff => Ok(kind as u8),
+            _ => Err(self.error(&format!("unknown reference kind '{}'", word))),
+        }
+    }
+
+    /// Parses a reference to a constant of any kind, either as an explicit
+    /// index or symbolically.
+    fn any_ref(&mut self) -> Result<u16> {
+        if self.peek_index() {
+            return self.index();
+        }
+        let tag = try!(self.word());
+        let constant = match tag.as_ref() {
+            "Utf8" => Constant::Utf8(try!(self.string())),
+            "Integer" => Constant::Integer(try!(self.i32())),
+            "Long" => {
+                Constant::Long(try!(self.integer(i64::min_value(), i64::max_value())))
+            }
+            "Float" => Constant::Float(try!(self.float())),
+            "Double" => Constant::Double(try!(self.double())),
+            "Class" => Constant::Class(try!(self.utf8())),
+            "String" => Constant::String(try!(self.utf8())),
+            "MethodType" => Constant::MethodType { descriptor_index: try!(self.utf8()) },
+            "NameAndType" => return self.name_and_type(),
+            "Field" | "Method" | "InterfaceMethod" => {
+                let entity = TypedEntityConstant {
+                    class_index: try!(self.class_ref()),
+                    name_and_type_index: try!(self.name_and_type()),
+                };
+                match tag.as_ref() {
+                    "Field" => Constant::Fieldref(entity),
+                    "Method" => Constant::Methodref(entity),
+                    _ => Constant::InterfaceMethodref(entity),
+                }
+            }
+            "CallSite" => {
+                Constant::InvokeDynamic {
+                    bootstrap_method_attr_index: try!(self.u16()),
+                    name_and_type_index: try!(self.name_and_type()),
+                }
+            }
+            "MethodHandle" => {
+                Constant::MethodHandle {
+                    reference_kind: try!(self.reference_kind()),
+                    reference_index: try!(self.any_ref()),
+                }
+            }
+            _ => return Err(self.error(&format!("unknown constant kind '{}'", tag))),
+        };
+        self.intern(constant)
+    }
+
+    /// Parses the remainder of a `.const #n = ...` declaration.
+    fn constant_declaration(&mut self) -> Result<(u16, Constant)> {
+        let index = try!(self.index());
+        if index == 0 {
+            return Err(self.error("constant #0 cannot be declared"));
+        }
+        try!(self.expect("="));
+        let tag = try!(self.word());
+        let constant = match tag.as_ref() {
+            "Utf8" => Constant::Utf8(try!(self.string())),
+            "Integer" => Constant::Integer(try!(self.i32())),
+            "Long" => {
+                Constant::Long(try!(self.integer(i64::min_value(), i64::max_value())))
+            }
+            "Float" => Constant::Float(try!(self.float())),
+            "Double" => Constant::Double(try!(self.double())),
+            "Class" => Constant::Class(try!(self.index())),
+            "String" => Constant::String(try!(self.index())),
+            "MethodType" => Constant::MethodType { descriptor_index: try!(self.index()) },
+            "NameAndType" => {
+                Constant::NameAndType(NameAndTypeConstant {
+                    name_index: try!(self.index()),
+                    descriptor_index: try!(self.index()),
+                })
+            }
+            "Fieldref" | "Methodref" | "InterfaceMethodref" => {
+                let entity = TypedEntityConstant {
+                    class_index: try!(self.index()),
+                    name_and_type_index: try!(self.index()),
+                };
+                match tag.as_ref() {
+                    "Fieldref" => Constant::Fieldref(entity),
+                    "Methodref" => Constant::Methodref(entity),
+                    _ => Constant::InterfaceMethodref(entity),
+                }
+            }
+            "InvokeDynamic" => {
+                Constant::InvokeDynamic {
+                    bootstrap_method_attr_index: try!(self.u16()),
+                    name_and_type_index: try!(self.index()),
+                }
+            }
+            "MethodHandle" => {
+                Constant::MethodHandle {
+                    reference_kind: try!(self.reference_kind()),
+                    reference_index: try!(self.index()),
+                }
+            }
+            _ => return Err(self.error(&format!("unknown constant kind '{}'", tag))),
+        };
+        Ok((index, constant))
+    }
+
+    fn flags(&mut self, table: &[(&str, u16)]) -> u16 {
+        let mut bits = 0;
+        while let Some(flag) = self.peek_word()
+            .and_then(|word| table.iter().find(|&&(name, _)| name == word)) {
+            bits |= flag.1;
+            self.pos += 1;
+        }
+        bits
+    }
+
+    fn class(&mut self) -> Result<ClassFile> {
+        let mut version = (52, 0);
+        let mut this_class = None;
+        let mut super_class = None;
+        let mut interfaces = vec![];
+        let mut fields = vec![];
+        let mut methods = vec![];
+        let mut attrs = vec![];
+        while !self.at_end() {
+            let directive = try!(self.word());
+            match directive.as_ref() {
+                ".version" => version = (try!(self.u16()), try!(self.u16())),
+                ".const" => {
+                    // Already collected by `explicit_constants`.
+                    try!(self.constant_declaration());
+                }
+                ".class" => {
+                    let flags = self.flags(syntax::CLASS_FLAGS);
+                    this_class = Some((flags, try!(self.class_ref())));
+                }
+                ".super" => super_class = Some(try!(self.optional_class_ref())),
+                ".implements" => interfaces.push(try!(self.class_ref())),
+                ".field" => {
+                    let flags = self.flags(syntax::FIELD_FLAGS);
+                    let name_index = try!(self.utf8());
+                    let descriptor_index = try!(self.utf8());
+                    fields.push(FieldInfo {
+                        access_flags: FieldAccessFlags::from_bits_truncate(flags),
+                        name_index: name_index,
+                        descriptor_index: descriptor_index,
+                        attrs: try!(self.member_attributes(AttributeLocation::FieldInfo)),
+                    });
+                }
+                ".method" => {
+                    let flags = self.flags(syntax::METHOD_FLAGS);
+                    let name_index = try!(self.utf8());
+                    let descriptor_index = try!(self.utf8());
+                    methods.push(MethodInfo {
+                        access_flags: MethodAccessFlags::from_bits_truncate(flags),
+                        name_index: name_index,
+                        descriptor_index: descriptor_index,
+                        attrs: try!(self.member_attributes(AttributeLocation::MethodInfo)),
+                    });
+                }
+                _ => attrs.push(try!(self.attribute(&directive))),
+            }
+        }
+        let (flags, this_class) = match this_class {
+            Some(this_class) => this_class,
+            None => return Err(self.error("missing .class declaration")),
+        };
+        let super_class = match super_class {
+            Some(super_class) => super_class,
+            None => try!(self.pool.class("java/lang/Object").ok_or(self.error("the constant pool is full"))),
+        };
+        let attrs = Attributes::new(AttributeLocation::ClassFile, attrs);
+        try!(self.intern_attribute_names(&attrs));
+        for field in fields.iter() {
+            try!(self.intern_attribute_names(&field.attrs));
+        }
+        for method in methods.iter() {
+            try!(self.intern_attribute_names(&method.attrs));
+        }
+        let pool = ::std::mem::replace(&mut self.pool, PoolBuilder::new(vec![]));
+        Ok(ClassFile {
+            magic: 0xcafebabe,
+            minor_version: version.1,
+            major_version: version.0,
+            constants: pool.into_pool(),
+            access_flags: ClassAccessFlags::from_bits_truncate(flags),
+            this_class: this_class,
+            super_class: super_class,
+            interfaces: interfaces,
+            fields: fields,
+            methods: methods,
+            attrs: attrs,
+        })
+    }
+
+    /// Makes sure the constant pool holds the name of every attribute, which
+    /// the class writer requires.
+    fn intern_attribute_names(&mut self, attrs: &Attributes) -> Result<()> {
+        for attr in attrs.iter() {
+            match *attr {
+                AttributeInfo::Raw(_) => continue,
+                AttributeInfo::Code(ref code) => try!(self.intern_attribute_names(&code.attrs)),
+                _ => {}
+            }
+            if self.pool.utf8(attr.name()).is_none() {
+                return Err(self.error("the constant pool is full"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the optional `{ ... }` block of attributes following a field or
+    /// method declaration.
+    fn member_attributes(&mut self, location: AttributeLocation) -> Result<Attributes> {
+        let mut attrs = vec![];
+        if self.peek_is("{") {
+            self.pos += 1;
+            while !self.peek_is("}") {
+                let directive = try!(self.word());
+                attrs.push(try!(self.attribute(&directive)));
+            }
+            self.pos += 1;
+        }
+        Ok(Attributes::new(location, attrs))
+    }
+
+    /// Parses `{ item item ... }`, calling `item` for each item.
+    fn block<T, F>(&mut self, mut item: F) -> Result<Vec<T>>
+        where F: FnMut(&mut Parser) -> Result<T>
+    {
+        try!(self.expect("{"));
+        let mut items = vec![];
+        while !self.peek_is("}") {
+            if self.at_end() {
+                return Err(self.error("expected '}'"));
+            }
+            items.push(try!(item(self)));
+        }
+        self.pos += 1;
+        Ok(items)
+    }
+
+    fn attribute(&mut self, directive: &str) -> Result<AttributeInfo> {
+        let attr = match directive {
+            ".sourcefile" => AttributeInfo::SourceFile(try!(self.utf8())),
+            ".signature" => AttributeInfo::Signature(try!(self.utf8())),
+            ".constantvalue" => AttributeInfo::ConstantValue(try!(self.any_ref())),
+            ".synthetic" => AttributeInfo::Synthetic,
+            ".deprecated" => AttributeInfo::Deprecated,
+            ".sourcedebugextension" => AttributeInfo::SourceDebugExtension(try!(self.hex())),
+            ".exceptions" => AttributeInfo::Exceptions(try!(self.block(|p| p.class_ref()))),
+            ".innerclasses" => {
+                AttributeInfo::InnerClasses(try!(self.block(|p| {
+                    let flags = p.flags(syntax::INNER_CLASS_FLAGS);
+                    Ok(InnerClassInfo {
+                        inner_class_info_index: try!(p.class_ref()),
+                        outer_class_info_index: try!(p.optional_class_ref()),
+                        inner_name_index: try!(p.optional_utf8()),
+                        inner_class_access_flags: InnerClassAccessFlags::from_bits_truncate(flags),
+                    })
+                })))
+            }
+            ".enclosingmethod" => {
+                let class_index = try!(self.class_ref());
+                let method_index = if self.peek_is("none") {
+                    self.pos += 1;
+                    0
+                } else {
+                    try!(self.any_ref())
+                };
+                AttributeInfo::EnclosingMethod(EnclosingMethodAttribute {
+                    class_index: class_index,
+                    method_index: method_index,
+                })
+            }
+            ".bootstrapmethods" => {
+                AttributeInfo::BootstrapMethods(try!(self.block(|p| {
+                    Ok(BootstrapMethodInfo {
+                        bootstrap_method_ref: try!(p.any_ref()),
+                        bootstrap_arguments: try!(p.block(|p| p.any_ref())),
+                    })
+                })))
+            }
+            ".methodparameters" => {
+                AttributeInfo::MethodParameters(try!(self.block(|p| {
+                    let flags = p.flags(syntax::PARAMETER_FLAGS);
+                    Ok(MethodParameterInfo {
+                        name_index: try!(p.optional_utf8()),
+                        access_flags: MethodParameterAccessFlags::from_bits_truncate(flags),
+                    })
+                })))
+            }
+            ".annotationdefault" => AttributeInfo::AnnotationDefault(try!(self.element_value())),
+            ".runtimevisibleannotations" => {
+                AttributeInfo::RuntimeVisibleAnnotations(try!(self.block(|p| p.annotation())))
+            }
+            ".runtimeinvisibleannotations" => {
+                AttributeInfo::RuntimeInvisibleAnnotations(try!(self.block(|p| p.annotation())))
+            }
+            ".runtimevisibleparameterannotations" => {
+                AttributeInfo::RuntimeVisibleParameterAnnotations(try!(self.block(|p| {
+                    p.block(|p| p.annotation())
+                })))
+            }
+            ".runtimeinvisibleparameterannotations" => {
+                AttributeInfo::RuntimeInvisibleParameterAnnotations(try!(self.block(|p| {
+                    p.block(|p| p.annotation())
+                })))
+            }
+            ".runtimevisibletypeannotations" => {
+                AttributeInfo::RuntimeVisibleTypeAnnotations(try!(self.block(|p| {
+                    p.type_annotation()
+                })))
+            }
+            ".runtimeinvisibletypeannotations" => {
+                AttributeInfo::RuntimeInvisibleTypeAnnotations(try!(self.block(|p| {
+                    p.type_annotation()
+                })))
+            }
+            ".code" => AttributeInfo::Code(Box::new(try!(self.code()))),
+            ".linenumbertable" => {
+                AttributeInfo::LineNumberTable(try!(self.block(|p| {
+                    Ok(LineNumberTableEntry {
+                        start_pc: try!(p.pc()),
+                        line_number: try!(p.u16()),
+                    })
+                })))
+            }
+            ".localvariabletable" => {
+                AttributeInfo::LocalVariableTable(try!(self.block(|p| {
+                    let (start_pc, length) = try!(p.range());
+                    Ok(LocalVariableTableEntry {
+                        start_pc: start_pc,
+                        length: length,
+                        index: try!(p.u16()),
+                        name_index: try!(p.utf8()),
+                        descriptor_index: try!(p.utf8()),
+                    })
+                })))
+            }
+            ".localvariabletypetable" => {
+                AttributeInfo::LocalVariableTypeTable(try!(self.block(|p| {
+                    let (start_pc, length) = try!(p.range());
+                    Ok(LocalVariableTypeTableEntry {
+                        start_pc: start_pc,
+                        length: length,
+                        index: try!(p.u16()),
+                        name_index: try!(p.utf8()),
+                        signature_index: try!(p.utf8()),
+                    })
+                })))
+            }
+            ".stackmaptable" => {
+                let mut previous = -1;
+                AttributeInfo::StackMapTable(try!(self.block(|p| p.frame(&mut previous))))
+            }
+            ".attribute" => {
+                AttributeInfo::Raw(RawAttribute {
+                    name_index: try!(self.utf8()),
+                    info: try!(self.hex()),
+                })
+            }
+            _ => return Err(self.error(&format!("unknown directive '{}'", directive))),
+        };
+        Ok(attr)
+    }
+
+    fn hex(&mut self) -> Result<Vec<u8>> {
+        let word = try!(self.word());
+        match syntax::bytes(&word) {
+            Some(bytes) => Ok(bytes),
+            None => Err(self.error(&format!("expected hexadecimal bytes, found '{}'", word))),
+        }
+    }
+
+    fn element_value(&mut self) -> Result<ElementValue> {
+        let tag = try!(self.word());
+        let value = match tag.as_ref() {
+            "enum" => {
+                ElementValue::EnumConstValue(EnumConstValue {
+                    type_name_index: try!(self.utf8()),
+                    const_name_index: try!(self.utf8()),
+                })
+            }
+            "class" => ElementValue::ClassInfo(try!(self.utf8())),
+            "annotation" => ElementValue::AnnotationValue(try!(self.annotation())),
+            "array" => {
+                ElementValue::ArrayValue(ArrayValue {
+                    values: try!(self.block(|p| p.element_value())),
+                })
+            }
+            "B" | "C" | "D" | "F" | "I" | "J" | "S" | "Z" | "s" => {
+                ElementValue::ConstantValue(ConstantValue {
+                    tag: tag.as_bytes()[0],
+                    const_value_index: try!(self.any_ref()),
+                })
+            }
+            _ => return Err(self.error(&format!("unknown element value kind '{}'", tag))),
+        };
+        Ok(value)
+    }
+
+    fn element_value_pairs(&mut self) -> Result<Vec<ElementValuePair>> {
+        self.block(|p| {
+            let name = try!(p.utf8());
+            try!(p.expect("="));
+            Ok(ElementValuePair {
+                element_name_index: name,
+                value: try!(p.element_value()),
+            })
+        })
+    }
+
+    fn annotation(&mut self) -> Result<Annotation> {
+        Ok(Annotation {
+            type_index: try!(self.utf8()),
+            element_value_pairs: try!(self.element_value_pairs()),
+        })
+    }
+
+    fn type_annotation(&mut self) -> Result<TypeAnnotation> {
+        let target_type = try!(self.u8());
+        let kind = try!(self.word());
+        let target_info = match kind.as_ref() {
+            "type_parameter" => TargetInfo::TypeParameter(try!(self.u8())),
+            "supertype" => TargetInfo::Supertype(try!(self.u16())),
+            "type_parameter_bound" => {
+                TargetInfo::TypeParameterBound {
+                    type_parameter_index: try!(self.u8()),
+                    bound_index: try!(self.u8()),
+                }
+            }
+            "empty" => TargetInfo::Empty,
+            "formal_parameter" => TargetInfo::MethodFormalParameter(try!(self.u8())),
+            "throws" => TargetInfo::Throws(try!(self.u16())),
+            "localvar" => {
+                TargetInfo::Localvar(try!(self.block(|p| {
+                    Ok(LocalvarInfo {
+                        start_pc: try!(p.u16()),
+                        length: try!(p.u16()),
+                        index: try!(p.u16()),
+                    })
+                })))
+            }
+            "catch" => TargetInfo::Catch(try!(self.u16())),
+            "offset" => TargetInfo::Offset(try!(self.u16())),
+            "type_argument" => {
+                TargetInfo::TypeArgument {
+                    offset: try!(self.u16()),
+                    type_argument_index: try!(self.u8()),
+                }
+            }
+            _ => return Err(self.error(&format!("unknown type annotation target '{}'", kind))),
+        };
+        try!(self.expect("path"));
+        let target_path = try!(self.block(|p| {
+            Ok(TypePathEntry {
+                type_path_kind: try!(p.u8()),
+                type_argument_index: try!(p.u8()),
+            })
+        }));
+        Ok(TypeAnnotation {
+            target_type: target_type,
+            target_info: target_info,
+            target_path: target_path,
+            type_index: try!(self.utf8()),
+            element_value_pairs: try!(self.element_value_pairs()),
+        })
+    }
+
+    fn verification_type(&mut self) -> Result<VerificationTypeInfo> {
+        let kind = try!(self.word());
+        let info = match kind.as_ref() {
+            "top" => VerificationTypeInfo::Top,
+            "int" => VerificationTypeInfo::Integer,
+            "float" => VerificationTypeInfo::Float,
+            "long" => VerificationTypeInfo::Long,
+            "double" => VerificationTypeInfo::Double,
+            "null" => VerificationTypeInfo::Null,
+            "uninitialized_this" => VerificationTypeInfo::UninitializedThis,
+            "object" => {
+                VerificationTypeInfo::Object(ObjectVariableInfo {
+                    cpool_index: try!(self.class_ref()),
+                })
+            }
+            "uninitialized" => {
+                VerificationTypeInfo::Uninitialized(UninitializedVariableInfo {
+                    offset: try!(self.pc()),
+                })
+            }
+            _ => return Err(self.error(&format!("unknown verification type '{}'", kind))),
+        };
+        Ok(info)
+    }
+
+    /// Parses a stack map frame. `previous` holds the pc of the previous frame,
+    /// or -1 for the first frame.
+    fn frame(&mut self, previous: &mut i64) -> Result<StackMapFrame> {
+        let kind = try!(self.word());
+        let pc = try!(self.label());
+        let delta = pc - *previous - 1;
+        *previous = pc;
+        if self.resolving && (delta < 0 || delta > 0xffff) {
+            return Err(self.error("stack map frames must be in increasing pc order"));
+        }
+        let offset_delta = delta as u16;
+        let short = |p: &Parser| if p.resolving && delta > 63 {
+            Err(p.error(&format!("the offset delta of a {} frame must be at most 63", kind)))
+        } else {
+            Ok(offset_delta as u8 & 63)
+        };
+        let frame = match kind.as_ref() {
+            "same" => StackMapFrame::SameFrame { frame_type: try!(short(self)) },
+            "same_extended" => StackMapFrame::SameFrameExtended { offset_delta: offset_delta },
+            "same_locals_1_stack_item" => {
+                StackMapFrame::SameLocals1StackItemFrame {
+                    frame_type: 64 + try!(short(self)),
+                    stack: [try!(self.verification_type())],
+                }
+            }
+            "same_locals_1_stack_item_extended" => {
+                StackMapFrame::SameLocals1StackItemFrameExtended {
+                    offset_delta: offset_delta,
+                    stack: [try!(self.verification_type())],
+                }
+            }
+            "chop" => {
+                StackMapFrame::ChopFrame {
+                    frame_type: 251 - try!(self.integer(1, 3)) as u8,
+                    offset_delta: offset_delta,
+                }
+            }
+            "append" => {
+                let locals = try!(self.block(|p| p.verification_type()));
+                if locals.is_empty() || locals.len() > 3 {
+                    return Err(self.error("an append frame must add between 1 and 3 locals"));
+                }
+                StackMapFrame::AppendFrame {
+                    frame_type: 251 + locals.len() as u8,
+                    offset_delta: offset_delta,
+                    locals: locals,
+                }
+            }
+            "full" => {
+                StackMapFrame::FullFrame {
+                    offset_delta: offset_delta,
+                    locals: try!(self.block(|p| p.verification_type())),
+                    stack: try!(self.block(|p| p.verification_type())),
+                }
+            }
+            _ => return Err(self.error(&format!("unknown stack map frame '{}'", kind))),
+        };
+        Ok(frame)
+    }
+
+    /// Parses a code offset, given either as a label or an absolute pc.
+    fn label(&mut self) -> Result<i64> {
+        let word = try!(self.word());
+        if let Some(pc) = syntax::integer(&word) {
+            return Ok(pc);
+        }
+        if !self.resolving {
+            return Ok(0);
+        }
+        match self.labels.get(&word) {
+            Some(&pc) => Ok(pc as i64),
+            None => Err(self.error(&format!("undefined label '{}'", word))),
+        }
+    }
+
+    /// Parses a code offset that must fit in an unsigned short.
+    fn pc(&mut self) -> Result<u16> {
+        let pc = try!(self.label());
+        if self.resolving && (pc < 0 || pc > 0xffff) {
+            return Err(self.error(&format!("pc {} is out of range", pc)));
+        }
+        Ok(pc as u16)
+    }
+
+    /// Parses a `start end` pair of code offsets into a start and length.
+    fn range(&mut self) -> Result<(u16, u16)> {
+        let start = try!(self.pc());
+        let end = try!(self.pc());
+        if self.resolving && end < start {
+            return Err(self.error("the end of a range precedes its start"));
+        }
+        Ok((start, end.wrapping_sub(start)))
+    }
+
+    /// Parses the body of a `.code` directive.
+    ///
+    /// The body is parsed twice: first to find the pc of each label, and then
+    /// again with the labels resolved.
+    fn code(&mut self) -> Result<CodeAttribute> {
+        try!(self.expect("stack"));
+        let max_stack = try!(self.u16());
+        try!(self.expect("locals"));
+        let max_locals = try!(self.u16());
+        let start = self.pos;
+        self.labels.clear();
+        self.resolving = false;
+        let scan = self.code_body(max_stack, max_locals);
+        self.resolving = true;
+        try!(scan);
+        self.pos = start;
+        let code = try!(self.code_body(max_stack, max_locals));
+        self.labels.clear();
+        Ok(code)
+    }
+
+    fn code_body(&mut self, max_stack: u16, max_locals: u16) -> Result<CodeAttribute> {
+        try!(self.expect("{"));
+        let mut code: Vec<u8> = vec![];
+        let mut exception_table = vec![];
+        let mut attrs = vec![];
+        loop {
+            let word = try!(self.word());
+            if word == "}" {
+                break;
+            } else if word.ends_with(':') {
+                let name = word[..word.len() - 1].to_string();
+                if !self.resolving && self.labels.insert(name, code.len()).is_some() {
+                    return Err(self.error(&format!("label '{}' is defined twice", word)));
+                }
+            } else if word == ".catch" {
+                let catch_type = if self.peek_is("any") {
+                    self.pos += 1;
+                    0
+                } else {
+                    try!(self.class_ref())
+                };
+                exception_table.push(ExceptionInfo {
+                    start_pc: try!(self.pc()),
+                    end_pc: try!(self.pc()),
+                    handler_pc: try!(self.pc()),
+                    catch_type: catch_type,
+                });
+            } else if word.starts_with('.') {
+                attrs.push(try!(self.attribute(&word)));
+            } else {
+                try!(self.instruction(&word, &mut code));
+            }
+        }
+        Ok(CodeAttribute {
+            max_stack: max_stack,
+            max_locals: max_locals,
+            code: code,
+            exception_table: exception_table,
+            attrs: Attributes::new(AttributeLocation::Code, attrs),
+        })
+    }
+
+    /// Parses a branch target, returning its offset from `pc`.
+    fn branch(&mut self, pc: usize, min: i64, max: i64) -> Result<i64> {
+        let offset = try!(self.label()) - pc as i64;
+        if self.resolving && (offset < min || offset > max) {
+            return Err(self.error("branch target is out of range"));
+        }
+        Ok(offset)
+    }
+
+    fn instruction(&mut self, mnemonic: &str, code: &mut Vec<u8>) -> Result<()> {
+        let pc = code.len();
+        if mnemonic == "invalid" {
+            code.push(try!(self.u8()));
+            return Ok(());
+        }
+        let opcode = match opcodes::lookup(mnemonic) {
+            Some(opcode) => opcode,
+            None => return Err(self.error(&format!("unknown instruction '{}'", mnemonic))),
+        };
+        code.push(opcode);
+        match opcodes::operands(opcode) {
+            Operands::None => {}
+            Operands::Byte => code.push(try!(self.i8()) as u8),
+            Operands::Short => push_u16(code, try!(self.i16()) as u16),
+            Operands::Local => code.push(try!(self.u8())),
+            Operands::Constant8 => {
+                let index = try!(self.any_ref());
+                if index > 0xff {
+                    return Err(self.error(&format!("constant #{} is out of range for {}",
+                                                   index,
+                                                   mnemonic)));
+                }
+                code.push(index as u8);
+            }
+            Operands::Constant16 => push_u16(code, try!(self.any_ref())),
+            Operands::Iinc => {
+                code.push(try!(self.u8()));
+                code.push(try!(self.i8()) as u8);
+            }
+            Operands::Branch16 => {
+                let offset = try!(self.branch(pc, -0x8000, 0x7fff));
+                push_u16(code, offset as u16);
+            }
+            Operands::Branch32 => {
+                let offset = try!(self.branch(pc, i32::min_value() as i64, i32::max_value() as i64));
+                push_u32(code, offset as u32);
+            }
+            Operands::TableSwitch => {
+                let low = try!(self.i32());
+                let offsets = try!(self.block(|p| {
+                    p.branch(pc, i32::min_value() as i64, i32::max_value() as i64)
+                }));
+                try!(self.expect("default"));
+                let default = try!(self.branch(pc, i32::min_value() as i64, i32::max_value() as i64));
+                let high = low as i64 + offsets.len() as i64 - 1;
+                if offsets.is_empty() || high > i32::max_value() as i64 {
+                    return Err(self.error("invalid tableswitch range"));
+                }
+                pad(code);
+                push_u32(code, default as u32);
+                push_u32(code, low as u32);
+                push_u32(code, high as u32);
+                for offset in offsets {
+                    push_u32(code, offset as u32);
+                }
+            }
+            Operands::LookupSwitch => {
+                let pairs = try!(self.block(|p| {
+                    let key = try!(p.i32());
+                    let offset = try!(p.branch(pc, i32::min_value() as i64, i32::max_value() as i64));
+                    Ok((key, offset))
+                }));
+                try!(self.expect("default"));
+                let default = try!(self.branch(pc, i32::min_value() as i64, i32::max_value() as i64));
+                pad(code);
+                push_u32(code, default as u32);
+                push_u32(code, pairs.len() as u32);
+                for (key, offset) in pairs {
+                    push_u32(code, key as u32);
+                    push_u32(code, offset as u32);
+                }
+            }
+            Operands::InvokeInterface => {
+                push_u16(code, try!(self.any_ref()));
+                code.push(try!(self.u8()));
+                code.push(0);
+            }
+            Operands::InvokeDynamic => {
+                push_u16(code, try!(self.any_ref()));
+                code.push(0);
+                code.push(0);
+            }
+            Operands::MultiANewArray => {
+                push_u16(code, try!(self.any_ref()));
+                code.push(try!(self.u8()));
+            }
+            Operands::NewArray => {
+                let atype = match self.peek_word()
+                    .and_then(|word| opcodes::ARRAY_TYPES.iter().position(|t| *t == word)) {
+                    Some(i) => {
+                        self.pos += 1;
+                        i as u8 + 4
+                    }
+                    None => try!(self.u8()),
+                };
+                code.push(atype);
+            }
+            Operands::Wide => {
+                let modified = try!(self.word());
+                let opcode = opcodes::lookup(&modified);
+                match opcode.map(opcodes::operands) {
+                    Some(Operands::Local) => {
+                        code.push(opcode.unwrap());
+                        push_u16(code, try!(self.u16()));
+                    }
+                    Some(Operands::Iinc) => {
+                        code.push(opcode.unwrap());
+                        push_u16(code, try!(self.u16()));
+                        push_u16(code, try!(self.i16()) as u16);
+                    }
+                    _ => return Err(self.error(&format!("'{}' cannot be widened", modified))),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn push_u16(code: &mut Vec<u8>, value: u16) {
+    code.push((value >> 8) as u8);
+    code.push(value as u8);
+}
+
+fn push_u32(code: &mut Vec<u8>, value: u32) {
+    push_u16(code, (value >> 16) as u16);
+    push_u16(code, value as u16);
+}
+
+/// Pads `code` to a four byte boundary, as required after a switch opcode.
+fn pad(code: &mut Vec<u8>) {
+    while code.len() % 4 != 0 {
+        code.push(0);
+    }
+}
@@ -0,0 +1,838 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use classfile::*;
+
+use opcodes::{self, Operands};
+use pool;
+use syntax;
+
+/// Writes the textual form of `class` to `out`.
+///
+/// Assembling the output with `assemble` produces a class that serializes to
+/// the same bytes as `class`.
+pub fn disassemble<W: Write>(class: &ClassFile, out: &mut W) -> io::Result<()> {
+    let mut disassembler = Disassembler {
+        class: class,
+        canonical: pool::canonical(&class.constants),
+        out: out,
+        indent: 0,
+        labels: BTreeSet::new(),
+    };
+    disassembler.class()
+}
+
+struct Disassembler<'a, W: Write + 'a> {
+    class: &'a ClassFile,
+    canonical: Vec<bool>,
+    out: &'a mut W,
+    indent: usize,
+    /// The pcs in the current method that are written as labels.
+    labels: BTreeSet<usize>,
+}
+
+impl<'a, W: Write> Disassembler<'a, W> {
+    fn line(&mut self, text: &str) -> io::Result<()> {
+        for _ in 0..self.indent {
+            try!(write!(self.out, "    "));
+        }
+        writeln!(self.out, "{}", text)
+    }
+
+    fn open(&mut self, text: &str) -> io::Result<()> {
+        if text.is_empty() {
+            try!(self.line("{"));
+        } else {
+            try!(self.line(&format!("{} {{", text)));
+        }
+        self.indent += 1;
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.indent -= 1;
+        self.line("}")
+    }
+
+    fn constant(&self, index: u16) -> Option<&'a Constant> {
+        let constants = &self.class.constants;
+        if index == 0 || index >= constants.len() {
+            return None;
+        }
+        Some(&constants[index])
+    }
+
+    fn is_canonical(&self, index: u16) -> bool {
+        self.constant(index).is_some() && self.canonical[index as usize]
+    }
+
+    /// Formats a reference to a `Utf8` constant.
+    fn utf8(&self, index: u16) -> String {
+        match self.constant(index) {
+            Some(&Constant::Utf8(ref value)) if self.is_canonical(index) => syntax::string(value),
+            _ => format!("#{}", index),
+        }
+    }
+
+    /// Formats a reference to a `Class` constant.
+    fn class_ref(&self, index: u16) -> String {
+        match self.constant(index) {
+            Some(&Constant::Class(name)) if self.is_canonical(index) => self.utf8(name),
+            _ => format!("#{}", index),
+        }
+    }
+
+    /// Formats a reference to a `Class` constant that may be absent.
+    fn optional_class_ref(&self, index: u16) -> String {
+        if index == 0 {
+            "none".to_string()
+        } else {
+            self.class_ref(index)
+        }
+    }
+
+    fn optional_utf8(&self, index: u16) -> String {
+        if index == 0 {
+            "none".to_string()
+        } else {
+            self.utf8(index)
+        }
+    }
+
+    fn name_and_type(&self, index: u16) -> String {
+        match self.constant(index) {
+            Some(&Constant::NameAndType(ref nat)) => {
+                format!("{} {}", self.utf8(nat.name_index), self.utf8(nat.descriptor_index))
+            }
+            _ => format!("#{}", index),
+        }
+    }
+
+    /// Formats a reference to a constant of any kind, symbolically where the
+    /// assembler would resolve the symbolic form back to `index`.
+    fn any_ref(&self, index: u16) -> String {
+        if !self.is_canonical(index) {
+            return format!("#{}", index);
+        }
+        match *self.constant(index).unwrap() {
+            Constant::Utf8(ref value) => format!("Utf8 {}", syntax::string(value)),
+            Constant::Class(name) => format!("Class {}", self.utf8(name)),
+            Constant::String(value) => format!("String {}", self.utf8(value)),
+            Constant::MethodType { descriptor_index } => {
+                format!("MethodType {}", self.utf8(descriptor_index))
+            }
+            Constant::Fieldref(ref entity) => {
+                format!("Field {} {}",
+                        self.class_ref(entity.class_index),
+                        self.name_and_type(entity.name_and_type_index))
+            }
+            Constant::Methodref(ref entity) => {
+                format!("Method {} {}",
+                        self.class_ref(entity.class_index),
+                        self.name_and_type(entity.name_and_type_index))
+            }
+            Constant::InterfaceMethodref(ref entity) => {
+                format!("InterfaceMethod {} {}",
+                        self.class_ref(entity.class_index),
+                        self.name_and_type(entity.name_and_type_index))
+            }
+            Constant::NameAndType(_) => format!("NameAndType {}", self.name_and_type(index)),
+            Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                format!("CallSite {} {}",
+                        bootstrap_method_attr_index,
+                        self.name_and_type(name_and_type_index))
+            }
+            Constant::MethodHandle { reference_kind, reference_index } => {
+                format!("MethodHandle {} {}",
+                        reference_kind_name(reference_kind),
+                        self.any_ref(reference_index))
+            }
+            ref constant => self.raw_constant(constant),
+        }
+    }
+
+    /// Formats a constant pool entry in terms of the indices it refers to.
+    fn raw_constant(&self, constant: &Constant) -> String {
+        match *constant {
+            Constant::Utf8(ref value) => format!("Utf8 {}", syntax::string(value)),
+            Constant::Integer(value) => format!("Integer {}", value),
+            Constant::Long(value) => format!("Long {}", value),
+            Constant::Float(value) if value.is_nan() => {
+                format!("Float 0x{:08x}", value.to_bits())
+            }
+            Constant::Float(value) => format!("Float {:?}", value),
+            Constant::Double(value) if value.is_nan() => {
+                format!("Double 0x{:016x}", value.to_bits())
+            }
+            Constant::Double(value) => format!("Double {:?}", value),
+            Constant::Class(name) => format!("Class #{}", name),
+            Constant::String(value) => format!("String #{}", value),
+            Constant::MethodType { descriptor_index } => format!("MethodType #{}", descriptor_index),
+            Constant::Fieldref(ref entity) => {
+                format!("Fieldref #{} #{}", entity.class_index, entity.name_and_type_index)
+            }
+            Constant::Methodref(ref entity) => {
+                format!("Methodref #{} #{}", entity.class_index, entity.name_and_type_index)
+            }
+            Constant::InterfaceMethodref(ref entity) => {
+                format!("InterfaceMethodref #{} #{}",
+                        entity.class_index,
+                        entity.name_and_type_index)
+            }
+            Constant::NameAndType(ref nat) => {
+                format!("NameAndType #{} #{}", nat.name_index, nat.descriptor_index)
+            }
+            Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                format!("InvokeDynamic {} #{}",
+                        bootstrap_method_attr_index,
+                        name_and_type_index)
+            }
+            Constant::MethodHandle { reference_kind, reference_index } => {
+                format!("MethodHandle {} #{}",
+                        reference_kind_name(reference_kind),
+                        reference_index)
+            }
+            Constant::Skip => String::new(),
+        }
+    }
+
+    fn class(&mut self) -> io::Result<()> {
+        let class = self.class;
+        try!(self.line(&format!(".version {} {}", class.major_version, class.minor_version)));
+        for (i, constant) in class.constants.iter().enumerate() {
+            if let Constant::Skip = *constant {
+                continue;
+            }
+            let text = format!(".const #{} = {}", i + 1, self.raw_constant(constant));
+            try!(self.line(&text));
+        }
+        try!(self.line(""));
+        let text = format!(".class {}{}",
+                           syntax::flags(syntax::CLASS_FLAGS, class.access_flags.bits()),
+                           self.class_ref(class.this_class));
+        try!(self.line(&text));
+        let text = format!(".super {}", self.optional_class_ref(class.super_class));
+        try!(self.line(&text));
+        for interface in class.interfaces.iter() {
+            let text = format!(".implements {}", self.class_ref(*interface));
+            try!(self.line(&text));
+        }
+        for field in class.fields.iter() {
+            try!(self.line(""));
+            let text = format!(".field {}{} {}",
+                               syntax::flags(syntax::FIELD_FLAGS, field.access_flags.bits()),
+                               self.utf8(field.name_index),
+                               self.utf8(field.descriptor_index));
+            try!(self.member(&text, &field.attrs));
+        }
+        for method in class.methods.iter() {
+            try!(self.line(""));
+            let text = format!(".method {}{} {}",
+                               syntax::flags(syntax::METHOD_FLAGS, method.access_flags.bits()),
+                               self.utf8(method.name_index),
+                               self.utf8(method.descriptor_index));
+            try!(self.member(&text, &method.attrs));
+        }
+        if !class.attrs.is_empty() {
+            try!(self.line(""));
+        }
+        for attr in class.attrs.iter() {
+            try!(self.attribute(attr));
+        }
+        Ok(())
+    }
+
+    fn member(&mut self, text: &str, attrs: &Attributes) -> io::Result<()> {
+        if attrs.is_empty() {
+            return self.line(text);
+        }
+        try!(self.open(text));
+        for attr in attrs.iter() {
+            try!(self.attribute(attr));
+        }
+        self.close()
+    }
+
+    fn attribute(&mut self, attr: &AttributeInfo) -> io::Result<()> {
+        let directive = format!(".{}", attr.name().to_lowercase());
+        match *attr {
+            AttributeInfo::SourceFile(index) |
+            AttributeInfo::Signature(index) => {
+                let text = format!("{} {}", directive, self.utf8(index));
+                self.line(&text)
+            }
+            AttributeInfo::ConstantValue(index) => {
+                let text = format!("{} {}", directive, self.any_ref(index));
+                self.line(&text)
+            }
+            AttributeInfo::Synthetic |
+            AttributeInfo::Deprecated => self.line(&directive),
+            AttributeInfo::SourceDebugExtension(ref bytes) => {
+                self.line(&format!("{} {}", directive, syntax::hex(bytes)))
+            }
+            AttributeInfo::Exceptions(ref exceptions) => {
+                try!(self.open(&directive));
+                for exception in exceptions.iter() {
+                    let text = self.class_ref(*exception);
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::InnerClasses(ref classes) => {
+                try!(self.open(&directive));
+                for class in classes.iter() {
+                    let flags = syntax::flags(syntax::INNER_CLASS_FLAGS,
+                                              class.inner_class_access_flags.bits());
+                    let text = format!("{}{} {} {}",
+                                       flags,
+                                       self.class_ref(class.inner_class_info_index),
+                                       self.optional_class_ref(class.outer_class_info_index),
+                                       self.optional_utf8(class.inner_name_index));
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::EnclosingMethod(ref method) => {
+                let method_ref = if method.method_index == 0 {
+                    "none".to_string()
+                } else {
+                    self.any_ref(method.method_index)
+                };
+                let text = format!("{} {} {}",
+                                   directive,
+                                   self.class_ref(method.class_index),
+                                   method_ref);
+                self.line(&text)
+            }
+            AttributeInfo::BootstrapMethods(ref methods) => {
+                try!(self.open(&directive));
+                for method in methods.iter() {
+                    let text = self.any_ref(method.bootstrap_method_ref);
+                    try!(self.open(&text));
+                    for argument in method.bootstrap_arguments.iter() {
+                        let text = self.any_ref(*argument);
+                        try!(self.line(&text));
+                    }
+                    try!(self.close());
+                }
+                self.close()
+            }
+            AttributeInfo::MethodParameters(ref parameters) => {
+                try!(self.open(&directive));
+                for parameter in parameters.iter() {
+                    let text = format!("{}{}",
+                                       syntax::flags(syntax::PARAMETER_FLAGS,
+                                                     parameter.access_flags.bits()),
+                                       self.optional_utf8(parameter.name_index));
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::AnnotationDefault(ref value) => {
+                let text = format!("{} {}", directive, self.element_value(value));
+                self.line(&text)
+            }
+            AttributeInfo::RuntimeVisibleAnnotations(ref annotations) |
+            AttributeInfo::RuntimeInvisibleAnnotations(ref annotations) => {
+                try!(self.open(&directive));
+                for annotation in annotations.iter() {
+                    let text = self.annotation(annotation);
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::RuntimeVisibleParameterAnnotations(ref parameters) |
+            AttributeInfo::RuntimeInvisibleParameterAnnotations(ref parameters) => {
+                try!(self.open(&directive));
+                for annotations in parameters.iter() {
+                    try!(self.open(""));
+                    for annotation in annotations.iter() {
+                        let text = self.annotation(annotation);
+                        try!(self.line(&text));
+                    }
+                    try!(self.close());
+                }
+                self.close()
+            }
+            AttributeInfo::RuntimeVisibleTypeAnnotations(ref annotations) |
+            AttributeInfo::RuntimeInvisibleTypeAnnotations(ref annotations) => {
+                try!(self.open(&directive));
+                for annotation in annotations.iter() {
+                    let text = self.type_annotation(annotation);
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::Code(ref code) => self.code(code),
+            AttributeInfo::LineNumberTable(ref lines) => {
+                try!(self.open(&directive));
+                for line in lines.iter() {
+                    let text = format!("{} {}", self.pc(line.start_pc as i64), line.line_number);
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::LocalVariableTable(ref locals) => {
+                try!(self.open(&directive));
+                for local in locals.iter() {
+                    let text = format!("{} {} {} {} {}",
+                                       self.pc(local.start_pc as i64),
+                                       self.pc(local.start_pc as i64 + local.length as i64),
+                                       local.index,
+                                       self.utf8(local.name_index),
+                                       self.utf8(local.descriptor_index));
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::LocalVariableTypeTable(ref locals) => {
+                try!(self.open(&directive));
+                for local in locals.iter() {
+                    let text = format!("{} {} {} {} {}",
+                                       self.pc(local.start_pc as i64),
+                                       self.pc(local.start_pc as i64 + local.length as i64),
+                                       local.index,
+                                       self.utf8(local.name_index),
+                                       self.utf8(local.signature_index));
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::StackMapTable(ref frames) => {
+                try!(self.open(&directive));
+                let mut pc = -1;
+                for frame in frames.iter() {
+                    pc += frame_offset_delta(frame) as i64 + 1;
+                    let text = self.frame(frame, pc);
+                    try!(self.line(&text));
+                }
+                self.close()
+            }
+            AttributeInfo::Raw(ref raw) => {
+                let text = format!(".attribute {} {}",
+                                   self.utf8(raw.name_index),
+                                   syntax::hex(&raw.info));
+                self.line(&text)
+            }
+        }
+    }
+
+    fn element_value(&self, value: &ElementValue) -> String {
+        match *value {
+            ElementValue::ConstantValue(ref value) => {
+                format!("{} {}", value.tag as char, self.any_ref(value.const_value_index))
+            }
+            ElementValue::EnumConstValue(ref value) => {
+                format!("enum {} {}",
+                        self.utf8(value.type_name_index),
+                        self.utf8(value.const_name_index))
+            }
+            ElementValue::ClassInfo(index) => format!("class {}", self.utf8(index)),
+            ElementValue::AnnotationValue(ref annotation) => {
+                format!("annotation {}", self.annotation(annotation))
+            }
+            ElementValue::ArrayValue(ref array) => {
+                let values: Vec<String> = array.values
+                    .iter()
+                    .map(|value| self.element_value(value))
+                    .collect();
+                format!("array {}", syntax::braces(&values))
+            }
+        }
+    }
+
+    fn element_value_pairs(&self, pairs: &[ElementValuePair]) -> String {
+        let pairs: Vec<String> = pairs.iter()
+            .map(|pair| {
+                format!("{} = {}",
+                        self.utf8(pair.element_name_index),
+                        self.element_value(&pair.value))
+            })
+            .collect();
+        syntax::braces(&pairs)
+    }
+
+    fn annotation(&self, annotation: &Annotation) -> String {
+        format!("{} {}",
+                self.utf8(annotation.type_index),
+                self.element_value_pairs(&annotation.element_value_pairs))
+    }
+
+    fn type_annotation(&self, annotation: &TypeAnnotation) -> String {
+        let target = match annotation.target_info {
+            TargetInfo::TypeParameter(index) => format!("type_parameter {}", index),
+            TargetInfo::Supertype(index) => format!("supertype {}", index),
+            TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+                format!("type_parameter_bound {} {}", type_parameter_index, bound_index)
+            }
+            TargetInfo::Empty => "empty".to_string(),
+            TargetInfo::MethodFormalParameter(index) => format!("formal_parameter {}", index),
+            TargetInfo::Throws(index) => format!("throws {}", index),
+            TargetInfo::Localvar(ref table) => {
+                let entries: Vec<String> = table.iter()
+                    .map(|e| format!("{} {} {}", e.start_pc, e.length, e.index))
+                    .collect();
+                format!("localvar {}", syntax::braces(&entries))
+            }
+            TargetInfo::Catch(index) => format!("catch {}", index),
+            TargetInfo::Offset(offset) => format!("offset {}", offset),
+            TargetInfo::TypeArgument { offset, type_argument_index } => {
+                format!("type_argument {} {}", offset, type_argument_index)
+            }
+        };
+        let path: Vec<String> = annotation.target_path
+            .iter()
+            .map(|e| format!("{} {}", e.type_path_kind, e.type_argument_index))
+            .collect();
+        format!("0x{:02x} {} path {} {} {}",
+                annotation.target_type,
+                target,
+                syntax::braces(&path),
+                self.utf8(annotation.type_index),
+                self.element_value_pairs(&annotation.element_value_pairs))
+    }
+
+    fn verification_type(&self, info: &VerificationTypeInfo) -> String {
+        match *info {
+            VerificationTypeInfo::Top => "top".to_string(),
+            VerificationTypeInfo::Integer => "int".to_string(),
+            VerificationTypeInfo::Float => "float".to_string(),
+            VerificationTypeInfo::Long => "long".to_string(),
+            VerificationTypeInfo::Double => "double".to_string(),
+            VerificationTypeInfo::Null => "null".to_string(),
+            VerificationTypeInfo::UninitializedThis => "uninitialized_this".to_string(),
+            VerificationTypeInfo::Object(ref object) => {
+                format!("object {}", self.class_ref(object.cpool_index))
+            }
+            VerificationTypeInfo::Uninitialized(ref uninitialized) => {
+                format!("uninitialized {}", self.pc(uninitialized.offset as i64))
+            }
+        }
+    }
+
+    fn verification_types(&self, infos: &[VerificationTypeInfo]) -> String {
+        let infos: Vec<String> = infos.iter().map(|info| self.verification_type(info)).collect();
+        syntax::braces(&infos)
+    }
+
+    fn frame(&self, frame: &StackMapFrame, pc: i64) -> String {
+        let pc = self.pc(pc);
+        match *frame {
+            StackMapFrame::SameFrame { .. } => format!("same {}", pc),
+            StackMapFrame::SameFrameExtended { .. } => format!("same_extended {}", pc),
+            StackMapFrame::SameLocals1StackItemFrame { ref stack, .. } => {
+                format!("same_locals_1_stack_item {} {}",
+                        pc,
+                        self.verification_type(&stack[0]))
+            }
+            StackMapFrame::SameLocals1StackItemFrameExtended { ref stack, .. } => {
+                format!("same_locals_1_stack_item_extended {} {}",
+                        pc,
+                        self.verification_type(&stack[0]))
+            }
+            StackMapFrame::ChopFrame { frame_type, .. } => {
+                format!("chop {} {}", pc, 251 - frame_type)
+            }
+            StackMapFrame::AppendFrame { ref locals, .. } => {
+                format!("append {} {}", pc, self.verification_types(locals))
+            }
+            StackMapFrame::FullFrame { ref locals, ref stack, .. } => {
+                format!("full {} {} {}",
+                        pc,
+                        self.verification_types(locals),
+                        self.verification_types(stack))
+            }
+        }
+    }
+
+    /// Formats a code offset as a label when it is an instruction boundary.
+    fn pc(&self, pc: i64) -> String {
+        if pc >= 0 && self.labels.contains(&(pc as usize)) {
+            format!("L{}", pc)
+        } else {
+            format!("{}", pc)
+        }
+    }
+
+    fn code(&mut self, code: &CodeAttribute) -> io::Result<()> {
+        let instructions = decode_all(&code.code);
+        self.labels = label_pcs(code, &instructions);
+        let text = format!(".code stack {} locals {}", code.max_stack, code.max_locals);
+        try!(self.open(&text));
+        for &(pc, newpc) in instructions.iter() {
+            if self.labels.contains(&pc) {
+                try!(self.label(pc));
+            }
+            for text in self.instruction(&code.code, pc, newpc) {
+                try!(self.line(&text));
+            }
+        }
+        if self.labels.contains(&code.code.len()) {
+            try!(self.label(code.code.len()));
+        }
+        for handler in code.exception_table.iter() {
+            let catch_type = if handler.catch_type == 0 {
+                "any".to_string()
+            } else {
+                self.class_ref(handler.catch_type)
+            };
+            let text = format!(".catch {} {} {} {}",
+                               catch_type,
+                               self.pc(handler.start_pc as i64),
+                               self.pc(handler.end_pc as i64),
+                               self.pc(handler.handler_pc as i64));
+            try!(self.line(&text));
+        }
+        for attr in code.attrs.iter() {
+            try!(self.attribute(attr));
+        }
+        try!(self.close());
+        self.labels.clear();
+        Ok(())
+    }
+
+    fn label(&mut self, pc: usize) -> io::Result<()> {
+        self.indent -= 1;
+        try!(self.line(&format!("L{}:", pc)));
+        self.indent += 1;
+        Ok(())
+    }
+
+    /// Formats the instruction occupying `code[pc..newpc]`. Bytes that do not
+    /// form a valid instruction are written with the `invalid` pseudo
+    /// instruction, one per byte.
+    fn instruction(&self, code: &[u8], pc: usize, newpc: usize) -> Vec<String> {
+        let opcode = code[pc];
+        let mnemonic = match opcodes::mnemonic(opcode) {
+            Some(mnemonic) => mnemonic,
+            None => return invalid(&code[pc..newpc]),
+        };
+        let operands = &code[pc + 1..newpc];
+        let text = match opcodes::operands(opcode) {
+            Operands::None => mnemonic.to_string(),
+            Operands::Byte => format!("{} {}", mnemonic, operands[0] as i8),
+            Operands::Short => format!("{} {}", mnemonic, i16_at(operands, 0)),
+            Operands::Local => format!("{} {}", mnemonic, operands[0]),
+            Operands::Constant8 => format!("{} {}", mnemonic, self.any_ref(operands[0] as u16)),
+            Operands::Constant16 => format!("{} {}", mnemonic, self.any_ref(u16_at(operands, 0))),
+            Operands::Iinc => format!("{} {} {}", mnemonic, operands[0], operands[1] as i8),
+            Operands::Branch16 => {
+                let target = pc as i64 + i16_at(operands, 0) as i64;
+                format!("{} {}", mnemonic, self.pc(target))
+            }
+            Operands::Branch32 => {
+                let target = pc as i64 + i32_at(operands, 0) as i64;
+                format!("{} {}", mnemonic, self.pc(target))
+            }
+            Operands::TableSwitch => {
+                let operands = &operands[padding(pc)..];
+                let default = pc as i64 + i32_at(operands, 0) as i64;
+                let low = i32_at(operands, 4);
+                let count = (operands.len() - 12) / 4;
+                let targets: Vec<String> = (0..count)
+                    .map(|i| self.pc(pc as i64 + i32_at(operands, 12 + i * 4) as i64))
+                    .collect();
+                format!("{} {} {} default {}",
+                        mnemonic,
+                        low,
+                        syntax::braces(&targets),
+                        self.pc(default))
+            }
+            Operands::LookupSwitch => {
+                let operands = &operands[padding(pc)..];
+                let default = pc as i64 + i32_at(operands, 0) as i64;
+                let count = (operands.len() - 8) / 8;
+                let pairs: Vec<String> = (0..count)
+                    .map(|i| {
+                        let key = i32_at(operands, 8 + i * 8);
+                        let target = pc as i64 + i32_at(operands, 12 + i * 8) as i64;
+                        format!("{} {}", key, self.pc(target))
+                    })
+                    .collect();
+                format!("{} {} default {}",
+                        mnemonic,
+                        syntax::braces(&pairs),
+                        self.pc(default))
+            }
+            Operands::InvokeInterface if operands[3] == 0 => {
+                format!("{} {} {}",
+                        mnemonic,
+                        self.any_ref(u16_at(operands, 0)),
+                        operands[2])
+            }
+            Operands::InvokeDynamic if operands[2] == 0 && operands[3] == 0 => {
+                format!("{} {}", mnemonic, self.any_ref(u16_at(operands, 0)))
+            }
+            Operands::InvokeInterface | Operands::InvokeDynamic => {
+                return invalid(&code[pc..newpc]);
+            }
+            Operands::MultiANewArray => {
+                format!("{} {} {}",
+                        mnemonic,
+                        self.any_ref(u16_at(operands, 0)),
+                        operands[2])
+            }
+            Operands::NewArray => {
+                match opcodes::ARRAY_TYPES.get((operands[0] as usize).wrapping_sub(4)) {
+                    Some(name) => format!("{} {}", mnemonic, name),
+                    None => format!("{} {}", mnemonic, operands[0]),
+                }
+            }
+            Operands::Wide => {
+                let modified = match opcodes::mnemonic(operands[0]) {
+                    Some(modified) => modified,
+                    None => return invalid(&code[pc..newpc]),
+                };
+                match opcodes::operands(operands[0]) {
+                    Operands::Local if operands.len() == 3 => {
+                        format!("wide {} {}", modified, u16_at(operands, 1))
+                    }
+                    Operands::Iinc if operands.len() == 5 => {
+                        format!("wide {} {} {}",
+                                modified,
+                                u16_at(operands, 1),
+                                i16_at(operands, 3))
+                    }
+                    _ => return invalid(&code[pc..newpc]),
+                }
+            }
+        };
+        vec![text]
+    }
+}
+
+fn invalid(bytes: &[u8]) -> Vec<String> {
+    bytes.iter().map(|byte| format!("invalid 0x{:02x}", byte)).collect()
+}
+
+fn reference_kind_name(kind: u8) -> String {
+    match syntax::REFERENCE_KINDS.get((kind as usize).wrapping_sub(1)) {
+        Some(name) => name.to_string(),
+        None => format!("{}", kind),
+    }
+}
+
+fn frame_offset_delta(frame: &StackMapFrame) -> u16 {
+    match *frame {
+        StackMapFrame::SameFrame { frame_type } => frame_type as u16,
+        StackMapFrame::SameLocals1StackItemFrame { frame_type, .. } => frame_type as u16 - 64,
+        StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. } |
+        StackMapFrame::ChopFrame { offset_delta, .. } |
+        StackMapFrame::SameFrameExtended { offset_delta } |
+        StackMapFrame::AppendFrame { offset_delta, .. } |
+        StackMapFrame::FullFrame { offset_delta, .. } => offset_delta,
+    }
+}
+
+/// Returns the number of padding bytes that follow a switch opcode at `pc`.
+fn padding(pc: usize) -> usize {
+    (4 - (pc + 1) % 4) % 4
+}
+
+fn u16_at(bytes: &[u8], i: usize) -> u16 {
+    (bytes[i] as u16) << 8 | bytes[i + 1] as u16
+}
+
+fn i16_at(bytes: &[u8], i: usize) -> i16 {
+    u16_at(bytes, i) as i16
+}
+
+fn i32_at(bytes: &[u8], i: usize) -> i32 {
+    ((u16_at(bytes, i) as u32) << 16 | u16_at(bytes, i + 2) as u32) as i32
+}
+
+/// Returns the start and end offsets of each instruction in `code`.
+fn decode_all(code: &[u8]) -> Vec<(usize, usize)> {
+    let mut instructions = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let newpc = match Bytecode::decode(code, pc) {
+            Ok(result) => result.newpc,
+            // A truncated instruction runs to the end of the code.
+            Err(Error::TruncatedBytecode { .. }) => code.len(),
+            Err(_) => pc + 1,
+        };
+        instructions.push((pc, newpc));
+        pc = newpc;
+    }
+    instructions
+}
+
+/// Returns the instruction boundaries that are referred to by branches,
+/// exception handlers or code attributes.
+fn label_pcs(code: &CodeAttribute, instructions: &[(usize, usize)]) -> BTreeSet<usize> {
+    let mut referenced: Vec<i64> = vec![];
+    for &(pc, newpc) in instructions.iter() {
+        let bytes = &code.code[pc + 1..newpc];
+        match opcodes::operands(code.code[pc]) {
+            Operands::Branch16 => referenced.push(pc as i64 + i16_at(bytes, 0) as i64),
+            Operands::Branch32 => referenced.push(pc as i64 + i32_at(bytes, 0) as i64),
+            Operands::TableSwitch | Operands::LookupSwitch => {
+                let bytes = &bytes[padding(pc)..];
+                referenced.push(pc as i64 + i32_at(bytes, 0) as i64);
+                let (start, step) = if code.code[pc] == 0xaa { (12, 4) } else { (12, 8) };
+                let mut i = start;
+                while i + 4 <= bytes.len() {
+                    referenced.push(pc as i64 + i32_at(bytes, i) as i64);
+                    i += step;
+                }
+            }
+            _ => {}
+        }
+    }
+    for handler in code.exception_table.iter() {
+        referenced.push(handler.start_pc as i64);
+        referenced.push(handler.end_pc as i64);
+        referenced.push(handler.handler_pc as i64);
+    }
+    for attr in code.attrs.iter() {
+        match *attr {
+            AttributeInfo::LineNumberTable(ref lines) => {
+                referenced.extend(lines.iter().map(|line| line.start_pc as i64));
+            }
+            AttributeInfo::LocalVariableTable(ref locals) => {
+                for local in locals.iter() {
+                    referenced.push(local.start_pc as i64);
+                    referenced.push(local.start_pc as i64 + local.length as i64);
+                }
+            }
+            AttributeInfo::LocalVariableTypeTable(ref locals) => {
+                for local in locals.iter() {
+                    referenced.push(local.start_pc as i64);
+                    referenced.push(local.start_pc as i64 + local.length as i64);
+                }
+            }
+            AttributeInfo::StackMapTable(ref frames) => {
+                let mut pc = -1;
+                for frame in frames.iter() {
+                    pc += frame_offset_delta(frame) as i64 + 1;
+                    referenced.push(pc);
+                    let (locals, stack): (&[VerificationTypeInfo], &[VerificationTypeInfo]) =
+                        match *frame {
+                            StackMapFrame::SameLocals1StackItemFrame { ref stack, .. } |
+                            StackMapFrame::SameLocals1StackItemFrameExtended { ref stack, .. } => {
+                                (&[], stack)
+                            }
+                            StackMapFrame::AppendFrame { ref locals, .. } => (locals, &[]),
+                            StackMapFrame::FullFrame { ref locals, ref stack, .. } => {
+                                (locals, stack)
+                            }
+                            _ => (&[], &[]),
+                        };
+                    for info in locals.iter().chain(stack.iter()) {
+                        if let VerificationTypeInfo::Uninitialized(ref u) = *info {
+                            referenced.push(u.offset as i64);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut boundaries: BTreeSet<usize> = instructions.iter().map(|&(pc, _)| pc).collect();
+    boundaries.insert(code.code.len());
+    referenced.into_iter()
+        .filter(|&pc| pc >= 0 && boundaries.contains(&(pc as usize)))
+        .map(|pc| pc as usize)
+        .collect()
+}
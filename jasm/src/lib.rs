@@ -0,0 +1,69 @@
+//! A textual assembler and disassembler for class files.
+//!
+//! `disassemble` writes a class as a sequence of directives, and `assemble`
+//! parses that text back into a `ClassFile`. The two are inverses: a
+//! disassembled class assembles to a class that serializes to the original
+//! bytes. A short example:
+//!
+//! ```text
+//! .version 52 0
+//! .class public super HelloWorld
+//! .super java/lang/Object
+//!
+//! .method public static main ([Ljava/lang/String;)V {
+//!     .code stack 2 locals 1 {
+//!         getstatic Field java/lang/System out Ljava/io/PrintStream;
+//!         ldc String "Hello, World!"
+//!         invokevirtual Method java/io/PrintStream println (Ljava/lang/String;)V
+//!         return
+//!     }
+//! }
+//! ```
+//!
+//! Constant pool references may be written symbolically, as above, in which
+//! case the first equal entry in the pool is used or a new one is appended.
+//! They may also be written as explicit indices (`#12`) into a pool declared
+//! with `.const` directives; the disassembler declares the complete pool so
+//! that entry order and duplicate entries are preserved.
+//!
+//! Within a `.code` block, `name:` defines a label at the next instruction.
+//! Branch targets, exception ranges and code attributes refer to pcs either
+//! by label or by absolute pc.
+extern crate classfile;
+
+use std::fmt;
+
+mod assembler;
+mod disassembler;
+mod opcodes;
+mod pool;
+mod syntax;
+mod tokenizer;
+
+pub use assembler::assemble;
+pub use disassembler::disassemble;
+
+/// An error in assembler source.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Error {
+    /// The line number on which the error was detected.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error {
+    fn new(line: usize, message: &str) -> Error {
+        Error {
+            line: line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
@@ -0,0 +1,55 @@
+extern crate classfile;
+extern crate jasm;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+use classfile::reader::ClassReader;
+use classfile::writer::ClassWriter;
+
+const USAGE: &'static str = "usage: jasm <command> <input> [output]
+
+commands:
+    dis <class file>                disassemble a class to standard output
+    asm <source file> <class file>  assemble a class";
+
+fn usage() -> ! {
+    println!("{}", USAGE);
+    process::exit(1);
+}
+
+fn fail(message: String) -> ! {
+    println!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match (args.get(0).map(|s| s.as_ref()), args.len()) {
+        (Some("dis"), 2) => {
+            let file = File::open(&args[1])
+                .unwrap_or_else(|e| fail(format!("{}: {}", args[1], e)));
+            let class = ClassReader::new(file)
+                .read_class()
+                .unwrap_or_else(|e| fail(format!("{}: {:?}", args[1], e)));
+            let stdout = io::stdout();
+            jasm::disassemble(&class, &mut stdout.lock()).unwrap();
+        }
+        (Some("asm"), 3) => {
+            let mut source = String::new();
+            File::open(&args[1])
+                .and_then(|mut file| file.read_to_string(&mut source))
+                .unwrap_or_else(|e| fail(format!("{}: {}", args[1], e)));
+            let class = jasm::assemble(&source)
+                .unwrap_or_else(|e| fail(format!("{}:{}", args[1], e)));
+            let file = File::create(&args[2])
+                .unwrap_or_else(|e| fail(format!("{}: {}", args[2], e)));
+            ClassWriter::new(file)
+                .write_class(&class)
+                .unwrap_or_else(|e| fail(format!("{}: {:?}", args[2], e)));
+        }
+        _ => usage(),
+    }
+}
@@ -0,0 +1,110 @@
+/// Mnemonics for opcodes `0x00` through `0xc9`, indexed by opcode.
+pub static MNEMONICS: [&'static str; 0xca] = [
+    "nop", "aconst_null", "iconst_m1", "iconst_0", "iconst_1", "iconst_2", "iconst_3", "iconst_4",
+    "iconst_5", "lconst_0", "lconst_1", "fconst_0", "fconst_1", "fconst_2", "dconst_0", "dconst_1",
+    "bipush", "sipush", "ldc", "ldc_w", "ldc2_w", "iload", "lload", "fload",
+    "dload", "aload", "iload_0", "iload_1", "iload_2", "iload_3", "lload_0", "lload_1",
+    "lload_2", "lload_3", "fload_0", "fload_1", "fload_2", "fload_3", "dload_0", "dload_1",
+    "dload_2", "dload_3", "aload_0", "aload_1", "aload_2", "aload_3", "iaload", "laload",
+    "faload", "daload", "aaload", "baload", "caload", "saload", "istore", "lstore",
+    "fstore", "dstore", "astore", "istore_0", "istore_1", "istore_2", "istore_3", "lstore_0",
+    "lstore_1", "lstore_2", "lstore_3", "fstore_0", "fstore_1", "fstore_2", "fstore_3", "dstore_0",
+    "dstore_1", "dstore_2", "dstore_3", "astore_0", "astore_1", "astore_2", "astore_3", "iastore",
+    "lastore", "fastore", "dastore", "aastore", "bastore", "castore", "sastore", "pop",
+    "pop2", "dup", "dup_x1", "dup_x2", "dup2", "dup2_x1", "dup2_x2", "swap",
+    "iadd", "ladd", "fadd", "dadd", "isub", "lsub", "fsub", "dsub",
+    "imul", "lmul", "fmul", "dmul", "idiv", "ldiv", "fdiv", "ddiv",
+    "irem", "lrem", "frem", "drem", "ineg", "lneg", "fneg", "dneg",
+    "ishl", "lshl", "ishr", "lshr", "iushr", "lushr", "iand", "land",
+    "ior", "lor", "ixor", "lxor", "iinc", "i2l", "i2f", "i2d",
+    "l2i", "l2f", "l2d", "f2i", "f2l", "f2d", "d2i", "d2l",
+    "d2f", "i2b", "i2c", "i2s", "lcmp", "fcmpl", "fcmpg", "dcmpl",
+    "dcmpg", "ifeq", "ifne", "iflt", "ifge", "ifgt", "ifle", "if_icmpeq",
+    "if_icmpne", "if_icmplt", "if_icmpge", "if_icmpgt", "if_icmple", "if_acmpeq", "if_acmpne", "goto",
+    "jsr", "ret", "tableswitch", "lookupswitch", "ireturn", "lreturn", "freturn", "dreturn",
+    "areturn", "return", "getstatic", "putstatic", "getfield", "putfield", "invokevirtual", "invokespecial",
+    "invokestatic", "invokeinterface", "invokedynamic", "new", "newarray", "anewarray", "arraylength", "athrow",
+    "checkcast", "instanceof", "monitorenter", "monitorexit", "wide", "multianewarray", "ifnull", "ifnonnull",
+    "goto_w", "jsr_w",
+];
+
+/// Element type names used by `newarray`, indexed by `atype - 4`.
+pub static ARRAY_TYPES: [&'static str; 8] = ["boolean", "char", "float", "double", "byte",
+                                              "short", "int", "long"];
+
+/// Describes the operands that follow an opcode.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operands {
+    None,
+    /// A signed byte (`bipush`).
+    Byte,
+    /// A signed short (`sipush`).
+    Short,
+    /// An unsigned byte local variable index.
+    Local,
+    /// An unsigned byte constant pool index (`ldc`).
+    Constant8,
+    /// An unsigned short constant pool index.
+    Constant16,
+    /// A local variable index and a signed byte increment.
+    Iinc,
+    /// A signed 16 bit branch offset.
+    Branch16,
+    /// A signed 32 bit branch offset.
+    Branch32,
+    TableSwitch,
+    LookupSwitch,
+    /// A constant pool index, an argument count and a zero byte.
+    InvokeInterface,
+    /// A constant pool index and two zero bytes.
+    InvokeDynamic,
+    /// A constant pool index and a dimension count.
+    MultiANewArray,
+    /// An array element type code.
+    NewArray,
+    /// An opcode modified to take wider operands.
+    Wide,
+}
+
+pub fn lookup(mnemonic: &str) -> Option<u8> {
+    MNEMONICS.iter().position(|m| *m == mnemonic).map(|op| op as u8)
+}
+
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+    MNEMONICS.get(opcode as usize).map(|m| *m)
+}
+
+pub fn operands(opcode: u8) -> Operands {
+    match opcode {
+        0x10 => Operands::Byte,
+        0x11 => Operands::Short,
+        0x12 => Operands::Constant8,
+        0x13 | 0x14 => Operands::Constant16,
+        0x15..=0x19 | 0x36..=0x3a | 0xa9 => Operands::Local,
+        0x84 => Operands::Iinc,
+        0x99..=0xa8 | 0xc6 | 0xc7 => Operands::Branch16,
+        0xaa => Operands::TableSwitch,
+        0xab => Operands::LookupSwitch,
+        0xb2..=0xb8 | 0xbb | 0xbd | 0xc0 | 0xc1 => Operands::Constant16,
+        0xb9 => Operands::InvokeInterface,
+        0xba => Operands::InvokeDynamic,
+        0xbc => Operands::NewArray,
+        0xc4 => Operands::Wide,
+        0xc5 => Operands::MultiANewArray,
+        0xc8 | 0xc9 => Operands::Branch32,
+        _ => Operands::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_inverts_mnemonic() {
+        for opcode in 0..0xca {
+            assert_eq!(Some(opcode as u8), lookup(mnemonic(opcode as u8).unwrap()));
+        }
+        assert_eq!(None, mnemonic(0xca));
+    }
+}
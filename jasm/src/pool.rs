@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use classfile::{Constant, ConstantPool};
+
+/// Returns a key that identifies equal constants.
+///
+/// Floating point constants are keyed by their bit patterns so that `-0.0` and
+/// the various `NaN` encodings remain distinct.
+fn key(constant: &Constant) -> Option<String> {
+    match *constant {
+        Constant::Float(value) => Some(format!("Float({:08x})", value.to_bits())),
+        Constant::Double(value) => Some(format!("Double({:016x})", value.to_bits())),
+        Constant::Skip => None,
+        _ => Some(format!("{:?}", constant)),
+    }
+}
+
+/// Returns the indices of the constants that `constant` refers to.
+fn references(constant: &Constant) -> Vec<u16> {
+    match *constant {
+        Constant::Class(index) |
+        Constant::String(index) |
+        Constant::MethodType { descriptor_index: index } => vec![index],
+        Constant::Fieldref(ref entity) |
+        Constant::Methodref(ref entity) |
+        Constant::InterfaceMethodref(ref entity) => {
+            vec![entity.class_index, entity.name_and_type_index]
+        }
+        Constant::NameAndType(ref nat) => vec![nat.name_index, nat.descriptor_index],
+        Constant::MethodHandle { reference_index, .. } => vec![reference_index],
+        Constant::InvokeDynamic { name_and_type_index, .. } => vec![name_and_type_index],
+        _ => vec![],
+    }
+}
+
+/// Builds a constant pool, re-using existing entries where possible.
+pub struct PoolBuilder {
+    constants: Vec<Constant>,
+    first: HashMap<String, u16>,
+}
+
+impl PoolBuilder {
+    /// Creates a builder that starts from an existing list of constants.
+    pub fn new(constants: Vec<Constant>) -> PoolBuilder {
+        let mut first = HashMap::new();
+        for (i, constant) in constants.iter().enumerate() {
+            if let Some(key) = key(constant) {
+                first.entry(key).or_insert(i as u16 + 1);
+            }
+        }
+        PoolBuilder {
+            constants: constants,
+            first: first,
+        }
+    }
+
+    /// Returns the index of the first entry equal to `constant`, appending it
+    /// to the pool if there is no such entry. Returns `None` if the pool is full.
+    pub fn intern(&mut self, constant: Constant) -> Option<u16> {
+        let key = match key(&constant) {
+            Some(key) => key,
+            None => return None,
+        };
+        if let Some(&index) = self.first.get(&key) {
+            return Some(index);
+        }
+        let wide = match constant {
+            Constant::Long(_) | Constant::Double(_) => true,
+            _ => false,
+        };
+        let index = self.constants.len() + 1;
+        if index + (wide as usize) > 0xffff {
+            return None;
+        }
+        self.constants.push(constant);
+        if wide {
+            self.constants.push(Constant::Skip);
+        }
+        self.first.insert(key, index as u16);
+        Some(index as u16)
+    }
+
+    pub fn utf8(&mut self, value: &str) -> Option<u16> {
+        self.intern(Constant::Utf8(value.to_string()))
+    }
+
+    pub fn class(&mut self, name: &str) -> Option<u16> {
+        self.utf8(name).and_then(|index| self.intern(Constant::Class(index)))
+    }
+
+    pub fn into_pool(self) -> ConstantPool {
+        ConstantPool::new(self.constants)
+    }
+}
+
+/// Determines which constant pool entries can be written symbolically.
+///
+/// An entry is canonical when it, and every entry it refers to, is the first
+/// entry in the pool with its value. Interning the symbolic form of a canonical
+/// entry into the same pool yields the original index.
+pub fn canonical(pool: &ConstantPool) -> Vec<bool> {
+    let mut first = HashMap::new();
+    let mut canonical = vec![false; pool.len() as usize];
+    for (i, constant) in pool.iter().enumerate() {
+        if let Some(key) = key(constant) {
+            if !first.contains_key(&key) {
+                first.insert(key, i + 1);
+                canonical[i + 1] = true;
+            }
+        }
+    }
+    // Propagate non-canonical references until nothing changes; references
+    // may point forwards so a single pass is not enough.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, constant) in pool.iter().enumerate() {
+            if !canonical[i + 1] {
+                continue;
+            }
+            let valid = references(constant)
+                .iter()
+                .all(|&r| (r as usize) < canonical.len() && canonical[r as usize]);
+            if !valid {
+                canonical[i + 1] = false;
+                changed = true;
+            }
+        }
+    }
+    canonical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classfile::{Constant, ConstantPool};
+
+    #[test]
+    fn test_intern_reuses_first_entry() {
+        let mut pool = PoolBuilder::new(vec![Constant::Utf8("a".into()),
+                                             Constant::Utf8("a".into())]);
+        assert_eq!(Some(1), pool.utf8("a"));
+        assert_eq!(Some(3), pool.utf8("b"));
+        assert_eq!(Some(4), pool.intern(Constant::Long(1)));
+        assert_eq!(Some(6), pool.utf8("c"));
+    }
+
+    #[test]
+    fn test_intern_distinguishes_float_bits() {
+        let mut pool = PoolBuilder::new(vec![Constant::Float(0.0)]);
+        assert_eq!(Some(2), pool.intern(Constant::Float(-0.0)));
+    }
+
+    #[test]
+    fn test_canonical_rejects_duplicates() {
+        let pool = ConstantPool::new(vec![Constant::Utf8("a".into()),
+                                          Constant::Utf8("a".into()),
+                                          Constant::Class(2),
+                                          Constant::Class(1)]);
+        assert_eq!(vec![false, true, false, false, true], canonical(&pool));
+    }
+}
@@ -0,0 +1,180 @@
+//! Vocabulary shared by the assembler and disassembler.
+
+pub static CLASS_FLAGS: &'static [(&'static str, u16)] = &[("public", 0x0001),
+                                                          ("final", 0x0010),
+                                                          ("super", 0x0020),
+                                                          ("interface", 0x0200),
+                                                          ("abstract", 0x0400),
+                                                          ("synthetic", 0x1000),
+                                                          ("annotation", 0x2000),
+                                                          ("enum", 0x4000)];
+
+pub static FIELD_FLAGS: &'static [(&'static str, u16)] = &[("public", 0x0001),
+                                                          ("private", 0x0002),
+                                                          ("protected", 0x0004),
+                                                          ("static", 0x0008),
+                                                          ("final", 0x0010),
+                                                          ("volatile", 0x0040),
+                                                          ("transient", 0x0080),
+                                                          ("synthetic", 0x1000),
+                                                          ("enum", 0x4000)];
+
+pub static METHOD_FLAGS: &'static [(&'static str, u16)] = &[("public", 0x0001),
+                                                           ("private", 0x0002),
+                                                           ("protected", 0x0004),
+                                                           ("static", 0x0008),
+                                                           ("final", 0x0010),
+                                                           ("synchronized", 0x0020),
+                                                           ("bridge", 0x0040),
+                                                           ("varargs", 0x0080),
+                                                           ("native", 0x0100),
+                                                           ("abstract", 0x0400),
+                                                           ("strict", 0x0800),
+                                                           ("synthetic", 0x1000)];
+
+pub static INNER_CLASS_FLAGS: &'static [(&'static str, u16)] = &[("public", 0x0001),
+                                                                ("private", 0x0002),
+                                                                ("protected", 0x0004),
+                                                                ("static", 0x0008),
+                                                                ("final", 0x0010),
+                                                                ("interface", 0x0200),
+                                                                ("abstract", 0x0400),
+                                                                ("synthetic", 0x1000),
+                                                                ("annotation", 0x2000),
+                                                                ("enum", 0x4000)];
+
+pub static PARAMETER_FLAGS: &'static [(&'static str, u16)] = &[("final", 0x0010),
+                                                              ("synthetic", 0x1000),
+                                                              ("mandated", 0x8000)];
+
+/// Names of the `MethodHandle` reference kinds, indexed by `reference_kind - 1`.
+pub static REFERENCE_KINDS: [&'static str; 9] = ["getField",
+                                                 "getStatic",
+                                                 "putField",
+                                                 "putStatic",
+                                                 "invokeVirtual",
+                                                 "invokeStatic",
+                                                 "invokeSpecial",
+                                                 "newInvokeSpecial",
+                                                 "invokeInterface"];
+
+/// Words with a fixed meaning in some position, which must be quoted when
+/// used as a string.
+static KEYWORDS: &'static [&'static str] = &["{", "}", "=", "none", "any", "public", "private",
+                                             "protected", "static", "final", "super",
+                                             "synchronized", "volatile", "bridge", "transient",
+                                             "varargs", "native", "interface", "abstract",
+                                             "strict", "synthetic", "annotation", "enum",
+                                             "mandated"];
+
+/// Formats the names of the flags set in `bits`.
+pub fn flags(table: &[(&str, u16)], bits: u16) -> String {
+    let mut out = String::new();
+    for &(name, flag) in table {
+        if bits & flag != 0 {
+            out.push_str(name);
+            out.push(' ');
+        }
+    }
+    out
+}
+
+/// Formats a string either as a bare word, or quoted when it could otherwise
+/// be mistaken for some other token.
+pub fn string(value: &str) -> String {
+    let bare = !value.is_empty() && !KEYWORDS.contains(&value) &&
+               !value.starts_with(|c: char| {
+        c == '#' || c == '.' || c == '-' || c == '"' || c.is_digit(10)
+    }) && !value.starts_with("//") && !value.ends_with(':') &&
+               !value.chars().any(|c| c.is_whitespace() || c.is_control() || c == '"');
+    if bare {
+        value.to_string()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// Formats items as a single line `{ ... }` block.
+pub fn braces(items: &[String]) -> String {
+    if items.is_empty() {
+        "{ }".to_string()
+    } else {
+        format!("{{ {} }}", items.join(" "))
+    }
+}
+
+/// Formats bytes as a hexadecimal literal.
+pub fn hex(bytes: &[u8]) -> String {
+    let mut out = String::from("0x");
+    for byte in bytes {
+        out.push_str(&format!("{:02x}", byte));
+    }
+    out
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal integer, with an optional
+/// leading `-`.
+pub fn integer(word: &str) -> Option<i64> {
+    let (negative, digits) = if word.starts_with('-') {
+        (true, &word[1..])
+    } else {
+        (false, word)
+    };
+    let value = if digits.starts_with("0x") {
+        u64::from_str_radix(&digits[2..], 16).ok().map(|v| v as i64)
+    } else if !digits.is_empty() && digits.chars().all(|c| c.is_digit(10)) {
+        digits.parse::<u64>().ok().map(|v| v as i64)
+    } else {
+        None
+    };
+    value.map(|v| if negative { v.wrapping_neg() } else { v })
+}
+
+/// Parses a hexadecimal literal produced by `hex`.
+pub fn bytes(word: &str) -> Option<Vec<u8>> {
+    if !word.starts_with("0x") || word.len() % 2 != 0 {
+        return None;
+    }
+    let digits = &word[2..];
+    let mut out = vec![];
+    for i in 0..digits.len() / 2 {
+        match u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16) {
+            Ok(byte) => out.push(byte),
+            Err(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_quotes_ambiguous_words() {
+        assert_eq!("java/lang/Object", string("java/lang/Object"));
+        assert_eq!("<init>", string("<init>"));
+        assert_eq!("\"public\"", string("public"));
+        assert_eq!("\"Hello, World!\"", string("Hello, World!"));
+        assert_eq!("\"\"", string(""));
+        assert_eq!("\"#1\"", string("#1"));
+        assert_eq!("\"42\"", string("42"));
+    }
+
+    #[test]
+    fn test_integer() {
+        assert_eq!(Some(42), integer("42"));
+        assert_eq!(Some(-42), integer("-42"));
+        assert_eq!(Some(255), integer("0xff"));
+        assert_eq!(None, integer("L42"));
+        assert_eq!(None, integer("-"));
+        assert_eq!(Some(i64::min_value()), integer("-9223372036854775808"));
+    }
+
+    #[test]
+    fn test_bytes_inverts_hex() {
+        assert_eq!(Some(vec![0, 0xca, 0xfe]), bytes(&hex(&[0, 0xca, 0xfe])));
+        assert_eq!(Some(vec![]), bytes("0x"));
+        assert_eq!(None, bytes("0xf"));
+    }
+}
@@ -0,0 +1,144 @@
+use super::{Error, Result};
+
+/// A single token of assembler source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token {
+    /// A run of non-whitespace characters.
+    Word(String),
+    /// A double quoted string, with escapes already processed.
+    Str(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Spanned {
+    pub token: Token,
+    pub line: usize,
+}
+
+/// Splits assembler source into tokens.
+///
+/// Tokens are separated by whitespace. A token beginning with `//` starts a
+/// comment that runs to the end of the line. Strings are delimited by double
+/// quotes and support the escapes produced by Rust's `Debug` formatting of
+/// `str` (`\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'` and `\u{...}`).
+pub fn tokenize(source: &str) -> Result<Vec<Spanned>> {
+    let mut tokens: Vec<Spanned> = vec![];
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    loop {
+        let c = match chars.peek() {
+            Some(&c) => c,
+            None => break,
+        };
+        if c == '\n' {
+            line += 1;
+            chars.next();
+        } else if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => {
+                        let escaped = match chars.next() {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('r') => '\r',
+                            Some('0') => '\0',
+                            Some('\\') => '\\',
+                            Some('"') => '"',
+                            Some('\'') => '\'',
+                            Some('u') => try!(unicode_escape(&mut chars, line)),
+                            _ => return Err(Error::new(line, "invalid escape in string")),
+                        };
+                        value.push(escaped);
+                    }
+                    Some('\n') => return Err(Error::new(line, "unterminated string")),
+                    Some(c) => value.push(c),
+                    None => return Err(Error::new(line, "unterminated string")),
+                }
+            }
+            tokens.push(Spanned {
+                token: Token::Str(value),
+                line: line,
+            });
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if word.starts_with("//") {
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+                continue;
+            }
+            tokens.push(Spanned {
+                token: Token::Word(word),
+                line: line,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+fn unicode_escape<I>(chars: &mut I, line: usize) -> Result<char>
+    where I: Iterator<Item = char>
+{
+    if chars.next() != Some('{') {
+        return Err(Error::new(line, "expected '{' after \\u"));
+    }
+    let mut digits = String::new();
+    loop {
+        match chars.next() {
+            Some('}') => break,
+            Some(c) => digits.push(c),
+            None => return Err(Error::new(line, "unterminated unicode escape")),
+        }
+    }
+    let value = match u32::from_str_radix(&digits, 16) {
+        Ok(value) => value,
+        Err(_) => return Err(Error::new(line, "invalid unicode escape")),
+    };
+    match ::std::char::from_u32(value) {
+        Some(c) => Ok(c),
+        None => Err(Error::new(line, "invalid unicode escape")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(source: &str) -> Vec<Token> {
+        tokenize(source).unwrap().into_iter().map(|t| t.token).collect()
+    }
+
+    #[test]
+    fn test_tokenize_skips_comments() {
+        assert_eq!(vec![Token::Word(".super".into()), Token::Word("java/lang/Object".into())],
+                   words(".super java/lang/Object // the super class\n// another\n"));
+    }
+
+    #[test]
+    fn test_tokenize_string_escapes() {
+        assert_eq!(vec![Token::Str("a \"b\"\n\u{1b}".into())],
+                   words("\"a \\\"b\\\"\\n\\u{1b}\""));
+    }
+
+    #[test]
+    fn test_tokenize_tracks_lines() {
+        let tokens = tokenize("a\n\nb").unwrap();
+        assert_eq!(1, tokens[0].line);
+        assert_eq!(3, tokens[1].line);
+    }
+}
@@ -0,0 +1,68 @@
+extern crate classfile;
+extern crate jasm;
+
+const HELLO: &'static str = r#"
+// A minimal class written by hand.
+.version 52 0
+.class public super Hello
+.super java/lang/Object
+
+.method public static main ([Ljava/lang/String;)V {
+    .code stack 2 locals 2 {
+        iconst_3
+        istore_1
+    loop:
+        getstatic Field java/lang/System out Ljava/io/PrintStream;
+        ldc String "Hello, World!"
+        invokevirtual Method java/io/PrintStream println (Ljava/lang/String;)V
+        iinc 1 -1
+        iload_1
+        ifne loop
+        return
+        .stackmaptable {
+            append loop { int }
+        }
+    }
+}
+"#;
+
+#[test]
+fn should_assemble_hand_written_source() {
+    // When
+    let class = jasm::assemble(HELLO).unwrap();
+
+    // Then
+    assert_eq!("Hello", class.this_class_name());
+    assert_eq!(Some(&"java/lang/Object".to_string()), class.super_class_name());
+    let main = class.find_method("main").unwrap();
+    let code = main.attrs.code().unwrap();
+    assert_eq!(2, code.max_stack);
+    // ifne at pc 14 branches back to the label at pc 2.
+    assert_eq!(vec![0x9a, 0xff, 0xf4, 0xb1], &code.code[14..]);
+}
+
+#[test]
+fn should_report_undefined_labels() {
+    // Given
+    let source = ".class Broken\n.method m ()V {\n  .code stack 0 locals 0 {\n    goto \
+                  nowhere\n  }\n}\n";
+
+    // When
+    let error = jasm::assemble(source).unwrap_err();
+
+    // Then
+    assert_eq!(4, error.line);
+    assert_eq!("undefined label 'nowhere'", error.message);
+}
+
+#[test]
+fn should_reject_gaps_in_declared_constants() {
+    // Given
+    let source = ".const #1 = Utf8 A\n.const #3 = Utf8 B\n.class A\n";
+
+    // When
+    let error = jasm::assemble(source).unwrap_err();
+
+    // Then
+    assert_eq!(2, error.line);
+}
@@ -0,0 +1,59 @@
+extern crate classfile;
+extern crate jasm;
+
+use std::fs::File;
+use std::io::Read;
+
+use classfile::reader::ClassReader;
+use classfile::writer::ClassWriter;
+
+fn assert_round_trip(path: &str) {
+    // Given
+    let mut bytes: Vec<u8> = vec![];
+    File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+    let class = ClassReader::new(&bytes[..]).read_class().unwrap();
+
+    // When
+    let mut text: Vec<u8> = vec![];
+    jasm::disassemble(&class, &mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    let assembled = jasm::assemble(&text).unwrap();
+    let mut writer = ClassWriter::new(Vec::new());
+    writer.write_class(&assembled).unwrap();
+
+    // Then
+    assert!(bytes == writer.into_inner(),
+            "{} did not round trip:\n{}",
+            path,
+            text);
+}
+
+#[test]
+fn should_round_trip_hello_world() {
+    assert_round_trip("../test-classes/HelloWorld.class");
+}
+
+#[test]
+fn should_round_trip_constants() {
+    assert_round_trip("../test-classes/Constants.class");
+}
+
+#[test]
+fn should_round_trip_point() {
+    assert_round_trip("../test-classes/Point.class");
+}
+
+#[test]
+fn should_round_trip_features() {
+    assert_round_trip("../test-classes/Features.class");
+}
+
+#[test]
+fn should_round_trip_inner_class() {
+    assert_round_trip("../test-classes/Features$Inner.class");
+}
+
+#[test]
+fn should_round_trip_annotation() {
+    assert_round_trip("../test-classes/Features$Marker.class");
+}
@@ -22,6 +22,10 @@ impl Formatter {
 pub struct Options<'a> {
     pub verbose: bool,
     pub constants: &'a ConstantPool,
+    /// The class whose members are being printed, if any.
+    pub class: Option<&'a ClassFile>,
+    /// The method whose attributes are being printed, if any.
+    pub method: Option<&'a MethodInfo>,
 }
 
 pub trait Disassemble {
@@ -47,11 +51,20 @@ impl Disassemble for ClassFile {
         } else {
             "class"
         };
-        try!(write!(fmt.out, "{}{} {}", access_mode, class_type, class_name));
-        if let Some(super_class_name) = self.super_class_name() {
-            if !(super_class_name == "java/lang/Object") {
-                let super_class_name = super_class_name.replace("/", ".");
-                try!(write!(fmt.out, " extends {} ", super_class_name));
+        if let Some(Ok(signature)) = self.signature() {
+            try!(write!(fmt.out,
+                        "{}{} {} ",
+                        access_mode,
+                        class_type,
+                        signature.java_declaration(&class_name,
+                                                   self.access_flags.is_interface())));
+        } else {
+            try!(write!(fmt.out, "{}{} {}", access_mode, class_type, class_name));
+            if let Some(super_class_name) = self.super_class_name() {
+                if !(super_class_name == "java/lang/Object") {
+                    let super_class_name = super_class_name.replace("/", ".");
+                    try!(write!(fmt.out, " extends {} ", super_class_name));
+                }
             }
         }
         if opts.verbose {
@@ -62,7 +75,8 @@ impl Disassemble for ClassFile {
             try!(self.constants.pretty_print(fmt, opts));
         }
         try!(write!(fmt.out, "{{\n"));
-        try!(self.methods.pretty_print(fmt, opts));
+        let opts = Options { class: Some(self), ..*opts };
+        try!(self.methods.pretty_print(fmt, &opts));
         try!(write!(fmt.out, "}}"));
         Ok(())
     }
@@ -240,6 +254,11 @@ impl Disassemble for AttributeInfo {
             AttributeInfo::SourceFile(_) => {
                 try!(write!(fmt.out, "SourceFile"));
             }
+            AttributeInfo::Signature(signature_index) => {
+                let signature = opts.constants[signature_index].as_utf8();
+                let line = format!("    Signature: #{:<27}// {}", signature_index, signature);
+                try!(write!(fmt.out, "{}\n", line));
+            }
             AttributeInfo::AnnotationDefault(ref element_value) => {
                 try!(write!(fmt.out, "AnnotationDefault:\n"));
                 try!(write!(fmt.out, "  default_value: "));
@@ -312,15 +331,42 @@ impl Disassemble for MethodInfo {
             ""
         };
         let method_name = opts.constants[self.name_index].as_utf8();
-        try!(write!(fmt.out, "{}{} {};\n", access_mode, scope, method_name));
         let method_descriptor = opts.constants[self.descriptor_index].as_utf8();
+        let is_initializer = method_name == "<init>" || method_name == "<clinit>";
+        // Constructors are declared with the name of their class.
+        let name = match opts.class {
+            Some(class) if method_name == "<init>" => class.this_class_name().replace("/", "."),
+            _ => method_name.to_string(),
+        };
+        let declaration = match self.signature(opts.constants) {
+            Some(Ok(signature)) => signature.java_declaration(&name, is_initializer),
+            _ => {
+                match MethodDescriptor::parse(method_descriptor) {
+                    Ok(descriptor) => {
+                        let ret = match descriptor.ret {
+                            _ if is_initializer => String::new(),
+                            Some(ref ret) => format!("{} ", ret.java_name()),
+                            None => "void ".to_string(),
+                        };
+                        let params: Vec<String> = descriptor.params
+                            .iter()
+                            .map(|param| param.java_name())
+                            .collect();
+                        format!("{}{}({})", ret, name, params.join(", "))
+                    }
+                    Err(_) => name,
+                }
+            }
+        };
+        try!(write!(fmt.out, "{}{} {};\n", access_mode, scope, declaration));
         if opts.verbose {
             try!(write!(fmt.out, "    descriptor: {}\n", method_descriptor));
             try!(write!(fmt.out, "    flags: "));
             try!(self.access_flags.pretty_print(fmt, opts));
             try!(write!(fmt.out, "\n"));
+            let opts = Options { method: Some(self), ..*opts };
             for attr in self.attrs.iter() {
-                try!(attr.pretty_print(fmt, opts));
+                try!(attr.pretty_print(fmt, &opts));
             }
         }
         Ok(())
@@ -389,20 +435,28 @@ impl Disassemble for ElementValue {
 
 impl Disassemble for CodeAttribute {
     fn pretty_print(&self, fmt: &mut Formatter, opts: &Options) -> io::Result<()> {
+        let args_size = match opts.method.map(|method| method.arg_slots(opts.constants)) {
+            Some(Ok(args_size)) => args_size.to_string(),
+            _ => "?".to_string(),
+        };
         try!(write!(fmt.out,
                     "      stack={}, locals={}, args_size={}\n",
                     self.max_stack,
                     self.max_locals,
-                    "TODO!"));
-        let length = self.code.len();
-        let mut pc: usize = 0;
-        while pc < length {
-            let result = Bytecode::decode(&self.code, pc);
+                    args_size));
+        for instruction in self.instructions() {
+            let (pc, bytecode) = match instruction {
+                Ok(instruction) => instruction,
+                Err(err) => {
+                    // The rest of the code cannot be decoded reliably.
+                    try!(write!(fmt.out, "      // {}\n", err));
+                    break;
+                }
+            };
             try!(write!(fmt.out, "{:>10}: ", pc));
-            let line = disassemble_bytecode(&result.bytecode, pc, opts);
+            let line = disassemble_bytecode(&bytecode, pc, opts);
             try!(write!(fmt.out, "{}", line.trim()));
             try!(write!(fmt.out, "\n"));
-            pc = result.newpc;
         }
         for attr in self.attrs.iter() {
             try!(attr.pretty_print(fmt, opts));
@@ -496,8 +550,30 @@ struct BytecodeFormat {
     detail: Option<String>,
 }
 
+/// The absolute target of a branch that has a single target.
+fn branch_target(bytecode: &Bytecode, pc: usize) -> usize {
+    bytecode.branch_targets(pc)[0]
+}
+
+/// Formats a switch as javap does, with one line per case followed by the
+/// default. `targets` is the default target followed by the case targets.
+fn switch_arg<I>(op: &str, summary: String, keys: I, targets: Vec<usize>) -> BytecodeFormat
+    where I: Iterator<Item = String>
+{
+    let mut cases = String::new();
+    for (key, target) in keys.zip(targets[1..].iter()) {
+        cases.push_str(&format!("{:>24}: {}\n", key, target));
+    }
+    cases.push_str(&format!("{:>24}: {}\n", "default", targets[0]));
+    cases.push_str(&format!("{:>13}", "}"));
+    BytecodeFormat {
+        op: op.into(),
+        arg: Some(format!("{{ // {}\n{}", summary, cases)),
+        detail: None,
+    }
+}
+
 fn disassemble_bytecode(bytecode: &Bytecode, pc: usize, opts: &Options) -> String {
-    let pc = pc as u32;
     let format = match *bytecode {
         Bytecode::nop => no_arg_bytecode!("nop"),
         Bytecode::aconst_null => no_arg_bytecode!("aconst_null"),
@@ -619,25 +695,34 @@ fn disassemble_bytecode(bytecode: &Bytecode, pc: usize, opts: &Options) -> Strin
         Bytecode::fcmpg => no_arg_bytecode!("fcmpg"),
         Bytecode::dcmpl => no_arg_bytecode!("dcmpl"),
         Bytecode::dcmpg => no_arg_bytecode!("dcmpg"),
-        Bytecode::ifeq { branchoffset } => simple_arg("ifeq", pc + branchoffset as u32),
-        Bytecode::ifne { branchoffset } => simple_arg("ifne", pc + branchoffset as u32),
-        Bytecode::iflt { branchoffset } => simple_arg("iflt", pc + branchoffset as u32),
-        Bytecode::ifge { branchoffset } => simple_arg("ifge", pc + branchoffset as u32),
-        Bytecode::ifgt { branchoffset } => simple_arg("ifgt", pc + branchoffset as u32),
-        Bytecode::ifle { branchoffset } => simple_arg("ifle", pc + branchoffset as u32),
-        Bytecode::if_icmpeq { branchoffset } => simple_arg("if_icmpeq", pc + branchoffset as u32),
-        Bytecode::if_icmpne { branchoffset } => simple_arg("if_icmpne", pc + branchoffset as u32),
-        Bytecode::if_icmplt { branchoffset } => simple_arg("if_icmplt", pc + branchoffset as u32),
-        Bytecode::if_icmpge { branchoffset } => simple_arg("if_icmpge", pc + branchoffset as u32),
-        Bytecode::if_icmpgt { branchoffset } => simple_arg("if_icmpgt", pc + branchoffset as u32),
-        Bytecode::if_icmple { branchoffset } => simple_arg("if_icmple", pc + branchoffset as u32),
-        Bytecode::if_acmpeq { branchoffset } => simple_arg("if_acmpeq", pc + branchoffset as u32),
-        Bytecode::if_acmpne { branchoffset } => simple_arg("if_acmpne", pc + branchoffset as u32),
-        Bytecode::goto { branchoffset } => simple_arg("goto", pc + branchoffset as u32),
-        Bytecode::jsr { branchoffset } => simple_arg("jsr", pc + branchoffset as u32),
+        Bytecode::ifeq { .. } => simple_arg("ifeq", branch_target(bytecode, pc)),
+        Bytecode::ifne { .. } => simple_arg("ifne", branch_target(bytecode, pc)),
+        Bytecode::iflt { .. } => simple_arg("iflt", branch_target(bytecode, pc)),
+        Bytecode::ifge { .. } => simple_arg("ifge", branch_target(bytecode, pc)),
+        Bytecode::ifgt { .. } => simple_arg("ifgt", branch_target(bytecode, pc)),
+        Bytecode::ifle { .. } => simple_arg("ifle", branch_target(bytecode, pc)),
+        Bytecode::if_icmpeq { .. } => simple_arg("if_icmpeq", branch_target(bytecode, pc)),
+        Bytecode::if_icmpne { .. } => simple_arg("if_icmpne", branch_target(bytecode, pc)),
+        Bytecode::if_icmplt { .. } => simple_arg("if_icmplt", branch_target(bytecode, pc)),
+        Bytecode::if_icmpge { .. } => simple_arg("if_icmpge", branch_target(bytecode, pc)),
+        Bytecode::if_icmpgt { .. } => simple_arg("if_icmpgt", branch_target(bytecode, pc)),
+        Bytecode::if_icmple { .. } => simple_arg("if_icmple", branch_target(bytecode, pc)),
+        Bytecode::if_acmpeq { .. } => simple_arg("if_acmpeq", branch_target(bytecode, pc)),
+        Bytecode::if_acmpne { .. } => simple_arg("if_acmpne", branch_target(bytecode, pc)),
+        Bytecode::goto { .. } => simple_arg("goto", branch_target(bytecode, pc)),
+        Bytecode::jsr { .. } => simple_arg("jsr", branch_target(bytecode, pc)),
         Bytecode::ret { index } => simple_arg("ret", index),
-        Bytecode::tableswitch { .. } => no_arg_bytecode!("tableswitch"),
-        Bytecode::lookupswitch { .. } => no_arg_bytecode!("lookupswitch"),
+        Bytecode::tableswitch { low, high, .. } => {
+            let keys = (low as i64..high as i64 + 1).map(|key| key.to_string());
+            switch_arg("tableswitch",
+                       format!("{} to {}", low, high),
+                       keys,
+                       bytecode.branch_targets(pc))
+        }
+        Bytecode::lookupswitch { npairs, ref pairs, .. } => {
+            let keys = pairs.iter().map(|&(key, _)| key.to_string());
+            switch_arg("lookupswitch", npairs.to_string(), keys, bytecode.branch_targets(pc))
+        }
         Bytecode::ireturn => no_arg_bytecode!("ireturn"),
         Bytecode::lreturn => no_arg_bytecode!("lreturn"),
         Bytecode::freturn => no_arg_bytecode!("freturn"),
@@ -693,11 +778,13 @@ fn disassemble_bytecode(bytecode: &Bytecode, pc: usize, opts: &Options) -> Strin
                 detail: constant_arg_detail(index, opts),
             }
         }
-        Bytecode::ifnull { branchoffset } => simple_arg("ifnull", pc + branchoffset as u32),
-        Bytecode::ifnonnull { branchoffset } => simple_arg("ifnonnull", pc + branchoffset as u32),
-        Bytecode::goto_w { .. } => no_arg_bytecode!("goto_w"),
-        Bytecode::jsr_w { .. } => no_arg_bytecode!("jsr_w"),
-        Bytecode::invalid(op) => no_arg_bytecode!(invalid_ @ op),
+        Bytecode::ifnull { .. } => simple_arg("ifnull", branch_target(bytecode, pc)),
+        Bytecode::ifnonnull { .. } => simple_arg("ifnonnull", branch_target(bytecode, pc)),
+        Bytecode::goto_w { .. } => simple_arg("goto_w", branch_target(bytecode, pc)),
+        Bytecode::jsr_w { .. } => simple_arg("jsr_w", branch_target(bytecode, pc)),
+        Bytecode::breakpoint => no_arg_bytecode!("breakpoint"),
+        Bytecode::impdep1 => no_arg_bytecode!("impdep1"),
+        Bytecode::impdep2 => no_arg_bytecode!("impdep2"),
     };
     let arg_string = format.arg.unwrap_or(String::new());
     let comment_string = format.detail.map_or(String::new(), |s| format!("// {}", s));
@@ -1,4 +1,5 @@
 extern crate classfile;
+extern crate classpath;
 extern crate javap;
 #[macro_use]
 extern crate env_logger;
@@ -7,18 +8,33 @@ use std::fs::File;
 use std::env;
 
 use classfile::reader::ClassReader;
+use classpath::ClassPath;
 use javap::{Disassemble, Formatter, Options};
 
 fn main() {
     env_logger::init().unwrap();
     let verbose = true;
-    let class_filename = env::args().nth(1).expect("usage: class_reader <class file>");
-    let class_file = File::open(class_filename).unwrap();
+    let usage = "usage: javap [-classpath <path>] <class file or class name>";
+    let mut args = env::args().skip(1);
+    let mut classpath = None;
+    let mut target = None;
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "-classpath" | "-cp" => classpath = Some(args.next().expect(usage)),
+            _ => target = Some(arg),
+        }
+    }
+    let target = target.expect(usage);
     let mut fmt = Formatter::new();
-    let class = ClassReader::new(class_file).read_class().unwrap();
+    let class = match classpath {
+        Some(paths) => ClassPath::parse(&paths).unwrap().load(&target.replace('.', "/")).unwrap(),
+        None => ClassReader::new(File::open(target).unwrap()).read_class().unwrap(),
+    };
     let opts = Options {
         verbose: verbose,
         constants: &class.constants,
+        class: None,
+        method: None,
     };
     class.pretty_print(&mut fmt, &opts).unwrap();
 }
@@ -44,6 +44,8 @@ fn parse_all_files_in_rt_jar() {
         let opts = Options {
             verbose: true,
             constants: &classfile.constants,
+            class: None,
+            method: None,
         };
         classfile.pretty_print(&mut fmt, &opts).unwrap();
     }
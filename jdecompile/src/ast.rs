@@ -0,0 +1,684 @@
+//! The Java syntax tree that decompiled methods are built into.
+//!
+//! Expressions and statements keep class names in internal form (for example
+//! `java/lang/String`); the printer decides how to spell them. Local
+//! variables are numbered `VarId`s into the `Variable` table of the method
+//! they belong to.
+use classfile::{FieldType, MethodDescriptor, TypeSignature};
+
+/// An index into the variable table of a method body.
+pub type VarId = usize;
+
+/// A local variable, parameter or temporary of a method.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub ty: TypeSignature,
+    /// Whether the variable needs no declaration in the body, as for
+    /// parameters, catch parameters and lambda parameters.
+    pub declared: bool,
+    /// Whether the variable was made up by the decompiler or the compiler,
+    /// rather than named in the `LocalVariableTable`, so that passes may
+    /// remove it.
+    pub synthetic: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Boolean(bool),
+    Char(u16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    /// A class literal such as `String.class` or `int[].class`.
+    Class(FieldType),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    UShr,
+    And,
+    Or,
+    Xor,
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+    LogicalAnd,
+    LogicalOr,
+}
+
+impl BinaryOp {
+    /// The operator as written in Java source.
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::UShr => ">>>",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::LogicalAnd => "&&",
+            BinaryOp::LogicalOr => "||",
+        }
+    }
+
+    /// Returns `true` for the six comparison operators.
+    pub fn is_comparison(&self) -> bool {
+        match *self {
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Gt |
+            BinaryOp::Le => true,
+            _ => false,
+        }
+    }
+
+    /// The comparison that holds exactly when this one does not.
+    pub fn negated(&self) -> Option<BinaryOp> {
+        match *self {
+            BinaryOp::Eq => Some(BinaryOp::Ne),
+            BinaryOp::Ne => Some(BinaryOp::Eq),
+            BinaryOp::Lt => Some(BinaryOp::Ge),
+            BinaryOp::Ge => Some(BinaryOp::Lt),
+            BinaryOp::Gt => Some(BinaryOp::Le),
+            BinaryOp::Le => Some(BinaryOp::Gt),
+            _ => None,
+        }
+    }
+}
+
+/// The receiver of a method call, field access or method reference.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /// A static member of the named class.
+    Static(String),
+    /// An instance member of the value of an expression.
+    Instance(Box<Expr>),
+    /// A member of the superclass of `this`, such as `super.toString()`.
+    Super,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    Local(VarId),
+    This,
+    /// An object that has been allocated but whose constructor has not run
+    /// yet. Never printed; it becomes a `New` once the constructor is called.
+    Uninitialized(String),
+    /// A raw comparison of two `long`, `float` or `double` values, as pushed
+    /// by `lcmp` and friends, with the result it has if either is NaN. Never
+    /// printed; the branch consuming it turns it into a `Binary` comparison.
+    Compare(i32, Box<Expr>, Box<Expr>),
+    Field {
+        target: Target,
+        name: String,
+        descriptor: FieldType,
+    },
+    ArrayElement(Box<Expr>, Box<Expr>),
+    ArrayLength(Box<Expr>),
+    Invoke {
+        target: Target,
+        name: String,
+        descriptor: MethodDescriptor,
+        args: Vec<Expr>,
+    },
+    /// A call to a constructor of `this` or its superclass, as the first
+    /// statement of a constructor.
+    ConstructorCall {
+        is_super: bool,
+        descriptor: MethodDescriptor,
+        args: Vec<Expr>,
+    },
+    New {
+        class: String,
+        descriptor: MethodDescriptor,
+        args: Vec<Expr>,
+    },
+    /// `new int[n][m][]`: the array type and the given dimensions.
+    NewArray {
+        ty: FieldType,
+        dimensions: Vec<Expr>,
+    },
+    /// `new int[] {1, 2, 3}`.
+    ArrayInit {
+        ty: FieldType,
+        elements: Vec<Expr>,
+    },
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Cast(FieldType, Box<Expr>),
+    InstanceOf(Box<Expr>, FieldType),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    CompoundAssign(BinaryOp, Box<Expr>, Box<Expr>),
+    /// `++x`, `x++`, `--x` or `x--`.
+    Increment {
+        target: Box<Expr>,
+        prefix: bool,
+        decrement: bool,
+    },
+    /// A string concatenation of all operands with `+`.
+    Concat(Vec<Expr>),
+    Lambda {
+        params: Vec<VarId>,
+        body: Vec<Stmt>,
+    },
+    /// `Owner::name`, `expr::name` or `Owner::new`.
+    MethodRef {
+        target: Target,
+        name: String,
+    },
+    /// An `invokedynamic` call site with a bootstrap method that is not
+    /// understood.
+    InvokeDynamic {
+        name: String,
+        args: Vec<Expr>,
+    },
+}
+
+impl Expr {
+    pub fn int(value: i32) -> Expr {
+        Expr::Literal(Literal::Int(value))
+    }
+
+    pub fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        Expr::Binary(op, Box::new(left), Box::new(right))
+    }
+
+    /// The logical negation of this expression, flipping comparisons rather
+    /// than wrapping them in `!` where possible.
+    pub fn negate(self) -> Expr {
+        match self {
+            Expr::Binary(op, left, right) => {
+                match op {
+                    BinaryOp::LogicalAnd => {
+                        Expr::binary(BinaryOp::LogicalOr, left.negate(), right.negate())
+                    }
+                    BinaryOp::LogicalOr => {
+                        Expr::binary(BinaryOp::LogicalAnd, left.negate(), right.negate())
+                    }
+                    _ => {
+                        match op.negated() {
+                            Some(negated) => Expr::Binary(negated, left, right),
+                            None => {
+                                Expr::Unary(UnaryOp::Not, Box::new(Expr::Binary(op, left, right)))
+                            }
+                        }
+                    }
+                }
+            }
+            Expr::Unary(UnaryOp::Not, operand) => *operand,
+            Expr::Literal(Literal::Boolean(value)) => Expr::Literal(Literal::Boolean(!value)),
+            other => Expr::Unary(UnaryOp::Not, Box::new(other)),
+        }
+    }
+
+    /// Returns `true` if evaluating the expression has no side effects and
+    /// its value cannot be changed by other code, so that it may be
+    /// evaluated more than once or later than written.
+    pub fn is_simple(&self) -> bool {
+        match *self {
+            Expr::Literal(_) | Expr::Local(_) | Expr::This => true,
+            _ => false,
+        }
+    }
+
+    /// The operands of the expression, in evaluation order.
+    pub fn children(&self) -> Vec<&Expr> {
+        let mut children = vec![];
+        match *self {
+            Expr::Literal(_) | Expr::Local(_) | Expr::This | Expr::Uninitialized(_) => {}
+            Expr::Compare(_, ref left, ref right) |
+            Expr::ArrayElement(ref left, ref right) |
+            Expr::Binary(_, ref left, ref right) |
+            Expr::Assign(ref left, ref right) |
+            Expr::CompoundAssign(_, ref left, ref right) => {
+                children.push(&**left);
+                children.push(&**right);
+            }
+            Expr::Field { ref target, .. } |
+            Expr::MethodRef { ref target, .. } => {
+                if let Target::Instance(ref object) = *target {
+                    children.push(&**object);
+                }
+            }
+            Expr::Invoke { ref target, ref args, .. } => {
+                if let Target::Instance(ref object) = *target {
+                    children.push(&**object);
+                }
+                children.extend(args);
+            }
+            Expr::ConstructorCall { ref args, .. } |
+            Expr::New { ref args, .. } |
+            Expr::InvokeDynamic { ref args, .. } => children.extend(args),
+            Expr::NewArray { ref dimensions, .. } => children.extend(dimensions),
+            Expr::ArrayInit { ref elements, .. } => children.extend(elements),
+            Expr::Concat(ref operands) => children.extend(operands),
+            Expr::ArrayLength(ref operand) |
+            Expr::Unary(_, ref operand) |
+            Expr::Cast(_, ref operand) |
+            Expr::InstanceOf(ref operand, _) |
+            Expr::Increment { target: ref operand, .. } => children.push(&**operand),
+            Expr::Conditional(ref condition, ref then, ref otherwise) => {
+                children.push(&**condition);
+                children.push(&**then);
+                children.push(&**otherwise);
+            }
+            Expr::Lambda { .. } => {}
+        }
+        children
+    }
+
+    /// Mutable access to the operands of the expression, in evaluation
+    /// order. The bodies of lambdas are not included.
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        let mut children = vec![];
+        match *self {
+            Expr::Literal(_) | Expr::Local(_) | Expr::This | Expr::Uninitialized(_) => {}
+            Expr::Compare(_, ref mut left, ref mut right) |
+            Expr::ArrayElement(ref mut left, ref mut right) |
+            Expr::Binary(_, ref mut left, ref mut right) |
+            Expr::Assign(ref mut left, ref mut right) |
+            Expr::CompoundAssign(_, ref mut left, ref mut right) => {
+                children.push(&mut **left);
+                children.push(&mut **right);
+            }
+            Expr::Field { ref mut target, .. } |
+            Expr::MethodRef { ref mut target, .. } => {
+                if let Target::Instance(ref mut object) = *target {
+                    children.push(&mut **object);
+                }
+            }
+            Expr::Invoke { ref mut target, ref mut args, .. } => {
+                if let Target::Instance(ref mut object) = *target {
+                    children.push(&mut **object);
+                }
+                children.extend(args.iter_mut());
+            }
+            Expr::ConstructorCall { ref mut args, .. } |
+            Expr::New { ref mut args, .. } |
+            Expr::InvokeDynamic { ref mut args, .. } => children.extend(args.iter_mut()),
+            Expr::NewArray { ref mut dimensions, .. } => children.extend(dimensions.iter_mut()),
+            Expr::ArrayInit { ref mut elements, .. } => children.extend(elements.iter_mut()),
+            Expr::Concat(ref mut operands) => children.extend(operands.iter_mut()),
+            Expr::ArrayLength(ref mut operand) |
+            Expr::Unary(_, ref mut operand) |
+            Expr::Cast(_, ref mut operand) |
+            Expr::InstanceOf(ref mut operand, _) |
+            Expr::Increment { target: ref mut operand, .. } => children.push(&mut **operand),
+            Expr::Conditional(ref mut condition, ref mut then, ref mut otherwise) => {
+                children.push(&mut **condition);
+                children.push(&mut **then);
+                children.push(&mut **otherwise);
+            }
+            Expr::Lambda { .. } => {}
+        }
+        children
+    }
+
+    /// Calls `f` on this expression and every expression inside it,
+    /// including those in the bodies of lambdas, parents before children.
+    pub fn walk(&self, f: &mut FnMut(&Expr)) {
+        f(self);
+        if let Expr::Lambda { ref body, .. } = *self {
+            for stmt in body {
+                stmt.walk_exprs(f);
+            }
+        }
+        for child in self.children() {
+            child.walk(f);
+        }
+    }
+
+    /// Rewrites the expression bottom-up: `f` is called on each expression
+    /// after its operands have been rewritten, including inside lambdas.
+    pub fn rewrite(&mut self, f: &mut FnMut(&mut Expr)) {
+        if let Expr::Lambda { ref mut body, .. } = *self {
+            for stmt in body.iter_mut() {
+                stmt.rewrite_exprs(f);
+            }
+        }
+        for child in self.children_mut() {
+            child.rewrite(f);
+        }
+        f(self);
+    }
+
+    /// The number of times variable `var` is read or written inside the
+    /// expression.
+    pub fn count_var(&self, var: VarId) -> usize {
+        let mut count = 0;
+        self.walk(&mut |expr| if *expr == Expr::Local(var) {
+            count += 1;
+        });
+        count
+    }
+}
+
+/// The keys of one `case` group of a switch, with `None` for `default`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwitchCase {
+    pub keys: Vec<Option<i32>>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Catch {
+    /// The internal names of the caught classes; more than one for a
+    /// multi-catch.
+    pub types: Vec<String>,
+    pub var: VarId,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoopKind {
+    /// `while (true)`
+    Infinite,
+    While(Expr),
+    DoWhile(Expr),
+    For {
+        init: Option<Box<Stmt>>,
+        condition: Expr,
+        update: Vec<Expr>,
+    },
+}
+
+/// Labels are numbered per method; the printer only names the ones that a
+/// `break` or `continue` needs.
+pub type Label = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+    Expr(Expr),
+    Declare(VarId, Option<Expr>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Loop {
+        label: Label,
+        kind: LoopKind,
+        body: Vec<Stmt>,
+    },
+    /// A labeled block, `label: { ... }`, that `break` can leave.
+    Block {
+        label: Label,
+        body: Vec<Stmt>,
+    },
+    Switch {
+        label: Label,
+        value: Expr,
+        cases: Vec<SwitchCase>,
+    },
+    Try {
+        body: Vec<Stmt>,
+        catches: Vec<Catch>,
+        finally: Option<Vec<Stmt>>,
+    },
+    Synchronized(Expr, Vec<Stmt>),
+    MonitorEnter(Expr),
+    MonitorExit(Expr),
+    Break(Label),
+    Continue(Label),
+    Return(Option<Expr>),
+    Throw(Expr),
+}
+
+impl Stmt {
+    /// The statement lists nested directly inside this statement.
+    pub fn bodies(&self) -> Vec<&Vec<Stmt>> {
+        match *self {
+            Stmt::If(_, ref then, ref otherwise) => vec![then, otherwise],
+            Stmt::Loop { ref body, .. } |
+            Stmt::Block { ref body, .. } |
+            Stmt::Synchronized(_, ref body) => vec![body],
+            Stmt::Switch { ref cases, .. } => cases.iter().map(|case| &case.body).collect(),
+            Stmt::Try { ref body, ref catches, ref finally } => {
+                let mut bodies = vec![body];
+                bodies.extend(catches.iter().map(|catch| &catch.body));
+                bodies.extend(finally.iter());
+                bodies
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn bodies_mut(&mut self) -> Vec<&mut Vec<Stmt>> {
+        match *self {
+            Stmt::If(_, ref mut then, ref mut otherwise) => vec![then, otherwise],
+            Stmt::Loop { ref mut body, .. } |
+            Stmt::Block { ref mut body, .. } |
+            Stmt::Synchronized(_, ref mut body) => vec![body],
+            Stmt::Switch { ref mut cases, .. } => {
+                cases.iter_mut().map(|case| &mut case.body).collect()
+            }
+            Stmt::Try { ref mut body, ref mut catches, ref mut finally } => {
+                let mut bodies = vec![body];
+                bodies.extend(catches.iter_mut().map(|catch| &mut catch.body));
+                bodies.extend(finally.iter_mut());
+                bodies
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The expressions that belong to this statement itself, not to the
+    /// statements nested inside it.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match *self {
+            Stmt::Expr(ref expr) |
+            Stmt::If(ref expr, _, _) |
+            Stmt::Switch { value: ref expr, .. } |
+            Stmt::Synchronized(ref expr, _) |
+            Stmt::MonitorEnter(ref expr) |
+            Stmt::MonitorExit(ref expr) |
+            Stmt::Throw(ref expr) |
+            Stmt::Declare(_, Some(ref expr)) |
+            Stmt::Return(Some(ref expr)) => vec![expr],
+            Stmt::Loop { ref kind, .. } => {
+                match *kind {
+                    LoopKind::Infinite => vec![],
+                    LoopKind::While(ref condition) |
+                    LoopKind::DoWhile(ref condition) => vec![condition],
+                    LoopKind::For { ref init, ref condition, ref update } => {
+                        let mut exprs = vec![];
+                        if let Some(ref init) = *init {
+                            exprs.extend(init.exprs());
+                        }
+                        exprs.push(condition);
+                        exprs.extend(update);
+                        exprs
+                    }
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr> {
+        match *self {
+            Stmt::Expr(ref mut expr) |
+            Stmt::If(ref mut expr, _, _) |
+            Stmt::Switch { value: ref mut expr, .. } |
+            Stmt::Synchronized(ref mut expr, _) |
+            Stmt::MonitorEnter(ref mut expr) |
+            Stmt::MonitorExit(ref mut expr) |
+            Stmt::Throw(ref mut expr) |
+            Stmt::Declare(_, Some(ref mut expr)) |
+            Stmt::Return(Some(ref mut expr)) => vec![expr],
+            Stmt::Loop { ref mut kind, .. } => {
+                match *kind {
+                    LoopKind::Infinite => vec![],
+                    LoopKind::While(ref mut condition) |
+                    LoopKind::DoWhile(ref mut condition) => vec![condition],
+                    LoopKind::For { ref mut init, ref mut condition, ref mut update } => {
+                        let mut exprs = vec![];
+                        if let Some(ref mut init) = *init {
+                            exprs.extend(init.exprs_mut());
+                        }
+                        exprs.push(condition);
+                        exprs.extend(update.iter_mut());
+                        exprs
+                    }
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Calls `f` on every expression in this statement and the statements
+    /// nested inside it.
+    pub fn walk_exprs(&self, f: &mut FnMut(&Expr)) {
+        for expr in self.exprs() {
+            expr.walk(f);
+        }
+        for body in self.bodies() {
+            for stmt in body {
+                stmt.walk_exprs(f);
+            }
+        }
+    }
+
+    /// Rewrites every expression in this statement and the statements nested
+    /// inside it with `Expr::rewrite`.
+    pub fn rewrite_exprs(&mut self, f: &mut FnMut(&mut Expr)) {
+        for expr in self.exprs_mut() {
+            expr.rewrite(f);
+        }
+        for body in self.bodies_mut() {
+            for stmt in body.iter_mut() {
+                stmt.rewrite_exprs(f);
+            }
+        }
+    }
+
+    /// Calls `f` on this statement and every statement nested inside it,
+    /// including those in the bodies of lambdas.
+    pub fn walk(&self, f: &mut FnMut(&Stmt)) {
+        f(self);
+        for body in self.bodies() {
+            for stmt in body {
+                stmt.walk(f);
+            }
+        }
+        for expr in self.exprs() {
+            walk_lambdas(expr, f);
+        }
+    }
+
+    /// The number of times variable `var` is read or written in the
+    /// statement, counting declarations.
+    pub fn count_var(&self, var: VarId) -> usize {
+        let mut count = 0;
+        self.walk(&mut |stmt| if let Stmt::Declare(declared, _) = *stmt {
+            if declared == var {
+                count += 1;
+            }
+        });
+        self.walk_exprs(&mut |expr| if *expr == Expr::Local(var) {
+            count += 1;
+        });
+        count
+    }
+
+    /// Returns `true` if control can never continue with the next
+    /// statement after this one.
+    pub fn is_abrupt(&self) -> bool {
+        match *self {
+            Stmt::Break(_) | Stmt::Continue(_) | Stmt::Return(_) | Stmt::Throw(_) => true,
+            Stmt::If(_, ref then, ref otherwise) => ends_abruptly(then) && ends_abruptly(otherwise),
+            Stmt::Loop { kind: LoopKind::Infinite, ref body, label } => !breaks_to(body, label),
+            Stmt::Block { ref body, label } => ends_abruptly(body) && !breaks_to(body, label),
+            Stmt::Switch { ref cases, label, .. } => {
+                let has_default = cases.iter().any(|case| case.keys.contains(&None));
+                has_default && cases.last().map_or(false, |case| ends_abruptly(&case.body)) &&
+                !cases.iter().any(|case| breaks_to(&case.body, label))
+            }
+            Stmt::Try { ref body, ref catches, ref finally } => {
+                if let Some(ref finally) = *finally {
+                    if ends_abruptly(finally) {
+                        return true;
+                    }
+                }
+                ends_abruptly(body) && catches.iter().all(|catch| ends_abruptly(&catch.body))
+            }
+            Stmt::Synchronized(_, ref body) => ends_abruptly(body),
+            _ => false,
+        }
+    }
+}
+
+/// Walks the statements of the outermost lambdas in `expr`; `Stmt::walk`
+/// takes care of the ones nested inside them.
+fn walk_lambdas(expr: &Expr, f: &mut FnMut(&Stmt)) {
+    if let Expr::Lambda { ref body, .. } = *expr {
+        for stmt in body {
+            stmt.walk(f);
+        }
+        return;
+    }
+    for child in expr.children() {
+        walk_lambdas(child, f);
+    }
+}
+
+/// Returns `true` if control can never fall off the end of `stmts`.
+pub fn ends_abruptly(stmts: &[Stmt]) -> bool {
+    stmts.last().map_or(false, |stmt| stmt.is_abrupt())
+}
+
+/// Returns `true` if a `break` out of the statement labeled `label` occurs in
+/// `stmts`. Lambda bodies are not searched, as they number their labels on
+/// their own.
+pub fn breaks_to(stmts: &[Stmt], label: Label) -> bool {
+    stmts.iter().any(|stmt| {
+        *stmt == Stmt::Break(label) ||
+        stmt.bodies().into_iter().any(|body| breaks_to(body, label))
+    })
+}
+
+/// Returns `true` if a `break` or `continue` naming `label` occurs in `stmts`,
+/// not counting lambda bodies.
+pub fn jumps_to(stmts: &[Stmt], label: Label) -> bool {
+    stmts.iter().any(|stmt| {
+        *stmt == Stmt::Break(label) || *stmt == Stmt::Continue(label) ||
+        stmt.bodies().into_iter().any(|body| jumps_to(body, label))
+    })
+}
+
+/// A decompiled method body together with its variables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Body {
+    pub vars: Vec<Variable>,
+    /// The parameters of the method, without `this`.
+    pub params: Vec<VarId>,
+    pub stmts: Vec<Stmt>,
+}
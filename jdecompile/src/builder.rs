@@ -0,0 +1,2043 @@
+//! Rebuilds expressions and statements from the bytecode of each basic block.
+//!
+//! Each block is run over a symbolic operand stack whose entries are
+//! expressions rather than values: a load pushes the variable, an `iadd`
+//! pops two expressions and pushes their sum, and an instruction with an
+//! effect, such as a store or a `void` call, emits a statement. Entries still
+//! on the stack when a statement is emitted are first saved to temporaries
+//! if the statement could change their value, so that evaluation order is
+//! kept.
+//!
+//! Values left on the stack at the end of a block, as for the arms of a
+//! `?:`, are assigned to temporaries that all successors share; later passes
+//! fold those assignments back into expressions.
+use std::collections::{BTreeSet, HashMap};
+
+use analysis::cfg::ControlFlowGraph;
+use classfile::*;
+
+use ast::*;
+use types;
+use super::{Error, Result};
+
+/// The way control leaves a block.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Exit {
+    Goto(usize),
+    /// Go to the first block if the condition holds, else to the second.
+    If(Expr, usize, usize),
+    Switch(Expr, Vec<(i32, usize)>, usize),
+    Return(Option<Expr>),
+    Throw(Expr),
+}
+
+impl Exit {
+    /// The blocks control may continue to, without duplicates.
+    pub fn successors(&self) -> Vec<usize> {
+        let mut successors = match *self {
+            Exit::Goto(target) => vec![target],
+            Exit::If(_, then, otherwise) => vec![then, otherwise],
+            Exit::Switch(_, ref cases, default) => {
+                let mut targets: Vec<usize> = cases.iter().map(|&(_, target)| target).collect();
+                targets.push(default);
+                targets
+            }
+            Exit::Return(_) | Exit::Throw(_) => vec![],
+        };
+        let mut seen = BTreeSet::new();
+        successors.retain(|&target| seen.insert(target));
+        successors
+    }
+
+    /// Replaces every occurrence of `from` among the targets with `to`.
+    pub fn retarget(&mut self, from: usize, to: usize) {
+        let replace = |target: &mut usize| if *target == from {
+            *target = to;
+        };
+        match *self {
+            Exit::Goto(ref mut target) => replace(target),
+            Exit::If(_, ref mut then, ref mut otherwise) => {
+                replace(then);
+                replace(otherwise);
+            }
+            Exit::Switch(_, ref mut cases, ref mut default) => {
+                for &mut (_, ref mut target) in cases.iter_mut() {
+                    replace(target);
+                }
+                replace(default);
+            }
+            Exit::Return(_) | Exit::Throw(_) => {}
+        }
+    }
+}
+
+/// A basic block rebuilt into statements.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub pc: usize,
+    pub stmts: Vec<Stmt>,
+    pub exit: Exit,
+}
+
+/// An exception handler and the blocks it protects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handler {
+    /// The block the handler starts at.
+    pub node: usize,
+    /// The internal names of the caught classes, in exception table order.
+    pub types: Vec<String>,
+    /// Whether the handler catches everything, as for `finally`.
+    pub catches_any: bool,
+    pub protected: BTreeSet<usize>,
+    /// The variable holding the caught exception on entry to the handler.
+    pub var: VarId,
+}
+
+/// The blocks of a method, rebuilt into statements.
+#[derive(Debug)]
+pub struct Method {
+    pub vars: Vec<Variable>,
+    pub params: Vec<VarId>,
+    /// The blocks by their id in the `ControlFlowGraph`, or `None` for
+    /// blocks that cannot be reached. Block 0 is the entry.
+    pub nodes: Vec<Option<Node>>,
+    /// The handlers in order of their first exception table entry.
+    pub handlers: Vec<Handler>,
+}
+
+/// Rebuilds the blocks of `method`, which must have code.
+pub fn build(class: &ClassFile, method: &MethodInfo, code: &CodeAttribute) -> Result<Method> {
+    let cfg = try!(ControlFlowGraph::new(code));
+    let mut builder = try!(Builder::new(class, method, code, &cfg));
+    builder.run()
+}
+
+/// An expression on the symbolic operand stack.
+#[derive(Clone, Debug, PartialEq)]
+struct Item {
+    expr: Expr,
+    ty: FieldType,
+    /// Identifies the copies of an object under construction, or of an array
+    /// whose elements are being initialized, left on the stack by `dup`.
+    id: Option<usize>,
+}
+
+impl Item {
+    fn new(expr: Expr, ty: FieldType) -> Item {
+        Item {
+            expr: expr,
+            ty: ty,
+            id: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum VarKey<'a> {
+    /// A variable named by the `LocalVariableTable`: its slot, name and
+    /// descriptor.
+    Debug(u16, &'a str, &'a str),
+    /// A variable without debugging information: its slot and the kind of
+    /// value in it, as the first letter of the instruction accessing it.
+    Slot(u16, u8),
+}
+
+/// An entry of the `LocalVariableTable`, with its generic signature from the
+/// `LocalVariableTypeTable` if it has one.
+struct LocalInfo<'a> {
+    index: u16,
+    start: usize,
+    length: usize,
+    name: &'a str,
+    descriptor: &'a str,
+    signature: Option<&'a str>,
+}
+
+struct Builder<'a> {
+    class: &'a ClassFile,
+    cp: &'a ConstantPool,
+    this_class: &'a str,
+    is_static: bool,
+    /// Whether slot 0 of an instance method is ever overwritten, so that it
+    /// cannot be printed as `this`.
+    this_overwritten: bool,
+    code: &'a CodeAttribute,
+    cfg: &'a ControlFlowGraph,
+    locals: Vec<LocalInfo<'a>>,
+    keys: HashMap<VarKey<'a>, VarId>,
+    vars: Vec<Variable>,
+    params: Vec<VarId>,
+    /// A union-find forest over the variables, joining the temporaries that
+    /// carry one stack entry into a block from all its predecessors.
+    parents: Vec<VarId>,
+    /// The temporary carrying each stack entry into a block, by block and
+    /// depth.
+    stack_vars: HashMap<(usize, usize), VarId>,
+    /// The types of the stack entries on entry to each block, once known.
+    entry_types: Vec<Option<Vec<FieldType>>>,
+    /// Objects under construction carried into a block, by block and depth.
+    entry_items: HashMap<(usize, usize), Item>,
+    handlers: Vec<Handler>,
+    stack: Vec<Item>,
+    next_id: usize,
+    temps: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn new(class: &'a ClassFile,
+           method: &'a MethodInfo,
+           code: &'a CodeAttribute,
+           cfg: &'a ControlFlowGraph)
+           -> Result<Builder<'a>> {
+        let cp = &class.constants;
+        let is_static = method.access_flags.is_static();
+        let mut locals = vec![];
+        if let Some(table) = code.attrs.local_variable_table() {
+            let types = code.attrs.local_variable_type_table();
+            for entry in table {
+                let signature = types.and_then(|types| {
+                    types.iter()
+                        .find(|t| t.index == entry.index && t.start_pc == entry.start_pc)
+                        .map(|t| cp[t.signature_index].as_utf8().as_ref())
+                });
+                locals.push(LocalInfo {
+                    index: entry.index,
+                    start: entry.start_pc as usize,
+                    length: entry.length as usize,
+                    name: cp[entry.name_index].as_utf8(),
+                    descriptor: cp[entry.descriptor_index].as_utf8(),
+                    signature: signature,
+                });
+            }
+        }
+        let this_overwritten = !is_static &&
+                               cfg.instructions().iter().any(|&(_, ref insn)| {
+            insn.local_access().map_or(false, |access| access.writes && access.index == 0)
+        });
+        let count = cfg.blocks().len();
+        let mut builder = Builder {
+            class: class,
+            cp: cp,
+            this_class: class.this_class_name(),
+            is_static: is_static,
+            this_overwritten: this_overwritten,
+            code: code,
+            cfg: cfg,
+            locals: locals,
+            keys: HashMap::new(),
+            vars: vec![],
+            params: vec![],
+            parents: vec![],
+            stack_vars: HashMap::new(),
+            entry_types: vec![None; count],
+            entry_items: HashMap::new(),
+            handlers: vec![],
+            stack: vec![],
+            next_id: 0,
+            temps: 0,
+        };
+        try!(builder.declare_params(method));
+        Ok(builder)
+    }
+
+    fn add_var(&mut self,
+               name: String,
+               ty: TypeSignature,
+               declared: bool,
+               synthetic: bool)
+               -> VarId {
+        let id = self.vars.len();
+        self.vars.push(Variable {
+            name: name,
+            ty: ty,
+            declared: declared,
+            synthetic: synthetic,
+        });
+        self.parents.push(id);
+        id
+    }
+
+    /// Creates the parameter variables, named from the debugging
+    /// information or the `MethodParameters` attribute where present, and
+    /// typed from the generic signature when it matches the descriptor.
+    fn declare_params(&mut self, method: &MethodInfo) -> Result<()> {
+        let descriptor = try!(method.descriptor(self.cp));
+        let signature = match method.signature(self.cp) {
+            Some(Ok(signature)) => {
+                if signature.params.len() == descriptor.params.len() {
+                    Some(signature)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let names = method.attrs.method_parameters();
+        let mut slot = if self.is_static { 0 } else { 1 };
+        for (index, param) in descriptor.params.iter().enumerate() {
+            let kind = kind_of(param);
+            let ty = match signature {
+                Some(ref signature) => signature.params[index].clone(),
+                None => types::signature(param),
+            };
+            let local = self.local_info(slot, 0, false, kind);
+            let (key, name) = match local {
+                Some(local) => (VarKey::Debug(slot, local.0, local.1), local.0.to_string()),
+                None => {
+                    let name = names.and_then(|names| names.get(index))
+                        .and_then(|info| match self.cp.get(info.name_index) {
+                            Some(&Constant::Utf8(ref name)) => Some(name.clone()),
+                            _ => None,
+                        })
+                        .unwrap_or_else(|| format!("arg{}", index));
+                    (VarKey::Slot(slot, kind), name)
+                }
+            };
+            let var = self.add_var(name, ty, true, false);
+            self.keys.insert(key, var);
+            self.params.push(var);
+            slot += param.slots();
+        }
+        Ok(())
+    }
+
+    /// Finds the `LocalVariableTable` entry for `slot` at `pc` that holds
+    /// values of the given kind, returning its name and descriptor. A store
+    /// also matches an entry starting right after it, even an empty one.
+    fn local_info(&self,
+                  slot: u16,
+                  pc: usize,
+                  store: bool,
+                  kind: u8)
+                  -> Option<(&'a str, &'a str)> {
+        let compatible = |descriptor: &str| {
+            let first = descriptor.as_bytes()[0];
+            let local_kind = match first {
+                b'L' | b'[' => b'a',
+                b'J' => b'l',
+                b'F' => b'f',
+                b'D' => b'd',
+                _ => b'i',
+            };
+            local_kind == kind
+        };
+        self.locals
+            .iter()
+            .find(|local| {
+                local.index == slot && compatible(local.descriptor) &&
+                ((local.start <= pc && pc < local.start + local.length) ||
+                 (store && local.start == pc))
+            })
+            .map(|local| (local.name, local.descriptor))
+    }
+
+    /// The variable in `slot` at `pc`, creating it on first use. `ty` is the
+    /// type of the value stored, used for variables without debugging
+    /// information.
+    fn var_at(&mut self, slot: u16, pc: usize, store: bool, kind: u8, ty: &FieldType) -> VarId {
+        let key = match self.local_info(slot, pc, store, kind) {
+            Some((name, descriptor)) => VarKey::Debug(slot, name, descriptor),
+            None => VarKey::Slot(slot, kind),
+        };
+        if let Some(&var) = self.keys.get(&key) {
+            return var;
+        }
+        let (name, ty) = match key {
+            VarKey::Debug(_, name, descriptor) => {
+                let signature = self.locals
+                    .iter()
+                    .find(|local| {
+                        local.index == slot && local.name == name &&
+                        local.descriptor == descriptor && local.signature.is_some()
+                    })
+                    .and_then(|local| FieldSignature::parse(local.signature.unwrap()).ok());
+                let ty = match signature {
+                    Some(signature) => signature.field_type,
+                    None => {
+                        FieldType::parse(descriptor)
+                            .map(|ty| types::signature(&ty))
+                            .unwrap_or_else(|_| types::signature(&types::object()))
+                    }
+                };
+                (name.to_string(), ty)
+            }
+            VarKey::Slot(..) => {
+                let ty = match kind {
+                    b'i' if types::is_int_like(ty) => ty.clone(),
+                    b'i' => FieldType::Int,
+                    b'l' => FieldType::Long,
+                    b'f' => FieldType::Float,
+                    b'd' => FieldType::Double,
+                    _ if ty.is_reference() => ty.clone(),
+                    _ => types::object(),
+                };
+                (slot_name(&ty, slot), types::signature(&ty))
+            }
+        };
+        let synthetic = match key {
+            VarKey::Debug(..) => false,
+            VarKey::Slot(..) => true,
+        };
+        let var = self.add_var(name, ty, false, synthetic);
+        self.keys.insert(key, var);
+        var
+    }
+
+    /// A new temporary of type `ty`.
+    fn temp(&mut self, ty: &FieldType) -> VarId {
+        self.temps += 1;
+        let name = format!("tmp{}", self.temps);
+        self.add_var(name, types::signature(ty), false, true)
+    }
+
+    fn find(&mut self, var: VarId) -> VarId {
+        let mut root = var;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut current = var;
+        while self.parents[current] != root {
+            let next = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Joins two temporaries into one, keeping the more specific type.
+    fn union(&mut self, a: VarId, b: VarId) -> VarId {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[b] = a;
+            let object = types::signature(&types::object());
+            if self.vars[a].ty == object {
+                self.vars[a].ty = self.vars[b].ty.clone();
+            }
+        }
+        a
+    }
+
+    /// The temporary carrying stack entry `depth` into `block`.
+    fn stack_var(&mut self, block: usize, depth: usize, ty: &FieldType) -> VarId {
+        if let Some(&var) = self.stack_vars.get(&(block, depth)) {
+            return var;
+        }
+        let var = self.temp(ty);
+        self.stack_vars.insert((block, depth), var);
+        var
+    }
+
+    fn run(&mut self) -> Result<Method> {
+        try!(self.collect_handlers());
+        let order = self.reverse_postorder();
+        let mut nodes: Vec<Option<Node>> = vec![None; self.cfg.blocks().len()];
+        for &id in &order {
+            nodes[id] = Some(try!(self.block(id)));
+        }
+
+        // Resolve the temporaries joined at block boundaries.
+        let mut roots = vec![];
+        for var in 0..self.vars.len() {
+            roots.push(self.find(var));
+        }
+        let mut resolve = |expr: &mut Expr| if let Expr::Local(ref mut var) = *expr {
+            *var = roots[*var];
+        };
+        for node in nodes.iter_mut().filter_map(|node| node.as_mut()) {
+            for stmt in node.stmts.iter_mut() {
+                stmt.rewrite_exprs(&mut resolve);
+            }
+            match node.exit {
+                Exit::If(ref mut expr, _, _) |
+                Exit::Switch(ref mut expr, _, _) |
+                Exit::Throw(ref mut expr) |
+                Exit::Return(Some(ref mut expr)) => expr.rewrite(&mut resolve),
+                _ => {}
+            }
+        }
+        let mut handlers = self.handlers.clone();
+        handlers.retain(|handler| nodes[handler.node].is_some());
+        for handler in handlers.iter_mut() {
+            handler.protected.retain(|&id| nodes[id].is_some());
+        }
+        Ok(Method {
+            vars: self.vars.clone(),
+            params: self.params.clone(),
+            nodes: nodes,
+            handlers: handlers,
+        })
+    }
+
+    /// Groups the exception table by handler block.
+    fn collect_handlers(&mut self) -> Result<()> {
+        for info in &self.code.exception_table {
+            let node = match self.cfg.block_at(info.handler_pc as usize) {
+                Some(node) => node,
+                None => {
+                    return Err(Error::Unsupported(format!("invalid handler pc {}",
+                                                          info.handler_pc)))
+                }
+            };
+            let catch_type = if info.catch_type == 0 {
+                None
+            } else {
+                match self.cp.class_name(info.catch_type) {
+                    Some(name) => Some(name.clone()),
+                    None => return Err(Error::Unsupported("invalid catch type".to_string())),
+                }
+            };
+            let protected: Vec<usize> = self.cfg
+                .blocks()
+                .iter()
+                .filter(|block| {
+                    block.start_pc >= info.start_pc as usize &&
+                    block.start_pc < info.end_pc as usize
+                })
+                .map(|block| block.id)
+                .collect();
+            let index = match self.handlers.iter().position(|handler| handler.node == node) {
+                Some(index) => index,
+                None => {
+                    let var = self.temp(&FieldType::Object("java/lang/Throwable".to_string()));
+                    self.vars[var].declared = true;
+                    self.handlers.push(Handler {
+                        node: node,
+                        types: vec![],
+                        catches_any: false,
+                        protected: BTreeSet::new(),
+                        var: var,
+                    });
+                    self.handlers.len() - 1
+                }
+            };
+            let handler = &mut self.handlers[index];
+            match catch_type {
+                Some(name) => {
+                    if !handler.types.contains(&name) {
+                        handler.types.push(name);
+                    }
+                }
+                None => handler.catches_any = true,
+            }
+            handler.protected.extend(protected);
+            // A handler that protects itself, as for `synchronized`, is
+            // handled by the handler it is part of.
+            handler.protected.remove(&node);
+        }
+        for handler in &self.handlers {
+            let ty = if handler.types.len() == 1 && !handler.catches_any {
+                FieldType::Object(handler.types[0].clone())
+            } else {
+                FieldType::Object("java/lang/Throwable".to_string())
+            };
+            self.vars[handler.var].ty = types::signature(&ty);
+            self.vars[handler.var].name = "ex".to_string();
+        }
+        Ok(())
+    }
+
+    /// The reachable blocks in reverse postorder, following normal and
+    /// exceptional edges.
+    fn reverse_postorder(&self) -> Vec<usize> {
+        let count = self.cfg.blocks().len();
+        let mut successors = vec![vec![]; count];
+        for edge in self.cfg.edges() {
+            successors[edge.from].push(edge.to);
+        }
+        let mut visited = vec![false; count];
+        let mut postorder = vec![];
+        if count == 0 {
+            return postorder;
+        }
+        let mut pending = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((id, next)) = pending.pop() {
+            if next < successors[id].len() {
+                pending.push((id, next + 1));
+                let successor = successors[id][next];
+                if !visited[successor] {
+                    visited[successor] = true;
+                    pending.push((successor, 0));
+                }
+            } else {
+                postorder.push(id);
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    fn block(&mut self, id: usize) -> Result<Node> {
+        let block = self.cfg.block(id).clone();
+        self.stack.clear();
+        if let Some(handler) = self.handlers.iter().find(|handler| handler.node == id) {
+            let ty = types::erasure(&self.vars[handler.var].ty);
+            self.stack.push(Item::new(Expr::Local(handler.var), ty));
+        } else if let Some(types) = self.entry_types[id].clone() {
+            for (depth, ty) in types.iter().enumerate() {
+                let item = match self.entry_items.get(&(id, depth)) {
+                    Some(item) => item.clone(),
+                    None => Item::new(Expr::Local(self.stack_var(id, depth, ty)), ty.clone()),
+                };
+                self.stack.push(item);
+            }
+        }
+
+        let mut stmts = vec![];
+        let mut exit = None;
+        let mut index = block.instructions.start;
+        while index < block.instructions.end {
+            let instructions = self.cfg.instructions();
+            let pc = instructions.pc(index).unwrap();
+            let insn = instructions.get(index).unwrap().clone();
+            let next_pc = instructions.end_pc(index).unwrap();
+            // The following instruction, with the pc after it, for the
+            // instructions rebuilt together with it.
+            let next = if index + 1 < block.instructions.end {
+                instructions.get(index + 1)
+                    .map(|next| (instructions.end_pc(index + 1).unwrap(), next.clone()))
+            } else {
+                None
+            };
+            let (result, skip) = try!(self.execute(&mut stmts, pc, next_pc, &insn, next.as_ref()));
+            if skip {
+                index += 1;
+            }
+            index += 1;
+            if let Some(result) = result {
+                exit = Some(result);
+                break;
+            }
+        }
+        let exit = match exit {
+            Some(exit) => exit,
+            None => {
+                match self.cfg.block_at(block.end_pc) {
+                    Some(next) => Exit::Goto(next),
+                    None => return Err(Error::Unsupported("code falls off its end".to_string())),
+                }
+            }
+        };
+        let exit = self.finish(&mut stmts, exit);
+        Ok(Node {
+            pc: block.start_pc,
+            stmts: stmts,
+            exit: exit,
+        })
+    }
+
+    /// Passes the entries left on the stack to the successors of a block.
+    fn finish(&mut self, stmts: &mut Vec<Stmt>, exit: Exit) -> Exit {
+        let successors = exit.successors();
+        let items: Vec<Item> = self.stack.drain(..).collect();
+        if successors.is_empty() {
+            for item in items {
+                if !is_pure(&item.expr) {
+                    stmts.push(Stmt::Expr(item.expr));
+                }
+            }
+            return exit;
+        }
+        let types: Vec<FieldType> = items.iter().map(|item| item.ty.clone()).collect();
+        for &successor in &successors {
+            if self.entry_types[successor].is_none() {
+                self.entry_types[successor] = Some(types.clone());
+            }
+        }
+        for (depth, item) in items.into_iter().enumerate() {
+            if let Expr::Uninitialized(_) = item.expr {
+                for &successor in &successors {
+                    self.entry_items.insert((successor, depth), item.clone());
+                }
+                continue;
+            }
+            let mut shared = None;
+            for &successor in &successors {
+                let var = self.stack_var(successor, depth, &item.ty);
+                shared = Some(match shared {
+                    Some(other) => self.union(other, var),
+                    None => self.find(var),
+                });
+            }
+            let shared = shared.unwrap();
+            if let Expr::Local(var) = item.expr {
+                let passes_through = self.stack_vars.values().any(|&stack_var| stack_var == var);
+                if passes_through && self.find(var) != shared {
+                    let own_depth = self.stack_vars
+                        .iter()
+                        .any(|(&(_, other_depth), &stack_var)| {
+                            stack_var == var && other_depth == depth
+                        });
+                    if own_depth {
+                        self.union(shared, var);
+                        continue;
+                    }
+                }
+                if self.find(var) == shared {
+                    continue;
+                }
+            }
+            stmts.push(assign(Expr::Local(shared), item.expr));
+        }
+        exit
+    }
+
+    fn pop(&mut self, pc: usize) -> Result<Item> {
+        match self.stack.pop() {
+            Some(item) => Ok(item),
+            None => Err(Error::Unsupported(format!("pc {}: stack underflow", pc))),
+        }
+    }
+
+    fn pop_n(&mut self, pc: usize, count: usize) -> Result<Vec<Item>> {
+        if self.stack.len() < count {
+            return Err(Error::Unsupported(format!("pc {}: stack underflow", pc)));
+        }
+        let at = self.stack.len() - count;
+        Ok(self.stack.split_off(at))
+    }
+
+    fn push(&mut self, expr: Expr, ty: FieldType) {
+        self.stack.push(Item::new(expr, ty));
+    }
+
+    /// Saves the stack entry at `index`, and every copy of it, to a
+    /// temporary.
+    fn spill_item(&mut self, index: usize, stmts: &mut Vec<Stmt>) {
+        let item = self.stack[index].clone();
+        if let Expr::Uninitialized(_) = item.expr {
+            return;
+        }
+        let var = self.temp(&item.ty);
+        stmts.push(assign(Expr::Local(var), item.expr));
+        for other in self.stack.iter_mut() {
+            if other.id.is_some() && other.id == item.id {
+                *other = Item::new(Expr::Local(var), item.ty.clone());
+            }
+        }
+        self.stack[index] = Item::new(Expr::Local(var), item.ty);
+    }
+
+    /// Saves the stack entries that a statement writing `writes` could
+    /// change, or that have effects of their own, to temporaries.
+    fn spill(&mut self, stmts: &mut Vec<Stmt>, writes: Option<VarId>) {
+        for index in 0..self.stack.len() {
+            let needed = {
+                let expr = &self.stack[index].expr;
+                !is_pure(expr) || writes.map_or(false, |var| expr.count_var(var) > 0)
+            };
+            if needed {
+                self.spill_item(index, stmts);
+            }
+        }
+    }
+
+    fn emit(&mut self, stmts: &mut Vec<Stmt>, stmt: Stmt, writes: Option<VarId>) {
+        self.spill(stmts, writes);
+        stmts.push(stmt);
+    }
+
+    /// Makes the top `count` stack entries safe to duplicate, saving them
+    /// and any entry below them with effects to temporaries.
+    fn prepare_dup(&mut self, stmts: &mut Vec<Stmt>, count: usize) {
+        let len = self.stack.len();
+        for index in 0..len {
+            let needed = {
+                let item = &self.stack[index];
+                if index + count >= len {
+                    !item.expr.is_simple() && item.id.is_none() &&
+                    !is_uninitialized(&item.expr)
+                } else {
+                    !is_pure(&item.expr)
+                }
+            };
+            if needed {
+                self.spill_item(index, stmts);
+            }
+        }
+    }
+
+    /// The number of stack slots the entry `depth` places from the top
+    /// takes.
+    fn size_at(&self, depth: usize) -> usize {
+        let len = self.stack.len();
+        if depth >= len {
+            return 1;
+        }
+        self.stack[len - 1 - depth].ty.slots() as usize
+    }
+
+    fn execute(&mut self,
+               stmts: &mut Vec<Stmt>,
+               pc: usize,
+               next_pc: usize,
+               insn: &Bytecode,
+               next: Option<&(usize, Bytecode)>)
+               -> Result<(Option<Exit>, bool)> {
+        if let Some(access) = insn.local_access() {
+            let kind = insn.info().mnemonic().as_bytes()[0];
+            match *insn {
+                Bytecode::iinc { constant, .. } => {
+                    self.increment(stmts, pc, access.index, constant as i8 as i32);
+                }
+                Bytecode::wide_iinc { constant, .. } => {
+                    self.increment(stmts, pc, access.index, constant as i16 as i32);
+                }
+                Bytecode::ret { .. } |
+                Bytecode::wide_ret { .. } => {
+                    return Err(Error::Unsupported("subroutines are not supported".to_string()))
+                }
+                _ if access.writes => {
+                    let value = try!(self.pop(pc));
+                    let var = self.var_at(access.index, next_pc, true, kind, &value.ty);
+                    self.emit(stmts, assign(Expr::Local(var), value.expr), Some(var));
+                }
+                _ => {
+                    if !self.is_static && access.index == 0 && !self.this_overwritten {
+                        let ty = FieldType::Object(self.this_class.to_string());
+                        self.push(Expr::This, ty);
+                    } else {
+                        let hint = match kind {
+                            b'a' => types::object(),
+                            b'l' => FieldType::Long,
+                            b'f' => FieldType::Float,
+                            b'd' => FieldType::Double,
+                            _ => FieldType::Int,
+                        };
+                        let var = self.var_at(access.index, pc, false, kind, &hint);
+                        let ty = types::erasure(&self.vars[var].ty);
+                        self.push(Expr::Local(var), ty);
+                    }
+                }
+            }
+            return Ok((None, false));
+        }
+
+        match *insn {
+            Bytecode::nop => {}
+            Bytecode::aconst_null => self.push(Expr::Literal(Literal::Null), types::object()),
+            Bytecode::iconst_i(value) => self.push(Expr::int(value as i32), FieldType::Int),
+            Bytecode::lconst_l(value) => {
+                self.push(Expr::Literal(Literal::Long(value as i64)), FieldType::Long)
+            }
+            Bytecode::fconst_f(value) => {
+                self.push(Expr::Literal(Literal::Float(value as f32)), FieldType::Float)
+            }
+            Bytecode::dconst_d(value) => {
+                self.push(Expr::Literal(Literal::Double(value as f64)), FieldType::Double)
+            }
+            Bytecode::bipush { byte } => self.push(Expr::int(byte as i8 as i32), FieldType::Int),
+            Bytecode::sipush { short } => self.push(Expr::int(short as i32), FieldType::Int),
+            Bytecode::ldc { .. } |
+            Bytecode::ldc_w { .. } |
+            Bytecode::ldc2_w { .. } => {
+                let (literal, ty) = try!(self.constant(insn.constant_index().unwrap()));
+                self.push(Expr::Literal(literal), ty);
+            }
+
+            Bytecode::iaload => try!(self.array_load(pc, FieldType::Int)),
+            Bytecode::laload => try!(self.array_load(pc, FieldType::Long)),
+            Bytecode::faload => try!(self.array_load(pc, FieldType::Float)),
+            Bytecode::daload => try!(self.array_load(pc, FieldType::Double)),
+            Bytecode::aaload => try!(self.array_load(pc, types::object())),
+            Bytecode::baload => try!(self.array_load(pc, FieldType::Byte)),
+            Bytecode::caload => try!(self.array_load(pc, FieldType::Char)),
+            Bytecode::saload => try!(self.array_load(pc, FieldType::Short)),
+            Bytecode::iastore | Bytecode::lastore | Bytecode::fastore | Bytecode::dastore |
+            Bytecode::aastore | Bytecode::bastore | Bytecode::castore | Bytecode::sastore => {
+                try!(self.array_store(stmts, pc))
+            }
+
+            Bytecode::pop | Bytecode::pop2 => {
+                let item = try!(self.pop(pc));
+                if *insn == Bytecode::pop2 && item.ty.slots() == 1 {
+                    let other = try!(self.pop(pc));
+                    self.discard(stmts, other);
+                }
+                self.discard(stmts, item);
+            }
+            Bytecode::dup => {
+                if let Some(skip) = try!(self.dup_store(stmts, pc, next)) {
+                    return Ok((None, skip));
+                }
+                self.prepare_dup(stmts, 1);
+                let item = try!(self.pop(pc));
+                self.stack.push(item.clone());
+                self.stack.push(item);
+            }
+            Bytecode::dup_x1 => {
+                self.prepare_dup(stmts, 2);
+                let value1 = try!(self.pop(pc));
+                let value2 = try!(self.pop(pc));
+                self.stack.extend(vec![value1.clone(), value2, value1]);
+            }
+            Bytecode::dup_x2 => {
+                let count = if self.size_at(1) == 2 { 2 } else { 3 };
+                self.prepare_dup(stmts, count);
+                let value1 = try!(self.pop(pc));
+                let value2 = try!(self.pop(pc));
+                if value2.ty.slots() == 2 {
+                    self.stack.extend(vec![value1.clone(), value2, value1]);
+                } else {
+                    let value3 = try!(self.pop(pc));
+                    self.stack.extend(vec![value1.clone(), value3, value2, value1]);
+                }
+            }
+            Bytecode::dup2 => {
+                if self.size_at(0) == 2 {
+                    if let Some(skip) = try!(self.dup_store(stmts, pc, next)) {
+                        return Ok((None, skip));
+                    }
+                    self.prepare_dup(stmts, 1);
+                    let value = try!(self.pop(pc));
+                    self.stack.extend(vec![value.clone(), value]);
+                } else {
+                    self.prepare_dup(stmts, 2);
+                    let value1 = try!(self.pop(pc));
+                    let value2 = try!(self.pop(pc));
+                    self.stack.extend(vec![value2.clone(), value1.clone(), value2, value1]);
+                }
+            }
+            Bytecode::dup2_x1 => {
+                let count = if self.size_at(0) == 2 { 2 } else { 3 };
+                self.prepare_dup(stmts, count);
+                let value1 = try!(self.pop(pc));
+                let value2 = try!(self.pop(pc));
+                if value1.ty.slots() == 2 {
+                    self.stack.extend(vec![value1.clone(), value2, value1]);
+                } else {
+                    let value3 = try!(self.pop(pc));
+                    self.stack.extend(vec![value2.clone(), value1.clone(), value3, value2, value1]);
+                }
+            }
+            Bytecode::dup2_x2 => {
+                let count = match (self.size_at(0), self.size_at(1), self.size_at(2)) {
+                    (2, 2, _) => 2,
+                    (2, _, _) | (_, _, 2) => 3,
+                    _ => 4,
+                };
+                self.prepare_dup(stmts, count);
+                let value1 = try!(self.pop(pc));
+                let value2 = try!(self.pop(pc));
+                if value1.ty.slots() == 2 {
+                    if value2.ty.slots() == 2 {
+                        self.stack.extend(vec![value1.clone(), value2, value1]);
+                    } else {
+                        let value3 = try!(self.pop(pc));
+                        self.stack.extend(vec![value1.clone(), value3, value2, value1]);
+                    }
+                } else {
+                    let value3 = try!(self.pop(pc));
+                    if value3.ty.slots() == 2 {
+                        self.stack
+                            .extend(vec![value2.clone(), value1.clone(), value3, value2, value1]);
+                    } else {
+                        let value4 = try!(self.pop(pc));
+                        self.stack.extend(vec![value2.clone(),
+                                               value1.clone(),
+                                               value4,
+                                               value3,
+                                               value2,
+                                               value1]);
+                    }
+                }
+            }
+            Bytecode::swap => {
+                self.prepare_dup(stmts, 0);
+                let value1 = try!(self.pop(pc));
+                let value2 = try!(self.pop(pc));
+                self.stack.extend(vec![value1, value2]);
+            }
+
+            Bytecode::iadd | Bytecode::ladd | Bytecode::fadd | Bytecode::dadd => {
+                try!(self.binary(pc, BinaryOp::Add))
+            }
+            Bytecode::isub | Bytecode::lsub | Bytecode::fsub | Bytecode::dsub => {
+                try!(self.binary(pc, BinaryOp::Sub))
+            }
+            Bytecode::imul | Bytecode::lmul | Bytecode::fmul | Bytecode::dmul => {
+                try!(self.binary(pc, BinaryOp::Mul))
+            }
+            Bytecode::idiv | Bytecode::ldiv | Bytecode::fdiv | Bytecode::ddiv => {
+                try!(self.binary(pc, BinaryOp::Div))
+            }
+            Bytecode::irem | Bytecode::lrem | Bytecode::frem | Bytecode::drem => {
+                try!(self.binary(pc, BinaryOp::Rem))
+            }
+            Bytecode::ishl | Bytecode::lshl => try!(self.binary(pc, BinaryOp::Shl)),
+            Bytecode::ishr | Bytecode::lshr => try!(self.binary(pc, BinaryOp::Shr)),
+            Bytecode::iushr | Bytecode::lushr => try!(self.binary(pc, BinaryOp::UShr)),
+            Bytecode::iand | Bytecode::land => try!(self.binary(pc, BinaryOp::And)),
+            Bytecode::ior | Bytecode::lor => try!(self.binary(pc, BinaryOp::Or)),
+            Bytecode::ixor | Bytecode::lxor => try!(self.binary(pc, BinaryOp::Xor)),
+            Bytecode::ineg | Bytecode::lneg | Bytecode::fneg | Bytecode::dneg => {
+                let value = try!(self.pop(pc));
+                self.push(Expr::Unary(UnaryOp::Neg, Box::new(value.expr)), value.ty);
+            }
+
+            Bytecode::i2l | Bytecode::f2l | Bytecode::d2l => {
+                try!(self.convert(pc, FieldType::Long))
+            }
+            Bytecode::i2f | Bytecode::l2f | Bytecode::d2f => {
+                try!(self.convert(pc, FieldType::Float))
+            }
+            Bytecode::i2d | Bytecode::l2d | Bytecode::f2d => {
+                try!(self.convert(pc, FieldType::Double))
+            }
+            Bytecode::l2i | Bytecode::f2i | Bytecode::d2i => try!(self.convert(pc, FieldType::Int)),
+            Bytecode::i2b => try!(self.convert(pc, FieldType::Byte)),
+            Bytecode::i2c => try!(self.convert(pc, FieldType::Char)),
+            Bytecode::i2s => try!(self.convert(pc, FieldType::Short)),
+            Bytecode::lcmp => try!(self.compare(pc, 0)),
+            Bytecode::fcmpl | Bytecode::dcmpl => try!(self.compare(pc, -1)),
+            Bytecode::fcmpg | Bytecode::dcmpg => try!(self.compare(pc, 1)),
+
+            Bytecode::ifeq { .. } | Bytecode::ifnull { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Eq, false)
+            }
+            Bytecode::ifne { .. } | Bytecode::ifnonnull { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Ne, false)
+            }
+            Bytecode::iflt { .. } => return self.branch(pc, next_pc, insn, BinaryOp::Lt, false),
+            Bytecode::ifge { .. } => return self.branch(pc, next_pc, insn, BinaryOp::Ge, false),
+            Bytecode::ifgt { .. } => return self.branch(pc, next_pc, insn, BinaryOp::Gt, false),
+            Bytecode::ifle { .. } => return self.branch(pc, next_pc, insn, BinaryOp::Le, false),
+            Bytecode::if_icmpeq { .. } | Bytecode::if_acmpeq { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Eq, true)
+            }
+            Bytecode::if_icmpne { .. } | Bytecode::if_acmpne { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Ne, true)
+            }
+            Bytecode::if_icmplt { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Lt, true)
+            }
+            Bytecode::if_icmpge { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Ge, true)
+            }
+            Bytecode::if_icmpgt { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Gt, true)
+            }
+            Bytecode::if_icmple { .. } => {
+                return self.branch(pc, next_pc, insn, BinaryOp::Le, true)
+            }
+            Bytecode::goto { .. } |
+            Bytecode::goto_w { .. } => {
+                let target = self.block_of(insn.branch_targets(pc)[0]);
+                return Ok((Some(Exit::Goto(target)), false));
+            }
+            Bytecode::jsr { .. } |
+            Bytecode::jsr_w { .. } => {
+                return Err(Error::Unsupported("subroutines are not supported".to_string()))
+            }
+            Bytecode::tableswitch { low, .. } => {
+                let value = try!(self.pop(pc));
+                let targets = insn.branch_targets(pc);
+                let cases = targets[1..]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &target)| (low.wrapping_add(i as i32), self.block_of(target)))
+                    .collect();
+                let default = self.block_of(targets[0]);
+                return Ok((Some(Exit::Switch(value.expr, cases, default)), false));
+            }
+            Bytecode::lookupswitch { ref pairs, .. } => {
+                let value = try!(self.pop(pc));
+                let targets = insn.branch_targets(pc);
+                let cases = pairs.iter()
+                    .zip(targets[1..].iter())
+                    .map(|(&(key, _), &target)| (key, self.block_of(target)))
+                    .collect();
+                let default = self.block_of(targets[0]);
+                return Ok((Some(Exit::Switch(value.expr, cases, default)), false));
+            }
+            Bytecode::ireturn | Bytecode::lreturn | Bytecode::freturn | Bytecode::dreturn |
+            Bytecode::areturn => {
+                let value = try!(self.pop(pc));
+                self.spill(stmts, None);
+                return Ok((Some(Exit::Return(Some(value.expr))), false));
+            }
+            Bytecode::Return => {
+                self.spill(stmts, None);
+                return Ok((Some(Exit::Return(None)), false));
+            }
+            Bytecode::athrow => {
+                let value = try!(self.pop(pc));
+                self.spill(stmts, None);
+                return Ok((Some(Exit::Throw(value.expr)), false));
+            }
+
+            Bytecode::getstatic { index } => {
+                let (owner, name, descriptor) = try!(self.field(index));
+                let expr = Expr::Field {
+                    target: Target::Static(owner),
+                    name: name,
+                    descriptor: descriptor.clone(),
+                };
+                self.push(expr, descriptor);
+            }
+            Bytecode::putstatic { index } => {
+                let (owner, name, descriptor) = try!(self.field(index));
+                let value = try!(self.pop(pc));
+                let field = Expr::Field {
+                    target: Target::Static(owner),
+                    name: name,
+                    descriptor: descriptor,
+                };
+                self.emit(stmts, assign(field, value.expr), None);
+            }
+            Bytecode::getfield { index } => {
+                let (_, name, descriptor) = try!(self.field(index));
+                let object = try!(self.pop(pc));
+                let expr = Expr::Field {
+                    target: Target::Instance(Box::new(object.expr)),
+                    name: name,
+                    descriptor: descriptor.clone(),
+                };
+                self.push(expr, descriptor);
+            }
+            Bytecode::putfield { index } => {
+                let (_, name, descriptor) = try!(self.field(index));
+                let value = try!(self.pop(pc));
+                let object = try!(self.pop(pc));
+                let field = Expr::Field {
+                    target: Target::Instance(Box::new(object.expr)),
+                    name: name,
+                    descriptor: descriptor,
+                };
+                self.emit(stmts, assign(field, value.expr), None);
+            }
+            Bytecode::invokevirtual { index } |
+            Bytecode::invokeinterface { index, .. } => try!(self.invoke(stmts, pc, index, false)),
+            Bytecode::invokespecial { index } => try!(self.invoke(stmts, pc, index, true)),
+            Bytecode::invokestatic { index } => try!(self.invoke_static(stmts, pc, index)),
+            Bytecode::invokedynamic { index } => try!(self.invoke_dynamic(stmts, pc, index)),
+
+            Bytecode::new { index } => {
+                let class = try!(self.class_name(index));
+                let id = self.fresh_id();
+                self.stack.push(Item {
+                    expr: Expr::Uninitialized(class.clone()),
+                    ty: FieldType::Object(class),
+                    id: Some(id),
+                });
+            }
+            Bytecode::newarray { atype } => {
+                let component = match atype {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => FieldType::Float,
+                    7 => FieldType::Double,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => FieldType::Int,
+                    11 => FieldType::Long,
+                    _ => return Err(Error::Unsupported(format!("pc {}: bad array type", pc))),
+                };
+                try!(self.new_array(pc, component));
+            }
+            Bytecode::anewarray { index } => {
+                let class = try!(self.class_name(index));
+                let component = if class.starts_with('[') {
+                    try!(FieldType::parse(&class))
+                } else {
+                    FieldType::Object(class)
+                };
+                try!(self.new_array(pc, component));
+            }
+            Bytecode::multianewarray { index, dimensions } => {
+                let ty = try!(FieldType::parse(&try!(self.class_name(index))));
+                let lengths = try!(self.pop_n(pc, dimensions as usize));
+                let expr = Expr::NewArray {
+                    ty: ty.clone(),
+                    dimensions: lengths.into_iter().map(|item| item.expr).collect(),
+                };
+                self.push(expr, ty);
+            }
+            Bytecode::arraylength => {
+                let array = try!(self.pop(pc));
+                self.push(Expr::ArrayLength(Box::new(array.expr)), FieldType::Int);
+            }
+            Bytecode::checkcast { index } => {
+                let ty = try!(self.class_type(index));
+                let value = try!(self.pop(pc));
+                self.push(Expr::Cast(ty.clone(), Box::new(value.expr)), ty);
+            }
+            Bytecode::instanceof { index } => {
+                let ty = try!(self.class_type(index));
+                let value = try!(self.pop(pc));
+                self.push(Expr::InstanceOf(Box::new(value.expr), ty), FieldType::Boolean);
+            }
+            Bytecode::monitorenter => {
+                let value = try!(self.pop(pc));
+                self.emit(stmts, Stmt::MonitorEnter(value.expr), None);
+            }
+            Bytecode::monitorexit => {
+                let value = try!(self.pop(pc));
+                self.emit(stmts, Stmt::MonitorExit(value.expr), None);
+            }
+            _ => {
+                let message = format!("pc {}: unsupported instruction {}",
+                                      pc,
+                                      insn.info().mnemonic());
+                return Err(Error::Unsupported(message));
+            }
+        }
+        Ok((None, false))
+    }
+
+    fn fresh_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn block_of(&self, pc: usize) -> usize {
+        self.cfg.block_at(pc).unwrap()
+    }
+
+    /// Drops a popped stack entry, keeping its effects as a statement.
+    fn discard(&mut self, stmts: &mut Vec<Stmt>, item: Item) {
+        if !is_pure(&item.expr) {
+            self.emit(stmts, Stmt::Expr(item.expr), None);
+        }
+    }
+
+    /// Turns a `dup` of a value followed by a store of the copy into an
+    /// assignment expression, as for `a = b = c`. Returns `Some(true)` if the
+    /// store was consumed.
+    fn dup_store(&mut self,
+                 stmts: &mut Vec<Stmt>,
+                 pc: usize,
+                 next: Option<&(usize, Bytecode)>)
+                 -> Result<Option<bool>> {
+        let (store_pc, store) = match next {
+            Some(&(store_pc, ref store)) => (store_pc, store),
+            None => return Ok(None),
+        };
+        let access = match store.local_access() {
+            Some(access) if access.writes && !access.reads => access,
+            _ => return Ok(None),
+        };
+        let simple = self.stack.last().map_or(true, |item| {
+            item.expr.is_simple() || item.id.is_some()
+        });
+        if simple {
+            return Ok(None);
+        }
+        let kind = store.info().mnemonic().as_bytes()[0];
+        let value = try!(self.pop(pc));
+        let var = self.var_at(access.index, store_pc, true, kind, &value.ty);
+        self.spill(stmts, Some(var));
+        let ty = value.ty.clone();
+        self.push(Expr::Assign(Box::new(Expr::Local(var)), Box::new(value.expr)), ty);
+        Ok(Some(true))
+    }
+
+    fn increment(&mut self, stmts: &mut Vec<Stmt>, pc: usize, slot: u16, constant: i32) {
+        let var = self.var_at(slot, pc, false, b'i', &FieldType::Int);
+        let target = Box::new(Expr::Local(var));
+        if constant == 1 || constant == -1 {
+            let on_top = self.stack.last().map_or(false, |item| item.expr == Expr::Local(var));
+            if on_top {
+                // A load of the variable just before the increment: `i++`
+                // used as a value.
+                let top = self.stack.pop().unwrap();
+                self.spill(stmts, Some(var));
+                let expr = Expr::Increment {
+                    target: target,
+                    prefix: false,
+                    decrement: constant < 0,
+                };
+                self.push(expr, top.ty);
+                return;
+            }
+            let stmt = Stmt::Expr(Expr::Increment {
+                target: target,
+                prefix: false,
+                decrement: constant < 0,
+            });
+            self.emit(stmts, stmt, Some(var));
+            return;
+        }
+        let expr = if constant < 0 {
+            Expr::CompoundAssign(BinaryOp::Sub, target, Box::new(Expr::int(-constant)))
+        } else {
+            Expr::CompoundAssign(BinaryOp::Add, target, Box::new(Expr::int(constant)))
+        };
+        self.emit(stmts, Stmt::Expr(expr), Some(var));
+    }
+
+    fn array_load(&mut self, pc: usize, element: FieldType) -> Result<()> {
+        let index = try!(self.pop(pc));
+        let array = try!(self.pop(pc));
+        let ty = match (&array.ty, element) {
+            (&FieldType::Array(ref component), FieldType::Object(_)) => (**component).clone(),
+            (&FieldType::Array(ref component), FieldType::Byte) => (**component).clone(),
+            (_, element) => element,
+        };
+        self.push(Expr::ArrayElement(Box::new(array.expr), Box::new(index.expr)), ty);
+        Ok(())
+    }
+
+    fn array_store(&mut self, stmts: &mut Vec<Stmt>, pc: usize) -> Result<()> {
+        let value = try!(self.pop(pc));
+        let index = try!(self.pop(pc));
+        let array = try!(self.pop(pc));
+
+        // Stores into a new array still on the stack fill in its initializer.
+        if let (Some(id), &Expr::Literal(Literal::Int(position))) = (array.id, &index.expr) {
+            let element = match array.ty {
+                FieldType::Array(ref component) => (**component).clone(),
+                _ => types::object(),
+            };
+            let elements = match array.expr {
+                Expr::NewArray { ref dimensions, .. } => {
+                    match dimensions[0] {
+                        Expr::Literal(Literal::Int(length)) => {
+                            let value = Expr::Literal(types::default_value(&element));
+                            Some(vec![value; length as usize])
+                        }
+                        _ => None,
+                    }
+                }
+                Expr::ArrayInit { ref elements, .. } => Some(elements.clone()),
+                _ => None,
+            };
+            if let Some(mut elements) = elements {
+                if position >= 0 && (position as usize) < elements.len() {
+                    elements[position as usize] = value.expr;
+                    let init = Expr::ArrayInit {
+                        ty: array.ty.clone(),
+                        elements: elements,
+                    };
+                    for item in self.stack.iter_mut() {
+                        if item.id == Some(id) {
+                            item.expr = init.clone();
+                        }
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        let element = Expr::ArrayElement(Box::new(array.expr), Box::new(index.expr));
+        self.emit(stmts, assign(element, value.expr), None);
+        Ok(())
+    }
+
+    fn new_array(&mut self, pc: usize, component: FieldType) -> Result<()> {
+        let length = try!(self.pop(pc));
+        let ty = FieldType::Array(Box::new(component));
+        let id = match length.expr {
+            Expr::Literal(Literal::Int(length)) if length >= 0 && length <= 1024 => {
+                Some(self.fresh_id())
+            }
+            _ => None,
+        };
+        self.stack.push(Item {
+            expr: Expr::NewArray {
+                ty: ty.clone(),
+                dimensions: vec![length.expr],
+            },
+            ty: ty,
+            id: id,
+        });
+        Ok(())
+    }
+
+    fn binary(&mut self, pc: usize, op: BinaryOp) -> Result<()> {
+        let mut right = try!(self.pop(pc));
+        let mut left = try!(self.pop(pc));
+        let logical = op == BinaryOp::And || op == BinaryOp::Or || op == BinaryOp::Xor;
+        if logical && (left.ty == FieldType::Boolean || right.ty == FieldType::Boolean) {
+            // `a | (b ? 1 : 0)` on booleans.
+            left = Item::new(to_boolean(left.expr), FieldType::Boolean);
+            right = Item::new(to_boolean(right.expr), FieldType::Boolean);
+        }
+        let minus_one = right.expr == Expr::int(-1) ||
+                        right.expr == Expr::Literal(Literal::Long(-1));
+        let expr = if op == BinaryOp::Xor && minus_one {
+            Expr::Unary(UnaryOp::BitNot, Box::new(left.expr))
+        } else {
+            Expr::binary(op, left.expr, right.expr)
+        };
+        let ty = match (&left.ty, &right.ty) {
+            (&FieldType::Boolean, &FieldType::Boolean) => FieldType::Boolean,
+            (ty, _) if types::is_int_like(ty) => FieldType::Int,
+            (ty, _) => ty.clone(),
+        };
+        self.push(expr, ty);
+        Ok(())
+    }
+
+    fn convert(&mut self, pc: usize, to: FieldType) -> Result<()> {
+        let value = try!(self.pop(pc));
+        self.push(Expr::Cast(to.clone(), Box::new(value.expr)), to);
+        Ok(())
+    }
+
+    fn compare(&mut self, pc: usize, nan: i32) -> Result<()> {
+        let right = try!(self.pop(pc));
+        let left = try!(self.pop(pc));
+        let expr = Expr::Compare(nan, Box::new(left.expr), Box::new(right.expr));
+        self.push(expr, FieldType::Int);
+        Ok(())
+    }
+
+    fn branch(&mut self,
+              pc: usize,
+              next_pc: usize,
+              insn: &Bytecode,
+              op: BinaryOp,
+              binary: bool)
+              -> Result<(Option<Exit>, bool)> {
+        let right = if binary {
+            Some(try!(self.pop(pc)))
+        } else {
+            None
+        };
+        let left = try!(self.pop(pc));
+        let condition = match right {
+            Some(right) => {
+                let (left_expr, right_expr) = match (&left.ty, &right.ty) {
+                    (&FieldType::Char, _) => (left.expr, to_char(right.expr)),
+                    (_, &FieldType::Char) => (to_char(left.expr), right.expr),
+                    (&FieldType::Boolean, _) => (left.expr, to_boolean(right.expr)),
+                    (_, &FieldType::Boolean) => (to_boolean(left.expr), right.expr),
+                    _ => (left.expr, right.expr),
+                };
+                Expr::binary(op, left_expr, right_expr)
+            }
+            None => {
+                match left.expr {
+                    // Flipping a comparison of floating point values is
+                    // wrong for NaN, so the raw comparison is kept until the
+                    // structure is settled; see `passes::float_comparisons`.
+                    compare @ Expr::Compare(..) => Expr::binary(op, compare, Expr::int(0)),
+                    expr => {
+                        if left.ty == FieldType::Boolean {
+                            if op == BinaryOp::Eq { expr.negate() } else { expr }
+                        } else if left.ty.is_reference() {
+                            Expr::binary(op, expr, Expr::Literal(Literal::Null))
+                        } else if left.ty == FieldType::Char {
+                            Expr::binary(op, expr, Expr::Literal(Literal::Char(0)))
+                        } else {
+                            Expr::binary(op, expr, Expr::int(0))
+                        }
+                    }
+                }
+            }
+        };
+        let then = self.block_of(insn.branch_targets(pc)[0]);
+        let otherwise = match self.cfg.block_at(next_pc) {
+            Some(otherwise) => otherwise,
+            None => return Err(Error::Unsupported("code falls off its end".to_string())),
+        };
+        let exit = if then == otherwise {
+            Exit::Goto(then)
+        } else {
+            Exit::If(condition, then, otherwise)
+        };
+        Ok((Some(exit), false))
+    }
+
+    fn push_result(&mut self,
+                   stmts: &mut Vec<Stmt>,
+                   expr: Expr,
+                   ret: &Option<FieldType>) {
+        match *ret {
+            Some(ref ty) => self.push(expr, ty.clone()),
+            None => self.emit(stmts, Stmt::Expr(expr), None),
+        }
+    }
+
+    fn invoke_static(&mut self, stmts: &mut Vec<Stmt>, pc: usize, index: u16) -> Result<()> {
+        let (owner, name, descriptor) = try!(self.member(index));
+        let args = try!(self.pop_n(pc, descriptor.params.len()));
+        let ret = descriptor.ret.clone();
+        let args = args.into_iter()
+            .zip(descriptor.params.iter())
+            .map(|(item, ty)| argument(item, ty))
+            .collect();
+        let expr = Expr::Invoke {
+            target: Target::Static(owner),
+            name: name,
+            descriptor: descriptor,
+            args: args,
+        };
+        self.push_result(stmts, expr, &ret);
+        Ok(())
+    }
+
+    fn invoke(&mut self,
+              stmts: &mut Vec<Stmt>,
+              pc: usize,
+              index: u16,
+              special: bool)
+              -> Result<()> {
+        let (owner, name, descriptor) = try!(self.member(index));
+        let args: Vec<Expr> = try!(self.pop_n(pc, descriptor.params.len()))
+            .into_iter()
+            .zip(descriptor.params.iter())
+            .map(|(item, ty)| argument(item, ty))
+            .collect();
+        let receiver = try!(self.pop(pc));
+        let ret = descriptor.ret.clone();
+
+        if special && name == "<init>" {
+            match receiver.expr {
+                Expr::Uninitialized(class) => {
+                    let expr = Expr::New {
+                        class: class.clone(),
+                        descriptor: descriptor,
+                        args: args,
+                    };
+                    let mut used = false;
+                    for item in self.stack.iter_mut() {
+                        if receiver.id.is_some() && item.id == receiver.id {
+                            *item = Item::new(expr.clone(), FieldType::Object(class.clone()));
+                            used = true;
+                        }
+                    }
+                    if !used {
+                        self.emit(stmts, Stmt::Expr(expr), None);
+                    }
+                }
+                Expr::This => {
+                    let stmt = Stmt::Expr(Expr::ConstructorCall {
+                        is_super: owner != self.this_class,
+                        descriptor: descriptor,
+                        args: args,
+                    });
+                    self.emit(stmts, stmt, None);
+                }
+                other => {
+                    let expr = Expr::Invoke {
+                        target: Target::Instance(Box::new(other)),
+                        name: name,
+                        descriptor: descriptor,
+                        args: args,
+                    };
+                    self.emit(stmts, Stmt::Expr(expr), None);
+                }
+            }
+            return Ok(());
+        }
+
+        let target = if special && receiver.expr == Expr::This && owner != self.this_class {
+            Target::Super
+        } else {
+            Target::Instance(Box::new(receiver.expr))
+        };
+        let is_builder = owner == "java/lang/StringBuilder" || owner == "java/lang/StringBuffer";
+        if is_builder && name == "toString" && args.is_empty() {
+            if let Target::Instance(ref chain) = target {
+                if let Some(concat) = concat_chain(chain) {
+                    self.push(concat, types::string());
+                    return Ok(());
+                }
+            }
+        }
+        let expr = Expr::Invoke {
+            target: target,
+            name: name,
+            descriptor: descriptor,
+            args: args,
+        };
+        self.push_result(stmts, expr, &ret);
+        Ok(())
+    }
+
+    fn invoke_dynamic(&mut self, stmts: &mut Vec<Stmt>, pc: usize, index: u16) -> Result<()> {
+        let (bootstrap_index, name, descriptor) = match self.cp.get(index) {
+            Some(&Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index }) => {
+                let (name, descriptor) = try!(self.name_and_type(name_and_type_index));
+                (bootstrap_method_attr_index, name, try!(MethodDescriptor::parse(&descriptor)))
+            }
+            _ => return Err(invalid_constant(index)),
+        };
+        let args = try!(self.pop_n(pc, descriptor.params.len()));
+        let bootstrap = self.class
+            .attrs
+            .bootstrap_methods()
+            .and_then(|methods| methods.get(bootstrap_index as usize));
+        let bootstrap = match bootstrap {
+            Some(bootstrap) => bootstrap,
+            None => return Err(Error::Unsupported("missing bootstrap method".to_string())),
+        };
+        let (_, factory, method, _) = try!(self.method_handle(bootstrap.bootstrap_method_ref));
+        let expr = match (factory.as_ref(), method.as_ref()) {
+            ("java/lang/invoke/LambdaMetafactory", "metafactory") |
+            ("java/lang/invoke/LambdaMetafactory", "altMetafactory") => {
+                let handle = match bootstrap.bootstrap_arguments.get(1) {
+                    Some(&handle) => handle,
+                    None => return Err(Error::Unsupported("malformed lambda".to_string())),
+                };
+                try!(self.lambda(handle, args))
+            }
+            ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") => {
+                let recipe = match bootstrap.bootstrap_arguments.first() {
+                    Some(&recipe) => try!(self.constant(recipe)).0,
+                    None => return Err(Error::Unsupported("malformed concatenation".to_string())),
+                };
+                let recipe = match recipe {
+                    Literal::String(recipe) => recipe,
+                    _ => return Err(Error::Unsupported("malformed concatenation".to_string())),
+                };
+                let mut constants = vec![];
+                for &constant in &bootstrap.bootstrap_arguments[1..] {
+                    constants.push(try!(self.constant(constant)).0);
+                }
+                concat_recipe(&recipe, args, &descriptor.params, constants)
+            }
+            ("java/lang/invoke/StringConcatFactory", "makeConcat") => {
+                let recipe: String = args.iter().map(|_| '\u{1}').collect();
+                concat_recipe(&recipe, args, &descriptor.params, vec![])
+            }
+            _ => {
+                Expr::InvokeDynamic {
+                    name: name,
+                    args: args.into_iter().map(|item| item.expr).collect(),
+                }
+            }
+        };
+        self.push_result(stmts, expr, &descriptor.ret);
+        Ok(())
+    }
+
+    /// Rebuilds a lambda or method reference from the implementation method
+    /// handle of a `LambdaMetafactory` call site capturing `args`.
+    fn lambda(&mut self, handle: u16, args: Vec<Item>) -> Result<Expr> {
+        let (kind, owner, name, descriptor) = try!(self.method_handle(handle));
+        let is_instance = kind != 6 && kind != 8;
+        if owner == self.this_class && name.starts_with("lambda$") {
+            let method = self.class.methods.iter().find(|method| {
+                self.cp[method.name_index].as_utf8() == &name &&
+                self.cp[method.descriptor_index].as_utf8() == &descriptor
+            });
+            if let Some(method) = method {
+                if let Ok(body) = ::method_body(self.class, method) {
+                    return Ok(self.inline_lambda(body, args, is_instance));
+                }
+            }
+        }
+        let mut args = args;
+        let target = if kind == 8 {
+            return Ok(Expr::MethodRef {
+                target: Target::Static(owner),
+                name: "new".to_string(),
+            });
+        } else if is_instance && !args.is_empty() {
+            let receiver = args.remove(0).expr;
+            if kind == 7 && receiver == Expr::This && owner != self.this_class {
+                Target::Super
+            } else {
+                Target::Instance(Box::new(receiver))
+            }
+        } else {
+            Target::Static(owner)
+        };
+        Ok(Expr::MethodRef {
+            target: target,
+            name: name,
+        })
+    }
+
+    /// Moves the body of a lambda implementation method into this method,
+    /// replacing its parameters for captured values with the captured
+    /// expressions.
+    fn inline_lambda(&mut self, body: Body, args: Vec<Item>, is_instance: bool) -> Expr {
+        let captured: Vec<Expr> = args.into_iter()
+            .skip(if is_instance { 1 } else { 0 })
+            .map(|item| item.expr)
+            .collect();
+        let offset = self.vars.len();
+        for (index, var) in body.vars.iter().enumerate() {
+            self.vars.push(var.clone());
+            self.parents.push(offset + index);
+        }
+        let count = captured.len().min(body.params.len());
+        let substitutions: Vec<(VarId, Expr)> = body.params[..count]
+            .iter()
+            .cloned()
+            .zip(captured)
+            .collect();
+        let params: Vec<VarId> = body.params[count..].iter().map(|&var| var + offset).collect();
+        let mut stmts = body.stmts;
+        for stmt in stmts.iter_mut() {
+            stmt.rewrite_exprs(&mut |expr| {
+                let replacement = match *expr {
+                    Expr::Local(var) => {
+                        match substitutions.iter().find(|&&(param, _)| param == var) {
+                            Some(&(_, ref value)) => value.clone(),
+                            None => Expr::Local(var + offset),
+                        }
+                    }
+                    _ => return,
+                };
+                *expr = replacement;
+            });
+            renumber_declarations(stmt, offset);
+        }
+        Expr::Lambda {
+            params: params,
+            body: stmts,
+        }
+    }
+
+    fn utf8(&self, index: u16) -> Result<String> {
+        match self.cp.get(index) {
+            Some(&Constant::Utf8(ref value)) => Ok(value.clone()),
+            _ => Err(invalid_constant(index)),
+        }
+    }
+
+    fn name_and_type(&self, index: u16) -> Result<(String, String)> {
+        match self.cp.get(index) {
+            Some(&Constant::NameAndType(ref name_and_type)) => {
+                Ok((try!(self.utf8(name_and_type.name_index)),
+                    try!(self.utf8(name_and_type.descriptor_index))))
+            }
+            _ => Err(invalid_constant(index)),
+        }
+    }
+
+    /// Resolves a field or method reference into its owner, name and
+    /// descriptor.
+    fn reference(&self, index: u16) -> Result<(String, String, String)> {
+        let entity = match self.cp.get(index) {
+            Some(&Constant::Fieldref(ref entity)) |
+            Some(&Constant::Methodref(ref entity)) |
+            Some(&Constant::InterfaceMethodref(ref entity)) => entity,
+            _ => return Err(invalid_constant(index)),
+        };
+        let owner = try!(self.class_name(entity.class_index));
+        let (name, descriptor) = try!(self.name_and_type(entity.name_and_type_index));
+        Ok((owner, name, descriptor))
+    }
+
+    fn field(&self, index: u16) -> Result<(String, String, FieldType)> {
+        let (owner, name, descriptor) = try!(self.reference(index));
+        Ok((owner, name, try!(FieldType::parse(&descriptor))))
+    }
+
+    fn member(&self, index: u16) -> Result<(String, String, MethodDescriptor)> {
+        let (owner, name, descriptor) = try!(self.reference(index));
+        Ok((owner, name, try!(MethodDescriptor::parse(&descriptor))))
+    }
+
+    fn method_handle(&self, index: u16) -> Result<(u8, String, String, String)> {
+        match self.cp.get(index) {
+            Some(&Constant::MethodHandle { reference_kind, reference_index }) => {
+                let (owner, name, descriptor) = try!(self.reference(reference_index));
+                Ok((reference_kind, owner, name, descriptor))
+            }
+            _ => Err(invalid_constant(index)),
+        }
+    }
+
+    fn class_name(&self, index: u16) -> Result<String> {
+        match self.cp.class_name(index) {
+            Some(name) => Ok(name.clone()),
+            None => Err(invalid_constant(index)),
+        }
+    }
+
+    /// The type named by a `Class` constant, which is either a class name or
+    /// an array descriptor.
+    fn class_type(&self, index: u16) -> Result<FieldType> {
+        let name = try!(self.class_name(index));
+        if name.starts_with('[') {
+            Ok(try!(FieldType::parse(&name)))
+        } else {
+            Ok(FieldType::Object(name))
+        }
+    }
+
+    fn constant(&self, index: u16) -> Result<(Literal, FieldType)> {
+        let constant = match self.cp.get(index) {
+            Some(&Constant::Integer(value)) => (Literal::Int(value), FieldType::Int),
+            Some(&Constant::Float(value)) => (Literal::Float(value), FieldType::Float),
+            Some(&Constant::Long(value)) => (Literal::Long(value), FieldType::Long),
+            Some(&Constant::Double(value)) => (Literal::Double(value), FieldType::Double),
+            Some(&Constant::String(string_index)) => {
+                (Literal::String(try!(self.utf8(string_index))), types::string())
+            }
+            Some(&Constant::Class(_)) => {
+                let ty = try!(self.class_type(index));
+                (Literal::Class(ty), FieldType::Object("java/lang/Class".to_string()))
+            }
+            _ => {
+                return Err(Error::Unsupported(format!("unsupported constant #{}", index)));
+            }
+        };
+        Ok(constant)
+    }
+}
+
+fn invalid_constant(index: u16) -> Error {
+    Error::ClassFile(classfile::Error::InvalidConstantReference(index))
+}
+
+fn assign(target: Expr, value: Expr) -> Stmt {
+    Stmt::Expr(Expr::Assign(Box::new(target), Box::new(value)))
+}
+
+/// The kind letter of the instructions that load and store values of `ty`.
+fn kind_of(ty: &FieldType) -> u8 {
+    match *ty {
+        FieldType::Long => b'l',
+        FieldType::Float => b'f',
+        FieldType::Double => b'd',
+        FieldType::Object(_) | FieldType::Array(_) => b'a',
+        _ => b'i',
+    }
+}
+
+/// A name for a variable without debugging information, from its type and
+/// slot, such as `i3` or `string2`.
+fn slot_name(ty: &FieldType, slot: u16) -> String {
+    let prefix = match *ty {
+        FieldType::Boolean => "flag".to_string(),
+        FieldType::Byte => "b".to_string(),
+        FieldType::Char => "c".to_string(),
+        FieldType::Short => "s".to_string(),
+        FieldType::Int => "i".to_string(),
+        FieldType::Long => "l".to_string(),
+        FieldType::Float => "f".to_string(),
+        FieldType::Double => "d".to_string(),
+        FieldType::Array(_) => "array".to_string(),
+        FieldType::Object(ref name) => {
+            let simple = types::simple_name(name);
+            let mut chars = simple.chars();
+            match chars.next() {
+                Some(first) if first.is_alphabetic() => {
+                    first.to_lowercase().chain(chars).collect()
+                }
+                _ => "obj".to_string(),
+            }
+        }
+    };
+    format!("{}{}", prefix, slot)
+}
+
+fn is_uninitialized(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Uninitialized(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if evaluating `expr` neither has effects nor depends on
+/// anything but local variables, so that it can be moved past a statement
+/// that does not write those variables.
+fn is_pure(expr: &Expr) -> bool {
+    let own = match *expr {
+        Expr::Literal(_) | Expr::Local(_) | Expr::This | Expr::Uninitialized(_) |
+        Expr::Compare(..) | Expr::ArrayLength(_) | Expr::NewArray { .. } |
+        Expr::ArrayInit { .. } | Expr::Unary(..) | Expr::Binary(..) | Expr::Cast(..) |
+        Expr::InstanceOf(..) | Expr::Conditional(..) | Expr::Lambda { .. } |
+        Expr::MethodRef { .. } => true,
+        _ => false,
+    };
+    own && expr.children().into_iter().all(is_pure)
+}
+
+/// Turns an `int` literal into the `char` literal it stands for.
+pub fn to_char(expr: Expr) -> Expr {
+    match expr {
+        Expr::Literal(Literal::Int(value)) if value >= 0 && value <= 0xffff => {
+            Expr::Literal(Literal::Char(value as u16))
+        }
+        other => other,
+    }
+}
+
+/// Turns an `int` literal or a `?:` choosing between `1` and `0` into the
+/// `boolean` it stands for, looking into the operands of `&`, `|` and `^`.
+pub fn to_boolean(expr: Expr) -> Expr {
+    match expr {
+        Expr::Literal(Literal::Int(0)) => Expr::Literal(Literal::Boolean(false)),
+        Expr::Literal(Literal::Int(1)) => Expr::Literal(Literal::Boolean(true)),
+        Expr::Binary(op, left, right) => {
+            match op {
+                BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => {
+                    Expr::binary(op, to_boolean(*left), to_boolean(*right))
+                }
+                _ => Expr::Binary(op, left, right),
+            }
+        }
+        Expr::Conditional(condition, then, otherwise) => {
+            let then = to_boolean(*then);
+            let otherwise = to_boolean(*otherwise);
+            match (then, otherwise) {
+                (Expr::Literal(Literal::Boolean(true)), Expr::Literal(Literal::Boolean(false))) => {
+                    *condition
+                }
+                (Expr::Literal(Literal::Boolean(false)), Expr::Literal(Literal::Boolean(true))) => {
+                    condition.negate()
+                }
+                (then, otherwise) => {
+                    Expr::Conditional(condition, Box::new(then), Box::new(otherwise))
+                }
+            }
+        }
+        other => other,
+    }
+}
+
+/// Adjusts a literal passed where a value of `ty` is expected.
+pub fn coerce(expr: Expr, ty: &FieldType) -> Expr {
+    match *ty {
+        FieldType::Boolean => to_boolean(expr),
+        FieldType::Char => to_char(expr),
+        _ => expr,
+    }
+}
+
+/// The expression for a value passed as a parameter of type `ty`. A `char`
+/// passed as a number needs a cast to pick the same overload, as with
+/// `StringBuilder.append(int)`.
+fn argument(item: Item, ty: &FieldType) -> Expr {
+    if item.ty != FieldType::Char {
+        return coerce(item.expr, ty);
+    }
+    match (item.expr, ty) {
+        (Expr::Literal(Literal::Char(value)), &FieldType::Int) => Expr::int(value as i32),
+        (expr, &FieldType::Int) |
+        (expr, &FieldType::Long) |
+        (expr, &FieldType::Float) |
+        (expr, &FieldType::Double) => Expr::Cast(ty.clone(), Box::new(expr)),
+        (expr, _) => expr,
+    }
+}
+
+/// Rebuilds a string concatenation from a chain of `StringBuilder.append`
+/// calls on a new builder, or returns `None` if `expr` is not one.
+fn concat_chain(expr: &Expr) -> Option<Expr> {
+    let mut operands: Vec<(Expr, bool)> = vec![];
+    let mut current = expr;
+    loop {
+        match *current {
+            Expr::Invoke { target: Target::Instance(ref inner), ref name, ref descriptor, ref args }
+                if name == "append" && args.len() == 1 => {
+                let ty = &descriptor.params[0];
+                operands.push((coerce(args[0].clone(), ty), *ty == types::string()));
+                current = inner;
+            }
+            Expr::New { ref class, ref descriptor, ref args }
+                if class == "java/lang/StringBuilder" || class == "java/lang/StringBuffer" => {
+                match args.len() {
+                    0 => {}
+                    1 if descriptor.params[0] == types::string() => {
+                        operands.push((args[0].clone(), true))
+                    }
+                    _ => return None,
+                }
+                break;
+            }
+            _ => return None,
+        }
+    }
+    operands.reverse();
+    Some(make_concat(operands))
+}
+
+/// Builds a concatenation, starting it with `""` if neither of its first
+/// two operands is a string, so that `+` still means concatenation.
+fn make_concat(operands: Vec<(Expr, bool)>) -> Expr {
+    let is_string = |&(ref expr, string): &(Expr, bool)| {
+        string ||
+        match *expr {
+            Expr::Literal(Literal::String(_)) => true,
+            _ => false,
+        }
+    };
+    let needs_empty = match operands.len() {
+        0 | 1 => !operands.iter().any(&is_string) || operands.is_empty(),
+        _ => !is_string(&operands[0]) && !is_string(&operands[1]),
+    };
+    let mut exprs = vec![];
+    if needs_empty {
+        exprs.push(Expr::Literal(Literal::String(String::new())));
+    }
+    exprs.extend(operands.into_iter().map(|(expr, _)| expr));
+    if exprs.len() == 1 {
+        return exprs.pop().unwrap();
+    }
+    Expr::Concat(exprs)
+}
+
+/// Rebuilds a `StringConcatFactory` concatenation from its recipe, in which
+/// `\u{1}` stands for the next argument and `\u{2}` for the next constant.
+fn concat_recipe(recipe: &str,
+                 args: Vec<Item>,
+                 params: &[FieldType],
+                 constants: Vec<Literal>)
+                 -> Expr {
+    let mut operands = vec![];
+    let mut args = args.into_iter().zip(params.iter());
+    let mut constants = constants.into_iter();
+    let mut text = String::new();
+    for c in recipe.chars() {
+        match c {
+            '\u{1}' | '\u{2}' => {
+                if !text.is_empty() {
+                    operands.push((Expr::Literal(Literal::String(text.clone())), true));
+                    text.clear();
+                }
+                if c == '\u{1}' {
+                    if let Some((item, ty)) = args.next() {
+                        let string = *ty == types::string();
+                        operands.push((argument(item, ty), string));
+                    }
+                } else if let Some(constant) = constants.next() {
+                    operands.push((Expr::Literal(constant), false));
+                }
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        operands.push((Expr::Literal(Literal::String(text)), true));
+    }
+    make_concat(operands)
+}
+
+/// Shifts the variables declared in a lambda body moved into another method.
+fn renumber_declarations(stmt: &mut Stmt, offset: usize) {
+    match *stmt {
+        Stmt::Declare(ref mut var, _) => *var += offset,
+        Stmt::Try { ref mut catches, .. } => {
+            for catch in catches.iter_mut() {
+                catch.var += offset;
+            }
+        }
+        Stmt::Loop { kind: LoopKind::For { init: Some(ref mut init), .. }, .. } => {
+            renumber_declarations(init, offset);
+        }
+        _ => {}
+    }
+    for expr in stmt.exprs_mut() {
+        renumber_lambdas(expr, offset);
+    }
+    for body in stmt.bodies_mut() {
+        for stmt in body.iter_mut() {
+            renumber_declarations(stmt, offset);
+        }
+    }
+}
+
+/// Renumbers the parameters and declarations of the lambdas in `expr`.
+fn renumber_lambdas(expr: &mut Expr, offset: usize) {
+    if let Expr::Lambda { ref mut params, ref mut body } = *expr {
+        for param in params.iter_mut() {
+            *param += offset;
+        }
+        for stmt in body.iter_mut() {
+            renumber_declarations(stmt, offset);
+        }
+        return;
+    }
+    for child in expr.children_mut() {
+        renumber_lambdas(child, offset);
+    }
+}
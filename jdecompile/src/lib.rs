@@ -0,0 +1,95 @@
+//! Turns class files back into Java source.
+//!
+//! Each method is decompiled in three steps. The `builder` runs every basic
+//! block over a stack of expressions, giving statements for the block and
+//! the way control leaves it. `structure` then arranges the blocks into
+//! loops, conditionals, switches and try statements, using labeled blocks
+//! for the jumps that structured statements cannot express. Finally the
+//! `passes` tidy up the result: they restore `for` loops, `finally` blocks,
+//! `?:` expressions and compound assignments, inline temporaries and place
+//! variable declarations. The `printer` writes out the class around the
+//! method bodies.
+extern crate analysis;
+extern crate classfile;
+
+use std::error;
+use std::fmt;
+use std::result;
+
+use classfile::{ClassFile, MethodInfo};
+
+pub mod ast;
+pub mod builder;
+pub mod passes;
+pub mod printer;
+pub mod structure;
+pub mod types;
+
+use ast::Body;
+
+#[derive(Debug)]
+pub enum Error {
+    ClassFile(classfile::Error),
+    /// The method could not be analyzed, for example because its code is
+    /// invalid.
+    Analysis(analysis::Error),
+    /// The method is valid, but uses bytecode the decompiler cannot turn
+    /// into Java, such as subroutines.
+    Unsupported(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ClassFile(ref err) => write!(f, "{}", err),
+            Error::Analysis(ref err) => write!(f, "{}", err),
+            Error::Unsupported(ref message) => write!(f, "unsupported: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "decompiler error"
+    }
+}
+
+impl From<classfile::Error> for Error {
+    fn from(err: classfile::Error) -> Error {
+        Error::ClassFile(err)
+    }
+}
+
+impl From<analysis::Error> for Error {
+    fn from(err: analysis::Error) -> Error {
+        Error::Analysis(err)
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// Decompiles a class into the source of a Java compilation unit.
+///
+/// Methods that cannot be decompiled are still declared, with a comment
+/// giving the reason in place of their body.
+pub fn decompile(class: &ClassFile) -> Result<String> {
+    printer::print_class(class)
+}
+
+/// Decompiles the body of a method, which must have code.
+pub fn method_body(class: &ClassFile, method: &MethodInfo) -> Result<Body> {
+    let code = match method.attrs.code() {
+        Some(code) => code,
+        None => return Err(Error::Unsupported("method has no code".to_string())),
+    };
+    let built = try!(builder::build(class, method, code));
+    let params = built.params.clone();
+    let (stmts, vars) = try!(structure::structure(built));
+    let mut body = Body {
+        vars: vars,
+        params: params,
+        stmts: stmts,
+    };
+    passes::run(class, method, &mut body);
+    Ok(body)
+}
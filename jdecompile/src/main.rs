@@ -0,0 +1,24 @@
+extern crate classfile;
+extern crate env_logger;
+extern crate jdecompile;
+
+use std::env;
+use std::fs::File;
+use std::thread;
+
+use classfile::reader::ClassReader;
+
+fn main() {
+    env_logger::init().unwrap();
+    let class_filename = env::args().nth(1).expect("usage: jdecompile <class file>");
+    let class_file = File::open(class_filename).unwrap();
+    let class = ClassReader::new(class_file).read_class().unwrap();
+    // Deeply nested code makes for deep recursion over the syntax tree.
+    let source = thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(move || jdecompile::decompile(&class).unwrap())
+        .unwrap()
+        .join()
+        .unwrap();
+    print!("{}", source);
+}
@@ -0,0 +1,1492 @@
+//! Rewrites structured method bodies into the forms Java source uses.
+//!
+//! The structuring leaves every jump explicit and every value that crosses
+//! a branch in a temporary. The passes here remove jumps that control would
+//! take anyway, fold temporaries back into the expressions that use them,
+//! recognize `while`, `do`, `for`, `finally` and `synchronized` statements,
+//! and finally declare each variable where it is first needed. The
+//! rewriting passes run until none of them changes the body.
+use std::collections::{HashMap, HashSet};
+
+use classfile::*;
+
+use ast::*;
+use ast::Label;
+use builder::{coerce, to_boolean};
+use types;
+
+/// The most rounds of rewriting to run on one body; each round only makes
+/// the body smaller, so this is only reached for very large methods.
+const MAX_ROUNDS: usize = 64;
+
+/// Runs every pass over the body of `method`.
+pub fn run(class: &ClassFile, method: &MethodInfo, body: &mut Body) {
+    let cp = &class.constants;
+    let name = cp[method.name_index].as_utf8();
+    let descriptor = method.descriptor(cp).ok();
+
+    for _ in 0..MAX_ROUNDS {
+        let before = body.stmts.clone();
+        remove_tail_jumps(&mut body.stmts, &[]);
+        retarget_breaks(&mut body.stmts);
+        unwrap_blocks(&mut body.stmts);
+        simplify_ifs(&mut body.stmts);
+        simplify_switches(&mut body.stmts);
+        conditional_assignments(&mut body.stmts, &body.vars);
+        inline_temporaries(&mut body.stmts, &body.vars);
+        finally_blocks(&mut body.stmts, &body.vars);
+        synchronized_blocks(&mut body.stmts);
+        loops(&mut body.stmts);
+        compound_assignments(&mut body.stmts, &body.vars);
+        if body.stmts == before {
+            break;
+        }
+    }
+
+    float_comparisons(&mut body.stmts);
+    if let Some(ref descriptor) = descriptor {
+        boolean_temporaries(body, &descriptor.ret);
+        coerce_literals(&mut body.stmts, &body.vars, &descriptor.ret);
+        if descriptor.ret.is_none() && body.stmts.last() == Some(&Stmt::Return(None)) {
+            body.stmts.pop();
+        }
+    }
+    if name == "<init>" {
+        // Inner and local classes store their outer instance and captured
+        // values in synthetic fields before calling the super constructor.
+        while body.stmts.first().map_or(false, is_capture) {
+            body.stmts.remove(0);
+        }
+        let implicit = match body.stmts.first() {
+            Some(&Stmt::Expr(Expr::ConstructorCall { is_super: true, ref args, .. })) => {
+                args.is_empty()
+            }
+            _ => false,
+        };
+        if implicit {
+            body.stmts.remove(0);
+        }
+    }
+    generic_copies(body);
+    declare_variables(body);
+}
+
+/// Returns `true` for an assignment to a synthetic field of `this`, such as
+/// `this.this$0 = this$0`.
+fn is_capture(stmt: &Stmt) -> bool {
+    match *stmt {
+        Stmt::Expr(Expr::Assign(ref target, _)) => {
+            match **target {
+                Expr::Field { target: Target::Instance(ref object), ref name, .. } => {
+                    **object == Expr::This && name.contains('$')
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn is_jump(stmt: &Stmt) -> bool {
+    match *stmt {
+        Stmt::Break(_) | Stmt::Continue(_) => true,
+        _ => false,
+    }
+}
+
+/// Removes the `break` and `continue` statements that go where control
+/// would go anyway. `exits` holds the jumps equivalent to falling off the
+/// end of `stmts`.
+fn remove_tail_jumps(stmts: &mut Vec<Stmt>, exits: &[Stmt]) {
+    while stmts.last().map_or(false, |last| is_jump(last) && exits.contains(last)) {
+        stmts.pop();
+    }
+    let len = stmts.len();
+    for index in 0..len {
+        let after: Vec<Stmt> = if index + 1 == len {
+            exits.to_vec()
+        } else if is_jump(&stmts[index + 1]) {
+            vec![stmts[index + 1].clone()]
+        } else {
+            vec![]
+        };
+        match stmts[index] {
+            Stmt::If(_, ref mut then, ref mut otherwise) => {
+                remove_tail_jumps(then, &after);
+                remove_tail_jumps(otherwise, &after);
+            }
+            Stmt::Block { label, ref mut body } => {
+                let mut exits = after.clone();
+                exits.push(Stmt::Break(label));
+                remove_tail_jumps(body, &exits);
+            }
+            Stmt::Loop { label, ref mut body, .. } => {
+                remove_tail_jumps(body, &[Stmt::Continue(label)]);
+            }
+            Stmt::Switch { label, ref mut cases, .. } => {
+                let count = cases.len();
+                for (position, case) in cases.iter_mut().enumerate() {
+                    if position + 1 == count {
+                        let mut exits = after.clone();
+                        exits.push(Stmt::Break(label));
+                        remove_tail_jumps(&mut case.body, &exits);
+                    } else {
+                        remove_tail_jumps(&mut case.body, &[]);
+                    }
+                }
+            }
+            Stmt::Try { ref mut body, ref mut catches, ref mut finally } => {
+                remove_tail_jumps(body, &after);
+                for catch in catches.iter_mut() {
+                    remove_tail_jumps(&mut catch.body, &after);
+                }
+                if let Some(ref mut finally) = *finally {
+                    remove_tail_jumps(finally, &[]);
+                }
+            }
+            Stmt::Synchronized(_, ref mut body) => remove_tail_jumps(body, &after),
+            _ => {}
+        }
+    }
+}
+
+/// Replaces every `break` of `from` in `stmts` with `to`.
+fn replace_jumps(stmts: &mut Vec<Stmt>, from: &Stmt, to: &Stmt) {
+    for stmt in stmts.iter_mut() {
+        if *stmt == *from {
+            *stmt = to.clone();
+        }
+        for body in stmt.bodies_mut() {
+            replace_jumps(body, from, to);
+        }
+    }
+}
+
+/// Makes `break`s of a labeled block that ends with a loop or switch break
+/// that statement instead, and those of a labeled block ending a loop body
+/// continue the loop, so that the labeled block can go.
+fn retarget_breaks(stmts: &mut Vec<Stmt>) {
+    for stmt in stmts.iter_mut() {
+        match *stmt {
+            Stmt::Block { label, ref mut body } => {
+                let target = match body.last() {
+                    Some(&Stmt::Loop { label: inner, .. }) |
+                    Some(&Stmt::Switch { label: inner, .. }) |
+                    Some(&Stmt::Block { label: inner, .. }) => Some(inner),
+                    _ => None,
+                };
+                if let Some(inner) = target {
+                    let last = body.last_mut().unwrap();
+                    for inner_body in last.bodies_mut() {
+                        replace_jumps(inner_body, &Stmt::Break(label), &Stmt::Break(inner));
+                    }
+                }
+            }
+            Stmt::Loop { label, ref mut body, .. } => {
+                if let Some(&mut Stmt::Block { label: inner, body: ref mut inner_body }) =
+                    body.last_mut() {
+                    replace_jumps(inner_body, &Stmt::Break(inner), &Stmt::Continue(label));
+                }
+            }
+            _ => {}
+        }
+        for body in stmt.bodies_mut() {
+            retarget_breaks(body);
+        }
+    }
+}
+
+/// Removes labeled blocks that nothing breaks out of.
+fn unwrap_blocks(stmts: &mut Vec<Stmt>) {
+    let mut index = 0;
+    while index < stmts.len() {
+        let unused = match stmts[index] {
+            Stmt::Block { label, ref body } => !jumps_to(body, label),
+            _ => false,
+        };
+        if unused {
+            let body = match stmts.remove(index) {
+                Stmt::Block { body, .. } => body,
+                _ => unreachable!(),
+            };
+            let count = body.len();
+            for (offset, stmt) in body.into_iter().enumerate() {
+                stmts.insert(index + offset, stmt);
+            }
+            if count == 0 {
+                continue;
+            }
+            continue;
+        }
+        for body in stmts[index].bodies_mut() {
+            unwrap_blocks(body);
+        }
+        index += 1;
+    }
+    // Code after a statement that cannot complete normally is never run.
+    if let Some(end) = stmts.iter().position(|stmt| stmt.is_abrupt()) {
+        stmts.truncate(end + 1);
+    }
+}
+
+/// Puts the branch of an `if` that ends abruptly first and moves the other
+/// one after the `if`, and drops empty `else` branches.
+fn simplify_ifs(stmts: &mut Vec<Stmt>) {
+    let mut index = 0;
+    while index < stmts.len() {
+        for body in stmts[index].bodies_mut() {
+            simplify_ifs(body);
+        }
+        let replacement = match stmts[index] {
+            Stmt::If(ref condition, ref then, ref otherwise) => {
+                if then.is_empty() && !otherwise.is_empty() {
+                    Some((Stmt::If(condition.clone().negate(), otherwise.clone(), vec![]), vec![]))
+                } else if !otherwise.is_empty() && ends_abruptly(then) {
+                    Some((Stmt::If(condition.clone(), then.clone(), vec![]), otherwise.clone()))
+                } else if !then.is_empty() && ends_abruptly(otherwise) && !ends_abruptly(then) {
+                    let negated = Stmt::If(condition.clone().negate(), otherwise.clone(), vec![]);
+                    Some((negated, then.clone()))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some((stmt, rest)) = replacement {
+            stmts[index] = stmt;
+            for (offset, stmt) in rest.into_iter().enumerate() {
+                stmts.insert(index + 1 + offset, stmt);
+            }
+        }
+        index += 1;
+    }
+}
+
+/// Merges empty cases into the case they fall through to, and drops an
+/// empty last case.
+fn simplify_switches(stmts: &mut Vec<Stmt>) {
+    for stmt in stmts.iter_mut() {
+        if let Stmt::Switch { ref mut cases, .. } = *stmt {
+            let mut index = 0;
+            while index + 1 < cases.len() {
+                if cases[index].body.is_empty() {
+                    let keys = cases.remove(index).keys;
+                    let next = &mut cases[index].keys;
+                    let mut merged = keys;
+                    merged.extend(next.drain(..));
+                    merged.sort_by_key(|key| key.map_or((1, 0), |key| (0, key)));
+                    *next = merged;
+                } else {
+                    index += 1;
+                }
+            }
+            if cases.last().map_or(false, |case| case.body.is_empty()) {
+                cases.pop();
+            }
+        }
+        for body in stmt.bodies_mut() {
+            simplify_switches(body);
+        }
+    }
+}
+
+/// The variable assigned by `stmt`, if it is an assignment to a local
+/// variable, together with the value assigned.
+fn local_assignment(stmt: &Stmt) -> Option<(VarId, &Expr)> {
+    match *stmt {
+        Stmt::Expr(Expr::Assign(ref target, ref value)) => {
+            match **target {
+                Expr::Local(var) => Some((var, value)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Turns `if (c) t = a; else t = b;` into `t = c ? a : b` for temporaries,
+/// as that is how the temporary came to be.
+fn conditional_assignments(stmts: &mut Vec<Stmt>, vars: &[Variable]) {
+    for stmt in stmts.iter_mut() {
+        for body in stmt.bodies_mut() {
+            conditional_assignments(body, vars);
+        }
+        let replacement = match *stmt {
+            Stmt::If(ref condition, ref then, ref otherwise) if then.len() == 1 &&
+                                                                 otherwise.len() == 1 => {
+                match (local_assignment(&then[0]), local_assignment(&otherwise[0])) {
+                    (Some((a, then)), Some((b, otherwise))) if a == b && vars[a].synthetic => {
+                        let value = Expr::Conditional(Box::new(condition.clone()),
+                                                      Box::new(then.clone()),
+                                                      Box::new(otherwise.clone()));
+                        Some(Stmt::Expr(Expr::Assign(Box::new(Expr::Local(a)), Box::new(value))))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(replacement) = replacement {
+            *stmt = replacement;
+        }
+    }
+}
+
+/// Counts the occurrences of every variable in `stmts`, including lambda
+/// bodies and declarations.
+fn count_vars(stmts: &[Stmt], counts: &mut HashMap<VarId, usize>) {
+    for stmt in stmts {
+        stmt.walk(&mut |stmt| if let Stmt::Declare(var, _) = *stmt {
+            *counts.entry(var).or_insert(0) += 1;
+        });
+        stmt.walk_exprs(&mut |expr| if let Expr::Local(var) = *expr {
+            *counts.entry(var).or_insert(0) += 1;
+        });
+    }
+}
+
+/// The local variables that evaluating `expr` may assign.
+fn written_vars(expr: &Expr) -> Vec<VarId> {
+    let mut written = vec![];
+    expr.walk(&mut |expr| match *expr {
+        Expr::Assign(ref target, _) |
+        Expr::CompoundAssign(_, ref target, _) |
+        Expr::Increment { ref target, .. } => {
+            if let Expr::Local(var) = **target {
+                written.push(var);
+            }
+        }
+        _ => {}
+    });
+    written
+}
+
+enum Inlined {
+    NotFound,
+    Done,
+    /// The variable is used where the value cannot be moved to.
+    Blocked,
+}
+
+/// Replaces the use of `var` in `expr` with `value`, if everything
+/// evaluated before the use could just as well be evaluated after `value`.
+fn inline_expr(expr: &mut Expr,
+               var: VarId,
+               value: &mut Option<Expr>,
+               written: &[VarId])
+               -> Inlined {
+    if *expr == Expr::Local(var) {
+        *expr = value.take().unwrap();
+        return Inlined::Done;
+    }
+    let conditional = match *expr {
+        Expr::Lambda { .. } => return if expr.count_var(var) > 0 {
+            Inlined::Blocked
+        } else {
+            Inlined::NotFound
+        },
+        Expr::Binary(BinaryOp::LogicalAnd, _, _) |
+        Expr::Binary(BinaryOp::LogicalOr, _, _) |
+        Expr::Conditional(..) => true,
+        _ => false,
+    };
+    let is_assign_target = match *expr {
+        Expr::Assign(ref target, _) => Some((**target).clone()),
+        _ => None,
+    };
+    for (position, child) in expr.children_mut().into_iter().enumerate() {
+        if conditional && position > 0 {
+            return if child.count_var(var) > 0 {
+                Inlined::Blocked
+            } else {
+                Inlined::NotFound
+            };
+        }
+        let is_target = position == 0 && is_assign_target.as_ref() == Some(child);
+        match *child {
+            Expr::Local(other) if is_target && other != var => continue,
+            _ => {}
+        }
+        match inline_expr(child, var, value, written) {
+            Inlined::NotFound => {}
+            result => return result,
+        }
+        let movable = match *child {
+            // Only the operands of an assigned field or array element are
+            // evaluated before the value, and they have just been checked.
+            Expr::Field { .. } | Expr::ArrayElement(..) if is_target => true,
+            Expr::Literal(_) | Expr::This => true,
+            Expr::Local(other) => !written.contains(&other),
+            _ => false,
+        };
+        if !movable {
+            return Inlined::Blocked;
+        }
+    }
+    Inlined::NotFound
+}
+
+/// The expressions of `stmt` that are evaluated once, before anything else
+/// the statement does.
+fn leading_expr(stmt: &mut Stmt) -> Option<&mut Expr> {
+    match *stmt {
+        Stmt::Expr(ref mut expr) |
+        Stmt::If(ref mut expr, _, _) |
+        Stmt::Switch { value: ref mut expr, .. } |
+        Stmt::Synchronized(ref mut expr, _) |
+        Stmt::MonitorEnter(ref mut expr) |
+        Stmt::MonitorExit(ref mut expr) |
+        Stmt::Throw(ref mut expr) |
+        Stmt::Declare(_, Some(ref mut expr)) |
+        Stmt::Return(Some(ref mut expr)) => Some(expr),
+        _ => None,
+    }
+}
+
+/// Folds temporaries assigned once and used once, in the next statement,
+/// into the expression using them. A temporary holding a value that the
+/// next statements then increment becomes `x++` or `++x`.
+fn inline_temporaries(stmts: &mut Vec<Stmt>, vars: &[Variable]) {
+    let mut counts = HashMap::new();
+    count_vars(stmts, &mut counts);
+    inline_in(stmts, vars, &counts);
+}
+
+fn inline_in(stmts: &mut Vec<Stmt>, vars: &[Variable], counts: &HashMap<VarId, usize>) {
+    for stmt in stmts.iter_mut() {
+        for body in stmt.bodies_mut() {
+            inline_in(body, vars, counts);
+        }
+    }
+    let mut index = 0;
+    while index + 1 < stmts.len() {
+        if increments(stmts, index, vars, counts) {
+            continue;
+        }
+        let (var, value) = match local_assignment(&stmts[index]) {
+            Some((var, value)) if vars[var].synthetic && counts.get(&var) == Some(&2) => {
+                (var, value.clone())
+            }
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        let written = written_vars(&value);
+        let mut value = Some(value);
+        let done = match leading_expr(&mut stmts[index + 1]) {
+            Some(expr) => {
+                match inline_expr(expr, var, &mut value, &written) {
+                    Inlined::Done => true,
+                    _ => false,
+                }
+            }
+            None => false,
+        };
+        if done {
+            stmts.remove(index);
+            index = index.saturating_sub(1);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// The operation of an expression adding or subtracting one to `operand`.
+fn plus_minus_one(expr: &Expr) -> Option<(bool, &Expr)> {
+    let expr = match *expr {
+        Expr::Cast(_, ref inner) => inner,
+        ref expr => expr,
+    };
+    match *expr {
+        Expr::Binary(op, ref left, ref right) if op == BinaryOp::Add || op == BinaryOp::Sub => {
+            match **right {
+                Expr::Literal(Literal::Int(1)) |
+                Expr::Literal(Literal::Long(1)) => Some((op == BinaryOp::Sub, left)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Rebuilds `x++` and `++x` used as values from `t = x; x = t + 1` and
+/// `t = x + 1; x = t` followed by a use of `t`.
+fn increments(stmts: &mut Vec<Stmt>,
+              index: usize,
+              vars: &[Variable],
+              counts: &HashMap<VarId, usize>)
+              -> bool {
+    if index + 2 >= stmts.len() {
+        return false;
+    }
+    let (temp, first) = match local_assignment(&stmts[index]) {
+        Some((var, value)) if vars[var].synthetic && counts.get(&var) == Some(&3) => {
+            (var, value.clone())
+        }
+        _ => return false,
+    };
+    let (target, second) = match stmts[index + 1] {
+        Stmt::Expr(Expr::Assign(ref target, ref value)) => ((**target).clone(), (**value).clone()),
+        _ => return false,
+    };
+    if !is_plain_target(&target) {
+        return false;
+    }
+    let increment = match plus_minus_one(&second) {
+        // t = x; x = t + 1
+        Some((decrement, operand)) if *operand == Expr::Local(temp) && first == target => {
+            Expr::Increment {
+                target: Box::new(target),
+                prefix: false,
+                decrement: decrement,
+            }
+        }
+        _ => {
+            // t = x + 1; x = t
+            match plus_minus_one(&first) {
+                Some((decrement, operand)) if *operand == target && second == Expr::Local(temp) => {
+                    Expr::Increment {
+                        target: Box::new(target),
+                        prefix: true,
+                        decrement: decrement,
+                    }
+                }
+                _ => return false,
+            }
+        }
+    };
+    let mut value = Some(increment);
+    let done = match leading_expr(&mut stmts[index + 2]) {
+        Some(expr) => {
+            match inline_expr(expr, temp, &mut value, &[]) {
+                Inlined::Done => true,
+                _ => false,
+            }
+        }
+        None => false,
+    };
+    if done {
+        stmts.drain(index..index + 2);
+    }
+    done
+}
+
+/// Returns `true` for a variable, or a field of a variable, `this` or a
+/// class, which can be read and written again without any effect.
+fn is_plain_target(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Local(_) => true,
+        Expr::Field { target: Target::Static(_), .. } => true,
+        Expr::Field { target: Target::Instance(ref object), .. } => object.is_simple(),
+        Expr::ArrayElement(ref array, ref index) => array.is_simple() && index.is_simple(),
+        _ => false,
+    }
+}
+
+/// Labels of the loops, switches and blocks inside `stmts`.
+fn defined_labels(stmts: &[Stmt], labels: &mut HashSet<Label>) {
+    for stmt in stmts {
+        match *stmt {
+            Stmt::Loop { label, .. } |
+            Stmt::Block { label, .. } |
+            Stmt::Switch { label, .. } => {
+                labels.insert(label);
+            }
+            _ => {}
+        }
+        for body in stmt.bodies() {
+            defined_labels(body, labels);
+        }
+    }
+}
+
+/// A copy of `stmts` with labels and temporaries renumbered in order of
+/// appearance, so that copies of the same code compare equal.
+fn canonical(stmts: &[Stmt], vars: &[Variable]) -> Vec<Stmt> {
+    let mut labels = HashMap::new();
+    let mut temps = HashMap::new();
+    let mut result = stmts.to_vec();
+    for stmt in result.iter_mut() {
+        canonical_stmt(stmt, vars, &mut labels, &mut temps);
+    }
+    result
+}
+
+fn canonical_stmt(stmt: &mut Stmt,
+                  vars: &[Variable],
+                  labels: &mut HashMap<Label, Label>,
+                  temps: &mut HashMap<VarId, VarId>) {
+    {
+        let mut rename = |label: &mut Label| {
+            let next = labels.len();
+            *label = *labels.entry(*label).or_insert(next);
+        };
+        match *stmt {
+            Stmt::Loop { ref mut label, .. } |
+            Stmt::Block { ref mut label, .. } |
+            Stmt::Switch { ref mut label, .. } |
+            Stmt::Break(ref mut label) |
+            Stmt::Continue(ref mut label) => rename(label),
+            _ => {}
+        }
+    }
+    {
+        let mut rename_var = |var: &mut VarId| if vars[*var].synthetic {
+            let next = usize::max_value() - temps.len();
+            *var = *temps.entry(*var).or_insert(next);
+        };
+        if let Stmt::Declare(ref mut var, _) = *stmt {
+            rename_var(var);
+        }
+        for expr in stmt.exprs_mut() {
+            expr.rewrite(&mut |expr| if let Expr::Local(ref mut var) = *expr {
+                rename_var(var);
+            });
+        }
+    }
+    for body in stmt.bodies_mut() {
+        for stmt in body.iter_mut() {
+            canonical_stmt(stmt, vars, labels, temps);
+        }
+    }
+}
+
+/// Removes the copy of `finally` before every statement in `stmts` that
+/// leaves the try statement, returning `false` if one is missing.
+fn strip_exit_copies(stmts: &mut Vec<Stmt>,
+                     finally: &[Stmt],
+                     inner_labels: &HashSet<Label>,
+                     vars: &[Variable])
+                     -> bool {
+    let mut index = 0;
+    while index < stmts.len() {
+        let exits = match stmts[index] {
+            Stmt::Return(_) => true,
+            Stmt::Break(label) | Stmt::Continue(label) => !inner_labels.contains(&label),
+            _ => false,
+        };
+        if exits {
+            if index < finally.len() {
+                return false;
+            }
+            let start = index - finally.len();
+            if canonical(&stmts[start..index], vars) != finally {
+                return false;
+            }
+            stmts.drain(start..index);
+            index = start + 1;
+            continue;
+        }
+        if let Stmt::Try { finally: Some(_), .. } = stmts[index] {
+            // Exits from a nested try statement with its own finally block
+            // run that block first, so the copies are not where expected.
+            let mut nested_exits = false;
+            stmts[index].walk(&mut |stmt| match *stmt {
+                Stmt::Return(_) => nested_exits = true,
+                Stmt::Break(label) | Stmt::Continue(label) if !inner_labels.contains(&label) => {
+                    nested_exits = true
+                }
+                _ => {}
+            });
+            if nested_exits {
+                return false;
+            }
+        }
+        for body in stmts[index].bodies_mut() {
+            if !strip_exit_copies(body, finally, inner_labels, vars) {
+                return false;
+            }
+        }
+        index += 1;
+    }
+    true
+}
+
+/// Rebuilds `finally` blocks from the handler catching everything that
+/// runs the block and rethrows, and the copies of the block before every
+/// other way out of the try statement.
+fn finally_blocks(stmts: &mut Vec<Stmt>, vars: &[Variable]) {
+    let mut index = 0;
+    while index < stmts.len() {
+        if let Some(rest) = try_finally(stmts, index, vars) {
+            let after = stmts.split_off(index + 1);
+            stmts.extend(after.into_iter().skip(rest));
+        }
+        return_into_try(stmts, index);
+        for body in stmts[index].bodies_mut() {
+            finally_blocks(body, vars);
+        }
+        index += 1;
+    }
+}
+
+/// Turns the try statement at `index` into one with a `finally` block if it
+/// has the form javac compiles those to, returning the number of
+/// statements after it that were the copy of the block run after the try
+/// statement completes normally.
+fn try_finally(stmts: &mut Vec<Stmt>, index: usize, vars: &[Variable]) -> Option<usize> {
+    let (body, catches) = match stmts[index] {
+        Stmt::Try { ref body, ref catches, finally: None } => (body, catches),
+        _ => return None,
+    };
+    let any = match catches.last() {
+        Some(any) if any.types.is_empty() => any,
+        _ => return None,
+    };
+    let len = any.body.len();
+    if len < 2 || any.body[len - 1] != Stmt::Throw(Expr::Local(any.var)) {
+        return None;
+    }
+    let finally = any.body[..len - 1].to_vec();
+    if ends_abruptly(&finally) || finally.iter().any(|stmt| stmt.count_var(any.var) > 0) {
+        return None;
+    }
+    let pattern = canonical(&finally, vars);
+
+    let mut inner_labels = HashSet::new();
+    defined_labels(body, &mut inner_labels);
+    let mut body = body.clone();
+    let mut catches: Vec<Catch> = catches[..catches.len() - 1].to_vec();
+    for catch in &catches {
+        defined_labels(&catch.body, &mut inner_labels);
+    }
+    if !strip_exit_copies(&mut body, &pattern, &inner_labels, vars) {
+        return None;
+    }
+    for catch in catches.iter_mut() {
+        if !strip_exit_copies(&mut catch.body, &pattern, &inner_labels, vars) {
+            return None;
+        }
+    }
+
+    // The copy run when the try statement completes normally ends each
+    // part that can, or else starts the code after the try statement.
+    let mut parts: Vec<&mut Vec<Stmt>> = vec![&mut body];
+    parts.extend(catches.iter_mut().map(|catch| &mut catch.body));
+    let completing: Vec<&mut Vec<Stmt>> =
+        parts.into_iter().filter(|part| !ends_abruptly(part)).collect();
+    let mut rest = 0;
+    if !completing.is_empty() {
+        let tails_match = completing.iter().all(|part| {
+            part.len() >= finally.len() &&
+            canonical(&part[part.len() - finally.len()..], vars) == pattern &&
+            !ends_abruptly(&part[..part.len() - finally.len()])
+        });
+        if tails_match && !finally.is_empty() {
+            for part in completing {
+                let len = part.len() - finally.len();
+                part.truncate(len);
+            }
+        } else {
+            let end = index + 1 + finally.len();
+            if end > stmts.len() || canonical(&stmts[index + 1..end], vars) != pattern {
+                return None;
+            }
+            rest = finally.len();
+        }
+    }
+
+    // A try statement with only a finally block around one with only catch
+    // clauses is a single statement in the source.
+    let nested = if catches.is_empty() && body.len() == 1 {
+        match body[0] {
+            Stmt::Try { finally: None, .. } => true,
+            _ => false,
+        }
+    } else {
+        false
+    };
+    if nested {
+        if let Stmt::Try { body: inner_body, catches: inner_catches, .. } = body.remove(0) {
+            body = inner_body;
+            catches = inner_catches;
+        }
+    }
+    stmts[index] = Stmt::Try {
+        body: body,
+        catches: catches,
+        finally: Some(finally),
+    };
+    Some(rest)
+}
+
+/// Moves a `return` right after a try statement whose catch clauses all end
+/// abruptly into the end of the try block, where it reads as the source
+/// would have it.
+fn return_into_try(stmts: &mut Vec<Stmt>, index: usize) {
+    if index + 1 >= stmts.len() {
+        return;
+    }
+    let movable = match stmts[index + 1] {
+        Stmt::Return(None) => true,
+        Stmt::Return(Some(Expr::Local(var))) => {
+            match stmts[index] {
+                Stmt::Try { finally: Some(ref finally), .. } => {
+                    !finally.iter().any(|stmt| {
+                        let mut writes = false;
+                        stmt.walk_exprs(&mut |expr| if written_vars(expr).contains(&var) {
+                            writes = true;
+                        });
+                        writes
+                    })
+                }
+                _ => true,
+            }
+        }
+        Stmt::Return(Some(Expr::Literal(_))) => true,
+        _ => false,
+    };
+    if !movable {
+        return;
+    }
+    let fits = match stmts[index] {
+        Stmt::Try { ref body, ref catches, .. } => {
+            !body.is_empty() && !ends_abruptly(body) &&
+            catches.iter().all(|catch| ends_abruptly(&catch.body))
+        }
+        _ => false,
+    };
+    if fits {
+        let stmt = stmts.remove(index + 1);
+        if let Stmt::Try { ref mut body, .. } = stmts[index] {
+            body.push(stmt);
+        }
+    }
+}
+
+/// Turns a monitor entered around a try statement whose finally block
+/// exits it into a `synchronized` statement.
+fn synchronized_blocks(stmts: &mut Vec<Stmt>) {
+    let mut index = 0;
+    while index + 1 < stmts.len() {
+        let (monitor, value) = match stmts[index] {
+            Stmt::MonitorEnter(ref expr) => {
+                match *expr {
+                    Expr::Assign(ref target, ref value) => {
+                        ((**target).clone(), Some((**value).clone()))
+                    }
+                    ref other => (other.clone(), None),
+                }
+            }
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        let matches = match stmts[index + 1] {
+            Stmt::Try { ref catches, finally: Some(ref finally), .. } => {
+                catches.is_empty() && finally.len() == 1 &&
+                (finally[0] == Stmt::MonitorExit(monitor.clone()) || index > 0 &&
+                 match local_assignment(&stmts[index - 1]) {
+                    Some((var, value)) => {
+                        finally[0] == Stmt::MonitorExit(Expr::Local(var)) &&
+                        (*value == monitor || Expr::Local(var) == monitor)
+                    }
+                    None => false,
+                })
+            }
+            _ => false,
+        };
+        if !matches {
+            index += 1;
+            continue;
+        }
+        let body = match stmts.remove(index + 1) {
+            Stmt::Try { body, .. } => body,
+            _ => unreachable!(),
+        };
+        let lock = match value {
+            Some(value) => value,
+            None => {
+                match (index > 0, monitor.clone()) {
+                    (true, Expr::Local(_)) => {
+                        match stmts[index - 1].clone() {
+                            Stmt::Expr(Expr::Assign(_, value)) => {
+                                stmts.remove(index - 1);
+                                index -= 1;
+                                *value
+                            }
+                            _ => monitor,
+                        }
+                    }
+                    (true, ref other) => {
+                        if local_assignment(&stmts[index - 1]).map_or(false, |(_, v)| v == other) {
+                            stmts.remove(index - 1);
+                            index -= 1;
+                        }
+                        monitor
+                    }
+                    _ => monitor,
+                }
+            }
+        };
+        stmts[index] = Stmt::Synchronized(lock, body);
+        index += 1;
+    }
+    for stmt in stmts.iter_mut() {
+        for body in stmt.bodies_mut() {
+            synchronized_blocks(body);
+        }
+    }
+}
+
+/// Recognizes `while`, `do` and `for` loops among infinite loops.
+fn loops(stmts: &mut Vec<Stmt>) {
+    for stmt in stmts.iter_mut() {
+        for body in stmt.bodies_mut() {
+            loops(body);
+        }
+        if let Stmt::Loop { label, ref mut kind, ref mut body } = *stmt {
+            if *kind != LoopKind::Infinite {
+                continue;
+            }
+            let leading = match body.first() {
+                Some(&Stmt::If(ref condition, ref then, ref otherwise))
+                    if otherwise.is_empty() && *then == vec![Stmt::Break(label)] => {
+                    Some(condition.clone())
+                }
+                _ => None,
+            };
+            if let Some(condition) = leading {
+                body.remove(0);
+                *kind = LoopKind::While(condition.negate());
+                continue;
+            }
+            let trailing = match body.last() {
+                Some(&Stmt::If(ref condition, ref then, ref otherwise))
+                    if otherwise.is_empty() && *then == vec![Stmt::Break(label)] => {
+                    Some(condition.clone())
+                }
+                _ => None,
+            };
+            if let Some(condition) = trailing {
+                let len = body.len();
+                let continues = body[..len - 1].iter().any(|stmt| {
+                    let mut found = false;
+                    for inner in stmt.bodies() {
+                        found = found || contains_continue(inner, label);
+                    }
+                    found || *stmt == Stmt::Continue(label)
+                });
+                if !continues {
+                    body.pop();
+                    *kind = LoopKind::DoWhile(condition.negate());
+                }
+            }
+        }
+    }
+    for_loops(stmts);
+}
+
+fn contains_continue(stmts: &[Stmt], label: Label) -> bool {
+    stmts.iter().any(|stmt| {
+        *stmt == Stmt::Continue(label) ||
+        stmt.bodies().into_iter().any(|body| contains_continue(body, label))
+    })
+}
+
+/// The local variable an update expression of a `for` loop changes.
+fn updated_var(expr: &Expr) -> Option<VarId> {
+    match *expr {
+        Expr::Assign(ref target, _) |
+        Expr::CompoundAssign(_, ref target, _) |
+        Expr::Increment { ref target, .. } => {
+            match **target {
+                Expr::Local(var) => Some(var),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Turns `v = a; while (c) { ...; v++; }` into a `for` loop when the
+/// condition tests `v`.
+fn for_loops(stmts: &mut Vec<Stmt>) {
+    let mut index = 1;
+    while index < stmts.len() {
+        let init_var = local_assignment(&stmts[index - 1]).map(|(var, _)| var);
+        let converted = match stmts[index] {
+            Stmt::Loop { label, kind: LoopKind::While(ref condition), ref mut body } => {
+                let update = match body.last() {
+                    Some(&Stmt::Expr(ref update)) => Some(update.clone()),
+                    _ => None,
+                };
+                match (init_var, update) {
+                    (Some(var), Some(update)) if updated_var(&update) == Some(var) &&
+                                                 condition.count_var(var) > 0 => {
+                        body.pop();
+                        // A block the body ends with, which `continue` in the
+                        // source breaks out of to reach the update.
+                        let continue_block = match body.last() {
+                            Some(&Stmt::Block { label, .. }) => Some(label),
+                            _ => None,
+                        };
+                        if contains_continue(body, label) {
+                            body.push(Stmt::Expr(update));
+                            None
+                        } else {
+                            if let Some(block) = continue_block {
+                                if let Some(Stmt::Block { body: inner, .. }) = body.pop() {
+                                    let mut inner = inner;
+                                    replace_jumps(&mut inner,
+                                                  &Stmt::Break(block),
+                                                  &Stmt::Continue(label));
+                                    body.extend(inner);
+                                }
+                            }
+                            Some((condition.clone(), update))
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some((condition, update)) = converted {
+            let init = stmts.remove(index - 1);
+            index -= 1;
+            if let Stmt::Loop { ref mut kind, .. } = stmts[index] {
+                *kind = LoopKind::For {
+                    init: Some(Box::new(init)),
+                    condition: condition,
+                    update: vec![update],
+                };
+            }
+        }
+        index += 1;
+    }
+}
+
+/// The declared type of an assignable expression, where it is known.
+fn target_type(expr: &Expr, vars: &[Variable]) -> Option<FieldType> {
+    match *expr {
+        Expr::Local(var) => Some(types::erasure(&vars[var].ty)),
+        Expr::Field { ref descriptor, .. } => Some(descriptor.clone()),
+        Expr::ArrayElement(ref array, _) => {
+            match target_type(array, vars) {
+                Some(FieldType::Array(component)) => Some(*component),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Turns `x = x op y` into `x op= y`, and `x += 1` statements into `x++`.
+fn compound_assignments(stmts: &mut Vec<Stmt>, vars: &[Variable]) {
+    for stmt in stmts.iter_mut() {
+        for expr in stmt.exprs_mut() {
+            expr.rewrite(&mut |expr| {
+                let replacement = match *expr {
+                    Expr::Assign(ref target, ref value) if is_plain_target(target) => {
+                        let ty = target_type(target, vars);
+                        let (narrowed, inner) = match **value {
+                            Expr::Cast(ref cast, ref inner) if Some(cast) == ty.as_ref() => {
+                                (true, &**inner)
+                            }
+                            ref other => (false, other),
+                        };
+                        match *inner {
+                            Expr::Binary(op, ref left, ref right)
+                                if **left == **target && !op.is_comparison() &&
+                                   op != BinaryOp::LogicalAnd &&
+                                   op != BinaryOp::LogicalOr &&
+                                   (narrowed || !is_narrow(&ty)) => {
+                                Some(Expr::CompoundAssign(op, target.clone(), right.clone()))
+                            }
+                            Expr::Concat(ref parts) if parts.len() == 2 && parts[0] == **target => {
+                                Some(Expr::CompoundAssign(BinaryOp::Add,
+                                                          target.clone(),
+                                                          Box::new(parts[1].clone())))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(replacement) = replacement {
+                    *expr = replacement;
+                }
+            });
+        }
+        let increment = match *stmt {
+            Stmt::Expr(Expr::CompoundAssign(op, ref target, ref value)) if op == BinaryOp::Add ||
+                                                                           op == BinaryOp::Sub => {
+                match **value {
+                    Expr::Literal(Literal::Int(1)) |
+                    Expr::Literal(Literal::Long(1)) => {
+                        Some(Expr::Increment {
+                            target: target.clone(),
+                            prefix: false,
+                            decrement: op == BinaryOp::Sub,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(increment) = increment {
+            *stmt = Stmt::Expr(increment);
+        }
+        if let Stmt::Loop { kind: LoopKind::For { ref mut update, .. }, .. } = *stmt {
+            for expr in update.iter_mut() {
+                let increment = match *expr {
+                    Expr::CompoundAssign(op, ref target, ref value) if op == BinaryOp::Add ||
+                                                                       op == BinaryOp::Sub => {
+                        match **value {
+                            Expr::Literal(Literal::Int(1)) => {
+                                Some(Expr::Increment {
+                                    target: target.clone(),
+                                    prefix: false,
+                                    decrement: op == BinaryOp::Sub,
+                                })
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(increment) = increment {
+                    *expr = increment;
+                }
+            }
+        }
+        for body in stmt.bodies_mut() {
+            compound_assignments(body, vars);
+        }
+    }
+}
+
+/// Returns `true` for the types that arithmetic widens to `int`, where
+/// `x = x + y` needs a cast that `x += y` includes.
+fn is_narrow(ty: &Option<FieldType>) -> bool {
+    match *ty {
+        Some(FieldType::Byte) | Some(FieldType::Char) | Some(FieldType::Short) => true,
+        _ => false,
+    }
+}
+
+/// Writes `int` literals standing for `boolean` and `char` values as such,
+/// using the types of the variables, fields, parameters and return values
+/// they are assigned or passed to, and casts those passed as `byte` or
+/// `short` arguments.
+fn coerce_literals(stmts: &mut Vec<Stmt>, vars: &[Variable], ret: &Option<FieldType>) {
+    for stmt in stmts.iter_mut() {
+        for expr in stmt.exprs_mut() {
+            expr.rewrite(&mut |expr| coerce_expr(expr, vars));
+        }
+        if let Stmt::Return(Some(ref mut value)) = *stmt {
+            if let Some(ref ret) = *ret {
+                let old = value.clone();
+                *value = coerce(old, ret);
+            }
+        }
+        if let Stmt::Declare(var, Some(ref mut value)) = *stmt {
+            let old = value.clone();
+            *value = coerce(old, &types::erasure(&vars[var].ty));
+        }
+        for body in stmt.bodies_mut() {
+            coerce_literals(body, vars, ret);
+        }
+    }
+}
+
+fn coerce_expr(expr: &mut Expr, vars: &[Variable]) {
+    match *expr {
+        Expr::Assign(ref target, ref mut value) => {
+            if let Some(ty) = target_type(target, vars) {
+                let old = (**value).clone();
+                **value = coerce(old, &ty);
+            }
+        }
+        Expr::Invoke { ref descriptor, ref mut args, .. } |
+        Expr::New { ref descriptor, ref mut args, .. } |
+        Expr::ConstructorCall { ref descriptor, ref mut args, .. } => {
+            for (arg, ty) in args.iter_mut().zip(descriptor.params.iter()) {
+                let old = arg.clone();
+                *arg = match (old, ty) {
+                    // Unlike assignments, calls do not narrow constants.
+                    (Expr::Literal(Literal::Int(value)), &FieldType::Byte) |
+                    (Expr::Literal(Literal::Int(value)), &FieldType::Short) => {
+                        Expr::Cast(ty.clone(), Box::new(Expr::int(value)))
+                    }
+                    (old, ty) => coerce(old, ty),
+                };
+            }
+        }
+        Expr::ArrayInit { ref ty, ref mut elements } => {
+            if let FieldType::Array(ref component) = *ty {
+                for element in elements.iter_mut() {
+                    let old = element.clone();
+                    *element = coerce(old, component);
+                }
+            }
+        }
+        _ => {}
+    }
+    let replacement = match *expr {
+        Expr::Binary(op, ref left, ref right) if op == BinaryOp::Eq || op == BinaryOp::Ne => {
+            match (&**left, &**right) {
+                (&Expr::Conditional(..), &Expr::Literal(Literal::Int(0))) => {
+                    match to_boolean((**left).clone()) {
+                        Expr::Conditional(..) => None,
+                        condition => {
+                            Some(if op == BinaryOp::Eq { condition.negate() } else { condition })
+                        }
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    if let Some(replacement) = replacement {
+        *expr = replacement;
+    }
+}
+
+/// Turns the tests of `lcmp`, `fcmpl` and friends against zero into
+/// comparisons of their operands. A branch is taken for NaN operands exactly
+/// when the test holds for the NaN result of the instruction, which needs a
+/// `!` where Java's comparison would be false.
+fn float_comparisons(stmts: &mut Vec<Stmt>) {
+    for stmt in stmts.iter_mut() {
+        stmt.rewrite_exprs(&mut |expr| {
+            let replacement = match *expr {
+                Expr::Binary(op, ref compare, ref zero) if op.is_comparison() &&
+                                                           **zero == Expr::int(0) => {
+                    match **compare {
+                        Expr::Compare(nan, ref a, ref b) => {
+                            let nan_taken = match op {
+                                BinaryOp::Eq => nan == 0,
+                                BinaryOp::Ne => nan != 0,
+                                BinaryOp::Lt => nan < 0,
+                                BinaryOp::Ge => nan >= 0,
+                                BinaryOp::Gt => nan > 0,
+                                _ => nan <= 0,
+                            };
+                            let (a, b) = (a.clone(), b.clone());
+                            if nan_taken && nan != 0 && op != BinaryOp::Ne {
+                                let negated = op.negated().unwrap();
+                                Expr::Unary(UnaryOp::Not, Box::new(Expr::Binary(negated, a, b)))
+                            } else {
+                                Expr::Binary(op, a, b)
+                            }
+                        }
+                        _ => return,
+                    }
+                }
+                _ => return,
+            };
+            *expr = replacement;
+        });
+    }
+}
+
+/// Types the temporaries that hold `boolean` values as `boolean`, going by
+/// where they are used, so that the values assigned to them are written as
+/// `true` and `false`.
+fn boolean_temporaries(body: &mut Body, ret: &Option<FieldType>) {
+    let boolean = TypeSignature::Base(FieldType::Boolean);
+    loop {
+        let mut found = HashSet::new();
+        {
+            let vars = &body.vars;
+            let mut check = |expr: &Expr, ty: Option<FieldType>| {
+                if let Expr::Local(var) = *expr {
+                    if ty == Some(FieldType::Boolean) && vars[var].synthetic &&
+                       vars[var].ty == TypeSignature::Base(FieldType::Int) {
+                        found.insert(var);
+                    }
+                }
+            };
+            own_stmts(&body.stmts, &mut |stmt| match *stmt {
+                Stmt::Return(Some(ref value)) => check(value, ret.clone()),
+                Stmt::Declare(var, Some(ref value)) => {
+                    check(value, Some(types::erasure(&vars[var].ty)))
+                }
+                _ => {}
+            });
+            for stmt in body.stmts.iter() {
+                stmt.walk_exprs(&mut |expr| match *expr {
+                    Expr::Assign(ref target, ref value) => check(value, target_type(target, vars)),
+                    Expr::Invoke { ref descriptor, ref args, .. } |
+                    Expr::New { ref descriptor, ref args, .. } |
+                    Expr::ConstructorCall { ref descriptor, ref args, .. } => {
+                        for (arg, ty) in args.iter().zip(descriptor.params.iter()) {
+                            check(arg, Some(ty.clone()));
+                        }
+                    }
+                    _ => {}
+                });
+            }
+        }
+        if found.is_empty() {
+            return;
+        }
+        for var in found {
+            body.vars[var].ty = boolean.clone();
+        }
+    }
+}
+
+/// Calls `f` on every statement in `stmts` and nested inside them, but not
+/// on those of lambdas.
+fn own_stmts(stmts: &[Stmt], f: &mut FnMut(&Stmt)) {
+    for stmt in stmts {
+        f(stmt);
+        for body in stmt.bodies() {
+            own_stmts(body, f);
+        }
+    }
+}
+
+/// Gives a temporary that is only ever a copy of another variable the
+/// generic type of that variable, as for the array javac copies in an
+/// enhanced `for` loop.
+fn generic_copies(body: &mut Body) {
+    let mut sources: HashMap<VarId, Vec<Option<VarId>>> = HashMap::new();
+    for stmt in body.stmts.iter() {
+        stmt.walk(&mut |stmt| {
+            let assigned = match *stmt {
+                Stmt::Declare(var, Some(ref value)) => Some((var, value)),
+                _ => None,
+            };
+            if let Some((var, value)) = assigned {
+                sources.entry(var).or_insert_with(Vec::new).push(copy_of(value));
+            }
+        });
+        stmt.walk_exprs(&mut |expr| {
+            if let Expr::Assign(ref target, ref value) = *expr {
+                if let Expr::Local(var) = **target {
+                    sources.entry(var).or_insert_with(Vec::new).push(copy_of(value));
+                }
+            }
+        });
+    }
+    for (var, sources) in sources {
+        if !body.vars[var].synthetic || sources.len() != 1 {
+            continue;
+        }
+        if let Some(source) = sources[0] {
+            let ty = body.vars[source].ty.clone();
+            if ty != body.vars[var].ty &&
+               types::erasure(&ty) == types::erasure(&body.vars[var].ty) {
+                body.vars[var].ty = ty;
+            }
+        }
+    }
+}
+
+fn copy_of(value: &Expr) -> Option<VarId> {
+    match *value {
+        Expr::Local(source) => Some(source),
+        _ => None,
+    }
+}
+
+/// Where a variable occurs within a statement list.
+fn occurs(stmt: &Stmt, var: VarId) -> bool {
+    stmt.count_var(var) > 0
+}
+
+/// Declares every variable that needs it at the innermost statement list
+/// containing all its uses, merging the declaration into the first
+/// assignment where possible, and renames variables that share a name.
+fn declare_variables(body: &mut Body) {
+    for var in 0..body.vars.len() {
+        if body.vars[var].declared {
+            continue;
+        }
+        if declare_in(&mut body.stmts, var) {
+            body.vars[var].declared = true;
+        }
+    }
+
+    // Give distinct variables distinct names.
+    let mut used = HashSet::new();
+    let mut counts: HashMap<VarId, usize> = HashMap::new();
+    count_vars(&body.stmts, &mut counts);
+    for &param in &body.params {
+        counts.insert(param, 1);
+    }
+    let mut order: Vec<VarId> = counts.keys().cloned().collect();
+    order.sort();
+    // Parameters keep their names.
+    for &param in &body.params {
+        used.insert(body.vars[param].name.clone());
+    }
+    for var in order {
+        if body.params.contains(&var) {
+            continue;
+        }
+        let name = body.vars[var].name.clone();
+        if used.insert(name.clone()) {
+            continue;
+        }
+        let mut suffix = 2;
+        while !used.insert(format!("{}{}", name, suffix)) {
+            suffix += 1;
+        }
+        body.vars[var].name = format!("{}{}", name, suffix);
+    }
+}
+
+fn declare_in(stmts: &mut Vec<Stmt>, var: VarId) -> bool {
+    let hits: Vec<usize> = (0..stmts.len()).filter(|&index| occurs(&stmts[index], var)).collect();
+    let first = match hits.first() {
+        Some(&first) => first,
+        None => return false,
+    };
+    if hits.len() == 1 {
+        let own = stmts[first].exprs().iter().any(|expr| expr.count_var(var) > 0) ||
+                  match stmts[first] {
+            Stmt::Declare(declared, _) => declared == var,
+            _ => false,
+        };
+        if !own {
+            let inner: Vec<usize> = {
+                let bodies = stmts[first].bodies();
+                (0..bodies.len())
+                    .filter(|&index| bodies[index].iter().any(|stmt| occurs(stmt, var)))
+                    .collect()
+            };
+            if inner.len() == 1 {
+                let mut bodies = stmts[first].bodies_mut();
+                return declare_in(bodies.swap_remove(inner[0]), var);
+            }
+        }
+    }
+    let merged = match stmts[first] {
+        Stmt::Expr(Expr::Assign(ref target, ref value)) if **target == Expr::Local(var) &&
+                                                            value.count_var(var) == 0 => {
+            Some(Stmt::Declare(var, Some((**value).clone())))
+        }
+        _ => None,
+    };
+    if let Some(declaration) = merged {
+        stmts[first] = declaration;
+        return true;
+    }
+    if hits.len() == 1 {
+        if let Stmt::Loop { kind: LoopKind::For { init: Some(ref mut init), .. }, .. } =
+            stmts[first] {
+            let declaration = match **init {
+                Stmt::Expr(Expr::Assign(ref target, ref value)) if **target == Expr::Local(var) &&
+                                                                    value.count_var(var) == 0 => {
+                    Some(Stmt::Declare(var, Some((**value).clone())))
+                }
+                _ => None,
+            };
+            if let Some(declaration) = declaration {
+                **init = declaration;
+                return true;
+            }
+        }
+    }
+    stmts.insert(first, Stmt::Declare(var, None));
+    true
+}
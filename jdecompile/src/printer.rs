@@ -0,0 +1,1210 @@
+//! Writes decompiled classes out as Java source.
+//!
+//! Class names are shortened to their simple names where that is
+//! unambiguous, collecting the `import` declarations needed on the way. Only
+//! the labels that a `break` or `continue` actually needs are printed.
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use classfile::*;
+
+use ast::*;
+use ast::Label;
+use types;
+use super::{method_body, Result};
+
+const INDENT: &'static str = "    ";
+const ANNOTATION: &'static str = "java/lang/annotation/Annotation";
+
+// Operator precedences, from loosest to tightest.
+const ASSIGNMENT: u8 = 1;
+const CONDITIONAL: u8 = 2;
+const LOGICAL_OR: u8 = 3;
+const LOGICAL_AND: u8 = 4;
+const BIT_OR: u8 = 5;
+const BIT_XOR: u8 = 6;
+const BIT_AND: u8 = 7;
+const EQUALITY: u8 = 8;
+const RELATIONAL: u8 = 9;
+const SHIFT: u8 = 10;
+const ADDITIVE: u8 = 11;
+const MULTIPLICATIVE: u8 = 12;
+const UNARY: u8 = 13;
+const POSTFIX: u8 = 14;
+const PRIMARY: u8 = 15;
+
+/// Decompiles `class` into the source of a compilation unit.
+pub fn print_class(class: &ClassFile) -> Result<String> {
+    let name = class.this_class_name().clone();
+    let mut printer = Printer {
+        class: class,
+        name: name.clone(),
+        imports: Imports::new(&name),
+        out: String::new(),
+        indent: 0,
+        vars: vec![],
+        labels: HashMap::new(),
+        next_label: 1,
+        breakable: vec![],
+        static_init: false,
+    };
+    try!(printer.class_declaration());
+
+    let mut source = String::new();
+    if let Some(slash) = name.rfind('/') {
+        source.push_str(&format!("package {};\n\n", name[..slash].replace("/", ".")));
+    }
+    if !printer.imports.imports.is_empty() {
+        for import in printer.imports.imports.iter() {
+            source.push_str(&format!("import {};\n", import));
+        }
+        source.push('\n');
+    }
+    source.push_str(&printer.out);
+    Ok(source)
+}
+
+/// The simple names in use and the imports they need.
+struct Imports {
+    package: String,
+    /// Maps simple names of top level classes to the internal names they
+    /// stand for.
+    names: HashMap<String, String>,
+    imports: BTreeSet<String>,
+}
+
+impl Imports {
+    fn new(this_class: &str) -> Imports {
+        let mut imports = Imports {
+            package: package_of(this_class).to_string(),
+            names: HashMap::new(),
+            imports: BTreeSet::new(),
+        };
+        imports.class_name(this_class);
+        imports
+    }
+
+    /// How to refer to the class with internal name `name`: by its simple
+    /// name if that is free or already stands for it, else by its qualified
+    /// name.
+    fn class_name(&mut self, name: &str) -> String {
+        let package = package_of(name);
+        let binary = &name[package.len()..];
+        let split = match binary[1..].find('$') {
+            Some(index) if index + 2 < binary.len() => index + 1,
+            _ => binary.len(),
+        };
+        let top = &binary[..split];
+        let nested = binary[split..].replace("$", ".");
+        let top_name = format!("{}{}", package, top);
+        let claimed = self.names.get(top).cloned();
+        match claimed {
+            Some(ref owner) if *owner == top_name => format!("{}{}", top, nested),
+            Some(_) => name.replace("/", ".").replace("$", "."),
+            None => {
+                self.names.insert(top.to_string(), top_name.clone());
+                if package != self.package && package != "java/lang/" {
+                    self.imports.insert(top_name.replace("/", "."));
+                }
+                format!("{}{}", top, nested)
+            }
+        }
+    }
+}
+
+/// The package of an internal class name, with its trailing `/`.
+fn package_of(name: &str) -> &str {
+    match name.rfind('/') {
+        Some(slash) => &name[..slash + 1],
+        None => "",
+    }
+}
+
+struct Printer<'a> {
+    class: &'a ClassFile,
+    /// The internal name of the class being printed.
+    name: String,
+    imports: Imports,
+    out: String,
+    indent: usize,
+    /// The variables of the method being printed.
+    vars: Vec<Variable>,
+    /// The names of the labels the current method body needs.
+    labels: HashMap<Label, String>,
+    next_label: usize,
+    /// The statements that an unlabeled `break` or `continue` could refer to
+    /// at the current point, innermost last, with whether each is a loop.
+    breakable: Vec<(Label, bool)>,
+    /// Whether the static initializer is being printed, where the final
+    /// static fields it assigns must be named without their class.
+    static_init: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn cp(&self) -> &'a ConstantPool {
+        &self.class.constants
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn class_declaration(&mut self) -> Result<()> {
+        let class = self.class;
+        let flags = class.access_flags;
+        let signature = match class.signature() {
+            Some(Ok(signature)) => Some(signature),
+            _ => None,
+        };
+
+        self.annotations(&class.attrs);
+        let mut header = String::new();
+        if flags.is_public() {
+            header.push_str("public ");
+        }
+        if flags.is_abstract() && !flags.is_interface() {
+            header.push_str("abstract ");
+        }
+        if flags.is_final() {
+            header.push_str("final ");
+        }
+        header.push_str(if flags.is_annotation() {
+            "@interface "
+        } else if flags.is_interface() {
+            "interface "
+        } else {
+            "class "
+        });
+        header.push_str(&simple_class_name(&self.name));
+
+        let mut interfaces = vec![];
+        match signature {
+            Some(ref signature) => {
+                header.push_str(&self.type_parameters(&signature.type_parameters));
+                if !flags.is_interface() &&
+                   signature.super_class.binary_name() != types::OBJECT {
+                    let super_class = self.class_type(&signature.super_class);
+                    header.push_str(&format!(" extends {}", super_class));
+                }
+                for interface in signature.interfaces.iter() {
+                    if flags.is_annotation() && interface.binary_name() == ANNOTATION {
+                        continue;
+                    }
+                    interfaces.push(self.class_type(interface));
+                }
+            }
+            None => {
+                if let Some(super_class) = class.super_class_name() {
+                    if !flags.is_interface() && super_class != types::OBJECT {
+                        let super_class = self.imports.class_name(super_class);
+                        header.push_str(&format!(" extends {}", super_class));
+                    }
+                }
+                for &interface in class.interfaces.iter() {
+                    match self.cp().class_name(interface) {
+                        Some(name) if !flags.is_annotation() || name != ANNOTATION => {
+                            interfaces.push(self.imports.class_name(name));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        if !interfaces.is_empty() {
+            let keyword = if flags.is_interface() { "extends" } else { "implements" };
+            header.push_str(&format!(" {} {}", keyword, interfaces.join(", ")));
+        }
+        header.push_str(" {");
+        self.line(&header);
+
+        self.indent += 1;
+        let mut first = true;
+        for field in class.fields.iter() {
+            if field.access_flags.is_synthetic() {
+                continue;
+            }
+            try!(self.field(field));
+            first = false;
+        }
+        let constructors = class.methods
+            .iter()
+            .filter(|method| self.cp()[method.name_index].as_utf8() == "<init>")
+            .count();
+        for method in class.methods.iter() {
+            if method.access_flags.is_synthetic() {
+                continue;
+            }
+            let start = self.out.len();
+            if !first {
+                self.out.push('\n');
+            }
+            if try!(self.method(method, constructors)) {
+                first = false;
+            } else {
+                self.out.truncate(start);
+            }
+        }
+        self.indent -= 1;
+        self.line("}");
+        Ok(())
+    }
+
+    fn field(&mut self, field: &FieldInfo) -> Result<()> {
+        let flags = field.access_flags;
+        self.annotations(&field.attrs);
+        let descriptor = try!(field.descriptor(self.cp()));
+        let ty = match field.signature(self.cp()) {
+            Some(Ok(signature)) => self.signature(&signature.field_type),
+            _ => self.field_type(&descriptor),
+        };
+        let mut declaration = String::new();
+        push_modifiers(&mut declaration,
+                       &[(flags.is_public(), "public"),
+                         (flags.is_private(), "private"),
+                         (flags.is_protected(), "protected"),
+                         (flags.is_static(), "static"),
+                         (flags.is_final(), "final"),
+                         (flags.is_transient(), "transient"),
+                         (flags.is_volatile(), "volatile")]);
+        declaration.push_str(&format!("{} {}", ty, self.cp()[field.name_index].as_utf8()));
+        if flags.is_static() {
+            let constant = field.attrs.constant_value().and_then(|index| self.cp().get(index));
+            let value = match constant {
+                Some(&Constant::Integer(value)) => {
+                    match descriptor {
+                        FieldType::Boolean => Some(Literal::Boolean(value != 0)),
+                        FieldType::Char => Some(Literal::Char(value as u16)),
+                        _ => Some(Literal::Int(value)),
+                    }
+                }
+                Some(&Constant::Long(value)) => Some(Literal::Long(value)),
+                Some(&Constant::Float(value)) => Some(Literal::Float(value)),
+                Some(&Constant::Double(value)) => Some(Literal::Double(value)),
+                Some(&Constant::String(index)) => {
+                    match self.cp().get(index) {
+                        Some(&Constant::Utf8(ref value)) => Some(Literal::String(value.clone())),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            if let Some(value) = value {
+                let value = self.literal(&value);
+                declaration.push_str(&format!(" = {}", value));
+            }
+        }
+        declaration.push(';');
+        self.line(&declaration);
+        Ok(())
+    }
+
+    /// Prints a method, returning `false` if it was left out because the
+    /// compiler would generate it anyway.
+    fn method(&mut self, method: &MethodInfo, constructors: usize) -> Result<bool> {
+        let cp = self.cp();
+        let flags = method.access_flags;
+        let name = cp[method.name_index].as_utf8().as_str();
+        let descriptor = try!(method.descriptor(cp));
+        let body = if flags.is_abstract() || flags.is_native() {
+            None
+        } else {
+            Some(method_body(self.class, method))
+        };
+        if let Some(Ok(ref body)) = body {
+            let empty = body.stmts.is_empty();
+            if name == "<clinit>" && empty {
+                return Ok(false);
+            }
+            if name == "<init>" && empty && descriptor.params.is_empty() && constructors == 1 {
+                return Ok(false);
+            }
+        }
+
+        self.vars = match body {
+            Some(Ok(ref body)) => body.vars.clone(),
+            _ => vec![],
+        };
+        self.labels.clear();
+        self.next_label = 1;
+        self.static_init = name == "<clinit>";
+        if name == "<clinit>" {
+            self.line("static {");
+        } else {
+            let header = self.method_header(method, name, &descriptor, &body);
+            self.annotations(&method.attrs);
+            match body {
+                Some(_) => self.line(&format!("{} {{", header)),
+                None => {
+                    self.line(&format!("{};", header));
+                    return Ok(true);
+                }
+            }
+        }
+        self.indent += 1;
+        match body {
+            Some(Ok(body)) => self.block(&body.stmts),
+            Some(Err(err)) => self.line(&format!("// Could not decompile: {}", err)),
+            None => {}
+        }
+        self.indent -= 1;
+        self.line("}");
+        Ok(true)
+    }
+
+    fn method_header(&mut self,
+                     method: &MethodInfo,
+                     name: &str,
+                     descriptor: &MethodDescriptor,
+                     body: &Option<Result<Body>>)
+                     -> String {
+        let cp = self.cp();
+        let flags = method.access_flags;
+        let in_interface = self.class.access_flags.is_interface();
+        let signature = match method.signature(cp) {
+            Some(Ok(signature)) => Some(signature),
+            _ => None,
+        };
+
+        let mut header = String::new();
+        let is_default = in_interface && !flags.is_static() && !flags.is_abstract() &&
+                         !flags.is_private();
+        push_modifiers(&mut header,
+                       &[(flags.is_public(), "public"),
+                         (flags.is_private(), "private"),
+                         (flags.is_protected(), "protected"),
+                         (flags.is_abstract() && !in_interface, "abstract"),
+                         (is_default, "default"),
+                         (flags.is_static(), "static"),
+                         (flags.is_final(), "final"),
+                         (flags.is_synchronized(), "synchronized"),
+                         (flags.is_native(), "native")]);
+        if let Some(ref signature) = signature {
+            let parameters = self.type_parameters(&signature.type_parameters);
+            if !parameters.is_empty() {
+                header.push_str(&parameters);
+                header.push(' ');
+            }
+        }
+        if name == "<init>" {
+            header.push_str(&simple_class_name(&self.name));
+        } else {
+            let ret = match (&signature, &descriptor.ret) {
+                (&Some(ref signature), _) if signature.ret.is_some() ||
+                                             descriptor.ret.is_none() => {
+                    match signature.ret {
+                        Some(ref ret) => self.signature(ret),
+                        None => "void".to_string(),
+                    }
+                }
+                (_, &Some(ref ret)) => self.field_type(ret),
+                (_, &None) => "void".to_string(),
+            };
+            header.push_str(&format!("{} {}", ret, name));
+        }
+
+        let names: Vec<String> = match *body {
+            Some(Ok(ref body)) => {
+                body.params.iter().map(|&var| body.vars[var].name.clone()).collect()
+            }
+            _ => parameter_names(cp, method, descriptor.params.len()),
+        };
+        let count = descriptor.params.len();
+        let mut params = vec![];
+        for (index, param) in descriptor.params.iter().enumerate() {
+            let mut ty = match signature {
+                Some(ref signature) if signature.params.len() == count => {
+                    self.signature(&signature.params[index])
+                }
+                _ => self.field_type(param),
+            };
+            if flags.is_varargs() && index + 1 == count && ty.ends_with("[]") {
+                let length = ty.len() - 2;
+                ty.truncate(length);
+                ty.push_str("...");
+            }
+            params.push(format!("{} {}", ty, names[index]));
+        }
+        header.push_str(&format!("({})", params.join(", ")));
+
+        let mut throws = vec![];
+        match signature {
+            Some(ref signature) if !signature.throws.is_empty() => {
+                for thrown in signature.throws.iter() {
+                    throws.push(self.signature(thrown));
+                }
+            }
+            _ => {
+                if let Some(exceptions) = method.attrs.exceptions() {
+                    for &exception in exceptions.iter() {
+                        if let Some(name) = cp.class_name(exception) {
+                            throws.push(self.imports.class_name(name));
+                        }
+                    }
+                }
+            }
+        }
+        if !throws.is_empty() {
+            header.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+        header
+    }
+
+    /// Prints the annotations in `attrs` that can be written without
+    /// looking at other classes.
+    fn annotations(&mut self, attrs: &Attributes) {
+        let annotations = match attrs.runtime_visible_annotations() {
+            Some(annotations) => annotations,
+            None => return,
+        };
+        for annotation in annotations.iter() {
+            if let Some(text) = self.annotation(annotation) {
+                self.line(&text);
+            }
+        }
+    }
+
+    fn annotation(&mut self, annotation: &Annotation) -> Option<String> {
+        let cp = self.cp();
+        let ty = match FieldType::parse(cp[annotation.type_index].as_utf8()) {
+            Ok(FieldType::Object(name)) => self.imports.class_name(&name),
+            _ => return None,
+        };
+        if annotation.element_value_pairs.is_empty() {
+            return Some(format!("@{}", ty));
+        }
+        let mut elements = vec![];
+        for pair in annotation.element_value_pairs.iter() {
+            let value = match self.element_value(&pair.value) {
+                Some(value) => value,
+                None => return None,
+            };
+            elements.push(format!("{} = {}", cp[pair.element_name_index].as_utf8(), value));
+        }
+        Some(format!("@{}({})", ty, elements.join(", ")))
+    }
+
+    fn element_value(&mut self, value: &ElementValue) -> Option<String> {
+        let cp = self.cp();
+        let literal = match *value {
+            ElementValue::ConstantValue(ref constant) => {
+                match (constant.tag, cp.get(constant.const_value_index)) {
+                    (b'Z', Some(&Constant::Integer(value))) => Literal::Boolean(value != 0),
+                    (b'C', Some(&Constant::Integer(value))) => Literal::Char(value as u16),
+                    (_, Some(&Constant::Integer(value))) => Literal::Int(value),
+                    (_, Some(&Constant::Long(value))) => Literal::Long(value),
+                    (_, Some(&Constant::Float(value))) => Literal::Float(value),
+                    (_, Some(&Constant::Double(value))) => Literal::Double(value),
+                    (_, Some(&Constant::Utf8(ref value))) => Literal::String(value.clone()),
+                    _ => return None,
+                }
+            }
+            ElementValue::EnumConstValue(ref constant) => {
+                let ty = FieldType::parse(cp[constant.type_name_index].as_utf8());
+                return match ty {
+                    Ok(FieldType::Object(name)) => {
+                        let name = self.imports.class_name(&name);
+                        Some(format!("{}.{}", name, cp[constant.const_name_index].as_utf8()))
+                    }
+                    _ => None,
+                };
+            }
+            ElementValue::ClassInfo(index) => {
+                let descriptor = cp[index].as_utf8();
+                if descriptor == "V" {
+                    return Some("void.class".to_string());
+                }
+                match FieldType::parse(descriptor) {
+                    Ok(ty) => Literal::Class(ty),
+                    Err(_) => return None,
+                }
+            }
+            ElementValue::ArrayValue(ref array) => {
+                let mut values = vec![];
+                for value in array.values.iter() {
+                    match self.element_value(value) {
+                        Some(value) => values.push(value),
+                        None => return None,
+                    }
+                }
+                return Some(format!("{{{}}}", values.join(", ")));
+            }
+            ElementValue::AnnotationValue(ref annotation) => return self.annotation(annotation),
+        };
+        Some(self.literal(&literal))
+    }
+
+    fn type_parameters(&mut self, parameters: &[TypeParameter]) -> String {
+        if parameters.is_empty() {
+            return String::new();
+        }
+        let mut printed = vec![];
+        for parameter in parameters.iter() {
+            let mut bounds = vec![];
+            match parameter.class_bound {
+                Some(TypeSignature::Class(ref class)) if class.binary_name() == types::OBJECT &&
+                                                         parameter.interface_bounds.is_empty() => {}
+                Some(ref bound) => bounds.push(self.signature(bound)),
+                None => {}
+            }
+            for bound in parameter.interface_bounds.iter() {
+                bounds.push(self.signature(bound));
+            }
+            if bounds.is_empty() {
+                printed.push(parameter.name.clone());
+            } else {
+                printed.push(format!("{} extends {}", parameter.name, bounds.join(" & ")));
+            }
+        }
+        format!("<{}>", printed.join(", "))
+    }
+
+    fn field_type(&mut self, ty: &FieldType) -> String {
+        match *ty {
+            FieldType::Byte => "byte".to_string(),
+            FieldType::Char => "char".to_string(),
+            FieldType::Double => "double".to_string(),
+            FieldType::Float => "float".to_string(),
+            FieldType::Int => "int".to_string(),
+            FieldType::Long => "long".to_string(),
+            FieldType::Short => "short".to_string(),
+            FieldType::Boolean => "boolean".to_string(),
+            FieldType::Object(ref name) => self.imports.class_name(name),
+            FieldType::Array(ref component) => format!("{}[]", self.field_type(component)),
+        }
+    }
+
+    fn signature(&mut self, ty: &TypeSignature) -> String {
+        match *ty {
+            TypeSignature::Base(ref base) => self.field_type(base),
+            TypeSignature::Class(ref class) => self.class_type(class),
+            TypeSignature::TypeVariable(ref name) => name.clone(),
+            TypeSignature::Array(ref component) => format!("{}[]", self.signature(component)),
+        }
+    }
+
+    fn class_type(&mut self, ty: &ClassTypeSignature) -> String {
+        let mut printed = String::new();
+        for (index, class) in ty.classes.iter().enumerate() {
+            if index == 0 {
+                let name = format!("{}{}", ty.package, class.name);
+                printed.push_str(&self.imports.class_name(&name));
+            } else {
+                printed.push('.');
+                printed.push_str(&class.name);
+            }
+            if !class.type_arguments.is_empty() {
+                let mut arguments = vec![];
+                for argument in class.type_arguments.iter() {
+                    arguments.push(match *argument {
+                        TypeArgument::Any => "?".to_string(),
+                        TypeArgument::Extends(ref bound) => {
+                            format!("? extends {}", self.signature(bound))
+                        }
+                        TypeArgument::Super(ref bound) => {
+                            format!("? super {}", self.signature(bound))
+                        }
+                        TypeArgument::Exact(ref argument) => self.signature(argument),
+                    });
+                }
+                printed.push_str(&format!("<{}>", arguments.join(", ")));
+            }
+        }
+        printed
+    }
+
+    /// Prints the statements of a method or lambda body, which starts a new
+    /// scope for labels.
+    fn block(&mut self, stmts: &[Stmt]) {
+        let mut needed = HashSet::new();
+        needed_labels(stmts, &mut vec![], &mut needed);
+        let labels = self.labels.clone();
+        let breakable = self.breakable.split_off(0);
+        for label in needed.into_iter() {
+            self.labels.insert(label, String::new());
+        }
+        self.stmts(stmts);
+        self.labels = labels;
+        self.breakable = breakable;
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts.iter() {
+            self.stmt(stmt);
+        }
+    }
+
+    /// The prefix naming `label`, if any jump needs it.
+    fn label(&mut self, label: Label) -> String {
+        if !self.labels.contains_key(&label) {
+            return String::new();
+        }
+        let name = format!("label{}", self.next_label);
+        self.next_label += 1;
+        self.labels.insert(label, name.clone());
+        format!("{}: ", name)
+    }
+
+    fn jump(&mut self, keyword: &str, label: Label) {
+        match self.labels.get(&label) {
+            Some(name) if !name.is_empty() => {
+                let text = format!("{} {};", keyword, name);
+                self.line(&text);
+            }
+            _ => self.line(&format!("{};", keyword)),
+        }
+    }
+
+    fn nested(&mut self, stmts: &[Stmt]) {
+        self.indent += 1;
+        self.stmts(stmts);
+        self.indent -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match *stmt {
+            Stmt::Expr(ref expr) => {
+                let expr = self.expr(expr, ASSIGNMENT);
+                self.line(&format!("{};", expr));
+            }
+            Stmt::Declare(..) => {
+                let declaration = self.simple_stmt(stmt);
+                self.line(&format!("{};", declaration));
+            }
+            Stmt::If(ref condition, ref then, ref otherwise) => {
+                let condition = self.expr(condition, ASSIGNMENT);
+                self.line(&format!("if ({}) {{", condition));
+                self.nested(then);
+                let mut otherwise = otherwise;
+                loop {
+                    if otherwise.is_empty() {
+                        break;
+                    }
+                    if otherwise.len() == 1 {
+                        if let Stmt::If(ref condition, ref then, ref rest) = otherwise[0] {
+                            let condition = self.expr(condition, ASSIGNMENT);
+                            self.line(&format!("}} else if ({}) {{", condition));
+                            self.nested(then);
+                            otherwise = rest;
+                            continue;
+                        }
+                    }
+                    self.line("} else {");
+                    self.nested(otherwise);
+                    break;
+                }
+                self.line("}");
+            }
+            Stmt::Loop { label, ref kind, ref body } => {
+                let prefix = self.label(label);
+                let header = match *kind {
+                    LoopKind::Infinite => "while (true)".to_string(),
+                    LoopKind::While(ref condition) => {
+                        format!("while ({})", self.expr(condition, ASSIGNMENT))
+                    }
+                    LoopKind::DoWhile(_) => "do".to_string(),
+                    LoopKind::For { ref init, ref condition, ref update } => {
+                        let init = match *init {
+                            Some(ref init) => self.simple_stmt(init),
+                            None => String::new(),
+                        };
+                        let condition = self.expr(condition, ASSIGNMENT);
+                        let update: Vec<String> =
+                            update.iter().map(|expr| self.expr(expr, ASSIGNMENT)).collect();
+                        format!("for ({}; {}; {})", init, condition, update.join(", "))
+                    }
+                };
+                self.line(&format!("{}{} {{", prefix, header));
+                self.breakable.push((label, true));
+                self.nested(body);
+                self.breakable.pop();
+                match *kind {
+                    LoopKind::DoWhile(ref condition) => {
+                        let condition = self.expr(condition, ASSIGNMENT);
+                        self.line(&format!("}} while ({});", condition));
+                    }
+                    _ => self.line("}"),
+                }
+            }
+            Stmt::Block { label, ref body } => {
+                let prefix = self.label(label);
+                self.line(&format!("{}{{", prefix));
+                self.nested(body);
+                self.line("}");
+            }
+            Stmt::Switch { label, ref value, ref cases } => {
+                let prefix = self.label(label);
+                let value = self.expr(value, ASSIGNMENT);
+                self.line(&format!("{}switch ({}) {{", prefix, value));
+                self.breakable.push((label, false));
+                self.indent += 1;
+                for case in cases.iter() {
+                    for key in case.keys.iter() {
+                        match *key {
+                            Some(key) => self.line(&format!("case {}:", key)),
+                            None => self.line("default:"),
+                        }
+                    }
+                    self.nested(&case.body);
+                }
+                self.indent -= 1;
+                self.breakable.pop();
+                self.line("}");
+            }
+            Stmt::Try { ref body, ref catches, ref finally } => {
+                self.line("try {");
+                self.nested(body);
+                for catch in catches.iter() {
+                    let types: Vec<String> = if catch.types.is_empty() {
+                        vec![self.imports.class_name("java/lang/Throwable")]
+                    } else {
+                        catch.types.iter().map(|ty| self.imports.class_name(ty)).collect()
+                    };
+                    let name = self.vars[catch.var].name.clone();
+                    self.line(&format!("}} catch ({} {}) {{", types.join(" | "), name));
+                    self.nested(&catch.body);
+                }
+                if let Some(ref finally) = *finally {
+                    self.line("} finally {");
+                    self.nested(finally);
+                }
+                self.line("}");
+            }
+            Stmt::Synchronized(ref lock, ref body) => {
+                let lock = self.expr(lock, ASSIGNMENT);
+                self.line(&format!("synchronized ({}) {{", lock));
+                self.nested(body);
+                self.line("}");
+            }
+            Stmt::MonitorEnter(ref lock) => {
+                let lock = self.expr(lock, ASSIGNMENT);
+                self.line(&format!("// monitorenter({})", lock));
+            }
+            Stmt::MonitorExit(ref lock) => {
+                let lock = self.expr(lock, ASSIGNMENT);
+                self.line(&format!("// monitorexit({})", lock));
+            }
+            Stmt::Break(label) => {
+                if self.breakable.last().map(|&(innermost, _)| innermost) == Some(label) {
+                    self.line("break;");
+                } else {
+                    self.jump("break", label);
+                }
+            }
+            Stmt::Continue(label) => {
+                let innermost = self.breakable.iter().rev().find(|&&(_, is_loop)| is_loop);
+                if innermost.map(|&(innermost, _)| innermost) == Some(label) {
+                    self.line("continue;");
+                } else {
+                    self.jump("continue", label);
+                }
+            }
+            Stmt::Return(None) => self.line("return;"),
+            Stmt::Return(Some(ref value)) => {
+                let value = self.expr(value, ASSIGNMENT);
+                self.line(&format!("return {};", value));
+            }
+            Stmt::Throw(ref value) => {
+                let value = self.expr(value, ASSIGNMENT);
+                self.line(&format!("throw {};", value));
+            }
+        }
+    }
+
+    /// A declaration or expression statement without its semicolon, as in
+    /// the header of a `for` loop.
+    fn simple_stmt(&mut self, stmt: &Stmt) -> String {
+        match *stmt {
+            Stmt::Declare(var, ref init) => {
+                let ty = self.vars[var].ty.clone();
+                let mut declaration = format!("{} {}", self.signature(&ty), self.vars[var].name);
+                if let Some(ref init) = *init {
+                    declaration.push_str(&format!(" = {}", self.expr(init, ASSIGNMENT)));
+                }
+                declaration
+            }
+            Stmt::Expr(ref expr) => self.expr(expr, ASSIGNMENT),
+            _ => String::new(),
+        }
+    }
+
+    /// Prints `expr`, in parentheses if it binds less tightly than
+    /// `precedence`.
+    fn expr(&mut self, expr: &Expr, precedence: u8) -> String {
+        let printed = self.bare_expr(expr);
+        if expr_precedence(expr) < precedence {
+            format!("({})", printed)
+        } else {
+            printed
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) -> String {
+        let args: Vec<String> = args.iter().map(|arg| self.expr(arg, ASSIGNMENT)).collect();
+        args.join(", ")
+    }
+
+    fn target(&mut self, target: &Target) -> String {
+        match *target {
+            Target::Static(ref owner) => self.imports.class_name(owner),
+            Target::Instance(ref object) => self.expr(object, PRIMARY),
+            Target::Super => "super".to_string(),
+        }
+    }
+
+    fn bare_expr(&mut self, expr: &Expr) -> String {
+        match *expr {
+            Expr::Literal(ref literal) => self.literal(literal),
+            Expr::Local(var) => self.vars[var].name.clone(),
+            Expr::This => "this".to_string(),
+            Expr::Uninitialized(ref class) => format!("new {}()", self.imports.class_name(class)),
+            Expr::Compare(nan, ref left, ref right) => {
+                if nan == 0 {
+                    let long = self.imports.class_name("java/lang/Long");
+                    let args = self.args(&[(**left).clone(), (**right).clone()]);
+                    return format!("{}.compare({})", long, args);
+                }
+                let left = self.expr(left, RELATIONAL + 1);
+                let right = self.expr(right, RELATIONAL + 1);
+                if nan < 0 {
+                    format!("{0} > {1} ? 1 : {0} == {1} ? 0 : -1", left, right)
+                } else {
+                    format!("{0} < {1} ? -1 : {0} == {1} ? 0 : 1", left, right)
+                }
+            }
+            Expr::Field { target: Target::Static(ref owner), ref name, .. }
+                if self.static_init && *owner == self.name => name.clone(),
+            Expr::Field { ref target, ref name, .. } => {
+                format!("{}.{}", self.target(target), name)
+            }
+            Expr::ArrayElement(ref array, ref index) => {
+                let array = self.expr(array, PRIMARY);
+                format!("{}[{}]", array, self.expr(index, ASSIGNMENT))
+            }
+            Expr::ArrayLength(ref array) => format!("{}.length", self.expr(array, PRIMARY)),
+            Expr::Invoke { ref target, ref name, ref args, .. } => {
+                let args = self.args(args);
+                match *target {
+                    Target::Static(ref owner) if *owner == self.name => {
+                        format!("{}({})", name, args)
+                    }
+                    Target::Instance(ref object) if **object == Expr::This => {
+                        format!("{}({})", name, args)
+                    }
+                    _ => format!("{}.{}({})", self.target(target), name, args),
+                }
+            }
+            Expr::ConstructorCall { is_super, ref args, .. } => {
+                let keyword = if is_super { "super" } else { "this" };
+                format!("{}({})", keyword, self.args(args))
+            }
+            Expr::New { ref class, ref args, .. } => {
+                let class = self.imports.class_name(class);
+                format!("new {}({})", class, self.args(args))
+            }
+            Expr::NewArray { ref ty, ref dimensions } => {
+                let mut element = ty;
+                while let FieldType::Array(ref component) = *element {
+                    element = component;
+                }
+                let mut printed = format!("new {}", self.field_type(element));
+                for dimension in dimensions.iter() {
+                    printed.push_str(&format!("[{}]", self.expr(dimension, ASSIGNMENT)));
+                }
+                for _ in dimensions.len()..ty.dimensions() {
+                    printed.push_str("[]");
+                }
+                printed
+            }
+            Expr::ArrayInit { ref ty, ref elements } => {
+                let ty = self.field_type(ty);
+                format!("new {}{{{}}}", ty, self.args(elements))
+            }
+            Expr::Unary(op, ref operand) => {
+                let operand = self.expr(operand, UNARY);
+                let symbol = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                    UnaryOp::BitNot => "~",
+                };
+                if op == UnaryOp::Neg && (operand.starts_with('-') || operand.starts_with('+')) {
+                    format!("{}({})", symbol, operand)
+                } else {
+                    format!("{}{}", symbol, operand)
+                }
+            }
+            Expr::Binary(op, ref left, ref right) => {
+                let precedence = binary_precedence(op);
+                let associative = op == BinaryOp::LogicalAnd || op == BinaryOp::LogicalOr;
+                let left = self.expr(left, precedence);
+                let right = self.expr(right, if associative { precedence } else { precedence + 1 });
+                format!("{} {} {}", left, op.symbol(), right)
+            }
+            Expr::Cast(ref ty, ref operand) => {
+                let ty = self.field_type(ty);
+                let operand = self.expr(operand, UNARY);
+                if operand.starts_with('-') || operand.starts_with('+') {
+                    format!("({}) ({})", ty, operand)
+                } else {
+                    format!("({}) {}", ty, operand)
+                }
+            }
+            Expr::InstanceOf(ref operand, ref ty) => {
+                let operand = self.expr(operand, RELATIONAL);
+                format!("{} instanceof {}", operand, self.field_type(ty))
+            }
+            Expr::Conditional(ref condition, ref then, ref otherwise) => {
+                let condition = self.expr(condition, LOGICAL_OR);
+                let then = self.expr(then, CONDITIONAL);
+                let otherwise = self.expr(otherwise, CONDITIONAL);
+                format!("{} ? {} : {}", condition, then, otherwise)
+            }
+            Expr::Assign(ref target, ref value) => {
+                let target = self.expr(target, PRIMARY);
+                format!("{} = {}", target, self.expr(value, ASSIGNMENT))
+            }
+            Expr::CompoundAssign(op, ref target, ref value) => {
+                let target = self.expr(target, PRIMARY);
+                format!("{} {}= {}", target, op.symbol(), self.expr(value, ASSIGNMENT))
+            }
+            Expr::Increment { ref target, prefix, decrement } => {
+                let target = self.expr(target, PRIMARY);
+                let symbol = if decrement { "--" } else { "++" };
+                if prefix {
+                    format!("{}{}", symbol, target)
+                } else {
+                    format!("{}{}", target, symbol)
+                }
+            }
+            Expr::Concat(ref parts) => {
+                let mut printed = vec![];
+                for (index, part) in parts.iter().enumerate() {
+                    let precedence = if index == 0 { ADDITIVE } else { ADDITIVE + 1 };
+                    printed.push(self.expr(part, precedence));
+                }
+                printed.join(" + ")
+            }
+            Expr::Lambda { ref params, ref body } => self.lambda(params, body),
+            Expr::MethodRef { ref target, ref name } => {
+                let target = match *target {
+                    Target::Static(ref owner) if owner.starts_with('[') => {
+                        match FieldType::parse(owner) {
+                            Ok(ty) => self.field_type(&ty),
+                            Err(_) => owner.clone(),
+                        }
+                    }
+                    _ => self.target(target),
+                };
+                format!("{}::{}", target, name)
+            }
+            Expr::InvokeDynamic { ref name, ref args } => {
+                format!("/* invokedynamic */ {}({})", name, self.args(args))
+            }
+        }
+    }
+
+    fn lambda(&mut self, params: &[VarId], body: &[Stmt]) -> String {
+        let names: Vec<String> = params.iter().map(|&var| self.vars[var].name.clone()).collect();
+        let params = if names.len() == 1 {
+            names[0].clone()
+        } else {
+            format!("({})", names.join(", "))
+        };
+        if body.len() == 1 {
+            match body[0] {
+                Stmt::Return(Some(ref value)) |
+                Stmt::Expr(ref value) => {
+                    return format!("{} -> {}", params, self.expr(value, ASSIGNMENT));
+                }
+                _ => {}
+            }
+        }
+        if body.is_empty() {
+            return format!("{} -> {{}}", params);
+        }
+
+        let out = ::std::mem::replace(&mut self.out, String::new());
+        self.indent += 1;
+        self.block(body);
+        self.indent -= 1;
+        let printed = ::std::mem::replace(&mut self.out, out);
+        let mut close = String::new();
+        for _ in 0..self.indent {
+            close.push_str(INDENT);
+        }
+        format!("{} -> {{\n{}{}}}", params, printed, close)
+    }
+
+    fn literal(&mut self, literal: &Literal) -> String {
+        match *literal {
+            Literal::Null => "null".to_string(),
+            Literal::Boolean(value) => value.to_string(),
+            Literal::Char(value) => format!("'{}'", escape(&[value], '\'')),
+            Literal::Int(value) => value.to_string(),
+            Literal::Long(value) => format!("{}L", value),
+            Literal::Float(value) => {
+                if value.is_nan() || value.is_infinite() {
+                    let class = self.imports.class_name("java/lang/Float");
+                    format!("{}.{}", class, special_float_name(value as f64))
+                } else {
+                    format!("{:?}F", value)
+                }
+            }
+            Literal::Double(value) => {
+                if value.is_nan() || value.is_infinite() {
+                    let class = self.imports.class_name("java/lang/Double");
+                    format!("{}.{}", class, special_float_name(value))
+                } else {
+                    format!("{:?}", value)
+                }
+            }
+            Literal::String(ref value) => {
+                let units: Vec<u16> = value.encode_utf16().collect();
+                format!("\"{}\"", escape(&units, '"'))
+            }
+            Literal::Class(ref ty) => format!("{}.class", self.field_type(ty)),
+        }
+    }
+}
+
+fn special_float_name(value: f64) -> &'static str {
+    if value.is_nan() {
+        "NaN"
+    } else if value > 0.0 {
+        "POSITIVE_INFINITY"
+    } else {
+        "NEGATIVE_INFINITY"
+    }
+}
+
+/// Escapes UTF-16 code units for a character or string literal delimited by
+/// `quote`. Line terminators must not be written as unicode escapes, which
+/// Java translates before reading literals.
+fn escape(units: &[u16], quote: char) -> String {
+    let mut escaped = String::new();
+    for &unit in units.iter() {
+        match unit {
+            0x08 => escaped.push_str("\\b"),
+            0x09 => escaped.push_str("\\t"),
+            0x0a => escaped.push_str("\\n"),
+            0x0c => escaped.push_str("\\f"),
+            0x0d => escaped.push_str("\\r"),
+            0x5c => escaped.push_str("\\\\"),
+            _ if unit == quote as u16 => {
+                escaped.push('\\');
+                escaped.push(quote);
+            }
+            0x20...0x7e => escaped.push(unit as u8 as char),
+            _ => escaped.push_str(&format!("\\u{:04x}", unit)),
+        }
+    }
+    escaped
+}
+
+fn push_modifiers(out: &mut String, modifiers: &[(bool, &str)]) {
+    for &(present, modifier) in modifiers.iter() {
+        if present {
+            out.push_str(modifier);
+            out.push(' ');
+        }
+    }
+}
+
+/// The name a class is declared with in source.
+fn simple_class_name(name: &str) -> String {
+    types::simple_name(name).to_string()
+}
+
+/// Parameter names for a method without code, from the `MethodParameters`
+/// attribute where present.
+fn parameter_names(cp: &ConstantPool, method: &MethodInfo, count: usize) -> Vec<String> {
+    let names = method.attrs.method_parameters();
+    (0..count)
+        .map(|index| {
+            names.and_then(|names| names.get(index))
+                .and_then(|info| match cp.get(info.name_index) {
+                    Some(&Constant::Utf8(ref name)) => Some(name.clone()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| format!("arg{}", index))
+        })
+        .collect()
+}
+
+/// Collects the labels in `stmts` that jumps must name, because the jump
+/// does not refer to the innermost statement an unlabeled one would.
+fn needed_labels(stmts: &[Stmt], breakable: &mut Vec<(Label, bool)>, needed: &mut HashSet<Label>) {
+    for stmt in stmts.iter() {
+        match *stmt {
+            Stmt::Break(label) => {
+                if breakable.last().map(|&(innermost, _)| innermost) != Some(label) {
+                    needed.insert(label);
+                }
+            }
+            Stmt::Continue(label) => {
+                let innermost = breakable.iter().rev().find(|&&(_, is_loop)| is_loop);
+                if innermost.map(|&(innermost, _)| innermost) != Some(label) {
+                    needed.insert(label);
+                }
+            }
+            Stmt::Loop { label, ref body, .. } => {
+                breakable.push((label, true));
+                needed_labels(body, breakable, needed);
+                breakable.pop();
+            }
+            Stmt::Switch { label, ref cases, .. } => {
+                breakable.push((label, false));
+                for case in cases.iter() {
+                    needed_labels(&case.body, breakable, needed);
+                }
+                breakable.pop();
+            }
+            _ => {
+                for body in stmt.bodies() {
+                    needed_labels(body, breakable, needed);
+                }
+            }
+        }
+    }
+}
+
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => MULTIPLICATIVE,
+        BinaryOp::Add | BinaryOp::Sub => ADDITIVE,
+        BinaryOp::Shl | BinaryOp::Shr | BinaryOp::UShr => SHIFT,
+        BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Gt | BinaryOp::Le => RELATIONAL,
+        BinaryOp::Eq | BinaryOp::Ne => EQUALITY,
+        BinaryOp::And => BIT_AND,
+        BinaryOp::Xor => BIT_XOR,
+        BinaryOp::Or => BIT_OR,
+        BinaryOp::LogicalAnd => LOGICAL_AND,
+        BinaryOp::LogicalOr => LOGICAL_OR,
+    }
+}
+
+fn expr_precedence(expr: &Expr) -> u8 {
+    match *expr {
+        Expr::Lambda { .. } |
+        Expr::Assign(..) |
+        Expr::CompoundAssign(..) => ASSIGNMENT,
+        Expr::Conditional(..) => CONDITIONAL,
+        Expr::Compare(nan, ..) if nan != 0 => CONDITIONAL,
+        Expr::Binary(op, ..) => binary_precedence(op),
+        Expr::Concat(_) => ADDITIVE,
+        Expr::InstanceOf(..) => RELATIONAL,
+        Expr::Unary(..) |
+        Expr::Cast(..) |
+        Expr::Increment { prefix: true, .. } => UNARY,
+        Expr::Increment { .. } |
+        Expr::NewArray { .. } |
+        Expr::ArrayInit { .. } => POSTFIX,
+        Expr::Literal(Literal::Int(value)) if value < 0 => UNARY,
+        Expr::Literal(Literal::Long(value)) if value < 0 => UNARY,
+        Expr::Literal(Literal::Float(value)) if value.is_sign_negative() => UNARY,
+        Expr::Literal(Literal::Double(value)) if value.is_sign_negative() => UNARY,
+        _ => PRIMARY,
+    }
+}
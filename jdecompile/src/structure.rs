@@ -0,0 +1,641 @@
+//! Arranges the blocks of a method into structured statements.
+//!
+//! Blocks are placed following the dominator tree, as in Ramsey's "Beyond
+//! Relooper": a block is written out inside the code of its immediate
+//! dominator. A block that several others branch forward to is written
+//! after a labeled block enclosing the code that reaches it, so that every
+//! forward branch becomes a `break` and every backward branch a `continue`
+//! of the loop the target heads. Loops and try statements are wrapped
+//! around the code of their entry block, with the blocks after them
+//! following the loop or try.
+//!
+//! Loops with more than one entry cannot be written in Java; the blocks
+//! they are entered at are duplicated until every loop has a single entry.
+//! The labeled blocks are left in the result where no loop, `if` or
+//! `switch` can replace them; the passes remove the rest.
+use std::collections::BTreeSet;
+
+use analysis::dominators::DominatorTree;
+
+use ast::*;
+use builder::{Exit, Handler, Method, Node};
+use super::{Error, Result};
+
+/// Structures the blocks of `method`, returning the statements of its body
+/// and its variables.
+pub fn structure(method: Method) -> Result<(Vec<Stmt>, Vec<Variable>)> {
+    let Method { vars, nodes, handlers, .. } = method;
+    let mut graph = Graph {
+        nodes: nodes,
+        handlers: handlers,
+    };
+    graph.merge_conditions();
+    try!(graph.split_irreducible());
+    let mut structurer = try!(Structurer::new(&graph, vars));
+    let stmts = try!(structurer.tree(0, &mut vec![]));
+    Ok((stmts, structurer.vars))
+}
+
+struct Graph {
+    nodes: Vec<Option<Node>>,
+    handlers: Vec<Handler>,
+}
+
+impl Graph {
+    fn node(&self, id: usize) -> &Node {
+        self.nodes[id].as_ref().unwrap()
+    }
+
+    /// The successors of each block, including the handlers protecting it.
+    fn successors(&self) -> Vec<Vec<usize>> {
+        let mut successors: Vec<Vec<usize>> = self.nodes
+            .iter()
+            .map(|node| node.as_ref().map_or(vec![], |node| node.exit.successors()))
+            .collect();
+        for handler in &self.handlers {
+            for &id in &handler.protected {
+                if !successors[id].contains(&handler.node) {
+                    successors[id].push(handler.node);
+                }
+            }
+        }
+        successors
+    }
+
+    /// The blocks that branch to each block, not counting exceptions.
+    fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![vec![]; self.nodes.len()];
+        for (id, node) in self.nodes.iter().enumerate() {
+            if let Some(ref node) = *node {
+                for target in node.exit.successors() {
+                    predecessors[target].push(id);
+                }
+            }
+        }
+        predecessors
+    }
+
+    fn is_handler(&self, id: usize) -> bool {
+        self.handlers.iter().any(|handler| handler.node == id)
+    }
+
+    /// The indexes of the handlers protecting block `id`.
+    fn handlers_of(&self, id: usize) -> Vec<usize> {
+        (0..self.handlers.len())
+            .filter(|&index| self.handlers[index].protected.contains(&id))
+            .collect()
+    }
+
+    /// The blocks in reverse postorder from the entry, and the position of
+    /// each block in that order.
+    fn reverse_postorder(&self) -> (Vec<usize>, Vec<usize>) {
+        let successors = self.successors();
+        let mut visited = vec![false; self.nodes.len()];
+        let mut postorder = vec![];
+        let mut pending = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((id, next)) = pending.pop() {
+            if next < successors[id].len() {
+                pending.push((id, next + 1));
+                let successor = successors[id][next];
+                if !visited[successor] {
+                    visited[successor] = true;
+                    pending.push((successor, 0));
+                }
+            } else {
+                postorder.push(id);
+            }
+        }
+        postorder.reverse();
+        let mut numbers = vec![usize::max_value(); self.nodes.len()];
+        for (number, &id) in postorder.iter().enumerate() {
+            numbers[id] = number;
+        }
+        (postorder, numbers)
+    }
+
+    /// Joins the blocks that only test a condition into the block branching
+    /// to them, building the `&&` and `||` conditions they were compiled
+    /// from.
+    fn merge_conditions(&mut self) {
+        loop {
+            let predecessors = self.predecessors();
+            let mut merged = None;
+            for b in 1..self.nodes.len() {
+                let (cb, bt, be) = match self.nodes[b] {
+                    Some(Node { ref stmts, exit: Exit::If(ref c, t, e), .. })
+                        if stmts.is_empty() => {
+                        (c.clone(), t, e)
+                    }
+                    _ => continue,
+                };
+                if predecessors[b].len() != 1 || self.is_handler(b) {
+                    continue;
+                }
+                let a = predecessors[b][0];
+                let (ca, at, ae) = match self.nodes[a] {
+                    Some(Node { exit: Exit::If(ref c, t, e), .. }) => (c.clone(), t, e),
+                    _ => continue,
+                };
+                if a == b || bt == b || be == b || self.handlers_of(a) != self.handlers_of(b) {
+                    continue;
+                }
+                let condition = if at == b && ae == be {
+                    Expr::binary(BinaryOp::LogicalAnd, ca, cb)
+                } else if at == b && ae == bt {
+                    Expr::binary(BinaryOp::LogicalOr, ca.negate(), cb)
+                } else if ae == b && at == bt {
+                    Expr::binary(BinaryOp::LogicalOr, ca, cb)
+                } else if ae == b && at == be {
+                    Expr::binary(BinaryOp::LogicalAnd, ca.negate(), cb)
+                } else {
+                    continue;
+                };
+                merged = Some((a, b, Exit::If(condition, bt, be)));
+                break;
+            }
+            match merged {
+                Some((a, b, exit)) => {
+                    self.nodes[a].as_mut().unwrap().exit = exit;
+                    self.nodes[b] = None;
+                    for handler in self.handlers.iter_mut() {
+                        handler.protected.remove(&b);
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Duplicates the blocks that loops with several entries are entered
+    /// at, until every loop has a single entry that dominates it.
+    fn split_irreducible(&mut self) -> Result<()> {
+        let limit = self.nodes.len() * 4 + 64;
+        loop {
+            let (order, numbers) = self.reverse_postorder();
+            let dominators = DominatorTree::from_successors(&self.successors());
+            let mut irreducible = None;
+            'search: for &from in &order {
+                for to in self.node(from).exit.successors() {
+                    if numbers[to] <= numbers[from] && !dominators.dominates(to, from) {
+                        irreducible = Some((from, to));
+                        break 'search;
+                    }
+                }
+            }
+            let (from, to) = match irreducible {
+                Some(edge) => edge,
+                None => return Ok(()),
+            };
+            if self.is_handler(to) || self.nodes.len() >= limit {
+                return Err(Error::Unsupported("irreducible control flow".to_string()));
+            }
+            let copy = self.nodes.len();
+            let node = self.node(to).clone();
+            self.nodes.push(Some(node));
+            for handler in self.handlers.iter_mut() {
+                if handler.protected.contains(&to) {
+                    handler.protected.insert(copy);
+                }
+            }
+            self.nodes[from].as_mut().unwrap().exit.retarget(to, copy);
+        }
+    }
+}
+
+/// A try statement: the blocks it protects, the block it is entered at and
+/// the indexes of its handlers.
+#[derive(Clone)]
+struct Try {
+    entry: usize,
+    region: BTreeSet<usize>,
+    handlers: Vec<usize>,
+}
+
+/// One statement a block may be placed in, wrapping the code of its entry
+/// block.
+enum Layer {
+    Loop,
+    Try(Try),
+}
+
+/// A statement enclosing the code being generated, which branches may leave
+/// or repeat.
+#[derive(Clone, Copy)]
+enum Frame {
+    /// The loop headed by a block, which a branch to the block continues.
+    Loop(usize, Label),
+    /// A labeled block that the code of a block follows, which a branch to
+    /// the block breaks.
+    Follow(usize, Label),
+}
+
+struct Structurer<'a> {
+    graph: &'a Graph,
+    vars: Vec<Variable>,
+    numbers: Vec<usize>,
+    dominators: DominatorTree,
+    /// The number of distinct blocks branching forward to each block.
+    forward: Vec<usize>,
+    /// The blocks of the loop each block heads, if it heads one.
+    loops: Vec<Option<BTreeSet<usize>>>,
+    tries: Vec<Try>,
+    /// The handlers whose code is being produced, so that a handler that
+    /// reaches its own try statement is not structured forever.
+    catching: Vec<usize>,
+    labels: usize,
+}
+
+impl<'a> Structurer<'a> {
+    fn new(graph: &'a Graph, vars: Vec<Variable>) -> Result<Structurer<'a>> {
+        let (order, numbers) = graph.reverse_postorder();
+        let successors = graph.successors();
+        let dominators = DominatorTree::from_successors(&successors);
+        let count = graph.nodes.len();
+
+        let mut all_predecessors = vec![vec![]; count];
+        for (from, targets) in successors.iter().enumerate() {
+            for &to in targets {
+                all_predecessors[to].push(from);
+            }
+        }
+        let mut forward = vec![0; count];
+        let mut loops: Vec<Option<BTreeSet<usize>>> = vec![None; count];
+        for (to, predecessors) in graph.predecessors().into_iter().enumerate() {
+            let mut seen = BTreeSet::new();
+            for from in predecessors {
+                if numbers[from] < numbers[to] {
+                    if seen.insert(from) {
+                        forward[to] += 1;
+                    }
+                } else {
+                    // A back edge: add the blocks that reach it without
+                    // passing through the header to the loop.
+                    let body = loops[to].get_or_insert_with(BTreeSet::new);
+                    body.insert(to);
+                    let mut pending = vec![from];
+                    while let Some(id) = pending.pop() {
+                        if body.insert(id) {
+                            pending.extend(all_predecessors[id].iter().cloned());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Handlers protecting the same blocks belong to one try statement.
+        let mut tries: Vec<Try> = vec![];
+        for (index, handler) in graph.handlers.iter().enumerate() {
+            if handler.protected.is_empty() || numbers[handler.node] == usize::max_value() {
+                continue;
+            }
+            if let Some(existing) = tries.iter_mut().find(|t| t.region == handler.protected) {
+                existing.handlers.push(index);
+                continue;
+            }
+            let entry = *handler.protected.iter().min_by_key(|&&id| numbers[id]).unwrap();
+            if !handler.protected.iter().all(|&id| dominators.dominates(entry, id)) {
+                return Err(Error::Unsupported(format!("try block at pc {} has several entries",
+                                                      graph.node(entry).pc)));
+            }
+            tries.push(Try {
+                entry: entry,
+                region: handler.protected.clone(),
+                handlers: vec![index],
+            });
+        }
+        let _ = order;
+        Ok(Structurer {
+            graph: graph,
+            vars: vars,
+            numbers: numbers,
+            dominators: dominators,
+            forward: forward,
+            loops: loops,
+            tries: tries,
+            catching: vec![],
+            labels: 0,
+        })
+    }
+
+    fn label(&mut self) -> Label {
+        self.labels += 1;
+        self.labels
+    }
+
+    fn is_merge(&self, id: usize) -> bool {
+        self.forward[id] > 1
+    }
+
+    fn pc(&self, id: usize) -> usize {
+        self.graph.node(id).pc
+    }
+
+    fn unstructured(&self, id: usize) -> Error {
+        Error::Unsupported(format!("unstructured branch to pc {}", self.pc(id)))
+    }
+
+    /// The code for block `x` and the blocks it dominates.
+    fn tree(&mut self, x: usize, context: &mut Vec<Frame>) -> Result<Vec<Stmt>> {
+        let graph = self.graph;
+        let mut tries: Vec<usize> =
+            (0..self.tries.len()).filter(|&t| self.tries[t].entry == x).collect();
+        tries.sort_by_key(|&t| usize::max_value() - self.tries[t].region.len());
+        for pair in tries.windows(2) {
+            if !self.tries[pair[1]].region.is_subset(&self.tries[pair[0]].region) {
+                return Err(Error::Unsupported(format!("overlapping try blocks at pc {}",
+                                                       self.pc(x))));
+            }
+        }
+
+        // A loop goes inside the try statements protecting all of it.
+        let tries: Vec<Try> = tries.iter().map(|&t| self.tries[t].clone()).collect();
+        let mut layers = vec![];
+        let mut remaining = &tries[..];
+        if let Some(ref body) = self.loops[x] {
+            let outside = remaining.iter().take_while(|t| body.is_subset(&t.region)).count();
+            layers.extend(remaining[..outside].iter().map(|t| Layer::Try(t.clone())));
+            layers.push(Layer::Loop);
+            remaining = &remaining[outside..];
+        }
+        layers.extend(remaining.iter().map(|t| Layer::Try(t.clone())));
+
+        let handlers: Vec<usize> = tries.iter()
+            .flat_map(|t| t.handlers.iter().map(|&index| graph.handlers[index].node))
+            .collect();
+        let children: Vec<usize> = self.dominators
+            .children(x)
+            .iter()
+            .cloned()
+            .filter(|&child| !handlers.contains(&child) && !in_context(context, child))
+            .collect();
+        self.layer(x, &layers, children, context)
+    }
+
+    fn layer(&mut self,
+             x: usize,
+             layers: &[Layer],
+             children: Vec<usize>,
+             context: &mut Vec<Frame>)
+             -> Result<Vec<Stmt>> {
+        match layers.first() {
+            None => {
+                let switch_cases = self.fallthrough_cases(x);
+                let followers: Vec<usize> = children.into_iter()
+                    .filter(|&child| self.is_merge(child) && !switch_cases.contains(&child))
+                    .collect();
+                self.with_followers(followers, context, &mut |s, context| s.node_code(x, context))
+            }
+            Some(&Layer::Loop) => {
+                let (inside, outside): (Vec<usize>, Vec<usize>) = {
+                    let body = self.loops[x].as_ref().unwrap();
+                    children.into_iter().partition(|child| body.contains(child))
+                };
+                let rest = &layers[1..];
+                self.with_followers(outside, context, &mut |s, context| {
+                    let label = s.label();
+                    context.push(Frame::Loop(x, label));
+                    let body = s.layer(x, rest, inside.clone(), context);
+                    context.pop();
+                    Ok(vec![Stmt::Loop {
+                                label: label,
+                                kind: LoopKind::Infinite,
+                                body: try!(body),
+                            }])
+                })
+            }
+            Some(&Layer::Try(ref t)) => {
+                let (inside, outside): (Vec<usize>, Vec<usize>) =
+                    children.into_iter().partition(|child| t.region.contains(child));
+                let rest = &layers[1..];
+                self.with_followers(outside, context, &mut |s, context| {
+                    let body = try!(s.layer(x, rest, inside.clone(), context));
+                    let mut catches = vec![];
+                    for &index in &t.handlers {
+                        catches.push(try!(s.catch(index, context)));
+                    }
+                    Ok(vec![Stmt::Try {
+                                body: body,
+                                catches: catches,
+                                finally: None,
+                            }])
+                })
+            }
+        }
+    }
+
+    fn catch(&mut self, index: usize, context: &mut Vec<Frame>) -> Result<Catch> {
+        let graph = self.graph;
+        let handler = &graph.handlers[index];
+        if self.catching.contains(&index) {
+            return Err(Error::Unsupported(format!("exception handler at pc {} is inside its own \
+                                                   try block",
+                                                  self.pc(handler.node))));
+        }
+        self.catching.push(index);
+        let body = self.tree(handler.node, context);
+        self.catching.pop();
+        let mut body = try!(body);
+        let mut var = handler.var;
+        // The handler usually starts by storing the exception in the
+        // variable the source names in the catch clause.
+        let stored = match body.first() {
+            Some(&Stmt::Expr(Expr::Assign(ref target, ref value))) => {
+                match (&**target, &**value) {
+                    (&Expr::Local(target), &Expr::Local(value)) if value == handler.var => {
+                        Some(target)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(target) = stored {
+            body.remove(0);
+            var = target;
+            self.vars[var].declared = true;
+        }
+        let types = if handler.catches_any {
+            vec![]
+        } else {
+            handler.types.clone()
+        };
+        Ok(Catch {
+            types: types,
+            var: var,
+            body: body,
+        })
+    }
+
+    /// Wraps the code produced by `inner` in labeled blocks, one for each
+    /// of `followers`, and places the code of each follower after its
+    /// block. The follower coming last in reverse postorder is outermost.
+    fn with_followers(&mut self,
+                      mut followers: Vec<usize>,
+                      context: &mut Vec<Frame>,
+                      inner: &mut FnMut(&mut Structurer<'a>, &mut Vec<Frame>) -> Result<Vec<Stmt>>)
+                      -> Result<Vec<Stmt>> {
+        followers.sort_by_key(|&id| self.numbers[id]);
+        let last = match followers.pop() {
+            Some(last) => last,
+            None => return inner(self, context),
+        };
+        let label = self.label();
+        context.push(Frame::Follow(last, label));
+        let body = self.with_followers(followers, context, inner);
+        context.pop();
+        let mut stmts = vec![Stmt::Block {
+                                 label: label,
+                                 body: try!(body),
+                             }];
+        stmts.extend(try!(self.tree(last, context)));
+        Ok(stmts)
+    }
+
+    /// The statements of block `x` followed by the way it exits.
+    fn node_code(&mut self, x: usize, context: &mut Vec<Frame>) -> Result<Vec<Stmt>> {
+        let graph = self.graph;
+        let node = graph.node(x);
+        let mut stmts = node.stmts.clone();
+        match node.exit {
+            Exit::Goto(target) => stmts.extend(try!(self.branch(x, target, context))),
+            Exit::If(ref condition, then, otherwise) => {
+                let then_code = try!(self.branch(x, then, context));
+                let otherwise_code = try!(self.branch(x, otherwise, context));
+                // Keep the branches in the order they appear in the code.
+                if self.pc(otherwise) < self.pc(then) {
+                    stmts.push(Stmt::If(condition.clone().negate(), otherwise_code, then_code));
+                } else {
+                    stmts.push(Stmt::If(condition.clone(), then_code, otherwise_code));
+                }
+            }
+            Exit::Switch(ref value, ref cases, default) => {
+                stmts.push(try!(self.switch(x, value, cases, default, context)));
+            }
+            Exit::Return(ref value) => stmts.push(Stmt::Return(value.clone())),
+            Exit::Throw(ref value) => stmts.push(Stmt::Throw(value.clone())),
+        }
+        Ok(stmts)
+    }
+
+    /// The code for a branch from block `from` to block `to`.
+    fn branch(&mut self, from: usize, to: usize, context: &mut Vec<Frame>) -> Result<Vec<Stmt>> {
+        for frame in context.iter().rev() {
+            match *frame {
+                Frame::Loop(header, label) if header == to => {
+                    return Ok(vec![Stmt::Continue(label)])
+                }
+                Frame::Follow(follower, label) if follower == to => {
+                    return Ok(vec![Stmt::Break(label)])
+                }
+                _ => {}
+            }
+        }
+        if self.dominators.immediate_dominator(to) == Some(from) && !self.graph.is_handler(to) {
+            return self.tree(to, context);
+        }
+        Err(self.unstructured(to))
+    }
+
+    /// The targets of the switch ending block `x`, with the keys branching
+    /// to each, in the order of their code.
+    fn switch_targets(&self, x: usize) -> Vec<(usize, Vec<Option<i32>>)> {
+        let (cases, default) = match self.graph.node(x).exit {
+            Exit::Switch(_, ref cases, default) => (cases, default),
+            _ => return vec![],
+        };
+        let mut targets: Vec<(usize, Vec<Option<i32>>)> = vec![];
+        let keys = cases.iter().map(|&(key, target)| (Some(key), target));
+        for (key, target) in keys.chain(Some((None, default))) {
+            match targets.iter().position(|&(other, _)| other == target) {
+                Some(index) => targets[index].1.push(key),
+                None => targets.push((target, vec![key])),
+            }
+        }
+        targets.sort_by_key(|&(target, _)| (self.pc(target), self.numbers[target]));
+        targets
+    }
+
+    /// The targets of the switch ending block `x` that are only reached
+    /// from the switch and by falling through from the code of the case
+    /// before them, so that they can be written as a case body.
+    fn fallthrough_cases(&self, x: usize) -> Vec<usize> {
+        let targets = self.switch_targets(x);
+        let predecessors = self.graph.predecessors();
+        let mut cases = vec![];
+        for pair in targets.windows(2) {
+            let (previous, target) = (pair[0].0, pair[1].0);
+            let previous_is_case = !self.is_merge(previous) || cases.contains(&previous);
+            let dominated = self.dominators.immediate_dominator(previous) == Some(x) &&
+                            self.dominators.immediate_dominator(target) == Some(x);
+            if !self.is_merge(target) || !previous_is_case || !dominated {
+                continue;
+            }
+            let falls_through = predecessors[target].iter().all(|&from| {
+                from == x || (self.numbers[from] < self.numbers[target] &&
+                              self.dominators.dominates(previous, from)) ||
+                self.numbers[from] >= self.numbers[target]
+            });
+            if falls_through {
+                cases.push(target);
+            }
+        }
+        cases
+    }
+
+    fn switch(&mut self,
+              x: usize,
+              value: &Expr,
+              _: &[(i32, usize)],
+              _: usize,
+              context: &mut Vec<Frame>)
+              -> Result<Stmt> {
+        let targets = self.switch_targets(x);
+        let fallthrough = self.fallthrough_cases(x);
+        let is_case = |s: &Structurer, context: &[Frame], target: usize| {
+            s.dominators.immediate_dominator(target) == Some(x) && !in_context(context, target) &&
+            !s.graph.is_handler(target) &&
+            (!s.is_merge(target) || fallthrough.contains(&target))
+        };
+        let mut cases = vec![];
+        for (index, &(target, ref keys)) in targets.iter().enumerate() {
+            let mut keys = keys.clone();
+            keys.sort_by_key(|key| key.map_or((1, 0), |key| (0, key)));
+            let body = if is_case(self, context, target) {
+                let next = targets.get(index + 1).map(|&(next, _)| next);
+                match next {
+                    Some(next) if fallthrough.contains(&next) => {
+                        // Branches to the next case fall through to it.
+                        let label = self.label();
+                        context.push(Frame::Follow(next, label));
+                        let body = self.tree(target, context);
+                        context.pop();
+                        vec![Stmt::Block {
+                                 label: label,
+                                 body: try!(body),
+                             }]
+                    }
+                    _ => try!(self.tree(target, context)),
+                }
+            } else {
+                try!(self.branch(x, target, context))
+            };
+            cases.push(SwitchCase {
+                keys: keys,
+                body: body,
+            });
+        }
+        Ok(Stmt::Switch {
+            label: self.label(),
+            value: value.clone(),
+            cases: cases,
+        })
+    }
+}
+
+fn in_context(context: &[Frame], id: usize) -> bool {
+    context.iter().any(|frame| match *frame {
+        Frame::Loop(header, _) => header == id,
+        Frame::Follow(follower, _) => follower == id,
+    })
+}
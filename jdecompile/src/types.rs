@@ -0,0 +1,77 @@
+//! Conversions between descriptor types and signature types.
+use classfile::{ClassTypeSignature, FieldType, SimpleClassTypeSignature, TypeSignature};
+
+use ast::Literal;
+
+pub const OBJECT: &'static str = "java/lang/Object";
+pub const STRING: &'static str = "java/lang/String";
+
+pub fn object() -> FieldType {
+    FieldType::Object(OBJECT.to_string())
+}
+
+pub fn string() -> FieldType {
+    FieldType::Object(STRING.to_string())
+}
+
+/// The signature type for a descriptor type. Nested classes keep their
+/// binary names, such as `Map$Entry`.
+pub fn signature(ty: &FieldType) -> TypeSignature {
+    match *ty {
+        FieldType::Object(ref name) => TypeSignature::Class(class_signature(name)),
+        FieldType::Array(ref component) => TypeSignature::Array(Box::new(signature(component))),
+        ref base => TypeSignature::Base(base.clone()),
+    }
+}
+
+/// The signature type for the class with internal name `name`.
+pub fn class_signature(name: &str) -> ClassTypeSignature {
+    let split = name.rfind('/').map_or(0, |slash| slash + 1);
+    ClassTypeSignature {
+        package: name[..split].to_string(),
+        classes: vec![SimpleClassTypeSignature {
+                          name: name[split..].to_string(),
+                          type_arguments: vec![],
+                      }],
+    }
+}
+
+/// The erasure of a signature type. Type variables erase to `Object`, which
+/// is not exact for variables with bounds, but is only used to pick names
+/// and literal forms.
+pub fn erasure(ty: &TypeSignature) -> FieldType {
+    match *ty {
+        TypeSignature::Base(ref base) => base.clone(),
+        TypeSignature::Class(ref class) => FieldType::Object(class.binary_name()),
+        TypeSignature::TypeVariable(_) => object(),
+        TypeSignature::Array(ref component) => FieldType::Array(Box::new(erasure(component))),
+    }
+}
+
+/// The value that elements of a new array of `ty` start out with.
+pub fn default_value(ty: &FieldType) -> Literal {
+    match *ty {
+        FieldType::Boolean => Literal::Boolean(false),
+        FieldType::Char => Literal::Char(0),
+        FieldType::Byte | FieldType::Short | FieldType::Int => Literal::Int(0),
+        FieldType::Long => Literal::Long(0),
+        FieldType::Float => Literal::Float(0.0),
+        FieldType::Double => Literal::Double(0.0),
+        FieldType::Object(_) | FieldType::Array(_) => Literal::Null,
+    }
+}
+
+/// Returns `true` for `int` and the types that the JVM widens to `int`.
+pub fn is_int_like(ty: &FieldType) -> bool {
+    match *ty {
+        FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short |
+        FieldType::Int => true,
+        _ => false,
+    }
+}
+
+/// The simple name of a class, without its package or enclosing classes.
+pub fn simple_name(name: &str) -> &str {
+    let start = name.rfind(|c| c == '/' || c == '$').map_or(0, |index| index + 1);
+    &name[start..]
+}
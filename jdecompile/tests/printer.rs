@@ -0,0 +1,149 @@
+extern crate classfile;
+extern crate jdecompile;
+
+use std::fs::File;
+
+use classfile::*;
+use classfile::reader::ClassReader;
+
+fn read_class(name: &str) -> ClassFile {
+    let mut file = File::open(format!("../test-classes/{}.class", name)).unwrap();
+    ClassReader::new(&mut file).read_class().unwrap()
+}
+
+#[test]
+fn should_decompile_hello_world() {
+    // Given
+    let class = read_class("HelloWorld");
+
+    // When
+    let source = jdecompile::decompile(&class).unwrap();
+
+    // Then
+    assert_eq!("package io.hcf.frappe;\n\npublic class HelloWorld {\n    \
+                public static void main(String[] arg0) {\n        \
+                System.out.println(\"Hello World!\");\n    }\n}\n",
+               source);
+}
+
+#[test]
+fn should_restore_loops_switches_and_try_statements() {
+    // Given
+    let class = read_class("ControlFlow");
+
+    // When
+    let source = jdecompile::decompile(&class).unwrap();
+
+    // Then
+    let expected = "package io.hcf.frappe;
+
+public class ControlFlow {
+    public static int count;
+
+    public static int sum(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += i;
+        }
+        return total;
+    }
+
+    public static int pairs(int n) {
+        int count = 0;
+        for (int i = 0; i < n; i++) {
+            for (int j = 0; j < i; j++) {
+                count++;
+            }
+        }
+        return count;
+    }
+
+    public static int abs(int x) {
+        return x < 0 ? -x : x;
+    }
+
+    public static String describe(int x) {
+        switch (x) {
+            case 1:
+                return \"one\";
+            case 2:
+                return \"two\";
+            default:
+                return \"many\";
+        }
+    }
+
+    public static int parse(String s) {
+        try {
+            return Integer.parseInt(s);
+        } catch (NumberFormatException e) {
+            return -1;
+        } finally {
+            ControlFlow.count++;
+        }
+    }
+
+    public static long fib(int n) {
+        long a = 0L;
+        long b = 1L;
+        while (n-- > 0) {
+            long next = a + b;
+            a = b;
+            b = next;
+        }
+        return a;
+    }
+}
+";
+    assert_eq!(expected, source);
+}
+
+#[test]
+fn should_recover_lambdas_and_generic_signatures() {
+    // Given
+    let class = read_class("Features");
+
+    // When
+    let source = jdecompile::decompile(&class).unwrap();
+
+    // Then
+    assert!(source.contains("import java.util.function.Function;\n"));
+    assert!(source.contains("public class Features<T extends Comparable<T>> \
+                             implements Comparable<Features<T>> {"));
+    assert!(source.contains("    private final List<T> items;\n"));
+    assert!(source.contains("    public static Function<Integer, String> formatter(String prefix) \
+                             {\n        return x -> prefix + x;\n    }\n"));
+    assert!(source.contains("    public <R extends Number> List<? super R> \
+                             widen(List<? extends R> in) throws IOException {"));
+    assert!(source.contains("    @Features.Marker(value = \"add\", ids = {3})\n    \
+                             public void add(T item) {"));
+}
+
+#[test]
+fn should_print_constant_fields() {
+    // Given
+    let class = read_class("Constants");
+
+    // When
+    let source = jdecompile::decompile(&class).unwrap();
+
+    // Then
+    assert!(source.contains("    public static final int INT_MIN = -2147483648;\n"));
+    assert!(source.contains("    public static final long LONG_MIN = -9223372036854775808L;\n"));
+    assert!(source.contains("    public static final float FLOAT_NAN = Float.NaN;\n"));
+    assert!(source.contains("    public static final double DOUBLE_POSITIVE_INF = \
+                             Double.POSITIVE_INFINITY;\n"));
+    assert!(source.contains("    static {\n        CLASS_VALUE = new Constants();\n    }\n"));
+}
+
+#[test]
+fn should_leave_out_the_outer_instance_of_inner_class_constructors() {
+    // Given
+    let class = read_class("Features$Inner");
+
+    // When
+    let source = jdecompile::decompile(&class).unwrap();
+
+    // Then
+    assert!(source.contains("    public Inner(Features this$0) {\n    }\n"));
+}
@@ -0,0 +1,147 @@
+extern crate jasm;
+extern crate jdecompile;
+
+fn decompile_method(source: &str, method: &str) -> String {
+    let class = jasm::assemble(source).unwrap();
+    let source = jdecompile::decompile(&class).unwrap();
+    let start = source.find(method).unwrap();
+    source[start..].to_string()
+}
+
+#[test]
+fn should_nest_conditionals_that_join_at_the_same_block() {
+    // Given
+    let source = ".class Blocks\n.method static m (II)I {\n  .code stack 2 locals 2 {\n    \
+                  iload_0\n    ifeq done\n    iload_1\n    ifeq skip\n    iinc 1 5\n    \
+                  goto done\n  skip:\n    iinc 0 3\n  done:\n    iload_0\n    iload_1\n    \
+                  iadd\n    ireturn\n  }\n}\n";
+
+    // When
+    let method = decompile_method(source, "static int m");
+
+    // Then
+    assert_eq!("static int m(int arg0, int arg1) {
+        if (arg0 != 0) {
+            if (arg1 != 0) {
+                arg1 += 5;
+            } else {
+                arg0 += 3;
+            }
+        }
+        return arg0 + arg1;
+    }
+}
+",
+               method);
+}
+
+#[test]
+fn should_fall_back_to_labeled_blocks_for_irreducible_loops() {
+    // Given
+    //  0: iload_0, ifeq 7
+    //  4: iinc 0 1
+    //  7: iload_0, ifne 4
+    // 11: return
+    let source = ".class Irreducible\n.method static m (I)V {\n  .code stack 1 locals 1 {\n    \
+                  iload_0\n    ifeq b\n  a:\n    iinc 0 1\n  b:\n    iload_0\n    ifne a\n    \
+                  return\n  }\n}\n";
+
+    // When
+    let method = decompile_method(source, "static void m");
+
+    // Then
+    assert_eq!("static void m(int arg0) {
+        label1: {
+            if (arg0 == 0) {
+                if (arg0 == 0) {
+                    break label1;
+                }
+            }
+            do {
+                arg0++;
+            } while (arg0 != 0);
+        }
+    }
+}
+",
+               method);
+}
+
+#[test]
+fn should_keep_nan_behaviour_of_negated_float_comparisons() {
+    // Given
+    //  if (!(d <= 2.0)) return 1; return 0;
+    let source = ".class Floats\n.method static m (D)I {\n  .code stack 4 locals 2 {\n    \
+                  dload_0\n    dconst_1\n    dcmpg\n    ifle small\n    iconst_1\n    \
+                  ireturn\n  small:\n    iconst_0\n    ireturn\n  }\n}\n";
+
+    // When
+    let method = decompile_method(source, "static int m");
+
+    // Then
+    assert!(method.contains("if (!(arg0 <= 1.0)) {\n            return 1;\n        }"));
+}
+
+#[test]
+fn should_report_methods_that_cannot_be_decompiled() {
+    // Given
+    let source = ".class Subroutine\n.method static m ()V {\n  .code stack 1 locals 1 {\n    \
+                  jsr sub\n    return\n  sub:\n    astore_0\n    ret 0\n  }\n}\n";
+    let class = jasm::assemble(source).unwrap();
+
+    // When
+    let method = class.find_method("m").unwrap();
+    let result = jdecompile::method_body(&class, method);
+
+    // Then
+    assert!(result.is_err());
+    assert!(jdecompile::decompile(&class).unwrap().contains("// Could not decompile: "));
+}
+
+#[test]
+fn should_reject_handlers_inside_their_own_try_block() {
+    // Given
+    let class = jasm::assemble(r#"
+.class Handler
+.method static m (Ljava/lang/Object;)V {
+    .code stack 2 locals 3 {
+    entry:
+        aload_0
+        dup
+        astore_1
+        monitorenter
+    start:
+        aload_0
+        invokevirtual Method java/lang/Object hashCode ()I
+        pop
+        aload_1
+        monitorexit
+    end:
+        goto out
+    handler:
+        astore_2
+        aload_1
+        monitorexit
+    handler_end:
+        aload_2
+        athrow
+    out:
+        return
+        .catch any start end entry
+        .catch any handler handler_end entry
+    }
+}
+"#)
+        .unwrap();
+
+    // When
+    let result = jdecompile::method_body(&class, &class.methods[0]);
+
+    // Then
+    match result {
+        Err(jdecompile::Error::Unsupported(ref message)) => {
+            assert_eq!("exception handler at pc 0 is inside its own try block", message)
+        }
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,132 @@
+//! Removes debugging information from class files and jars.
+extern crate classfile;
+#[macro_use]
+extern crate log;
+extern crate zip;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::result::ZipError;
+
+use classfile::reader::ClassReader;
+use classfile::strip::{self, StripPolicy};
+use classfile::writer::ClassWriter;
+
+#[derive(Debug)]
+pub enum Error {
+    ClassFile(classfile::Error),
+    Zip(ZipError),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ClassFile(ref err) => write!(f, "{}", err),
+            Error::Zip(ref err) => write!(f, "{}", err),
+            Error::Io(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "strip error"
+    }
+}
+
+impl From<classfile::Error> for Error {
+    fn from(err: classfile::Error) -> Error {
+        Error::ClassFile(err)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(err: ZipError) -> Error {
+        Error::Zip(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Size of a class (or a whole jar of classes) before and after stripping.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StripStats {
+    pub original_size: usize,
+    pub stripped_size: usize,
+}
+
+impl StripStats {
+    /// The number of bytes removed, or zero if stripping made the class
+    /// larger, which re-encoding its attributes may do.
+    pub fn saved(&self) -> usize {
+        self.original_size.saturating_sub(self.stripped_size)
+    }
+
+    fn add(&mut self, other: &StripStats) {
+        self.original_size += other.original_size;
+        self.stripped_size += other.stripped_size;
+    }
+}
+
+/// Strips a single serialized class, returning the new class bytes.
+pub fn strip_class(bytes: &[u8], policy: &StripPolicy) -> Result<(Vec<u8>, StripStats)> {
+    let mut class = try!(ClassReader::new(bytes).read_class());
+    strip::strip(&mut class, policy);
+    try!(strip::compact_constant_pool(&mut class));
+    let mut writer = ClassWriter::new(Vec::new());
+    try!(writer.write_class(&class));
+    let stripped = writer.into_inner();
+    let stats = StripStats {
+        original_size: bytes.len(),
+        stripped_size: stripped.len(),
+    };
+    Ok((stripped, stats))
+}
+
+/// Strips every class in a jar, copying all other entries unchanged.
+///
+/// `report` is invoked with the name and stats of each class as it is
+/// processed. The returned stats are the totals over all classes.
+pub fn strip_jar<R, W, F>(input: R,
+                          output: W,
+                          policy: &StripPolicy,
+                          mut report: F)
+                          -> Result<StripStats>
+    where R: Read + Seek,
+          W: Write + Seek,
+          F: FnMut(&str, &StripStats)
+{
+    let mut archive = try!(ZipArchive::new(input));
+    let mut writer = ZipWriter::new(output);
+    let mut total = StripStats::default();
+    for i in 0..archive.len() {
+        let mut file = try!(archive.by_index(i));
+        let name = file.name().to_string();
+        let mut bytes: Vec<u8> = vec![];
+        try!(file.read_to_end(&mut bytes));
+        let contents = if name.ends_with(".class") {
+            debug!("Stripping {}", name);
+            let (stripped, stats) = try!(strip_class(&bytes, policy));
+            report(&name, &stats);
+            total.add(&stats);
+            stripped
+        } else {
+            bytes
+        };
+        try!(writer.start_file(name, CompressionMethod::Deflated));
+        try!(writer.write_all(&contents));
+    }
+    try!(writer.finish());
+    Ok(total)
+}
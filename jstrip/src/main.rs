@@ -0,0 +1,85 @@
+extern crate classfile;
+extern crate env_logger;
+extern crate jstrip;
+
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+
+use classfile::strip::StripPolicy;
+use jstrip::StripStats;
+
+const USAGE: &'static str = "usage: jstrip [options] <input .class or .jar> <output>
+
+options:
+    --keep-line-numbers     keep LineNumberTable attributes
+    --keep-local-variables  keep LocalVariableTable and LocalVariableTypeTable attributes
+    --keep-source-file      keep SourceFile and SourceDebugExtension attributes
+    --keep-deprecated       keep Deprecated attributes
+    --keep-unknown          keep attributes that are not recognized";
+
+fn usage() -> ! {
+    println!("{}", USAGE);
+    process::exit(1);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn print_stats(name: &str, stats: &StripStats) {
+    println!("{}: {} -> {} bytes (saved {})",
+             name,
+             stats.original_size,
+             stats.stripped_size,
+             stats.saved());
+}
+
+fn main() {
+    env_logger::init().unwrap();
+    let mut policy = StripPolicy::default();
+    let mut paths: Vec<String> = vec![];
+    for arg in env::args().skip(1) {
+        match arg.as_ref() {
+            "--keep-line-numbers" => policy.line_number_table = false,
+            "--keep-local-variables" => {
+                policy.local_variable_table = false;
+                policy.local_variable_type_table = false;
+            }
+            "--keep-source-file" => {
+                policy.source_file = false;
+                policy.source_debug_extension = false;
+            }
+            "--keep-deprecated" => policy.deprecated = false,
+            "--keep-unknown" => policy.unknown = false,
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+    }
+    let (input_path, output_path) = (&paths[0], &paths[1]);
+
+    let input = File::open(input_path)
+        .unwrap_or_else(|e| fail(format!("{}: {}", input_path, e)));
+    let mut output = File::create(output_path)
+        .unwrap_or_else(|e| fail(format!("{}: {}", output_path, e)));
+    if input_path.ends_with(".jar") {
+        let total = jstrip::strip_jar(input, output, &policy, print_stats)
+            .unwrap_or_else(|e| fail(format!("{}: {}", input_path, e)));
+        print_stats(input_path, &total);
+    } else {
+        let mut bytes: Vec<u8> = vec![];
+        let mut input = input;
+        input.read_to_end(&mut bytes)
+            .unwrap_or_else(|e| fail(format!("{}: {}", input_path, e)));
+        let (stripped, stats) = jstrip::strip_class(&bytes, &policy)
+            .unwrap_or_else(|e| fail(format!("{}: {}", input_path, e)));
+        output.write_all(&stripped)
+            .unwrap_or_else(|e| fail(format!("{}: {}", output_path, e)));
+        print_stats(input_path, &stats);
+    }
+}
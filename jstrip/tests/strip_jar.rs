@@ -0,0 +1,92 @@
+extern crate classfile;
+extern crate jstrip;
+extern crate zip;
+
+use std::fs::File;
+use std::io::{self, Cursor};
+use std::io::prelude::*;
+
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use classfile::reader::ClassReader;
+use classfile::strip::StripPolicy;
+
+fn read_file(path: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+    File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn should_strip_class() {
+    // Given
+    let bytes = read_file("../test-classes/Point.class");
+
+    // When
+    let (stripped, stats) = jstrip::strip_class(&bytes, &StripPolicy::default()).unwrap();
+
+    // Then
+    assert_eq!(bytes.len(), stats.original_size);
+    assert_eq!(stripped.len(), stats.stripped_size);
+    assert!(stats.saved() > 0);
+    let class = ClassReader::new(&stripped[..]).read_class().unwrap();
+    assert!(class.attrs.source_file(&class.constants).is_none());
+}
+
+#[test]
+fn should_strip_every_class_in_jar() {
+    // Given
+    let mut jar = ZipWriter::new(Cursor::new(Vec::new()));
+    for name in ["HelloWorld", "Point"].iter() {
+        let bytes = read_file(&format!("../test-classes/{}.class", name));
+        jar.start_file(format!("io/hcf/frappe/{}.class", name), CompressionMethod::Deflated)
+            .unwrap();
+        jar.write_all(&bytes).unwrap();
+    }
+    jar.start_file("META-INF/MANIFEST.MF", CompressionMethod::Stored).unwrap();
+    jar.write_all(b"Manifest-Version: 1.0\n").unwrap();
+    let jar = jar.finish().unwrap().into_inner();
+
+    // When
+    let mut reported: Vec<String> = vec![];
+    let mut output = Cursor::new(Vec::new());
+    let total = jstrip::strip_jar(Cursor::new(jar),
+                                  &mut output,
+                                  &StripPolicy::default(),
+                                  |name, _| reported.push(name.to_string()))
+        .unwrap();
+
+    // Then
+    assert_eq!(vec!["io/hcf/frappe/HelloWorld.class", "io/hcf/frappe/Point.class"],
+               reported);
+    assert!(total.saved() > 0);
+    let mut archive = ZipArchive::new(Cursor::new(output.into_inner())).unwrap();
+    assert_eq!(3, archive.len());
+    let mut manifest = String::new();
+    archive.by_name("META-INF/MANIFEST.MF").unwrap().read_to_string(&mut manifest).unwrap();
+    assert_eq!("Manifest-Version: 1.0\n", manifest);
+}
+
+#[test]
+fn should_not_report_negative_savings() {
+    // Given
+    let stats = jstrip::StripStats {
+        original_size: 10,
+        stripped_size: 12,
+    };
+
+    // Then
+    assert_eq!(0, stats.saved());
+}
+
+#[test]
+fn should_report_the_cause_of_io_errors() {
+    // Given
+    let err = io::Error::new(io::ErrorKind::PermissionDenied, "access denied");
+
+    // When
+    let err = jstrip::Error::from(err);
+
+    // Then
+    assert_eq!("access denied", err.to_string());
+}
@@ -0,0 +1,56 @@
+package io.hcf.frappe;
+
+public class ControlFlow {
+    public static int count;
+
+    public static int sum(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += i;
+        }
+        return total;
+    }
+
+    public static int pairs(int n) {
+        int count = 0;
+        for (int i = 0; i < n; i++) {
+            for (int j = 0; j < i; j++) {
+                count++;
+            }
+        }
+        return count;
+    }
+
+    public static int abs(int x) {
+        return x < 0 ? -x : x;
+    }
+
+    public static String describe(int x) {
+        switch (x) {
+            case 1: return "one";
+            case 2: return "two";
+            default: return "many";
+        }
+    }
+
+    public static int parse(String s) {
+        try {
+            return Integer.parseInt(s);
+        } catch (NumberFormatException e) {
+            return -1;
+        } finally {
+            count++;
+        }
+    }
+
+    public static long fib(int n) {
+        long a = 0;
+        long b = 1;
+        while (n-- > 0) {
+            long next = a + b;
+            a = b;
+            b = next;
+        }
+        return a;
+    }
+}
@@ -0,0 +1,87 @@
+package io.hcf.frappe;
+
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Function;
+
+@Deprecated
+public class Features<T extends Comparable<T>> implements Comparable<Features<T>> {
+    @Retention(RetentionPolicy.RUNTIME)
+    @Target({ElementType.TYPE_USE, ElementType.METHOD, ElementType.PARAMETER})
+    public @interface Marker {
+        String value() default "marker";
+        int[] ids() default {1, 2};
+    }
+
+    public static final long BIG = 1234567890123L;
+
+    private final List<T> items = new ArrayList<>();
+
+    @Marker(value = "add", ids = {3})
+    public void add(@Marker T item) {
+        items.add(item);
+    }
+
+    public int size() {
+        return items.size();
+    }
+
+    public static int classify(int value) {
+        switch (value) {
+            case 0: return 10;
+            case 1: return 20;
+            case 2: return 30;
+            default: return -1;
+        }
+    }
+
+    public static int lookup(int value) {
+        switch (value) {
+            case -100: return 1;
+            case 0: return 2;
+            case 1000: return 3;
+            default: return 0;
+        }
+    }
+
+    public static int sum(int[] values) {
+        int total = 0;
+        for (int i = 0; i < values.length; i++) {
+            total += values[i];
+        }
+        return total;
+    }
+
+    public static int parse(String s) {
+        try {
+            return Integer.parseInt(s);
+        } catch (NumberFormatException e) {
+            return -1;
+        } finally {
+            System.out.println("parsed");
+        }
+    }
+
+    public static Function<Integer, @Marker String> formatter(final String prefix) {
+        return x -> prefix + x;
+    }
+
+    public <R extends Number> List<? super R> widen(List<? extends R> in) throws java.io.IOException {
+        return new ArrayList<R>(in);
+    }
+
+    @Override
+    public int compareTo(Features<T> other) {
+        return Integer.compare(size(), other.size());
+    }
+
+    public class Inner {
+        public int outerSize() {
+            return size();
+        }
+    }
+}
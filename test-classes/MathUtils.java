@@ -0,0 +1,83 @@
+package io.hcf.frappe;
+
+public class MathUtils {
+    public static final double EPSILON = 1e-12;
+
+    public static int fibonacci(int n) {
+        return n < 2 ? n : fibonacci(n - 1) + fibonacci(n - 2);
+    }
+
+    public static int gcd(int a, int b) {
+        while (b != 0) {
+            int t = a % b;
+            a = b;
+            b = t;
+        }
+        return a;
+    }
+
+    public static long factorial(int n) {
+        long result = 1;
+        for (int i = 2; i <= n; i++) {
+            result *= i;
+        }
+        return result;
+    }
+
+    public static boolean isPrime(int n) {
+        if (n < 2) {
+            return false;
+        }
+        for (int i = 2; (long) i * i <= n; i++) {
+            if (n % i == 0) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    public static int bitCount(long x) {
+        int count = 0;
+        while (x != 0) {
+            count += (int) (x & 1);
+            x >>>= 1;
+        }
+        return count;
+    }
+
+    public static double sqrt(double x) {
+        double guess = x / 2;
+        while (abs(guess * guess - x) > EPSILON * x) {
+            guess = (guess + x / guess) / 2;
+        }
+        return guess;
+    }
+
+    public static double abs(double x) {
+        return x < 0 ? -x : x;
+    }
+
+    public static int round(float x) {
+        return (int) (x + 0.5f);
+    }
+
+    public static double mean(long total, int count) {
+        return (double) total / count;
+    }
+
+    public static int compare(double a, double b) {
+        return a < b ? -1 : a > b ? 1 : 0;
+    }
+
+    public static char toUpper(char c) {
+        return c >= 'a' && c <= 'z' ? (char) (c - 'a' + 'A') : c;
+    }
+
+    public static int divide(int a, int b) {
+        return a / b;
+    }
+
+    public static int depth(int n) {
+        return n == 0 ? 0 : 1 + depth(n - 1);
+    }
+}
@@ -0,0 +1,62 @@
+package io.hcf.frappe;
+
+public class Programs {
+    static class Pair {
+        final Object first;
+        final Object second;
+
+        Pair(Object first, Object second) {
+            this.first = first;
+            this.second = second;
+        }
+
+        public String toString() {
+            return "(" + first + ", " + second + ")";
+        }
+    }
+
+    public static void main(String[] args) {
+        Point point = new Point(3, 4);
+        System.out.println("point " + point.getX() + "," + point.getY());
+        double distance = Math.sqrt(point.getX() * point.getX() + point.getY() * point.getY());
+        System.out.println("distance " + distance);
+
+        StringBuilder builder = new StringBuilder();
+        for (int i = 0; i < 20; i++) {
+            builder.append(i % 10);
+        }
+        System.out.println(builder.reverse());
+
+        Integer boxed = 41;
+        int sum = boxed + Integer.parseInt("1");
+        System.out.println(sum);
+        Long big = 1L << 40;
+        System.out.println(big);
+        System.out.println(new Pair(boxed, "x"));
+
+        String text = "Hello, frappe";
+        System.out.println(text.length() + " " + text.charAt(7) + " " + text.indexOf('f'));
+        System.out.println(text.substring(7).toUpperCase() + text.substring(0, 5).concat("!"));
+        System.out.println(text.equals("Hello, " + "frappe".toLowerCase()));
+        System.out.println(Math.max(3, 7) + Math.abs(-2) + " " + Math.PI + " " + 1e10 + " " + 0.5f);
+
+        for (String arg : args) {
+            System.out.print(arg);
+            System.out.print(' ');
+        }
+        System.out.println(args.length);
+        System.err.println("done");
+    }
+
+    public static String join(int n) {
+        String joined = "";
+        for (int i = 0; i < n; i++) {
+            joined += i;
+        }
+        return joined;
+    }
+
+    public static int parse(String value) {
+        return Integer.parseInt(value);
+    }
+}
@@ -0,0 +1,162 @@
+package io.hcf.frappe;
+
+public class Shapes {
+    interface Shape {
+        int area();
+
+        default int scaled(int factor) {
+            return area() * factor * factor;
+        }
+    }
+
+    static class Rect implements Shape {
+        final int width;
+        final int height;
+
+        Rect(int width, int height) {
+            this.width = width;
+            this.height = height;
+        }
+
+        public int area() {
+            return width * height;
+        }
+    }
+
+    static class Square extends Rect {
+        final int width;
+
+        Square(int side) {
+            super(side, side);
+            this.width = -side;
+        }
+
+        public int area() {
+            return super.area() + 1;
+        }
+    }
+
+    static int created;
+    static final Shape[] CACHE = new Shape[4];
+
+    static Shape create(int width, int height) {
+        created++;
+        return width == height ? new Square(width) : new Rect(width, height);
+    }
+
+    public static int totalArea(int n) {
+        int total = 0;
+        for (int i = 1; i <= n; i++) {
+            total += create(i, i + i % 2).area();
+        }
+        return total + created * 1000;
+    }
+
+    public static int hiddenField(int side) {
+        Square square = new Square(side);
+        Rect rect = square;
+        return square.width * 100 + rect.width;
+    }
+
+    public static int scaled(int width, int height, int factor) {
+        return create(width, height).scaled(factor);
+    }
+
+    public static long sumOfSquares(int n) {
+        long[] values = new long[n];
+        for (int i = 0; i < n; i++) {
+            values[i] = (long) i * i;
+        }
+        long sum = 0;
+        for (long value : values) {
+            sum += value;
+        }
+        return sum;
+    }
+
+    public static int grid(int rows, int columns) {
+        int[][] grid = new int[rows][columns];
+        for (int row = 0; row < rows; row++) {
+            for (int column = 0; column < columns; column++) {
+                grid[row][column] = row * column;
+            }
+        }
+        int sum = 0;
+        for (int[] row : grid) {
+            for (int cell : row) {
+                sum += cell;
+            }
+        }
+        return sum + grid.length * 1000 + grid[0].length * 100;
+    }
+
+    public static int bytes(int n) {
+        byte[] bytes = new byte[n];
+        char[] chars = new char[n];
+        boolean[] flags = new boolean[n];
+        for (int i = 0; i < n; i++) {
+            bytes[i] = (byte) (i * 100);
+            chars[i] = (char) (-i);
+            flags[i] = i % 3 == 0;
+        }
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            sum += bytes[i] + chars[i] + (flags[i] ? 1 : 0);
+        }
+        return sum;
+    }
+
+    public static int garbage(int n, int size) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            int[] array = new int[size];
+            array[size - 1] = i;
+            sum += array[size - 1];
+        }
+        return sum;
+    }
+
+    public static int retain(int n, int size) {
+        Object[] all = new Object[n];
+        for (int i = 0; i < n; i++) {
+            all[i] = new int[size];
+        }
+        return all.length;
+    }
+
+    public static int cached(int index) {
+        if (CACHE[index] == null) {
+            CACHE[index] = create(index + 1, index + 2);
+        }
+        return CACHE[index].area();
+    }
+
+    public static boolean isSquare(int width, int height) {
+        Object shape = create(width, height);
+        return shape instanceof Square;
+    }
+
+    public static int cast(int width, int height) {
+        Object shape = create(width, height);
+        return ((Square) shape).area();
+    }
+
+    public static int store(int index) {
+        Object[] shapes = new Rect[2];
+        shapes[index] = new Object();
+        return index;
+    }
+
+    public static int element(int index) {
+        int[] array = new int[2];
+        return array[index];
+    }
+
+    public static int length(int n) {
+        return new int[n].length;
+    }
+
+    public static int width(Rect rect) {
+        return rect.width;
+    }
+}